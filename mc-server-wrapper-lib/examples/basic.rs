@@ -36,23 +36,23 @@ async fn main() {
         .unwrap();
 
     while let Some(e) = event_receiver.recv().await {
-        match e {
-            ServerEvent::ConsoleEvent(console_msg, Some(specific_msg)) => {
+        match e.kind {
+            ServerEventKind::ConsoleEvent(console_msg, Some(specific_msg)) => {
                 println!("{}", console_msg);
                 // You can match on and handle the `specific_msg`s as desired
                 println!("      specific_msg: {:?}", specific_msg);
             }
-            ServerEvent::ConsoleEvent(console_msg, None) => {
+            ServerEventKind::ConsoleEvent(console_msg, None) => {
                 println!("{}", console_msg);
             }
-            ServerEvent::StdoutLine(line) => {
+            ServerEventKind::StdoutLine(line) => {
                 println!("{}", line);
             }
-            ServerEvent::StderrLine(line) => {
+            ServerEventKind::StderrLine(line) => {
                 eprintln!("{}", line);
             }
 
-            ServerEvent::ServerStopped(process_result, reason) => {
+            ServerEventKind::ServerStopped(process_result, reason) => {
                 if let Some(ShutdownReason::EulaNotAccepted) = reason {
                     println!("Agreeing to EULA!");
                     cmd_sender.send(ServerCommand::AgreeToEula).await.unwrap();
@@ -75,7 +75,7 @@ async fn main() {
                 }
             }
 
-            ServerEvent::AgreeToEulaResult(res) => {
+            ServerEventKind::AgreeToEulaResult(res) => {
                 if let Err(e) = res {
                     eprintln!("Failed to agree to EULA: {:?}", e);
                     cmd_sender
@@ -89,7 +89,9 @@ async fn main() {
                         .unwrap();
                 }
             }
-            ServerEvent::StartServerResult(res) => {
+            // This example never attaches to a running server
+            ServerEventKind::Attached => {}
+            ServerEventKind::StartServerResult(res) => {
                 if let Err(e) = res {
                     eprintln!("Failed to start the Minecraft server: {}", e);
                     cmd_sender
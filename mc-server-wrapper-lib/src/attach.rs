@@ -0,0 +1,134 @@
+use std::{io, net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
+
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncSeekExt, BufReader, SeekFrom},
+    sync::Mutex,
+};
+
+use crate::{
+    local_time,
+    parse::{ConsoleMsg, ConsoleMsgType},
+    rcon::{RconClient, RconError},
+};
+
+/// How often to check that an attached server is still reachable
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to wait before checking the log file for new lines
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Settings for attaching to a server that was started by other means
+///
+/// Instead of running the server process, the manager connects to the
+/// server's RCON interface to send commands and follows `logs/latest.log` to
+/// receive its console output. RCON must be enabled in the server's
+/// `server.properties` (`enable-rcon=true`).
+///
+/// An attached server cannot be started by the manager; once it stops, it
+/// must be started again by whatever started it originally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttachConfig {
+    /// The address of the server's RCON interface
+    pub rcon_address: SocketAddr,
+    /// The server's `rcon.password`
+    pub rcon_password: String,
+}
+
+/// Runs `command` over `rcon`, turning its output into a `ConsoleMsg`
+///
+/// The server doesn't log the output of commands run over RCON, so this is
+/// the only way to see it.
+pub(crate) async fn run_command(
+    rcon: &Mutex<RconClient>,
+    command: &str,
+) -> Result<Option<ConsoleMsg>, RconError> {
+    let output = rcon.lock().await.command(command).await?;
+    if output.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(ConsoleMsg {
+        timestamp: local_time::now_local(),
+        thread_name: "RCON".into(),
+        msg_type: ConsoleMsgType::Info,
+        msg: output,
+    }))
+}
+
+/// Waits until the server behind `rcon` stops responding
+pub(crate) async fn wait_for_disconnect(rcon: Arc<Mutex<RconClient>>) -> RconError {
+    let mut interval = tokio::time::interval(KEEPALIVE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = rcon.lock().await.command("list").await {
+            return e;
+        }
+    }
+}
+
+/// Follows the log file at `path`, calling `on_line` with every line added to
+/// it from now on
+///
+/// If the file is rotated it is followed from the start of the new file.
+/// This never returns unless an IO error occurs.
+pub(crate) async fn tail_log<F, Fut>(path: PathBuf, mut on_line: F) -> io::Error
+where
+    F: FnMut(String) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let mut position = None;
+
+    loop {
+        let mut file = match File::open(&path).await {
+            Ok(file) => file,
+            // The log may not exist yet if the server was just started
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                tokio::time::sleep(LOG_POLL_INTERVAL).await;
+                position = Some(0);
+                continue;
+            }
+            Err(e) => return e,
+        };
+
+        let start = match position {
+            Some(position) => SeekFrom::Start(position),
+            None => SeekFrom::End(0),
+        };
+        let mut pos = match file.seek(start).await {
+            Ok(pos) => pos,
+            Err(e) => return e,
+        };
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+
+        loop {
+            match reader.read_line(&mut line).await {
+                Ok(0) => {
+                    tokio::time::sleep(LOG_POLL_INTERVAL).await;
+
+                    // A file shorter than what's been read has been replaced
+                    match tokio::fs::metadata(&path).await {
+                        Ok(metadata) if metadata.len() >= pos => continue,
+                        _ => {
+                            position = Some(0);
+                            break;
+                        }
+                    }
+                }
+                Ok(n) => {
+                    pos += n as u64;
+
+                    // Partial lines are finished by the next read
+                    if line.ends_with('\n') {
+                        on_line(line.trim_end_matches(&['\r', '\n'][..]).to_string()).await;
+                        line.clear();
+                    }
+                }
+                Err(e) => return e,
+            }
+        }
+    }
+}
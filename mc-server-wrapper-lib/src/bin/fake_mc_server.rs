@@ -0,0 +1,125 @@
+/*!
+A fake Minecraft server for testing the library without a JVM.
+
+Run it in place of `java` (see `McServerConfig::with_java_path`). It ignores
+its arguments, refuses to start unless `eula.txt` in the current directory
+agrees to the EULA, prints vanilla-style startup output, and then handles
+commands from stdin:
+
+* `stop` stops the server
+* `crash` exits with an error, like a crashed server
+* `oom` runs out of memory, printing the error to stderr and exiting
+* `join <name>` and `leave <name>` print a player joining and leaving
+* `chat <name> <message>` prints a chat message from a player
+* `list` prints the players that have joined
+* anything else is echoed back
+*/
+
+use std::{
+    io::{self, BufRead},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Prints a console message the way a vanilla server would
+fn log(thread: &str, level: &str, msg: &str) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    println!(
+        "[{:02}:{:02}:{:02}] [{}/{}]: {}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        thread,
+        level,
+        msg
+    );
+}
+
+fn info(msg: &str) {
+    log("Server thread", "INFO", msg);
+}
+
+fn main() {
+    let eula = std::fs::read_to_string("eula.txt").unwrap_or_default();
+    if !eula.lines().any(|line| line.trim() == "eula=true") {
+        log("main", "WARN", "Failed to load eula.txt");
+        log(
+            "main",
+            "INFO",
+            "You need to agree to the EULA in order to run the server. Go to eula.txt for more info.",
+        );
+        return;
+    }
+
+    info("Starting minecraft server version 1.20.1");
+    info("Loading properties");
+    info("Default game type: SURVIVAL");
+    info("Starting Minecraft server on *:25565");
+    info("Preparing level \"world\"");
+    for progress in [0, 50] {
+        log(
+            "Worker-Main-1",
+            "INFO",
+            &format!("Preparing spawn area: {}%", progress),
+        );
+    }
+    info("Time elapsed: 1234 ms");
+    info("Done (1.234s)! For help, type \"help\"");
+
+    let mut players: Vec<String> = vec![];
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let (command, args) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+
+        match command {
+            "stop" => {
+                info("Stopping the server");
+                info("Stopping server");
+                return;
+            }
+            "crash" => {
+                log(
+                    "Server thread",
+                    "ERROR",
+                    "Encountered an unexpected exception",
+                );
+                process::exit(1);
+            }
+            "oom" => {
+                eprintln!(
+                    "Exception in thread \"Server thread\" java.lang.OutOfMemoryError: Java heap space"
+                );
+                process::exit(1);
+            }
+            "join" => {
+                info(&format!(
+                    "{}[/127.0.0.1:56538] logged in with entity id 97 at (0.5, 64.0, 0.5)",
+                    args
+                ));
+                info(&format!("{} joined the game", args));
+                players.push(args.to_string());
+            }
+            "leave" => {
+                info(&format!("{} lost connection: Disconnected", args));
+                info(&format!("{} left the game", args));
+                players.retain(|player| player != args);
+            }
+            "chat" => {
+                let (name, msg) = args.split_once(' ').unwrap_or((args, ""));
+                info(&format!("<{}> {}", name, msg));
+            }
+            "list" => info(&format!(
+                "There are {} of a max of 20 players online: {}",
+                players.len(),
+                players.join(", ")
+            )),
+            _ => info(&format!("Unknown command: {}", line)),
+        }
+    }
+}
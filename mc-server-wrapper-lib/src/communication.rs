@@ -1,13 +1,23 @@
 use crate::{parse::*, McServerConfig, McServerStartError};
 
-use std::{io, process::ExitStatus};
+use std::{fmt, io, process::ExitStatus, sync::Arc};
 
-/// Events from a Minecraft server.
+/// An event from a Minecraft server, labeled with the instance it came from
+#[derive(Debug)]
+pub struct ServerEvent {
+    /// The instance label from the `McServerConfig` the server was started
+    /// with (if one was set)
+    pub instance: Option<Arc<str>>,
+    /// What happened
+    pub kind: ServerEventKind,
+}
+
+/// Kinds of events from a Minecraft server.
 // TODO: derive serialize, deserialize
 // TODO: restructure so there are two main variants: stuff you get directly
 // from the server, and stuff more related to management
 #[derive(Debug)]
-pub enum ServerEvent {
+pub enum ServerEventKind {
     /// An event parsed from the server's console output (stderr or stdout)
     ///
     /// You are given a `ConsoleMsg` representing a generic form of the console
@@ -33,6 +43,9 @@ pub enum ServerEvent {
     AgreeToEulaResult(io::Result<()>),
     /// Response to `StartServer`
     StartServerResult(Result<(), McServerStartError>),
+    /// Sent in response to `StartServer` when the manager has attached to an
+    /// already-running server (see `AttachConfig`)
+    Attached,
 }
 
 /// Commands that can be sent over channels to be performed by the MC server.
@@ -69,11 +82,103 @@ pub enum ServerCommand {
 }
 
 /// Reasons that a Minecraft server stopped running
-// TODO: add variant indicating user requested server be stopped
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ShutdownReason {
     /// The server stopped because the EULA has not been accepted
     EulaNotAccepted,
     /// The server stopped because `ServerCommand::StopServer` was received
     RequestedToStop,
+    /// The server exited unsuccessfully for a reason that isn't known more
+    /// precisely
+    Crashed {
+        /// The exit code, if it exited normally
+        code: Option<i32>,
+        /// The signal that killed it, if it was killed (Unix only)
+        signal: Option<i32>,
+    },
+    /// The server ran out of memory (it printed a `java.lang.OutOfMemoryError`)
+    OutOfMemory,
+    /// The server failed to bind one of its ports
+    PortInUse,
+    /// The server's watchdog stopped it because a single tick took too long
+    WatchdogTimeout,
+    /// The server was told to stop by the system rather than the manager (it
+    /// received `SIGTERM`, `SIGINT`, or `SIGHUP`), like when the machine is
+    /// shutting down
+    HostShutdown,
+}
+
+/// Signals that ask a process to stop, rather than killing it outright
+const STOP_SIGNALS: [i32; 3] = [
+    1,  // SIGHUP
+    2,  // SIGINT
+    15, // SIGTERM
+];
+
+impl ShutdownReason {
+    /// Returns the reason the server is about to stop, if the given line of
+    /// its output (stdout or stderr) says
+    pub fn from_output(line: &str) -> Option<Self> {
+        if line.contains("java.lang.OutOfMemoryError") {
+            Some(ShutdownReason::OutOfMemory)
+        } else if line.contains("**** FAILED TO BIND TO PORT!") {
+            Some(ShutdownReason::PortInUse)
+        } else if line.contains("Considering it to be crashed, server will forcibly shutdown")
+            || line.contains("[Spigot Watchdog Thread/ERROR]: The server has stopped responding!")
+        {
+            Some(ShutdownReason::WatchdogTimeout)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the reason the server stopped going by how its process exited,
+    /// or `None` if it exited successfully
+    ///
+    /// Java handles stop signals by running its shutdown hooks and then
+    /// exiting with 128 plus the signal number, so those exit codes count as
+    /// having received the signal.
+    pub fn from_exit_status(status: &ExitStatus) -> Option<Self> {
+        if status.success() {
+            return None;
+        }
+
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(status);
+        #[cfg(not(unix))]
+        let signal: Option<i32> = None;
+        let code = status.code();
+
+        let stop_signal = signal
+            .or_else(|| code.map(|code| code - 128))
+            .filter(|signal| STOP_SIGNALS.contains(signal));
+        if cfg!(unix) && stop_signal.is_some() {
+            Some(ShutdownReason::HostShutdown)
+        } else {
+            Some(ShutdownReason::Crashed { code, signal })
+        }
+    }
+}
+
+impl fmt::Display for ShutdownReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShutdownReason::EulaNotAccepted => f.write_str("the EULA hasn't been agreed to"),
+            ShutdownReason::RequestedToStop => f.write_str("it was asked to stop"),
+            ShutdownReason::Crashed {
+                signal: Some(signal),
+                ..
+            } => write!(f, "it crashed (killed by signal {})", signal),
+            ShutdownReason::Crashed {
+                code: Some(code), ..
+            } => write!(f, "it crashed (exit code {})", code),
+            ShutdownReason::Crashed { .. } => f.write_str("it crashed"),
+            ShutdownReason::OutOfMemory => f.write_str("it ran out of memory"),
+            ShutdownReason::PortInUse => f.write_str("it couldn't bind its port"),
+            ShutdownReason::WatchdogTimeout => {
+                f.write_str("its watchdog stopped it after a tick took too long")
+            }
+            ShutdownReason::HostShutdown => f.write_str("the system told it to stop"),
+        }
+    }
 }
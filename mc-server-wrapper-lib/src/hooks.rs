@@ -0,0 +1,67 @@
+use std::{io, path::Path, process::ExitStatus, time::Duration};
+
+use thiserror::Error;
+use tokio::process::Command;
+
+/// A shell command to run before the server process starts or after it exits
+///
+/// The command is run with `sh -c` (or `cmd /C` on Windows) in the server's
+/// folder. It's killed if it runs for longer than `timeout`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hook {
+    /// The command to run
+    pub command: String,
+    /// How long the command may run for
+    pub timeout: Duration,
+}
+
+/// Errors that can occur when running a `Hook`
+#[derive(Error, Debug)]
+pub enum HookError {
+    #[error("failed to run `{command}`: {source}")]
+    Io { command: String, source: io::Error },
+    #[error("`{command}` exited with {status}")]
+    Failed { command: String, status: ExitStatus },
+    #[error("`{command}` did not finish within {} seconds", timeout.as_secs())]
+    TimedOut { command: String, timeout: Duration },
+}
+
+impl Hook {
+    /// Runs the command in `dir`, waiting for it to finish
+    pub async fn run(&self, dir: &Path) -> Result<(), HookError> {
+        #[cfg(unix)]
+        let mut command = {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        };
+        #[cfg(windows)]
+        let mut command = {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        };
+
+        let child = command
+            .arg(&self.command)
+            .current_dir(dir)
+            .kill_on_drop(true)
+            .status();
+
+        match tokio::time::timeout(self.timeout, child).await {
+            Ok(Ok(status)) if status.success() => Ok(()),
+            Ok(Ok(status)) => Err(HookError::Failed {
+                command: self.command.clone(),
+                status,
+            }),
+            Ok(Err(source)) => Err(HookError::Io {
+                command: self.command.clone(),
+                source,
+            }),
+            Err(_) => Err(HookError::TimedOut {
+                command: self.command.clone(),
+                timeout: self.timeout,
+            }),
+        }
+    }
+}
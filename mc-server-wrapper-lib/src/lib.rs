@@ -2,7 +2,7 @@ use tokio::{
     fs::File,
     io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
     process,
-    sync::{mpsc, oneshot, Mutex},
+    sync::{mpsc, oneshot, watch, Mutex},
 };
 
 use thiserror::Error;
@@ -18,13 +18,35 @@ use std::{
 };
 
 use crate::{
+    attach::AttachConfig,
     communication::*,
-    parse::{ConsoleMsg, ConsoleMsgSpecific},
+    hooks::{Hook, HookError},
+    parse::{ConsoleMsg, ConsoleMsgSpecific, MessagePatterns},
+    ports::PortInUse,
+    rcon::{RconClient, RconError},
+    replay::ReplayConfig,
+    resources::{ResourceLimits, ResourceLimitsError},
+    run_as::{RunAs, RunAsError},
+    status::{McServerStatus, ProcessInfo},
 };
 use process::Child;
 
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+#[cfg(windows)]
+use std::os::windows::process::ExitStatusExt;
+
+pub mod attach;
 pub mod communication;
+pub mod hooks;
+pub mod local_time;
 pub mod parse;
+pub mod ports;
+pub mod rcon;
+pub mod replay;
+pub mod resources;
+pub mod run_as;
+pub mod status;
 #[cfg(test)]
 mod test;
 
@@ -33,6 +55,40 @@ mod test;
 /// Will be set to a default of `mc` if not set elsewhere.
 pub static CONSOLE_MSG_LOG_TARGET: OnceCell<&str> = OnceCell::new();
 
+/// Quotes `s` so that `sh` treats it as a single word, without expanding
+/// anything in it
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Updates `status` from a message parsed from the server's console, only
+/// notifying subscribers if it changed
+fn apply_status(
+    status: &watch::Sender<McServerStatus>,
+    specific_msg: &ConsoleMsgSpecific,
+    pid: Option<u32>,
+) {
+    status.send_if_modified(|status| {
+        let before = status.clone();
+        status.apply(specific_msg, pid);
+        *status != before
+    });
+}
+
+/// Returns the log target to use for console output from the given instance
+///
+/// This is `CONSOLE_MSG_LOG_TARGET` for unlabeled instances and
+/// `CONSOLE_MSG_LOG_TARGET::label` for labeled ones, so that filtering on
+/// `CONSOLE_MSG_LOG_TARGET` still covers every instance.
+pub fn console_log_target(instance: Option<&str>) -> String {
+    let base = CONSOLE_MSG_LOG_TARGET.get_or_init(|| "mc");
+
+    match instance {
+        Some(instance) => format!("{}::{}", base, instance),
+        None => base.to_string(),
+    }
+}
+
 /// Configuration to run a Minecraft server instance with
 // TODO: make a builder for this
 #[derive(Debug, Clone)]
@@ -43,6 +99,9 @@ pub struct McServerConfig {
     memory: u16,
     /// Custom flags to pass to the JVM
     jvm_flags: Option<String>,
+    /// The Java executable to run the server with, instead of `java` from the
+    /// `PATH`
+    java_path: Option<PathBuf>,
     /// Whether or not the server's `stdin` should be inherited from the parent
     /// process's `stdin`.
     ///
@@ -54,6 +113,27 @@ pub struct McServerConfig {
     /// if you'd rather manually handle stdin and send data to the Minecraft
     /// server yourself (more work, but more flexible).
     inherit_stdin: bool,
+    /// A label identifying this server instance
+    ///
+    /// This is included in every `ServerEvent` and in the log target used
+    /// for the server's console output.
+    instance: Option<Arc<str>>,
+    /// Priority, affinity, and cgroup limits to apply to the server process
+    resource_limits: Option<Box<ResourceLimits>>,
+    /// An account to run the server process as (Unix only)
+    run_as: Option<RunAs>,
+    /// Attach to an already-running server instead of starting one
+    attach: Option<Box<AttachConfig>>,
+    /// Replay a recorded log instead of starting a server
+    replay: Option<Box<ReplayConfig>>,
+    /// Templates for console messages in languages other than English
+    message_patterns: Arc<MessagePatterns>,
+    /// A command to run before the server process starts
+    pre_start_hook: Option<Arc<Hook>>,
+    /// A command to run after the server process exits
+    post_stop_hook: Option<Arc<Hook>>,
+    /// A file to write the server process's ID to while it's running
+    pid_file: Option<PathBuf>,
 }
 
 /// Errors regarding an `McServerConfig`
@@ -61,6 +141,12 @@ pub struct McServerConfig {
 pub enum McServerConfigError {
     #[error("the provided server path \"{0}\" was not an accessible file")]
     ServerPathFileNotPresent(PathBuf),
+    #[error("the log to replay \"{0}\" was not an accessible file")]
+    ReplayFileNotPresent(PathBuf),
+    #[error("invalid resource limits: {0}")]
+    ResourceLimits(#[from] ResourceLimitsError),
+    #[error("cannot run the server as the configured user: {0}")]
+    RunAs(#[from] RunAsError),
 }
 
 impl McServerConfig {
@@ -76,21 +162,148 @@ impl McServerConfig {
             server_path,
             memory,
             jvm_flags,
+            java_path: None,
             inherit_stdin,
+            instance: None,
+            resource_limits: None,
+            run_as: None,
+            attach: None,
+            replay: None,
+            message_patterns: Arc::default(),
+            pre_start_hook: None,
+            post_stop_hook: None,
+            pid_file: None,
         }
     }
 
+    /// Run the server with the given Java executable instead of `java` from
+    /// the `PATH`
+    pub fn with_java_path<P: Into<PathBuf>>(mut self, java_path: P) -> Self {
+        self.java_path = Some(java_path.into());
+        self
+    }
+
+    /// Run the given command before each time the server process starts
+    ///
+    /// The server isn't started if the command fails. This doesn't apply when
+    /// attaching to a running server.
+    pub fn with_pre_start_hook(mut self, hook: Hook) -> Self {
+        self.pre_start_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Run the given command each time the server process exits, before
+    /// `ServerStopped` is sent
+    ///
+    /// This doesn't apply when attaching to a running server.
+    pub fn with_post_stop_hook(mut self, hook: Hook) -> Self {
+        self.post_stop_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Write the ID of the server process to the given file while it runs
+    ///
+    /// The file is removed when the process exits, so one that's left behind
+    /// points to a server process that outlived whatever started it. This
+    /// doesn't apply when attaching to a running server or replaying a log.
+    pub fn with_pid_file<P: Into<PathBuf>>(mut self, pid_file: P) -> Self {
+        self.pid_file = Some(pid_file.into());
+        self
+    }
+
+    /// Recognize console messages matching the given templates, for servers
+    /// that print messages in a language other than English
+    pub fn with_message_patterns(mut self, message_patterns: MessagePatterns) -> Self {
+        self.message_patterns = Arc::new(message_patterns);
+        self
+    }
+
+    /// Attach to an already-running server instead of starting one
+    ///
+    /// See `AttachConfig` for details.
+    pub fn with_attach(mut self, attach: AttachConfig) -> Self {
+        self.attach = Some(Box::new(attach));
+        self
+    }
+
+    /// Replay a recorded log instead of starting a server
+    ///
+    /// See `ReplayConfig` for details.
+    pub fn with_replay(mut self, replay: ReplayConfig) -> Self {
+        self.replay = Some(Box::new(replay));
+        self
+    }
+
+    /// Run the server process as the given account (Unix only)
+    pub fn with_run_as(mut self, run_as: RunAs) -> Self {
+        self.run_as = Some(run_as);
+        self
+    }
+
+    /// Apply the given resource limits to the server process
+    pub fn with_resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        self.resource_limits = Some(Box::new(resource_limits));
+        self
+    }
+
+    /// Label this server instance with the given identifier
+    pub fn with_instance<S: AsRef<str>>(mut self, instance: S) -> Self {
+        self.instance = Some(Arc::from(instance.as_ref()));
+        self
+    }
+
+    /// The label identifying this server instance (if one was set)
+    pub fn instance(&self) -> Option<&str> {
+        self.instance.as_deref()
+    }
+
+    /// The folder the server jar is in
+    fn server_dir(&self) -> &Path {
+        self.server_path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+    }
+
     /// Validates aspects of the config
     ///
     /// The validation ensures that the provided `server_path` is a path to a
-    /// file present on the filesystem.
+    /// file present on the filesystem, that any resource limits are
+    /// supported on this platform, and that the server can be run as the
+    /// configured account (if any). Only the server's folder needs to exist
+    /// when attaching to a running server, and only the log needs to exist
+    /// when replaying one.
     pub fn validate(&self) -> Result<(), McServerConfigError> {
         use McServerConfigError::*;
 
+        if let Some(replay) = &self.replay {
+            return if replay.path.is_file() {
+                Ok(())
+            } else {
+                Err(ReplayFileNotPresent(replay.path.clone()))
+            };
+        }
+
+        if self.attach.is_some() {
+            return if self.server_dir().is_dir() {
+                Ok(())
+            } else {
+                Err(ServerPathFileNotPresent(self.server_path.clone()))
+            };
+        }
+
         if !self.server_path.is_file() {
             return Err(ServerPathFileNotPresent(self.server_path.clone()));
         }
 
+        if let Some(resource_limits) = &self.resource_limits {
+            resource_limits.validate()?;
+        }
+
+        if let Some(run_as) = &self.run_as {
+            run_as.validate(&self.server_path)?;
+        }
+
         Ok(())
     }
 }
@@ -102,6 +315,12 @@ pub enum McServerStartError {
     ConfigError(#[from] McServerConfigError),
     #[error("io error: {0}")]
     IoError(#[from] io::Error),
+    #[error("failed to attach to the server over RCON: {0}")]
+    Rcon(#[from] RconError),
+    #[error("pre-start command failed: {0}")]
+    PreStartHook(#[from] HookError),
+    #[error("{0}")]
+    PortInUse(#[from] PortInUse),
     #[error(
         "no config provided with the request to start the server and no previous \
         config existed"
@@ -114,6 +333,8 @@ pub enum McServerStartError {
 pub struct McServerManager {
     /// Handle to server internals (present if server is running)
     internal: Arc<Mutex<Option<McServerInternal>>>,
+    /// The current status of the server, maintained from parsed events
+    status: Arc<watch::Sender<McServerStatus>>,
 }
 
 impl McServerManager {
@@ -134,6 +355,7 @@ impl McServerManager {
 
         let server = Arc::new(McServerManager {
             internal: Arc::new(Mutex::new(None)),
+            status: Arc::new(watch::channel(McServerStatus::Stopped).0),
         });
 
         let self_clone = server.clone();
@@ -152,7 +374,12 @@ impl McServerManager {
 
             while let Some(cmd) = cmd_receiver.recv().await {
                 use ServerCommand::*;
-                use ServerEvent::*;
+                use ServerEventKind::*;
+
+                let event_sender = EventSender {
+                    instance: current_config.as_ref().and_then(|c| c.instance.clone()),
+                    sender: event_sender.clone(),
+                };
 
                 match cmd {
                     TellRawAll(json) => {
@@ -175,8 +402,7 @@ impl McServerManager {
                                     .send(AgreeToEulaResult(
                                         McServerManager::agree_to_eula(server_path).await,
                                     ))
-                                    .await
-                                    .unwrap();
+                                    .await;
                             });
                         }
                     }
@@ -192,43 +418,148 @@ impl McServerManager {
                             current_config
                         } else {
                             event_sender
-                                .send(ServerEvent::StartServerResult(Err(
-                                    McServerStartError::NoPreviousConfig,
-                                )))
-                                .await
-                                .unwrap();
+                                .send(StartServerResult(Err(McServerStartError::NoPreviousConfig)))
+                                .await;
                             continue;
                         };
+                        let event_sender = EventSender {
+                            instance: config.instance.clone(),
+                            ..event_sender
+                        };
+
+                        if let Some(attach) = &config.attach {
+                            match McServerInternal::attach(config, attach, event_sender.clone())
+                                .await
+                            {
+                                Ok((internal, rcon, rx)) => {
+                                    *self.internal.lock().await = Some(internal);
+                                    self.status.send_replace(McServerStatus::Running {
+                                        since: time::OffsetDateTime::now_utc(),
+                                        players: Default::default(),
+                                        pid: None,
+                                    });
+                                    event_sender.send(Attached).await;
+
+                                    let log_path =
+                                        config.server_dir().join("logs").join("latest.log");
+                                    let internal_clone = self.internal.clone();
+                                    let status_clone = self.status.clone();
+                                    let message_patterns = config.message_patterns.clone();
+
+                                    tokio::spawn(async move {
+                                        let ret = McServerInternal::run_attached(
+                                            rcon,
+                                            log_path,
+                                            rx,
+                                            event_sender.clone(),
+                                            status_clone.clone(),
+                                            message_patterns,
+                                        )
+                                        .await;
+                                        let _ = internal_clone.lock().await.take();
+                                        status_clone.send_replace(McServerStatus::Stopped);
+
+                                        event_sender.send(ServerStopped(ret.0, ret.1)).await;
+                                    });
+                                }
+                                Err(e) => event_sender.send(StartServerResult(Err(e))).await,
+                            }
+
+                            continue;
+                        }
+
+                        if let Some(replay) = &config.replay {
+                            if let Err(e) = config.validate() {
+                                event_sender.send(StartServerResult(Err(e.into()))).await;
+                                continue;
+                            }
+
+                            let (tx, rx) = oneshot::channel();
+                            *self.internal.lock().await = Some(McServerInternal {
+                                stdin: None,
+                                rcon: None,
+                                shutdown_reason_oneshot: Some(tx),
+                                process_info: None,
+                            });
+                            self.status.send_replace(McServerStatus::Starting { progress: None });
+
+                            let replay = replay.as_ref().clone();
+                            let internal_clone = self.internal.clone();
+                            let status_clone = self.status.clone();
+                            let message_patterns = config.message_patterns.clone();
+
+                            tokio::spawn(async move {
+                                let ret = McServerInternal::run_replay(
+                                    replay,
+                                    rx,
+                                    event_sender.clone(),
+                                    status_clone.clone(),
+                                    message_patterns,
+                                )
+                                .await;
+                                let _ = internal_clone.lock().await.take();
+                                status_clone.send_replace(McServerStatus::Stopped);
+
+                                event_sender.send(ServerStopped(ret.0, ret.1)).await;
+                            });
+
+                            continue;
+                        }
+
+                        if let Some(hook) = &config.pre_start_hook {
+                            if let Err(e) = hook.run(config.server_dir()).await {
+                                event_sender.send(StartServerResult(Err(e.into()))).await;
+                                continue;
+                            }
+                        }
 
                         let (child, rx) = match McServerInternal::setup_server(config) {
                             Ok((internal, child, rx)) => {
                                 *self.internal.lock().await = Some(internal);
+                                self.status.send_replace(McServerStatus::Starting { progress: None });
                                 (child, rx)
                             }
                             Err(e) => {
-                                event_sender
-                                    .send(ServerEvent::StartServerResult(Err(e)))
-                                    .await
-                                    .unwrap();
+                                event_sender.send(StartServerResult(Err(e))).await;
                                 continue;
                             }
                         };
 
                         let event_sender_clone = event_sender.clone();
                         let internal_clone = self.internal.clone();
+                        let status_clone = self.status.clone();
+                        let message_patterns = config.message_patterns.clone();
+                        let post_stop_hook = config.post_stop_hook.clone();
+                        let server_dir = config.server_dir().to_path_buf();
+                        let pid_file = config.pid_file.clone();
 
                         // Spawn a task to drive the server process to completion
                         // and send an event when it exits
                         tokio::spawn(async move {
                             let event_sender = event_sender_clone;
-                            let ret =
-                                McServerInternal::run_server(child, rx, event_sender.clone()).await;
+                            let ret = McServerInternal::run_server(
+                                child,
+                                rx,
+                                event_sender.clone(),
+                                status_clone.clone(),
+                                message_patterns,
+                            )
+                            .await;
+                            if let Some(pid_file) = pid_file {
+                                let _ = tokio::fs::remove_file(pid_file).await;
+                            }
+
+                            // Run this before clearing `internal` so the server
+                            // can't be started again until it's done
+                            if let Some(hook) = post_stop_hook {
+                                if let Err(e) = hook.run(&server_dir).await {
+                                    log::warn!("Post-stop command failed: {}", e);
+                                }
+                            }
                             let _ = internal_clone.lock().await.take();
+                            status_clone.send_replace(McServerStatus::Stopped);
 
-                            event_sender
-                                .send(ServerStopped(ret.0, ret.1))
-                                .await
-                                .unwrap();
+                            event_sender.send(ServerStopped(ret.0, ret.1)).await;
                         });
                     }
                     StopServer { forever } => {
@@ -254,6 +585,8 @@ impl McServerManager {
                 if let Some(tx) = internal.shutdown_reason_oneshot.take() {
                     let _ = tx.send(ShutdownReason::RequestedToStop);
                 }
+
+                self.status.send_replace(McServerStatus::Stopping);
             }
 
             if let Some(stdin) = &mut internal.stdin {
@@ -261,6 +594,28 @@ impl McServerManager {
                     log::warn!("Failed to write to Minecraft server stdin: {}", e);
                 }
             }
+
+            if let Some((rcon, event_sender, message_patterns)) = &internal.rcon {
+                for command in String::from_utf8_lossy(bytes).lines() {
+                    match attach::run_command(rcon, command).await {
+                        Ok(Some(console_msg)) => {
+                            let specific_msg =
+                                ConsoleMsgSpecific::try_parse_with(&console_msg, message_patterns);
+                            if let Some(specific_msg) = &specific_msg {
+                                apply_status(&self.status, specific_msg, None);
+                            }
+
+                            event_sender
+                                .send(ServerEventKind::ConsoleEvent(console_msg, specific_msg))
+                                .await;
+                        }
+                        Ok(None) => {}
+                        // The server closes the connection as it stops
+                        Err(_) if command == "stop" => {}
+                        Err(e) => log::warn!("Failed to send command over RCON: {}", e),
+                    }
+                }
+            }
         }
     }
 
@@ -270,11 +625,58 @@ impl McServerManager {
         running.is_some()
     }
 
+    /// Returns the current status of the server
+    ///
+    /// This is kept up-to-date from the events parsed out of the server's
+    /// console output.
+    pub async fn status(&self) -> McServerStatus {
+        self.status.borrow().clone()
+    }
+
+    /// Returns a handle that sees the current status of the server and is
+    /// notified when it changes
+    pub fn subscribe_status(&self) -> watch::Receiver<McServerStatus> {
+        self.status.subscribe()
+    }
+
+    /// Returns information about the server process, if it's running
+    ///
+    /// This is `None` when attached to a server or replaying a log, since the
+    /// manager didn't start a process for them. It's also always `None` on
+    /// Windows, where the process the manager starts is PowerShell rather
+    /// than Java.
+    pub async fn process_info(&self) -> Option<ProcessInfo> {
+        self.internal.lock().await.as_ref()?.process_info.clone()
+    }
+
     /// Overwrites the `eula.txt` file with the contents `eula=true`.
     async fn agree_to_eula<P: AsRef<Path>>(server_path: P) -> io::Result<()> {
         let mut file = File::create(server_path.as_ref().with_file_name("eula.txt")).await?;
 
-        file.write_all(b"eula=true").await
+        file.write_all(b"eula=true").await?;
+        // Writes to a tokio `File` finish in the background unless it's
+        // flushed, and the server reads it right after this
+        file.flush().await
+    }
+}
+
+/// Sends `ServerEvent`s labeled with a particular instance
+#[derive(Debug, Clone)]
+struct EventSender {
+    instance: Option<Arc<str>>,
+    sender: mpsc::Sender<ServerEvent>,
+}
+
+impl EventSender {
+    /// Send an event of the given kind
+    async fn send(&self, kind: ServerEventKind) {
+        self.sender
+            .send(ServerEvent {
+                instance: self.instance.clone(),
+                kind,
+            })
+            .await
+            .unwrap();
     }
 }
 
@@ -294,8 +696,13 @@ impl McServerManager {
 struct McServerInternal {
     /// Handle to the server's stdin (if captured)
     stdin: Option<process::ChildStdin>,
+    /// The RCON connection commands are sent over (if attached), along with
+    /// where to send their output
+    rcon: Option<(Arc<Mutex<RconClient>>, EventSender, Arc<MessagePatterns>)>,
     /// Provides a way for the manager to set a shutdown reason
     shutdown_reason_oneshot: Option<oneshot::Sender<ShutdownReason>>,
+    /// Information about the server process (if the manager started one)
+    process_info: Option<ProcessInfo>,
 }
 
 impl McServerInternal {
@@ -306,6 +713,10 @@ impl McServerInternal {
         config: &McServerConfig,
     ) -> Result<(Self, Child, oneshot::Receiver<ShutdownReason>), McServerStartError> {
         config.validate()?;
+        // The server would otherwise fail to bind the port after loading the
+        // world and stop, and be started again if it was restarted after a
+        // crash
+        ports::check(config.server_dir())?;
 
         let folder = config
             .server_path
@@ -315,21 +726,31 @@ impl McServerInternal {
             .unwrap_or_else(|| OsStr::new("."));
         let file = config.server_path.file_name().unwrap();
 
-        let java_args = format!(
-            "-Xms{}M -Xmx{}M {} -jar {:?} nogui",
-            config.memory,
-            config.memory,
-            config.jvm_flags.as_deref().unwrap_or(""),
-            file
-        );
+        // The JVM flags are left unquoted so they're split into words
+        let java_args = |jar: String| {
+            format!(
+                "-Xms{}M -Xmx{}M {} -jar {} nogui",
+                config.memory,
+                config.memory,
+                config.jvm_flags.as_deref().unwrap_or(""),
+                jar
+            )
+        };
 
         // I don't know much about powershell but this works so ¯\_(ツ)_/¯
-        let args = if cfg!(windows) {
-            vec![
+        let (args, command_line) = if cfg!(windows) {
+            let java = config
+                .java_path
+                .as_ref()
+                .map(|java_path| java_path.to_string_lossy())
+                .unwrap_or_else(|| "java.exe".into());
+
+            let java_args = java_args(format!("{:?}", file));
+            let args = vec![
                 "Start-Process",
                 "-NoNewWindow",
                 "-FilePath",
-                "java.exe",
+                &java,
                 "-WorkingDirectory",
                 &folder.to_string_lossy(),
                 "-ArgumentList",
@@ -337,19 +758,66 @@ impl McServerInternal {
             ]
             .into_iter()
             .map(|s| s.into())
-            .collect()
+            .collect();
+            (args, format!("{} {}", java, java_args))
         } else {
-            vec![
+            let mut command_prefix = String::new();
+            let mut cgroup_setup = String::new();
+
+            if let Some(resource_limits) = &config.resource_limits {
+                for word in resource_limits.command_prefix() {
+                    command_prefix.push_str(&word);
+                    command_prefix.push(' ');
+                }
+
+                // The shell moves itself into the cgroup before `exec`ing java,
+                // so the server process starts out inside of it
+                if let Some(cgroup) = &resource_limits.cgroup {
+                    let procs_path = cgroup.setup()?;
+                    cgroup_setup = format!(
+                        "echo $$ > {} && ",
+                        shell_quote(&procs_path.to_string_lossy())
+                    );
+                }
+            }
+
+            // Privileges are dropped last so that everything above can still
+            // make use of them
+            #[cfg(target_os = "linux")]
+            if let Some(run_as) = &config.run_as {
+                for word in
+                    run_as.command_prefix(run_as.resolve().map_err(McServerConfigError::from)?)
+                {
+                    command_prefix.push_str(&word);
+                    command_prefix.push(' ');
+                }
+            }
+
+            let java = match &config.java_path {
+                Some(java_path) => shell_quote(&java_path.to_string_lossy()),
+                None => "java".into(),
+            };
+
+            let command_line = format!(
+                "{}{} {}",
+                command_prefix,
+                java,
+                java_args(shell_quote(&file.to_string_lossy()))
+            );
+            let args = vec![
                 "-c".into(),
                 format!(
-                    "cd {} && exec java {}",
-                    folder.to_string_lossy(),
-                    &java_args
+                    "cd {} && {}exec {}",
+                    shell_quote(&folder.to_string_lossy()),
+                    cgroup_setup,
+                    command_line
                 ),
-            ]
+            ];
+            (args, command_line)
         };
 
-        let mut process = process::Command::new(if cfg!(windows) { "PowerShell" } else { "sh" })
+        let mut command = process::Command::new(if cfg!(windows) { "PowerShell" } else { "sh" });
+        command
             .stdin(if config.inherit_stdin {
                 Stdio::inherit()
             } else {
@@ -357,8 +825,21 @@ impl McServerInternal {
             })
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .args(&args)
-            .spawn()?;
+            .args(&args);
+
+        #[cfg(all(unix, not(target_os = "linux")))]
+        if let Some(run_as) = &config.run_as {
+            let ids = run_as.resolve().map_err(McServerConfigError::from)?;
+            command.uid(ids.uid).gid(ids.gid);
+        }
+
+        let mut process = command.spawn()?;
+        let started = time::OffsetDateTime::now_utc();
+        if let (Some(pid_file), Some(pid)) = (&config.pid_file, process.id()) {
+            if let Err(e) = std::fs::write(pid_file, pid.to_string()) {
+                log::warn!("Failed to write the server's PID to {:?}: {}", pid_file, e);
+            }
+        }
 
         let stdin = if !config.inherit_stdin {
             Some(process.stdin.take().unwrap())
@@ -371,20 +852,167 @@ impl McServerInternal {
         Ok((
             Self {
                 stdin,
+                rcon: None,
                 shutdown_reason_oneshot: Some(tx),
+                // On Unix the shell `exec`s the server, so it keeps the shell's
+                // ID. On Windows the process is PowerShell, which starts Java
+                // as a separate process, so there's nothing accurate to give.
+                process_info: process.id().filter(|_| cfg!(unix)).map(|pid| ProcessInfo {
+                    pid,
+                    started,
+                    // Collapses the gap left when there are no JVM flags
+                    command_line: command_line
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                }),
             },
             process,
             rx,
         ))
     }
 
+    /// Connect to the already-running server described by `config`
+    async fn attach(
+        config: &McServerConfig,
+        attach: &AttachConfig,
+        event_sender: EventSender,
+    ) -> Result<
+        (
+            Self,
+            Arc<Mutex<RconClient>>,
+            oneshot::Receiver<ShutdownReason>,
+        ),
+        McServerStartError,
+    > {
+        config.validate()?;
+
+        let rcon = Arc::new(Mutex::new(
+            RconClient::connect(attach.rcon_address, &attach.rcon_password).await?,
+        ));
+        let (tx, rx) = oneshot::channel();
+
+        Ok((
+            Self {
+                stdin: None,
+                rcon: Some((rcon.clone(), event_sender, config.message_patterns.clone())),
+                shutdown_reason_oneshot: Some(tx),
+                process_info: None,
+            },
+            rcon,
+            rx,
+        ))
+    }
+
+    /// Follow an attached server's log until it stops responding over RCON,
+    /// sending any events over the `event_sender`
+    async fn run_attached(
+        rcon: Arc<Mutex<RconClient>>,
+        log_path: PathBuf,
+        mut shutdown_reason_oneshot: oneshot::Receiver<ShutdownReason>,
+        event_sender: EventSender,
+        server_status: Arc<watch::Sender<McServerStatus>>,
+        message_patterns: Arc<MessagePatterns>,
+    ) -> (io::Result<ExitStatus>, Option<ShutdownReason>) {
+        use ServerEventKind::*;
+
+        let tail = attach::tail_log(log_path, |line| {
+            let event_sender = event_sender.clone();
+            let server_status = server_status.clone();
+            let message_patterns = message_patterns.clone();
+
+            async move {
+                match ConsoleMsg::try_parse_from(&line) {
+                    Some(console_msg) => {
+                        let specific_msg =
+                            ConsoleMsgSpecific::try_parse_with(&console_msg, &message_patterns);
+                        if let Some(specific_msg) = &specific_msg {
+                            apply_status(&server_status, specific_msg, None);
+                        }
+
+                        event_sender
+                            .send(ConsoleEvent(console_msg, specific_msg))
+                            .await;
+                    }
+                    None => event_sender.send(StdoutLine(line)).await,
+                }
+            }
+        });
+
+        let error = tokio::select! {
+            e = attach::wait_for_disconnect(rcon) => {
+                io::Error::new(io::ErrorKind::ConnectionAborted, e.to_string())
+            }
+            e = tail => e,
+        };
+
+        match shutdown_reason_oneshot.try_recv() {
+            Ok(reason) => (Ok(ExitStatus::from_raw(0)), Some(reason)),
+            Err(_) => (Err(error), None),
+        }
+    }
+
+    /// Replay a recorded log, sending its events over the `event_sender`, and
+    /// then wait to be stopped
+    async fn run_replay(
+        replay: ReplayConfig,
+        shutdown_reason_oneshot: oneshot::Receiver<ShutdownReason>,
+        event_sender: EventSender,
+        server_status: Arc<watch::Sender<McServerStatus>>,
+        message_patterns: Arc<MessagePatterns>,
+    ) -> (io::Result<ExitStatus>, Option<ShutdownReason>) {
+        use ServerEventKind::*;
+
+        let replay_log = replay::replay_log(replay.path, replay.speed, |line| {
+            let event_sender = event_sender.clone();
+            let server_status = server_status.clone();
+            let message_patterns = message_patterns.clone();
+
+            async move {
+                match ConsoleMsg::try_parse_from(&line) {
+                    Some(console_msg) => {
+                        let specific_msg =
+                            ConsoleMsgSpecific::try_parse_with(&console_msg, &message_patterns);
+                        if let Some(specific_msg) = &specific_msg {
+                            apply_status(&server_status, specific_msg, None);
+                        }
+
+                        event_sender
+                            .send(ConsoleEvent(console_msg, specific_msg))
+                            .await;
+                    }
+                    None => event_sender.send(StdoutLine(line)).await,
+                }
+            }
+        });
+        tokio::pin!(shutdown_reason_oneshot);
+
+        tokio::select! {
+            result = replay_log => {
+                if let Err(e) = result {
+                    return (Err(e), None);
+                }
+            }
+            reason = &mut shutdown_reason_oneshot => {
+                return (Ok(ExitStatus::from_raw(0)), reason.ok());
+            }
+        }
+
+        // Like a real server, the replayed one keeps running until it's stopped
+        let reason = shutdown_reason_oneshot.await.ok();
+        (Ok(ExitStatus::from_raw(0)), reason)
+    }
+
     /// Drive the given server process to completion, sending any events over the
     /// `event_sender`
     async fn run_server(
         mut process: Child,
         mut shutdown_reason_oneshot: oneshot::Receiver<ShutdownReason>,
-        event_sender: mpsc::Sender<ServerEvent>,
+        event_sender: EventSender,
+        server_status: Arc<watch::Sender<McServerStatus>>,
+        message_patterns: Arc<MessagePatterns>,
     ) -> (io::Result<ExitStatus>, Option<ShutdownReason>) {
+        let pid = process.id();
         let mut stdout = BufReader::new(process.stdout.take().unwrap()).lines();
         let mut stderr = BufReader::new(process.stderr.take().unwrap()).lines();
 
@@ -392,50 +1020,73 @@ impl McServerInternal {
 
         let event_sender_clone = event_sender.clone();
         let stderr_handle = tokio::spawn(async move {
-            use ServerEvent::*;
+            use ServerEventKind::*;
             let event_sender = event_sender_clone;
 
+            let mut shutdown_reason = None;
+
             while let Some(line) = stderr.next_line().await.unwrap() {
-                event_sender.send(StderrLine(line)).await.unwrap();
+                // Out of memory errors are printed here
+                shutdown_reason = shutdown_reason.or_else(|| ShutdownReason::from_output(&line));
+                event_sender.send(StderrLine(line)).await;
             }
+
+            shutdown_reason
         });
 
         let stdout_handle = tokio::spawn(async move {
-            use ServerEvent::*;
+            use ServerEventKind::*;
             let event_sender = event_sender;
             let mut shutdown_reason = None;
 
             while let Some(line) = stdout.next_line().await.unwrap() {
+                // The first reason given is kept, since whatever went wrong
+                // first is most likely what stopped the server
+                shutdown_reason = shutdown_reason.or_else(|| ShutdownReason::from_output(&line));
+
                 if let Some(console_msg) = ConsoleMsg::try_parse_from(&line) {
-                    let specific_msg = ConsoleMsgSpecific::try_parse_from(&console_msg);
+                    let specific_msg =
+                        ConsoleMsgSpecific::try_parse_with(&console_msg, &message_patterns);
 
                     if specific_msg == Some(ConsoleMsgSpecific::MustAcceptEula) {
                         shutdown_reason = Some(ShutdownReason::EulaNotAccepted);
                     }
 
+                    if let Some(specific_msg) = &specific_msg {
+                        apply_status(&server_status, specific_msg, pid);
+                    }
+
                     event_sender
                         .send(ConsoleEvent(console_msg, specific_msg))
-                        .await
-                        .unwrap();
+                        .await;
                 } else {
                     // spigot servers print lines that reach this branch ("\n",
                     // "Loading libraries, please wait...")
-                    event_sender.send(StdoutLine(line)).await.unwrap();
+                    event_sender.send(StdoutLine(line)).await;
                 }
             }
 
             shutdown_reason
         });
 
-        let (status, shutdown_reason, _) =
+        let (status, stdout_reason, stderr_reason) =
             tokio::join!(status_handle, stdout_handle, stderr_handle,);
-        let mut shutdown_reason = shutdown_reason.unwrap();
+        let status = status.unwrap();
 
-        // Shutdown reason from the manager gets preference
-        if let Ok(reason) = shutdown_reason_oneshot.try_recv() {
-            shutdown_reason = Some(reason);
-        }
+        // Shutdown reason from the manager gets preference, then anything the
+        // server printed, and then how its process exited
+        let shutdown_reason = shutdown_reason_oneshot
+            .try_recv()
+            .ok()
+            .or(stdout_reason.unwrap())
+            .or(stderr_reason.unwrap())
+            .or_else(|| {
+                status
+                    .as_ref()
+                    .ok()
+                    .and_then(ShutdownReason::from_exit_status)
+            });
 
-        (status.unwrap(), shutdown_reason)
+        (status, shutdown_reason)
     }
 }
@@ -0,0 +1,34 @@
+//! The local time, used for console message timestamps
+//!
+//! On some platforms the local offset can only be determined while the
+//! process has a single thread (see
+//! https://github.com/time-rs/time/issues/293), so applications should
+//! determine it once at startup and provide it with `set_local_offset`.
+//! Until then, the offset is looked up each time it's needed and UTC is used
+//! if that fails.
+
+use once_cell::sync::OnceCell;
+use time::{OffsetDateTime, UtcOffset};
+
+static LOCAL_OFFSET: OnceCell<UtcOffset> = OnceCell::new();
+
+/// Sets the offset used for local times
+///
+/// This can only be set once; later calls are ignored.
+pub fn set_local_offset(offset: UtcOffset) {
+    let _ = LOCAL_OFFSET.set(offset);
+}
+
+/// Returns the offset used for local times
+pub fn local_offset() -> UtcOffset {
+    LOCAL_OFFSET
+        .get()
+        .copied()
+        .or_else(|| UtcOffset::current_local_offset().ok())
+        .unwrap_or(UtcOffset::UTC)
+}
+
+/// Returns the current local time
+pub fn now_local() -> OffsetDateTime {
+    OffsetDateTime::now_utc().to_offset(local_offset())
+}
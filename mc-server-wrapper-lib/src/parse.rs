@@ -2,7 +2,9 @@ use log::log;
 
 use fmt::Display;
 use std::fmt;
-use time::{format_description::FormatItem, OffsetDateTime, Time};
+use time::{format_description::FormatItem, Duration, OffsetDateTime, Time};
+
+use crate::local_time;
 
 /// More informative representations for specific, supported console messages.
 #[derive(Debug, Clone, PartialEq)]
@@ -12,6 +14,12 @@ pub enum ConsoleMsgSpecific {
         name: String,
         msg: String,
     },
+    /// A private message sent with `/msg`, `/tell`, or `/w`
+    PlayerWhisper {
+        from: String,
+        to: String,
+        msg: String,
+    },
     PlayerLogin {
         name: String,
         ip: String,
@@ -42,49 +50,394 @@ pub enum ConsoleMsgSpecific {
         /// The amount of time the server took to load
         time_elapsed_s: f32,
     },
+    /// The response to the `list` (or `list uuids`) command
+    PlayerList {
+        online: u32,
+        max: u32,
+        players: Vec<ListedPlayer>,
+    },
+    /// The Minecraft version the server is running, printed as it starts
+    ServerVersion {
+        version: String,
+    },
+    /// The response to the `seed` command
+    WorldSeed {
+        seed: i64,
+    },
+    /// The difficulty, printed when it's queried or changed with the
+    /// `difficulty` command
+    Difficulty {
+        difficulty: String,
+    },
+    /// A player was kicked, banned, or pardoned with the `kick`, `ban`, or
+    /// `pardon` command
+    Moderation {
+        action: ModerationAction,
+        /// The name of the player the command was run on
+        name: String,
+        /// Present for kicks and bans
+        reason: Option<String>,
+        /// The player that ran the command, if it wasn't run from the console
+        by: Option<String>,
+    },
+    /// The server has fallen behind ("Can't keep up! Is the server
+    /// overloaded?")
+    Overloaded {
+        /// How far behind the server is
+        behind_ms: u64,
+        ticks: u64,
+    },
+}
+
+/// What was done to a player by a moderation command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationAction {
+    Kick,
+    Ban,
+    Pardon,
+}
+
+impl ModerationAction {
+    /// Returns the action performed by `command` (like `ban`), if any
+    pub fn from_command(command: &str) -> Option<Self> {
+        match command {
+            "kick" => Some(ModerationAction::Kick),
+            "ban" => Some(ModerationAction::Ban),
+            "pardon" => Some(ModerationAction::Pardon),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ModerationAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ModerationAction::Kick => "Kicked",
+            ModerationAction::Ban => "Banned",
+            ModerationAction::Pardon => "Pardoned",
+        })
+    }
+}
+
+/// A player in the response to the `list` command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedPlayer {
+    pub name: String,
+    /// Present if the list was requested with `list uuids`
+    pub uuid: Option<String>,
+}
+
+/// Templates for recognizing console messages that a server prints in a
+/// language other than English
+///
+/// Templates are matched against the whole message. Placeholders are written
+/// as `{name}`; for example, `{name} a quitté la partie` matches a French
+/// logout message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessagePatterns {
+    /// Templates for a player leaving, with a `{name}` placeholder
+    pub player_logout: Vec<String>,
+    /// Templates for a player losing connection, with `{name}` and `{reason}`
+    /// placeholders
+    pub player_lost_connection: Vec<String>,
+    /// Templates for the response to `list`, with `{online}`, `{max}`, and
+    /// `{players}` placeholders
+    pub player_list: Vec<String>,
+    /// Templates for chat messages in a custom format (like one set by a chat
+    /// plugin), with `{name}` and `{msg}` placeholders
+    ///
+    /// Text before the name in the template can be matched with a placeholder
+    /// that isn't used, like `{prefix}`.
+    pub player_msg: Vec<String>,
+    /// Templates for private messages in a custom format, with `{from}`,
+    /// `{to}`, and `{msg}` placeholders
+    pub player_whisper: Vec<String>,
+}
+
+impl MessagePatterns {
+    /// Tries to parse the given `ConsoleMsg` as a chat message using these
+    /// templates
+    ///
+    /// This is tried before any other parsing so that chat like "I left the
+    /// game" isn't mistaken for something else.
+    fn parse_chat(&self, console_msg: &ConsoleMsg) -> Option<ConsoleMsgSpecific> {
+        if console_msg.msg_type != ConsoleMsgType::Info {
+            return None;
+        }
+
+        // Requiring a valid name keeps loose templates like `{name}: {msg}`
+        // from matching other console output
+        let (name, msg) = self.player_msg.iter().find_map(|template| {
+            let captures = match_template(template, &console_msg.msg)?;
+            let name = capture(&captures, "name").filter(|name| is_player_name(name))?;
+            Some((name, capture(&captures, "msg").unwrap_or_default()))
+        })?;
+
+        Some(ConsoleMsgSpecific::PlayerMsg {
+            name: name.into(),
+            msg: msg.into(),
+        })
+    }
+
+    /// Tries to parse the given `ConsoleMsg` as a private message using these
+    /// templates
+    fn parse_whisper(&self, console_msg: &ConsoleMsg) -> Option<ConsoleMsgSpecific> {
+        if console_msg.msg_type != ConsoleMsgType::Info {
+            return None;
+        }
+
+        self.player_whisper.iter().find_map(|template| {
+            let captures = match_template(template, &console_msg.msg)?;
+            let from = capture(&captures, "from").filter(|name| is_player_name(name))?;
+            let to = capture(&captures, "to").filter(|name| is_player_name(name))?;
+
+            Some(ConsoleMsgSpecific::PlayerWhisper {
+                from: from.into(),
+                to: to.into(),
+                msg: capture(&captures, "msg").unwrap_or_default().into(),
+            })
+        })
+    }
+
+    /// Tries to determine a `ConsoleMsgSpecific` variant for the given
+    /// `ConsoleMsg` using these templates
+    fn parse(&self, console_msg: &ConsoleMsg) -> Option<ConsoleMsgSpecific> {
+        let msg = console_msg.msg.as_str();
+        let find = |templates: &[String]| {
+            templates
+                .iter()
+                .find_map(|template| match_template(template, msg))
+        };
+
+        if let Some(captures) = find(&self.player_logout) {
+            return Some(ConsoleMsgSpecific::PlayerLogout {
+                name: capture(&captures, "name")?.into(),
+            });
+        }
+
+        if let Some(captures) = find(&self.player_lost_connection) {
+            return Some(ConsoleMsgSpecific::PlayerLostConnection {
+                name: capture(&captures, "name")?.into(),
+                reason: capture(&captures, "reason").unwrap_or_default().into(),
+            });
+        }
+
+        if let Some(captures) = find(&self.player_list) {
+            return Some(ConsoleMsgSpecific::PlayerList {
+                online: capture(&captures, "online")?.trim().parse().ok()?,
+                max: capture(&captures, "max")?.trim().parse().ok()?,
+                players: parse_listed_players(capture(&captures, "players").unwrap_or_default()),
+            });
+        }
+
+        None
+    }
+}
+
+/// Matches `msg` against a template with `{placeholder}`s, returning the text
+/// captured by each placeholder
+fn match_template<'a>(template: &str, msg: &'a str) -> Option<Vec<(String, &'a str)>> {
+    // Split the template into alternating literals and placeholder names
+    let mut literals = vec![];
+    let mut placeholders = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = start + rest[start..].find('}')?;
+        literals.push(&rest[..start]);
+        placeholders.push(&rest[start + 1..end]);
+        rest = &rest[end + 1..];
+    }
+    literals.push(rest);
+
+    let mut msg = msg.strip_prefix(literals[0])?;
+    let mut captures = Vec::with_capacity(placeholders.len());
+    for (placeholder, literal) in placeholders.iter().zip(&literals[1..]) {
+        let end = if literal.is_empty() {
+            msg.len()
+        } else {
+            msg.find(literal)?
+        };
+        if end == 0 {
+            return None;
+        }
+
+        captures.push((placeholder.to_string(), &msg[..end]));
+        msg = &msg[end + literal.len()..];
+    }
+
+    if msg.is_empty() {
+        Some(captures)
+    } else {
+        None
+    }
+}
+
+fn capture<'a>(captures: &[(String, &'a str)], placeholder: &str) -> Option<&'a str> {
+    captures
+        .iter()
+        .find(|(name, _)| name == placeholder)
+        .map(|(_, value)| *value)
+}
+
+/// Splits a chat message like `<Cldfire> hi` into the sender's name and the
+/// message
+///
+/// Prefixes before the name (`[Not Secure] <Cldfire> hi`, `[Admin] <Cldfire>
+/// hi`, or `<[Admin] Cldfire> hi`) are skipped.
+fn split_chat(msg: &str) -> Option<(&str, &str)> {
+    let mut msg = msg;
+    while let Some(rest) = msg.strip_prefix('[') {
+        msg = rest.split_once("] ")?.1;
+    }
+
+    // Display names can contain a `>`, so prefer splitting on "> "
+    let msg = msg.strip_prefix('<')?;
+    let (name, msg) = msg.split_once("> ").or_else(|| msg.split_once('>'))?;
+    let name = name.rsplit(' ').next()?;
+    if !is_player_name(name) {
+        return None;
+    }
+
+    Some((name, msg))
+}
+
+/// Splits a private message into the sender's name, the recipient's name, and
+/// the message
+///
+/// Both `Cldfire whispers to Notch: hi` and `[Cldfire -> Notch] hi` (used by
+/// Essentials and similar plugins) are supported.
+fn split_whisper(msg: &str) -> Option<(&str, &str, &str)> {
+    let (from, to, msg) = if let Some(rest) = msg.strip_prefix('[') {
+        let (names, msg) = rest.split_once("] ")?;
+        let (from, to) = names.split_once(" -> ")?;
+        (from, to, msg)
+    } else {
+        let (from, rest) = msg.split_once(" whispers to ")?;
+        let (to, msg) = rest.split_once(": ")?;
+        (from, to, msg)
+    };
+
+    if is_player_name(from) && is_player_name(to) {
+        Some((from, to, msg))
+    } else {
+        None
+    }
+}
+
+/// Parses the feedback from a moderation command into the action, the
+/// player's name, the reason, and who ran the command
+///
+/// The feedback is `Kicked Steve: reason`, `Banned Steve: reason`, or
+/// `Unbanned Steve` when the command was run from the console, and wrapped
+/// like `[Cldfire: Kicked Steve: reason]` when a player ran it. IP bans
+/// aren't included.
+fn split_moderation(msg: &str) -> Option<(ModerationAction, &str, Option<&str>, Option<&str>)> {
+    let (by, msg) = match msg.strip_prefix('[').and_then(|msg| msg.strip_suffix(']')) {
+        Some(msg) => {
+            let (by, msg) = msg.split_once(": ")?;
+            (Some(by), msg)
+        }
+        None => (None, msg),
+    };
+
+    let (action, name, reason) = if let Some(rest) = msg.strip_prefix("Unbanned ") {
+        (ModerationAction::Pardon, rest, None)
+    } else {
+        let (action, rest) = if let Some(rest) = msg.strip_prefix("Kicked ") {
+            (ModerationAction::Kick, rest)
+        } else {
+            (ModerationAction::Ban, msg.strip_prefix("Banned ")?)
+        };
+        let (name, reason) = rest.split_once(": ")?;
+        (action, name, Some(reason))
+    };
+
+    // IP addresses are preceded by "IP ", so they aren't valid names
+    if is_player_name(name) {
+        Some((action, name, reason, by))
+    } else {
+        None
+    }
+}
+
+/// Returns true if `name` could be a player's name
+fn is_player_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(char::is_whitespace)
+}
+
+/// Parses the player list in the response to `list`, like
+/// `name (uuid), name (uuid)`
+fn parse_listed_players(names: &str) -> Vec<ListedPlayer> {
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.find(" (") {
+            Some(idx) => ListedPlayer {
+                name: p[..idx].into(),
+                uuid: Some(p[idx + 2..].trim_end_matches(')').into()),
+            },
+            None => ListedPlayer {
+                name: p.into(),
+                uuid: None,
+            },
+        })
+        .collect()
 }
 
 impl ConsoleMsgSpecific {
+    /// Tries to determine a `ConsoleMsgSpecific` variant for the given
+    /// `ConsoleMsg`, also recognizing chat in the given custom formats and
+    /// falling back to the given templates for messages in other languages
+    pub fn try_parse_with(
+        console_msg: &ConsoleMsg,
+        patterns: &MessagePatterns,
+    ) -> Option<ConsoleMsgSpecific> {
+        patterns
+            .parse_chat(console_msg)
+            .or_else(|| patterns.parse_whisper(console_msg))
+            .or_else(|| Self::try_parse_from(console_msg))
+            .or_else(|| patterns.parse(console_msg))
+    }
+
     /// Tries to determine a `ConsoleMsgSpecific` variant for the given
     /// `ConsoleMsg`.
-    pub(crate) fn try_parse_from(console_msg: &ConsoleMsg) -> Option<ConsoleMsgSpecific> {
+    pub fn try_parse_from(console_msg: &ConsoleMsg) -> Option<ConsoleMsgSpecific> {
         // Note that the order in which these conditions are tested is important:
         // we need to make sure that we are not dealing with a player message before
         // it is okay to test for other things, for instance
         Some(if console_msg.thread_name.contains("User Authenticator") {
-            let (name, uuid) = {
-                // Get rid of "UUID of player "
-                let minus_start = &console_msg.msg[15..];
-                let (name, remain) = minus_start.split_at(minus_start.find(' ').unwrap());
-
-                // Slice `remain` to get rid of " is "
-                (name.to_string(), remain[4..].to_string())
-            };
+            // "UUID of player Cldfire is 361e5fb3-dbce-4f91-86b2-43423a4888d5"
+            let (name, uuid) = console_msg
+                .msg
+                .strip_prefix("UUID of player ")?
+                .split_once(" is ")?;
 
-            ConsoleMsgSpecific::PlayerAuth { name, uuid }
-        } else if console_msg.msg_type == ConsoleMsgType::Info
-            && (console_msg.thread_name.starts_with("Async Chat Thread")
-                || (console_msg.msg.starts_with('<')
-                    || console_msg.msg.starts_with("[Not Secure]"))
-                    && console_msg.thread_name == "Server thread")
+            ConsoleMsgSpecific::PlayerAuth {
+                name: name.into(),
+                uuid: uuid.into(),
+            }
+        } else if let Some((name, msg)) = Some(console_msg)
+            .filter(|console_msg| {
+                console_msg.msg_type == ConsoleMsgType::Info
+                    && (console_msg.thread_name.starts_with("Async Chat Thread")
+                        || console_msg.thread_name == "Server thread")
+            })
+            .and_then(|console_msg| split_chat(&console_msg.msg))
         {
-            let (name, msg) = {
-                // trim prefix from insecure messages.
-                let msg = console_msg
-                    .msg
-                    .strip_prefix("[Not Secure] ")
-                    .unwrap_or(&console_msg.msg);
-
-                let (name, remain) = msg
-                    // If a > cannot be found, this is not a player message
-                    // and therefore we return
-                    .split_at(msg.find('>')?);
-
-                // Trim "<" from the player's name and "> " from the msg
-                (name[1..].to_string(), remain[2..].to_string())
-            };
-
-            ConsoleMsgSpecific::PlayerMsg { name, msg }
+            ConsoleMsgSpecific::PlayerMsg {
+                name: name.into(),
+                msg: msg.into(),
+            }
+        } else if let Some((from, to, msg)) = Some(console_msg)
+            .filter(|console_msg| console_msg.msg_type == ConsoleMsgType::Info)
+            .and_then(|console_msg| split_whisper(&console_msg.msg))
+        {
+            ConsoleMsgSpecific::PlayerWhisper {
+                from: from.into(),
+                to: to.into(),
+                msg: msg.into(),
+            }
         } else if console_msg.msg
             == "You need to agree to the EULA in order to run the server. Go to \
                                 eula.txt for more info."
@@ -94,84 +447,148 @@ impl ConsoleMsgSpecific {
         } else if console_msg.msg.contains("logged in with entity id")
             && console_msg.msg_type == ConsoleMsgType::Info
         {
-            let (name, remain) = console_msg.msg.split_at(console_msg.msg.find('[').unwrap());
-            let name = name.to_string();
-
-            let (ip, mut remain) = remain.split_at(remain.find(']').unwrap());
-            let ip = ip[2..].to_string();
-
-            // Get rid of "] logged in with entity id "
-            remain = &remain[27..];
-
-            let (entity_id, mut remain) = remain.split_at(remain.find(' ').unwrap());
-            let entity_id = entity_id.parse().unwrap();
+            // "Cldfire[/127.0.0.1:56538] logged in with entity id 121 at (-2.5, 63.0, 256.5)"
+            let (name, remain) = console_msg.msg.split_once('[')?;
+            let (ip, remain) = remain.split_once(']')?;
+            let ip = ip.strip_prefix('/').unwrap_or(ip);
 
-            // Get rid of " at (" in front and ")" behind
-            remain = &remain[5..remain.len() - 1];
+            let remain = remain.strip_prefix(" logged in with entity id ")?;
+            let (entity_id, remain) = remain.split_once(' ')?;
+            let entity_id = entity_id.parse().ok()?;
 
-            let (world, remain) = if remain.starts_with('[') {
+            let remain = remain.strip_prefix("at (")?.strip_suffix(')')?;
+            let (world, remain) = match remain.strip_prefix('[') {
                 // This is a Spigot server; parse world
-                let (world, remain) = remain.split_at(remain.find(']').unwrap());
-                (Some(world[1..].to_string()), &remain[1..])
-            } else {
-                (None, remain)
+                Some(remain) => {
+                    let (world, remain) = remain.split_once(']')?;
+                    (Some(world.to_string()), remain)
+                }
+                None => (None, remain),
             };
 
-            // `remain = &remain[2..]` is used to skip ", "
-            let (x_coord, mut remain) = remain.split_at(remain.find(',').unwrap());
-            remain = &remain[2..];
-
-            let (y_coord, mut remain) = remain.split_at(remain.find(',').unwrap());
-            remain = &remain[2..];
-
-            let x_coord = x_coord.parse().unwrap();
-            let y_coord = y_coord.parse().unwrap();
-            let z_coord = remain.parse().unwrap();
+            let mut coords = remain.split(',').map(|c| c.trim().parse().ok());
+            let coords = (coords.next()??, coords.next()??, coords.next()??);
 
             ConsoleMsgSpecific::PlayerLogin {
-                name,
-                ip,
+                name: name.into(),
+                ip: ip.into(),
                 entity_id,
-                coords: (x_coord, y_coord, z_coord),
+                coords,
                 world,
             }
         } else if console_msg.msg.contains("Preparing spawn area: ")
             && console_msg.msg_type == ConsoleMsgType::Info
         {
-            let progress = console_msg.msg
-                [console_msg.msg.find(':').unwrap() + 2..console_msg.msg.len() - 1]
+            // "Preparing spawn area: 83%"
+            let progress = console_msg
+                .msg
+                .split_once(": ")?
+                .1
+                .strip_suffix('%')?
                 .parse()
-                .unwrap();
+                .ok()?;
 
             ConsoleMsgSpecific::SpawnPrepareProgress { progress }
         } else if console_msg.msg.contains("Time elapsed: ") {
-            let time_elapsed_ms = console_msg.msg
-                [console_msg.msg.find(':').unwrap() + 2..console_msg.msg.find("ms").unwrap() - 1]
+            // "Time elapsed: 3254 ms"
+            let time_elapsed_ms = console_msg
+                .msg
+                .split_once("Time elapsed: ")?
+                .1
+                .split_once(" ms")?
+                .0
                 .parse()
-                .unwrap();
+                .ok()?;
 
             ConsoleMsgSpecific::SpawnPrepareFinish { time_elapsed_ms }
+        } else if console_msg.msg.starts_with("There are ")
+            && console_msg.msg.contains(" players online:")
+            && console_msg.msg_type == ConsoleMsgType::Info
+        {
+            // "There are 2 of a max of 20 players online: name (uuid), name (uuid)"
+            let (counts, names) = console_msg.msg.split_once(':')?;
+            let mut counts = counts["There are ".len()..]
+                .split(' ')
+                .filter_map(|s| s.parse().ok());
+            let (online, max) = (counts.next()?, counts.next()?);
+
+            let players = parse_listed_players(names);
+
+            ConsoleMsgSpecific::PlayerList {
+                online,
+                max,
+                players,
+            }
+        } else if console_msg
+            .msg
+            .starts_with("Starting minecraft server version ")
+            && console_msg.msg_type == ConsoleMsgType::Info
+        {
+            let version = &console_msg.msg["Starting minecraft server version ".len()..];
+
+            ConsoleMsgSpecific::ServerVersion {
+                version: version.trim().into(),
+            }
+        } else if console_msg.msg.starts_with("Seed: [")
+            && console_msg.msg_type == ConsoleMsgType::Info
+        {
+            // "Seed: [-4172144997902289642]"
+            let seed = console_msg.msg["Seed: [".len()..]
+                .strip_suffix(']')?
+                .parse()
+                .ok()?;
+
+            ConsoleMsgSpecific::WorldSeed { seed }
+        } else if console_msg.msg.starts_with("The difficulty ")
+            && console_msg.msg_type == ConsoleMsgType::Info
+        {
+            // "The difficulty has been set to Hard", "The difficulty is Normal",
+            // or "The difficulty did not change; it is already set to Easy"
+            let difficulty = ["has been set to ", "already set to ", "is "]
+                .iter()
+                .find_map(|prefix| Some(console_msg.msg.split_once(prefix)?.1))?;
+
+            ConsoleMsgSpecific::Difficulty {
+                difficulty: difficulty.into(),
+            }
+        } else if let Some((action, name, reason, by)) = Some(console_msg)
+            .filter(|console_msg| console_msg.msg_type == ConsoleMsgType::Info)
+            .and_then(|console_msg| split_moderation(&console_msg.msg))
+        {
+            ConsoleMsgSpecific::Moderation {
+                action,
+                name: name.into(),
+                reason: reason.map(Into::into),
+                by: by.map(Into::into),
+            }
         } else if console_msg.msg.contains("lost connection: ") {
-            let (name, remain) = console_msg.msg.split_at(console_msg.msg.find(' ').unwrap());
-            let name = name.into();
-            let reason = remain[remain.find(':').unwrap() + 2..].into();
+            let (name, _) = console_msg.msg.split_once(' ')?;
+            let (_, reason) = console_msg.msg.split_once("lost connection: ")?;
 
-            ConsoleMsgSpecific::PlayerLostConnection { name, reason }
+            ConsoleMsgSpecific::PlayerLostConnection {
+                name: name.into(),
+                reason: reason.into(),
+            }
         } else if console_msg.msg.contains("left the game") {
-            let name = console_msg
-                .msg
-                .split_at(console_msg.msg.find(' ').unwrap())
-                .0
-                .into();
+            let (name, _) = console_msg.msg.split_once(' ')?;
 
-            ConsoleMsgSpecific::PlayerLogout { name }
+            ConsoleMsgSpecific::PlayerLogout { name: name.into() }
+        } else if console_msg.msg.starts_with("Can't keep up!")
+            && console_msg.msg_type == ConsoleMsgType::Warn
+        {
+            // "Can't keep up! Is the server overloaded? Running 2034ms or 40 ticks behind"
+            let (_, behind) = console_msg.msg.split_once("Running ")?;
+            let (behind_ms, rest) = behind.split_once("ms or ")?;
+            let (ticks, _) = rest.split_once(" ticks behind")?;
+
+            ConsoleMsgSpecific::Overloaded {
+                behind_ms: behind_ms.parse().ok()?,
+                ticks: ticks.parse().ok()?,
+            }
         } else if console_msg.msg.starts_with("Done (") {
-            let time = &console_msg
-                .msg
-                .split_at(console_msg.msg.find('(').unwrap())
-                .1[1..];
-
-            let time_elapsed_s = time.split_at(time.find('s').unwrap()).0.parse().unwrap();
+            // "Done (3.264s)! For help, type "help""
+            let (time, _) = console_msg.msg["Done (".len()..].split_once('s')?;
+            let time_elapsed_s = time.parse().ok()?;
 
             ConsoleMsgSpecific::FinishedLoading { time_elapsed_s }
         } else {
@@ -183,7 +600,11 @@ impl ConsoleMsgSpecific {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ConsoleMsg {
-    pub timestamp: Time,
+    /// When the message was printed
+    ///
+    /// Console output only includes the time of day, so the date is inferred
+    /// when parsing.
+    pub timestamp: OffsetDateTime,
     pub thread_name: String,
     pub msg_type: ConsoleMsgType,
     pub msg: String,
@@ -212,10 +633,7 @@ impl ConsoleMsg {
     /// Create a new `ConsoleMsg` with the current time and a blank thread name.
     pub fn new(msg_type: ConsoleMsgType, msg: String) -> Self {
         Self {
-            // TODO: do something better than unix epoch fallback in failure case
-            timestamp: OffsetDateTime::now_local()
-                .unwrap_or(OffsetDateTime::UNIX_EPOCH)
-                .time(),
+            timestamp: now(),
             thread_name: "".into(),
             msg_type,
             msg,
@@ -230,8 +648,17 @@ impl ConsoleMsg {
     /// The `target:` parameter of `log!` will be set to
     /// `CONSOLE_MSG_LOG_TARGET`.
     pub fn log(&self) {
+        self.log_for_instance(None);
+    }
+
+    /// Logs the `ConsoleMsg` based on its type, attributing it to the given
+    /// server instance
+    ///
+    /// The `target:` parameter of `log!` will be set to the value returned by
+    /// `console_log_target(instance)`.
+    pub fn log_for_instance(&self, instance: Option<&str>) {
         log!(
-            target: crate::CONSOLE_MSG_LOG_TARGET.get_or_init(|| "mc"),
+            target: &crate::console_log_target(instance),
             self.msg_type.clone().into(),
             "{}",
             self.msg
@@ -239,31 +666,86 @@ impl ConsoleMsg {
     }
 
     /// Constructs a `ConsoleMsg` from a line of console output.
-    pub(crate) fn try_parse_from(raw: &str) -> Option<ConsoleMsg> {
-        let (mut timestamp, remain) = raw.split_at(raw.find(']')?);
-        timestamp = &timestamp[1..];
+    ///
+    /// The message is assumed to have been printed around now.
+    pub fn try_parse_from(raw: &str) -> Option<ConsoleMsg> {
+        Self::try_parse_at(raw, now())
+    }
 
-        let (mut thread_name, remain) = remain.split_at(remain.find('/')?);
-        thread_name = &thread_name[3..];
+    /// Constructs a `ConsoleMsg` from a line of console output that was
+    /// printed around `reference`
+    ///
+    /// The timestamp is given the date (the day before, of, or after
+    /// `reference`) that puts it closest to `reference`, so lines printed just
+    /// before midnight and read just after it are dated correctly.
+    pub fn try_parse_at(raw: &str, reference: OffsetDateTime) -> Option<ConsoleMsg> {
+        let (timestamp, remain) = raw.split_at(raw.find(']')?);
+        let timestamp = timestamp.get(1..)?;
 
-        let (mut msg_type, remain) = remain.split_at(remain.find(']')?);
-        msg_type = &msg_type[1..];
+        let (thread_name, remain) = remain.split_at(remain.find('/')?);
+        let thread_name = thread_name.get(3..)?;
+
+        let (msg_type, remain) = remain.split_at(remain.find(']')?);
+        let msg_type = msg_type.get(1..)?;
 
         Some(Self {
-            // TODO: do something better than midnight as failure fallback here
-            timestamp: Time::from_hms(
-                timestamp[..2].parse().unwrap(),
-                timestamp[3..5].parse().unwrap(),
-                timestamp[6..].parse().unwrap(),
-            )
-            .unwrap_or(Time::MIDNIGHT),
+            timestamp: infer_date(parse_time(timestamp)?, reference),
             thread_name: thread_name.into(),
             msg_type: ConsoleMsgType::parse_from(msg_type),
-            msg: remain[3..].into(),
+            msg: remain.get(3..).unwrap_or_default().into(),
         })
     }
 }
 
+/// The current time, in the local offset (see `local_time`)
+fn now() -> OffsetDateTime {
+    local_time::now_local()
+}
+
+/// Parses a timestamp like `23:10:30`, optionally with fractional seconds
+/// (`23:10:30.123`)
+fn parse_time(raw: &str) -> Option<Time> {
+    let (hms, fraction) = match raw.split_once('.') {
+        Some((hms, fraction)) => (hms, Some(fraction)),
+        None => (raw, None),
+    };
+
+    let mut parts = hms.splitn(3, ':');
+    let hour = parts.next()?.parse().ok()?;
+    let minute = parts.next()?.parse().ok()?;
+    let second = parts.next()?.parse().ok()?;
+
+    let nanosecond = match fraction {
+        Some(fraction)
+            if !fraction.is_empty()
+                && fraction.len() <= 9
+                && fraction.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            fraction.parse::<u32>().ok()? * 10u32.pow(9 - fraction.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+
+    Time::from_hms_nano(hour, minute, second, nanosecond).ok()
+}
+
+/// Gives `time` the date (the day before, of, or after `reference`) that puts
+/// it closest to `reference`
+fn infer_date(time: Time, reference: OffsetDateTime) -> OffsetDateTime {
+    let same_day = reference.replace_time(time);
+    [same_day - Duration::DAY, same_day + Duration::DAY]
+        .iter()
+        .copied()
+        .fold(same_day, |closest, candidate| {
+            if (candidate - reference).abs() < (closest - reference).abs() {
+                candidate
+            } else {
+                closest
+            }
+        })
+}
+
 /// Various types of console messages that can occur
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ConsoleMsgType {
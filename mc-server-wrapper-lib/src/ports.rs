@@ -0,0 +1,170 @@
+use std::{
+    fmt, fs, io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket},
+    path::Path,
+};
+
+/// The port servers listen on if `server-port` isn't set
+const DEFAULT_SERVER_PORT: u16 = 25565;
+/// The port RCON listens on if `rcon.port` isn't set
+const DEFAULT_RCON_PORT: u16 = 25575;
+
+/// A port the server is configured to listen on that something else is
+/// already using
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortInUse {
+    /// The `server.properties` key the port is set with
+    pub property: &'static str,
+    pub port: u16,
+    /// The process using the port, if it could be found (Linux only)
+    pub pid: Option<u32>,
+}
+
+impl fmt::Display for PortInUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "port {} (`{}`) is already in use",
+            self.port, self.property
+        )?;
+        match self.pid {
+            Some(pid) => write!(f, " by PID {}", pid),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for PortInUse {}
+
+/// The protocol a port is used with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// A port the server listens on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ServerPort {
+    pub property: &'static str,
+    pub address: SocketAddr,
+    pub protocol: Protocol,
+}
+
+/// Returns the ports the server with the given `server.properties` contents
+/// listens on
+///
+/// RCON and query ports are only included if they're enabled.
+pub(crate) fn server_ports(properties: &str) -> Vec<ServerPort> {
+    let get = |key: &str| {
+        properties
+            .lines()
+            .filter(|line| !line.trim_start().starts_with('#'))
+            .filter_map(|line| line.split_once('='))
+            .find(|(k, _)| k.trim() == key)
+            .map(|(_, value)| value.trim())
+    };
+    let port = |key: &str, default: u16| get(key).and_then(|p| p.parse().ok()).unwrap_or(default);
+
+    let ip = get("server-ip")
+        .and_then(|ip| ip.parse().ok())
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    let server_port = port("server-port", DEFAULT_SERVER_PORT);
+
+    let mut ports = vec![ServerPort {
+        property: "server-port",
+        address: SocketAddr::new(ip, server_port),
+        protocol: Protocol::Tcp,
+    }];
+    if get("enable-rcon") == Some("true") {
+        // RCON always listens on every address
+        ports.push(ServerPort {
+            property: "rcon.port",
+            address: SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                port("rcon.port", DEFAULT_RCON_PORT),
+            ),
+            protocol: Protocol::Tcp,
+        });
+    }
+    if get("enable-query") == Some("true") {
+        ports.push(ServerPort {
+            property: "query.port",
+            address: SocketAddr::new(ip, port("query.port", server_port)),
+            protocol: Protocol::Udp,
+        });
+    }
+    ports
+}
+
+/// Returns the ID of the process listening on `port`, if it can be found
+///
+/// This looks the socket up in `/proc`, so it only works on Linux, and only
+/// for processes whose file descriptors can be read (ones run by the same
+/// user, or any when running as root).
+fn find_listener(port: u16, protocol: Protocol) -> Option<u32> {
+    let (tables, listening_state) = match protocol {
+        Protocol::Tcp => (["/proc/net/tcp", "/proc/net/tcp6"], "0A"),
+        Protocol::Udp => (["/proc/net/udp", "/proc/net/udp6"], "07"),
+    };
+
+    let inode = tables.iter().find_map(|table| {
+        let contents = fs::read_to_string(table).ok()?;
+        contents.lines().skip(1).find_map(|line| {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            let local_port = fields.get(1)?.rsplit(':').next()?;
+            if u16::from_str_radix(local_port, 16).ok()? == port
+                && *fields.get(3)? == listening_state
+            {
+                fields.get(9).map(|inode| inode.to_string())
+            } else {
+                None
+            }
+        })
+    })?;
+    let socket = format!("socket:[{}]", inode);
+
+    fs::read_dir("/proc").ok()?.flatten().find_map(|entry| {
+        let pid = entry.file_name().to_str()?.parse().ok()?;
+        let uses_socket = fs::read_dir(entry.path().join("fd"))
+            .ok()?
+            .flatten()
+            .any(|fd| {
+                fs::read_link(fd.path())
+                    .map(|target| target.as_os_str() == socket.as_str())
+                    .unwrap_or(false)
+            });
+        uses_socket.then_some(pid)
+    })
+}
+
+/// Checks that nothing else is using the ports the server in `server_dir` is
+/// configured to listen on
+///
+/// Nothing is checked if the server doesn't have a `server.properties` yet,
+/// since it's created with default settings the first time the server runs.
+pub(crate) fn check(server_dir: &Path) -> Result<(), PortInUse> {
+    let properties = match fs::read_to_string(server_dir.join("server.properties")) {
+        Ok(properties) => properties,
+        Err(_) => return Ok(()),
+    };
+
+    for port in server_ports(&properties) {
+        let bound = match port.protocol {
+            Protocol::Tcp => TcpListener::bind(port.address).map(|_| ()),
+            Protocol::Udp => UdpSocket::bind(port.address).map(|_| ()),
+        };
+        // Other failures (like `server-ip` not being an address of this
+        // machine) are left for the server to report
+        if let Err(e) = bound {
+            if e.kind() == io::ErrorKind::AddrInUse {
+                return Err(PortInUse {
+                    property: port.property,
+                    port: port.address.port(),
+                    pid: find_listener(port.address.port(), port.protocol),
+                });
+            }
+        }
+    }
+    Ok(())
+}
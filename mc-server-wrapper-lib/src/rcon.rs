@@ -0,0 +1,106 @@
+use std::{io, net::SocketAddr};
+
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+/// Packet type for authenticating with the server
+const LOGIN: i32 = 3;
+/// Packet type for running a command
+const COMMAND: i32 = 2;
+
+/// The largest packet the server will send
+const MAX_PACKET_LEN: i32 = 4096 + 10;
+
+/// Errors regarding an RCON connection
+#[derive(Error, Debug)]
+pub enum RconError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("the RCON password was rejected")]
+    AuthFailed,
+    #[error("the server sent a malformed RCON packet")]
+    Malformed,
+}
+
+/// A connection to a Minecraft server's RCON interface
+///
+/// Responses longer than a single packet (about 4 KB) are truncated.
+#[derive(Debug)]
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl RconClient {
+    /// Connect to the server at `address` and log in with `password`
+    pub async fn connect(address: SocketAddr, password: &str) -> Result<Self, RconError> {
+        let mut client = Self {
+            stream: TcpStream::connect(address).await?,
+            next_id: 1,
+        };
+
+        let id = client.send(LOGIN, password).await?;
+        let (response_id, _) = client.receive().await?;
+        if response_id != id {
+            return Err(RconError::AuthFailed);
+        }
+
+        Ok(client)
+    }
+
+    /// Run `command` on the server, returning its output
+    pub async fn command(&mut self, command: &str) -> Result<String, RconError> {
+        let id = self.send(COMMAND, command).await?;
+
+        loop {
+            let (response_id, body) = self.receive().await?;
+            if response_id == id {
+                return Ok(body);
+            }
+        }
+    }
+
+    async fn send(&mut self, kind: i32, body: &str) -> Result<i32, RconError> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+
+        self.stream.write_all(&encode(id, kind, body)).await?;
+        Ok(id)
+    }
+
+    async fn receive(&mut self) -> Result<(i32, String), RconError> {
+        let len = self.stream.read_i32_le().await?;
+        if !(10..=MAX_PACKET_LEN).contains(&len) {
+            return Err(RconError::Malformed);
+        }
+
+        let mut buf = vec![0; len as usize];
+        self.stream.read_exact(&mut buf).await?;
+        decode(&buf).ok_or(RconError::Malformed)
+    }
+}
+
+/// Encodes a packet, including its length prefix
+pub(crate) fn encode(id: i32, kind: i32, body: &str) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(body.len() + 14);
+    packet.extend_from_slice(&(body.len() as i32 + 10).to_le_bytes());
+    packet.extend_from_slice(&id.to_le_bytes());
+    packet.extend_from_slice(&kind.to_le_bytes());
+    packet.extend_from_slice(body.as_bytes());
+    packet.extend_from_slice(&[0, 0]);
+    packet
+}
+
+/// Decodes the ID and body of a packet (without its length prefix)
+pub(crate) fn decode(packet: &[u8]) -> Option<(i32, String)> {
+    if packet.len() < 10 {
+        return None;
+    }
+
+    let id = i32::from_le_bytes([packet[0], packet[1], packet[2], packet[3]]);
+    let body = &packet[8..packet.len() - 2];
+    Some((id, String::from_utf8_lossy(body).into_owned()))
+}
@@ -0,0 +1,71 @@
+use std::{io, path::PathBuf, time::Duration};
+
+use time::Time;
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, BufReader},
+};
+
+use crate::parse::ConsoleMsg;
+
+/// The longest to pause between two lines, so quiet stretches of a log don't
+/// stall the replay
+const MAX_PAUSE: Duration = Duration::from_secs(5);
+
+/// Settings for replaying a recorded server log instead of running a server
+///
+/// The lines of the log are parsed and sent as events the same way a running
+/// server's output is, paced by the timestamps in the log. Commands sent to a
+/// replayed server are ignored, except for `stop`; the replayed server keeps
+/// "running" after the end of the log until it's stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayConfig {
+    /// The log file to replay
+    pub path: PathBuf,
+    /// How many times faster than real time to replay the log, or zero to
+    /// replay it as fast as possible
+    pub speed: f64,
+}
+
+/// Returns how long to pause between lines logged at `prev` and `next`
+///
+/// Lines that appear to be logged earlier than the previous line were logged
+/// the next day.
+pub(crate) fn pause(prev: Time, next: Time, speed: f64) -> Duration {
+    if speed <= 0.0 {
+        return Duration::ZERO;
+    }
+
+    let mut elapsed = next - prev;
+    if elapsed.is_negative() {
+        elapsed += time::Duration::DAY;
+    }
+    Duration::try_from_secs_f64(elapsed.as_seconds_f64() / speed)
+        .unwrap_or(MAX_PAUSE)
+        .min(MAX_PAUSE)
+}
+
+/// Reads the log file at `path`, calling `on_line` with each of its lines as
+/// if it were being printed at `speed` times real time
+pub(crate) async fn replay_log<F, Fut>(path: PathBuf, speed: f64, mut on_line: F) -> io::Result<()>
+where
+    F: FnMut(String) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let mut lines = BufReader::new(File::open(path).await?).lines();
+    let mut prev = None;
+
+    while let Some(line) = lines.next_line().await? {
+        if let Some(console_msg) = ConsoleMsg::try_parse_from(&line) {
+            let logged_at = console_msg.timestamp.time();
+            if let Some(prev) = prev {
+                tokio::time::sleep(pause(prev, logged_at, speed)).await;
+            }
+            prev = Some(logged_at);
+        }
+
+        on_line(line).await;
+    }
+
+    Ok(())
+}
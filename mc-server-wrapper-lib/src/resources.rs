@@ -0,0 +1,200 @@
+use std::{fs, io, path::PathBuf};
+
+use thiserror::Error;
+
+/// The root of the cgroup v2 hierarchy
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// The period (in microseconds) used when writing `cpu.max`
+const CGROUP_CPU_PERIOD_US: u64 = 100_000;
+
+/// Limits on the resources the Minecraft server process is allowed to use
+///
+/// Everything here is optional and only applied when set. Priority and
+/// affinity are applied with the standard `nice`, `ionice`, and `taskset`
+/// utilities, so those need to be installed to use them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResourceLimits {
+    /// Niceness to run the server process with (-20 to 19, higher is lower
+    /// priority)
+    pub nice: Option<i8>,
+    /// IO scheduling class and priority to run the server process with
+    pub ionice: Option<IoPriority>,
+    /// The CPUs the server process is allowed to run on
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// A cgroup to place the server process in (Linux only)
+    pub cgroup: Option<CgroupLimits>,
+}
+
+/// IO scheduling settings, as understood by `ionice`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Realtime scheduling with the given priority (0 to 7, lower is higher
+    /// priority)
+    Realtime(u8),
+    /// Best-effort scheduling with the given priority (0 to 7, lower is
+    /// higher priority)
+    BestEffort(u8),
+    /// Only get disk time when nothing else needs it
+    Idle,
+}
+
+/// Limits applied through a cgroup v2 group created for the server process
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgroupLimits {
+    /// Name of the cgroup to create under the cgroup v2 root
+    pub name: String,
+    /// Maximum memory usage in megabytes
+    pub memory_max: Option<u64>,
+    /// Maximum CPU usage as a number of CPUs (e.g. `1.5` for one and a half
+    /// CPUs worth of time)
+    pub cpu_max: Option<f32>,
+}
+
+/// Errors regarding a `ResourceLimits`
+#[derive(Error, Debug)]
+pub enum ResourceLimitsError {
+    #[error("niceness {0} is out of range (must be between -20 and 19)")]
+    NiceOutOfRange(i8),
+    #[error("ionice priority {0} is out of range (must be between 0 and 7)")]
+    IoNiceOutOfRange(u8),
+    #[error("the CPU affinity list is empty")]
+    EmptyCpuAffinity,
+    #[error("cgroup CPU limit {0} must be greater than zero")]
+    CgroupCpuMaxInvalid(f32),
+    #[error("{0} is not supported on this platform")]
+    Unsupported(&'static str),
+}
+
+impl ResourceLimits {
+    /// Validates the limits against each other and the current platform
+    pub fn validate(&self) -> Result<(), ResourceLimitsError> {
+        use ResourceLimitsError::*;
+
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(NiceOutOfRange(nice));
+            }
+
+            if cfg!(windows) {
+                return Err(Unsupported("setting niceness"));
+            }
+        }
+
+        if let Some(ionice) = self.ionice {
+            if let IoPriority::Realtime(level) | IoPriority::BestEffort(level) = ionice {
+                if level > 7 {
+                    return Err(IoNiceOutOfRange(level));
+                }
+            }
+
+            if !cfg!(target_os = "linux") {
+                return Err(Unsupported("setting IO priority"));
+            }
+        }
+
+        if let Some(cpus) = &self.cpu_affinity {
+            if cpus.is_empty() {
+                return Err(EmptyCpuAffinity);
+            }
+
+            if !cfg!(target_os = "linux") {
+                return Err(Unsupported("setting CPU affinity"));
+            }
+        }
+
+        if let Some(cgroup) = &self.cgroup {
+            if let Some(cpu_max) = cgroup.cpu_max {
+                if cpu_max <= 0.0 {
+                    return Err(CgroupCpuMaxInvalid(cpu_max));
+                }
+            }
+
+            if !cfg!(target_os = "linux") {
+                return Err(Unsupported("cgroup limits"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the shell words that should precede the `java` invocation in
+    /// order to apply priority and affinity settings
+    pub(crate) fn command_prefix(&self) -> Vec<String> {
+        let mut prefix = vec![];
+
+        if let Some(nice) = self.nice {
+            prefix.extend(["nice".into(), "-n".into(), nice.to_string()]);
+        }
+
+        if let Some(ionice) = self.ionice {
+            prefix.extend(["ionice".into(), "-c".into()]);
+            match ionice {
+                IoPriority::Realtime(level) => {
+                    prefix.extend(["1".into(), "-n".into(), level.to_string()])
+                }
+                IoPriority::BestEffort(level) => {
+                    prefix.extend(["2".into(), "-n".into(), level.to_string()])
+                }
+                IoPriority::Idle => prefix.push("3".into()),
+            }
+        }
+
+        if let Some(cpus) = &self.cpu_affinity {
+            prefix.extend([
+                "taskset".into(),
+                "-c".into(),
+                cpus.iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ]);
+        }
+
+        prefix
+    }
+}
+
+impl CgroupLimits {
+    /// The path of the cgroup on disk
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(CGROUP_ROOT).join(&self.name)
+    }
+
+    /// Creates the cgroup (if needed) and writes the configured limits to it
+    ///
+    /// Returns the path to the cgroup's `cgroup.procs` file, which the server
+    /// process should be added to.
+    pub(crate) fn setup(&self) -> io::Result<PathBuf> {
+        let path = self.path();
+        fs::create_dir_all(&path)?;
+
+        fs::write(
+            path.join("memory.max"),
+            self.memory_max_value().unwrap_or_else(|| "max".into()),
+        )?;
+        fs::write(
+            path.join("cpu.max"),
+            self.cpu_max_value()
+                .unwrap_or_else(|| format!("max {}", CGROUP_CPU_PERIOD_US)),
+        )?;
+
+        Ok(path.join("cgroup.procs"))
+    }
+
+    /// The value to write to `memory.max`, if a memory limit is set
+    pub(crate) fn memory_max_value(&self) -> Option<String> {
+        self.memory_max.map(|mb| (mb * 1024 * 1024).to_string())
+    }
+
+    /// The value to write to `cpu.max`, if a CPU limit is set
+    pub(crate) fn cpu_max_value(&self) -> Option<String> {
+        self.cpu_max.map(|cpus| {
+            format!(
+                "{} {}",
+                (cpus * CGROUP_CPU_PERIOD_US as f32) as u64,
+                CGROUP_CPU_PERIOD_US
+            )
+        })
+    }
+}
@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// An account to run the Minecraft server process as (Unix only)
+///
+/// The wrapper needs to be running as root in order to switch to a different
+/// account. On Linux the switch is performed with `setpriv` (part of
+/// util-linux) after any cgroup and priority settings have been applied, and
+/// the account's supplementary groups are initialized from the group
+/// database. On other Unix platforms supplementary groups are dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunAs {
+    /// The name of the user to run the server as
+    pub user: String,
+    /// The name of the group to run the server as
+    ///
+    /// Defaults to the user's primary group.
+    pub group: Option<String>,
+}
+
+/// Errors regarding a `RunAs`
+#[derive(Error, Debug)]
+pub enum RunAsError {
+    #[error("running the server as a different user is not supported on this platform")]
+    Unsupported,
+    #[error("the wrapper must be running as root to run the server as user \"{0}\"")]
+    NotRoot(String),
+    #[error("no user named \"{0}\" exists")]
+    UserNotFound(String),
+    #[error("no group named \"{0}\" exists")]
+    GroupNotFound(String),
+    #[error(
+        "user \"{user}\" cannot write to \"{path}\"; change its ownership with \
+        `chown -R {user} {path}`"
+    )]
+    NotWritable { user: String, path: PathBuf },
+    #[error("user \"{user}\" cannot read \"{path}\"")]
+    NotReadable { user: String, path: PathBuf },
+    #[error("failed to check permissions of \"{path}\": {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// The numeric IDs a `RunAs` resolved to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResolvedIds {
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl RunAs {
+    /// Looks up the configured user and group
+    #[cfg(unix)]
+    pub fn resolve(&self) -> Result<ResolvedIds, RunAsError> {
+        let (uid, primary_gid) = sys::lookup_user(&self.user)
+            .ok_or_else(|| RunAsError::UserNotFound(self.user.clone()))?;
+
+        let gid = match &self.group {
+            Some(group) => {
+                sys::lookup_group(group).ok_or_else(|| RunAsError::GroupNotFound(group.clone()))?
+            }
+            None => primary_gid,
+        };
+
+        Ok(ResolvedIds { uid, gid })
+    }
+
+    #[cfg(not(unix))]
+    pub fn resolve(&self) -> Result<ResolvedIds, RunAsError> {
+        Err(RunAsError::Unsupported)
+    }
+
+    /// Ensures the server can be run as the configured account
+    ///
+    /// This checks that the wrapper has the privileges to switch accounts and
+    /// that the account can read the server jar and write to the server's
+    /// directory.
+    pub fn validate(&self, server_path: &Path) -> Result<ResolvedIds, RunAsError> {
+        let ids = self.resolve()?;
+
+        #[cfg(unix)]
+        {
+            if !sys::is_root() {
+                return Err(RunAsError::NotRoot(self.user.clone()));
+            }
+
+            let folder = server_path
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+
+            if !self.check_access(folder, ids, 0o2)? {
+                return Err(RunAsError::NotWritable {
+                    user: self.user.clone(),
+                    path: folder.to_path_buf(),
+                });
+            }
+
+            if !self.check_access(server_path, ids, 0o4)? {
+                return Err(RunAsError::NotReadable {
+                    user: self.user.clone(),
+                    path: server_path.to_path_buf(),
+                });
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Returns true if the given IDs are granted the permission `bit`
+    /// (`0o4` for read, `0o2` for write) by the mode of the file at `path`
+    #[cfg(unix)]
+    fn check_access(&self, path: &Path, ids: ResolvedIds, bit: u32) -> Result<bool, RunAsError> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = path.metadata().map_err(|source| RunAsError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Ok(mode_grants(
+            metadata.mode(),
+            metadata.uid(),
+            metadata.gid(),
+            ids,
+            bit,
+        ))
+    }
+
+    /// Returns the shell words that switch to this account before running the
+    /// rest of the command (Linux only)
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub(crate) fn command_prefix(&self, ids: ResolvedIds) -> Vec<String> {
+        vec![
+            "setpriv".into(),
+            format!("--reuid={}", ids.uid),
+            format!("--regid={}", ids.gid),
+            "--init-groups".into(),
+            "--".into(),
+        ]
+    }
+}
+
+/// Checks the given permission `bit` against the owner, group, and other
+/// portions of `mode`
+pub(crate) fn mode_grants(
+    mode: u32,
+    owner_uid: u32,
+    owner_gid: u32,
+    ids: ResolvedIds,
+    bit: u32,
+) -> bool {
+    if owner_uid == ids.uid {
+        mode & (bit << 6) != 0
+    } else if owner_gid == ids.gid {
+        mode & (bit << 3) != 0
+    } else {
+        mode & bit != 0
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::{ffi::CString, mem::MaybeUninit, ptr};
+
+    /// Returns true if the current process is running as root
+    pub fn is_root() -> bool {
+        // SAFETY: `geteuid` is always successful and has no preconditions
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    /// Looks up the given user, returning its uid and primary gid
+    pub fn lookup_user(name: &str) -> Option<(u32, u32)> {
+        let name = CString::new(name).ok()?;
+        let mut buf = vec![0; 16 * 1024];
+        let mut passwd = MaybeUninit::<libc::passwd>::uninit();
+        let mut result = ptr::null_mut();
+
+        // SAFETY: all pointers are valid for the duration of the call and
+        // `buf.len()` accurately describes the buffer
+        let ret = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                passwd.as_mut_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+
+        if ret != 0 || result.is_null() {
+            return None;
+        }
+
+        // SAFETY: `result` is non-null, so `passwd` was initialized
+        let passwd = unsafe { passwd.assume_init() };
+        Some((passwd.pw_uid, passwd.pw_gid))
+    }
+
+    /// Looks up the given group, returning its gid
+    pub fn lookup_group(name: &str) -> Option<u32> {
+        let name = CString::new(name).ok()?;
+        let mut buf = vec![0; 16 * 1024];
+        let mut group = MaybeUninit::<libc::group>::uninit();
+        let mut result = ptr::null_mut();
+
+        // SAFETY: all pointers are valid for the duration of the call and
+        // `buf.len()` accurately describes the buffer
+        let ret = unsafe {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                group.as_mut_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+
+        if ret != 0 || result.is_null() {
+            return None;
+        }
+
+        // SAFETY: `result` is non-null, so `group` was initialized
+        let group = unsafe { group.assume_init() };
+        Some(group.gr_gid)
+    }
+}
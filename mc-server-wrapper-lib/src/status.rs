@@ -0,0 +1,110 @@
+use std::collections::BTreeSet;
+
+use time::OffsetDateTime;
+
+use crate::parse::ConsoleMsgSpecific;
+
+/// The current state of a Minecraft server managed by an `McServerManager`
+///
+/// This is maintained by the manager from the events it parses out of the
+/// server's console output, so library consumers can query it at any time
+/// rather than tracking it themselves.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum McServerStatus {
+    /// The server process is not running
+    #[default]
+    Stopped,
+    /// The server process has been started but is not yet ready for players
+    Starting {
+        /// World loading progress (as a percentage), if the server has
+        /// reported any
+        progress: Option<u8>,
+    },
+    /// The server is finished loading and is ready for people to connect
+    Running {
+        /// When the server finished loading
+        since: OffsetDateTime,
+        /// Names of the players currently on the server
+        players: BTreeSet<String>,
+        /// The ID of the server process (if known)
+        pid: Option<u32>,
+    },
+    /// The server has been asked to stop and is shutting down
+    Stopping,
+}
+
+/// Information about a server process started by an `McServerManager`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    /// The ID of the server process
+    pub pid: u32,
+    /// When the process was started
+    pub started: OffsetDateTime,
+    /// The command the process runs (Java and its arguments, after any
+    /// prefix for resource limits or running as another user)
+    pub command_line: String,
+}
+
+impl McServerStatus {
+    /// Returns true if the server process is running (in any state other than
+    /// `Stopped`)
+    pub fn is_running(&self) -> bool {
+        !matches!(self, McServerStatus::Stopped)
+    }
+
+    /// Returns the names of online players if the server is `Running`
+    pub fn players(&self) -> Option<&BTreeSet<String>> {
+        match self {
+            McServerStatus::Running { players, .. } => Some(players),
+            _ => None,
+        }
+    }
+
+    /// Update the status based on a message parsed from the server's console
+    ///
+    /// `pid` is the ID of the server process, used when transitioning to
+    /// `Running`.
+    pub(crate) fn apply(&mut self, specific_msg: &ConsoleMsgSpecific, pid: Option<u32>) {
+        use McServerStatus::*;
+
+        match specific_msg {
+            ConsoleMsgSpecific::SpawnPrepareProgress { progress } => {
+                if let Starting { progress: p } = self {
+                    *p = Some(*progress);
+                }
+            }
+            ConsoleMsgSpecific::SpawnPrepareFinish { .. } => {
+                if let Starting { progress: p } = self {
+                    *p = Some(100);
+                }
+            }
+            ConsoleMsgSpecific::FinishedLoading { .. } => {
+                if let Starting { .. } = self {
+                    *self = Running {
+                        since: OffsetDateTime::now_utc(),
+                        players: BTreeSet::new(),
+                        pid,
+                    };
+                }
+            }
+            ConsoleMsgSpecific::PlayerLogin { name, .. } => {
+                if let Running { players, .. } = self {
+                    players.insert(name.clone());
+                }
+            }
+            ConsoleMsgSpecific::PlayerLogout { name } => {
+                if let Running { players, .. } = self {
+                    players.remove(name);
+                }
+            }
+            ConsoleMsgSpecific::PlayerList {
+                players: listed, ..
+            } => {
+                if let Running { players, .. } = self {
+                    *players = listed.iter().map(|p| p.name.clone()).collect();
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Tests for running pre-start and post-stop commands
+
+use std::{path::Path, time::Duration};
+
+use crate::hooks::{Hook, HookError};
+
+fn hook(command: &str, timeout: Duration) -> Hook {
+    Hook {
+        command: command.into(),
+        timeout,
+    }
+}
+
+#[tokio::test]
+async fn runs_in_dir() {
+    let dir = std::env::temp_dir();
+    let marker = format!("mc-server-wrapper-hook-test-{}", std::process::id());
+
+    hook(&format!("touch {}", marker), Duration::from_secs(5))
+        .run(&dir)
+        .await
+        .unwrap();
+    assert!(dir.join(&marker).exists());
+    std::fs::remove_file(dir.join(&marker)).unwrap();
+}
+
+#[tokio::test]
+async fn failure() {
+    assert!(matches!(
+        hook("exit 3", Duration::from_secs(5))
+            .run(Path::new("."))
+            .await,
+        Err(HookError::Failed { .. })
+    ));
+}
+
+#[tokio::test]
+async fn timeout() {
+    assert!(matches!(
+        hook("sleep 5", Duration::from_millis(100))
+            .run(Path::new("."))
+            .await,
+        Err(HookError::TimedOut { .. })
+    ));
+}
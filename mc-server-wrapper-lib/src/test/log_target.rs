@@ -0,0 +1,13 @@
+//! Tests for the log target used for console output
+
+use crate::console_log_target;
+
+#[test]
+fn unlabeled_instance() {
+    assert_eq!(console_log_target(None), "mc");
+}
+
+#[test]
+fn labeled_instance() {
+    assert_eq!(console_log_target(Some("survival")), "mc::survival");
+}
@@ -1 +1,12 @@
+#[cfg(unix)]
+mod hooks;
+mod log_target;
 mod parse;
+mod ports;
+mod rcon;
+mod replay;
+mod resources;
+mod run_as;
+mod shell_quote;
+mod shutdown_reason;
+mod status;
@@ -0,0 +1,135 @@
+//! Tests that malformed console output is rejected rather than panicking
+
+use crate::parse::{ConsoleMsg, ConsoleMsgSpecific, MessagePatterns};
+
+/// Lines that each parse into a `ConsoleMsgSpecific`
+const LINES: &[&str] = &[
+    "[23:11:12] [User Authenticator #1/INFO]: UUID of player Cldfire is \
+        361e5fb3-dbce-4f91-86b2-43423a4888d5",
+    "[23:12:39] [Server thread/INFO]: <Cldfire> hi!",
+    "[19:19:48] [Server thread/INFO]: [Not Secure] <despiuvas> hello world! :)",
+    "[23:11:12] [Server thread/INFO]: Cldfire[/127.0.0.1:56538] logged in with entity id 97 \
+        at (-2.5, 63.0, 256.5)",
+    "[23:11:12] [Server thread/INFO]: Cldfire[/127.0.0.1:56538] logged in with entity id 97 \
+        at ([world]8185.897723692287, 65.0, -330.1145592972985)",
+    "[23:10:35] [Server thread/INFO]: Preparing spawn area: 44%",
+    "[23:10:35] [Server thread/INFO]: Time elapsed: 3292 ms",
+    "[19:10:21] [Server thread/INFO]: Cldfire lost connection: Disconnected",
+    "[19:10:21] [Server thread/INFO]: Cldfire left the game",
+    "[19:10:21] [Server thread/INFO]: Cldfire whispers to Notch: hi",
+    "[19:10:21] [Server thread/INFO]: [Cldfire -> Notch] hi",
+    "[21:57:50] [Server thread/INFO]: Done (7.410s)! For help, type \"help\"",
+    "[21:58:10] [Server thread/INFO]: There are 1 of a max of 20 players online: \
+        Cldfire (361e5fb3-dbce-4f91-86b2-43423a4888d5)",
+];
+
+/// Characters that are significant to the parser, plus some multi-byte ones
+const ALPHABET: &[char] = &[
+    '[', ']', '/', ':', ' ', '<', '>', '(', ')', ',', '.', '%', 's', 'm', '0', '9', '-', 'é', '✓',
+    '\n',
+];
+
+/// A small deterministic PRNG (xorshift) so failures are reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 as usize
+    }
+}
+
+fn parse(line: &str) {
+    let patterns = MessagePatterns {
+        player_logout: vec!["{name} a quitté la partie".into()],
+        player_lost_connection: vec!["{name} a perdu la connexion : {reason}".into()],
+        player_list: vec!["Il y a {online} joueurs sur {max} : {players}".into()],
+        player_msg: vec!["[{prefix}] {name}: {msg}".into()],
+        player_whisper: vec!["{from} » {to}: {msg}".into()],
+    };
+
+    if let Some(console_msg) = ConsoleMsg::try_parse_from(line) {
+        ConsoleMsgSpecific::try_parse_with(&console_msg, &patterns);
+    }
+}
+
+#[test]
+fn truncated_lines() {
+    for line in LINES {
+        for (idx, _) in line.char_indices() {
+            parse(&line[..idx]);
+            parse(&line[idx..]);
+        }
+    }
+}
+
+#[test]
+fn mutated_lines() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for line in LINES {
+        for _ in 0..2000 {
+            let mut chars: Vec<char> = line.chars().collect();
+            for _ in 0..1 + rng.next() % 4 {
+                let idx = rng.next() % chars.len();
+                let c = ALPHABET[rng.next() % ALPHABET.len()];
+                match rng.next() % 3 {
+                    0 => chars[idx] = c,
+                    1 => chars.insert(idx, c),
+                    _ => {
+                        chars.remove(idx);
+                    }
+                }
+            }
+
+            parse(&chars.into_iter().collect::<String>());
+        }
+    }
+}
+
+#[test]
+fn random_lines() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..20_000 {
+        let len = rng.next() % 64;
+        let line: String = (0..len)
+            .map(|_| ALPHABET[rng.next() % ALPHABET.len()])
+            .collect();
+
+        parse(&line);
+        parse(&format!("[23:10:30] [Server thread/INFO]: {}", line));
+    }
+}
+
+#[test]
+fn malformed_specific_msgs() {
+    for msg in &[
+        "Cldfire[/127.0.0.1:56538] logged in with entity id x at (1, 2, 3)",
+        "Cldfire[/127.0.0.1:56538] logged in with entity id 97 at (1, 2)",
+        "Preparing spawn area: lots",
+        "Done (soon)!",
+        "<Cldfire>",
+    ] {
+        let console_msg =
+            ConsoleMsg::try_parse_from(&format!("[23:10:30] [Server thread/INFO]: {}", msg))
+                .unwrap();
+        let specific_msg = ConsoleMsgSpecific::try_parse_from(&console_msg);
+        assert!(
+            !matches!(specific_msg, Some(ConsoleMsgSpecific::PlayerLogin { .. }))
+                && !matches!(
+                    specific_msg,
+                    Some(ConsoleMsgSpecific::SpawnPrepareProgress { .. })
+                )
+                && !matches!(
+                    specific_msg,
+                    Some(ConsoleMsgSpecific::FinishedLoading { .. })
+                ),
+            "{:?} parsed as {:?}",
+            msg,
+            specific_msg
+        );
+    }
+}
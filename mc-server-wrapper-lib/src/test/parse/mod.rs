@@ -1,2 +1,4 @@
+mod malformed;
+mod patterns;
 mod spigot;
 mod vanilla;
@@ -0,0 +1,146 @@
+//! Tests for parsing console output in other languages with `MessagePatterns`
+
+use crate::parse::{ConsoleMsg, ConsoleMsgSpecific, ListedPlayer, MessagePatterns};
+
+fn french() -> MessagePatterns {
+    MessagePatterns {
+        player_logout: vec!["{name} a quitté la partie".into()],
+        player_lost_connection: vec!["{name} a perdu la connexion : {reason}".into()],
+        player_list: vec![
+            "Il y a {online} joueurs connectés sur un maximum de {max} : {players}".into(),
+        ],
+        player_msg: vec![],
+        player_whisper: vec![],
+    }
+}
+
+fn parse(msg: &str, patterns: &MessagePatterns) -> Option<ConsoleMsgSpecific> {
+    let console_msg = ConsoleMsg::try_parse_from(msg).unwrap();
+    ConsoleMsgSpecific::try_parse_with(&console_msg, patterns)
+}
+
+#[test]
+fn player_logout() {
+    let msg = "[23:12:39] [Server thread/INFO]: Cldfire a quitté la partie";
+    assert_eq!(
+        parse(msg, &french()).unwrap(),
+        ConsoleMsgSpecific::PlayerLogout {
+            name: "Cldfire".into()
+        }
+    );
+    assert!(parse(msg, &MessagePatterns::default()).is_none());
+}
+
+#[test]
+fn player_lost_connection() {
+    let msg = "[23:12:39] [Server thread/INFO]: Cldfire a perdu la connexion : Déconnecté";
+    assert_eq!(
+        parse(msg, &french()).unwrap(),
+        ConsoleMsgSpecific::PlayerLostConnection {
+            name: "Cldfire".into(),
+            reason: "Déconnecté".into()
+        }
+    );
+}
+
+#[test]
+fn player_list() {
+    let msg = "[23:12:39] [Server thread/INFO]: Il y a 2 joueurs connectés sur un maximum de 20 : \
+        Cldfire (361e5fb3-dbce-4f91-86b2-43423a4888d5), Notch";
+    assert_eq!(
+        parse(msg, &french()).unwrap(),
+        ConsoleMsgSpecific::PlayerList {
+            online: 2,
+            max: 20,
+            players: vec![
+                ListedPlayer {
+                    name: "Cldfire".into(),
+                    uuid: Some("361e5fb3-dbce-4f91-86b2-43423a4888d5".into())
+                },
+                ListedPlayer {
+                    name: "Notch".into(),
+                    uuid: None
+                }
+            ]
+        }
+    );
+}
+
+#[test]
+fn english_still_parsed() {
+    let msg = "[23:12:39] [Server thread/INFO]: Cldfire left the game";
+    assert_eq!(
+        parse(msg, &french()).unwrap(),
+        ConsoleMsgSpecific::PlayerLogout {
+            name: "Cldfire".into()
+        }
+    );
+}
+
+#[test]
+fn partial_match() {
+    // Extra text after the template shouldn't match
+    let msg = "[23:12:39] [Server thread/INFO]: Cldfire a quitté la partie (AFK)";
+    assert!(parse(msg, &french()).is_none());
+    // Neither should an empty placeholder
+    let msg = "[23:12:39] [Server thread/INFO]:  a quitté la partie";
+    assert!(parse(msg, &french()).is_none());
+}
+
+#[test]
+fn chat_format() {
+    let patterns = MessagePatterns {
+        player_msg: vec!["[{prefix}] {name}: {msg}".into(), "{name} » {msg}".into()],
+        ..MessagePatterns::default()
+    };
+
+    let msg = "[23:12:39] [Async Chat Thread - #2/INFO]: [Admin] Cldfire: I left the game";
+    assert_eq!(
+        parse(msg, &patterns).unwrap(),
+        ConsoleMsgSpecific::PlayerMsg {
+            name: "Cldfire".into(),
+            msg: "I left the game".into()
+        }
+    );
+
+    let msg = "[23:12:39] [Async Chat Thread - #2/INFO]: Cldfire » hi: there";
+    assert_eq!(
+        parse(msg, &patterns).unwrap(),
+        ConsoleMsgSpecific::PlayerMsg {
+            name: "Cldfire".into(),
+            msg: "hi: there".into()
+        }
+    );
+}
+
+#[test]
+fn loose_chat_format() {
+    let patterns = MessagePatterns {
+        player_msg: vec!["{name}: {msg}".into()],
+        ..MessagePatterns::default()
+    };
+
+    // Names can't contain spaces, so other output isn't mistaken for chat
+    let msg = "[23:12:39] [Server thread/INFO]: Preparing level: world";
+    assert!(parse(msg, &patterns).is_none());
+    let msg = "[23:12:39] [Server thread/WARN]: Cldfire: moved too quickly!";
+    assert!(parse(msg, &patterns).is_none());
+}
+
+#[test]
+fn whisper_format() {
+    let patterns = MessagePatterns {
+        player_whisper: vec!["[PM] {from} » {to}: {msg}".into()],
+        ..MessagePatterns::default()
+    };
+
+    let msg = "[23:12:39] [Server thread/INFO]: [PM] Cldfire » Notch: hi";
+    assert_eq!(
+        parse(msg, &patterns).unwrap(),
+        ConsoleMsgSpecific::PlayerWhisper {
+            from: "Cldfire".into(),
+            to: "Notch".into(),
+            msg: "hi".into()
+        }
+    );
+}
@@ -1,6 +1,10 @@
 //! Tests for parsing vanilla console output
 
-use crate::parse::{ConsoleMsg, ConsoleMsgSpecific, ConsoleMsgType};
+use time::macros::datetime;
+
+use crate::parse::{
+    ConsoleMsg, ConsoleMsgSpecific, ConsoleMsgType, ListedPlayer, ModerationAction,
+};
 
 #[test]
 fn warn_msg() {
@@ -92,6 +96,52 @@ fn insecure_player_msg() {
     }
 }
 
+#[test]
+fn player_msg_with_angle_brackets() {
+    let msg = "[23:12:39] [Server thread/INFO]: <Cldfire> 2 > 1 <3";
+    let specific_msg =
+        ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap()).unwrap();
+
+    assert_eq!(
+        specific_msg,
+        ConsoleMsgSpecific::PlayerMsg {
+            name: "Cldfire".into(),
+            msg: "2 > 1 <3".into()
+        }
+    );
+}
+
+#[test]
+fn prefixed_player_msg() {
+    for msg in &[
+        "[23:12:39] [Server thread/INFO]: [Admin] <Cldfire> hi!",
+        "[23:12:39] [Server thread/INFO]: [Not Secure] [Admin] <Cldfire> hi!",
+        "[23:12:39] [Server thread/INFO]: <[Admin] Cldfire> hi!",
+        "[23:12:39] [Server thread/INFO]: <[>_<] Cldfire> hi!",
+    ] {
+        let specific_msg =
+            ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap());
+
+        assert_eq!(
+            specific_msg,
+            Some(ConsoleMsgSpecific::PlayerMsg {
+                name: "Cldfire".into(),
+                msg: "hi!".into()
+            }),
+            "{}",
+            msg
+        );
+    }
+}
+
+#[test]
+fn say_not_player_msg() {
+    let msg = "[23:12:39] [Server thread/INFO]: [Server] Restarting soon";
+    let console_msg = ConsoleMsg::try_parse_from(msg).unwrap();
+
+    assert!(ConsoleMsgSpecific::try_parse_from(&console_msg).is_none());
+}
+
 #[test]
 fn player_login() {
     let msg = "[23:11:12] [Server thread/INFO]: Cldfire[/127.0.0.1:56538] logged in with entity \
@@ -203,3 +253,263 @@ fn server_finished_loading() {
         _ => unreachable!(),
     }
 }
+
+#[test]
+fn overloaded() {
+    let msg = "[21:58:10] [Server thread/WARN]: Can't keep up! Is the server overloaded? \
+        Running 2034ms or 40 ticks behind";
+    let specific_msg =
+        ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap());
+
+    assert_eq!(
+        specific_msg,
+        Some(ConsoleMsgSpecific::Overloaded {
+            behind_ms: 2034,
+            ticks: 40
+        })
+    );
+}
+
+#[test]
+fn player_list_uuids() {
+    let msg = "[21:58:10] [Server thread/INFO]: There are 2 of a max of 20 players online: \
+        Cldfire (5f8eb73b-25be-4c5a-a50f-d27d65e30ca0), Notch (069a79f4-44e9-4726-a5be-fca90e38aaf5)";
+    let specific_msg =
+        ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap()).unwrap();
+
+    match specific_msg {
+        ConsoleMsgSpecific::PlayerList {
+            online,
+            max,
+            players,
+        } => {
+            assert_eq!(online, 2);
+            assert_eq!(max, 20);
+            assert_eq!(
+                players,
+                vec![
+                    ListedPlayer {
+                        name: "Cldfire".into(),
+                        uuid: Some("5f8eb73b-25be-4c5a-a50f-d27d65e30ca0".into())
+                    },
+                    ListedPlayer {
+                        name: "Notch".into(),
+                        uuid: Some("069a79f4-44e9-4726-a5be-fca90e38aaf5".into())
+                    },
+                ]
+            );
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn player_list_names() {
+    let msg = "[21:58:10] [Server thread/INFO]: There are 3 of a max of 20 players online: a, b, c";
+    let specific_msg =
+        ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap()).unwrap();
+
+    match specific_msg {
+        ConsoleMsgSpecific::PlayerList {
+            online,
+            max,
+            players,
+        } => {
+            assert_eq!(online, 3);
+            assert_eq!(max, 20);
+            let names: Vec<_> = players.iter().map(|p| p.name.as_str()).collect();
+            assert_eq!(names, ["a", "b", "c"]);
+            assert!(players.iter().all(|p| p.uuid.is_none()));
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn player_list_empty() {
+    let msg = "[21:58:10] [Server thread/INFO]: There are 0 of a max of 20 players online: ";
+    let specific_msg =
+        ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap()).unwrap();
+
+    match specific_msg {
+        ConsoleMsgSpecific::PlayerList {
+            online, players, ..
+        } => {
+            assert_eq!(online, 0);
+            assert!(players.is_empty());
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn millisecond_timestamp() {
+    let msg = "[23:10:31.042] [Server thread/INFO]: Starting Minecraft server on *:25565";
+    let console_msg = ConsoleMsg::try_parse_from(msg).unwrap();
+
+    assert_eq!(console_msg.timestamp.second(), 31);
+    assert_eq!(console_msg.timestamp.millisecond(), 42);
+    assert_eq!(console_msg.msg, "Starting Minecraft server on *:25565");
+}
+
+#[test]
+fn invalid_timestamp() {
+    let msg = "[25:10:31] [Server thread/INFO]: Starting Minecraft server on *:25565";
+    assert!(ConsoleMsg::try_parse_from(msg).is_none());
+}
+
+#[test]
+fn date_inferred_across_midnight() {
+    let msg =
+        "[23:59:58] [Server thread/INFO]: Saving chunks for level 'world'/minecraft:overworld";
+
+    // Read just after midnight, so it was printed the day before
+    let console_msg = ConsoleMsg::try_parse_at(msg, datetime!(2023-10-14 00:00:01 UTC)).unwrap();
+    assert_eq!(console_msg.timestamp, datetime!(2023-10-13 23:59:58 UTC));
+
+    // Read a bit later the same day
+    let console_msg = ConsoleMsg::try_parse_at(msg, datetime!(2023-10-13 23:59:59 UTC)).unwrap();
+    assert_eq!(console_msg.timestamp, datetime!(2023-10-13 23:59:58 UTC));
+
+    // The server's clock is slightly ahead of ours
+    let msg =
+        "[00:00:01] [Server thread/INFO]: Saving chunks for level 'world'/minecraft:overworld";
+    let console_msg = ConsoleMsg::try_parse_at(msg, datetime!(2023-10-13 23:59:59 UTC)).unwrap();
+    assert_eq!(console_msg.timestamp, datetime!(2023-10-14 00:00:01 UTC));
+}
+
+#[test]
+fn server_version() {
+    let msg = "[21:57:43] [Server thread/INFO]: Starting minecraft server version 1.21.1";
+    let specific_msg =
+        ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap()).unwrap();
+
+    assert_eq!(
+        specific_msg,
+        ConsoleMsgSpecific::ServerVersion {
+            version: "1.21.1".into()
+        }
+    );
+}
+
+#[test]
+fn world_seed() {
+    let msg = "[21:58:10] [Server thread/INFO]: Seed: [-4172144997902289642]";
+    let specific_msg =
+        ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap()).unwrap();
+
+    assert_eq!(
+        specific_msg,
+        ConsoleMsgSpecific::WorldSeed {
+            seed: -4172144997902289642
+        }
+    );
+}
+
+#[test]
+fn difficulty() {
+    for msg in &[
+        "[21:58:10] [Server thread/INFO]: The difficulty has been set to Hard",
+        "[21:58:10] [Server thread/INFO]: The difficulty is Hard",
+        "[21:58:10] [Server thread/INFO]: The difficulty did not change; it is already set to Hard",
+    ] {
+        let specific_msg =
+            ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap());
+
+        assert_eq!(
+            specific_msg,
+            Some(ConsoleMsgSpecific::Difficulty {
+                difficulty: "Hard".into()
+            }),
+            "{}",
+            msg
+        );
+    }
+}
+
+#[test]
+fn moderation() {
+    let cases = [
+        (
+            "[21:58:10] [Server thread/INFO]: Kicked Steve: Kicked by an operator",
+            ModerationAction::Kick,
+            Some("Kicked by an operator"),
+            None,
+        ),
+        (
+            "[21:58:10] [Server thread/INFO]: [Cldfire: Banned Steve: griefing: again]",
+            ModerationAction::Ban,
+            Some("griefing: again"),
+            Some("Cldfire"),
+        ),
+        (
+            "[21:58:10] [Server thread/INFO]: Unbanned Steve",
+            ModerationAction::Pardon,
+            None,
+            None,
+        ),
+    ];
+    for (msg, action, reason, by) in &cases {
+        let specific_msg =
+            ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap());
+
+        assert_eq!(
+            specific_msg,
+            Some(ConsoleMsgSpecific::Moderation {
+                action: *action,
+                name: "Steve".into(),
+                reason: reason.map(Into::into),
+                by: by.map(Into::into),
+            }),
+            "{}",
+            msg
+        );
+    }
+}
+
+#[test]
+fn ip_bans_ignored() {
+    for msg in &[
+        "[21:58:10] [Server thread/INFO]: Banned IP 127.0.0.1: Banned by an operator.",
+        "[21:58:10] [Server thread/INFO]: Unbanned IP 127.0.0.1",
+    ] {
+        let specific_msg =
+            ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap());
+
+        assert_eq!(specific_msg, None, "{}", msg);
+    }
+}
+
+#[test]
+fn player_whisper() {
+    for msg in &[
+        "[23:12:39] [Server thread/INFO]: Cldfire whispers to Notch: hi: there",
+        "[23:12:39] [Server thread/INFO]: [Cldfire -> Notch] hi: there",
+    ] {
+        let specific_msg =
+            ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap());
+
+        assert_eq!(
+            specific_msg,
+            Some(ConsoleMsgSpecific::PlayerWhisper {
+                from: "Cldfire".into(),
+                to: "Notch".into(),
+                msg: "hi: there".into()
+            }),
+            "{}",
+            msg
+        );
+    }
+}
+
+#[test]
+fn whisper_about_leaving() {
+    let msg = "[23:12:39] [Server thread/INFO]: Cldfire whispers to Notch: I left the game";
+    let specific_msg =
+        ConsoleMsgSpecific::try_parse_from(&ConsoleMsg::try_parse_from(msg).unwrap()).unwrap();
+
+    assert!(matches!(
+        specific_msg,
+        ConsoleMsgSpecific::PlayerWhisper { .. }
+    ));
+}
@@ -0,0 +1,76 @@
+//! Tests for detecting ports the server needs that are already in use
+
+use std::{
+    fs,
+    net::{SocketAddr, TcpListener},
+};
+
+use crate::ports::{check, server_ports, Protocol};
+
+#[test]
+fn default_ports() {
+    let ports = server_ports("motd=A Minecraft Server\n");
+
+    assert_eq!(ports.len(), 1);
+    assert_eq!(ports[0].property, "server-port");
+    assert_eq!(ports[0].address, "0.0.0.0:25565".parse().unwrap());
+}
+
+#[test]
+fn enabled_ports() {
+    let ports = server_ports(
+        "server-ip=127.0.0.1\nserver-port=25600\nenable-rcon=true\n\
+        enable-query=true\nquery.port=25601\n",
+    );
+
+    let ports: Vec<_> = ports
+        .iter()
+        .map(|p| (p.property, p.address, p.protocol))
+        .collect();
+    assert_eq!(
+        ports,
+        [
+            (
+                "server-port",
+                "127.0.0.1:25600".parse::<SocketAddr>().unwrap(),
+                Protocol::Tcp
+            ),
+            ("rcon.port", "0.0.0.0:25575".parse().unwrap(), Protocol::Tcp),
+            (
+                "query.port",
+                "127.0.0.1:25601".parse().unwrap(),
+                Protocol::Udp
+            ),
+        ]
+    );
+}
+
+#[test]
+fn port_in_use() {
+    let dir = std::env::temp_dir().join(format!("mcsw-lib-test-ports-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    fs::write(
+        dir.join("server.properties"),
+        format!("server-ip=127.0.0.1\nserver-port={}\n", port),
+    )
+    .unwrap();
+
+    let in_use = check(&dir);
+    drop(listener);
+    let free = check(&dir);
+    let _ = fs::remove_dir_all(&dir);
+
+    let in_use = in_use.unwrap_err();
+    assert_eq!(in_use.property, "server-port");
+    assert_eq!(in_use.port, port);
+    #[cfg(target_os = "linux")]
+    assert_eq!(in_use.pid, Some(std::process::id()));
+    assert!(free.is_ok());
+}
+
+#[test]
+fn no_properties_yet() {
+    assert!(check(&std::env::temp_dir().join("mcsw-lib-test-no-such-server")).is_ok());
+}
@@ -0,0 +1,74 @@
+//! Tests for the RCON client
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::rcon::{decode, encode, RconClient, RconError};
+
+#[test]
+fn round_trip() {
+    let packet = encode(7, 2, "list uuids");
+
+    assert_eq!(&packet[..4], &20i32.to_le_bytes());
+    assert_eq!(decode(&packet[4..]), Some((7, "list uuids".into())));
+}
+
+#[test]
+fn too_short() {
+    assert_eq!(decode(&[0; 9]), None);
+}
+
+/// Reads a packet, returning its ID, type, and body
+async fn read_packet(stream: &mut TcpStream) -> (i32, i32, String) {
+    let len = stream.read_i32_le().await.unwrap();
+    let mut buf = vec![0; len as usize];
+    stream.read_exact(&mut buf).await.unwrap();
+
+    let (id, body) = decode(&buf).unwrap();
+    let kind = i32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]);
+    (id, kind, body)
+}
+
+/// Runs a fake RCON server that accepts `password` and answers one command
+async fn fake_server(password: &'static str) -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let (id, kind, body) = read_packet(&mut stream).await;
+        assert_eq!(kind, 3);
+        let response_id = if body == password { id } else { -1 };
+        stream.write_all(&encode(response_id, 2, "")).await.unwrap();
+
+        let (id, kind, body) = read_packet(&mut stream).await;
+        assert_eq!(kind, 2);
+        stream
+            .write_all(&encode(id, 0, &format!("ran {}", body)))
+            .await
+            .unwrap();
+    });
+
+    address
+}
+
+#[tokio::test]
+async fn command() {
+    let address = fake_server("hunter2").await;
+    let mut client = RconClient::connect(address, "hunter2").await.unwrap();
+
+    assert_eq!(client.command("list").await.unwrap(), "ran list");
+}
+
+#[tokio::test]
+async fn wrong_password() {
+    let address = fake_server("hunter2").await;
+
+    assert!(matches!(
+        RconClient::connect(address, "password").await,
+        Err(RconError::AuthFailed)
+    ));
+}
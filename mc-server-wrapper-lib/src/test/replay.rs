@@ -0,0 +1,41 @@
+//! Tests for pacing replayed logs
+
+use std::time::Duration;
+
+use time::macros::time;
+
+use crate::replay::pause;
+
+#[test]
+fn real_time() {
+    assert_eq!(
+        pause(time!(12:00:00), time!(12:00:02), 1.0),
+        Duration::from_secs(2)
+    );
+    assert_eq!(pause(time!(12:00:00), time!(12:00:00), 1.0), Duration::ZERO);
+}
+
+#[test]
+fn accelerated() {
+    assert_eq!(
+        pause(time!(12:00:00), time!(12:00:04), 4.0),
+        Duration::from_secs(1)
+    );
+    assert_eq!(pause(time!(12:00:00), time!(12:00:04), 0.0), Duration::ZERO);
+}
+
+#[test]
+fn long_gaps_are_capped() {
+    assert_eq!(
+        pause(time!(12:00:00), time!(13:00:00), 1.0),
+        Duration::from_secs(5)
+    );
+}
+
+#[test]
+fn past_midnight() {
+    assert_eq!(
+        pause(time!(23:59:59), time!(00:00:01), 1.0),
+        Duration::from_secs(2)
+    );
+}
@@ -0,0 +1,58 @@
+//! Tests for applying resource limits to the server process
+
+use crate::resources::{CgroupLimits, IoPriority, ResourceLimits, ResourceLimitsError};
+
+#[test]
+fn empty_prefix() {
+    assert!(ResourceLimits::default().command_prefix().is_empty());
+}
+
+#[test]
+fn full_prefix() {
+    let limits = ResourceLimits {
+        nice: Some(10),
+        ionice: Some(IoPriority::BestEffort(4)),
+        cpu_affinity: Some(vec![0, 2, 3]),
+        cgroup: None,
+    };
+
+    assert_eq!(
+        limits.command_prefix().join(" "),
+        "nice -n 10 ionice -c 2 -n 4 taskset -c 0,2,3"
+    );
+}
+
+#[test]
+fn idle_ionice_has_no_level() {
+    let limits = ResourceLimits {
+        ionice: Some(IoPriority::Idle),
+        ..Default::default()
+    };
+
+    assert_eq!(limits.command_prefix().join(" "), "ionice -c 3");
+}
+
+#[test]
+fn nice_out_of_range() {
+    let limits = ResourceLimits {
+        nice: Some(20),
+        ..Default::default()
+    };
+
+    assert!(matches!(
+        limits.validate(),
+        Err(ResourceLimitsError::NiceOutOfRange(20))
+    ));
+}
+
+#[test]
+fn cgroup_values() {
+    let cgroup = CgroupLimits {
+        name: "mc-server-wrapper".into(),
+        memory_max: Some(2048),
+        cpu_max: Some(1.5),
+    };
+
+    assert_eq!(cgroup.memory_max_value().unwrap(), "2147483648");
+    assert_eq!(cgroup.cpu_max_value().unwrap(), "150000 100000");
+}
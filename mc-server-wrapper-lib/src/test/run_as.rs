@@ -0,0 +1,50 @@
+//! Tests for running the server process as a different user
+
+use crate::run_as::{mode_grants, ResolvedIds, RunAs};
+
+const IDS: ResolvedIds = ResolvedIds {
+    uid: 1000,
+    gid: 1000,
+};
+
+#[test]
+fn owner_write() {
+    assert!(mode_grants(0o755, 1000, 0, IDS, 0o2));
+    assert!(!mode_grants(0o555, 1000, 0, IDS, 0o2));
+}
+
+#[test]
+fn group_write() {
+    assert!(mode_grants(0o775, 0, 1000, IDS, 0o2));
+    assert!(!mode_grants(0o755, 0, 1000, IDS, 0o2));
+}
+
+#[test]
+fn other_read() {
+    assert!(mode_grants(0o644, 0, 0, IDS, 0o4));
+    assert!(!mode_grants(0o640, 0, 0, IDS, 0o4));
+}
+
+#[test]
+fn setpriv_prefix() {
+    let run_as = RunAs {
+        user: "minecraft".into(),
+        group: None,
+    };
+
+    assert_eq!(
+        run_as.command_prefix(IDS).join(" "),
+        "setpriv --reuid=1000 --regid=1000 --init-groups --"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn unknown_user() {
+    let run_as = RunAs {
+        user: "this-user-does-not-exist".into(),
+        group: None,
+    };
+
+    assert!(run_as.resolve().is_err());
+}
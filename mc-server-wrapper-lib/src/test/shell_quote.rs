@@ -0,0 +1,26 @@
+//! Tests for quoting words for `sh`
+
+use crate::shell_quote;
+
+#[test]
+fn plain() {
+    assert_eq!(shell_quote("/srv/minecraft"), "'/srv/minecraft'");
+}
+
+#[test]
+fn special_characters() {
+    assert_eq!(shell_quote("/srv/$HOME/`id`\\"), "'/srv/$HOME/`id`\\'");
+    assert_eq!(shell_quote("it's"), "'it'\\''s'");
+}
+
+#[cfg(unix)]
+#[test]
+fn survives_sh() {
+    let word = "a $b `c` \\d 'e' \"f\"";
+    let output = std::process::Command::new("sh")
+        .args(["-c", &format!("printf %s {}", shell_quote(word))])
+        .output()
+        .unwrap();
+
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), word);
+}
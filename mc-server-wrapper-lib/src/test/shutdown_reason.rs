@@ -0,0 +1,71 @@
+//! Tests for working out why a server stopped
+
+use crate::communication::ShutdownReason;
+
+#[test]
+fn from_output() {
+    assert_eq!(
+        ShutdownReason::from_output(
+            "Exception in thread \"Server thread\" java.lang.OutOfMemoryError: Java heap space"
+        ),
+        Some(ShutdownReason::OutOfMemory)
+    );
+    assert_eq!(
+        ShutdownReason::from_output(
+            "[14:02:11] [Server thread/WARN]: **** FAILED TO BIND TO PORT!"
+        ),
+        Some(ShutdownReason::PortInUse)
+    );
+    assert_eq!(
+        ShutdownReason::from_output(
+            "[14:02:11] [Server Watchdog/ERROR]: Considering it to be crashed, server will \
+            forcibly shutdown."
+        ),
+        Some(ShutdownReason::WatchdogTimeout)
+    );
+    assert_eq!(
+        ShutdownReason::from_output(
+            "[14:02:11] [Spigot Watchdog Thread/ERROR]: The server has stopped responding!"
+        ),
+        Some(ShutdownReason::WatchdogTimeout)
+    );
+    assert_eq!(
+        ShutdownReason::from_output("[14:02:11] [Server thread/INFO]: <Cldfire> out of memory?"),
+        None
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn from_exit_status() {
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+    // Wait statuses hold the exit code in the second byte and the signal in
+    // the first
+    let exited = |code: i32| ExitStatus::from_raw(code << 8);
+
+    assert_eq!(ShutdownReason::from_exit_status(&exited(0)), None);
+    assert_eq!(
+        ShutdownReason::from_exit_status(&exited(1)),
+        Some(ShutdownReason::Crashed {
+            code: Some(1),
+            signal: None
+        })
+    );
+    // Java exits with 128 + the signal number after running shutdown hooks
+    assert_eq!(
+        ShutdownReason::from_exit_status(&exited(143)),
+        Some(ShutdownReason::HostShutdown)
+    );
+    assert_eq!(
+        ShutdownReason::from_exit_status(&ExitStatus::from_raw(15)),
+        Some(ShutdownReason::HostShutdown)
+    );
+    assert_eq!(
+        ShutdownReason::from_exit_status(&ExitStatus::from_raw(9)),
+        Some(ShutdownReason::Crashed {
+            code: None,
+            signal: Some(9)
+        })
+    );
+}
@@ -0,0 +1,110 @@
+//! Tests for maintaining `McServerStatus` from parsed console messages
+
+use crate::{
+    parse::{ConsoleMsgSpecific, ListedPlayer},
+    status::McServerStatus,
+};
+
+fn login(name: &str) -> ConsoleMsgSpecific {
+    ConsoleMsgSpecific::PlayerLogin {
+        name: name.into(),
+        ip: "127.0.0.1:56538".into(),
+        entity_id: 97,
+        coords: (0.0, 64.0, 0.0),
+        world: None,
+    }
+}
+
+#[test]
+fn starting_progress() {
+    let mut status = McServerStatus::Starting { progress: None };
+    status.apply(
+        &ConsoleMsgSpecific::SpawnPrepareProgress { progress: 42 },
+        None,
+    );
+
+    assert_eq!(status, McServerStatus::Starting { progress: Some(42) });
+}
+
+#[test]
+fn finished_loading_is_running() {
+    let mut status = McServerStatus::Starting {
+        progress: Some(100),
+    };
+    status.apply(
+        &ConsoleMsgSpecific::FinishedLoading {
+            time_elapsed_s: 4.2,
+        },
+        Some(1234),
+    );
+
+    match status {
+        McServerStatus::Running { players, pid, .. } => {
+            assert!(players.is_empty());
+            assert_eq!(pid, Some(1234));
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn players_join_and_leave() {
+    let mut status = McServerStatus::Starting { progress: None };
+    status.apply(
+        &ConsoleMsgSpecific::FinishedLoading {
+            time_elapsed_s: 4.2,
+        },
+        None,
+    );
+    status.apply(&login("Cldfire"), None);
+    status.apply(&login("Notch"), None);
+    status.apply(
+        &ConsoleMsgSpecific::PlayerLogout {
+            name: "Notch".into(),
+        },
+        None,
+    );
+
+    let players = status.players().unwrap();
+    assert_eq!(players.len(), 1);
+    assert!(players.contains("Cldfire"));
+}
+
+#[test]
+fn stopped_ignores_events() {
+    let mut status = McServerStatus::Stopped;
+    status.apply(&login("Cldfire"), None);
+    status.apply(
+        &ConsoleMsgSpecific::SpawnPrepareProgress { progress: 42 },
+        None,
+    );
+
+    assert_eq!(status, McServerStatus::Stopped);
+}
+
+#[test]
+fn player_list_replaces_players() {
+    let mut status = McServerStatus::Starting { progress: None };
+    status.apply(
+        &ConsoleMsgSpecific::FinishedLoading {
+            time_elapsed_s: 4.2,
+        },
+        None,
+    );
+    status.apply(&login("Notch"), None);
+    status.apply(
+        &ConsoleMsgSpecific::PlayerList {
+            online: 1,
+            max: 20,
+            players: vec![ListedPlayer {
+                name: "Cldfire".into(),
+                uuid: None,
+            }],
+        },
+        None,
+    );
+
+    let players = status.players().unwrap();
+    assert_eq!(players.len(), 1);
+    assert!(players.contains("Cldfire"));
+}
@@ -0,0 +1,144 @@
+//! A harness for testing `McServerManager` end-to-end without a JVM
+//!
+//! The `fake_mc_server` binary is run in place of Java, so servers start
+//! instantly and what they print can be controlled with commands (see
+//! `src/bin/fake_mc_server.rs`).
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    process::ExitStatus,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use mc_server_wrapper_lib::{
+    communication::*, parse::ConsoleMsgSpecific, McServerConfig, McServerManager,
+};
+use tokio::sync::mpsc;
+
+/// How long to wait for an event before failing the test
+const EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Used to give each harness its own server directory
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// An `McServerManager` running a fake server in a temporary directory
+pub struct McServerHarness {
+    pub manager: Arc<McServerManager>,
+    cmd_sender: mpsc::Sender<ServerCommand>,
+    event_receiver: mpsc::Receiver<ServerEvent>,
+    dir: PathBuf,
+}
+
+impl McServerHarness {
+    /// Sets up a harness with an empty server directory, agreeing to the
+    /// EULA if `eula` is set
+    pub fn new(eula: bool) -> Self {
+        let dir = std::env::temp_dir().join(format!(
+            "mc-server-wrapper-harness-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        // The fake server doesn't read the jar, but it has to exist
+        std::fs::write(dir.join("server.jar"), "").unwrap();
+        if eula {
+            std::fs::write(dir.join("eula.txt"), "eula=true").unwrap();
+        }
+
+        let (manager, cmd_sender, event_receiver) = McServerManager::new();
+        Self {
+            manager,
+            cmd_sender,
+            event_receiver,
+            dir,
+        }
+    }
+
+    /// The server's directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// A config that runs the fake server
+    pub fn config(&self) -> McServerConfig {
+        McServerConfig::new(self.dir.join("server.jar"), 128, None, false)
+            .with_java_path(env!("CARGO_BIN_EXE_fake_mc_server"))
+    }
+
+    pub async fn send(&self, cmd: ServerCommand) {
+        self.cmd_sender.send(cmd).await.unwrap();
+    }
+
+    /// Starts the server with `config()`
+    pub async fn start(&self) {
+        self.send(ServerCommand::StartServer {
+            config: Some(self.config()),
+        })
+        .await;
+    }
+
+    /// Sends a command to the server's console
+    pub async fn command(&self, command: &str) {
+        self.send(ServerCommand::WriteCommandToStdin(command.into()))
+            .await;
+    }
+
+    /// Returns the next event from the server, panicking if there isn't one
+    /// soon
+    pub async fn next_event(&mut self) -> ServerEventKind {
+        match tokio::time::timeout(EVENT_TIMEOUT, self.event_receiver.recv()).await {
+            Ok(Some(event)) => event.kind,
+            Ok(None) => panic!("the server manager stopped"),
+            Err(_) => panic!("timed out waiting for an event from the server"),
+        }
+    }
+
+    /// Skips events until `f` returns something for one
+    pub async fn wait_for<T>(&mut self, mut f: impl FnMut(ServerEventKind) -> Option<T>) -> T {
+        loop {
+            if let Some(found) = f(self.next_event().await) {
+                return found;
+            }
+        }
+    }
+
+    /// Skips events until a console message matching `f` is parsed
+    pub async fn wait_for_msg(
+        &mut self,
+        mut f: impl FnMut(&ConsoleMsgSpecific) -> bool,
+    ) -> ConsoleMsgSpecific {
+        self.wait_for(|event| match event {
+            ServerEventKind::ConsoleEvent(_, Some(specific_msg)) if f(&specific_msg) => {
+                Some(specific_msg)
+            }
+            _ => None,
+        })
+        .await
+    }
+
+    /// Skips events until the server finishes loading
+    pub async fn wait_until_loaded(&mut self) {
+        self.wait_for_msg(|msg| matches!(msg, ConsoleMsgSpecific::FinishedLoading { .. }))
+            .await;
+    }
+
+    /// Skips events until the server stops, returning how it stopped
+    pub async fn wait_for_stop(&mut self) -> (io::Result<ExitStatus>, Option<ShutdownReason>) {
+        self.wait_for(|event| match event {
+            ServerEventKind::ServerStopped(result, reason) => Some((result, reason)),
+            _ => None,
+        })
+        .await
+    }
+}
+
+impl Drop for McServerHarness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
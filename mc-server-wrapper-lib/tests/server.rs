@@ -0,0 +1,303 @@
+//! End-to-end tests of running a server with `McServerManager`
+// Servers are started through `sh`, so the fake server only runs on Unix
+#![cfg(unix)]
+
+mod harness;
+
+use harness::McServerHarness;
+use mc_server_wrapper_lib::{
+    communication::*,
+    parse::{ConsoleMsgSpecific, ListedPlayer},
+    replay::ReplayConfig,
+    status::McServerStatus,
+};
+
+#[tokio::test]
+async fn start_and_stop() {
+    let mut harness = McServerHarness::new(true);
+    harness.start().await;
+
+    let version = harness
+        .wait_for_msg(|msg| matches!(msg, ConsoleMsgSpecific::ServerVersion { .. }))
+        .await;
+    assert_eq!(
+        version,
+        ConsoleMsgSpecific::ServerVersion {
+            version: "1.20.1".into()
+        }
+    );
+    harness.wait_until_loaded().await;
+    assert!(harness.manager.running().await);
+    assert!(matches!(
+        harness.manager.status().await,
+        McServerStatus::Running { .. }
+    ));
+
+    harness
+        .send(ServerCommand::StopServer { forever: false })
+        .await;
+    let (result, reason) = harness.wait_for_stop().await;
+    assert!(result.unwrap().success());
+    assert_eq!(reason, Some(ShutdownReason::RequestedToStop));
+    assert!(!harness.manager.running().await);
+    assert_eq!(harness.manager.status().await, McServerStatus::Stopped);
+}
+
+#[tokio::test]
+async fn pid_file() {
+    let mut harness = McServerHarness::new(true);
+    let pid_file = harness.dir().join("server.pid");
+    harness
+        .send(ServerCommand::StartServer {
+            config: Some(harness.config().with_pid_file(&pid_file)),
+        })
+        .await;
+    harness.wait_until_loaded().await;
+
+    let pid: u32 = std::fs::read_to_string(&pid_file).unwrap().parse().unwrap();
+    assert!(matches!(
+        harness.manager.status().await,
+        McServerStatus::Running { pid: Some(running), .. } if running == pid
+    ));
+
+    harness
+        .send(ServerCommand::StopServer { forever: false })
+        .await;
+    let (result, _) = harness.wait_for_stop().await;
+    assert!(result.unwrap().success());
+    assert!(!pid_file.exists());
+}
+
+#[tokio::test]
+async fn process_info() {
+    let mut harness = McServerHarness::new(true);
+    harness.start().await;
+    harness.wait_until_loaded().await;
+
+    let info = harness.manager.process_info().await.unwrap();
+    assert!(matches!(
+        harness.manager.status().await,
+        McServerStatus::Running { pid: Some(pid), .. } if pid == info.pid
+    ));
+    assert!(info.started <= time::OffsetDateTime::now_utc());
+    assert!(info.command_line.contains("fake_mc_server"));
+    assert!(info.command_line.ends_with("-jar 'server.jar' nogui"));
+
+    harness
+        .send(ServerCommand::StopServer { forever: false })
+        .await;
+    let (result, _) = harness.wait_for_stop().await;
+    assert!(result.unwrap().success());
+    assert_eq!(harness.manager.process_info().await, None);
+}
+
+#[tokio::test]
+async fn eula() {
+    let mut harness = McServerHarness::new(false);
+    harness.start().await;
+
+    harness
+        .wait_for_msg(|msg| *msg == ConsoleMsgSpecific::MustAcceptEula)
+        .await;
+    let (_, reason) = harness.wait_for_stop().await;
+    assert_eq!(reason, Some(ShutdownReason::EulaNotAccepted));
+
+    harness.send(ServerCommand::AgreeToEula).await;
+    let result = harness
+        .wait_for(|event| match event {
+            ServerEventKind::AgreeToEulaResult(result) => Some(result),
+            _ => None,
+        })
+        .await;
+    assert!(result.is_ok());
+    assert_eq!(
+        std::fs::read_to_string(harness.dir().join("eula.txt")).unwrap(),
+        "eula=true"
+    );
+
+    // The previous config is used when none is given
+    harness
+        .send(ServerCommand::StartServer { config: None })
+        .await;
+    harness.wait_until_loaded().await;
+}
+
+#[tokio::test]
+async fn restart_after_crash() {
+    let mut harness = McServerHarness::new(true);
+    harness.start().await;
+    harness.wait_until_loaded().await;
+
+    harness.command("crash").await;
+    let (result, reason) = harness.wait_for_stop().await;
+    assert!(!result.unwrap().success());
+    assert_eq!(
+        reason,
+        Some(ShutdownReason::Crashed {
+            code: Some(1),
+            signal: None
+        })
+    );
+    assert_eq!(harness.manager.status().await, McServerStatus::Stopped);
+
+    harness
+        .send(ServerCommand::StartServer { config: None })
+        .await;
+    harness.wait_until_loaded().await;
+    assert!(harness.manager.running().await);
+}
+
+#[tokio::test]
+async fn out_of_memory() {
+    let mut harness = McServerHarness::new(true);
+    harness.start().await;
+    harness.wait_until_loaded().await;
+
+    harness.command("oom").await;
+    let (result, reason) = harness.wait_for_stop().await;
+    assert!(!result.unwrap().success());
+    assert_eq!(reason, Some(ShutdownReason::OutOfMemory));
+}
+
+#[tokio::test]
+async fn host_shutdown() {
+    let mut harness = McServerHarness::new(true);
+    harness.start().await;
+    harness.wait_until_loaded().await;
+
+    let pid = match harness.manager.status().await {
+        McServerStatus::Running { pid: Some(pid), .. } => pid,
+        status => panic!("unexpected status {:?}", status),
+    };
+    let killed = std::process::Command::new("kill")
+        .args(["-TERM", &pid.to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+
+    let (_, reason) = harness.wait_for_stop().await;
+    assert_eq!(reason, Some(ShutdownReason::HostShutdown));
+}
+
+#[tokio::test]
+async fn start_without_config() {
+    let mut harness = McServerHarness::new(true);
+    harness
+        .send(ServerCommand::StartServer { config: None })
+        .await;
+
+    let result = harness
+        .wait_for(|event| match event {
+            ServerEventKind::StartServerResult(result) => Some(result),
+            _ => None,
+        })
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn players_and_chat() {
+    let mut harness = McServerHarness::new(true);
+    harness.start().await;
+    harness.wait_until_loaded().await;
+
+    harness.command("join Cldfire").await;
+    harness
+        .wait_for_msg(
+            |msg| matches!(msg, ConsoleMsgSpecific::PlayerLogin { name, .. } if name == "Cldfire"),
+        )
+        .await;
+
+    harness.command("chat Cldfire hello there").await;
+    let chat = harness
+        .wait_for_msg(|msg| matches!(msg, ConsoleMsgSpecific::PlayerMsg { .. }))
+        .await;
+    assert_eq!(
+        chat,
+        ConsoleMsgSpecific::PlayerMsg {
+            name: "Cldfire".into(),
+            msg: "hello there".into()
+        }
+    );
+
+    harness.command("list").await;
+    let list = harness
+        .wait_for_msg(|msg| matches!(msg, ConsoleMsgSpecific::PlayerList { .. }))
+        .await;
+    assert_eq!(
+        list,
+        ConsoleMsgSpecific::PlayerList {
+            online: 1,
+            max: 20,
+            players: vec![ListedPlayer {
+                name: "Cldfire".into(),
+                uuid: None
+            }],
+        }
+    );
+    let players = harness.manager.status().await.players().cloned().unwrap();
+    assert!(players.contains("Cldfire"));
+
+    harness.command("leave Cldfire").await;
+    harness
+        .wait_for_msg(|msg| matches!(msg, ConsoleMsgSpecific::PlayerLogout { .. }))
+        .await;
+
+    // Commands the server doesn't know are echoed back
+    harness.command("help").await;
+    let line = harness
+        .wait_for(|event| match event {
+            ServerEventKind::ConsoleEvent(console_msg, None) => Some(console_msg.msg),
+            _ => None,
+        })
+        .await;
+    assert_eq!(line, "Unknown command: help");
+}
+
+#[tokio::test]
+async fn replay() {
+    let mut harness = McServerHarness::new(false);
+    let log = harness.dir().join("latest.log");
+    std::fs::write(
+        &log,
+        "[12:00:00] [Server thread/INFO]: Starting minecraft server version 1.20.1\n\
+         [12:00:01] [Server thread/INFO]: Done (1.234s)! For help, type \"help\"\n\
+         \tat some.stack.Trace\n\
+         [12:00:02] [Server thread/INFO]: Cldfire[/127.0.0.1:56538] logged in with entity id 97 at (0.5, 64.0, 0.5)\n",
+    )
+    .unwrap();
+
+    let config = harness.config().with_replay(ReplayConfig {
+        path: log,
+        speed: 0.0,
+    });
+    harness
+        .send(ServerCommand::StartServer {
+            config: Some(config),
+        })
+        .await;
+
+    harness.wait_until_loaded().await;
+    let line = harness
+        .wait_for(|event| match event {
+            ServerEventKind::StdoutLine(line) => Some(line),
+            _ => None,
+        })
+        .await;
+    assert_eq!(line, "\tat some.stack.Trace");
+    harness
+        .wait_for_msg(|msg| matches!(msg, ConsoleMsgSpecific::PlayerLogin { .. }))
+        .await;
+
+    // The replayed server keeps running until it's stopped
+    assert!(harness.manager.running().await);
+    let players = harness.manager.status().await.players().cloned().unwrap();
+    assert!(players.contains("Cldfire"));
+
+    harness
+        .send(ServerCommand::StopServer { forever: false })
+        .await;
+    let (result, reason) = harness.wait_for_stop().await;
+    assert!(result.unwrap().success());
+    assert_eq!(reason, Some(ShutdownReason::RequestedToStop));
+}
@@ -0,0 +1,376 @@
+//! Finding updates for the plugins or mods installed on the server
+//!
+//! Installed jars are identified by their SHA-1 hashes using the Modrinth API,
+//! which also reports the newest compatible version of each. Updates are
+//! downloaded into a staging folder and swapped in while the server is
+//! stopped. Jars that aren't published on Modrinth are skipped.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use tokio::fs;
+
+use crate::{
+    config::Provision,
+    provision::{server_dir, update::InstalledJar, Checksum, Flavor},
+};
+
+const MODRINTH_API_BASE: &str = "https://api.modrinth.com/v2";
+
+/// The folder (inside a plugins or mods folder) updates are staged in
+const STAGING_DIR_NAME: &str = ".mc-server-wrapper-staged";
+
+/// The file in the staging folder recording which jars replace which
+const STAGING_MANIFEST_NAME: &str = "staged.toml";
+
+/// A plugin or mod jar found on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Addon {
+    pub path: PathBuf,
+    pub sha1: String,
+}
+
+/// An available update for an installed addon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddonUpdate {
+    /// The installed jar
+    pub path: PathBuf,
+    pub name: String,
+    pub current_version: String,
+    pub latest_version: String,
+    pub url: String,
+    pub filename: String,
+    pub sha1: String,
+}
+
+impl fmt::Display for AddonUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} -> {}",
+            self.name, self.current_version, self.latest_version
+        )
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ModrinthVersion {
+    id: String,
+    name: String,
+    version_number: String,
+    files: Vec<ModrinthFile>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModrinthFile {
+    url: String,
+    filename: String,
+    primary: bool,
+    hashes: HashMap<String, String>,
+}
+
+/// Records which installed jars staged jars replace
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct StagingManifest {
+    /// Staged filename -> installed filename it replaces
+    replacements: HashMap<String, String>,
+}
+
+/// Returns the plugin and mod folders that exist in `server_dir`
+pub fn addon_dirs(server_dir: &Path) -> Vec<PathBuf> {
+    ["plugins", "mods"]
+        .iter()
+        .map(|d| server_dir.join(d))
+        .filter(|d| d.is_dir())
+        .collect()
+}
+
+/// The Modrinth loader names for addons compatible with the given flavor
+pub fn modrinth_loaders(flavor: Flavor) -> &'static [&'static str] {
+    match flavor {
+        Flavor::Vanilla => &[],
+        Flavor::Paper => &["paper", "spigot", "bukkit"],
+        Flavor::Fabric => &["fabric"],
+        Flavor::Forge => &["forge"],
+        Flavor::NeoForge => &["neoforge"],
+    }
+}
+
+/// Finds and hashes the jars in `dir`
+pub async fn scan(dir: &Path) -> Result<Vec<Addon>, anyhow::Error> {
+    let mut addons = vec![];
+    let mut entries = fs::read_dir(dir)
+        .await
+        .with_context(|| format!("Failed to read {:?}", dir))?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().map(|e| e == "jar").unwrap_or(false) && path.is_file() {
+            let bytes = fs::read(&path)
+                .await
+                .with_context(|| format!("Failed to read {:?}", path))?;
+            addons.push(Addon {
+                sha1: Sha1::digest(&bytes)
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect(),
+                path,
+            });
+        }
+    }
+
+    addons.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(addons)
+}
+
+/// Scans the plugin and mod folders of the server at `server_path` and asks
+/// Modrinth for updates to the jars found there
+///
+/// The loader and Minecraft version to find updates for are taken from the
+/// record written when the wrapper installed the server, falling back to the
+/// provisioning config.
+pub async fn find_updates(
+    client: &reqwest::Client,
+    server_path: &Path,
+    provision: Option<&Provision>,
+) -> Result<Vec<AddonUpdate>, anyhow::Error> {
+    let (flavor, game_version) = match InstalledJar::load(server_path).await? {
+        Some(installed) => (installed.flavor, installed.version),
+        None => match provision {
+            Some(provision) if provision.version != "latest" => {
+                (provision.flavor, provision.version.clone())
+            }
+            _ => {
+                return Err(anyhow!(
+                    "the server's Minecraft version is unknown; set a version in the \
+                    `minecraft.provision` config section"
+                ))
+            }
+        },
+    };
+
+    let loaders = modrinth_loaders(flavor);
+    if loaders.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut addons = vec![];
+    for dir in addon_dirs(&server_dir(server_path)) {
+        addons.extend(scan(&dir).await?);
+    }
+    if addons.is_empty() {
+        return Ok(vec![]);
+    }
+
+    check_updates(client, &addons, loaders, &game_version).await
+}
+
+/// Asks Modrinth for updates to the given addons
+pub async fn check_updates(
+    client: &reqwest::Client,
+    addons: &[Addon],
+    loaders: &[&str],
+    game_version: &str,
+) -> Result<Vec<AddonUpdate>, anyhow::Error> {
+    let hashes: Vec<_> = addons.iter().map(|a| a.sha1.as_str()).collect();
+
+    let current: HashMap<String, ModrinthVersion> = client
+        .post(format!("{}/version_files", MODRINTH_API_BASE))
+        .json(&json!({ "hashes": hashes, "algorithm": "sha1" }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let latest: HashMap<String, ModrinthVersion> = client
+        .post(format!("{}/version_files/update", MODRINTH_API_BASE))
+        .json(&json!({
+            "hashes": hashes,
+            "algorithm": "sha1",
+            "loaders": loaders,
+            "game_versions": [game_version],
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(addons
+        .iter()
+        .filter_map(|addon| {
+            let current = current.get(&addon.sha1)?;
+            let latest = latest.get(&addon.sha1)?;
+            make_update(addon, current, latest)
+        })
+        .collect())
+}
+
+/// Builds an `AddonUpdate` if `latest` is a different version than `current`
+fn make_update(
+    addon: &Addon,
+    current: &ModrinthVersion,
+    latest: &ModrinthVersion,
+) -> Option<AddonUpdate> {
+    if current.id == latest.id {
+        return None;
+    }
+
+    let file = latest
+        .files
+        .iter()
+        .find(|f| f.primary)
+        .or_else(|| latest.files.first())?;
+
+    Some(AddonUpdate {
+        path: addon.path.clone(),
+        name: latest.name.clone(),
+        current_version: current.version_number.clone(),
+        latest_version: latest.version_number.clone(),
+        url: file.url.clone(),
+        filename: file.filename.clone(),
+        sha1: file.hashes.get("sha1")?.clone(),
+    })
+}
+
+/// Downloads the given updates into the staging folders next to the jars
+/// they replace
+///
+/// The updates are applied by `apply_staged` the next time the server is
+/// stopped or started.
+pub async fn stage(client: &reqwest::Client, updates: &[AddonUpdate]) -> Result<(), anyhow::Error> {
+    for update in updates {
+        let dir = update
+            .path
+            .parent()
+            .ok_or_else(|| anyhow!("{:?} has no parent folder", update.path))?;
+        let staging_dir = dir.join(STAGING_DIR_NAME);
+        fs::create_dir_all(&staging_dir).await?;
+
+        if update.filename.contains(['/', '\\']) || update.filename.starts_with('.') {
+            return Err(anyhow!(
+                "refusing to stage unsafe file name {}",
+                update.filename
+            ));
+        }
+
+        let bytes = client
+            .get(&update.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await
+            .with_context(|| format!("Failed to download {}", update))?;
+        Checksum::Sha1(update.sha1.clone())
+            .verify(&bytes)
+            .with_context(|| format!("Failed to verify {}", update))?;
+        fs::write(staging_dir.join(&update.filename), &bytes).await?;
+
+        let manifest_path = staging_dir.join(STAGING_MANIFEST_NAME);
+        let mut manifest: StagingManifest = match fs::read_to_string(&manifest_path).await {
+            Ok(s) => toml::from_str(&s)?,
+            Err(_) => StagingManifest::default(),
+        };
+        manifest.replacements.insert(
+            update.filename.clone(),
+            update
+                .path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
+        );
+        fs::write(&manifest_path, toml::to_string(&manifest)?).await?;
+    }
+
+    Ok(())
+}
+
+/// Swaps staged updates into the plugin and mod folders in `server_dir`
+///
+/// This must only be called while the server is stopped. Returns the names of
+/// the jars that were installed.
+pub fn apply_staged(server_dir: &Path) -> Result<Vec<String>, anyhow::Error> {
+    let mut applied = vec![];
+
+    for dir in addon_dirs(server_dir) {
+        let staging_dir = dir.join(STAGING_DIR_NAME);
+        let manifest_path = staging_dir.join(STAGING_MANIFEST_NAME);
+        if !manifest_path.exists() {
+            continue;
+        }
+
+        let manifest: StagingManifest =
+            toml::from_str(&std::fs::read_to_string(&manifest_path)?)
+                .with_context(|| format!("Failed to parse {:?}", manifest_path))?;
+
+        for (staged, replaces) in manifest.replacements {
+            let old_path = dir.join(&replaces);
+            if old_path.exists() {
+                std::fs::remove_file(&old_path)
+                    .with_context(|| format!("Failed to remove {:?}", old_path))?;
+            }
+
+            std::fs::rename(staging_dir.join(&staged), dir.join(&staged))
+                .with_context(|| format!("Failed to install {}", staged))?;
+            applied.push(staged);
+        }
+
+        std::fs::remove_dir_all(&staging_dir)?;
+    }
+
+    applied.sort();
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn version(id: &str, version_number: &str) -> ModrinthVersion {
+        ModrinthVersion {
+            id: id.into(),
+            name: "Lithium".into(),
+            version_number: version_number.into(),
+            files: vec![ModrinthFile {
+                url: format!("https://cdn.modrinth.com/{}.jar", id),
+                filename: format!("lithium-{}.jar", version_number),
+                primary: true,
+                hashes: std::iter::once(("sha1".to_string(), "abcd".to_string())).collect(),
+            }],
+        }
+    }
+
+    fn addon() -> Addon {
+        Addon {
+            path: "mods/lithium-0.11.1.jar".into(),
+            sha1: "1234".into(),
+        }
+    }
+
+    #[test]
+    fn same_version_is_not_update() {
+        assert_eq!(
+            make_update(&addon(), &version("a", "0.11.1"), &version("a", "0.11.1")),
+            None
+        );
+    }
+
+    #[test]
+    fn newer_version_is_update() {
+        let update =
+            make_update(&addon(), &version("a", "0.11.1"), &version("b", "0.11.2")).unwrap();
+
+        assert_eq!(update.to_string(), "Lithium 0.11.1 -> 0.11.2");
+        assert_eq!(update.filename, "lithium-0.11.2.jar");
+        assert_eq!(update.sha1, "abcd");
+    }
+}
@@ -0,0 +1,184 @@
+//! Detecting players that are away from keyboard
+//!
+//! Players count as active when they join, chat, or (if position checks are
+//! enabled) move. Positions are found by running `data get entity <name> Pos`
+//! and reading the response with `parse_position`.
+
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+/// How far (in blocks) a player has to move to count as active
+const MIN_MOVEMENT: f64 = 1.0;
+
+#[derive(Debug)]
+struct Activity {
+    last_active: Instant,
+    position: Option<[f64; 3]>,
+    /// Whether the player has been reported as AFK since they were last active
+    flagged: bool,
+}
+
+/// Tracks when online players were last active
+#[derive(Debug)]
+pub struct AfkTracker {
+    idle_after: Duration,
+    players: BTreeMap<String, Activity>,
+}
+
+impl AfkTracker {
+    /// Players are considered AFK once they've been inactive for `idle_after`
+    pub fn new(idle_after: Duration) -> Self {
+        Self {
+            idle_after,
+            players: BTreeMap::new(),
+        }
+    }
+
+    /// Records activity from `name`
+    pub fn active(&mut self, name: &str, now: Instant) {
+        let activity = self.players.entry(name.into()).or_insert(Activity {
+            last_active: now,
+            position: None,
+            flagged: false,
+        });
+        activity.last_active = now;
+        activity.flagged = false;
+    }
+
+    /// Forgets about `name` after they leave
+    pub fn left(&mut self, name: &str) {
+        self.players.remove(name);
+    }
+
+    /// Forgets about all players
+    pub fn clear(&mut self) {
+        self.players.clear();
+    }
+
+    /// Records the position of `name`, counting it as activity if they moved
+    pub fn position(&mut self, name: &str, position: [f64; 3], now: Instant) {
+        let moved = match self.players.get(name).and_then(|a| a.position) {
+            Some(last) => distance(last, position) >= MIN_MOVEMENT,
+            // The first known position is only a starting point
+            None => false,
+        };
+        if moved || !self.players.contains_key(name) {
+            self.active(name, now);
+        }
+
+        if let Some(activity) = self.players.get_mut(name) {
+            activity.position = Some(position);
+        }
+    }
+
+    /// Returns the players that are AFK, with how long they've been inactive
+    /// for, most inactive first
+    pub fn idle_players(&self, now: Instant) -> Vec<(&str, Duration)> {
+        let mut idle: Vec<_> = self
+            .players
+            .iter()
+            .map(|(name, a)| (name.as_str(), now.saturating_duration_since(a.last_active)))
+            .filter(|(_, inactive)| *inactive >= self.idle_after)
+            .collect();
+        idle.sort_by_key(|(_, inactive)| std::cmp::Reverse(*inactive));
+        idle
+    }
+
+    /// Returns the players that have become AFK since this was last called
+    pub fn newly_idle(&mut self, now: Instant) -> Vec<String> {
+        let idle_after = self.idle_after;
+        self.players
+            .iter_mut()
+            .filter(|(_, a)| {
+                !a.flagged && now.saturating_duration_since(a.last_active) >= idle_after
+            })
+            .map(|(name, a)| {
+                a.flagged = true;
+                name.clone()
+            })
+            .collect()
+    }
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Parses the response to `data get entity <name> Pos`, like
+/// `Cldfire has the following entity data: [0.5d, 64.0d, -12.3d]`
+pub fn parse_position(msg: &str) -> Option<(&str, [f64; 3])> {
+    let (name, data) = msg.split_once(" has the following entity data: ")?;
+    let data = data.strip_prefix('[')?.strip_suffix(']')?;
+
+    let mut coords = data
+        .split(',')
+        .map(|c| c.trim().trim_end_matches('d').parse::<f64>());
+    let position = [
+        coords.next()?.ok()?,
+        coords.next()?.ok()?,
+        coords.next()?.ok()?,
+    ];
+    if coords.next().is_some() {
+        return None;
+    }
+
+    Some((name, position))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_position("Cldfire has the following entity data: [0.5d, 64.0d, -12.3d]"),
+            Some(("Cldfire", [0.5, 64.0, -12.3]))
+        );
+        assert_eq!(
+            parse_position("Cldfire has the following entity data: 20.0f"),
+            None
+        );
+    }
+
+    #[test]
+    fn idle_after_inactivity() {
+        let mut tracker = AfkTracker::new(Duration::from_secs(600));
+        let now = Instant::now();
+        tracker.active("Cldfire", now);
+        tracker.active("Notch", now + Duration::from_secs(300));
+
+        let later = now + Duration::from_secs(700);
+        assert_eq!(
+            tracker.idle_players(later),
+            vec![("Cldfire", Duration::from_secs(700))]
+        );
+        assert_eq!(tracker.newly_idle(later), vec!["Cldfire".to_string()]);
+        assert!(tracker.newly_idle(later).is_empty());
+
+        tracker.active("Cldfire", later);
+        assert!(tracker.idle_players(later).is_empty());
+    }
+
+    #[test]
+    fn movement_counts_as_activity() {
+        let mut tracker = AfkTracker::new(Duration::from_secs(600));
+        let now = Instant::now();
+        tracker.active("Cldfire", now);
+        tracker.position("Cldfire", [0.0, 64.0, 0.0], now);
+
+        // Looking around in place isn't enough
+        let later = now + Duration::from_secs(700);
+        tracker.position("Cldfire", [0.2, 64.0, 0.0], later);
+        assert_eq!(tracker.idle_players(later).len(), 1);
+
+        tracker.position("Cldfire", [5.0, 64.0, 0.0], later);
+        assert!(tracker.idle_players(later).is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+//! Expanding the command aliases defined in the `[aliases]` config table
+//!
+//! An alias is run by typing its name followed by its arguments, like
+//! `tpspawn Cldfire` for `tpspawn = "tp {player} 0 70 0"`. Commands that
+//! don't start with the name of an alias are passed through unchanged.
+
+use std::{collections::BTreeMap, fmt};
+
+use crate::config::Alias;
+
+/// The arguments given to an alias didn't match its placeholders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageError {
+    pub usage: String,
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Usage: {}", self.usage)
+    }
+}
+
+/// Expands `command` if it starts with the name of an alias, returning the
+/// server commands to run in order
+pub fn expand(aliases: &BTreeMap<String, Alias>, command: &str) -> Result<Vec<String>, UsageError> {
+    let mut words = command.split_whitespace();
+    let alias = match words.next().and_then(|name| aliases.get(name)) {
+        Some(alias) => alias,
+        None => return Ok(vec![command.to_string()]),
+    };
+    let args: Vec<_> = words.collect();
+
+    let mut names: Vec<&str> = vec![];
+    for command in alias.commands() {
+        for name in placeholders(command) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    if args.len() != names.len() {
+        let name = command.split_whitespace().next().unwrap_or_default();
+        let usage = std::iter::once(name.to_string())
+            .chain(names.iter().map(|name| format!("<{}>", name)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        return Err(UsageError { usage });
+    }
+
+    Ok(alias
+        .commands()
+        .iter()
+        .map(|command| {
+            names
+                .iter()
+                .zip(&args)
+                .fold(command.clone(), |command, (name, arg)| {
+                    command.replace(&format!("{{{}}}", name), arg)
+                })
+        })
+        .collect())
+}
+
+/// Returns the names of the `{placeholders}` in `command`
+///
+/// Only names made of letters, digits, and underscores count, so JSON text
+/// components like `{"text":"hi"}` are left alone.
+fn placeholders(command: &str) -> impl Iterator<Item = &str> {
+    command.split('{').skip(1).filter_map(|rest| {
+        let name = rest.split_once('}')?.0;
+        let valid = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        valid.then_some(name)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn aliases() -> BTreeMap<String, Alias> {
+        let mut aliases = BTreeMap::new();
+        aliases.insert("night".into(), Alias::Command("time set night".into()));
+        aliases.insert(
+            "tpspawn".into(),
+            Alias::Command("tp {player} 0 70 0".into()),
+        );
+        aliases.insert(
+            "welcome".into(),
+            Alias::Commands(vec![
+                "give {player} bread 16".into(),
+                r#"tellraw {player} {"text":"Welcome, {player}!"}"#.into(),
+            ]),
+        );
+        aliases
+    }
+
+    #[test]
+    fn expanded() {
+        let aliases = aliases();
+
+        assert_eq!(expand(&aliases, "night").unwrap(), ["time set night"]);
+        assert_eq!(
+            expand(&aliases, "tpspawn Cldfire").unwrap(),
+            ["tp Cldfire 0 70 0"]
+        );
+        assert_eq!(
+            expand(&aliases, "welcome Cldfire").unwrap(),
+            [
+                "give Cldfire bread 16",
+                r#"tellraw Cldfire {"text":"Welcome, Cldfire!"}"#
+            ]
+        );
+    }
+
+    #[test]
+    fn other_commands_unchanged() {
+        assert_eq!(
+            expand(&aliases(), "time set day").unwrap(),
+            ["time set day"]
+        );
+    }
+
+    #[test]
+    fn wrong_arguments() {
+        let aliases = aliases();
+
+        assert_eq!(
+            expand(&aliases, "tpspawn").unwrap_err().to_string(),
+            "Usage: tpspawn <player>"
+        );
+        assert_eq!(
+            expand(&aliases, "night now").unwrap_err().to_string(),
+            "Usage: night"
+        );
+    }
+}
@@ -0,0 +1,302 @@
+//! Tracking how much of the time the Minecraft server has been up
+//!
+//! Time is accounted for since the wrapper launched, and per calendar day (in
+//! the local timezone) for the daily summary.
+
+use std::fmt;
+
+use time::{Date, Duration, OffsetDateTime, Time, UtcOffset};
+
+use crate::ui::make_session_time_string;
+
+/// Uptime, downtime, and restarts since the wrapper launched
+#[derive(Debug)]
+pub struct Availability {
+    launched_at: OffsetDateTime,
+    /// The offset used to decide where days start and end
+    offset: UtcOffset,
+    /// When the server last came up, if it's up
+    up_since: Option<OffsetDateTime>,
+    /// Time up to which uptime has been added to the totals
+    accrued_until: OffsetDateTime,
+    /// Total uptime since launch, up to `accrued_until`
+    uptime: Duration,
+    starts: u32,
+    restarts: u32,
+    /// The day currently being tracked
+    day: DayTracker,
+}
+
+#[derive(Debug)]
+struct DayTracker {
+    date: Date,
+    /// When tracking started for this day (midnight, unless the wrapper
+    /// launched partway through it)
+    tracked_from: OffsetDateTime,
+    uptime: Duration,
+    restarts: u32,
+}
+
+impl DayTracker {
+    fn new(tracked_from: OffsetDateTime) -> Self {
+        Self {
+            date: tracked_from.date(),
+            tracked_from,
+            uptime: Duration::ZERO,
+            restarts: 0,
+        }
+    }
+}
+
+/// Availability for a single day
+#[derive(Debug, Clone, PartialEq)]
+pub struct DaySummary {
+    pub date: Date,
+    /// How much of the day was tracked (less than a full day if the wrapper
+    /// launched partway through it)
+    pub tracked: Duration,
+    pub uptime: Duration,
+    pub restarts: u32,
+    /// The most players that were online at once
+    pub peak_players: Option<u32>,
+}
+
+impl DaySummary {
+    /// The percentage of the tracked time the server was up
+    pub fn uptime_percent(&self) -> f64 {
+        if self.tracked <= Duration::ZERO {
+            return 0.0;
+        }
+
+        (self.uptime.as_seconds_f64() / self.tracked.as_seconds_f64() * 100.0).min(100.0)
+    }
+}
+
+impl fmt::Display for DaySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The server was up {:.1}% yesterday, {} restart{}",
+            self.uptime_percent(),
+            self.restarts,
+            if self.restarts == 1 { "" } else { "s" }
+        )?;
+        if let Some(peak) = self.peak_players {
+            write!(
+                f,
+                ", peak of {} player{}",
+                peak,
+                if peak == 1 { "" } else { "s" }
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Availability {
+    /// Starts tracking at `now`, with days split at midnight in `offset`
+    pub fn new(now: OffsetDateTime, offset: UtcOffset) -> Self {
+        let now = now.to_offset(offset);
+
+        Self {
+            launched_at: now,
+            offset,
+            up_since: None,
+            accrued_until: now,
+            uptime: Duration::ZERO,
+            starts: 0,
+            restarts: 0,
+            day: DayTracker::new(now),
+        }
+    }
+
+    /// Records that the server came up
+    pub fn server_started(&mut self, now: OffsetDateTime) {
+        self.accrue(now);
+        if self.up_since.is_some() {
+            return;
+        }
+
+        if self.starts > 0 {
+            self.restarts += 1;
+            self.day.restarts += 1;
+        }
+        self.starts += 1;
+        self.up_since = Some(now);
+    }
+
+    /// Records that the server went down
+    pub fn server_stopped(&mut self, now: OffsetDateTime) {
+        self.accrue(now);
+        self.up_since = None;
+    }
+
+    /// How long the wrapper has been running
+    pub fn wrapper_uptime(&self, now: OffsetDateTime) -> Duration {
+        now - self.launched_at
+    }
+
+    /// How long the server has been up since it last started, if it's up
+    pub fn server_uptime(&self, now: OffsetDateTime) -> Option<Duration> {
+        self.up_since.map(|up_since| now - up_since)
+    }
+
+    /// Total time the server has been up since the wrapper launched
+    pub fn total_uptime(&self, now: OffsetDateTime) -> Duration {
+        self.uptime + self.unaccrued(now)
+    }
+
+    /// Total time the server has been down since the wrapper launched
+    pub fn total_downtime(&self, now: OffsetDateTime) -> Duration {
+        self.wrapper_uptime(now) - self.total_uptime(now)
+    }
+
+    /// How many times the server has been started again after the first time
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Finishes the current day if `now` is on a later one, returning a
+    /// summary of it
+    ///
+    /// Only the most recently finished day is summarized if several have
+    /// passed.
+    pub fn roll_day(&mut self, now: OffsetDateTime) -> Option<DaySummary> {
+        let now = now.to_offset(self.offset);
+        if now.date() <= self.day.date {
+            return None;
+        }
+
+        let mut summary = None;
+        while self.day.date < now.date() {
+            let next_day = self.day.date.next_day()?;
+            let midnight = next_day
+                .with_time(Time::MIDNIGHT)
+                .assume_offset(self.offset);
+            self.accrue(midnight);
+
+            summary = Some(DaySummary {
+                date: self.day.date,
+                tracked: midnight - self.day.tracked_from,
+                uptime: self.day.uptime,
+                restarts: self.day.restarts,
+                peak_players: None,
+            });
+            self.day = DayTracker::new(midnight);
+        }
+        self.accrue(now);
+
+        summary
+    }
+
+    /// Formats the stats for the `status` command
+    pub fn describe(&self, now: OffsetDateTime) -> Vec<String> {
+        let mut lines = vec![format!(
+            "Wrapper uptime: {}",
+            make_session_time_string(self.wrapper_uptime(now))
+        )];
+        match self.server_uptime(now) {
+            Some(uptime) => lines.push(format!(
+                "Server uptime: {}",
+                make_session_time_string(uptime)
+            )),
+            None => lines.push("Server uptime: not running".into()),
+        }
+        lines.push(format!(
+            "Total downtime: {} ({} restart{})",
+            make_session_time_string(self.total_downtime(now)),
+            self.restarts(),
+            if self.restarts() == 1 { "" } else { "s" }
+        ));
+
+        lines
+    }
+
+    /// Adds uptime between the last time this was called and `now`
+    fn accrue(&mut self, now: OffsetDateTime) {
+        let unaccrued = self.unaccrued(now);
+        self.uptime += unaccrued;
+        self.day.uptime += unaccrued;
+        self.accrued_until = self.accrued_until.max(now);
+    }
+
+    fn unaccrued(&self, now: OffsetDateTime) -> Duration {
+        match self.up_since {
+            Some(up_since) => (now - self.accrued_until.max(up_since)).max(Duration::ZERO),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn totals() {
+        let launch = datetime!(2023-10-01 12:00 UTC);
+        let mut availability = Availability::new(launch, UtcOffset::UTC);
+        availability.server_started(launch + Duration::minutes(1));
+        availability.server_stopped(launch + Duration::minutes(31));
+        availability.server_started(launch + Duration::minutes(41));
+
+        let now = launch + Duration::minutes(61);
+        assert_eq!(availability.total_uptime(now), Duration::minutes(50));
+        assert_eq!(availability.total_downtime(now), Duration::minutes(11));
+        assert_eq!(availability.server_uptime(now), Some(Duration::minutes(20)));
+        assert_eq!(availability.restarts(), 1);
+    }
+
+    #[test]
+    fn daily_summary() {
+        let launch = datetime!(2023-10-01 00:00 UTC);
+        let mut availability = Availability::new(launch, UtcOffset::UTC);
+        availability.server_started(launch);
+        availability.server_stopped(launch + Duration::hours(6));
+        availability.server_started(launch + Duration::hours(12));
+
+        assert_eq!(availability.roll_day(launch + Duration::hours(20)), None);
+
+        let summary = availability
+            .roll_day(datetime!(2023-10-02 00:01 UTC))
+            .unwrap();
+        assert_eq!(summary.date, launch.date());
+        assert_eq!(summary.tracked, Duration::days(1));
+        assert_eq!(summary.uptime, Duration::hours(18));
+        assert_eq!(summary.restarts, 1);
+        assert_eq!(
+            summary.to_string(),
+            "The server was up 75.0% yesterday, 1 restart"
+        );
+        let summary = DaySummary {
+            peak_players: Some(12),
+            ..summary
+        };
+        assert_eq!(
+            summary.to_string(),
+            "The server was up 75.0% yesterday, 1 restart, peak of 12 players"
+        );
+
+        // The server stayed up across midnight
+        let summary = availability
+            .roll_day(datetime!(2023-10-03 00:00 UTC))
+            .unwrap();
+        assert_eq!(summary.uptime, Duration::days(1));
+        assert_eq!(summary.restarts, 0);
+    }
+
+    #[test]
+    fn partial_first_day() {
+        let launch = datetime!(2023-10-01 18:00 UTC);
+        let mut availability = Availability::new(launch, UtcOffset::UTC);
+        availability.server_started(launch);
+
+        let summary = availability
+            .roll_day(datetime!(2023-10-02 01:00 UTC))
+            .unwrap();
+        assert_eq!(summary.tracked, Duration::hours(6));
+        assert_eq!(summary.uptime_percent(), 100.0);
+    }
+}
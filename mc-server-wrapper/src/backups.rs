@@ -0,0 +1,327 @@
+//! Restoring worlds from backup archives
+//!
+//! Backups are `.zip` archives in the backup folder whose top-level folders
+//! are worlds (e.g. `world/level.dat`). A backup's ID is its file name
+//! without the extension.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    fs::{self, File},
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use time::OffsetDateTime;
+
+use crate::{provision::server_dir, worlds::format_size};
+
+/// The folder backups are read from if one isn't configured
+const DEFAULT_BACKUP_DIR_NAME: &str = "backups";
+
+/// A backup archive on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub id: String,
+    pub path: PathBuf,
+    /// The size of the archive in bytes
+    pub size: u64,
+}
+
+impl fmt::Display for Backup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.id, format_size(self.size))
+    }
+}
+
+/// Returns the folder backups are read from for the server at `server_path`
+pub fn backup_dir(server_path: &Path, configured: Option<&Path>) -> PathBuf {
+    match configured {
+        Some(dir) => dir.to_path_buf(),
+        None => server_dir(server_path).join(DEFAULT_BACKUP_DIR_NAME),
+    }
+}
+
+/// Lists the backups in `dir`, sorted by ID
+pub fn list(dir: &Path) -> Result<Vec<Backup>, anyhow::Error> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut backups = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        if path.extension().map(|e| e != "zip").unwrap_or(true) || !path.is_file() {
+            continue;
+        }
+
+        backups.push(Backup {
+            id: path.file_stem().unwrap().to_string_lossy().into_owned(),
+            size: path.metadata()?.len(),
+            path,
+        });
+    }
+
+    backups.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(backups)
+}
+
+/// Finds the backup with the given ID in `dir`
+pub fn find(dir: &Path, id: &str) -> Result<Backup, anyhow::Error> {
+    list(dir)?
+        .into_iter()
+        .find(|b| b.id == id)
+        .ok_or_else(|| anyhow!("there is no backup named \"{}\" in {:?}", id, dir))
+}
+
+/// Returns the most recently modified backup in `dir`, if there are any
+pub fn latest(dir: &Path) -> Result<Option<Backup>, anyhow::Error> {
+    let mut latest = None;
+    for backup in list(dir)? {
+        let modified = backup.path.metadata()?.modified()?;
+        if latest
+            .as_ref()
+            .is_none_or(|(latest, _)| modified >= *latest)
+        {
+            latest = Some((modified, backup));
+        }
+    }
+
+    Ok(latest.map(|(_, backup)| backup))
+}
+
+/// Reads every file in `backup` to check its integrity, returning the names
+/// of the worlds it contains
+pub fn verify(backup: &Backup) -> Result<Vec<String>, anyhow::Error> {
+    let mut archive = zip::ZipArchive::new(File::open(&backup.path)?)
+        .with_context(|| format!("{} is not a valid zip archive", backup.id))?;
+    let mut top_level = BTreeSet::new();
+    let mut worlds = BTreeSet::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let path = entry_path(file.name())?;
+        let name = file.name().to_string();
+
+        // Reading the whole file makes the zip crate check its CRC
+        io::copy(&mut file, &mut io::sink())
+            .with_context(|| format!("{} is corrupt (failed to read {})", backup.id, name))?;
+
+        let mut components = path.components();
+        let first = match components.next() {
+            Some(Component::Normal(first)) => first.to_string_lossy().into_owned(),
+            _ => continue,
+        };
+        if components.as_path() == Path::new("level.dat") {
+            worlds.insert(first.clone());
+        }
+        top_level.insert(first);
+    }
+
+    if worlds.is_empty() {
+        bail!("{} does not contain any worlds", backup.id);
+    }
+    if let Some(other) = top_level.difference(&worlds).next() {
+        bail!(
+            "{} contains \"{}\", which is not a world; only worlds can be restored",
+            backup.id,
+            other
+        );
+    }
+
+    Ok(worlds.into_iter().collect())
+}
+
+/// Replaces the worlds of the server at `server_path` with those in `backup`
+///
+/// Each world being replaced is first renamed aside with a `.pre-restore-`
+/// suffix. If unpacking fails the worlds are put back. The server must not be
+/// running when this is called. Returns the paths the old worlds were moved
+/// to.
+pub fn restore(server_path: &Path, backup: &Backup) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let worlds = verify(backup)?;
+    let dir = server_dir(server_path);
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+
+    let mut snapshots = vec![];
+    for world in &worlds {
+        let path = dir.join(world);
+        if path.exists() {
+            let snapshot = dir.join(format!("{}.pre-restore-{}", world, timestamp));
+            fs::rename(&path, &snapshot)
+                .with_context(|| format!("Failed to move {:?} aside", path))?;
+            snapshots.push((path, snapshot));
+        }
+    }
+
+    if let Err(e) = unpack(&dir, backup) {
+        for world in &worlds {
+            let _ = fs::remove_dir_all(dir.join(world));
+        }
+        for (path, snapshot) in &snapshots {
+            let _ = fs::rename(snapshot, path);
+        }
+
+        return Err(e.context(format!(
+            "Failed to unpack {}, the worlds were put back",
+            backup.id
+        )));
+    }
+
+    Ok(snapshots
+        .into_iter()
+        .map(|(_, snapshot)| snapshot)
+        .collect())
+}
+
+/// Replaces the given files of the server at `server_path` with their copies
+/// in `backup`
+///
+/// `paths` are relative to the server's folder. Each file being replaced is
+/// first renamed aside with a `.corrupt-` suffix. The server must not be
+/// running when this is called. Returns the files that were restored; files
+/// that aren't in the backup are left alone.
+pub fn restore_files(
+    server_path: &Path,
+    backup: &Backup,
+    paths: &[PathBuf],
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let dir = server_dir(server_path);
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp();
+    let mut archive = zip::ZipArchive::new(File::open(&backup.path)?)
+        .with_context(|| format!("{} is not a valid zip archive", backup.id))?;
+
+    let mut restored = vec![];
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let path = entry_path(file.name())?;
+        if file.is_dir() || !paths.contains(&path) {
+            continue;
+        }
+
+        let out_path = dir.join(&path);
+        let mut aside = out_path.clone().into_os_string();
+        aside.push(format!(".corrupt-{}", timestamp));
+        fs::rename(&out_path, &aside)
+            .with_context(|| format!("Failed to move {:?} aside", out_path))?;
+
+        let written = File::create(&out_path).and_then(|mut out| io::copy(&mut file, &mut out));
+        if let Err(e) = written {
+            let _ = fs::rename(&aside, &out_path);
+            return Err(anyhow::Error::from(e)
+                .context(format!("Failed to restore {:?}, it was put back", out_path)));
+        }
+        restored.push(path);
+    }
+
+    Ok(restored)
+}
+
+fn unpack(dir: &Path, backup: &Backup) -> Result<(), anyhow::Error> {
+    let mut archive = zip::ZipArchive::new(File::open(&backup.path)?)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let out_path = dir.join(entry_path(file.name())?);
+
+        if file.is_dir() {
+            fs::create_dir_all(&out_path)?;
+        } else {
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut file, &mut File::create(&out_path)?)
+                .with_context(|| format!("Failed to write {:?}", out_path))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the path of an archive entry, rejecting any that would escape the
+/// folder it's unpacked into
+pub fn entry_path(name: &str) -> Result<PathBuf, anyhow::Error> {
+    let path = Path::new(name);
+    if path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        Ok(path.to_path_buf())
+    } else {
+        Err(anyhow!("refusing to unpack unsafe path \"{}\"", name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn safe_entry_paths() {
+        assert_eq!(
+            entry_path("world/region/r.0.0.mca").unwrap(),
+            Path::new("world/region/r.0.0.mca")
+        );
+        assert!(entry_path("../world/level.dat").is_err());
+        assert!(entry_path("/etc/passwd").is_err());
+        assert!(entry_path("world/../../level.dat").is_err());
+    }
+
+    #[test]
+    fn restore_single_files() {
+        use std::io::Write;
+
+        let dir = std::env::temp_dir().join(format!("mcsw-test-restore-{}", std::process::id()));
+        fs::create_dir_all(dir.join("world/region")).unwrap();
+        fs::write(dir.join("world/region/r.0.0.mca"), "corrupt").unwrap();
+        fs::write(dir.join("world/region/r.0.1.mca"), "fine").unwrap();
+
+        let backup_path = dir.join("backup.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&backup_path).unwrap());
+        for name in ["world/region/r.0.0.mca", "world/region/r.0.1.mca"] {
+            zip.start_file(name, Default::default()).unwrap();
+            zip.write_all(b"backed up").unwrap();
+        }
+        zip.finish().unwrap();
+        let backup = Backup {
+            id: "backup".into(),
+            size: 0,
+            path: backup_path,
+        };
+
+        let restored = restore_files(
+            &dir.join("server.jar"),
+            &backup,
+            &[
+                PathBuf::from("world/region/r.0.0.mca"),
+                PathBuf::from("world/region/r.5.5.mca"),
+            ],
+        );
+        let read = |name: &str| fs::read_to_string(dir.join(name)).unwrap();
+        let (r00, r01) = (
+            read("world/region/r.0.0.mca"),
+            read("world/region/r.0.1.mca"),
+        );
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(restored.unwrap(), [PathBuf::from("world/region/r.0.0.mca")]);
+        assert_eq!(r00, "backed up");
+        assert_eq!(r01, "fine");
+    }
+
+    #[test]
+    fn default_backup_dir() {
+        assert_eq!(
+            backup_dir(Path::new("server/server.jar"), None),
+            Path::new("server/backups")
+        );
+        assert_eq!(
+            backup_dir(
+                Path::new("server/server.jar"),
+                Some(Path::new("/mnt/backups"))
+            ),
+            Path::new("/mnt/backups")
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! Building Minecraft chat components for bridged messages
+//!
+//! A message is built as a Minecraft chat component (for `tellraw`) and as
+//! plain text (for the console and logs) at the same time. URLs are found
+//! here too so they can be made clickable, as long as their domain is
+//! allowed.
+
+use std::{borrow::Cow, ops::Range};
+
+use minecraft_chat::{Color, MessageBuilder, Payload};
+
+/// Characters that end a URL
+const URL_TERMINATORS: &[char] = &['<', '>', '"', '`'];
+/// Characters that are left out if they end a URL, since they're more likely
+/// punctuation or markdown
+const URL_TRAILING: &[char] = &['.', ',', ':', ';', '!', '?', '\'', '*', '_', '~', '|'];
+
+/// How many characters of a message are shown in one chat message, which is
+/// as long as players' own chat messages can be
+pub const MAX_LEN: usize = 256;
+
+/// Shown where a message is split
+const CONTINUATION: &str = "…";
+
+/// A Minecraft chat message being built alongside its plain text
+///
+/// Messages longer than the maximum length are split into parts, with
+/// continuation markers where they're split.
+pub struct ChatMessage<'a> {
+    /// Starts each part of the message
+    start: Box<dyn Fn() -> MessageBuilder + 'a>,
+    max_len: usize,
+    /// The parts of the message before the current one
+    parts: Vec<MessageBuilder>,
+    builder: MessageBuilder,
+    /// How many characters are in the current part
+    len: usize,
+    plain: Vec<Cow<'a, str>>,
+}
+
+impl<'a> ChatMessage<'a> {
+    /// Starts building a message, with `max_len` characters in each part
+    ///
+    /// Each part is built onto a builder from `start`, and what's in it isn't
+    /// part of the plain text.
+    pub fn new(start: impl Fn() -> MessageBuilder + 'a, max_len: usize) -> Self {
+        Self {
+            builder: start(),
+            start: Box::new(start),
+            max_len: max_len.max(1),
+            parts: vec![],
+            len: 0,
+            plain: vec![],
+        }
+    }
+
+    /// Adds a component showing `text`, which `style` is given to style
+    ///
+    /// If `text` is split across parts, `style` is given each piece of it.
+    pub fn push(
+        &mut self,
+        text: impl Into<Cow<'a, str>>,
+        style: impl Fn(MessageBuilder, &str) -> MessageBuilder,
+    ) {
+        let text = text.into();
+        let mut rest: &str = &text;
+        while !rest.is_empty() {
+            let piece_len = split_len(rest, self.max_len - self.len, self.len == 0);
+            if piece_len == 0 {
+                self.next_part();
+                continue;
+            }
+
+            let (piece, after) = rest.split_at(piece_len);
+            let builder = self.take_builder();
+            self.builder = style(builder.then(Payload::text(piece)), piece);
+            self.len += piece.chars().count();
+            rest = after;
+        }
+        self.plain.push(text);
+    }
+
+    /// Adds text that's only part of the plain text
+    pub fn push_plain(&mut self, text: impl Into<Cow<'a, str>>) {
+        self.plain.push(text.into());
+    }
+
+    /// Adds `text`, making the URLs in it that `links` allows clickable
+    ///
+    /// Each component is given to `style` before URLs are styled as links.
+    pub fn push_linkified(
+        &mut self,
+        text: &'a str,
+        links: &LinkFilter,
+        style: impl Fn(MessageBuilder, &str) -> MessageBuilder,
+    ) {
+        let mut start = 0;
+        for range in find_urls(text) {
+            let url = &text[range.clone()];
+            if !links.allows(url) {
+                continue;
+            }
+
+            if start < range.start {
+                self.push(&text[start..range.start], &style);
+            }
+            self.push(url, |builder, piece| link(style(builder, piece), url));
+            start = range.end;
+        }
+
+        if start < text.len() {
+            self.push(&text[start..], style);
+        }
+    }
+
+    /// Returns the plain text and the parts of the finished message
+    pub fn finish(mut self) -> (String, Vec<MessageBuilder>) {
+        let last = self.take_builder();
+        self.parts.push(last);
+        (self.plain.into_iter().collect(), self.parts)
+    }
+
+    fn take_builder(&mut self) -> MessageBuilder {
+        std::mem::replace(
+            &mut self.builder,
+            MessageBuilder::builder(Payload::text("")),
+        )
+    }
+
+    /// Finishes the current part and starts the next one
+    fn next_part(&mut self) {
+        let finished = self.take_builder();
+        self.parts.push(
+            finished
+                .then(Payload::text(CONTINUATION))
+                .color(Color::Gray),
+        );
+        self.builder = (self.start)()
+            .then(Payload::text(CONTINUATION))
+            .color(Color::Gray);
+        self.len = 0;
+    }
+}
+
+/// Returns the length (in bytes) of the start of `text` that fits in `room`
+/// characters
+///
+/// Text is split after whitespace if it can be. If it can't, it's only split
+/// in the middle of a word if `split_words` is set, and otherwise none of it
+/// fits.
+fn split_len(text: &str, room: usize, split_words: bool) -> usize {
+    let end = match text.char_indices().nth(room) {
+        Some((end, _)) => end,
+        None => return text.len(),
+    };
+
+    match text[..end].rfind(char::is_whitespace) {
+        Some(space) => space + text[space..].chars().next().map_or(0, char::len_utf8),
+        None if split_words => end,
+        None => 0,
+    }
+}
+
+/// Styles the last component added to `builder` as a link to `url`
+pub fn link(builder: MessageBuilder, url: &str) -> MessageBuilder {
+    builder
+        .color(Color::Blue)
+        .underlined(true)
+        .click_open_url(url)
+        .hover_show_text(&format!("Click to open in your browser: {}", url))
+}
+
+/// Decides which links are made clickable
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LinkFilter {
+    /// Allowed domains (and their subdomains), or empty to allow all of them
+    domains: Vec<String>,
+}
+
+impl LinkFilter {
+    pub fn new(domains: &[String]) -> Self {
+        Self {
+            domains: domains
+                .iter()
+                .map(|domain| domain.trim_start_matches("*.").to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Returns true if `url` links to an allowed domain
+    pub fn allows(&self, url: &str) -> bool {
+        if self.domains.is_empty() {
+            return true;
+        }
+
+        let host = match url_host(url) {
+            Some(host) => host.to_lowercase(),
+            None => return false,
+        };
+        self.domains.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain.as_str())
+                    .is_some_and(|sub| sub.ends_with('.'))
+        })
+    }
+}
+
+/// Returns the length of the URL at the start of `buf`, if there is one
+pub fn url_len(buf: &str) -> Option<usize> {
+    let after_scheme = buf
+        .strip_prefix("https://")
+        .or_else(|| buf.strip_prefix("http://"))?;
+    let scheme_len = buf.len() - after_scheme.len();
+
+    let mut url = &buf[..scheme_len
+        + after_scheme
+            .find(|c: char| c.is_whitespace() || URL_TERMINATORS.contains(&c))
+            .unwrap_or(after_scheme.len())];
+    loop {
+        url = url.trim_end_matches(URL_TRAILING);
+        // Closing parentheses are only part of the URL if they close one in
+        // the URL, like on Wikipedia
+        match url.strip_suffix(')') {
+            Some(trimmed) if url.matches('(').count() < url.matches(')').count() => url = trimmed,
+            _ => break,
+        }
+    }
+
+    url_host(url).filter(|host| !host.is_empty())?;
+    Some(url.len())
+}
+
+/// Finds the URLs in `text`
+pub fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = vec![];
+    let mut prev = None;
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        // URLs have to start a word
+        if !prev.is_some_and(char::is_alphanumeric) {
+            if let Some(len) = url_len(&text[i..]) {
+                urls.push(i..i + len);
+                i += len;
+                prev = text[..i].chars().last();
+                continue;
+            }
+        }
+        prev = Some(c);
+        i += c.len_utf8();
+    }
+
+    urls
+}
+
+/// Returns the host of `url`, without any user info or port
+fn url_host(url: &str) -> Option<&str> {
+    let after_scheme = url.split_once("://")?.1;
+    let authority = after_scheme.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    Some(host.split(':').next().unwrap_or(host))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn urls(text: &str) -> Vec<&str> {
+        find_urls(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn finds_urls() {
+        assert_eq!(
+            urls("see https://minecraft.wiki/w/Redstone_(disambiguation)."),
+            ["https://minecraft.wiki/w/Redstone_(disambiguation)"]
+        );
+        assert_eq!(
+            urls("(http://a.com) and <https://b.com/x?y=1>, **https://c.com**"),
+            ["http://a.com", "https://b.com/x?y=1", "https://c.com"]
+        );
+        assert!(urls("nohttps://a.com https:// http:/a.com").is_empty());
+    }
+
+    #[test]
+    fn filters_domains() {
+        let all = LinkFilter::default();
+        assert!(all.allows("https://anything.example"));
+
+        let filter = LinkFilter::new(&["Minecraft.net".into(), "*.imgur.com".into()]);
+        assert!(filter.allows("https://minecraft.net/en-us"));
+        assert!(filter.allows("https://www.minecraft.net"));
+        assert!(filter.allows("https://i.imgur.com/a.png"));
+        assert!(filter.allows("https://user@imgur.com:443/"));
+        assert!(!filter.allows("https://notminecraft.net"));
+        assert!(!filter.allows("https://minecraft.net.evil.com"));
+    }
+
+    #[test]
+    fn linkified() {
+        let mut chat = ChatMessage::new(|| MessageBuilder::builder(Payload::text("")), MAX_LEN);
+        let filter = LinkFilter::new(&["minecraft.net".into()]);
+        chat.push_linkified(
+            "get it at https://minecraft.net, not https://evil.com",
+            &filter,
+            |builder, _| builder,
+        );
+        chat.push_plain("!");
+
+        let (plain, parts) = chat.finish();
+        assert_eq!(
+            plain,
+            "get it at https://minecraft.net, not https://evil.com!"
+        );
+        assert_eq!(parts.len(), 1);
+        let json = parts.into_iter().next().unwrap().build().to_json().unwrap();
+        assert!(json.contains(r#""value":"https://minecraft.net""#));
+        assert!(!json.contains(r#""value":"https://evil.com""#));
+    }
+
+    /// Returns the text of each part of a message
+    fn part_texts(parts: Vec<MessageBuilder>) -> Vec<String> {
+        parts
+            .into_iter()
+            .map(|part| {
+                let json: serde_json::Value =
+                    serde_json::from_str(&part.build().to_json().unwrap()).unwrap();
+                json["extra"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|component| component["text"].as_str().unwrap())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split() {
+        let mut chat = ChatMessage::new(|| MessageBuilder::builder(Payload::text("")), 10);
+        chat.push("one two three ", |builder, _| builder);
+        chat.push("@mention", |builder, _| builder.color(Color::Blue));
+        chat.push(" abcdefghijklmno", |builder, _| builder);
+
+        let (plain, parts) = chat.finish();
+        assert_eq!(plain, "one two three @mention abcdefghijklmno");
+        assert_eq!(
+            part_texts(parts),
+            [
+                "one two …",
+                "…three …",
+                "…@mention …",
+                "…abcdefghij…",
+                "…klmno",
+            ]
+        );
+    }
+
+    #[test]
+    fn split_link() {
+        let mut chat = ChatMessage::new(|| MessageBuilder::builder(Payload::text("")), 20);
+        chat.push_linkified(
+            "https://example.com/a/long/path",
+            &LinkFilter::default(),
+            |builder, _| builder,
+        );
+
+        let (_, parts) = chat.finish();
+        assert_eq!(parts.len(), 2);
+        for part in parts {
+            let json = part.build().to_json().unwrap();
+            assert!(json.contains(r#""value":"https://example.com/a/long/path""#));
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! An HTTP endpoint for other programs to send messages to the server's chat
+//!
+//! `POST /api/chat` with an `Authorization: Bearer <token>` header and a JSON
+//! body like `{"from": "Donations", "message": "Thanks for the support!",
+//! "discord": true}` shows the message to everyone in Minecraft, and with
+//! `discord` also posts it in the bridged channel. This lets things like
+//! donation bots and stream alerts talk to players.
+
+use minecraft_chat::{Color, MessageBuilder, Payload};
+use serde_derive::Deserialize;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, oneshot},
+};
+
+use crate::{
+    chat::{self, ChatMessage},
+    http::{self, Request, Response},
+};
+
+/// The longest `from` that's accepted
+const MAX_FROM_LEN: usize = 32;
+/// The longest message that's accepted, which is split into as many chat
+/// messages as needed
+const MAX_MESSAGE_LEN: usize = 1024;
+
+/// A message to send to the server's chat
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ChatPost {
+    /// Shown before the message, like `[Donations]`
+    #[serde(default)]
+    pub from: Option<String>,
+    pub message: String,
+    /// Also post the message in the bridged Discord channel
+    #[serde(default)]
+    pub discord: bool,
+}
+
+/// A message posted to the API, to be sent by the main loop
+#[derive(Debug)]
+pub struct ChatInjection {
+    pub post: ChatPost,
+    /// Receives whether the message was sent (it isn't if the server isn't
+    /// running)
+    pub sent: oneshot::Sender<bool>,
+}
+
+/// Whether `request` has the right bearer token
+fn authorized(request: &Request, token: &str) -> bool {
+    let given = request
+        .header("authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    // An empty token would let in requests without an `Authorization` header
+    // (one is refused when the config is loaded, but this doesn't rely on it)
+    if token.is_empty() {
+        return false;
+    }
+
+    // Compared in constant time so the token can't be guessed a byte at a
+    // time
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Parses and checks the body of a chat post
+fn parse_post(body: &[u8]) -> Result<ChatPost, String> {
+    let post: ChatPost =
+        serde_json::from_slice(body).map_err(|e| format!("invalid request body: {}", e))?;
+
+    if post.message.trim().is_empty() {
+        return Err("`message` can't be empty".into());
+    }
+    if post.message.chars().count() > MAX_MESSAGE_LEN {
+        return Err(format!(
+            "`message` can't be longer than {} characters",
+            MAX_MESSAGE_LEN
+        ));
+    }
+    if let Some(from) = &post.from {
+        if from.is_empty() || from.chars().count() > MAX_FROM_LEN {
+            return Err(format!(
+                "`from` must be between 1 and {} characters",
+                MAX_FROM_LEN
+            ));
+        }
+    }
+
+    Ok(post)
+}
+
+/// Returns the response to `request`, sending valid posts over
+/// `injection_sender`
+async fn respond(
+    request: Request,
+    token: &str,
+    injection_sender: &mpsc::Sender<ChatInjection>,
+) -> Response {
+    if request.path != "/api/chat" {
+        return Response::not_found();
+    }
+    if request.method != "POST" {
+        return Response::text(405, "method not allowed\n");
+    }
+    if !authorized(&request, token) {
+        return Response::text(401, "unauthorized\n");
+    }
+    let post = match parse_post(&request.body) {
+        Ok(post) => post,
+        Err(e) => return Response::text(400, format!("{}\n", e)),
+    };
+
+    let (sent, sent_receiver) = oneshot::channel();
+    if injection_sender
+        .send(ChatInjection { post, sent })
+        .await
+        .is_err()
+    {
+        return Response::text(503, "the wrapper is shutting down\n");
+    }
+    match sent_receiver.await {
+        Ok(true) => Response::text(200, "sent\n"),
+        _ => Response::text(503, "the Minecraft server isn't running\n"),
+    }
+}
+
+/// Builds the `tellraw` components for a post, returning them along with the
+/// plain text of the message
+pub fn tellraw(post: &ChatPost) -> (String, Vec<String>) {
+    let start = || {
+        let builder = MessageBuilder::builder(Payload::text(""));
+        match &post.from {
+            // Setting styles on the first payload sets them for all future
+            // payloads, so the prefix's are set on itself
+            Some(from) => builder
+                .then(Payload::text(&format!("[{}] ", from)))
+                .bold(true)
+                .color(Color::Gold),
+            None => builder,
+        }
+    };
+
+    let mut message = ChatMessage::new(start, chat::MAX_LEN);
+    if let Some(from) = &post.from {
+        message.push_plain(format!("[{}] ", from));
+    }
+    message.push(post.message.as_str(), |builder, _| {
+        builder.bold(false).color(Color::White)
+    });
+
+    let (plain, parts) = message.finish();
+    (
+        plain,
+        parts
+            .into_iter()
+            .map(|part| part.build().to_json().unwrap())
+            .collect(),
+    )
+}
+
+/// Serves the chat API on `listener` until the wrapper exits
+pub async fn serve(
+    listener: TcpListener,
+    token: String,
+    injection_sender: mpsc::Sender<ChatInjection>,
+) {
+    http::serve_api(listener, "chat API", move |request| {
+        let token = token.clone();
+        let injection_sender = injection_sender.clone();
+        async move { respond(request, &token, &injection_sender).await }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    use super::*;
+
+    #[test]
+    fn posts() {
+        assert_eq!(
+            parse_post(br#"{"from": "Donations", "message": "Thanks!", "discord": true}"#),
+            Ok(ChatPost {
+                from: Some("Donations".into()),
+                message: "Thanks!".into(),
+                discord: true,
+            })
+        );
+        assert!(parse_post(br#"{"message": "  "}"#).is_err());
+        assert!(parse_post(br#"{"from": "", "message": "hi"}"#).is_err());
+        assert!(parse_post(b"message=hi").is_err());
+    }
+
+    #[test]
+    fn components() {
+        let (plain, parts) = tellraw(&ChatPost {
+            from: Some("Stream".into()),
+            message: "New follower!".into(),
+            discord: false,
+        });
+
+        assert_eq!(plain, "[Stream] New follower!");
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].contains("[Stream] "));
+        assert!(parts[0].contains("New follower!"));
+    }
+
+    async fn request(address: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn serves_posts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (injection_sender, mut injection_receiver) = mpsc::channel(1);
+        tokio::spawn(serve(listener, "s3cret".into(), injection_sender));
+        tokio::spawn(async move {
+            while let Some(injection) = injection_receiver.recv().await {
+                assert_eq!(injection.post.message, "hello");
+                let _ = injection.sent.send(true);
+            }
+        });
+
+        let body = r#"{"message": "hello"}"#;
+        let post = |token: &str| {
+            format!(
+                "POST /api/chat HTTP/1.1\r\nAuthorization: Bearer {}\r\n\
+                Content-Length: {}\r\n\r\n{}",
+                token,
+                body.len(),
+                body
+            )
+        };
+
+        assert!(request(address, &post("s3cret"))
+            .await
+            .starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(request(address, &post("guess!"))
+            .await
+            .starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(request(address, "GET /api/chat HTTP/1.1\r\n\r\n")
+            .await
+            .starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+
+    #[tokio::test]
+    async fn empty_token_refuses_everything() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (injection_sender, _injection_receiver) = mpsc::channel(1);
+        tokio::spawn(serve(listener, String::new(), injection_sender));
+
+        let body = r#"{"message": "hello"}"#;
+        let post = format!(
+            "POST /api/chat HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        assert!(request(address, &post)
+            .await
+            .starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+    }
+}
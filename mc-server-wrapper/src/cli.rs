@@ -0,0 +1,67 @@
+//! Shell completions and the man page, generated from the CLI definition
+
+use std::io::Write;
+
+use structopt::{clap::Shell, StructOpt};
+
+use crate::{Opt, APPLICATION_VERSION};
+
+static BIN_NAME: &str = "mc-server-wrapper";
+
+/// Writes a completion script for `shell` to `out`
+pub fn write_completions(shell: Shell, out: &mut impl Write) {
+    Opt::clap().gen_completions_to(BIN_NAME, shell, out);
+}
+
+/// Returns a man page (in roff) describing the CLI
+pub fn man_page() -> String {
+    let mut help = vec![];
+    // Writing to a `Vec` can't fail
+    Opt::clap().write_long_help(&mut help).unwrap();
+    let help = String::from_utf8_lossy(&help);
+
+    let mut page = format!(
+        ".TH MC-SERVER-WRAPPER 1 \"\" \"{} {}\"\n\
+        .SH NAME\n\
+        {} \\- manage a Java Minecraft server process\n\
+        .SH DESCRIPTION\n\
+        .nf\n",
+        BIN_NAME, APPLICATION_VERSION, BIN_NAME
+    );
+    for line in help.lines() {
+        let line = line.replace('\\', "\\e");
+        // Lines starting with these would be read as roff requests
+        if line.starts_with('.') || line.starts_with('\'') {
+            page.push_str("\\&");
+        }
+        page.push_str(&line);
+        page.push('\n');
+    }
+    page.push_str(".fi\n");
+
+    page
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn completions_include_options() {
+        let mut out = vec![];
+        write_completions(Shell::Bash, &mut out);
+        let script = String::from_utf8(out).unwrap();
+
+        assert!(script.contains("--check-config"));
+        assert!(script.contains("completions"));
+    }
+
+    #[test]
+    fn man_page_includes_options() {
+        let page = man_page();
+
+        assert!(page.starts_with(".TH MC-SERVER-WRAPPER 1"));
+        assert!(page.contains("--check-config"));
+        assert!(page.contains("completions"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Capturing the console output of commands run from outside the TUI
+//!
+//! The server doesn't mark which output belongs to which command, so output
+//! is captured for a short time after the command is written instead. Any
+//! unrelated output in that time is captured as well.
+
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+use crate::remote_commands::{CommandOrigin, CommandRejected};
+
+/// How long output is captured for after a command is run
+const CAPTURE_DURATION: Duration = Duration::from_secs(1);
+
+/// A request to run a command and capture its output
+#[derive(Debug)]
+pub struct CaptureRequest {
+    pub command: String,
+    pub origin: CommandOrigin,
+    /// Receives the output once it's been captured, or why the command
+    /// wasn't run
+    pub output: oneshot::Sender<Result<Vec<String>, CommandRejected>>,
+}
+
+#[derive(Debug)]
+struct Capture {
+    until: Instant,
+    lines: Vec<String>,
+    output: oneshot::Sender<Result<Vec<String>, CommandRejected>>,
+}
+
+/// Output captures that are in progress
+#[derive(Debug, Default)]
+pub struct CommandCaptures {
+    active: Vec<Capture>,
+}
+
+impl CommandCaptures {
+    /// Starts capturing output for a command that was run at `now`
+    pub fn start(
+        &mut self,
+        output: oneshot::Sender<Result<Vec<String>, CommandRejected>>,
+        now: Instant,
+    ) {
+        self.start_for(output, now, CAPTURE_DURATION);
+    }
+
+    /// Starts capturing output for `duration` from `now`
+    pub fn start_for(
+        &mut self,
+        output: oneshot::Sender<Result<Vec<String>, CommandRejected>>,
+        now: Instant,
+        duration: Duration,
+    ) {
+        self.active.push(Capture {
+            until: now + duration,
+            lines: vec![],
+            output,
+        });
+    }
+
+    /// Adds a line of console output to every capture in progress
+    pub fn line(&mut self, line: &str) {
+        for capture in &mut self.active {
+            capture.lines.push(line.to_string());
+        }
+    }
+
+    /// Sends the output of captures that finished by `now`
+    pub fn finish(&mut self, now: Instant) {
+        let (finished, active) = std::mem::take(&mut self.active)
+            .into_iter()
+            .partition(|capture| capture.until <= now);
+        self.active = active;
+
+        for capture in finished {
+            let _ = capture.output.send(Ok(capture.lines));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn captures_output_for_a_while() {
+        let mut captures = CommandCaptures::default();
+        let now = Instant::now();
+        let (sender, mut receiver) = oneshot::channel();
+
+        captures.line("before");
+        captures.start(sender, now);
+        captures.line("There are 0 of a max of 20 players online:");
+        captures.finish(now + Duration::from_millis(500));
+        assert!(receiver.try_recv().is_err());
+
+        captures.finish(now + CAPTURE_DURATION);
+        assert!(captures.is_empty());
+        assert_eq!(
+            receiver.try_recv().unwrap().unwrap(),
+            ["There are 0 of a max of 20 players online:"]
+        );
+    }
+}
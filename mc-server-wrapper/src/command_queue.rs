@@ -0,0 +1,83 @@
+//! Queueing commands pasted into the TUI to send to the server one at a time
+//!
+//! Pasting several lines at once (like a batch of gamerules or a scoreboard
+//! setup) queues them, and they're written to the server's stdin with a short
+//! delay between each so they're run in order without flooding the console.
+
+use std::{collections::VecDeque, time::Duration};
+
+/// How long to wait between sending queued commands
+pub const SEND_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Commands waiting to be sent to the server, oldest first
+#[derive(Debug, Default)]
+pub struct CommandQueue {
+    commands: VecDeque<String>,
+}
+
+impl CommandQueue {
+    /// Queues each line of `text` as a command, returning how many were
+    /// queued
+    ///
+    /// Blank lines and lines starting with `#` are skipped, and a leading `/`
+    /// is removed since the console doesn't need one.
+    pub fn push_lines(&mut self, text: &str) -> usize {
+        let before = self.commands.len();
+        self.commands.extend(
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.strip_prefix('/').unwrap_or(line).to_string()),
+        );
+
+        self.commands.len() - before
+    }
+
+    /// Takes the next command to send
+    pub fn pop(&mut self) -> Option<String> {
+        self.commands.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Drops every queued command, returning how many there were
+    pub fn clear(&mut self) -> usize {
+        let len = self.commands.len();
+        self.commands.clear();
+        len
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let mut queue = CommandQueue::default();
+        let queued = queue.push_lines(
+            "# Game rules\r\n/gamerule keepInventory true\n\n  gamerule doInsomnia false  \n",
+        );
+
+        assert_eq!(queued, 2);
+        assert_eq!(queue.pop().as_deref(), Some("gamerule keepInventory true"));
+        assert_eq!(queue.pop().as_deref(), Some("gamerule doInsomnia false"));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn clear() {
+        let mut queue = CommandQueue::default();
+        queue.push_lines("say a\nsay b");
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.clear(), 2);
+        assert!(queue.is_empty());
+    }
+}
@@ -1,46 +1,154 @@
-use crate::Opt;
-use anyhow::{anyhow, Context};
+use crate::{chat::LinkFilter, config_migration, i18n::Language, provision::Flavor, Opt};
+use anyhow::{anyhow, bail, Context};
+use mc_server_wrapper_lib::{
+    attach::AttachConfig,
+    parse::MessagePatterns,
+    resources::{CgroupLimits, IoPriority, ResourceLimits},
+    run_as::RunAs,
+};
 use notify_debouncer_mini::{new_debouncer, notify, DebouncedEvent};
 use serde_derive::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
     num::NonZeroU64,
     path::{Path, PathBuf},
     time::Duration,
 };
+use time::{format_description::FormatItem, macros::format_description, UtcOffset};
 use tokio::{
     fs::File,
     io::{AsyncReadExt, AsyncWriteExt},
     sync::mpsc,
 };
 
+/// The prefix of environment variables that override config values
+const ENV_PREFIX: &str = "MCSW_";
+
 /// Represents the mc-server-wrapper config structure
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
+    /// The version of the config file's layout
+    ///
+    /// Older configs are upgraded automatically when they're loaded. Configs
+    /// from before this was added are version 0.
+    #[serde(default)]
+    pub config_version: u32,
+    /// Periodically check GitHub for new releases of mc-server-wrapper
+    #[serde(default = "Config::default_check_for_updates")]
+    pub check_for_updates: bool,
+    /// The offset from UTC to show times at (like "+02:00") if the local
+    /// time zone can't be determined
+    ///
+    /// Times are shown at UTC if this isn't set.
+    #[serde(default)]
+    pub fallback_utc_offset: Option<String>,
+    /// The language of the wrapper's text in the TUI, in Discord, and on the
+    /// status page
+    #[serde(default)]
+    pub language: Language,
+    /// How many console commands each person can run per second from outside
+    /// the TUI (like with `/run` in Discord), or zero for no limit
+    #[serde(default = "Config::default_remote_commands_per_second")]
+    pub remote_commands_per_second: u32,
+    /// The address to serve the `/healthz` and `/readyz` health checks on
+    ///
+    /// They aren't served if this isn't set.
+    #[serde(default)]
+    pub health_address: Option<SocketAddr>,
+    /// Config options for the public status page
+    pub status_page: Option<StatusPage>,
+    /// Config options for the API other programs can send chat messages with
+    pub chat_api: Option<ChatApi>,
+    /// Config options for receiving votes from server lists
+    pub votifier: Option<Votifier>,
+    /// Config options for announcing when streamers go live
+    pub stream_alerts: Option<StreamAlerts>,
+    /// Config options for reporting the wrapper's crashes and errors
+    ///
+    /// Nothing is reported if this isn't set.
+    pub crash_reporting: Option<CrashReporting>,
+    /// Short names for one or more server commands, usable from the console
+    /// and from Discord
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, Alias>,
     /// Minecraft-related config options
+    ///
+    /// These are used unless a server from `servers` is picked with
+    /// `--server`.
+    #[serde(default)]
     pub minecraft: Minecraft,
     /// Discord-related config options
     pub discord: Option<Discord>,
     /// Logging-related config options
     pub logging: Logging,
+    /// Named servers defined with `[[server]]`
+    #[serde(default, rename = "server", skip_serializing_if = "Vec::is_empty")]
+    pub servers: Vec<ServerProfile>,
+    /// Server list heartbeats defined with `[[heartbeat]]`
+    #[serde(default, rename = "heartbeat", skip_serializing_if = "Vec::is_empty")]
+    pub heartbeats: Vec<Heartbeat>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            config_version: config_migration::CURRENT_VERSION,
+            check_for_updates: true,
+            fallback_utc_offset: None,
+            language: Language::default(),
+            remote_commands_per_second: Self::default_remote_commands_per_second(),
+            health_address: None,
+            status_page: None,
+            chat_api: None,
+            votifier: None,
+            stream_alerts: None,
+            crash_reporting: None,
+            aliases: BTreeMap::new(),
             minecraft: Minecraft::default(),
             discord: Some(Discord::default()),
             logging: Logging::default(),
+            servers: vec![],
+            heartbeats: vec![],
         }
     }
 }
 
 impl Config {
+    fn default_check_for_updates() -> bool {
+        true
+    }
+
+    fn default_remote_commands_per_second() -> u32 {
+        2
+    }
+
+    /// Parse the offset set with `fallback_utc_offset`, if any
+    pub fn fallback_utc_offset(&self) -> Result<Option<UtcOffset>, anyhow::Error> {
+        const OFFSET_FORMAT: &[FormatItem] =
+            format_description!("[offset_hour sign:mandatory]:[offset_minute]");
+
+        self.fallback_utc_offset
+            .as_deref()
+            .map(|offset| {
+                UtcOffset::parse(offset, &OFFSET_FORMAT).with_context(|| {
+                    format!(
+                        "Invalid fallback_utc_offset {:?} (expected something like \"+02:00\")",
+                        offset
+                    )
+                })
+            })
+            .transpose()
+    }
+
     /// Load a config file at `path`
     ///
     /// If the config does not exist at the path a default config will be created,
     /// returned, and also written to the path.
     ///
-    /// This will not overwrite an existing file, however.
+    /// This will not overwrite an existing file, however. An existing config
+    /// from an older version of mc-server-wrapper is upgraded in place, and
+    /// the original is kept next to it with a `.v<version>.bak` extension.
     pub async fn load(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
         let path = path.as_ref();
         if !path.exists() {
@@ -60,6 +168,34 @@ impl Config {
                 .await
                 .with_context(|| format!("Failed to read config file at {:?}", path))?;
 
+            let migrated = config_migration::migrate(&buffer)
+                .with_context(|| format!("Failed to upgrade config file at {:?}", path))?;
+            // A config with other problems is left alone so that parse errors
+            // point at the lines that were actually written
+            let migrated =
+                migrated.filter(|migrated| toml::from_str::<Config>(&migrated.contents).is_ok());
+            if let Some(migrated) = migrated {
+                let mut backup_path = path.as_os_str().to_owned();
+                backup_path.push(format!(".v{}.bak", migrated.from_version));
+                tokio::fs::write(&backup_path, &buffer)
+                    .await
+                    .with_context(|| {
+                        format!("Failed to back up config file to {:?}", backup_path)
+                    })?;
+                tokio::fs::write(path, &migrated.contents)
+                    .await
+                    .with_context(|| format!("Failed to write config file to {:?}", path))?;
+                log::info!(
+                    "Upgraded config file at {:?} from version {} to {} (the original was saved to {:?})",
+                    path,
+                    migrated.from_version,
+                    config_migration::CURRENT_VERSION,
+                    backup_path
+                );
+
+                buffer = migrated.contents;
+            }
+
             Ok(toml::from_str(&buffer)
                 .with_context(|| format!("Failed to parse config file at {:?}", path))?)
         }
@@ -79,8 +215,19 @@ impl Config {
             .with_context(|| format!("Failed to write config file to {:?}", path))
     }
 
-    /// Merge args passed in via the CLI into this config
+    /// Merge args passed in via the CLI and overrides set with environment
+    /// variables into this config
+    ///
+    /// Environment variables take precedence over the config file, and CLI
+    /// args take precedence over both.
     pub fn merge_in_args(&mut self, args: Opt) -> Result<(), anyhow::Error> {
+        if let Some(name) = &args.server {
+            self.select_server(name)?;
+        }
+
+        self.apply_env_overrides(std::env::vars())?;
+        self.resolve_secrets()?;
+
         if args.bridge_to_discord {
             if let Some(discord) = &mut self.discord {
                 discord.enable_bridge = true;
@@ -96,9 +243,193 @@ impl Config {
             self.minecraft.server_path = path;
         }
 
+        if let Some(memory) = args.memory {
+            self.minecraft.memory = memory;
+        }
+
+        if let Some(jvm_flags) = args.jvm_flags {
+            self.minecraft.jvm_flags = Some(jvm_flags);
+        }
+
+        Ok(())
+    }
+
+    /// Override config values with environment variables like
+    /// `MCSW_MINECRAFT__MEMORY=4096`
+    ///
+    /// Keys are separated by `__`. Values are parsed as TOML (so `4096` is a
+    /// number and `["a", "b"]` is an array), falling back to a string if that
+    /// fails; values that replace a string are always kept as strings.
+    pub fn apply_env_overrides(
+        &mut self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<(), anyhow::Error> {
+        let mut value = toml::Value::try_from(&*self)?;
+        let mut changed = false;
+
+        for (key, raw) in vars {
+            let path = match key.strip_prefix(ENV_PREFIX) {
+                Some(path) => path,
+                None => continue,
+            };
+            let path: Vec<_> = path.split("__").map(str::to_lowercase).collect();
+
+            set_value(&mut value, &path, &raw)
+                .with_context(|| format!("Failed to apply config override {}", key))?;
+            changed = true;
+        }
+
+        if changed {
+            *self = value
+                .try_into()
+                .with_context(|| "Invalid config value set by an environment variable")?;
+        }
+
+        Ok(())
+    }
+
+    /// Replace secrets given as references (see `resolve_secret`) with their
+    /// values
+    pub fn resolve_secrets(&mut self) -> Result<(), anyhow::Error> {
+        // The token isn't needed (and its reference might not resolve) unless
+        // the bridge is enabled
+        if let Some(discord) = self.discord.as_mut().filter(|d| d.enable_bridge) {
+            discord.token = resolve_secret(&discord.token)
+                .with_context(|| "Failed to read the Discord bot token")?;
+        }
+
+        if let Some(attach) = &mut self.minecraft.attach {
+            attach.rcon_password = resolve_secret(&attach.rcon_password)
+                .with_context(|| "Failed to read the RCON password")?;
+        }
+
+        if let Some(key) = self
+            .minecraft
+            .provision
+            .as_mut()
+            .and_then(|p| p.curseforge_api_key.as_mut())
+        {
+            *key = resolve_secret(key).with_context(|| "Failed to read the CurseForge API key")?;
+        }
+
+        if let Some(chat_api) = &mut self.chat_api {
+            chat_api.token = resolve_secret(&chat_api.token)
+                .with_context(|| "Failed to read the chat API token")?;
+            // Otherwise anyone could post to the chat
+            if chat_api.token.trim().is_empty() {
+                bail!("The chat API's token can't be empty");
+            }
+        }
+
+        for heartbeat in &mut self.heartbeats {
+            if let Some(key) = &mut heartbeat.key {
+                *key = resolve_secret(key)
+                    .with_context(|| "Failed to read a server list heartbeat's key")?;
+            }
+        }
+
+        if let Some(votifier) = &mut self.votifier {
+            votifier.token = resolve_secret(&votifier.token)
+                .with_context(|| "Failed to read the Votifier token")?;
+            // Otherwise anyone could forge votes
+            if votifier.token.trim().is_empty() {
+                bail!("The Votifier token can't be empty");
+            }
+        }
+
+        if let Some(stream_alerts) = &mut self.stream_alerts {
+            stream_alerts.twitch_client_secret =
+                resolve_secret(&stream_alerts.twitch_client_secret)
+                    .with_context(|| "Failed to read the Twitch client secret")?;
+        }
+
+        if let Some(dsn) = self
+            .crash_reporting
+            .as_mut()
+            .and_then(|c| c.sentry_dsn.as_mut())
+        {
+            *dsn = resolve_secret(dsn).with_context(|| "Failed to read the Sentry DSN")?;
+        }
+
+        Ok(())
+    }
+
+    /// Use the settings of the server named `name` in place of `[minecraft]`
+    ///
+    /// The server's name is used as its instance label if it doesn't have one.
+    pub fn select_server(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        let idx = self
+            .servers
+            .iter()
+            .position(|server| server.name == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = self.servers.iter().map(|s| s.name.as_str()).collect();
+                anyhow!(
+                    "No server named \"{}\" is defined in the config (defined servers: {})",
+                    name,
+                    if names.is_empty() {
+                        "none".into()
+                    } else {
+                        names.join(", ")
+                    }
+                )
+            })?;
+
+        let server = self.servers.remove(idx);
+        self.minecraft = server.minecraft;
+        if self.minecraft.instance.is_none() {
+            self.minecraft.instance = Some(server.name);
+        }
+
         Ok(())
     }
 
+    /// Use the staging variant of the server in `[minecraft]`, returning the
+    /// path of the server it's a variant of
+    ///
+    /// The staging server runs in its own folder, so it's moved there, and it
+    /// shouldn't speak for the real server, so its outward-facing features
+    /// are turned off.
+    pub fn use_staging(&mut self) -> Result<PathBuf, anyhow::Error> {
+        let staging = self.minecraft.staging.clone().ok_or_else(|| {
+            anyhow!("No staging server is defined in the config (add a `staging` section to the server)")
+        })?;
+        let production_path = self.minecraft.server_path.clone();
+        let jar = production_path
+            .file_name()
+            .ok_or_else(|| anyhow!("{:?} isn't a server jar", production_path))?;
+
+        self.minecraft.server_path = staging.dir.join(jar);
+        if let Some(memory) = staging.memory {
+            self.minecraft.memory = memory;
+        }
+        if let Some(jvm_flags) = staging.jvm_flags {
+            self.minecraft.jvm_flags = Some(jvm_flags);
+        }
+        self.minecraft.instance = Some(match &self.minecraft.instance {
+            Some(instance) => format!("{}-staging", instance),
+            None => "staging".into(),
+        });
+        self.disable_outward_features();
+
+        Ok(production_path)
+    }
+
+    /// Turns off everything that would let a copy of a server speak for it
+    /// (like the Discord bridge) or take its ports
+    pub fn disable_outward_features(&mut self) {
+        self.minecraft.geyser = None;
+        if let Some(discord) = &mut self.discord {
+            discord.enable_bridge = false;
+        }
+        self.health_address = None;
+        self.status_page = None;
+        self.chat_api = None;
+        self.votifier = None;
+        self.stream_alerts = None;
+        self.heartbeats.clear();
+    }
+
     /// Setup a file watcher to be notified when the config file changes
     ///
     /// This spawns a separate thread to watch the config file because there aren't
@@ -137,6 +468,97 @@ impl Config {
     }
 }
 
+/// Resolves a secret that may be given as a reference to somewhere else, so
+/// that it doesn't have to be written in the config file
+///
+/// `env:NAME` is replaced with the value of the environment variable `NAME`,
+/// and `file:PATH` with the contents of the file at `PATH` (without
+/// surrounding whitespace). Other values are returned as-is.
+pub fn resolve_secret(value: &str) -> Result<String, anyhow::Error> {
+    if let Some(name) = value.strip_prefix("env:") {
+        std::env::var(name).with_context(|| format!("Failed to read environment variable {}", name))
+    } else if let Some(path) = value.strip_prefix("file:") {
+        std::fs::read_to_string(path)
+            .map(|contents| contents.trim().to_string())
+            .with_context(|| format!("Failed to read {:?}", path))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Sets the value at `path` in `root` to `raw`, creating tables as needed
+///
+/// Arrays (like `server`) are indexed with numbers.
+fn set_value(root: &mut toml::Value, path: &[String], raw: &str) -> Result<(), anyhow::Error> {
+    let (last, parents) = path
+        .split_last()
+        .filter(|(last, _)| !last.is_empty())
+        .ok_or_else(|| anyhow!("No config key given"))?;
+
+    let mut current = root;
+    for key in parents {
+        current = match current {
+            toml::Value::Table(table) => table
+                .entry(key.clone())
+                .or_insert_with(|| toml::Value::Table(Default::default())),
+            toml::Value::Array(array) => key
+                .parse::<usize>()
+                .ok()
+                .and_then(move |idx| array.get_mut(idx))
+                .ok_or_else(|| anyhow!("`{}` is not a valid index", key))?,
+            _ => return Err(anyhow!("`{}` is not a table", key)),
+        };
+    }
+
+    let table = current
+        .as_table_mut()
+        .ok_or_else(|| anyhow!("The parent of `{}` is not a table", last))?;
+    let value = match table.get(last) {
+        Some(toml::Value::String(_)) => toml::Value::String(raw.into()),
+        _ => parse_value(raw),
+    };
+    table.insert(last.clone(), value);
+
+    Ok(())
+}
+
+/// Parses `raw` as a TOML value, treating it as a string if it isn't one
+fn parse_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.into()))
+}
+
+/// A staging variant of a server for testing changes before they're promoted
+/// to it with `promote`
+///
+/// It runs the same jar in its own folder, with these settings changed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Staging {
+    /// The folder the staging server runs in
+    pub dir: PathBuf,
+    /// The port the staging server listens on (defaults to the server's port
+    /// plus one)
+    pub port: Option<u16>,
+    /// The world the staging server loads (defaults to the server's)
+    pub world: Option<String>,
+    /// Memory in megabytes for the staging server (defaults to the server's)
+    pub memory: Option<u16>,
+    /// JVM flags for the staging server (defaults to the server's)
+    pub jvm_flags: Option<String>,
+}
+
+/// A named server defined with `[[server]]`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServerProfile {
+    /// The name the server is picked with (`--server <name>`)
+    pub name: String,
+    /// The server's settings, which are the same as the ones in `[minecraft]`
+    #[serde(flatten)]
+    pub minecraft: Minecraft,
+}
+
 /// Minecraft-related config options
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Minecraft {
@@ -146,6 +568,103 @@ pub struct Minecraft {
     pub memory: u16,
     /// Custom flags to pass to the JVM
     pub jvm_flags: Option<String>,
+    /// A label identifying this server in logs
+    pub instance: Option<String>,
+    /// Priority, affinity, and cgroup limits for the server process
+    pub resources: Option<Resources>,
+    /// An account to run the server process as (Unix only)
+    pub run_as: Option<RunAsUser>,
+    /// Download and set up a server jar on first run
+    pub provision: Option<Provision>,
+    /// Periodically check world sizes and free disk space
+    pub disk_monitoring: Option<DiskMonitoring>,
+    /// Log the JVM's garbage collection and report long pauses
+    pub gc_logging: Option<GcLogging>,
+    /// The folder backups are restored from (defaults to `backups` next to
+    /// the server jar)
+    pub backup_dir: Option<PathBuf>,
+    /// How often (in minutes) to run `list` to correct the wrapper's record of
+    /// online players (0 disables this)
+    #[serde(default = "Minecraft::default_player_list_interval")]
+    pub player_list_interval: u64,
+    /// Agree to the Minecraft EULA without asking if the server requires it
+    ///
+    /// If this is off, the wrapper asks for confirmation in the console first.
+    #[serde(default = "Minecraft::default_auto_agree_eula")]
+    pub auto_agree_eula: bool,
+    /// A shell command to run before the server starts
+    ///
+    /// The server isn't started if the command fails.
+    pub pre_start_cmd: Option<String>,
+    /// A shell command to run after the server stops
+    pub post_stop_cmd: Option<String>,
+    /// How long (in seconds) `pre_start_cmd` and `post_stop_cmd` may run for
+    #[serde(default = "Minecraft::default_hook_timeout")]
+    pub hook_timeout: u64,
+    /// Attach to a server started by other means instead of starting one
+    pub attach: Option<Attach>,
+    /// Run a Geyser standalone proxy alongside the server
+    pub geyser: Option<Geyser>,
+    /// Keywords or patterns in console output to raise alerts for
+    pub watchlist: Option<Vec<WatchRule>>,
+    /// Settings for `maintenance on`
+    pub maintenance: Option<Maintenance>,
+    /// Let players vote to restart the server with `!restartvote`
+    pub restart_vote: Option<RestartVote>,
+    /// How long (in seconds) players are warned before `restart-servers`
+    /// restarts the server
+    pub restart_warning: Option<u64>,
+    /// Detect players that are away from keyboard
+    pub afk: Option<Afk>,
+    /// Announce when the player count reaches milestones or a new peak
+    pub player_count_notifications: Option<PlayerCountNotifications>,
+    /// Rules for how individual players' joins and leaves are announced on
+    /// Discord
+    pub player_notifications: Option<Vec<PlayerNotification>>,
+    /// Templates for recognizing console messages on servers that aren't
+    /// running in English
+    pub localized_messages: Option<LocalizedMessages>,
+    /// Templates for chat messages in a custom format, with `{name}` and
+    /// `{msg}` placeholders
+    pub chat_formats: Option<Vec<String>>,
+    /// Templates for private messages in a custom format, with `{from}`,
+    /// `{to}`, and `{msg}` placeholders
+    pub whisper_formats: Option<Vec<String>>,
+    /// A staging variant of the server, run with `--staging`
+    pub staging: Option<Staging>,
+}
+
+impl Minecraft {
+    fn default_player_list_interval() -> u64 {
+        5
+    }
+
+    fn default_auto_agree_eula() -> bool {
+        true
+    }
+
+    fn default_hook_timeout() -> u64 {
+        60
+    }
+
+    /// Returns the names of the options that are different in `other`
+    pub fn changed_options(&self, other: &Self) -> Vec<String> {
+        let tables = (toml::Value::try_from(self), toml::Value::try_from(other));
+        let (ours, theirs) = match tables {
+            (Ok(toml::Value::Table(ours)), Ok(toml::Value::Table(theirs))) => (ours, theirs),
+            _ => return vec![],
+        };
+
+        let mut changed: Vec<_> = ours
+            .keys()
+            .chain(theirs.keys())
+            .filter(|key| ours.get(*key) != theirs.get(*key))
+            .cloned()
+            .collect();
+        changed.sort();
+        changed.dedup();
+        changed
+    }
 }
 
 impl Default for Minecraft {
@@ -154,17 +673,515 @@ impl Default for Minecraft {
             server_path: "./server.jar".into(),
             memory: 1024,
             jvm_flags: None,
+            instance: None,
+            resources: None,
+            run_as: None,
+            provision: None,
+            disk_monitoring: None,
+            gc_logging: None,
+            backup_dir: None,
+            player_list_interval: Self::default_player_list_interval(),
+            auto_agree_eula: Self::default_auto_agree_eula(),
+            pre_start_cmd: None,
+            post_stop_cmd: None,
+            hook_timeout: Self::default_hook_timeout(),
+            attach: None,
+            geyser: None,
+            watchlist: None,
+            maintenance: None,
+            restart_vote: None,
+            restart_warning: None,
+            afk: None,
+            player_count_notifications: None,
+            player_notifications: None,
+            localized_messages: None,
+            chat_formats: None,
+            whisper_formats: None,
+            staging: None,
+        }
+    }
+}
+
+/// Config options for setting up a server on first run
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Provision {
+    /// The kind of server to download
+    pub flavor: Flavor,
+    /// The Minecraft version to download a server for (or `latest`)
+    pub version: String,
+    /// The mod loader version to install (for Fabric, Forge, and NeoForge)
+    ///
+    /// The newest stable loader release is used if this is not set.
+    pub loader_version: Option<String>,
+    /// A path or URL to a Modrinth (`.mrpack`) or CurseForge modpack to install
+    ///
+    /// The pack determines the Minecraft and loader versions that are used.
+    pub modpack: Option<String>,
+    /// API key used to download mods for CurseForge modpacks
+    pub curseforge_api_key: Option<String>,
+    /// Agree to the Minecraft EULA while setting up the server
+    #[serde(default)]
+    pub agree_to_eula: bool,
+    /// How often (in hours) to check for a newer server jar
+    ///
+    /// Update checks are disabled if this is not set.
+    pub update_check_interval: Option<u64>,
+}
+
+/// Config options for attaching to an already-running server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Attach {
+    /// The address of the server's RCON interface
+    #[serde(default = "Attach::default_rcon_address")]
+    pub rcon_address: SocketAddr,
+    /// The server's `rcon.password`
+    pub rcon_password: String,
+}
+
+impl Attach {
+    fn default_rcon_address() -> SocketAddr {
+        ([127, 0, 0, 1], 25575).into()
+    }
+}
+
+impl From<Attach> for AttachConfig {
+    fn from(attach: Attach) -> Self {
+        AttachConfig {
+            rcon_address: attach.rcon_address,
+            rcon_password: attach.rcon_password,
+        }
+    }
+}
+
+/// Templates for console messages printed in a language other than English
+///
+/// Each template is matched against the whole message, with placeholders like
+/// `{name}` standing in for the parts that vary.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LocalizedMessages {
+    /// Templates for a player leaving, with a `{name}` placeholder
+    #[serde(default)]
+    pub player_logout: Vec<String>,
+    /// Templates for a player losing connection, with `{name}` and `{reason}`
+    /// placeholders
+    #[serde(default)]
+    pub player_lost_connection: Vec<String>,
+    /// Templates for the response to `list`, with `{online}`, `{max}`, and
+    /// `{players}` placeholders
+    #[serde(default)]
+    pub player_list: Vec<String>,
+}
+
+impl From<LocalizedMessages> for MessagePatterns {
+    fn from(messages: LocalizedMessages) -> Self {
+        MessagePatterns {
+            player_logout: messages.player_logout,
+            player_lost_connection: messages.player_lost_connection,
+            player_list: messages.player_list,
+            ..MessagePatterns::default()
+        }
+    }
+}
+
+/// Config options for player count notifications
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerCountNotifications {
+    /// Player counts to announce when they're reached (at most once a day each)
+    #[serde(default)]
+    pub milestones: Vec<u32>,
+    /// Announce when more players are online than ever before
+    #[serde(default = "PlayerCountNotifications::default_all_time_peak")]
+    pub all_time_peak: bool,
+}
+
+impl PlayerCountNotifications {
+    fn default_all_time_peak() -> bool {
+        true
+    }
+}
+
+/// A rule for how a player's joins and leaves are announced on Discord
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlayerNotification {
+    /// The player's name (case-insensitive)
+    pub player: String,
+    /// The events the rule applies to
+    #[serde(default = "PlayerNotification::default_events")]
+    pub events: Vec<PlayerEvent>,
+    /// A mention (like `<@&role-id>`) to include in the announcement
+    pub discord_mention: Option<String>,
+    /// Don't announce these events at all
+    #[serde(default)]
+    pub silence: bool,
+}
+
+impl PlayerNotification {
+    fn default_events() -> Vec<PlayerEvent> {
+        vec![PlayerEvent::Join, PlayerEvent::Leave]
+    }
+}
+
+/// Events that player notification rules can apply to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlayerEvent {
+    Join,
+    Leave,
+}
+
+/// Config options for AFK detection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Afk {
+    /// How long (in minutes) a player has to be inactive to be AFK
+    #[serde(default = "Afk::default_idle_minutes")]
+    pub idle_minutes: u64,
+    /// Check player positions every minute so that moving counts as activity
+    #[serde(default)]
+    pub position_checks: bool,
+    /// Kick AFK players when the server is nearly full
+    #[serde(default)]
+    pub kick_when_full: bool,
+    /// AFK players are kicked when this many or fewer slots are free
+    #[serde(default = "Afk::default_free_slots")]
+    pub free_slots: u32,
+    /// The message AFK players are kicked with
+    #[serde(default = "Afk::default_kick_message")]
+    pub kick_message: String,
+}
+
+impl Afk {
+    fn default_idle_minutes() -> u64 {
+        10
+    }
+
+    fn default_free_slots() -> u32 {
+        1
+    }
+
+    fn default_kick_message() -> String {
+        "You were kicked for being AFK while the server is full".into()
+    }
+}
+
+/// Config options for restart votes
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RestartVote {
+    /// The fraction of online players that must vote to restart
+    #[serde(default = "RestartVote::default_quorum")]
+    pub quorum: f32,
+    /// The minimum number of votes needed to restart
+    #[serde(default = "RestartVote::default_min_votes")]
+    pub min_votes: u32,
+    /// How long (in seconds) a vote stays open
+    #[serde(default = "RestartVote::default_duration")]
+    pub duration: u64,
+    /// How long (in minutes) after a vote ends before another can be started
+    #[serde(default = "RestartVote::default_cooldown")]
+    pub cooldown: u64,
+}
+
+impl RestartVote {
+    fn default_quorum() -> f32 {
+        0.5
+    }
+
+    fn default_min_votes() -> u32 {
+        2
+    }
+
+    fn default_duration() -> u64 {
+        120
+    }
+
+    fn default_cooldown() -> u64 {
+        30
+    }
+}
+
+/// Config options for maintenance mode
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Maintenance {
+    /// Players besides operators that may stay on during maintenance
+    #[serde(default)]
+    pub admins: Vec<String>,
+    /// The message players are kicked with
+    #[serde(default = "Maintenance::default_kick_message")]
+    pub kick_message: String,
+    /// A MOTD to use during maintenance (takes effect when the server restarts)
+    pub motd: Option<String>,
+}
+
+impl Maintenance {
+    fn default_kick_message() -> String {
+        "The server is down for maintenance, please check back soon!".into()
+    }
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self {
+            admins: vec![],
+            kick_message: Self::default_kick_message(),
+            motd: None,
+        }
+    }
+}
+
+/// A keyword or pattern in console output to raise an alert for
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WatchRule {
+    /// The text to look for
+    pub pattern: String,
+    /// Treat `pattern` as a regular expression
+    #[serde(default)]
+    pub regex: bool,
+    /// The minimum number of seconds between alerts for this rule
+    #[serde(default = "WatchRule::default_cooldown")]
+    pub cooldown: u64,
+    /// Also send alerts to the Discord channel
+    #[serde(default)]
+    pub discord: bool,
+    /// A mention (like `<@&role-id>`) to include in Discord alerts
+    pub discord_mention: Option<String>,
+}
+
+impl WatchRule {
+    fn default_cooldown() -> u64 {
+        60
+    }
+}
+
+/// Config options for running Geyser alongside the server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Geyser {
+    /// Path to the Geyser standalone jar
+    pub jar_path: PathBuf,
+    /// Amount of memory in megabytes to allocate for Geyser
+    #[serde(default = "Geyser::default_memory")]
+    pub memory: u16,
+    /// Floodgate's `username-prefix`, used to tell which players joined from
+    /// Bedrock Edition
+    #[serde(default = "Geyser::default_bedrock_prefix")]
+    pub bedrock_prefix: String,
+}
+
+impl Geyser {
+    fn default_memory() -> u16 {
+        512
+    }
+
+    fn default_bedrock_prefix() -> String {
+        ".".into()
+    }
+}
+
+/// Config options for monitoring disk usage
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiskMonitoring {
+    /// How often (in minutes) to measure disk usage
+    #[serde(default = "DiskMonitoring::default_check_interval")]
+    pub check_interval: u64,
+    /// Alert when free space on the server's disk falls below this many
+    /// megabytes
+    pub min_free_space: u64,
+}
+
+impl DiskMonitoring {
+    fn default_check_interval() -> u64 {
+        10
+    }
+}
+
+/// Config options for garbage collection logging
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GcLogging {
+    /// Report garbage collection pauses longer than this many milliseconds
+    #[serde(default = "GcLogging::default_long_pause_ms")]
+    pub long_pause_ms: u64,
+}
+
+impl GcLogging {
+    fn default_long_pause_ms() -> u64 {
+        500
+    }
+}
+
+/// The account to run the server process as
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RunAsUser {
+    /// The name of the user to run the server as
+    pub user: String,
+    /// The name of the group to run the server as (defaults to the user's
+    /// primary group)
+    pub group: Option<String>,
+}
+
+impl From<RunAsUser> for RunAs {
+    fn from(run_as: RunAsUser) -> Self {
+        Self {
+            user: run_as.user,
+            group: run_as.group,
+        }
+    }
+}
+
+/// Resource-related config options for the server process
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Resources {
+    /// Niceness to run the server with (-20 to 19)
+    pub nice: Option<i8>,
+    /// IO scheduling class to run the server with
+    pub ionice_class: Option<IoNiceClass>,
+    /// IO scheduling priority (0 to 7) within `ionice_class`
+    ///
+    /// Defaults to 4 if a class that uses it is set.
+    pub ionice_level: Option<u8>,
+    /// The CPUs the server is allowed to run on
+    pub cpu_affinity: Option<Vec<usize>>,
+    /// A cgroup to run the server in (Linux only)
+    pub cgroup: Option<Cgroup>,
+}
+
+/// IO scheduling classes, as understood by `ionice`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoNiceClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+/// Cgroup-related config options for the server process
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cgroup {
+    /// Name of the cgroup to create under `/sys/fs/cgroup`
+    #[serde(default = "Cgroup::default_name")]
+    pub name: String,
+    /// Maximum memory usage in megabytes
+    pub memory_max: Option<u64>,
+    /// Maximum CPU usage as a number of CPUs
+    pub cpu_max: Option<f32>,
+}
+
+impl Cgroup {
+    fn default_name() -> String {
+        "mc-server-wrapper".into()
+    }
+}
+
+impl From<Resources> for ResourceLimits {
+    fn from(resources: Resources) -> Self {
+        let ionice_level = resources.ionice_level.unwrap_or(4);
+
+        Self {
+            nice: resources.nice,
+            ionice: resources.ionice_class.map(|class| match class {
+                IoNiceClass::Realtime => IoPriority::Realtime(ionice_level),
+                IoNiceClass::BestEffort => IoPriority::BestEffort(ionice_level),
+                IoNiceClass::Idle => IoPriority::Idle,
+            }),
+            cpu_affinity: resources.cpu_affinity,
+            cgroup: resources.cgroup.map(|cgroup| CgroupLimits {
+                name: cgroup.name,
+                memory_max: cgroup.memory_max,
+                cpu_max: cgroup.cpu_max,
+            }),
         }
     }
 }
 
 /// Discord-related config options
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Discord {
     pub enable_bridge: bool,
     pub token: String,
     pub channel_id: NonZeroU64,
     pub update_status: bool,
+    /// Bridge private messages between players to Discord
+    #[serde(default)]
+    pub bridge_whispers: bool,
+    /// Post a summary of the server's uptime to Discord each day
+    #[serde(default)]
+    pub daily_summary: bool,
+    /// IDs of Discord users who can be mentioned with `@name` from in-game chat
+    #[serde(default)]
+    pub mentionable_users: Vec<NonZeroU64>,
+    /// Templates for the bot's status to cycle between while the server is
+    /// running (the list of online players is shown if there are none)
+    #[serde(default)]
+    pub status_templates: Vec<String>,
+    /// How long (in seconds) to show each status template for
+    #[serde(default = "Discord::default_status_interval")]
+    pub status_interval: u64,
+    /// IDs of roles whose members may run console commands with `/run` or
+    /// `!run`
+    #[serde(default)]
+    pub run_roles: Vec<NonZeroU64>,
+    /// Let members add their Minecraft account to the whitelist with
+    /// `/whitelist me`
+    #[serde(default)]
+    pub self_whitelist: bool,
+    /// IDs of Discord users whose messages aren't bridged to Minecraft
+    #[serde(default)]
+    pub ignore_users: Vec<NonZeroU64>,
+    /// Regular expressions for console lines that shouldn't be bridged to
+    /// Discord
+    #[serde(default)]
+    pub filters: Vec<String>,
+    /// Bridge players joining and leaving the game to Discord
+    #[serde(default = "Discord::default_bridge_joins")]
+    pub bridge_joins: bool,
+    /// Keep a message showing the server's status pinned in the bridged
+    /// channel
+    #[serde(default)]
+    pub status_embed: bool,
+    /// The address players connect to, shown in the status message
+    pub server_address: Option<String>,
+    /// Show player avatars in the status message
+    ///
+    /// Discord fetches them from mc-heads.net using the players' UUIDs.
+    #[serde(default)]
+    pub player_avatars: bool,
+    /// Send a message with the server's world loading progress while it
+    /// starts, edited as it loads
+    #[serde(default)]
+    pub startup_progress: bool,
+    /// The ID of a voice channel to rename to show the player count
+    pub player_count_channel: Option<NonZeroU64>,
+    /// Domains that bridged links can point to (including their subdomains),
+    /// or empty to allow links to any domain
+    #[serde(default)]
+    pub link_domains: Vec<String>,
+    /// Shown before messages bridged from Discord in Minecraft
+    #[serde(default = "Discord::default_chat_prefix")]
+    pub chat_prefix: String,
+    /// Which way chat is bridged
+    #[serde(default)]
+    pub bridge_direction: BridgeDirection,
+}
+
+impl Discord {
+    fn default_bridge_joins() -> bool {
+        true
+    }
+
+    fn default_chat_prefix() -> String {
+        "[D] ".into()
+    }
+
+    /// Compiles `filters`, failing if any of them are invalid
+    pub fn bridge_filters(&self) -> Result<regex::RegexSet, regex::Error> {
+        regex::RegexSet::new(&self.filters)
+    }
+
+    /// Returns the filter for links to `link_domains`
+    pub fn link_filter(&self) -> LinkFilter {
+        LinkFilter::new(&self.link_domains)
+    }
+
+    fn default_status_interval() -> u64 {
+        60
+    }
 }
 
 impl Default for Discord {
@@ -174,12 +1191,198 @@ impl Default for Discord {
             token: "".into(),
             channel_id: NonZeroU64::new(123).unwrap(),
             update_status: true,
+            bridge_whispers: false,
+            daily_summary: false,
+            mentionable_users: vec![],
+            status_templates: vec![],
+            status_interval: Self::default_status_interval(),
+            run_roles: vec![],
+            self_whitelist: false,
+            ignore_users: vec![],
+            filters: vec![],
+            bridge_joins: true,
+            status_embed: false,
+            server_address: None,
+            player_avatars: false,
+            startup_progress: false,
+            player_count_channel: None,
+            link_domains: vec![],
+            chat_prefix: Self::default_chat_prefix(),
+            bridge_direction: BridgeDirection::default(),
+        }
+    }
+}
+
+/// Which way chat is bridged between Discord and Minecraft
+///
+/// Announcements from the wrapper (like the server crashing) are sent to
+/// Discord either way.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeDirection {
+    #[default]
+    Both,
+    /// Only Discord messages are bridged to Minecraft
+    ToMinecraft,
+    /// Only chat, joins, and leaves in Minecraft are bridged to Discord
+    ToDiscord,
+}
+
+impl BridgeDirection {
+    pub fn to_minecraft(self) -> bool {
+        self != BridgeDirection::ToDiscord
+    }
+
+    pub fn to_discord(self) -> bool {
+        self != BridgeDirection::ToMinecraft
+    }
+}
+
+/// Config options for the public status page
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StatusPage {
+    /// The address to serve the status page on
+    pub address: SocketAddr,
+    /// The address players connect to, shown on the page
+    ///
+    /// `discord.server_address` is used if this isn't set.
+    pub server_address: Option<String>,
+}
+
+/// Config options for the chat API
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChatApi {
+    /// The address to serve the API on
+    pub address: SocketAddr,
+    /// The token requests have to send in an `Authorization: Bearer <token>`
+    /// header
+    pub token: String,
+}
+
+/// A request sent to a server list periodically to keep the server's listing
+/// up to date
+///
+/// `{key}`, `{online}`, `{players}`, `{max_players}`, and `{version}` in the
+/// URL and body are filled in when it's sent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    /// The URL to request
+    pub url: String,
+    /// The API key for the server list, filled in for `{key}`
+    pub key: Option<String>,
+    /// A JSON body to POST (the URL is requested with GET if this isn't set)
+    pub body: Option<String>,
+    /// How often to send the heartbeat
+    #[serde(default = "Heartbeat::default_interval_minutes")]
+    pub interval_minutes: u64,
+}
+
+impl Heartbeat {
+    fn default_interval_minutes() -> u64 {
+        5
+    }
+}
+
+/// Config options for receiving votes from server lists with the NuVotifier
+/// protocol
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Votifier {
+    /// The address to receive votes on
+    #[serde(default = "Votifier::default_address")]
+    pub address: SocketAddr,
+    /// The token votes are signed with, which is given to server lists
+    pub token: String,
+    /// Thank voters in Minecraft chat
+    #[serde(default = "Votifier::default_announce")]
+    pub announce: bool,
+    /// Also thank voters in the bridged Discord channel
+    #[serde(default = "Votifier::default_announce")]
+    pub discord: bool,
+    /// Commands to run when someone votes, where `{player}` is replaced with
+    /// their name
+    #[serde(default)]
+    pub rewards: Vec<String>,
+}
+
+impl Votifier {
+    fn default_address() -> SocketAddr {
+        ([0, 0, 0, 0], 8192).into()
+    }
+
+    fn default_announce() -> bool {
+        true
+    }
+}
+
+/// Config options for announcing when streamers go live on Twitch
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamAlerts {
+    /// The client ID of a Twitch application
+    pub twitch_client_id: String,
+    /// The client secret of the Twitch application
+    pub twitch_client_secret: String,
+    /// The logins of the streamers to announce
+    pub streamers: Vec<String>,
+    /// How often to check whether the streamers are live
+    #[serde(default = "StreamAlerts::default_check_interval_minutes")]
+    pub check_interval_minutes: u64,
+    /// Also announce streams in the bridged Discord channel
+    #[serde(default = "StreamAlerts::default_discord")]
+    pub discord: bool,
+}
+
+impl StreamAlerts {
+    fn default_check_interval_minutes() -> u64 {
+        2
+    }
+
+    fn default_discord() -> bool {
+        true
+    }
+}
+
+/// Config options for reporting the wrapper's panics and errors
+///
+/// Exactly one of `sentry_dsn` and `url` should be set.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CrashReporting {
+    /// The DSN of a Sentry project to send reports to
+    pub sentry_dsn: Option<String>,
+    /// A URL to POST reports to as JSON
+    pub url: Option<String>,
+    /// Report errors the wrapper logs as well as panics
+    #[serde(default = "CrashReporting::default_report_errors")]
+    pub report_errors: bool,
+}
+
+impl CrashReporting {
+    fn default_report_errors() -> bool {
+        true
+    }
+}
+
+/// The server commands an alias runs
+///
+/// Placeholders like `{player}` in the commands are replaced by the alias's
+/// arguments, in the order the placeholders first appear.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Alias {
+    Command(String),
+    Commands(Vec<String>),
+}
+
+impl Alias {
+    pub fn commands(&self) -> &[String] {
+        match self {
+            Alias::Command(command) => std::slice::from_ref(command),
+            Alias::Commands(commands) => commands,
         }
     }
 }
 
 /// Logging-related config options
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Logging {
     /// Logging level for mc-server-wrapper dependencies
     ///
@@ -197,6 +1400,15 @@ pub struct Logging {
     ///
     /// This only affects file logging.
     pub discord: log::Level,
+    /// How many lines of output the TUI keeps
+    #[serde(default = "Logging::default_tui_max_records")]
+    pub tui_max_records: usize,
+}
+
+impl Logging {
+    fn default_tui_max_records() -> usize {
+        2000
+    }
 }
 
 impl Default for Logging {
@@ -205,6 +1417,7 @@ impl Default for Logging {
             all: log::Level::Warn,
             self_level: log::Level::Debug,
             discord: log::Level::Info,
+            tui_max_records: Logging::default_tui_max_records(),
         }
     }
 }
@@ -218,3 +1431,217 @@ enum LevelDef {
     Debug,
     Trace,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        [logging]
+        all = "Warn"
+        self = "Info"
+        discord = "Info"
+
+        [[server]]
+        name = "survival"
+        server_path = "./survival/server.jar"
+        memory = 4096
+
+        [[server]]
+        name = "creative"
+        server_path = "./creative/server.jar"
+        memory = 2048
+        jvm_flags = "-XX:+UseG1GC"
+        instance = "build"
+
+        [server.afk]
+        idle_minutes = 5
+    "#;
+
+    #[test]
+    fn aliases() {
+        let config: Config = toml::from_str(
+            r#"
+            [aliases]
+            night = "time set night"
+            welcome = ["give {player} bread 16", "msg {player} Welcome!"]
+
+            [logging]
+            all = "Warn"
+            self = "Info"
+            discord = "Info"
+        "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.aliases["night"].commands(), ["time set night"]);
+        assert_eq!(config.aliases["welcome"].commands().len(), 2);
+    }
+
+    #[test]
+    fn bridge_direction() {
+        let config: Config = toml::from_str(
+            r#"
+            [discord]
+            enable_bridge = true
+            token = "abc"
+            channel_id = 456
+            update_status = true
+            chat_prefix = "[Discord] "
+            bridge_direction = "to_minecraft"
+
+            [logging]
+            all = "Warn"
+            self = "Info"
+            discord = "Info"
+        "#,
+        )
+        .unwrap();
+        let discord = config.discord.unwrap();
+
+        assert_eq!(discord.chat_prefix, "[Discord] ");
+        assert!(discord.bridge_direction.to_minecraft());
+        assert!(!discord.bridge_direction.to_discord());
+        assert_eq!(Discord::default().chat_prefix, "[D] ");
+        assert!(Discord::default().bridge_direction.to_discord());
+    }
+
+    #[test]
+    fn select_server() {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+        assert_eq!(config.servers.len(), 2);
+
+        config.select_server("creative").unwrap();
+        assert_eq!(
+            config.minecraft.server_path,
+            Path::new("./creative/server.jar")
+        );
+        assert_eq!(config.minecraft.memory, 2048);
+        assert_eq!(config.minecraft.jvm_flags.as_deref(), Some("-XX:+UseG1GC"));
+        assert_eq!(config.minecraft.instance.as_deref(), Some("build"));
+        assert_eq!(config.minecraft.afk.unwrap().idle_minutes, 5);
+    }
+
+    #[test]
+    fn name_as_instance() {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+
+        config.select_server("survival").unwrap();
+        assert_eq!(config.minecraft.instance.as_deref(), Some("survival"));
+        assert_eq!(
+            config.minecraft.player_list_interval,
+            Minecraft::default_player_list_interval()
+        );
+    }
+
+    #[test]
+    fn unknown_server() {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+
+        let err = config.select_server("skyblock").unwrap_err();
+        assert!(err.to_string().contains("survival, creative"));
+    }
+
+    #[test]
+    fn changed_options() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let (survival, creative) = (&config.servers[0].minecraft, &config.servers[1].minecraft);
+
+        assert!(survival.changed_options(survival).is_empty());
+        assert_eq!(
+            survival.changed_options(creative),
+            ["afk", "instance", "jvm_flags", "memory", "server_path"]
+        );
+    }
+
+    #[test]
+    fn fallback_utc_offset() {
+        let mut config = Config::default();
+        assert_eq!(config.fallback_utc_offset().unwrap(), None);
+
+        config.fallback_utc_offset = Some("+02:00".into());
+        assert_eq!(
+            config.fallback_utc_offset().unwrap(),
+            Some(UtcOffset::from_hms(2, 0, 0).unwrap())
+        );
+        config.fallback_utc_offset = Some("-09:30".into());
+        assert_eq!(
+            config.fallback_utc_offset().unwrap(),
+            Some(UtcOffset::from_hms(-9, -30, 0).unwrap())
+        );
+
+        config.fallback_utc_offset = Some("2".into());
+        assert!(config.fallback_utc_offset().is_err());
+    }
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn env_overrides() {
+        let mut config = Config::default();
+        config
+            .apply_env_overrides(vars(&[
+                ("MCSW_MINECRAFT__MEMORY", "4096"),
+                ("MCSW_MINECRAFT__SERVER_PATH", "/srv/mc/server.jar"),
+                ("MCSW_MINECRAFT__JVM_FLAGS", "-XX:+UseG1GC"),
+                ("MCSW_MINECRAFT__AFK__IDLE_MINUTES", "15"),
+                ("MCSW_DISCORD__ENABLE_BRIDGE", "true"),
+                ("MCSW_LOGGING__SELF", "Trace"),
+                ("PATH", "/usr/bin"),
+            ]))
+            .unwrap();
+
+        assert_eq!(config.minecraft.memory, 4096);
+        assert_eq!(
+            config.minecraft.server_path,
+            Path::new("/srv/mc/server.jar")
+        );
+        assert_eq!(config.minecraft.jvm_flags.as_deref(), Some("-XX:+UseG1GC"));
+        assert_eq!(config.minecraft.afk.unwrap().idle_minutes, 15);
+        assert!(config.discord.unwrap().enable_bridge);
+        assert_eq!(config.logging.self_level, log::Level::Trace);
+    }
+
+    #[test]
+    fn string_overrides_stay_strings() {
+        let mut config = Config::default();
+        config
+            .apply_env_overrides(vars(&[("MCSW_DISCORD__TOKEN", "1234")]))
+            .unwrap();
+
+        assert_eq!(config.discord.unwrap().token, "1234");
+    }
+
+    #[test]
+    fn invalid_override() {
+        let mut config = Config::default();
+
+        assert!(config
+            .apply_env_overrides(vars(&[("MCSW_MINECRAFT__MEMORY", "lots")]))
+            .is_err());
+        assert!(config
+            .apply_env_overrides(vars(&[("MCSW_MINECRAFT__MEMORY__MAX", "1")]))
+            .is_err());
+    }
+
+    #[test]
+    fn secrets() {
+        std::env::set_var("MCSW_TEST_SECRET", "hunter2");
+        let path = std::env::temp_dir().join(format!("mcsw-test-secret-{}", std::process::id()));
+        std::fs::write(&path, "swordfish\n").unwrap();
+
+        assert_eq!(resolve_secret("env:MCSW_TEST_SECRET").unwrap(), "hunter2");
+        assert_eq!(
+            resolve_secret(&format!("file:{}", path.display())).unwrap(),
+            "swordfish"
+        );
+        assert_eq!(resolve_secret("plain").unwrap(), "plain");
+        assert!(resolve_secret("env:MCSW_TEST_SECRET_MISSING").is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
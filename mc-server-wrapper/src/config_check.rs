@@ -0,0 +1,545 @@
+//! Validating the config file with `--check-config`
+//!
+//! Problems that would stop the wrapper from working are errors, and anything
+//! that's merely suspicious is a warning. serde ignores keys it doesn't know
+//! about, so unknown keys are found by comparing the file with the config it
+//! deserializes into.
+
+use std::{collections::BTreeMap, fmt, path::Path};
+
+use anyhow::{anyhow, Context};
+
+use crate::{
+    config::{Config, Minecraft},
+    config_migration, crash_reports,
+    properties::ServerProperties,
+    stream_alerts,
+    watchlist::Watchlist,
+};
+
+/// Less memory (in megabytes) than this is probably a mistake
+const MIN_MEMORY: u16 = 512;
+
+/// The channel ID written to a generated config
+const PLACEHOLDER_CHANNEL_ID: u64 = 123;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the config
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: String,
+    /// The line of the config file the problem is on, if known
+    pub line: Option<usize>,
+}
+
+impl Issue {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            line: None,
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            line: None,
+        }
+    }
+
+    fn at(mut self, line: Option<usize>) -> Self {
+        self.line = line;
+        self
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Error => f.write_str("error")?,
+            Severity::Warning => f.write_str("warning")?,
+        }
+        if let Some(line) = self.line {
+            write!(f, " (line {})", line)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Checks the config file at `path`, printing any problems found
+///
+/// Fails if any of the problems are errors.
+pub fn run(path: &Path) -> Result<(), anyhow::Error> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file at {:?}", path))?;
+
+    let issues = check(&contents);
+    for issue in &issues {
+        println!("{}", issue);
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|i| i.severity == Severity::Error)
+        .count();
+    match (errors, issues.len()) {
+        (0, 0) => {
+            println!("No problems found in {:?}", path);
+            Ok(())
+        }
+        (0, warnings) => {
+            println!("Found {} warning(s) in {:?}", warnings, path);
+            Ok(())
+        }
+        (errors, _) => Err(anyhow!("Found {} error(s) in {:?}", errors, path)),
+    }
+}
+
+/// Returns the problems found in the given config file contents
+pub fn check(contents: &str) -> Vec<Issue> {
+    let mut issues = vec![];
+    let raw = contents.parse::<toml::Value>().ok();
+
+    match config_migration::migrate(contents) {
+        Ok(Some(migrated)) => issues.push(Issue::warning(format!(
+            "the config is version {}, it will be upgraded to version {} the next time \
+            the wrapper starts",
+            migrated.from_version,
+            config_migration::CURRENT_VERSION
+        ))),
+        Ok(None) => {}
+        // Syntax errors are reported below
+        Err(e) if raw.is_some() => issues.push(Issue::error(e.to_string())),
+        Err(_) => {}
+    }
+
+    let config: Config = match toml::from_str(contents) {
+        Ok(config) => config,
+        Err(e) => {
+            let line = e.span().map(|span| line_of(contents, span.start));
+            issues.push(Issue::error(e.message().trim()).at(line));
+            return issues;
+        }
+    };
+
+    if let (Some(raw), Ok(parsed)) = (&raw, toml::Value::try_from(&config)) {
+        let mut unknown = vec![];
+        find_unknown_keys(raw, &parsed, &mut vec![], &mut unknown);
+        for path in unknown {
+            let line = find_key_line(contents, &path);
+            issues.push(Issue::warning(format!("unknown key `{}`", path.join("."))).at(line));
+        }
+    }
+
+    if let Err(e) = config.fallback_utc_offset() {
+        let line = find_key_line(contents, &["fallback_utc_offset".into()]);
+        issues.push(Issue::error(e.to_string()).at(line));
+    }
+
+    let mut ports = BTreeMap::new();
+    let mut check_server = |label: String, minecraft: &Minecraft, issues: &mut Vec<Issue>| {
+        if let Some(port) = check_minecraft(&label, minecraft, issues) {
+            ports.entry(port).or_insert_with(Vec::new).push(label);
+        }
+    };
+    // `[minecraft]` can be left out if servers are defined with `[[server]]`
+    let has_minecraft = raw.is_none_or(|raw| raw.get("minecraft").is_some());
+    if has_minecraft || config.servers.is_empty() {
+        check_server("[minecraft]".into(), &config.minecraft, &mut issues);
+    }
+    for (idx, server) in config.servers.iter().enumerate() {
+        if config.servers[..idx].iter().any(|s| s.name == server.name) {
+            issues.push(Issue::error(format!(
+                "more than one server is named \"{}\"",
+                server.name
+            )));
+        }
+        check_server(
+            format!("server \"{}\"", server.name),
+            &server.minecraft,
+            &mut issues,
+        );
+    }
+    for (port, labels) in ports.iter().filter(|(_, labels)| labels.len() > 1) {
+        issues.push(Issue::warning(format!(
+            "{} all use port {}, so they can't run at the same time",
+            labels.join(", "),
+            port
+        )));
+    }
+
+    if let Some(discord) = config.discord.as_ref().filter(|d| d.enable_bridge) {
+        let line = find_key_line(contents, &["discord".into(), "token".into()]);
+        if discord.token.is_empty() || discord.token == "..." {
+            issues.push(
+                Issue::error("the Discord bridge is enabled but no bot token is set").at(line),
+            );
+        }
+
+        let line = find_key_line(contents, &["discord".into(), "channel_id".into()]);
+        if u64::from(discord.channel_id) == PLACEHOLDER_CHANNEL_ID {
+            issues.push(
+                Issue::error("the Discord bridge is enabled but `channel_id` is a placeholder")
+                    .at(line),
+            );
+        }
+
+        if let Err(e) = discord.bridge_filters() {
+            let line = find_key_line(contents, &["discord".into(), "filters".into()]);
+            issues.push(Issue::error(format!("invalid Discord bridge filter: {}", e)).at(line));
+        }
+    }
+
+    if let Some(chat_api) = &config.chat_api {
+        if chat_api.token.trim().is_empty() {
+            let line = find_key_line(contents, &["chat_api".into()]);
+            issues.push(Issue::error("the chat API's token can't be empty").at(line));
+        }
+    }
+
+    for heartbeat in &config.heartbeats {
+        let valid = reqwest::Url::parse(&heartbeat.url)
+            .is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+        if !valid {
+            issues.push(Issue::error(format!(
+                "the server list heartbeat URL {:?} isn't an http or https URL",
+                heartbeat.url
+            )));
+        }
+        if heartbeat.interval_minutes == 0 {
+            issues.push(Issue::error(format!(
+                "the server list heartbeat to {:?} needs an `interval_minutes` of at least 1",
+                heartbeat.url
+            )));
+        }
+    }
+
+    if let Some(votifier) = &config.votifier {
+        if votifier.token.trim().is_empty() {
+            let line = find_key_line(contents, &["votifier".into()]);
+            issues.push(Issue::error("the Votifier token can't be empty").at(line));
+        }
+    }
+
+    if let Some(stream_alerts) = &config.stream_alerts {
+        let line = find_key_line(contents, &["stream_alerts".into()]);
+        if stream_alerts.twitch_client_id.trim().is_empty()
+            || stream_alerts.twitch_client_secret.trim().is_empty()
+        {
+            issues.push(
+                Issue::error("stream alerts need a Twitch client ID and client secret").at(line),
+            );
+        }
+        if stream_alerts.streamers.is_empty()
+            || stream_alerts.streamers.len() > stream_alerts::MAX_STREAMERS
+        {
+            issues.push(
+                Issue::error(format!(
+                    "stream alerts need between 1 and {} streamers",
+                    stream_alerts::MAX_STREAMERS
+                ))
+                .at(line),
+            );
+        }
+        if stream_alerts.check_interval_minutes == 0 {
+            issues.push(Issue::error("`check_interval_minutes` must be at least 1").at(line));
+        }
+    }
+
+    if let Some(crash_reporting) = &config.crash_reporting {
+        if let Err(e) = crash_reports::check(crash_reporting) {
+            let line = find_key_line(contents, &["crash_reporting".into()]);
+            issues.push(Issue::error(e.to_string()).at(line));
+        }
+    }
+
+    issues
+}
+
+/// Checks the settings for a single server, returning the port it uses (if
+/// it's known)
+fn check_minecraft(label: &str, minecraft: &Minecraft, issues: &mut Vec<Issue>) -> Option<u16> {
+    let server_path = &minecraft.server_path;
+    let server_dir = server_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    if minecraft.attach.is_some() {
+        if !server_dir.is_dir() {
+            issues.push(Issue::error(format!(
+                "{}: the server folder {:?} doesn't exist",
+                label, server_dir
+            )));
+        }
+    } else if !server_path.is_file() && minecraft.provision.is_none() {
+        issues.push(Issue::error(format!(
+            "{}: the server jar {:?} doesn't exist (set up `provision` to download one)",
+            label, server_path
+        )));
+    }
+
+    if minecraft.memory < MIN_MEMORY {
+        issues.push(Issue::warning(format!(
+            "{}: {} MB of memory is very little for a Minecraft server",
+            label, minecraft.memory
+        )));
+    }
+
+    if let Some(jvm_flags) = &minecraft.jvm_flags {
+        if jvm_flags.contains("-Xmx") || jvm_flags.contains("-Xms") {
+            issues.push(Issue::warning(format!(
+                "{}: `jvm_flags` sets the heap size, which conflicts with `memory`",
+                label
+            )));
+        }
+    }
+
+    if let Some(backup_dir) = &minecraft.backup_dir {
+        if !backup_dir.is_dir() {
+            issues.push(Issue::warning(format!(
+                "{}: the backup folder {:?} doesn't exist",
+                label, backup_dir
+            )));
+        }
+    }
+
+    if let Some(geyser) = &minecraft.geyser {
+        if !geyser.jar_path.is_file() {
+            issues.push(Issue::error(format!(
+                "{}: the Geyser jar {:?} doesn't exist",
+                label, geyser.jar_path
+            )));
+        }
+    }
+
+    if let Some(watchlist) = &minecraft.watchlist {
+        if let Err(e) = Watchlist::new(watchlist) {
+            issues.push(Issue::error(format!(
+                "{}: invalid watchlist pattern: {}",
+                label, e
+            )));
+        }
+    }
+
+    let properties = std::fs::read_to_string(ServerProperties::path(server_path))
+        .map(|contents| ServerProperties::parse(&contents))
+        .ok()?;
+    let port = |key, default| {
+        properties
+            .get(key)
+            .and_then(|p| p.trim().parse().ok())
+            .unwrap_or(default)
+    };
+    let server_port = port("server-port", 25565);
+    let rcon_port = port("rcon.port", 25575);
+
+    if properties.get("enable-rcon").as_deref() == Some("true") && rcon_port == server_port {
+        issues.push(Issue::error(format!(
+            "{}: `server-port` and `rcon.port` in server.properties are both {}",
+            label, server_port
+        )));
+    }
+    if let Some(attach) = &minecraft.attach {
+        if attach.rcon_address.port() != rcon_port {
+            issues.push(Issue::warning(format!(
+                "{}: `rcon_address` uses port {}, but the server's `rcon.port` is {}",
+                label,
+                attach.rcon_address.port(),
+                rcon_port
+            )));
+        }
+    }
+
+    Some(server_port)
+}
+
+/// Adds the paths of keys in `raw` that aren't in `parsed` to `unknown`
+fn find_unknown_keys(
+    raw: &toml::Value,
+    parsed: &toml::Value,
+    path: &mut Vec<String>,
+    unknown: &mut Vec<Vec<String>>,
+) {
+    match (raw, parsed) {
+        (toml::Value::Table(raw), toml::Value::Table(parsed)) => {
+            for (key, raw_value) in raw {
+                path.push(key.clone());
+                match parsed.get(key) {
+                    Some(parsed_value) => find_unknown_keys(raw_value, parsed_value, path, unknown),
+                    None => unknown.push(path.clone()),
+                }
+                path.pop();
+            }
+        }
+        (toml::Value::Array(raw), toml::Value::Array(parsed)) => {
+            for (idx, (raw, parsed)) in raw.iter().zip(parsed).enumerate() {
+                path.push(idx.to_string());
+                find_unknown_keys(raw, parsed, path, unknown);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the (1-based) line that the byte offset `offset` is on
+fn line_of(contents: &str, offset: usize) -> usize {
+    contents[..offset.min(contents.len())]
+        .lines()
+        .count()
+        .max(1)
+}
+
+/// Finds the line the key at `path` is set on
+///
+/// This only understands plain `key = value` lines and `[table]` headers,
+/// which covers the way the config is usually written. Array indices in
+/// `path` are ignored, so the first matching entry is found.
+fn find_key_line(contents: &str, path: &[String]) -> Option<usize> {
+    let path: Vec<_> = path
+        .iter()
+        .filter(|segment| segment.parse::<usize>().is_err())
+        .map(String::as_str)
+        .collect();
+    let (key, parent) = path.split_last()?;
+    let parent = parent.join(".");
+    let full = path.join(".");
+
+    let mut table = String::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            table = line.trim_matches(|c| c == '[' || c == ']').trim().into();
+            if table == full {
+                return Some(idx + 1);
+            }
+        } else if table == parent {
+            let assigned = line.split('=').next().map(|k| k.trim().trim_matches('"'));
+            if line.contains('=') && assigned == Some(*key) {
+                return Some(idx + 1);
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const LOGGING: &str = r#"
+[logging]
+all = "Warn"
+self = "Info"
+discord = "Info"
+"#;
+
+    #[test]
+    fn parse_error_line() {
+        let issues = check("[minecraft]\nserver_path = \"./server.jar\"\nmemory = \"lots\"\n");
+        let errors: Vec<_> = issues
+            .iter()
+            .filter(|i| i.severity == Severity::Error)
+            .collect();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, Some(3));
+    }
+
+    #[test]
+    fn old_version() {
+        let issues = check("[minecraft]\nserver_path = \"./server.jar\"\n");
+        assert!(issues
+            .iter()
+            .any(|i| i.severity == Severity::Warning && i.message.contains("version 0")));
+
+        let issues = check("config_version = 99\n");
+        assert!(issues
+            .iter()
+            .any(|i| i.severity == Severity::Error && i.message.contains("newer")));
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let config = format!(
+            "[minecraft]\nserver_path = \"./server.jar\"\nmemory = 2048\nmemroy = 4096\n\
+            \n[minecraft.afk]\nidle_minute = 5\n{}",
+            LOGGING
+        );
+        let issues = check(&config);
+        let unknown: Vec<_> = issues
+            .iter()
+            .filter(|i| i.message.starts_with("unknown key"))
+            .map(|i| (i.message.as_str(), i.line))
+            .collect();
+
+        assert_eq!(
+            unknown,
+            [
+                ("unknown key `minecraft.afk.idle_minute`", Some(7)),
+                ("unknown key `minecraft.memroy`", Some(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn discord_placeholders() {
+        let config = format!(
+            "[minecraft]\nserver_path = \"./server.jar\"\nmemory = 2048\n\n[discord]\n\
+            enable_bridge = true\ntoken = \"...\"\nchannel_id = 123\nupdate_status = true\n{}",
+            LOGGING
+        );
+        let issues = check(&config);
+        let discord: Vec<_> = issues
+            .iter()
+            .filter(|i| i.message.contains("Discord"))
+            .map(|i| i.line)
+            .collect();
+
+        assert_eq!(discord, [Some(7), Some(8)]);
+    }
+
+    #[test]
+    fn invalid_bridge_filter() {
+        let config = format!(
+            "[minecraft]\nserver_path = \"./server.jar\"\nmemory = 2048\n\n[discord]\n\
+            enable_bridge = true\ntoken = \"abc\"\nchannel_id = 456\nupdate_status = true\n\
+            filters = [\"(unclosed\"]\n{}",
+            LOGGING
+        );
+        let issues = check(&config);
+        let filters: Vec<_> = issues
+            .iter()
+            .filter(|i| i.message.contains("filter"))
+            .map(|i| i.line)
+            .collect();
+
+        assert_eq!(filters, [Some(10)]);
+    }
+
+    #[test]
+    fn missing_jar_and_low_memory() {
+        let config = format!(
+            "[minecraft]\nserver_path = \"./does-not-exist/server.jar\"\nmemory = 256\n{}",
+            LOGGING
+        );
+        let issues = check(&config);
+
+        assert!(issues
+            .iter()
+            .any(|i| i.severity == Severity::Error && i.message.contains("doesn't exist")));
+        assert!(issues
+            .iter()
+            .any(|i| i.severity == Severity::Warning && i.message.contains("256 MB")));
+    }
+}
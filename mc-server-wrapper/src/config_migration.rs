@@ -0,0 +1,156 @@
+//! Upgrading config files written by older versions of mc-server-wrapper
+//!
+//! Migrations are run on the document itself rather than the deserialized
+//! config so that comments and formatting in the file are kept.
+
+use std::convert::TryFrom;
+
+use anyhow::{anyhow, Context};
+use toml_edit::{value, Document, Item};
+
+use crate::config::Logging;
+
+/// The config version written by this version of mc-server-wrapper
+pub const CURRENT_VERSION: u32 = 1;
+
+/// A migration upgrades a config from one version to the next
+type Migration = fn(&mut Document) -> Result<(), anyhow::Error>;
+
+/// `MIGRATIONS[n]` upgrades a config from version `n` to version `n + 1`
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [v0_to_v1];
+
+/// A config file that was upgraded to the current version
+#[derive(Debug)]
+pub struct Migrated {
+    /// The version the config was at before being upgraded
+    pub from_version: u32,
+    /// The contents of the upgraded config file
+    pub contents: String,
+}
+
+/// Upgrades the given config file contents to the current version
+///
+/// Returns `None` if the config is already up-to-date.
+pub fn migrate(contents: &str) -> Result<Option<Migrated>, anyhow::Error> {
+    let mut doc: Document = contents.parse()?;
+    let from_version = match doc.get("config_version") {
+        None => 0,
+        Some(item) => item
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| anyhow!("config_version must be a positive integer"))?,
+    };
+
+    if from_version > CURRENT_VERSION {
+        return Err(anyhow!(
+            "config version {} is newer than this version of mc-server-wrapper supports ({})",
+            from_version,
+            CURRENT_VERSION
+        ));
+    } else if from_version == CURRENT_VERSION {
+        return Ok(None);
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(from_version as usize) {
+        migration(&mut doc).with_context(|| {
+            format!(
+                "Failed to upgrade config from version {} to {}",
+                version,
+                version + 1
+            )
+        })?;
+    }
+    doc["config_version"] = value(i64::from(CURRENT_VERSION));
+
+    Ok(Some(Migrated {
+        from_version,
+        contents: doc.to_string(),
+    }))
+}
+
+/// Configs from before versioning was added
+///
+/// The `[logging]` section used to be required, and level names had to be
+/// capitalized.
+fn v0_to_v1(doc: &mut Document) -> Result<(), anyhow::Error> {
+    match doc.get_mut("logging").and_then(Item::as_table_mut) {
+        Some(logging) => {
+            for (_, item) in logging.iter_mut() {
+                let level = match item.as_str() {
+                    Some(level) => level,
+                    None => continue,
+                };
+                let mut chars = level.chars();
+                let capitalized = chars
+                    .next()
+                    .into_iter()
+                    .flat_map(char::to_uppercase)
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect::<String>();
+                if capitalized != level {
+                    *item = value(capitalized);
+                }
+            }
+        }
+        None => {
+            let defaults: Document = toml::to_string(&Logging::default())?.parse()?;
+            doc["logging"] = Item::Table(defaults.as_table().clone());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn unversioned_config() {
+        let contents = r#"
+# the server to run
+[minecraft]
+server_path = "./server.jar" # next to the wrapper
+memory = 2048
+"#;
+
+        let migrated = migrate(contents).unwrap().unwrap();
+        assert_eq!(migrated.from_version, 0);
+        assert!(migrated.contents.contains("# the server to run"));
+        assert!(migrated.contents.contains("# next to the wrapper"));
+
+        let config: Config = toml::from_str(&migrated.contents).unwrap();
+        assert_eq!(config.config_version, CURRENT_VERSION);
+        assert_eq!(config.minecraft.memory, 2048);
+        assert_eq!(config.logging.all, log::Level::Warn);
+    }
+
+    #[test]
+    fn lowercase_levels() {
+        let contents = r#"
+[logging]
+all = "warn"
+self = "DEBUG"
+discord = "info"
+"#;
+
+        let migrated = migrate(contents).unwrap().unwrap();
+        let config: Config = toml::from_str(&migrated.contents).unwrap();
+        assert_eq!(config.logging.all, log::Level::Warn);
+        assert_eq!(config.logging.self_level, log::Level::Debug);
+        assert_eq!(config.logging.discord, log::Level::Info);
+    }
+
+    #[test]
+    fn up_to_date() {
+        let contents = toml::to_string(&Config::default()).unwrap();
+        assert!(migrate(&contents).unwrap().is_none());
+    }
+
+    #[test]
+    fn newer_version() {
+        let contents = format!("config_version = {}\n", CURRENT_VERSION + 1);
+        assert!(migrate(&contents).is_err());
+    }
+}
@@ -0,0 +1,514 @@
+//! Opt-in reporting of the wrapper's own panics and errors
+//!
+//! Nothing is sent unless `[crash_reporting]` is set in the config. When it
+//! is, panics (and, with `report_errors`, errors the wrapper logs) are sent to
+//! Sentry or POSTed as JSON to any other URL. Each report carries breadcrumbs:
+//! the last things the server and the wrapper did before the problem, like the
+//! server starting or falling behind. Breadcrumbs leave out chat and player
+//! names, and are only kept in memory.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc as std_mpsc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use log::{warn, Level};
+use mc_server_wrapper_lib::{
+    communication::ServerEventKind,
+    parse::{ConsoleMsgSpecific, ConsoleMsgType},
+};
+use once_cell::sync::OnceCell;
+use reqwest::{header::CONTENT_TYPE, Url};
+use serde_derive::Serialize;
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use time::OffsetDateTime;
+
+use crate::{config::CrashReporting, provision};
+
+/// How many breadcrumbs are kept
+const MAX_BREADCRUMBS: usize = 50;
+/// The longest a breadcrumb's message can be before it's cut short
+const MAX_BREADCRUMB_LEN: usize = 300;
+/// The most logged errors reported while the wrapper runs
+const MAX_ERROR_REPORTS: usize = 20;
+/// How long sending a report can take
+const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+static REPORTER: OnceCell<Reporter> = OnceCell::new();
+static BREADCRUMBS: Mutex<Breadcrumbs> = Mutex::new(Breadcrumbs::new());
+
+/// Something that happened before a report was sent
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Breadcrumb {
+    /// When it happened, as a Unix timestamp
+    pub timestamp: i64,
+    /// `server` for the server's events and `log` for the wrapper's logs
+    pub category: &'static str,
+    pub level: &'static str,
+    pub message: String,
+}
+
+impl Breadcrumb {
+    fn new(category: &'static str, level: Level, message: &str) -> Self {
+        let message = match message.char_indices().nth(MAX_BREADCRUMB_LEN) {
+            Some((end, _)) => format!("{}…", &message[..end]),
+            None => message.to_string(),
+        };
+
+        Self {
+            timestamp: OffsetDateTime::now_utc().unix_timestamp(),
+            category,
+            level: sentry_level(level),
+            message,
+        }
+    }
+}
+
+/// The most recent breadcrumbs, oldest first
+#[derive(Debug)]
+struct Breadcrumbs {
+    crumbs: VecDeque<Breadcrumb>,
+}
+
+impl Breadcrumbs {
+    const fn new() -> Self {
+        Self {
+            crumbs: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, crumb: Breadcrumb) {
+        if self.crumbs.len() == MAX_BREADCRUMBS {
+            self.crumbs.pop_front();
+        }
+        self.crumbs.push_back(crumb);
+    }
+
+    fn to_vec(&self) -> Vec<Breadcrumb> {
+        self.crumbs.iter().cloned().collect()
+    }
+}
+
+/// Where reports are sent
+#[derive(Debug, Clone, PartialEq)]
+enum Endpoint {
+    /// Sentry's envelope endpoint for the project in a DSN
+    Sentry { url: Url, key: String, dsn: String },
+    /// Any other URL, which the report is POSTed to as JSON
+    Url(Url),
+}
+
+impl Endpoint {
+    fn new(config: &CrashReporting) -> Result<Self, anyhow::Error> {
+        match (&config.sentry_dsn, &config.url) {
+            (Some(dsn), None) => Self::sentry(dsn),
+            (None, Some(url)) => {
+                Ok(Endpoint::Url(url.parse().with_context(|| {
+                    format!("Invalid crash reporting URL {:?}", url)
+                })?))
+            }
+            _ => Err(anyhow!(
+                "Exactly one of `sentry_dsn` and `url` must be set in [crash_reporting]"
+            )),
+        }
+    }
+
+    /// Parses a DSN like `https://<key>@o0.ingest.sentry.io/<project>`
+    fn sentry(dsn: &str) -> Result<Self, anyhow::Error> {
+        let invalid = || anyhow!("Invalid Sentry DSN {:?}", dsn);
+        let parsed: Url = dsn.parse().map_err(|_| invalid())?;
+
+        let key = parsed.username();
+        let (path, project) = parsed.path().rsplit_once('/').ok_or_else(invalid)?;
+        if key.is_empty() || project.is_empty() || !project.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let mut url = parsed.clone();
+        url.set_username("").map_err(|_| invalid())?;
+        url.set_password(None).map_err(|_| invalid())?;
+        url.set_path(&format!("{}/api/{}/envelope/", path, project));
+
+        Ok(Endpoint::Sentry {
+            url,
+            key: key.to_string(),
+            dsn: dsn.to_string(),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Reporter {
+    endpoint: Endpoint,
+    report_errors: bool,
+    client: reqwest::Client,
+    /// The errors that have been reported, so each is only sent once
+    reported_errors: Mutex<HashSet<String>>,
+}
+
+impl Reporter {
+    async fn send(
+        &self,
+        client: &reqwest::Client,
+        report: &serde_json::Value,
+    ) -> Result<(), anyhow::Error> {
+        let request = match &self.endpoint {
+            Endpoint::Sentry { url, key, dsn } => client
+                .post(url.clone())
+                .header(
+                    "X-Sentry-Auth",
+                    format!(
+                        "Sentry sentry_version=7, sentry_key={}, sentry_client=mc-server-wrapper/{}",
+                        key,
+                        env!("CARGO_PKG_VERSION")
+                    ),
+                )
+                .header(CONTENT_TYPE, "application/x-sentry-envelope")
+                .body(envelope(dsn, report)),
+            Endpoint::Url(url) => client.post(url.clone()).json(report),
+        };
+
+        request
+            .timeout(SEND_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Turns on reporting with the given settings
+pub fn init(config: &CrashReporting) -> Result<(), anyhow::Error> {
+    let reporter = Reporter {
+        endpoint: Endpoint::new(config)?,
+        report_errors: config.report_errors,
+        client: provision::http_client()?,
+        reported_errors: Mutex::new(HashSet::new()),
+    };
+    let _ = REPORTER.set(reporter);
+    Ok(())
+}
+
+/// Checks the settings for problems that would stop reports from being sent
+///
+/// A DSN given as a secret reference (see `config::resolve_secret`) can't be
+/// checked until it's resolved.
+pub fn check(config: &CrashReporting) -> Result<(), anyhow::Error> {
+    let reference = config
+        .sentry_dsn
+        .as_deref()
+        .is_some_and(|dsn| dsn.starts_with("env:") || dsn.starts_with("file:"));
+    if reference && config.url.is_none() {
+        return Ok(());
+    }
+
+    Endpoint::new(config).map(|_| ())
+}
+
+/// Records a breadcrumb for an event from the server, if it's one worth
+/// keeping
+pub fn server_event(kind: &ServerEventKind) {
+    if REPORTER.get().is_none() {
+        return;
+    }
+    if let Some((level, message)) = describe_server_event(kind) {
+        breadcrumb("server", level, &message);
+    }
+}
+
+/// Handles a record logged by the wrapper, reporting it if it's an error
+pub fn log_record(record: &log::Record) {
+    let reporter = match REPORTER.get() {
+        Some(reporter) => reporter,
+        None => return,
+    };
+    let message = record.args().to_string();
+
+    if record.level() == Level::Error && reporter.report_errors {
+        let mut reported = reporter.reported_errors.lock().unwrap();
+        if reported.len() < MAX_ERROR_REPORTS && reported.insert(message.clone()) {
+            let report = report("error", record.target(), &message, breadcrumbs());
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move {
+                    if let Err(e) = reporter.send(&reporter.client, &report).await {
+                        warn!("Failed to send an error report: {:#}", e);
+                    }
+                });
+            }
+        }
+    }
+
+    breadcrumb("log", record.level(), &message);
+}
+
+/// Reports a panic, waiting for the report to be sent
+///
+/// This is called from the panic hook, where the async runtime might not be
+/// usable, so the report is sent from a thread of its own.
+pub fn report_panic(message: &str) {
+    let reporter = match REPORTER.get() {
+        Some(reporter) => reporter,
+        None => return,
+    };
+    let report = report("fatal", "panic", message, breadcrumbs());
+
+    let (result_sender, result_receiver) = std_mpsc::channel();
+    thread::spawn(move || {
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(anyhow::Error::from)
+            .and_then(|runtime| {
+                // Connections from the main runtime's client can't be used
+                // from this one
+                let client = provision::http_client()?;
+                runtime.block_on(reporter.send(&client, &report))
+            });
+        let _ = result_sender.send(result);
+    });
+
+    match result_receiver.recv_timeout(SEND_TIMEOUT + Duration::from_secs(1)) {
+        Ok(Ok(())) => eprintln!("Sent a crash report"),
+        Ok(Err(e)) => eprintln!("Failed to send a crash report: {:#}", e),
+        Err(_) => eprintln!("Timed out sending a crash report"),
+    }
+}
+
+fn breadcrumb(category: &'static str, level: Level, message: &str) {
+    BREADCRUMBS
+        .lock()
+        .unwrap()
+        .push(Breadcrumb::new(category, level, message));
+}
+
+fn breadcrumbs() -> Vec<Breadcrumb> {
+    BREADCRUMBS.lock().unwrap().to_vec()
+}
+
+/// Describes an event from the server for a breadcrumb
+///
+/// Chat and other events that would identify players are left out.
+fn describe_server_event(kind: &ServerEventKind) -> Option<(Level, String)> {
+    Some(match kind {
+        ServerEventKind::ConsoleEvent(_, Some(specific)) => match specific {
+            ConsoleMsgSpecific::PlayerLogin { .. } => (Level::Info, "A player joined".into()),
+            ConsoleMsgSpecific::PlayerLogout { .. } => (Level::Info, "A player left".into()),
+            ConsoleMsgSpecific::FinishedLoading { time_elapsed_s } => (
+                Level::Info,
+                format!("Server finished loading in {}s", time_elapsed_s),
+            ),
+            ConsoleMsgSpecific::Overloaded { behind_ms, .. } => (
+                Level::Warn,
+                format!("Server fell behind by {}ms", behind_ms),
+            ),
+            _ => return None,
+        },
+        ServerEventKind::ConsoleEvent(msg, None) => match msg.msg_type {
+            ConsoleMsgType::Warn => (Level::Warn, msg.msg.clone()),
+            ConsoleMsgType::Error => (Level::Error, msg.msg.clone()),
+            _ => return None,
+        },
+        ServerEventKind::ServerStopped(result, reason) => {
+            let result = match result {
+                Ok(status) => status.to_string(),
+                Err(e) => e.to_string(),
+            };
+            match reason {
+                Some(reason) => (
+                    Level::Info,
+                    format!("Server stopped ({}, {:?})", result, reason),
+                ),
+                None => (Level::Warn, format!("Server stopped ({})", result)),
+            }
+        }
+        ServerEventKind::StartServerResult(Ok(())) => (Level::Info, "Server started".into()),
+        ServerEventKind::StartServerResult(Err(e)) => {
+            (Level::Error, format!("Server failed to start: {}", e))
+        }
+        ServerEventKind::Attached => (Level::Info, "Attached to a running server".into()),
+        _ => return None,
+    })
+}
+
+/// Builds a report in Sentry's event format, which is also what's POSTed to
+/// other URLs
+///
+/// `level` is one of Sentry's levels (see `sentry_level`), or `fatal`.
+fn report(
+    level: &str,
+    logger: &str,
+    message: &str,
+    breadcrumbs: Vec<Breadcrumb>,
+) -> serde_json::Value {
+    json!({
+        "event_id": event_id(),
+        "timestamp": OffsetDateTime::now_utc().unix_timestamp(),
+        "platform": "other",
+        "level": level,
+        "logger": logger,
+        "release": concat!("mc-server-wrapper@", env!("CARGO_PKG_VERSION")),
+        "message": message,
+        "contexts": {
+            "os": { "name": std::env::consts::OS },
+        },
+        "breadcrumbs": { "values": breadcrumbs },
+    })
+}
+
+/// Wraps a report in an envelope for Sentry's envelope endpoint
+fn envelope(dsn: &str, report: &serde_json::Value) -> String {
+    format!(
+        "{}\n{}\n{}\n",
+        json!({ "event_id": report["event_id"], "dsn": dsn }),
+        json!({ "type": "event" }),
+        report
+    )
+}
+
+/// Returns an ID for a report: 32 hex digits that are unique enough
+fn event_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = Sha1::new();
+    hasher.update(
+        OffsetDateTime::now_utc()
+            .unix_timestamp_nanos()
+            .to_le_bytes(),
+    );
+    hasher.update(std::process::id().to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Sentry's name for a log level
+fn sentry_level(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warn => "warning",
+        Level::Info => "info",
+        Level::Debug | Level::Trace => "debug",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(sentry_dsn: Option<&str>, url: Option<&str>) -> CrashReporting {
+        CrashReporting {
+            sentry_dsn: sentry_dsn.map(String::from),
+            url: url.map(String::from),
+            report_errors: true,
+        }
+    }
+
+    #[test]
+    fn sentry_dsn() {
+        let dsn = "https://abc123@o42.ingest.sentry.io/1234";
+
+        assert_eq!(
+            Endpoint::new(&config(Some(dsn), None)).unwrap(),
+            Endpoint::Sentry {
+                url: "https://o42.ingest.sentry.io/api/1234/envelope/"
+                    .parse()
+                    .unwrap(),
+                key: "abc123".into(),
+                dsn: dsn.into(),
+            }
+        );
+        assert!(Endpoint::new(&config(Some("https://o42.ingest.sentry.io/1234"), None)).is_err());
+        assert!(Endpoint::new(&config(Some("https://abc123@sentry.example.com/"), None)).is_err());
+    }
+
+    #[test]
+    fn one_endpoint() {
+        assert_eq!(
+            Endpoint::new(&config(None, Some("https://example.com/reports"))).unwrap(),
+            Endpoint::Url("https://example.com/reports".parse().unwrap())
+        );
+        assert!(Endpoint::new(&config(None, None)).is_err());
+        assert!(Endpoint::new(&config(
+            Some("https://abc123@o42.ingest.sentry.io/1234"),
+            Some("https://example.com/reports")
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn secret_reference() {
+        assert!(check(&config(Some("env:SENTRY_DSN"), None)).is_ok());
+        assert!(check(&config(
+            Some("env:SENTRY_DSN"),
+            Some("https://example.com/")
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn breadcrumbs_bounded() {
+        let mut crumbs = Breadcrumbs::new();
+        for i in 0..MAX_BREADCRUMBS + 5 {
+            crumbs.push(Breadcrumb::new("log", Level::Info, &i.to_string()));
+        }
+
+        let crumbs = crumbs.to_vec();
+        assert_eq!(crumbs.len(), MAX_BREADCRUMBS);
+        assert_eq!(crumbs[0].message, "5");
+        assert_eq!(
+            Breadcrumb::new("log", Level::Warn, &"a".repeat(500)).message,
+            format!("{}…", "a".repeat(MAX_BREADCRUMB_LEN))
+        );
+    }
+
+    #[test]
+    fn server_events() {
+        assert_eq!(
+            describe_server_event(&ServerEventKind::ConsoleEvent(
+                mc_server_wrapper_lib::parse::ConsoleMsg {
+                    timestamp: OffsetDateTime::UNIX_EPOCH,
+                    thread_name: "Server thread".into(),
+                    msg_type: ConsoleMsgType::Info,
+                    msg: "Cldfire left the game".into(),
+                },
+                Some(ConsoleMsgSpecific::PlayerLogout {
+                    name: "Cldfire".into()
+                })
+            )),
+            Some((Level::Info, "A player left".into()))
+        );
+        assert_eq!(
+            describe_server_event(&ServerEventKind::StdoutLine("hello".into())),
+            None
+        );
+    }
+
+    #[test]
+    fn report_contents() {
+        let crumbs = vec![Breadcrumb::new("server", Level::Info, "Server started")];
+        let report = report("error", "mc_server_wrapper", "it broke", crumbs);
+
+        assert_eq!(report["level"], "error");
+        assert_eq!(report["message"], "it broke");
+        assert_eq!(report["event_id"].as_str().unwrap().len(), 32);
+        assert_eq!(
+            report["breadcrumbs"]["values"][0]["message"],
+            "Server started"
+        );
+
+        let envelope = envelope("https://abc123@o42.ingest.sentry.io/1234", &report);
+        let lines: Vec<_> = envelope.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1], r#"{"type":"event"}"#);
+        assert_ne!(event_id(), event_id());
+    }
+}
@@ -0,0 +1,175 @@
+//! Capturing thread and heap dumps of the server's JVM for debugging hangs
+//!
+//! Dumps are taken with `jcmd` (which comes with the JDK) and saved to the
+//! `diagnostics` folder next to the server jar. Without `jcmd`, a thread dump
+//! can still be taken on Unix by sending the JVM `SIGQUIT`, which makes it
+//! print the dump to its console; that output is captured into the file
+//! instead.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use time::OffsetDateTime;
+use tokio::{fs, process::Command};
+
+/// The folder (next to the server jar) dumps are saved in
+const DIAGNOSTICS_DIRNAME: &str = "diagnostics";
+/// How long console output is captured for after sending `SIGQUIT`
+pub const SIGNAL_CAPTURE_DURATION: Duration = Duration::from_secs(3);
+
+/// The kinds of dumps that can be taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpKind {
+    Threads,
+    Heap,
+}
+
+impl DumpKind {
+    fn file_name(&self, time: OffsetDateTime) -> String {
+        match self {
+            DumpKind::Threads => format!("threads-{}.txt", time.unix_timestamp()),
+            DumpKind::Heap => format!("heap-{}.hprof", time.unix_timestamp()),
+        }
+    }
+}
+
+impl FromStr for DumpKind {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "threads" => Ok(DumpKind::Threads),
+            "heap" => Ok(DumpKind::Heap),
+            _ => Err("Usage: dump threads|heap"),
+        }
+    }
+}
+
+impl fmt::Display for DumpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DumpKind::Threads => f.write_str("thread"),
+            DumpKind::Heap => f.write_str("heap"),
+        }
+    }
+}
+
+/// How a dump was taken
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dumped {
+    /// The dump was saved to the given path
+    Saved(PathBuf),
+    /// The JVM was sent `SIGQUIT` and is printing a thread dump to its
+    /// console, which should be saved to the given path
+    Signaled(PathBuf),
+}
+
+/// Returns the path to save a dump of `kind` taken at `time` to for the
+/// server at `server_path`
+pub fn dump_path(server_path: &Path, kind: DumpKind, time: OffsetDateTime) -> PathBuf {
+    server_path
+        .with_file_name(DIAGNOSTICS_DIRNAME)
+        .join(kind.file_name(time))
+}
+
+/// Takes a dump of `kind` from the JVM with the process ID `pid`, saving it
+/// to `path`
+pub async fn dump(kind: DumpKind, pid: u32, path: PathBuf) -> Result<Dumped, anyhow::Error> {
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Failed to create {:?}", dir))?;
+
+    match kind {
+        DumpKind::Threads => match jcmd(pid, &["Thread.print", "-l"]).await? {
+            Some(output) => {
+                fs::write(&path, output)
+                    .await
+                    .with_context(|| format!("Failed to write {:?}", path))?;
+                Ok(Dumped::Saved(path))
+            }
+            None if cfg!(unix) => {
+                let status = Command::new("kill")
+                    .arg("-QUIT")
+                    .arg(pid.to_string())
+                    .status()
+                    .await
+                    .with_context(|| "Failed to run kill")?;
+                if !status.success() {
+                    return Err(anyhow!("kill exited with {}", status));
+                }
+                Ok(Dumped::Signaled(path))
+            }
+            None => Err(anyhow!("jcmd wasn't found (it comes with the JDK)")),
+        },
+        DumpKind::Heap => {
+            // jcmd resolves relative paths from the server's folder
+            let path = fs::canonicalize(dir).await?.join(path.file_name().unwrap());
+            match jcmd(pid, &["GC.heap_dump", &path.to_string_lossy()]).await? {
+                Some(_) => Ok(Dumped::Saved(path)),
+                None => Err(anyhow!(
+                    "jcmd wasn't found (it comes with the JDK and is needed for heap dumps)"
+                )),
+            }
+        }
+    }
+}
+
+/// Runs `jcmd <pid> <args>`, returning its output or `None` if jcmd isn't
+/// installed
+async fn jcmd(pid: u32, args: &[&str]) -> Result<Option<String>, anyhow::Error> {
+    let output = match Command::new("jcmd")
+        .arg(pid.to_string())
+        .args(args)
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(anyhow::Error::new(e).context("Failed to run jcmd")),
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = if stderr.trim().is_empty() {
+            stdout.trim()
+        } else {
+            stderr.trim()
+        };
+        return Err(anyhow!("jcmd exited with {}: {}", output.status, message));
+    }
+
+    Ok(Some(stdout))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn paths() {
+        let time = OffsetDateTime::from_unix_timestamp(1700000000).unwrap();
+
+        assert_eq!(
+            dump_path(Path::new("./survival/server.jar"), DumpKind::Threads, time),
+            Path::new("./survival/diagnostics/threads-1700000000.txt")
+        );
+        assert_eq!(
+            dump_path(Path::new("server.jar"), DumpKind::Heap, time),
+            Path::new("diagnostics/heap-1700000000.hprof")
+        );
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!("threads".parse(), Ok(DumpKind::Threads));
+        assert_eq!("heap".parse(), Ok(DumpKind::Heap));
+        assert!("stack".parse::<DumpKind>().is_err());
+    }
+}
@@ -0,0 +1,87 @@
+//! Flood protection for messages bridged from Discord
+//!
+//! Each message is sent to Minecraft with `tellraw`, so a Discord user
+//! spamming the bridged channel would spam the server console as well. Users
+//! can only have so many messages bridged in a short time, and the rest are
+//! dropped.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use twilight_model::id::{marker::UserMarker, Id};
+
+/// How many messages a user can have bridged within `WINDOW`
+const MAX_MESSAGES: usize = 5;
+const WINDOW: Duration = Duration::from_secs(10);
+
+/// Whether a message should be bridged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloodCheck {
+    Allowed,
+    /// The user just started flooding
+    Started,
+    /// The user is still flooding
+    Flooding,
+}
+
+/// Tracks when each user's messages were bridged
+#[derive(Debug, Default)]
+pub struct FloodGuard {
+    messages: HashMap<Id<UserMarker>, VecDeque<Instant>>,
+}
+
+impl FloodGuard {
+    /// Checks whether a message `user` sent `now` should be bridged,
+    /// assuming it will be if it's allowed
+    pub fn check(&mut self, user: Id<UserMarker>, now: Instant) -> FloodCheck {
+        // Forget about users that haven't sent anything recently
+        self.messages.retain(|_, sent| {
+            while let Some(&oldest) = sent.front() {
+                if now.saturating_duration_since(oldest) >= WINDOW {
+                    sent.pop_front();
+                } else {
+                    break;
+                }
+            }
+            !sent.is_empty()
+        });
+
+        let sent = self.messages.entry(user).or_default();
+        if sent.len() < MAX_MESSAGES {
+            sent.push_back(now);
+            FloodCheck::Allowed
+        } else if sent.len() == MAX_MESSAGES {
+            // Dropped messages are counted once so the start of a flood can
+            // be told apart from the rest of it
+            sent.push_back(now);
+            FloodCheck::Started
+        } else {
+            FloodCheck::Flooding
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flooding() {
+        let start = Instant::now();
+        let spammer = Id::new(1);
+        let mut guard = FloodGuard::default();
+
+        for _ in 0..MAX_MESSAGES {
+            assert_eq!(guard.check(spammer, start), FloodCheck::Allowed);
+        }
+        assert_eq!(guard.check(spammer, start), FloodCheck::Started);
+        assert_eq!(guard.check(spammer, start), FloodCheck::Flooding);
+        // Other users aren't affected
+        assert_eq!(guard.check(Id::new(2), start), FloodCheck::Allowed);
+
+        let later = start + WINDOW;
+        assert_eq!(guard.check(spammer, later), FloodCheck::Allowed);
+    }
+}
@@ -0,0 +1,366 @@
+//! Parsing the markdown in Discord messages so it can be shown with Minecraft
+//! chat styles
+//!
+//! Only inline formatting is handled: bold, italics, underline,
+//! strikethrough, inline code and code blocks, spoilers, and masked links.
+//! Markers that aren't closed are left as-is, and mentions and URLs are never
+//! split.
+
+use super::message_span_iter::mention_len;
+use crate::chat;
+
+/// How a piece of text is styled
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Style<'a> {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    /// Inline code or a code block, which isn't formatted any further
+    pub code: bool,
+    pub spoiler: bool,
+    /// The URL of a masked link (`[text](url)`)
+    pub link: Option<&'a str>,
+}
+
+/// A piece of a message with the markdown that applies to it
+#[derive(Debug, PartialEq, Eq)]
+pub struct StyledText<'a> {
+    pub text: &'a str,
+    pub style: Style<'a>,
+}
+
+/// Markers that wrap formatted text, longest first so `**` is tried before
+/// `*`
+const MARKERS: &[&str] = &["```", "`", "||", "**", "__", "~~", "*", "_"];
+
+/// Characters that can be escaped with a backslash
+const ESCAPABLE: &[char] = &['\\', '*', '_', '~', '`', '|', '[', ']', '(', ')', '<', '>'];
+
+/// Splits `content` into pieces of text with the markdown that applies to
+/// them, with the markdown syntax itself removed
+pub fn parse(content: &str) -> Vec<StyledText<'_>> {
+    let mut parsed = vec![];
+    parse_into(content, &Style::default(), &mut parsed);
+    parsed
+}
+
+fn push<'a>(parsed: &mut Vec<StyledText<'a>>, text: &'a str, style: &Style<'a>) {
+    if !text.is_empty() {
+        parsed.push(StyledText {
+            text,
+            style: style.clone(),
+        });
+    }
+}
+
+fn parse_into<'a>(text: &'a str, style: &Style<'a>, parsed: &mut Vec<StyledText<'a>>) {
+    let mut plain_start = 0;
+    let mut i = 0;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+
+        if c == '\\' {
+            if let Some(escaped) = rest[1..].chars().next().filter(|c| ESCAPABLE.contains(c)) {
+                // The escaped character starts the next piece of plain text
+                push(parsed, &text[plain_start..i], style);
+                plain_start = i + 1;
+                i += 1 + escaped.len_utf8();
+                continue;
+            }
+        }
+
+        if let Some(len) = mention_len(rest).or_else(|| url_len_at(text, i)) {
+            i += len;
+            continue;
+        }
+
+        if let Some((link_text, url, len)) = parse_link(rest) {
+            push(parsed, &text[plain_start..i], style);
+            let link_style = Style {
+                link: Some(url),
+                ..style.clone()
+            };
+            parse_into(link_text, &link_style, parsed);
+            i += len;
+            plain_start = i;
+            continue;
+        }
+
+        if let Some((marker, inner)) = parse_delimited(text, i) {
+            push(parsed, &text[plain_start..i], style);
+            let mut inner_style = style.clone();
+            match marker {
+                "```" | "`" => {
+                    inner_style.code = true;
+                    // Code isn't formatted, and the language of a code block
+                    // isn't shown
+                    let code = match marker {
+                        "```" => strip_language(inner),
+                        _ => inner,
+                    };
+                    push(parsed, code, &inner_style);
+                }
+                _ => {
+                    match marker {
+                        "||" => inner_style.spoiler = true,
+                        "**" => inner_style.bold = true,
+                        "__" => inner_style.underline = true,
+                        "~~" => inner_style.strikethrough = true,
+                        _ => inner_style.italic = true,
+                    }
+                    parse_into(inner, &inner_style, parsed);
+                }
+            }
+            i += marker.len() * 2 + inner.len();
+            plain_start = i;
+            continue;
+        }
+
+        i += c.len_utf8();
+    }
+
+    push(parsed, &text[plain_start..], style);
+}
+
+/// Parses a masked link (`[text](https://...)`) at the start of `buf`,
+/// returning its text, URL, and length
+fn parse_link(buf: &str) -> Option<(&str, &str, usize)> {
+    let text_end = buf.strip_prefix('[')?.find(']')? + 1;
+    let text = &buf[1..text_end];
+    let rest = buf[text_end + 1..].strip_prefix('(')?;
+    let url = &rest[..rest.find(')')?];
+
+    let is_url = (url.starts_with("https://") || url.starts_with("http://"))
+        && !url.contains(char::is_whitespace);
+    if text.is_empty() || text.contains('[') || !is_url {
+        return None;
+    }
+
+    Some((text, url, text_end + 1 + url.len() + 2))
+}
+
+/// Parses text wrapped in one of the `MARKERS` starting at `start` in `text`,
+/// returning the marker and the text inside it
+fn parse_delimited(text: &str, start: usize) -> Option<(&'static str, &str)> {
+    let rest = &text[start..];
+    let marker = MARKERS.iter().copied().find(|m| rest.starts_with(m))?;
+    let after_open = start + marker.len();
+
+    // An underscore only starts italics at the start of a word, so names like
+    // snake_case aren't italicized
+    if marker == "_" && text[..start].chars().last().is_some_and(is_word_char) {
+        return None;
+    }
+
+    let close = find_closing(text, after_open, marker)?;
+    if marker == "_" && text[close + 1..].chars().next().is_some_and(is_word_char) {
+        return None;
+    }
+
+    Some((marker, &text[after_open..close]))
+}
+
+/// Finds where `marker` is closed in `text`, starting the search at `from`
+///
+/// The wrapped text can't be empty, and a closing marker can't be followed
+/// by another of the same character. Code can't contain escapes or mentions,
+/// but anything else can, and single `*`s and `_`s skip over doubled ones
+/// so `*a **b** c*` is closed at the end.
+fn find_closing(text: &str, from: usize, marker: &str) -> Option<usize> {
+    let is_code = marker.starts_with('`');
+    let doubled = marker.repeat(2);
+    let mut i = from;
+    while let Some(c) = text[i..].chars().next() {
+        let rest = &text[i..];
+
+        if !is_code {
+            if c == '\\' {
+                i += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+                continue;
+            }
+            if let Some(len) = mention_len(rest).or_else(|| url_len_at(text, i)) {
+                i += len;
+                continue;
+            }
+            if marker.len() == 1 && rest.starts_with(&doubled) {
+                i += 2;
+                continue;
+            }
+        }
+
+        if i > from
+            && rest.starts_with(marker)
+            && (is_code || !text[i + marker.len()..].starts_with(&marker[..1]))
+        {
+            return Some(i);
+        }
+        i += c.len_utf8();
+    }
+
+    None
+}
+
+/// Returns the length of the URL starting at `i` in `text`, if there is one
+///
+/// Markdown isn't parsed inside of URLs.
+fn url_len_at(text: &str, i: usize) -> Option<usize> {
+    if text[..i].chars().last().is_some_and(is_word_char) {
+        return None;
+    }
+    chat::url_len(&text[i..])
+}
+
+/// Removes the language from the first line of a code block, if there is one
+fn strip_language(code: &str) -> &str {
+    match code.split_once('\n') {
+        Some((first, rest)) if !first.is_empty() && !first.contains(char::is_whitespace) => rest,
+        _ => code.strip_prefix('\n').unwrap_or(code),
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Lists the text of each piece with the names of its styles
+    fn summarize(content: &str) -> Vec<(&str, String)> {
+        parse(content)
+            .into_iter()
+            .map(|piece| {
+                let style = piece.style;
+                let mut names = vec![];
+                for (name, on) in [
+                    ("bold", style.bold),
+                    ("italic", style.italic),
+                    ("underline", style.underline),
+                    ("strikethrough", style.strikethrough),
+                    ("code", style.code),
+                    ("spoiler", style.spoiler),
+                ] {
+                    if on {
+                        names.push(name.to_string());
+                    }
+                }
+                if let Some(url) = style.link {
+                    names.push(format!("link {}", url));
+                }
+                (piece.text, names.join(", "))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plain() {
+        assert_eq!(summarize("just text"), [("just text", "".into())]);
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn styles() {
+        assert_eq!(
+            summarize("**bold** *italic* _also italic_ __underline__ ~~gone~~ ||secret||"),
+            [
+                ("bold", "bold".into()),
+                (" ", "".into()),
+                ("italic", "italic".into()),
+                (" ", "".into()),
+                ("also italic", "italic".into()),
+                (" ", "".into()),
+                ("underline", "underline".into()),
+                (" ", "".into()),
+                ("gone", "strikethrough".into()),
+                (" ", "".into()),
+                ("secret", "spoiler".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(
+            summarize("***both*** and *a **b** c*"),
+            [
+                ("both", "bold, italic".into()),
+                (" and ", "".into()),
+                ("a ", "italic".into()),
+                ("b", "bold, italic".into()),
+                (" c", "italic".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn code() {
+        assert_eq!(
+            summarize("run `/tp **me**` or ```rust\nfn main() {}```"),
+            [
+                ("run ", "".into()),
+                ("/tp **me**", "code".into()),
+                (" or ", "".into()),
+                ("fn main() {}", "code".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            summarize("see [the **wiki**](https://minecraft.wiki) or [not a link](ftp://x)"),
+            [
+                ("see ", "".into()),
+                ("the ", "link https://minecraft.wiki".into()),
+                ("wiki", "bold, link https://minecraft.wiki".into()),
+                (" or [not a link](ftp://x)", "".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_and_escaped() {
+        assert_eq!(
+            summarize("2 * 3 = 6, **not bold, \\*literal\\*"),
+            [
+                ("2 * 3 = 6, **not bold, ", "".into()),
+                ("*literal", "".into()),
+                ("*", "".into()),
+            ]
+        );
+        assert_eq!(summarize("****"), [("****", "".into())]);
+    }
+
+    #[test]
+    fn underscores_in_words() {
+        assert_eq!(
+            summarize("snake_case_name and _this_"),
+            [
+                ("snake_case_name and ", "".into()),
+                ("this", "italic".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn urls_are_not_split() {
+        assert_eq!(
+            summarize("_see https://example.com/_a_b_/*c*_"),
+            [("see https://example.com/_a_b_/*c*", "italic".into())]
+        );
+    }
+
+    #[test]
+    fn mentions_are_not_split() {
+        assert_eq!(
+            summarize("<:big_smile_face:12> **<@34>** <t:1700000000:R>"),
+            [
+                ("<:big_smile_face:12> ", "".into()),
+                ("<@34>", "bold".into()),
+                (" <t:1700000000:R>", "".into()),
+            ]
+        );
+    }
+}
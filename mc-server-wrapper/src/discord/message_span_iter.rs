@@ -1,6 +1,9 @@
-use std::num::NonZeroU64;
+use std::{convert::TryFrom, num::NonZeroU64};
 
-use twilight_mention::parse::MentionType;
+use twilight_mention::{
+    parse::MentionType,
+    timestamp::{Timestamp, TimestampStyle},
+};
 use twilight_model::id::Id;
 
 trait MentionTypeExt: Sized {
@@ -12,6 +15,8 @@ impl MentionTypeExt for MentionType {
     ///
     /// This function will parse input such as "@!21984" successfully. It *does
     /// not* handle the < or > characters.
+    ///
+    /// Animated emoji are parsed as `Emoji`, the same as static ones.
     fn try_parse(buf: &str) -> Option<Self> {
         if let Some(buf) = buf.strip_prefix("@!") {
             // Parse user ID
@@ -28,7 +33,7 @@ impl MentionTypeExt for MentionType {
             buf.parse::<NonZeroU64>()
                 .ok()
                 .map(|n| Self::User(Id::from(n)))
-        } else if let Some(buf) = buf.strip_prefix(':') {
+        } else if let Some(buf) = buf.strip_prefix(':').or_else(|| buf.strip_prefix("a:")) {
             // Parse emoji ID (looks like "<:name:123>")
             //
             // Find the second ":"
@@ -42,6 +47,15 @@ impl MentionTypeExt for MentionType {
             buf.parse::<NonZeroU64>()
                 .ok()
                 .map(|n| Self::Channel(Id::from(n)))
+        } else if let Some(buf) = buf.strip_prefix("t:") {
+            // Parse timestamp (looks like "<t:12345>" or "<t:12345:R>")
+            let (unix, style) = match buf.split_once(':') {
+                Some((unix, style)) => (unix, Some(TimestampStyle::try_from(style).ok()?)),
+                None => (buf, None),
+            };
+            unix.parse::<u64>()
+                .ok()
+                .map(|unix| Self::Timestamp(Timestamp::new(unix, style)))
         } else {
             None
         }
@@ -60,6 +74,35 @@ impl StrExt for str {
     }
 }
 
+/// Parses the inner part of a slash command mention (such as "/name:123" or
+/// "/name sub:123") from the given `buf`, returning the command's name
+///
+/// Like `MentionTypeExt::try_parse`, this *does not* handle the < or >
+/// characters.
+fn try_parse_command(buf: &str) -> Option<&str> {
+    let (name, id) = buf.strip_prefix('/')?.rsplit_once(':')?;
+    id.parse::<NonZeroU64>().ok()?;
+
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Returns the length of the mention at the start of `buf`, if it starts with
+/// one
+pub fn mention_len(buf: &str) -> Option<usize> {
+    let end = buf.strip_prefix('<')?.find('>')? + 1;
+    let inner = &buf[1..end];
+
+    if MentionType::try_parse(inner).is_some() || try_parse_command(inner).is_some() {
+        Some(end + 1)
+    } else {
+        None
+    }
+}
+
 /// Spans parsed out of a Discord message.
 #[derive(Debug, Eq, PartialEq)]
 pub enum MessageSpan<'a> {
@@ -70,6 +113,12 @@ pub enum MessageSpan<'a> {
     /// The left side of the tuple is the parsed data and the right side is the
     /// string slice that it was parsed from.
     Mention(MentionType, &'a str),
+    /// A slash command mention
+    ///
+    /// The left side of the tuple is the command's name (including any
+    /// subcommands) and the right side is the string slice that it was parsed
+    /// from.
+    Command(&'a str, &'a str),
 }
 
 impl<'a> MessageSpan<'a> {
@@ -81,29 +130,36 @@ impl<'a> MessageSpan<'a> {
 #[derive(Debug)]
 pub struct MessageSpanIter<'a> {
     buf: &'a str,
-    mention: Option<(MentionType, &'a str)>,
+    mention: Option<MessageSpan<'a>>,
 }
 
 impl<'a> Iterator for MessageSpanIter<'a> {
     type Item = MessageSpan<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(mention_type) = self.mention.take() {
+        if let Some(mention) = self.mention.take() {
             // Yield the previously stored mention info if it's present
-            Some(MessageSpan::Mention(mention_type.0, mention_type.1))
+            Some(mention)
         } else if let Some((start, end)) = self
             .buf
             .find('<')
             .and_then(|start| self.buf.find_after(start, ">").map(|end| (start, end)))
         {
             // Check and see if we can parse a valid mention
-            if let Some(mention_type) = self
-                .buf
-                .get(start + 1..end)
-                .and_then(MentionType::try_parse)
-            {
+            let raw = &self.buf[start..=end];
+            let mention =
+                self.buf.get(start + 1..end).and_then(|inner| {
+                    match MentionType::try_parse(inner) {
+                        Some(mention_type) => Some(MessageSpan::Mention(mention_type, raw)),
+                        None => {
+                            try_parse_command(inner).map(|name| MessageSpan::Command(name, raw))
+                        }
+                    }
+                });
+
+            if let Some(mention) = mention {
                 // Store the mention info to be yielded on the next iteration
-                self.mention = Some((mention_type, &self.buf[start..=end]));
+                self.mention = Some(mention);
                 let ret = Some(MessageSpan::Text(&self.buf[..start]));
                 self.buf = self.buf.get(end + 1..).unwrap_or("");
                 ret
@@ -191,4 +247,87 @@ mod test {
             "##]],
         );
     }
+
+    #[test]
+    fn timestamps_commands_and_animated_emoji() {
+        check(
+            "<t:1618953630> <t:1618953630:R> </ping:12> </whitelist me:34> <a:party:56>",
+            expect![[r#"
+                [
+                    Text(
+                        "",
+                    ),
+                    Mention(
+                        Timestamp(
+                            Timestamp {
+                                style: None,
+                                unix: 1618953630,
+                            },
+                        ),
+                        "<t:1618953630>",
+                    ),
+                    Text(
+                        " ",
+                    ),
+                    Mention(
+                        Timestamp(
+                            Timestamp {
+                                style: Some(
+                                    RelativeTime,
+                                ),
+                                unix: 1618953630,
+                            },
+                        ),
+                        "<t:1618953630:R>",
+                    ),
+                    Text(
+                        " ",
+                    ),
+                    Command(
+                        "ping",
+                        "</ping:12>",
+                    ),
+                    Text(
+                        " ",
+                    ),
+                    Command(
+                        "whitelist me",
+                        "</whitelist me:34>",
+                    ),
+                    Text(
+                        " ",
+                    ),
+                    Mention(
+                        Emoji(
+                            Id<EmojiMarker>(56),
+                        ),
+                        "<a:party:56>",
+                    ),
+                ]
+            "#]],
+        );
+    }
+
+    #[test]
+    fn invalid_new_mentions() {
+        check(
+            "<t:abc> <t:1618953630:X> </:12> </ping:abc>",
+            expect![[r#"
+                [
+                    Text(
+                        "<t:abc>",
+                    ),
+                    Text(
+                        " <t:1618953630:X>",
+                    ),
+                    Text(
+                        " </:12>",
+                    ),
+                    Text(
+                        " </ping:abc>",
+                    ),
+                ]
+            "#]],
+        );
+    }
 }
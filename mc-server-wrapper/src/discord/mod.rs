@@ -2,74 +2,266 @@ use log::{debug, info, warn};
 
 use twilight_cache_inmemory::{model::CachedMember, InMemoryCache, Reference, ResourceType};
 use twilight_gateway::{Event, MessageSender, Shard, ShardId};
-use twilight_http::Client as DiscordClient;
+use twilight_http::{error::ErrorType, Client as DiscordClient};
 use twilight_model::{
-    channel::{message::MessageType, Message},
+    application::{
+        command::{CommandOption, CommandOptionType},
+        interaction::{
+            application_command::{CommandDataOption, CommandOptionValue},
+            Interaction, InteractionData,
+        },
+    },
+    channel::{
+        message::{embed::Embed, MessageFlags, MessageType},
+        Message,
+    },
     gateway::{
         payload::outgoing::{RequestGuildMembers, UpdatePresence},
         presence::Status,
         Intents,
     },
+    http::interaction::{InteractionResponse, InteractionResponseData, InteractionResponseType},
     id::{
-        marker::{ChannelMarker, GuildMarker, UserMarker},
+        marker::{
+            ApplicationMarker, ChannelMarker, GuildMarker, MessageMarker, RoleMarker, UserMarker,
+        },
         Id,
     },
+    user::User,
 };
 
 use mc_server_wrapper_lib::{communication::*, parse::*};
 use minecraft_chat::{Color, Payload};
 
+use crate::{
+    chat::LinkFilter,
+    command_capture::CaptureRequest,
+    exit_code::ExitCode,
+    remote_commands::CommandOrigin,
+    status_embed,
+    whitelist::{self, WhitelistRequest},
+};
+use flood::{FloodCheck, FloodGuard};
+use outbox::Outbox;
 use util::{activity, format_mentions_in, tellraw_prefix};
 
-use std::{borrow::Cow, collections::HashMap, sync::Arc};
-use tokio::sync::mpsc::Sender;
+use once_cell::sync::OnceCell;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    num::NonZeroU64,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError, Sender},
+    oneshot, Mutex, Notify,
+};
 
+mod flood;
+mod markdown;
 mod message_span_iter;
+mod outbox;
 pub mod util;
 
-static CHAT_PREFIX: &str = "[D] ";
+/// The prefix for running console commands with a chat message, for those
+/// that don't use slash commands
+static RUN_PREFIX: &str = "!run ";
+/// Discord doesn't allow messages longer than this
+const MAX_MESSAGE_LEN: usize = 2000;
+/// The most parts of a long message that are sent to Minecraft
+const MAX_MSG_PARTS: usize = 8;
+/// How many Discord events can wait to be handled before new ones are dropped
+const EVENT_QUEUE_LEN: usize = 256;
+/// The longest to wait before reconnecting after the connection fails
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// The state of the connection to Discord's gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connecting for the first time, or again after a failure
+    Connecting,
+    /// Connected and receiving events
+    Connected,
+    /// The connection was closed and is being resumed
+    Resuming,
+    /// The connection failed, and a new one will be made at the given time
+    Backoff { until: Instant },
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionState::Connecting => write!(f, "connecting"),
+            ConnectionState::Connected => write!(f, "connected"),
+            ConnectionState::Resuming => write!(f, "resuming"),
+            ConnectionState::Backoff { until } => write!(
+                f,
+                "reconnecting in {}s",
+                until.saturating_duration_since(Instant::now()).as_secs()
+            ),
+        }
+    }
+}
+
+/// Returns how long to wait before the given reconnect attempt (counting from
+/// zero), doubling each time up to `MAX_RECONNECT_DELAY`
+fn reconnect_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1)
+        .checked_mul(2u32.saturating_pow(attempt))
+        .unwrap_or(MAX_RECONNECT_DELAY)
+        .min(MAX_RECONNECT_DELAY)
+}
+
+/// Creates a shard connecting to Discord with `token`
+fn new_shard(token: String) -> Shard {
+    // Use intents to only receive guild message events.
+    Shard::new(
+        ShardId::ONE,
+        token,
+        Intents::GUILDS
+            | Intents::GUILD_MESSAGES
+            | Intents::GUILD_MEMBERS
+            | Intents::MESSAGE_CONTENT,
+    )
+}
+
+/// Controls what's bridged from Discord to Minecraft
+#[derive(Debug)]
+pub struct BridgeFilters {
+    /// Whether messages are bridged to Minecraft at all
+    ///
+    /// Commands like `!run` still work when they aren't.
+    pub enabled: bool,
+    /// Users whose messages aren't bridged
+    pub ignored_users: Vec<Id<UserMarker>>,
+    /// Which links are made clickable in Minecraft
+    pub links: LinkFilter,
+    /// Shown before bridged messages in Minecraft
+    pub chat_prefix: String,
+}
+
+/// Lets members with certain roles run console commands from Discord
+#[derive(Debug, Clone)]
+pub struct ConsoleAccess {
+    /// The roles allowed to run commands
+    pub roles: Vec<Id<RoleMarker>>,
+    /// Where to send commands to be run
+    pub capture_sender: Sender<CaptureRequest>,
+}
 
 /// Sets up a `DiscordBridge` and starts handling events
 ///
 /// If `allow_status_updates` is set to `false` any calls to `update_status()`
-/// will be no-ops
+/// will be no-ops. The `/run` command is only available if `console_access`
+/// is provided, and `/whitelist me` if `whitelist_sender` is. What's bridged
+/// is limited by `filters`.
 pub async fn setup_discord(
     token: String,
     bridge_channel_id: Id<ChannelMarker>,
     mc_cmd_sender: Sender<ServerCommand>,
     allow_status_updates: bool,
+    console_access: Option<ConsoleAccess>,
+    whitelist_sender: Option<Sender<WhitelistRequest>>,
+    filters: BridgeFilters,
 ) -> Result<DiscordBridge, anyhow::Error> {
     info!("Setting up Discord");
-    let (discord, mut shard) =
-        DiscordBridge::new(token, bridge_channel_id, allow_status_updates).await?;
+    let (discord, mut shard) = DiscordBridge::new(
+        token.clone(),
+        bridge_channel_id,
+        allow_status_updates,
+        console_access,
+        whitelist_sender,
+        filters,
+    )
+    .await?;
+
+    tokio::spawn(discord.clone().send_outbox());
+
+    let (event_sender, mut event_receiver) = mpsc::channel(EVENT_QUEUE_LEN);
+    let discord_clone = discord.clone();
+    tokio::spawn(async move {
+        while let Some(event) = event_receiver.recv().await {
+            if let Err(e) = discord_clone
+                .handle_discord_event(event, mc_cmd_sender.clone())
+                .await
+            {
+                warn!("Failed to handle Discord event: {}", e);
+            }
+        }
+    });
 
     let discord_clone = discord.clone();
     tokio::spawn(async move {
         let discord = discord_clone;
+        let inner = discord.inner.clone().unwrap();
+        // Failed connection attempts since the last successful one
+        let mut failed_attempts = 0;
 
         // For all received Discord events, map the event to a `ServerCommand`
         // (if necessary) and send it to the Minecraft server
         loop {
-            match shard.next_event().await {
+            let event = tokio::select! {
+                _ = inner.shutdown.notified() => {
+                    info!("Disconnecting from Discord");
+                    break;
+                },
+                event = shard.next_event() => event,
+            };
+
+            match event {
                 Ok(e) => {
-                    let discord = discord.clone();
-                    let cmd_sender_clone = mc_cmd_sender.clone();
+                    match &e {
+                        Event::Ready(_) | Event::Resumed => {
+                            failed_attempts = 0;
+                            inner.set_connection_state(ConnectionState::Connected);
+                        }
+                        // The shard reconnects by itself after these
+                        Event::GatewayClose(_) | Event::GatewayReconnect => {
+                            inner.set_connection_state(ConnectionState::Resuming);
+                        }
+                        _ => {}
+                    }
 
                     // Update the cache
-                    discord.inner.as_ref().unwrap().cache.update(&e);
+                    inner.cache.update(&e);
 
-                    tokio::spawn(async move {
-                        if let Err(e) = discord.handle_discord_event(e, cmd_sender_clone).await {
-                            warn!("Failed to handle Discord event: {}", e);
-                        }
-                    });
+                    // Events are handled in order, but elsewhere so they don't
+                    // hold up receiving the next ones
+                    if let Err(TrySendError::Full(_)) = event_sender.try_send(e) {
+                        warn!("Too many Discord events are waiting to be handled, dropping one");
+                    }
                 }
                 Err(source) => {
                     log::warn!("error receiving event from shard: {}", source);
 
                     if source.is_fatal() {
-                        log::error!("fatal event received, breaking shard event loop");
-                        break;
+                        // The shard can't be used anymore, so a new one is made
+                        let delay = reconnect_delay(failed_attempts);
+                        failed_attempts = failed_attempts.saturating_add(1);
+                        log::error!(
+                            "Lost the connection to Discord, reconnecting in {}s",
+                            delay.as_secs()
+                        );
+                        inner.set_connection_state(ConnectionState::Backoff {
+                            until: Instant::now() + delay,
+                        });
+
+                        tokio::select! {
+                            _ = inner.shutdown.notified() => {
+                                info!("Disconnecting from Discord");
+                                break;
+                            },
+                            _ = tokio::time::sleep(delay) => {},
+                        }
+
+                        shard = new_shard(token.clone());
+                        *inner.shard_message_sender.lock().unwrap() = shard.sender();
+                        inner.set_connection_state(ConnectionState::Connecting);
                     }
 
                     continue;
@@ -81,6 +273,15 @@ pub async fn setup_discord(
     Ok(discord)
 }
 
+/// Whether Discord rejected a request because of the token
+fn is_unauthorized(error: &twilight_http::Error) -> bool {
+    match error.kind() {
+        ErrorType::Unauthorized => true,
+        ErrorType::Response { status, .. } => status.get() == 401,
+        _ => false,
+    }
+}
+
 /// Represents a maybe-present Discord bridge to a single text channel
 ///
 /// All operations are no-ops if this struct is constructed without the
@@ -101,8 +302,101 @@ pub struct DiscordBridge {
 #[derive(Debug)]
 struct DiscordBridgeInner {
     client: DiscordClient,
-    shard_message_sender: MessageSender,
+    /// Replaced when the shard is recreated after a fatal error
+    shard_message_sender: std::sync::Mutex<MessageSender>,
     cache: InMemoryCache,
+    /// Notified to stop handling events and close the connection
+    shutdown: Notify,
+    console_access: Option<ConsoleAccess>,
+    /// Where to send `/whitelist me` requests, if it's enabled
+    whitelist_sender: Option<Sender<WhitelistRequest>>,
+    filters: BridgeFilters,
+    /// Set once the connection is ready
+    application_id: OnceCell<Id<ApplicationMarker>>,
+    /// The bot's user, set once it's known
+    user_id: OnceCell<Id<UserMarker>>,
+    /// The pinned status message, once it's been found or sent
+    status_message: Mutex<Option<Id<MessageMarker>>>,
+    /// The message showing the server's startup progress, while it's starting
+    progress_message: Mutex<Option<Id<MessageMarker>>>,
+    connection_state: std::sync::Mutex<ConnectionState>,
+    flood_guard: std::sync::Mutex<FloodGuard>,
+    /// Messages waiting to be sent to the bridged channel
+    outbox: std::sync::Mutex<Outbox>,
+    /// Notified when a message is added to the outbox or it's closed
+    outbox_notify: Notify,
+    /// Set when the bridge is shut down, after which the messages in the
+    /// outbox are sent and no more are
+    outbox_closed: AtomicBool,
+}
+
+impl DiscordBridgeInner {
+    /// Records a change in the state of the connection, logging it
+    fn set_connection_state(&self, state: ConnectionState) {
+        let mut connection_state = self.connection_state.lock().unwrap();
+        if *connection_state != state {
+            info!("Discord connection state: {}", state);
+            *connection_state = state;
+        }
+    }
+}
+
+/// Describes an option for a slash command
+fn command_option(
+    kind: CommandOptionType,
+    name: &str,
+    description: &str,
+    options: Option<Vec<CommandOption>>,
+) -> CommandOption {
+    CommandOption {
+        autocomplete: None,
+        channel_types: None,
+        choices: None,
+        description: description.into(),
+        description_localizations: None,
+        kind,
+        max_length: None,
+        max_value: None,
+        min_length: None,
+        min_value: None,
+        name: name.into(),
+        name_localizations: None,
+        options,
+        required: if kind == CommandOptionType::SubCommand {
+            None
+        } else {
+            Some(true)
+        },
+    }
+}
+
+/// Returns the value of the string option called `name`
+fn string_option<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|option| match &option.value {
+        CommandOptionValue::String(value) if option.name == name => Some(value.as_str()),
+        _ => None,
+    })
+}
+
+/// Formats the console output of a command as a Discord message
+fn format_command_output(lines: &[String]) -> String {
+    if lines.is_empty() {
+        return "The command didn't print anything".into();
+    }
+
+    // Backticks would end the code block early
+    let mut output = lines.join("\n").replace('`', "'");
+    let max_len = MAX_MESSAGE_LEN - "```\n\n```".len() - "...".len();
+    if output.len() > max_len {
+        let mut end = max_len;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+        output.push_str("...");
+    }
+
+    format!("```\n{}\n```", output)
 }
 
 impl DiscordBridge {
@@ -114,29 +408,52 @@ impl DiscordBridge {
         token: String,
         bridge_channel_id: Id<ChannelMarker>,
         allow_status_updates: bool,
+        console_access: Option<ConsoleAccess>,
+        whitelist_sender: Option<Sender<WhitelistRequest>>,
+        filters: BridgeFilters,
     ) -> Result<(Self, Shard), anyhow::Error> {
-        // Use intents to only receive guild message events.
-        let shard = Shard::new(
-            ShardId::ONE,
-            token.clone(),
-            Intents::GUILDS
-                | Intents::GUILD_MESSAGES
-                | Intents::GUILD_MEMBERS
-                | Intents::MESSAGE_CONTENT,
-        );
+        let shard = new_shard(token.clone());
 
         let client = DiscordClient::new(token);
 
+        // The gateway connection is made in the background, so the token is
+        // checked here to report a bad one right away
+        match client.current_user().await {
+            Err(e) if is_unauthorized(&e) => {
+                return Err(anyhow::Error::new(e).context(ExitCode::DiscordAuth));
+            }
+            Err(e) => warn!("Failed to check the Discord token: {}", e),
+            Ok(_) => {}
+        }
+
         let cache = InMemoryCache::builder()
-            .resource_types(ResourceType::GUILD | ResourceType::CHANNEL | ResourceType::MEMBER)
+            .resource_types(
+                ResourceType::GUILD
+                    | ResourceType::CHANNEL
+                    | ResourceType::MEMBER
+                    | ResourceType::USER,
+            )
             .build();
 
         Ok((
             Self {
                 inner: Some(Arc::new(DiscordBridgeInner {
                     client,
-                    shard_message_sender: shard.sender(),
+                    shard_message_sender: std::sync::Mutex::new(shard.sender()),
                     cache,
+                    shutdown: Notify::new(),
+                    console_access,
+                    whitelist_sender,
+                    filters,
+                    application_id: OnceCell::new(),
+                    user_id: OnceCell::new(),
+                    status_message: Mutex::new(None),
+                    progress_message: Mutex::new(None),
+                    connection_state: std::sync::Mutex::new(ConnectionState::Connecting),
+                    flood_guard: std::sync::Mutex::new(FloodGuard::default()),
+                    outbox: std::sync::Mutex::new(Outbox::default()),
+                    outbox_notify: Notify::new(),
+                    outbox_closed: AtomicBool::new(false),
                 })),
                 bridge_channel_id,
                 allow_status_updates,
@@ -154,9 +471,32 @@ impl DiscordBridge {
         }
     }
 
+    /// Disconnects from Discord
+    ///
+    /// Clones of this struct can still be used afterwards, but messages won't
+    /// be bridged from Discord anymore, and only messages that were already
+    /// queued are sent to it.
+    pub fn shutdown(&self) {
+        if let Some(inner) = &self.inner {
+            inner.shutdown.notify_one();
+            inner.outbox_closed.store(true, Ordering::Relaxed);
+            inner.outbox_notify.notify_one();
+        }
+    }
+
     /// Provides access to the `MessageSender` inside this struct
     pub fn shard_message_sender(&self) -> Option<MessageSender> {
-        self.inner.as_ref().map(|i| i.shard_message_sender.clone())
+        self.inner
+            .as_ref()
+            .map(|i| i.shard_message_sender.lock().unwrap().clone())
+    }
+
+    /// Returns the state of the connection to Discord, or `None` if the bridge
+    /// isn't enabled
+    pub fn connection_state(&self) -> Option<ConnectionState> {
+        self.inner
+            .as_ref()
+            .map(|i| *i.connection_state.lock().unwrap())
     }
 
     /// Provides access to the `InMemoryCache` inside this struct
@@ -191,10 +531,46 @@ impl DiscordBridge {
         })
     }
 
+    /// Returns the names in-game chat can mention the given users by (their
+    /// username, display name, and nickname in the bridged guild), lowercased
+    ///
+    /// Users that aren't cached are skipped.
+    pub fn mentionable_names(&self, user_ids: &[NonZeroU64]) -> HashMap<String, Id<UserMarker>> {
+        let mut names = HashMap::new();
+        let cache = match self.cache() {
+            Some(cache) => cache,
+            None => return names,
+        };
+        let guild_id = cache
+            .channel(self.bridge_channel_id)
+            .and_then(|channel| channel.guild_id);
+
+        for user_id in user_ids.iter().copied().map(Id::from) {
+            let mut user_names = vec![];
+            if let Some(user) = cache.user(user_id) {
+                user_names.push(user.name.clone());
+                user_names.extend(user.global_name.clone());
+            }
+            if let Some(member) = guild_id.and_then(|guild_id| cache.member(guild_id, user_id)) {
+                user_names.extend(member.nick().map(str::to_string));
+            }
+
+            for name in user_names {
+                // Names with spaces can't be typed as a single `@word`
+                if !name.contains(char::is_whitespace) {
+                    names.insert(name.to_lowercase(), user_id);
+                }
+            }
+        }
+
+        names
+    }
+
     /// Handle an event from Discord
     ///
-    /// The provided `cmd_parser` is used to parse commands (not
-    /// `ServerCommands`) from Discord messages.
+    /// Events are handled one at a time so messages reach Minecraft in order.
+    /// Anything that could take a while (like HTTP requests or waiting for a
+    /// command's output) is done in a new task.
     #[allow(clippy::single_match)]
     pub async fn handle_discord_event(
         &self,
@@ -202,8 +578,12 @@ impl DiscordBridge {
         mc_cmd_sender: Sender<ServerCommand>,
     ) -> Result<(), anyhow::Error> {
         match event {
-            Event::Ready(_) => {
+            Event::Ready(ready) => {
                 info!("Discord bridge online");
+                if let Some(inner) = &self.inner {
+                    let _ = inner.application_id.set(ready.application.id);
+                    let _ = inner.user_id.set(ready.user.id);
+                }
             }
             Event::GuildCreate(guild) => {
                 // Log the name of the channel we're bridging to as well if it's
@@ -229,39 +609,95 @@ impl DiscordBridge {
                     // all member info right out of the gate
                     message_sender
                         .command(&RequestGuildMembers::builder(guild.id).query("", None))?;
+
+                    let discord = self.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = discord.register_commands(guild.id).await {
+                            warn!("Failed to register slash commands: {}", e);
+                        }
+                    });
                 } else {
                     info!("Connected to guild '{}'", guild.name);
                 }
             }
-            Event::MessageCreate(msg) => {
+            Event::MessageCreate(msg)
                 if msg.kind == MessageType::Regular
                     && !msg.author.bot
                     && msg.channel_id == self.bridge_channel_id
-                {
-                    let cached_member = msg
-                        .guild_id
-                        .and_then(|guild_id| self.cached_guild_member(guild_id, msg.author.id));
+                    && !self.is_ignored(msg.author.id) =>
+            {
+                if let Some(command) = msg.content.strip_prefix(RUN_PREFIX) {
+                    if self.console_access().is_some() {
+                        // Waiting for the command's output would hold up
+                        // the events after this one
+                        let discord = self.clone();
+                        let command = command.to_string();
+                        let roles = msg.member.as_ref().map(|m| m.roles.clone());
+                        tokio::spawn(async move {
+                            let reply = discord
+                                .run_command_for(
+                                    &command,
+                                    &msg.author,
+                                    roles.as_deref().unwrap_or_default(),
+                                )
+                                .await;
+                            discord.send_channel_msg(reply);
+                        });
+                        return Ok(());
+                    }
+                }
 
-                    let author_display_name = cached_member
-                        .as_ref()
-                        .and_then(|cm| cm.nick())
-                        .unwrap_or(&msg.author.name);
-
-                    self.handle_attachments_in_msg(
-                        &msg,
-                        author_display_name,
-                        mc_cmd_sender.clone(),
-                    )
+                if !self.bridges_to_minecraft() {
+                    return Ok(());
+                }
+
+                let flood_check = self
+                    .inner
+                    .as_ref()
+                    .unwrap()
+                    .flood_guard
+                    .lock()
+                    .unwrap()
+                    .check(msg.author.id, Instant::now());
+                match flood_check {
+                    FloodCheck::Allowed => {}
+                    FloodCheck::Started => {
+                        warn!(
+                            "{} is sending messages too quickly, dropping them",
+                            msg.author.name
+                        );
+                        return Ok(());
+                    }
+                    FloodCheck::Flooding => return Ok(()),
+                }
+
+                let cached_member = msg
+                    .guild_id
+                    .and_then(|guild_id| self.cached_guild_member(guild_id, msg.author.id));
+
+                let author_display_name = cached_member
+                    .as_ref()
+                    .and_then(|cm| cm.nick())
+                    .unwrap_or(&msg.author.name);
+
+                self.handle_attachments_in_msg(&msg, author_display_name, mc_cmd_sender.clone())
                     .await;
 
-                    self.handle_msg_content(&msg, author_display_name, mc_cmd_sender.clone())
-                        .await;
+                self.handle_msg_content(&msg, author_display_name, mc_cmd_sender.clone())
+                    .await;
 
-                    // We handle embeds after the message contents to replicate
-                    // Discord's layout (embeds after message)
-                    self.handle_embeds_in_msg(&msg, author_display_name, mc_cmd_sender)
-                        .await;
-                }
+                // We handle embeds after the message contents to replicate
+                // Discord's layout (embeds after message)
+                self.handle_embeds_in_msg(&msg, author_display_name, mc_cmd_sender)
+                    .await;
+            }
+            Event::InteractionCreate(interaction) => {
+                let discord = self.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = discord.handle_interaction(&interaction).await {
+                        warn!("Failed to handle Discord interaction: {}", e);
+                    }
+                });
             }
             _ => {}
         }
@@ -269,6 +705,236 @@ impl DiscordBridge {
         Ok(())
     }
 
+    /// Whether messages are bridged to Minecraft
+    fn bridges_to_minecraft(&self) -> bool {
+        self.inner
+            .as_ref()
+            .is_some_and(|inner| inner.filters.enabled)
+    }
+
+    /// The prefix shown before bridged messages in Minecraft
+    fn chat_prefix(&self) -> &str {
+        &self.inner.as_ref().unwrap().filters.chat_prefix
+    }
+
+    /// Whether messages from the given user shouldn't be bridged
+    fn is_ignored(&self, user_id: Id<UserMarker>) -> bool {
+        self.inner
+            .as_ref()
+            .is_some_and(|inner| inner.filters.ignored_users.contains(&user_id))
+    }
+
+    /// Decides which links are made clickable in Minecraft
+    ///
+    /// This can only be called if `self.inner` is `Some`
+    fn link_filter(&self) -> &LinkFilter {
+        &self.inner.as_ref().unwrap().filters.links
+    }
+
+    fn console_access(&self) -> Option<&ConsoleAccess> {
+        self.inner.as_ref()?.console_access.as_ref()
+    }
+
+    /// Registers the slash commands that are enabled in the given guild
+    async fn register_commands(&self, guild_id: Id<GuildMarker>) -> Result<(), anyhow::Error> {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return Ok(()),
+        };
+        let application_id = match inner.application_id.get() {
+            Some(application_id) => *application_id,
+            None => return Ok(()),
+        };
+        let client = inner.client.interaction(application_id);
+
+        if inner.console_access.is_some() {
+            let options = [command_option(
+                CommandOptionType::String,
+                "command",
+                "The command to run",
+                None,
+            )];
+            client
+                .create_guild_command(guild_id)
+                .chat_input("run", "Run a command on the Minecraft server")?
+                .command_options(&options)?
+                .await?;
+        }
+
+        if inner.whitelist_sender.is_some() {
+            let options = [command_option(
+                CommandOptionType::SubCommand,
+                "me",
+                "Add your Minecraft account to the whitelist",
+                Some(vec![command_option(
+                    CommandOptionType::String,
+                    "name",
+                    "Your Minecraft name",
+                    None,
+                )]),
+            )];
+            client
+                .create_guild_command(guild_id)
+                .chat_input("whitelist", "Manage the Minecraft server's whitelist")?
+                .command_options(&options)?
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handles a slash command
+    async fn handle_interaction(&self, interaction: &Interaction) -> Result<(), anyhow::Error> {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return Ok(()),
+        };
+        let data = match &interaction.data {
+            Some(InteractionData::ApplicationCommand(data)) => data,
+            _ => return Ok(()),
+        };
+        let (member, author) = match (&interaction.member, interaction.author()) {
+            (Some(member), Some(author)) => (member, author),
+            _ => return Ok(()),
+        };
+        // Replies that only matter to the person that used the command are
+        // only shown to them
+        let ephemeral = match data.name.as_str() {
+            "run" => !self.may_run(&member.roles),
+            "whitelist" => true,
+            _ => return Ok(()),
+        };
+
+        let client = inner.client.interaction(interaction.application_id);
+        // Commands can take a moment to run, so Discord is told the response
+        // will come later
+        client
+            .create_response(
+                interaction.id,
+                &interaction.token,
+                &InteractionResponse {
+                    kind: InteractionResponseType::DeferredChannelMessageWithSource,
+                    data: if ephemeral {
+                        Some(InteractionResponseData {
+                            flags: Some(MessageFlags::EPHEMERAL),
+                            ..InteractionResponseData::default()
+                        })
+                    } else {
+                        None
+                    },
+                },
+            )
+            .await?;
+
+        let reply = if data.name == "run" {
+            match string_option(&data.options, "command") {
+                Some(command) => self.run_command_for(command, author, &member.roles).await,
+                None => "No command was given".into(),
+            }
+        } else {
+            let name = data.options.iter().find_map(|option| match &option.value {
+                CommandOptionValue::SubCommand(options) if option.name == "me" => {
+                    string_option(options, "name")
+                }
+                _ => None,
+            });
+            match name {
+                Some(name) => self.whitelist_for(author.id.get(), name).await,
+                None => "No name was given".into(),
+            }
+        };
+        client
+            .update_response(&interaction.token)
+            .content(Some(&reply))?
+            .await?;
+
+        Ok(())
+    }
+
+    /// Adds the Minecraft account `name` to the whitelist for the Discord
+    /// user `discord_id`, returning the reply to send them
+    async fn whitelist_for(&self, discord_id: u64, name: &str) -> String {
+        let whitelist_sender = match self
+            .inner
+            .as_ref()
+            .and_then(|i| i.whitelist_sender.as_ref())
+        {
+            Some(whitelist_sender) => whitelist_sender,
+            None => return "Adding yourself to the whitelist isn't enabled".into(),
+        };
+        let name = name.trim();
+        if !whitelist::is_valid_name(name) {
+            return format!("`{}` isn't a valid Minecraft name", name.replace('`', "'"));
+        }
+
+        let profile = match crate::provision::http_client() {
+            Ok(client) => whitelist::lookup(&client, name).await,
+            Err(e) => Err(e),
+        };
+        let profile = match profile {
+            Ok(Some(profile)) => profile,
+            Ok(None) => return format!("There's no Minecraft account named {}", name),
+            Err(e) => {
+                warn!("Failed to look up Minecraft account {}: {:#}", name, e);
+                return "Couldn't look up that Minecraft account, please try again later".into();
+            }
+        };
+
+        let (reply_sender, reply_receiver) = oneshot::channel();
+        let request = WhitelistRequest {
+            discord_id,
+            profile,
+            reply: reply_sender,
+        };
+        if whitelist_sender.send(request).await.is_err() {
+            return "Couldn't add you to the whitelist".into();
+        }
+
+        reply_receiver
+            .await
+            .unwrap_or_else(|_| "Couldn't add you to the whitelist".into())
+    }
+
+    /// Whether a member with the given roles may run console commands
+    fn may_run(&self, roles: &[Id<RoleMarker>]) -> bool {
+        self.console_access()
+            .map(|access| access.roles.iter().any(|role| roles.contains(role)))
+            .unwrap_or(false)
+    }
+
+    /// Runs `command` for the Discord user `author` if their `roles` allow
+    /// it, returning the reply to send them
+    async fn run_command_for(
+        &self,
+        command: &str,
+        author: &User,
+        roles: &[Id<RoleMarker>],
+    ) -> String {
+        let access = match self.console_access() {
+            Some(access) if self.may_run(roles) => access,
+            _ => return "You don't have permission to run commands".into(),
+        };
+
+        let (output_sender, output_receiver) = oneshot::channel();
+        let request = CaptureRequest {
+            command: command.to_string(),
+            origin: CommandOrigin::Discord {
+                user: author.id,
+                name: author.name.clone(),
+            },
+            output: output_sender,
+        };
+        if access.capture_sender.send(request).await.is_err() {
+            return "The command couldn't be run".into();
+        }
+
+        match output_receiver.await {
+            Ok(Ok(lines)) => format_command_output(&lines),
+            Ok(Err(rejected)) => rejected.to_string(),
+            Err(_) => "The Minecraft server isn't running".into(),
+        }
+    }
+
     /// Handles any attachments in the given message
     async fn handle_attachments_in_msg(
         &self,
@@ -284,7 +950,7 @@ impl DiscordBridge {
                 "file"
             };
 
-            let tellraw_msg = tellraw_prefix()
+            let tellraw_msg = tellraw_prefix(self.chat_prefix())
                 .then(Payload::text(&format!("{} uploaded ", author_display_name)))
                 .italic(true)
                 .color(Color::Gray)
@@ -303,7 +969,10 @@ impl DiscordBridge {
                 ConsoleMsgType::Info,
                 format!(
                     "{}{} uploaded {}: {}",
-                    CHAT_PREFIX, author_display_name, type_str, attachment.url
+                    self.chat_prefix(),
+                    author_display_name,
+                    type_str,
+                    attachment.url
                 ),
             )
             .log();
@@ -318,7 +987,7 @@ impl DiscordBridge {
     /// Handles the content of the message
     ///
     /// This can only be called if `self.inner` is `Some`
-    async fn handle_msg_content<'a>(
+    async fn handle_msg_content(
         &self,
         msg: &Message,
         author_display_name: &str,
@@ -378,16 +1047,19 @@ impl DiscordBridge {
             }
         };
 
-        let tellraw_msg_builder = tellraw_prefix()
-            .then(Payload::text(&format!("<{}> ", author_display_name)))
-            .hover_show_text(username().as_str());
+        let start_tellraw = || {
+            tellraw_prefix(self.chat_prefix())
+                .then(Payload::text(&format!("<{}> ", author_display_name)))
+                .hover_show_text(username().as_str())
+        };
 
-        let (content, tellraw_msg_builder) = format_mentions_in(
+        let (content, tellraw_msg_builders) = format_mentions_in(
             &msg.content,
-            tellraw_msg_builder,
+            start_tellraw,
             mentions_map,
             &msg.mention_roles,
             cache,
+            self.link_filter(),
         );
 
         // Tellraw commands do not get logged to the console, so we
@@ -396,7 +1068,7 @@ impl DiscordBridge {
             ConsoleMsgType::Info,
             format!(
                 "{}<{} ({})> {}",
-                CHAT_PREFIX,
+                self.chat_prefix(),
                 author_display_name,
                 username(),
                 &content
@@ -404,12 +1076,21 @@ impl DiscordBridge {
         )
         .log();
 
-        mc_cmd_sender
-            .send(ServerCommand::TellRawAll(
-                tellraw_msg_builder.build().to_json().unwrap(),
-            ))
-            .await
-            .ok();
+        if tellraw_msg_builders.len() > MAX_MSG_PARTS {
+            debug!(
+                "Only sending the first {} of {} parts of a long message to Minecraft",
+                MAX_MSG_PARTS,
+                tellraw_msg_builders.len()
+            );
+        }
+        for tellraw_msg_builder in tellraw_msg_builders.into_iter().take(MAX_MSG_PARTS) {
+            mc_cmd_sender
+                .send(ServerCommand::TellRawAll(
+                    tellraw_msg_builder.build().to_json().unwrap(),
+                ))
+                .await
+                .ok();
+        }
     }
 
     /// Handles any embeds in the given message
@@ -424,6 +1105,7 @@ impl DiscordBridge {
             .iter()
             // Right now we only handle embeds with URLs
             .filter_map(|e| e.url.as_ref().map(|embed_url| (e, embed_url)))
+            .filter(|(_, embed_url)| self.link_filter().allows(embed_url))
         {
             let link_text = embed
                 .title
@@ -437,7 +1119,7 @@ impl DiscordBridge {
                 .map(|(embed_title, provider_name)| format!("{} - {}", provider_name, embed_title))
                 .unwrap_or_else(|| embed_url.clone());
 
-            let tellraw_msg = tellraw_prefix()
+            let tellraw_msg = tellraw_prefix(self.chat_prefix())
                 .then(Payload::text(&format!("{} linked \"", author_display_name)))
                 .italic(true)
                 .color(Color::Gray)
@@ -456,7 +1138,10 @@ impl DiscordBridge {
                 ConsoleMsgType::Info,
                 format!(
                     "{}{} linked \"{}\": {}",
-                    CHAT_PREFIX, author_display_name, link_text, embed_url
+                    self.chat_prefix(),
+                    author_display_name,
+                    link_text,
+                    embed_url
                 ),
             )
             .log();
@@ -470,22 +1155,39 @@ impl DiscordBridge {
 
     /// Sends the given text to the channel being bridged to
     ///
-    /// A new task is spawned to send the message, and its `JoinHandle` is
-    /// returned so its completion can be `await`ed if desired.
-    pub fn send_channel_msg<T: AsRef<str> + Send + 'static>(
-        self,
-        text: T,
-    ) -> tokio::task::JoinHandle<()> {
-        tokio::spawn(async move {
-            let text = text.as_ref();
+    /// The message is queued and sent in the background, possibly combined
+    /// with other waiting messages. It's dropped if too many messages are
+    /// already waiting.
+    pub fn send_channel_msg<T: AsRef<str>>(self, text: T) {
+        if let Some(inner) = self.inner {
+            inner.outbox.lock().unwrap().push(text.as_ref().to_string());
+            inner.outbox_notify.notify_one();
+        }
+    }
 
-            if let Some(inner) = self.inner {
-                let content_res = inner
+    /// Sends the messages queued with `send_channel_msg` one at a time until
+    /// the bridge is shut down
+    async fn send_outbox(self) {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        while !inner.outbox_closed.load(Ordering::Relaxed) {
+            inner.outbox_notify.notified().await;
+
+            loop {
+                let batch = inner.outbox.lock().unwrap().next_batch(MAX_MESSAGE_LEN);
+                let text = match batch {
+                    Some(text) => text,
+                    None => break,
+                };
+
+                match inner
                     .client
                     .create_message(self.bridge_channel_id)
-                    .content(text);
-
-                match content_res {
+                    .content(&text)
+                {
                     Ok(cm) => {
                         if let Err(e) = cm.await {
                             warn!("Failed to send Discord message: {}", e);
@@ -497,6 +1199,172 @@ impl DiscordBridge {
                     ),
                 }
             }
+        }
+    }
+
+    /// Shows `embed` in the status message pinned in the channel being bridged
+    /// to
+    ///
+    /// The status message is edited if there is one (including one pinned by
+    /// a previous run), and otherwise it's sent and pinned. A new task is
+    /// spawned to do this, and its `JoinHandle` is returned so its completion
+    /// can be `await`ed if desired.
+    pub fn update_status_embed(self, embed: Embed) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Some(inner) = &self.inner {
+                // Held throughout so only one status message is ever sent
+                let mut status_message = inner.status_message.lock().await;
+                if let Err(e) = self
+                    .update_status_embed_inner(inner, &mut status_message, embed)
+                    .await
+                {
+                    warn!("Failed to update the status message: {}", e);
+                }
+            }
+        })
+    }
+
+    async fn update_status_embed_inner(
+        &self,
+        inner: &DiscordBridgeInner,
+        status_message: &mut Option<Id<MessageMarker>>,
+        embed: Embed,
+    ) -> Result<(), anyhow::Error> {
+        let user_id = match inner.user_id.get() {
+            Some(user_id) => *user_id,
+            None => {
+                let user = inner.client.current_user().await?.model().await?;
+                *inner.user_id.get_or_init(|| user.id)
+            }
+        };
+        let embeds = [embed];
+
+        if status_message.is_none() {
+            let pins = inner
+                .client
+                .pins(self.bridge_channel_id)
+                .await?
+                .models()
+                .await?;
+            *status_message = pins
+                .iter()
+                .find(|msg| {
+                    msg.author.id == user_id
+                        && msg
+                            .embeds
+                            .iter()
+                            .any(|e| e.title.as_deref().is_some_and(status_embed::is_title))
+                })
+                .map(|msg| msg.id);
+        }
+
+        if let Some(message_id) = *status_message {
+            let edited = inner
+                .client
+                .update_message(self.bridge_channel_id, message_id)
+                .embeds(Some(&embeds))?
+                .await;
+            match edited {
+                Ok(_) => return Ok(()),
+                // The message was deleted, so a new one is sent
+                Err(e) if matches!(e.kind(), ErrorType::Response { status, .. } if status.get() == 404) =>
+                {
+                    *status_message = None;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let message = inner
+            .client
+            .create_message(self.bridge_channel_id)
+            .embeds(&embeds)?
+            .await?
+            .model()
+            .await?;
+        *status_message = Some(message.id);
+        inner
+            .client
+            .create_pin(self.bridge_channel_id, message.id)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Shows `text` in the startup progress message in the channel being
+    /// bridged to, sending it if it hasn't been sent yet
+    ///
+    /// Once `done` is set the message is left as it is, and the next update
+    /// sends a new one. A new task is spawned to do this, and its
+    /// `JoinHandle` is returned so its completion can be `await`ed if desired.
+    pub fn update_progress_message(self, text: String, done: bool) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Some(inner) = &self.inner {
+                // Held throughout so only one progress message is sent
+                let mut progress_message = inner.progress_message.lock().await;
+                if let Err(e) = self
+                    .update_progress_message_inner(inner, &mut progress_message, &text)
+                    .await
+                {
+                    warn!("Failed to update the startup progress message: {}", e);
+                }
+                if done {
+                    *progress_message = None;
+                }
+            }
+        })
+    }
+
+    async fn update_progress_message_inner(
+        &self,
+        inner: &DiscordBridgeInner,
+        progress_message: &mut Option<Id<MessageMarker>>,
+        text: &str,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(message_id) = *progress_message {
+            inner
+                .client
+                .update_message(self.bridge_channel_id, message_id)
+                .content(Some(text))?
+                .await?;
+        } else {
+            let message = inner
+                .client
+                .create_message(self.bridge_channel_id)
+                .content(text)?
+                .await?
+                .model()
+                .await?;
+            *progress_message = Some(message.id);
+        }
+
+        Ok(())
+    }
+
+    /// Renames the channel with the given ID, such as a voice channel used to
+    /// show the player count
+    ///
+    /// A new task is spawned to rename the channel, and its `JoinHandle` is
+    /// returned so its completion can be `await`ed if desired.
+    pub fn rename_channel(
+        self,
+        channel_id: Id<ChannelMarker>,
+        name: String,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            if let Some(inner) = self.inner {
+                match inner.client.update_channel(channel_id).name(&name) {
+                    Ok(request) => {
+                        if let Err(e) = request.await {
+                            warn!("Failed to rename Discord channel: {}", e);
+                        }
+                    }
+                    Err(validation_err) => warn!(
+                        "Validation error while attempting to rename channel: {}",
+                        validation_err
+                    ),
+                }
+            }
         })
     }
 
@@ -516,8 +1384,7 @@ impl DiscordBridge {
             }
             let text = text.into();
 
-            if let Some(inner) = self.inner {
-                let message_sender = inner.shard_message_sender.clone();
+            if let Some(message_sender) = self.shard_message_sender() {
                 match message_sender.command(
                     &UpdatePresence::new(vec![activity(text)], false, None, Status::Online)
                         .unwrap(),
@@ -529,3 +1396,18 @@ impl DiscordBridge {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reconnect_delays() {
+        let delays: Vec<_> = (0..4)
+            .map(|attempt| reconnect_delay(attempt).as_secs())
+            .collect();
+        assert_eq!(delays, [1, 2, 4, 8]);
+        assert_eq!(reconnect_delay(12), MAX_RECONNECT_DELAY);
+        assert_eq!(reconnect_delay(u32::MAX), MAX_RECONNECT_DELAY);
+    }
+}
@@ -0,0 +1,147 @@
+//! Queueing messages sent to the bridged Discord channel
+//!
+//! Messages are sent one at a time, so a burst of them (like a chat storm on
+//! the Minecraft server) can't pile up requests to Discord. Messages that are
+//! waiting are combined into as few Discord messages as they fit in, and if
+//! too many are waiting, new ones are dropped and replaced with a notice.
+
+use std::collections::VecDeque;
+
+use log::warn;
+
+/// How many messages can wait to be sent before new ones are dropped
+const MAX_QUEUED: usize = 64;
+
+/// Messages waiting to be sent to Discord
+#[derive(Debug)]
+pub struct Outbox {
+    queue: VecDeque<String>,
+    max_queued: usize,
+    /// How many messages were dropped since the last notice about it
+    dropped: usize,
+}
+
+impl Default for Outbox {
+    fn default() -> Self {
+        Self::new(MAX_QUEUED)
+    }
+}
+
+impl Outbox {
+    fn new(max_queued: usize) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            max_queued,
+            dropped: 0,
+        }
+    }
+
+    /// Queues `msg` to be sent, unless too many messages are waiting
+    pub fn push(&mut self, msg: String) {
+        // Room is needed for the notice about dropped messages too
+        let needed = if self.dropped > 0 { 2 } else { 1 };
+        if self.queue.len() + needed > self.max_queued {
+            if self.dropped == 0 {
+                warn!("Too many messages are waiting to be sent to Discord, dropping new ones");
+            }
+            self.dropped += 1;
+            return;
+        }
+
+        if let Some(notice) = self.take_dropped_notice() {
+            self.queue.push_back(notice);
+        }
+        self.queue.push_back(msg);
+    }
+
+    /// Takes the next message to send, combining as many waiting messages as
+    /// fit in `max_len` characters
+    pub fn next_batch(&mut self, max_len: usize) -> Option<String> {
+        let mut batch = match self.queue.pop_front() {
+            Some(msg) => msg,
+            None => return self.take_dropped_notice(),
+        };
+        let mut len = batch.chars().count();
+
+        while let Some(next) = self.queue.front() {
+            let next_len = next.chars().count();
+            if len + 1 + next_len > max_len {
+                break;
+            }
+
+            batch.push('\n');
+            batch.push_str(next);
+            len += 1 + next_len;
+            self.queue.pop_front();
+        }
+
+        Some(batch)
+    }
+
+    fn take_dropped_notice(&mut self) -> Option<String> {
+        match std::mem::take(&mut self.dropped) {
+            0 => None,
+            1 => Some("(1 message was dropped because too many were being sent)".into()),
+            dropped => Some(format!(
+                "({} messages were dropped because too many were being sent)",
+                dropped
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn drain(outbox: &mut Outbox, max_len: usize) -> Vec<String> {
+        std::iter::from_fn(|| outbox.next_batch(max_len)).collect()
+    }
+
+    #[test]
+    fn combined() {
+        let mut outbox = Outbox::default();
+        for msg in ["one", "two", "three", "a much longer message"] {
+            outbox.push(msg.into());
+        }
+
+        assert_eq!(
+            drain(&mut outbox, 12),
+            ["one\ntwo", "three", "a much longer message"]
+        );
+        assert_eq!(outbox.next_batch(12), None);
+    }
+
+    #[test]
+    fn dropped_when_full() {
+        let mut outbox = Outbox::new(3);
+        for i in 0..6 {
+            outbox.push(i.to_string());
+        }
+        assert_eq!(
+            drain(&mut outbox, 1),
+            [
+                "0",
+                "1",
+                "2",
+                "(3 messages were dropped because too many were being sent)"
+            ]
+        );
+
+        for i in 0..4 {
+            outbox.push(i.to_string());
+        }
+        for _ in 0..3 {
+            outbox.next_batch(1);
+        }
+        // The notice is sent in order with new messages
+        outbox.push("after".into());
+        assert_eq!(
+            drain(&mut outbox, 1),
+            [
+                "(1 message was dropped because too many were being sent)",
+                "after"
+            ]
+        );
+    }
+}
@@ -1,14 +1,26 @@
-use crate::OnlinePlayerInfo;
+use crate::{
+    chat::{self, ChatMessage, LinkFilter},
+    player_tracker::OnlinePlayerInfo,
+};
 
-use super::{message_span_iter::MessageSpan, CHAT_PREFIX};
+use super::{
+    markdown::{self, Style},
+    message_span_iter::MessageSpan,
+};
+use mc_server_wrapper_lib::local_time;
 use minecraft_chat::{Color, MessageBuilder, Payload};
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashMap},
+    convert::TryFrom,
     fmt::Write as _,
 };
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime, UtcOffset};
 use twilight_cache_inmemory::InMemoryCache;
-use twilight_mention::parse::MentionType;
+use twilight_mention::{
+    parse::MentionType,
+    timestamp::{Timestamp, TimestampStyle},
+};
 use twilight_model::{
     gateway::presence::{Activity, ActivityType},
     id::{
@@ -17,13 +29,13 @@ use twilight_model::{
     },
 };
 
-/// Returns a `MessageBuilder` with a nice prefix for Discord messages in
+/// Returns a `MessageBuilder` with a nice `prefix` for Discord messages in
 /// Minecraft
-pub fn tellraw_prefix() -> MessageBuilder {
+pub fn tellraw_prefix(prefix: &str) -> MessageBuilder {
     // Setting styles on the first payload sets them for all future payloads
     // just fyi
     MessageBuilder::builder(Payload::text(""))
-        .then(Payload::text(CHAT_PREFIX))
+        .then(Payload::text(prefix))
         .bold(true)
         .color(Color::LightPurple)
 }
@@ -59,100 +71,275 @@ pub fn activity(name: String) -> Activity {
 /// The given `cache` is used to get data to replace channel and role mention
 /// names with.
 ///
-/// The given `message_builder` is used to build up a Minecraft chat object with
-/// well-formatted text.
+/// Minecraft chat objects with well-formatted text are built up onto builders
+/// from `start_message`, split so each is at most `chat::MAX_LEN` characters
+/// long. URLs that `links` allows are made clickable.
 ///
-/// Returns (formatted_string, modified_chat_object_builder)
+/// Returns (formatted_string, chat_object_builders)
 pub fn format_mentions_in<S: AsRef<str>>(
     content: S,
-    mut message_builder: MessageBuilder,
+    start_message: impl Fn() -> MessageBuilder,
     mentions: HashMap<Id<UserMarker>, &str>,
     mention_roles: &[Id<RoleMarker>],
     cache: &InMemoryCache,
-) -> (String, MessageBuilder) {
-    // TODO: write a mc chat object crate to clean this code up
-    let mut cows = vec![];
-
-    for span in MessageSpan::iter(content.as_ref()) {
-        // Map each span into well-formatted text, both for Minecraft and anything
-        // else (like the TUI logs)
-        match span {
-            MessageSpan::Text(text) => {
-                message_builder = message_builder.then(Payload::text(text));
-                cows.push(Cow::from(text));
-            }
-            MessageSpan::Mention(mention_type, raw) => match mention_type {
-                MentionType::Channel(id) => {
-                    let cow = cache
-                        .channel(id)
-                        .and_then(|channel| {
-                            channel
-                                .name
-                                .as_ref()
-                                .map(|channel_name| Cow::from(format!("#{}", channel_name)))
-                        })
-                        // Throughout this function we fallback to the raw, unformatted
-                        // text if we're unable to fetch relevant info from the cache
-                        .unwrap_or_else(|| Cow::from(raw));
-
-                    message_builder = message_builder
-                        .then(Payload::text(cow.as_ref()))
-                        .color(Color::Blue);
-                    cows.push(cow);
-                }
-                MentionType::Emoji(id) => {
-                    // Non-custom emoji don't fall under this branch, but it would be
-                    // annoying and non-performant to parse those out and replace them
-                    // with :names:
-                    let cow = cache
-                        .emoji(id)
-                        .map(|emoji| Cow::from(format!(":{}:", &emoji.name())))
-                        .unwrap_or_else(|| Cow::from(raw));
-
-                    message_builder = message_builder.then(Payload::text(cow.as_ref()));
-                    cows.push(cow);
-                }
-                MentionType::Role(id) => {
-                    let cow = mention_roles
-                        .iter()
-                        .find(|r| r == &&id)
-                        .and_then(|role_id| cache.role(*role_id))
-                        .map(|role| Cow::from(format!("@{}", &role.name)))
-                        .unwrap_or_else(|| Cow::from(raw));
-
-                    message_builder = message_builder
-                        .then(Payload::text(cow.as_ref()))
-                        .color(Color::Blue);
-                    cows.push(cow)
-                }
-                MentionType::User(id) => {
-                    let cow = mentions
-                        .get(&id)
-                        .map(|name| Cow::from(format!("@{}", name)))
-                        .unwrap_or_else(|| Cow::from(raw));
-
-                    message_builder = message_builder
-                        .then(Payload::text(cow.as_ref()))
-                        .color(Color::Blue);
-
-                    if let Some(cached_user) = cache.user(id) {
-                        message_builder = message_builder.hover_show_text(&format!(
-                            "{}#{}",
-                            &cached_user.name, &cached_user.discriminator
-                        ));
-                    }
+    links: &LinkFilter,
+) -> (String, Vec<MessageBuilder>) {
+    let mut chat = ChatMessage::new(start_message, chat::MAX_LEN);
+
+    let runs = markdown::parse(content.as_ref());
+    for (i, run) in runs.iter().enumerate() {
+        let style =
+            |builder: MessageBuilder, text: &str| apply_style(builder, &run.style, text, links);
+
+        // Code is shown exactly as it was written
+        if run.style.code {
+            chat.push(run.text, style);
+            continue;
+        }
 
-                    cows.push(cow);
-                }
-                _ => {
-                    message_builder = message_builder.then(Payload::text(raw));
-                    cows.push(Cow::from(raw));
+        for span in MessageSpan::iter(run.text) {
+            // Map each span into well-formatted text, both for Minecraft and anything
+            // else (like the TUI logs)
+            match span {
+                MessageSpan::Text(text) => chat.push_linkified(text, links, style),
+                MessageSpan::Mention(mention_type, raw) => match mention_type {
+                    MentionType::Channel(id) => {
+                        let cow = cache
+                            .channel(id)
+                            .and_then(|channel| {
+                                channel
+                                    .name
+                                    .as_ref()
+                                    .map(|channel_name| Cow::from(format!("#{}", channel_name)))
+                            })
+                            // Throughout this function we fallback to the raw, unformatted
+                            // text if we're unable to fetch relevant info from the cache
+                            .unwrap_or_else(|| Cow::from(raw));
+
+                        chat.push(cow, |builder, text| style(builder.color(Color::Blue), text));
+                    }
+                    MentionType::Emoji(id) => {
+                        // Non-custom emoji don't fall under this branch, but it would be
+                        // annoying and non-performant to parse those out and replace them
+                        // with :names:
+                        let cow = cache
+                            .emoji(id)
+                            .map(|emoji| Cow::from(format!(":{}:", &emoji.name())))
+                            .or_else(|| {
+                                emoji_name(raw).map(|name| Cow::from(format!(":{}:", name)))
+                            })
+                            .unwrap_or_else(|| Cow::from(raw));
+
+                        chat.push(cow, style);
+                    }
+                    MentionType::Role(id) => {
+                        let cow = mention_roles
+                            .iter()
+                            .find(|r| r == &&id)
+                            .and_then(|role_id| cache.role(*role_id))
+                            .map(|role| Cow::from(format!("@{}", &role.name)))
+                            .unwrap_or_else(|| Cow::from(raw));
+
+                        chat.push(cow, |builder, text| style(builder.color(Color::Blue), text));
+                    }
+                    MentionType::User(id) => {
+                        let cow = mentions
+                            .get(&id)
+                            .map(|name| Cow::from(format!("@{}", name)))
+                            .unwrap_or_else(|| Cow::from(raw));
+
+                        chat.push(cow, |builder, text| {
+                            let mut builder = builder.color(Color::Blue);
+                            if let Some(cached_user) = cache.user(id) {
+                                builder = builder.hover_show_text(&format!(
+                                    "{}#{}",
+                                    &cached_user.name, &cached_user.discriminator
+                                ));
+                            }
+                            style(builder, text)
+                        });
+                    }
+                    MentionType::Timestamp(timestamp) => {
+                        let now = OffsetDateTime::now_utc();
+                        let offset = local_time::local_offset();
+                        let cow = format_timestamp(timestamp, now, offset)
+                            .map(Cow::from)
+                            .unwrap_or_else(|| Cow::from(raw));
+                        let full = format_timestamp(
+                            Timestamp::new(timestamp.unix(), Some(TimestampStyle::LongDateTime)),
+                            now,
+                            offset,
+                        );
+
+                        chat.push(cow, |builder, text| match &full {
+                            Some(full) => style(builder.hover_show_text(full), text),
+                            None => style(builder, text),
+                        });
+                    }
+                    _ => chat.push(raw, style),
+                },
+                MessageSpan::Command(name, _) => {
+                    chat.push(format!("/{}", name), |builder, text| {
+                        style(builder.color(Color::Blue), text)
+                    });
                 }
-            },
+            }
         }
+
+        // Links can't be clicked outside of Minecraft, so the URL is shown
+        // after the link's text instead
+        if let Some(url) = run.style.link {
+            if runs.get(i + 1).and_then(|next| next.style.link) != Some(url) {
+                chat.push_plain(format!(" ({})", url));
+            }
+        }
+    }
+
+    chat.finish()
+}
+
+/// Applies markdown `style` to the last component added to `message_builder`,
+/// which shows `text`
+///
+/// Masked links are only made clickable if `links` allows them.
+fn apply_style(
+    mut message_builder: MessageBuilder,
+    style: &Style<'_>,
+    text: &str,
+    links: &LinkFilter,
+) -> MessageBuilder {
+    if style.bold {
+        message_builder = message_builder.bold(true);
+    }
+    if style.italic {
+        message_builder = message_builder.italic(true);
+    }
+    if style.underline {
+        message_builder = message_builder.underlined(true);
+    }
+    if style.strikethrough {
+        message_builder = message_builder.strikethrough(true);
+    }
+    if style.code {
+        message_builder = message_builder.color(Color::Gray);
     }
+    if let Some(url) = style.link.filter(|url| links.allows(url)) {
+        message_builder = chat::link(message_builder, url);
+    }
+    if style.spoiler {
+        // Spoilers are scrambled, and hovering over them shows what they say
+        message_builder = message_builder.obfuscated(true).hover_show_text(text);
+    }
+
+    message_builder
+}
 
-    (cows.into_iter().collect(), message_builder)
+/// Returns the name of a custom emoji from its raw mention text (such as
+/// `<:name:123>` or `<a:name:123>`)
+fn emoji_name(raw: &str) -> Option<&str> {
+    let inner = raw.strip_prefix('<')?.strip_suffix('>')?;
+    let inner = inner.strip_prefix('a').unwrap_or(inner);
+    inner.strip_prefix(':')?.split(':').next()
+}
+
+/// Formats a timestamp mention the way Discord would show it in `offset`,
+/// with relative timestamps relative to `now`
+///
+/// Returns `None` if the timestamp is out of range.
+pub fn format_timestamp(
+    timestamp: Timestamp,
+    now: OffsetDateTime,
+    offset: UtcOffset,
+) -> Option<String> {
+    const SHORT_TIME: &[FormatItem<'_>] = format_description!("[hour]:[minute]");
+    const LONG_TIME: &[FormatItem<'_>] = format_description!("[hour]:[minute]:[second]");
+    const SHORT_DATE: &[FormatItem<'_>] = format_description!("[day]/[month]/[year]");
+    const LONG_DATE: &[FormatItem<'_>] =
+        format_description!("[day padding:none] [month repr:long] [year]");
+    const SHORT_DATE_TIME: &[FormatItem<'_>] =
+        format_description!("[day padding:none] [month repr:long] [year] [hour]:[minute]");
+    const LONG_DATE_TIME: &[FormatItem<'_>] = format_description!(
+        "[weekday], [day padding:none] [month repr:long] [year] [hour]:[minute]"
+    );
+
+    let time = OffsetDateTime::from_unix_timestamp(i64::try_from(timestamp.unix()).ok()?)
+        .ok()?
+        .to_offset(offset);
+    let format = match timestamp.style() {
+        Some(TimestampStyle::RelativeTime) => return Some(format_relative(time - now)),
+        Some(TimestampStyle::ShortTime) => SHORT_TIME,
+        Some(TimestampStyle::LongTime) => LONG_TIME,
+        Some(TimestampStyle::ShortDate) => SHORT_DATE,
+        Some(TimestampStyle::LongDate) => LONG_DATE,
+        Some(TimestampStyle::LongDateTime) => LONG_DATE_TIME,
+        Some(TimestampStyle::ShortDateTime) | None => SHORT_DATE_TIME,
+    };
+
+    time.format(format).ok()
+}
+
+/// Formats the time until (or, if negative, since) something happens, like
+/// "in 5 minutes" or "2 days ago"
+fn format_relative(duration: time::Duration) -> String {
+    let secs = duration.whole_seconds().unsigned_abs();
+    let (amount, unit) = match secs {
+        0..=59 => {
+            return if duration.is_negative() {
+                "a few seconds ago"
+            } else {
+                "in a few seconds"
+            }
+            .into()
+        }
+        60..=3_599 => (secs / 60, "minute"),
+        3_600..=86_399 => (secs / 3_600, "hour"),
+        86_400..=2_591_999 => (secs / 86_400, "day"),
+        2_592_000..=31_535_999 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+
+    if duration.is_negative() {
+        format!("{} {}{} ago", amount, unit, plural)
+    } else {
+        format!("in {} {}{}", amount, unit, plural)
+    }
+}
+
+/// Replaces `@name`s in an in-game chat message with Discord mentions
+///
+/// `names` maps lowercase names to the users they should mention. Names are
+/// matched case-insensitively, and only when the `@` starts a word (so email
+/// addresses are left alone).
+pub fn mention_users_in(msg: &str, names: &HashMap<String, Id<UserMarker>>) -> String {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+
+    let mut formatted = String::with_capacity(msg.len());
+    let mut rest = msg;
+    while let Some(at) = rest.find('@') {
+        let (before, after) = rest.split_at(at);
+        formatted.push_str(before);
+        let after = &after[1..];
+
+        let starts_word = formatted.chars().last().is_none_or(char::is_whitespace);
+        let end = after.find(|c| !is_name_char(c)).unwrap_or(after.len());
+        // A trailing period more likely ends a sentence than the name
+        let name = after[..end].trim_end_matches('.');
+
+        match names.get(&name.to_lowercase()) {
+            Some(id) if starts_word && !name.is_empty() => {
+                formatted.push_str(&format!("<@{}>", id));
+                rest = &after[name.len()..];
+            }
+            _ => {
+                formatted.push('@');
+                rest = after;
+            }
+        }
+    }
+    formatted.push_str(rest);
+
+    formatted
 }
 
 /// Different formats online player data can be turned into
@@ -307,6 +494,48 @@ pub fn sanitize_for_markdown<T: AsRef<str>>(text: T) -> String {
     })
 }
 
+/// Wraps links in the given text that `links` doesn't allow in inline code,
+/// so Discord doesn't make them clickable
+pub fn disable_links<'a>(text: &'a str, links: &LinkFilter) -> Cow<'a, str> {
+    let disallowed: Vec<_> = chat::find_urls(text)
+        .into_iter()
+        .filter(|range| !links.allows(&text[range.clone()]))
+        .collect();
+    if disallowed.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    let mut disabled = String::with_capacity(text.len() + disallowed.len() * 2);
+    let mut start = 0;
+    for range in disallowed {
+        disabled.push_str(&text[start..range.start]);
+        disabled.push('`');
+        disabled.push_str(&text[range.clone()]);
+        disabled.push('`');
+        start = range.end;
+    }
+    disabled.push_str(&text[start..]);
+    Cow::Owned(disabled)
+}
+
+#[cfg(test)]
+mod disable_links {
+    use super::*;
+
+    #[test]
+    fn disallowed_domains() {
+        let links = LinkFilter::new(&["minecraft.net".into()]);
+        assert_eq!(
+            disable_links("https://minecraft.net and https://evil.com/x.", &links),
+            "https://minecraft.net and `https://evil.com/x`."
+        );
+        assert!(matches!(
+            disable_links("https://evil.com", &LinkFilter::default()),
+            Cow::Borrowed(_)
+        ));
+    }
+}
+
 #[cfg(test)]
 mod sanitize_for_markdown {
     use super::*;
@@ -394,10 +623,11 @@ mod content_format_mentions {
         let msg = "";
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
 
         assert_eq!(formatted, "");
@@ -408,10 +638,11 @@ mod content_format_mentions {
         let msg = "the upcoming bracket <@thing is not a mention";
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
 
         assert_eq!(formatted, msg);
@@ -422,10 +653,11 @@ mod content_format_mentions {
         let msg = "><@!kksdk";
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
 
         assert_eq!(formatted, msg);
@@ -436,10 +668,11 @@ mod content_format_mentions {
         let msg = "<<><><@!><#><>#<>>>>";
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
 
         assert_eq!(formatted, msg);
@@ -450,10 +683,11 @@ mod content_format_mentions {
         let msg = "<@!12notanumber>";
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
 
         assert_eq!(formatted, msg);
@@ -464,10 +698,11 @@ mod content_format_mentions {
         let msg = "this has a mention: <@123>, but we're not passing mentions";
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
 
         assert_eq!(formatted, msg);
@@ -481,10 +716,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             mentions,
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
         assert_eq!(
             formatted,
@@ -501,10 +737,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             mentions,
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
         assert_eq!(formatted, "@TestName, and even @AnotherTest!");
     }
@@ -518,10 +755,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             mentions,
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
         assert_eq!(formatted, "@TestName, and even <@!321>, and wow: @WowTest");
     }
@@ -532,10 +770,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
         assert_eq!(formatted, msg);
     }
@@ -549,10 +788,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &cache,
+            &LinkFilter::default(),
         );
         assert_eq!(formatted, "this is a channel mention: #test-channel");
     }
@@ -566,10 +806,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &cache,
+            &LinkFilter::default(),
         );
         assert_eq!(
             formatted,
@@ -583,10 +824,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &InMemoryCache::new(),
+            &LinkFilter::default(),
         );
         assert_eq!(formatted, "this is a role mention: <@&2345>");
     }
@@ -600,10 +842,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[],
             &cache,
+            &LinkFilter::default(),
         );
         assert_eq!(formatted, msg);
     }
@@ -617,10 +860,11 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             HashMap::new(),
             &[Id::new(2345)],
             &cache,
+            &LinkFilter::default(),
         );
         assert_eq!(formatted, "this is a role mention: @test-role");
     }
@@ -638,16 +882,147 @@ mod content_format_mentions {
 
         let (formatted, _) = format_mentions_in(
             msg,
-            MessageBuilder::builder(Payload::text("")),
+            || MessageBuilder::builder(Payload::text("")),
             mentions,
             &[Id::new(2345)],
             &cache,
+            &LinkFilter::default(),
         );
         assert_eq!(
             formatted,
             "@TestName this channel (#test-channel) is pretty cool for the role @test-role!"
         );
     }
+
+    #[test]
+    fn commands_and_emoji() {
+        let msg = "try </whitelist me:12> <:wave:34> <a:party:56>";
+
+        let (formatted, _) = format_mentions_in(
+            msg,
+            || MessageBuilder::builder(Payload::text("")),
+            HashMap::new(),
+            &[],
+            &InMemoryCache::new(),
+            &LinkFilter::default(),
+        );
+        assert_eq!(formatted, "try /whitelist me :wave: :party:");
+    }
+
+    #[test]
+    fn markdown() {
+        let msg = "**hey** <@123>, see ||the [wiki](https://minecraft.wiki)|| and `<@123>`";
+        let mut mentions = HashMap::new();
+        mentions.insert(Id::new(123), "TestName");
+
+        let (formatted, mut builders) = format_mentions_in(
+            msg,
+            || MessageBuilder::builder(Payload::text("")),
+            mentions,
+            &[],
+            &InMemoryCache::new(),
+            &LinkFilter::default(),
+        );
+        assert_eq!(
+            formatted,
+            "hey @TestName, see the wiki (https://minecraft.wiki) and <@123>"
+        );
+
+        assert_eq!(builders.len(), 1);
+        let json = builders.pop().unwrap().build().to_json().unwrap();
+        for expected in [
+            r#""bold":true"#,
+            r#""obfuscated":true"#,
+            r#""value":"https://minecraft.wiki""#,
+            r#""text":"<@123>""#,
+        ] {
+            assert!(json.contains(expected), "{} not in {}", expected, json);
+        }
+    }
+}
+
+#[cfg(test)]
+mod format_timestamp {
+    use super::*;
+
+    fn format(unix: u64, style: Option<TimestampStyle>) -> Option<String> {
+        let now = OffsetDateTime::from_unix_timestamp(1_618_953_630).unwrap();
+        format_timestamp(Timestamp::new(unix, style), now, UtcOffset::UTC)
+    }
+
+    #[test]
+    fn styles() {
+        // Tuesday, 20 April 2021 21:20:30 UTC
+        let unix = 1_618_953_630;
+
+        assert_eq!(format(unix, None).unwrap(), "20 April 2021 21:20");
+        assert_eq!(
+            format(unix, Some(TimestampStyle::ShortTime)).unwrap(),
+            "21:20"
+        );
+        assert_eq!(
+            format(unix, Some(TimestampStyle::LongTime)).unwrap(),
+            "21:20:30"
+        );
+        assert_eq!(
+            format(unix, Some(TimestampStyle::ShortDate)).unwrap(),
+            "20/04/2021"
+        );
+        assert_eq!(
+            format(unix, Some(TimestampStyle::LongDate)).unwrap(),
+            "20 April 2021"
+        );
+        assert_eq!(
+            format(unix, Some(TimestampStyle::LongDateTime)).unwrap(),
+            "Tuesday, 20 April 2021 21:20"
+        );
+    }
+
+    #[test]
+    fn relative() {
+        let now = 1_618_953_630;
+        let relative = |unix| format(unix, Some(TimestampStyle::RelativeTime)).unwrap();
+
+        assert_eq!(relative(now + 10), "in a few seconds");
+        assert_eq!(relative(now - 10), "a few seconds ago");
+        assert_eq!(relative(now + 5 * 60), "in 5 minutes");
+        assert_eq!(relative(now - 60 * 60), "1 hour ago");
+        assert_eq!(relative(now - 3 * 86_400), "3 days ago");
+        assert_eq!(relative(now + 400 * 86_400), "in 1 year");
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(format(u64::MAX, None), None);
+    }
+}
+
+#[cfg(test)]
+mod mention_users_in {
+    use super::*;
+
+    fn names() -> HashMap<String, Id<UserMarker>> {
+        let mut names = HashMap::new();
+        names.insert("cldfire".to_string(), Id::new(1));
+        names.insert("some.one".to_string(), Id::new(2));
+        names
+    }
+
+    #[test]
+    fn mentions() {
+        assert_eq!(
+            mention_users_in("hey @Cldfire, @some.one. and @nobody", &names()),
+            "hey <@1>, <@2>. and @nobody"
+        );
+    }
+
+    #[test]
+    fn not_at_word_start() {
+        assert_eq!(
+            mention_users_in("mail me@cldfire or @cldfire_", &names()),
+            "mail me@cldfire or @cldfire_"
+        );
+    }
 }
 
 #[cfg(test)]
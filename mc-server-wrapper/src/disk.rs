@@ -0,0 +1,84 @@
+//! Monitoring world sizes and free disk space
+
+use std::{path::PathBuf, time::Duration};
+
+use log::warn;
+use tokio::sync::mpsc;
+
+use crate::{provision::server_dir, worlds};
+
+/// A measurement of the server's disk usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// The combined size of the server's worlds in bytes
+    pub world_size: u64,
+    /// The free space on the disk the server is on in bytes
+    pub free_space: u64,
+    /// The total size of the disk the server is on in bytes
+    pub total_space: u64,
+}
+
+impl DiskUsage {
+    /// Measures the disk usage of the server at `server_path`
+    pub async fn measure(server_path: PathBuf) -> Result<Self, anyhow::Error> {
+        let world_size = worlds::list(&server_path)
+            .await?
+            .iter()
+            .map(|w| w.size)
+            .sum();
+        let dir = server_dir(&server_path);
+
+        Ok(Self {
+            world_size,
+            free_space: fs2::available_space(&dir)?,
+            total_space: fs2::total_space(&dir)?,
+        })
+    }
+
+    /// Returns true if the free space is below `min_free_space` megabytes
+    pub fn is_low(&self, min_free_space: u64) -> bool {
+        self.free_space < min_free_space * 1024 * 1024
+    }
+}
+
+/// Spawns a task that measures the disk usage of the server at `server_path`
+/// every `interval`, sending each measurement over `usage_sender`
+pub fn spawn_disk_monitor(
+    server_path: PathBuf,
+    interval: Duration,
+    usage_sender: mpsc::Sender<DiskUsage>,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            match DiskUsage::measure(server_path.clone()).await {
+                Ok(usage) => {
+                    if usage_sender.send(usage).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => warn!("Failed to measure disk usage: {:#}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn low_space() {
+        let usage = DiskUsage {
+            world_size: 0,
+            free_space: 500 * 1024 * 1024,
+            total_space: 1000 * 1024 * 1024,
+        };
+
+        assert!(usage.is_low(1024));
+        assert!(!usage.is_low(500));
+    }
+}
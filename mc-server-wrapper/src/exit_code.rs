@@ -0,0 +1,75 @@
+//! The wrapper's process exit codes
+//!
+//! These let service managers and scripts tell why the wrapper stopped. For
+//! example, a systemd unit with `Restart=on-failure` can use
+//! `RestartPreventExitStatus=2 4 5 6` to avoid restarting when doing so won't
+//! help.
+
+use std::fmt;
+
+/// Why the wrapper exited
+///
+/// Errors can be tagged with one of these as context (`.context(ExitCode::Config)`)
+/// to pick the code the wrapper exits with when they're returned from `main`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// The wrapper was stopped normally
+    Clean = 0,
+    /// An error not covered by another code
+    Error = 1,
+    /// The config couldn't be loaded or is invalid
+    Config = 2,
+    /// The Minecraft server crashed too soon after starting to be restarted
+    CrashLoop = 3,
+    /// The Minecraft EULA wasn't agreed to
+    EulaDeclined = 4,
+    /// Discord rejected the bot token
+    DiscordAuth = 5,
+    /// Another wrapper is already managing the server
+    AlreadyRunning = 6,
+}
+
+impl ExitCode {
+    /// Returns the code to exit with for the given error
+    pub fn for_error(error: &anyhow::Error) -> Self {
+        error
+            .downcast_ref::<ExitCode>()
+            .copied()
+            .unwrap_or(ExitCode::Error)
+    }
+}
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExitCode::Clean => "stopped cleanly",
+            ExitCode::Error => "error",
+            ExitCode::Config => "invalid config",
+            ExitCode::CrashLoop => "the Minecraft server kept crashing",
+            ExitCode::EulaDeclined => "the Minecraft EULA was not agreed to",
+            ExitCode::DiscordAuth => "failed to log in to Discord",
+            ExitCode::AlreadyRunning => "another mc-server-wrapper is managing the server",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use anyhow::{anyhow, Context};
+
+    #[test]
+    fn tagged_errors() {
+        let error = Err::<(), _>(anyhow!("missing field `logging`"))
+            .context(ExitCode::Config)
+            .context("Failed to load the config")
+            .unwrap_err();
+
+        assert_eq!(ExitCode::for_error(&error), ExitCode::Config);
+    }
+
+    #[test]
+    fn untagged_errors() {
+        assert_eq!(ExitCode::for_error(&anyhow!("oops")), ExitCode::Error);
+    }
+}
@@ -0,0 +1,327 @@
+//! Exporting a server's setup to an archive and cloning it for staging
+//!
+//! `mc-server-wrapper export <zip>` packages the wrapper config, the server
+//! jar, `server.properties`, the whitelist, ops, and bans, plugins and mods
+//! with their configs (and with `--worlds`, the worlds) into an archive that
+//! `import` can set up elsewhere. `mc-server-wrapper clone <dir>` copies the
+//! same files into a new folder with the server moved to another port and the
+//! wrapper's outward-facing features turned off, for trying out upgrades
+//! without touching the real server.
+
+use std::{
+    convert::TryFrom,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{config::Config, properties::ServerProperties, provision::server_dir, worlds};
+
+/// The name of the wrapper config in exported archives and cloned folders
+pub const CONFIG_NAME: &str = "mc-server-wrapper-config.toml";
+/// The files and folders next to the server jar that are part of its setup
+const SETUP_PATHS: &[&str] = &[
+    "server.properties",
+    "eula.txt",
+    "whitelist.json",
+    "ops.json",
+    "banned-players.json",
+    "banned-ips.json",
+    "bukkit.yml",
+    "spigot.yml",
+    "paper.yml",
+    "purpur.yml",
+    "commands.yml",
+    "permissions.yml",
+    "plugins",
+    "mods",
+    "config",
+];
+/// The port servers use if `server-port` isn't set
+const DEFAULT_PORT: u16 = 25565;
+/// The properties holding the ports a server listens on
+pub const PORT_PROPERTIES: &[&str] = &["server-port", "rcon.port", "query.port"];
+
+/// Returns the paths (relative to the server's folder) of the files and
+/// folders that make up the setup of the server at `server_path`
+pub async fn setup_paths(
+    server_path: &Path,
+    with_worlds: bool,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let dir = server_dir(server_path);
+    let jar = server_path
+        .file_name()
+        .ok_or_else(|| anyhow!("{:?} isn't a server jar", server_path))?;
+
+    let mut paths = vec![PathBuf::from(jar)];
+    paths.extend(
+        SETUP_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .filter(|path| dir.join(path).exists()),
+    );
+    if with_worlds {
+        paths.extend(
+            worlds::list(server_path)
+                .await?
+                .into_iter()
+                .map(|world| PathBuf::from(world.name)),
+        );
+    }
+    Ok(paths)
+}
+
+/// Returns a copy of `config` for the server at `server_path`, with the
+/// other servers defined with `[[server]]` left out
+fn config_for(config: &Config, server_path: PathBuf) -> Result<Config, anyhow::Error> {
+    // Round-tripped since `Config` isn't `Clone`
+    let mut copy: Config = toml::from_str(&toml::to_string(config)?)?;
+    copy.minecraft.server_path = server_path;
+    copy.servers.clear();
+    Ok(copy)
+}
+
+/// Adds the file or folder at `path` to `zip` as `name`
+fn add_to_zip(zip: &mut ZipWriter<File>, path: &Path, name: &Path) -> Result<(), anyhow::Error> {
+    // Archives always use `/`
+    let zip_name = name
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    if path.is_dir() {
+        zip.add_directory(zip_name, FileOptions::default())?;
+        for entry in fs::read_dir(path).with_context(|| format!("Failed to read {:?}", path))? {
+            let entry = entry?;
+            add_to_zip(zip, &entry.path(), &name.join(entry.file_name()))?;
+        }
+    } else {
+        zip.start_file(zip_name, FileOptions::default())?;
+        io::copy(
+            &mut File::open(path).with_context(|| format!("Failed to open {:?}", path))?,
+            zip,
+        )?;
+    }
+    Ok(())
+}
+
+/// Copies the file or folder at `from` to `to`
+pub fn copy_recursively(from: &Path, to: &Path) -> Result<(), anyhow::Error> {
+    if from.is_dir() {
+        fs::create_dir_all(to).with_context(|| format!("Failed to create {:?}", to))?;
+        for entry in fs::read_dir(from).with_context(|| format!("Failed to read {:?}", from))? {
+            let entry = entry?;
+            copy_recursively(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, to).with_context(|| format!("Failed to copy {:?} to {:?}", from, to))?;
+    }
+    Ok(())
+}
+
+/// Moves the server's ports in `properties` to `port`, returning the port it
+/// used before
+///
+/// RCON and query ports are moved by the same amount so they don't clash
+/// with the original server's either.
+pub fn move_ports(
+    properties: &mut ServerProperties,
+    port: Option<u16>,
+) -> Result<u16, anyhow::Error> {
+    let old_port = properties
+        .get("server-port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let new_port = match port {
+        Some(port) => port,
+        None => old_port
+            .checked_add(1)
+            .ok_or_else(|| anyhow!("pass `--port` to pick the copy's port"))?,
+    };
+    if new_port == old_port {
+        bail!("the copy has to use a different port than {}", old_port);
+    }
+
+    let offset = i32::from(new_port) - i32::from(old_port);
+    properties.set("server-port", &new_port.to_string());
+    for key in &PORT_PROPERTIES[1..] {
+        let moved = properties
+            .get(key)
+            .and_then(|port| port.parse::<u16>().ok())
+            .and_then(|port| u16::try_from(i32::from(port) + offset).ok());
+        if let Some(moved) = moved {
+            properties.set(key, &moved.to_string());
+        }
+    }
+    Ok(old_port)
+}
+
+/// Runs `mc-server-wrapper export`, packaging the setup of the server in
+/// `config` into an archive at `archive_path`
+pub async fn export(
+    config: &Config,
+    archive_path: PathBuf,
+    with_worlds: bool,
+) -> Result<(), anyhow::Error> {
+    if archive_path.exists() {
+        bail!("{:?} already exists", archive_path);
+    }
+    let server_path = config.minecraft.server_path.clone();
+    let dir = server_dir(&server_path);
+    let paths = setup_paths(&server_path, with_worlds).await?;
+    let exported_config = toml::to_string(&config_for(
+        config,
+        PathBuf::from(server_path.file_name().unwrap()),
+    )?)?;
+
+    tokio::task::spawn_blocking({
+        let archive_path = archive_path.clone();
+        move || -> Result<(), anyhow::Error> {
+            let mut zip = ZipWriter::new(
+                File::create(&archive_path)
+                    .with_context(|| format!("Failed to create {:?}", archive_path))?,
+            );
+            let written = paths
+                .iter()
+                .try_for_each(|path| add_to_zip(&mut zip, &dir.join(path), path))
+                .and_then(|_| {
+                    zip.start_file(CONFIG_NAME, FileOptions::default())?;
+                    zip.write_all(exported_config.as_bytes())?;
+                    zip.finish()?;
+                    Ok(())
+                });
+            if written.is_err() {
+                let _ = fs::remove_file(&archive_path);
+            }
+            written
+        }
+    })
+    .await??;
+
+    println!("Exported the server to {:?}", archive_path);
+    println!(
+        "The config is included as it's written, so any tokens set in it directly (rather than \
+        with `env:` or `file:`) are in the archive too"
+    );
+    Ok(())
+}
+
+/// Runs `mc-server-wrapper clone`, copying the setup of the server in
+/// `config` into `dir` as a staging server on `port` (or the next port up)
+pub async fn clone(
+    config: &Config,
+    dir: PathBuf,
+    port: Option<u16>,
+    with_worlds: bool,
+) -> Result<(), anyhow::Error> {
+    if dir
+        .read_dir()
+        .is_ok_and(|mut entries| entries.next().is_some())
+    {
+        bail!("{:?} already exists and isn't empty", dir);
+    }
+    let server_path = config.minecraft.server_path.clone();
+    let from_dir = server_dir(&server_path);
+    let paths = setup_paths(&server_path, with_worlds).await?;
+
+    tokio::task::spawn_blocking({
+        let dir = dir.clone();
+        move || -> Result<(), anyhow::Error> {
+            fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+            let copied = paths
+                .iter()
+                .try_for_each(|path| copy_recursively(&from_dir.join(path), &dir.join(path)));
+            // The folder was empty, so nothing but the copy is lost
+            if copied.is_err() {
+                let _ = fs::remove_dir_all(&dir);
+            }
+            copied
+        }
+    })
+    .await??;
+
+    let clone_path = dir.join(server_path.file_name().unwrap());
+    let mut properties = ServerProperties::load(&clone_path).await?;
+    let old_port = move_ports(&mut properties, port)?;
+    properties.store(&clone_path).await?;
+    let new_port = properties.get("server-port").unwrap();
+
+    let mut clone_config = config_for(config, clone_path)?;
+    clone_config.minecraft.instance = dir.file_name().map(|name| name.to_string_lossy().into());
+    clone_config.disable_outward_features();
+    let config_path = dir.join(CONFIG_NAME);
+    clone_config.store(&config_path).await?;
+
+    println!(
+        "Cloned the server into {:?} on port {} (moved from {})",
+        dir, new_port, old_port
+    );
+    println!(
+        "The Discord bridge, Geyser, and the wrapper's endpoints are turned off in the copy; \
+        run it with `mc-server-wrapper --config {}`",
+        config_path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ports_moved() {
+        let mut properties =
+            ServerProperties::parse("server-port=25565\nrcon.port=25575\nquery.port=25565\n");
+
+        assert_eq!(move_ports(&mut properties, Some(25600)).unwrap(), 25565);
+        assert_eq!(properties.get("server-port").as_deref(), Some("25600"));
+        assert_eq!(properties.get("rcon.port").as_deref(), Some("25610"));
+        assert_eq!(properties.get("query.port").as_deref(), Some("25600"));
+
+        let mut properties = ServerProperties::parse("motd=hi\n");
+        assert_eq!(move_ports(&mut properties, None).unwrap(), DEFAULT_PORT);
+        assert_eq!(properties.get("server-port").as_deref(), Some("25566"));
+        assert!(move_ports(&mut properties, Some(25566)).is_err());
+    }
+
+    #[tokio::test]
+    async fn exports() {
+        let dir = std::env::temp_dir().join(format!("mcsw-test-export-{}", std::process::id()));
+        fs::create_dir_all(dir.join("world")).unwrap();
+        fs::write(dir.join("server.jar"), "jar").unwrap();
+        fs::write(dir.join("server.properties"), "server-port=25565\n").unwrap();
+        fs::write(dir.join("world/level.dat"), "level").unwrap();
+        fs::write(dir.join("latest.log"), "not part of the setup").unwrap();
+        let mut config = Config::default();
+        config.minecraft.server_path = dir.join("server.jar");
+        let archive_path = dir.join("export.zip");
+
+        let exported = export(&config, archive_path.clone(), true).await;
+        let names = File::open(&archive_path).map(|archive| {
+            let mut names: Vec<_> = zip::ZipArchive::new(archive)
+                .unwrap()
+                .file_names()
+                .map(str::to_string)
+                .collect();
+            names.sort();
+            names
+        });
+        let _ = fs::remove_dir_all(&dir);
+
+        exported.unwrap();
+        assert_eq!(
+            names.unwrap(),
+            [
+                CONFIG_NAME,
+                "server.jar",
+                "server.properties",
+                "world/",
+                "world/level.dat"
+            ]
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! Restarting several servers defined with `[[server]]` at once
+//!
+//! `mc-server-wrapper restart-servers` asks the wrapper running each server to
+//! restart it by writing a request next to the server jar. Running wrappers
+//! check for a request every few seconds, warn the server's players (for the
+//! server's own `restart_warning`, cut short once nobody is online), restart
+//! the server, and write back whether it finished loading again. Servers are
+//! restarted one at a time, or up to `--parallel` at once, and the outcome for
+//! each is reported at the end.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, bail, Context};
+use futures::future;
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
+use tokio::{fs, sync::Semaphore};
+
+use crate::config::Config;
+
+/// The file (next to the server jar) restart requests are written to
+const REQUEST_FILENAME: &str = "mc-server-wrapper-restart.json";
+/// The file (next to the server jar) the outcome of a restart is written to
+const RESULT_FILENAME: &str = "mc-server-wrapper-restart-result.json";
+/// How often running wrappers check for a restart request
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a running wrapper has to pick up a request
+const PICKUP_TIMEOUT: Duration = Duration::from_secs(15);
+/// How long before a restart players are warned about it (in seconds), after
+/// the first warning
+const WARNING_MARKS: &[u64] = &[300, 120, 60, 30, 10, 5];
+/// How long players are warned for if the server doesn't set
+/// `restart_warning`
+pub const DEFAULT_WARNING: u64 = 60;
+
+/// A request to restart a server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RestartRequest {
+    /// Matches the request to its result
+    pub id: String,
+    /// How long to warn players for before restarting the server
+    pub warning_seconds: u64,
+}
+
+/// The outcome of a restart request
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct RestartResult {
+    id: String,
+    /// Why the server couldn't be restarted, if it couldn't
+    error: Option<String>,
+}
+
+fn request_path(server_path: &Path) -> PathBuf {
+    server_path.with_file_name(REQUEST_FILENAME)
+}
+
+fn result_path(server_path: &Path) -> PathBuf {
+    server_path.with_file_name(RESULT_FILENAME)
+}
+
+/// Removes and returns the restart request for the server at `server_path`,
+/// if there is one
+pub async fn take_request(server_path: &Path) -> Option<RestartRequest> {
+    let path = request_path(server_path);
+    let contents = fs::read_to_string(&path).await.ok()?;
+    if let Err(e) = fs::remove_file(&path).await {
+        warn!("Failed to remove {:?}: {}", path, e);
+        return None;
+    }
+
+    match serde_json::from_str(&contents) {
+        Ok(request) => Some(request),
+        Err(e) => {
+            warn!("Ignoring an invalid restart request: {}", e);
+            None
+        }
+    }
+}
+
+/// Records the outcome of the restart request with `id` for the server at
+/// `server_path`, logging any failure
+pub async fn write_result(server_path: &Path, id: String, outcome: Result<(), String>) {
+    let path = result_path(server_path);
+    let result = RestartResult {
+        id,
+        error: outcome.err(),
+    };
+    // Serializing this can't fail
+    let contents = serde_json::to_string(&result).unwrap();
+    if let Err(e) = fs::write(&path, contents).await {
+        warn!("Failed to write {:?}: {}", path, e);
+    }
+}
+
+/// What to do next while draining a server before a restart
+#[derive(Debug, PartialEq, Eq)]
+pub enum DrainStep {
+    /// Warn players that the server restarts in this many seconds
+    Warn(u64),
+    /// Restart the server
+    Restart,
+}
+
+/// Warns a server's players before restarting it for a restart request
+#[derive(Debug)]
+pub struct Drain {
+    /// The ID of the request being handled
+    pub id: String,
+    restart_at: Instant,
+    /// The warnings still to give, soonest to the restart last
+    marks: Vec<u64>,
+}
+
+impl Drain {
+    pub fn new(request: RestartRequest, now: Instant) -> Self {
+        let warning = request.warning_seconds;
+        let mut marks = vec![];
+        if warning > 0 {
+            marks.push(warning);
+        }
+        marks.extend(WARNING_MARKS.iter().filter(|mark| **mark < warning));
+
+        Self {
+            id: request.id,
+            restart_at: now + Duration::from_secs(warning),
+            marks,
+        }
+    }
+
+    /// Returns what to do at `now`, if anything
+    ///
+    /// The restart is brought forward if no players are `online`.
+    pub fn step(&mut self, now: Instant, online: usize) -> Option<DrainStep> {
+        if online == 0 || now >= self.restart_at {
+            return Some(DrainStep::Restart);
+        }
+
+        let remaining = (self.restart_at - now).as_secs_f64();
+        // Only the latest of the warnings that are due is given, since the
+        // others are out of date
+        let due = self
+            .marks
+            .iter()
+            .rposition(|mark| remaining <= *mark as f64)?;
+        let mark = self.marks[due];
+        self.marks.drain(..=due);
+        Some(DrainStep::Warn(mark))
+    }
+}
+
+/// Returns an ID that's different for each request
+fn new_request_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!(
+        "{}-{}-{}",
+        now.as_millis(),
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Asks the wrapper running the server at `server_path` to restart it,
+/// waiting for the outcome
+///
+/// The server has `timeout` after the warning is over to finish loading
+/// again.
+async fn restart(
+    server_path: &Path,
+    warning_seconds: u64,
+    timeout: Duration,
+) -> Result<(), anyhow::Error> {
+    let request_path = request_path(server_path);
+    let result_path = result_path(server_path);
+    if request_path.exists() {
+        bail!("a restart was already requested");
+    }
+
+    let request = RestartRequest {
+        id: new_request_id(),
+        warning_seconds,
+    };
+    let _ = fs::remove_file(&result_path).await;
+    fs::write(&request_path, serde_json::to_string(&request)?)
+        .await
+        .with_context(|| format!("Failed to write {:?}", request_path))?;
+
+    let picked_up = tokio::time::timeout(PICKUP_TIMEOUT, async {
+        while request_path.exists() {
+            tokio::time::sleep(POLL_INTERVAL / 4).await;
+        }
+    })
+    .await;
+    if picked_up.is_err() {
+        let _ = fs::remove_file(&request_path).await;
+        bail!("the request wasn't picked up (is the wrapper running?)");
+    }
+
+    let wait = Duration::from_secs(warning_seconds) + timeout;
+    tokio::time::timeout(wait, async {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL / 2).await;
+            let result = match fs::read_to_string(&result_path).await {
+                Ok(contents) => serde_json::from_str::<RestartResult>(&contents).ok(),
+                Err(_) => None,
+            };
+            match result {
+                Some(result) if result.id == request.id => {
+                    return match result.error {
+                        Some(e) => Err(anyhow!(e)),
+                        None => Ok(()),
+                    };
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .map_err(|_| anyhow!("the server didn't finish loading in time"))?
+}
+
+/// A server to restart
+#[derive(Debug, PartialEq, Eq)]
+struct Target {
+    name: String,
+    server_path: PathBuf,
+    warning_seconds: u64,
+}
+
+/// Picks the servers in `config` named in `names` (or all of them if it's
+/// empty), warning players for `warning` seconds if it's set
+fn targets(
+    config: &Config,
+    names: &[String],
+    warning: Option<u64>,
+) -> Result<Vec<Target>, anyhow::Error> {
+    if config.servers.is_empty() {
+        bail!("No servers are defined with `[[server]]` in the config");
+    }
+    if let Some(name) = names
+        .iter()
+        .find(|name| !config.servers.iter().any(|s| &s.name == *name))
+    {
+        bail!("No server named \"{}\" is defined in the config", name);
+    }
+
+    Ok(config
+        .servers
+        .iter()
+        .filter(|server| names.is_empty() || names.contains(&server.name))
+        .map(|server| Target {
+            name: server.name.clone(),
+            server_path: server.minecraft.server_path.clone(),
+            warning_seconds: warning
+                .or(server.minecraft.restart_warning)
+                .unwrap_or(DEFAULT_WARNING),
+        })
+        .collect())
+}
+
+/// Runs `mc-server-wrapper restart-servers`, restarting the servers in
+/// `config` named in `names` (or all of them) with at most `parallel` at once
+pub async fn run(
+    config: &Config,
+    names: &[String],
+    parallel: usize,
+    warning: Option<u64>,
+    timeout: Duration,
+) -> Result<(), anyhow::Error> {
+    let targets = targets(config, names, warning)?;
+    let semaphore = &Semaphore::new(parallel.max(1));
+
+    let outcomes = future::join_all(targets.iter().map(|target| async move {
+        // The semaphore is never closed
+        let _permit = semaphore.acquire().await.unwrap();
+        println!(
+            "Restarting {} (warning players for {}s)",
+            target.name, target.warning_seconds
+        );
+        let started = Instant::now();
+        let outcome = restart(&target.server_path, target.warning_seconds, timeout).await;
+        match &outcome {
+            Ok(()) => println!("Restarted {}", target.name),
+            Err(e) => println!("Failed to restart {}: {:#}", target.name, e),
+        }
+        (outcome, started.elapsed())
+    }))
+    .await;
+
+    println!();
+    let mut failed = 0;
+    for (target, (outcome, took)) in targets.iter().zip(&outcomes) {
+        match outcome {
+            Ok(()) => println!("  {}: restarted in {}s", target.name, took.as_secs()),
+            Err(e) => {
+                failed += 1;
+                println!("  {}: failed ({:#})", target.name, e);
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} of {} servers failed to restart", failed, targets.len());
+    }
+    println!("Restarted all {} servers", targets.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::{Minecraft, ServerProfile};
+
+    use super::*;
+
+    fn request(warning_seconds: u64) -> RestartRequest {
+        RestartRequest {
+            id: "1".into(),
+            warning_seconds,
+        }
+    }
+
+    #[test]
+    fn drains() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut drain = Drain::new(request(90), start);
+
+        assert_eq!(drain.step(at(0), 3), Some(DrainStep::Warn(90)));
+        assert_eq!(drain.step(at(1), 3), None);
+        assert_eq!(drain.step(at(30), 3), Some(DrainStep::Warn(60)));
+        // Only the latest warning is given after falling behind
+        assert_eq!(drain.step(at(82), 3), Some(DrainStep::Warn(10)));
+        assert_eq!(drain.step(at(85), 3), Some(DrainStep::Warn(5)));
+        assert_eq!(drain.step(at(86), 3), None);
+        assert_eq!(drain.step(at(90), 3), Some(DrainStep::Restart));
+
+        let mut drain = Drain::new(request(90), start);
+        assert_eq!(drain.step(at(0), 0), Some(DrainStep::Restart));
+        let mut drain = Drain::new(request(0), start);
+        assert_eq!(drain.step(at(0), 3), Some(DrainStep::Restart));
+    }
+
+    #[test]
+    fn picks_targets() {
+        let mut config = Config::default();
+        for (name, restart_warning) in [("survival", Some(300)), ("creative", None)] {
+            config.servers.push(ServerProfile {
+                name: name.into(),
+                minecraft: Minecraft {
+                    server_path: format!("./{}/server.jar", name).into(),
+                    restart_warning,
+                    ..Minecraft::default()
+                },
+            });
+        }
+
+        let targets = targets(&config, &[], None).unwrap();
+        assert_eq!(
+            targets
+                .iter()
+                .map(|t| (t.name.as_str(), t.warning_seconds))
+                .collect::<Vec<_>>(),
+            [("survival", 300), ("creative", DEFAULT_WARNING)]
+        );
+        assert_eq!(
+            super::targets(&config, &["creative".into()], Some(10)).unwrap(),
+            [Target {
+                name: "creative".into(),
+                server_path: "./creative/server.jar".into(),
+                warning_seconds: 10,
+            }]
+        );
+        assert!(super::targets(&config, &["hub".into()], None).is_err());
+    }
+
+    #[tokio::test]
+    async fn restarts() {
+        let dir = std::env::temp_dir().join(format!("mcsw-test-restart-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let server_path = dir.join("server.jar");
+
+        // Stands in for the wrapper running the server
+        let wrapper = tokio::spawn({
+            let server_path = server_path.clone();
+            async move {
+                loop {
+                    if let Some(request) = take_request(&server_path).await {
+                        write_result(&server_path, request.id, Ok(())).await;
+                        return request.warning_seconds;
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+            }
+        });
+        let restarted = restart(&server_path, 0, Duration::from_secs(5)).await;
+        let warning_seconds = wrapper.await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        restarted.unwrap();
+        assert_eq!(warning_seconds.unwrap(), 0);
+    }
+}
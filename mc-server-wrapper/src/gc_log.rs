@@ -0,0 +1,340 @@
+//! Collecting the JVM's garbage collection log and finding long pauses
+//!
+//! With `[minecraft.gc_logging]` set, the server is started with `-Xlog:gc`
+//! writing to a file next to the server jar, which the JVM rotates itself.
+//! The file is followed while the wrapper runs, and pauses longer than
+//! `long_pause_ms` are reported along with whether the server fell behind
+//! ("Can't keep up!") around the same time, which points at the server's
+//! memory settings.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use tokio::{
+    fs,
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc,
+};
+
+/// The file (next to the server jar) the JVM writes its GC log to
+pub const GC_LOG_FILENAME: &str = "mc-server-wrapper-gc.log";
+/// How often the GC log is checked for new lines
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How close together a pause and the server falling behind have to be to be
+/// reported as related
+const CORRELATION_WINDOW: Duration = Duration::from_secs(10);
+/// How long long pauses are kept for `GcMonitor::summary`
+const HISTORY: Duration = Duration::from_secs(60 * 60);
+
+/// Returns the path of the GC log for the server at `server_path`
+pub fn path(server_path: &Path) -> PathBuf {
+    server_path.with_file_name(GC_LOG_FILENAME)
+}
+
+/// Returns the JVM flag that enables GC logging
+///
+/// The server runs in its own folder, so the log's path is relative to it.
+pub fn jvm_flag() -> String {
+    format!(
+        "-Xlog:gc:file={}:uptime,level,tags:filecount=5,filesize=10m",
+        GC_LOG_FILENAME
+    )
+}
+
+/// A garbage collection pause
+#[derive(Debug, Clone, PartialEq)]
+pub struct GcPause {
+    /// What kind of pause this was, like `Pause Young (Normal) (G1 Evacuation
+    /// Pause)`
+    pub kind: String,
+    pub ms: f64,
+}
+
+impl fmt::Display for GcPause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.0}ms ({})", self.ms, self.kind)
+    }
+}
+
+/// Parses a pause from a line of the GC log, like `[12.345s][info][gc] GC(3)
+/// Pause Young (Normal) (G1 Evacuation Pause) 24M->8M(256M) 3.456ms`
+pub fn parse_pause(line: &str) -> Option<GcPause> {
+    let (_, event) = line.split_once("] GC(")?;
+    let (_, event) = event.split_once(") ")?;
+    if !event.starts_with("Pause ") {
+        return None;
+    }
+
+    let (event, ms) = event.trim_end().rsplit_once(' ')?;
+    let ms = ms.strip_suffix("ms")?.parse().ok()?;
+    let kind = event
+        .split(' ')
+        .take_while(|word| !word.contains("->"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Some(GcPause { kind, ms })
+}
+
+/// Spawns a task that follows the GC log at `path`, sending each pause that's
+/// logged over `pause_sender`
+///
+/// Pauses logged before the task started aren't sent.
+pub fn spawn_follower(path: PathBuf, pause_sender: mpsc::Sender<GcPause>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        let mut offset = fs::metadata(&path).await.map(|m| m.len()).unwrap_or(0);
+        // A line the JVM hasn't finished writing yet
+        let mut partial = String::new();
+
+        loop {
+            interval.tick().await;
+
+            let len = match fs::metadata(&path).await {
+                Ok(metadata) => metadata.len(),
+                Err(_) => continue,
+            };
+            // The log was rotated (or the server restarted and replaced it)
+            if len < offset {
+                offset = 0;
+                partial.clear();
+            }
+            if len == offset {
+                continue;
+            }
+
+            let mut new = vec![];
+            let read = async {
+                let mut file = fs::File::open(&path).await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+                file.read_to_end(&mut new).await
+            };
+            if let Err(e) = read.await {
+                warn!("Failed to read the GC log {:?}: {}", path, e);
+                continue;
+            }
+            offset += new.len() as u64;
+
+            partial.push_str(&String::from_utf8_lossy(&new));
+            let complete = match partial.rfind('\n') {
+                Some(end) => partial.drain(..=end).collect::<String>(),
+                None => continue,
+            };
+            for pause in complete.lines().filter_map(parse_pause) {
+                if pause_sender.send(pause).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+/// A long pause that was reported
+#[derive(Debug)]
+struct LongPause {
+    at: Instant,
+    pause: GcPause,
+    /// Whether the server fell behind around the same time
+    overloaded: bool,
+}
+
+/// Keeps track of long GC pauses and relates them to the server falling
+/// behind
+#[derive(Debug)]
+pub struct GcMonitor {
+    long_pause_ms: f64,
+    long_pauses: VecDeque<LongPause>,
+    /// When the server last fell behind and by how many milliseconds
+    last_overload: Option<(Instant, u64)>,
+}
+
+impl GcMonitor {
+    pub fn new(long_pause_ms: u64) -> Self {
+        Self {
+            long_pause_ms: long_pause_ms as f64,
+            long_pauses: VecDeque::new(),
+            last_overload: None,
+        }
+    }
+
+    /// Records a pause that happened at `now`, returning a warning if it was
+    /// a long one
+    pub fn pause(&mut self, pause: GcPause, now: Instant) -> Option<String> {
+        if pause.ms < self.long_pause_ms {
+            return None;
+        }
+
+        let behind_ms = self
+            .last_overload
+            .filter(|(at, _)| now.saturating_duration_since(*at) <= CORRELATION_WINDOW)
+            .map(|(_, behind_ms)| behind_ms);
+        let warning = match behind_ms {
+            Some(behind_ms) => format!(
+                "Long GC pause: {} (the server fell behind by {}ms around the same time)",
+                pause, behind_ms
+            ),
+            None => format!("Long GC pause: {}", pause),
+        };
+
+        self.long_pauses.push_back(LongPause {
+            at: now,
+            pause,
+            overloaded: behind_ms.is_some(),
+        });
+        self.prune(now);
+        Some(warning)
+    }
+
+    /// Records that the server fell behind by `behind_ms` at `now`, returning
+    /// a warning if a long pause happened around the same time
+    pub fn overloaded(&mut self, behind_ms: u64, now: Instant) -> Option<String> {
+        self.last_overload = Some((now, behind_ms));
+
+        let recent = self
+            .long_pauses
+            .back_mut()
+            .filter(|p| now.saturating_duration_since(p.at) <= CORRELATION_WINDOW)?;
+        recent.overloaded = true;
+
+        Some(format!(
+            "The server fell behind by {}ms right after a {} GC pause; \
+            its memory settings may need tuning",
+            behind_ms, recent.pause
+        ))
+    }
+
+    /// Describes the long pauses in the last hour
+    pub fn summary(&mut self, now: Instant) -> String {
+        self.prune(now);
+
+        let longest = self
+            .long_pauses
+            .iter()
+            .map(|p| &p.pause)
+            .max_by(|a, b| a.ms.total_cmp(&b.ms));
+        match longest {
+            Some(longest) => format!(
+                "GC: {} long pauses in the last hour (longest {}), {} while the server fell behind",
+                self.long_pauses.len(),
+                longest,
+                self.long_pauses.iter().filter(|p| p.overloaded).count()
+            ),
+            None => "GC: no long pauses in the last hour".into(),
+        }
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while self
+            .long_pauses
+            .front()
+            .is_some_and(|p| now.saturating_duration_since(p.at) > HISTORY)
+        {
+            self.long_pauses.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pause(ms: f64) -> GcPause {
+        GcPause {
+            kind: "Pause Full (G1 Compaction Pause)".into(),
+            ms,
+        }
+    }
+
+    #[test]
+    fn pauses() {
+        assert_eq!(
+            parse_pause(
+                "[12.345s][info][gc] GC(3) Pause Young (Normal) (G1 Evacuation Pause) \
+                24M->8M(256M) 3.456ms"
+            ),
+            Some(GcPause {
+                kind: "Pause Young (Normal) (G1 Evacuation Pause)".into(),
+                ms: 3.456
+            })
+        );
+        assert_eq!(
+            parse_pause("[20.001s][info][gc] GC(7) Pause Mark Start 0.012ms"),
+            Some(GcPause {
+                kind: "Pause Mark Start".into(),
+                ms: 0.012
+            })
+        );
+        assert_eq!(
+            parse_pause("[20.5s][info][gc] GC(8) Concurrent Mark Cycle 45.100ms"),
+            None
+        );
+        assert_eq!(parse_pause("[0.010s][info][gc] Using G1"), None);
+    }
+
+    #[test]
+    fn short_pauses_ignored() {
+        let mut monitor = GcMonitor::new(500);
+        assert_eq!(monitor.pause(pause(120.0), Instant::now()), None);
+        assert_eq!(
+            monitor.summary(Instant::now()),
+            "GC: no long pauses in the last hour"
+        );
+    }
+
+    #[test]
+    fn pause_then_overload() {
+        let start = Instant::now();
+        let mut monitor = GcMonitor::new(500);
+
+        assert_eq!(
+            monitor.pause(pause(812.4), start).as_deref(),
+            Some("Long GC pause: 812ms (Pause Full (G1 Compaction Pause))")
+        );
+        assert_eq!(
+            monitor
+                .overloaded(2034, start + Duration::from_secs(3))
+                .as_deref(),
+            Some(
+                "The server fell behind by 2034ms right after a \
+                812ms (Pause Full (G1 Compaction Pause)) GC pause; \
+                its memory settings may need tuning"
+            )
+        );
+        assert_eq!(
+            monitor.summary(start + Duration::from_secs(4)),
+            "GC: 1 long pauses in the last hour (longest 812ms (Pause Full (G1 Compaction Pause))), \
+            1 while the server fell behind"
+        );
+    }
+
+    #[test]
+    fn overload_then_pause() {
+        let start = Instant::now();
+        let mut monitor = GcMonitor::new(500);
+
+        assert_eq!(monitor.overloaded(2034, start), None);
+        assert_eq!(
+            monitor
+                .pause(pause(900.0), start + Duration::from_secs(2))
+                .as_deref(),
+            Some(
+                "Long GC pause: 900ms (Pause Full (G1 Compaction Pause)) \
+                (the server fell behind by 2034ms around the same time)"
+            )
+        );
+
+        // Much later pauses aren't related
+        assert_eq!(
+            monitor
+                .pause(pause(900.0), start + Duration::from_secs(60))
+                .as_deref(),
+            Some("Long GC pause: 900ms (Pause Full (G1 Compaction Pause))")
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Running a Geyser standalone proxy alongside the Minecraft server
+//!
+//! Geyser lets Bedrock Edition players join a Java Edition server. The proxy
+//! is started once the server has finished loading and stopped whenever the
+//! server stops. Its output is shown in its own tab in the TUI.
+//!
+//! With Floodgate installed on the server, Bedrock players join with their
+//! name prefixed by Floodgate's `username-prefix` (`.` by default). Players
+//! that link a Java account join under that account's name instead, which is
+//! picked up from Geyser's output.
+
+use std::{collections::BTreeMap, io, process::ExitStatus, process::Stdio, time::Duration};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+    process::{ChildStdin, Command},
+    sync::{mpsc, oneshot},
+};
+
+use crate::config::Geyser;
+
+/// How long Geyser is given to shut down before it is killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events from the Geyser process
+#[derive(Debug)]
+pub enum GeyserEvent {
+    /// A line of output from Geyser
+    Line(String),
+    /// The Geyser process exited
+    Stopped(io::Result<ExitStatus>),
+}
+
+/// A running Geyser standalone process
+#[derive(Debug)]
+pub struct GeyserProcess {
+    stdin: ChildStdin,
+    /// Dropping this tells the process task to stop Geyser
+    _stop_sender: oneshot::Sender<()>,
+}
+
+impl GeyserProcess {
+    /// Starts Geyser with the given config, sending its output and exit to
+    /// `events`
+    pub fn start(config: &Geyser, events: mpsc::Sender<GeyserEvent>) -> io::Result<Self> {
+        let mut command = Command::new("java");
+        command
+            .arg(format!("-Xms{}M", config.memory))
+            .arg(format!("-Xmx{}M", config.memory))
+            .arg("-jar")
+            .arg(&config.jar_path)
+            .arg("--nogui")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        // Geyser keeps its config and logs next to the jar
+        if let Some(dir) = config
+            .jar_path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+        {
+            command.current_dir(dir);
+        }
+
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        tokio::spawn(forward_lines(child.stdout.take().unwrap(), events.clone()));
+        tokio::spawn(forward_lines(child.stderr.take().unwrap(), events.clone()));
+
+        let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            let result = tokio::select! {
+                result = child.wait() => result,
+                _ = stop_receiver => {
+                    match tokio::time::timeout(STOP_TIMEOUT, child.wait()).await {
+                        Ok(result) => result,
+                        Err(_) => {
+                            let _ = child.kill().await;
+                            child.wait().await
+                        }
+                    }
+                }
+            };
+
+            let _ = events.send(GeyserEvent::Stopped(result)).await;
+        });
+
+        Ok(Self {
+            stdin,
+            _stop_sender: stop_sender,
+        })
+    }
+
+    /// Writes `command` to Geyser's console
+    pub async fn write_command(&mut self, command: &str) -> io::Result<()> {
+        self.stdin.write_all(command.as_bytes()).await?;
+        self.stdin.write_all(b"\n").await?;
+        self.stdin.flush().await
+    }
+
+    /// Asks Geyser to stop, killing it if it doesn't within a few seconds
+    ///
+    /// A `GeyserEvent::Stopped` is sent once it has exited.
+    pub async fn stop(mut self) {
+        let _ = self.write_command("geyser stop").await;
+    }
+}
+
+async fn forward_lines(output: impl AsyncRead + Unpin, events: mpsc::Sender<GeyserEvent>) {
+    let mut lines = BufReader::new(output).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if events.send(GeyserEvent::Line(line)).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// A player event in Geyser's output
+#[derive(Debug, PartialEq, Eq)]
+pub enum GeyserMsg {
+    /// A Bedrock player joined the Java server as `java_name`
+    Connected {
+        bedrock_name: String,
+        java_name: String,
+    },
+    /// A Bedrock player left
+    Disconnected { bedrock_name: String },
+}
+
+impl GeyserMsg {
+    /// Parses a line of Geyser's output, like
+    /// `[12:00:00 INFO] Steve (logged in as: Steve) has connected to the Java server!`
+    pub fn parse(line: &str) -> Option<Self> {
+        let msg = line.split_once("] ").map(|(_, msg)| msg).unwrap_or(line);
+
+        if let Some((bedrock_name, rest)) = msg.split_once(" (logged in as: ") {
+            let (java_name, rest) = rest.split_once(')')?;
+            if rest.starts_with(" has connected to the Java server") {
+                return Some(Self::Connected {
+                    bedrock_name: bedrock_name.into(),
+                    java_name: java_name.into(),
+                });
+            }
+        }
+
+        msg.split_once(" has disconnected from the Java server")
+            .map(|(bedrock_name, _)| Self::Disconnected {
+                bedrock_name: bedrock_name.into(),
+            })
+    }
+}
+
+/// Tracks which of the players on the server joined through Geyser
+#[derive(Debug)]
+pub struct BedrockPlayers {
+    /// Floodgate's `username-prefix`
+    prefix: String,
+    /// Bedrock name -> name on the Java server
+    connected: BTreeMap<String, String>,
+}
+
+impl BedrockPlayers {
+    pub fn new(prefix: String) -> Self {
+        Self {
+            prefix,
+            connected: BTreeMap::new(),
+        }
+    }
+
+    /// Updates the tracked players based on `msg`
+    pub fn apply(&mut self, msg: GeyserMsg) {
+        match msg {
+            GeyserMsg::Connected {
+                bedrock_name,
+                java_name,
+            } => {
+                self.connected.insert(bedrock_name, java_name);
+            }
+            GeyserMsg::Disconnected { bedrock_name } => {
+                self.connected.remove(&bedrock_name);
+            }
+        }
+    }
+
+    /// Forgets all connected players (used when Geyser stops)
+    pub fn clear(&mut self) {
+        self.connected.clear();
+    }
+
+    /// Returns true if the player with the given name on the Java server
+    /// joined from Bedrock Edition
+    pub fn is_bedrock(&self, name: &str) -> bool {
+        (!self.prefix.is_empty() && name.starts_with(&self.prefix))
+            || self.connected.values().any(|n| n == name)
+    }
+
+    /// Returns the given name with Floodgate's prefix removed
+    pub fn display_name<'a>(&self, name: &'a str) -> &'a str {
+        if self.prefix.is_empty() {
+            name
+        } else {
+            name.strip_prefix(self.prefix.as_str()).unwrap_or(name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_connected() {
+        assert_eq!(
+            GeyserMsg::parse(
+                "[12:00:00 INFO] Steve (logged in as: .Steve) has connected to the Java server!"
+            ),
+            Some(GeyserMsg::Connected {
+                bedrock_name: "Steve".into(),
+                java_name: ".Steve".into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_disconnected() {
+        assert_eq!(
+            GeyserMsg::parse(
+                "[12:05:00 INFO] Steve has disconnected from the Java server because of Bedrock client disconnected"
+            ),
+            Some(GeyserMsg::Disconnected {
+                bedrock_name: "Steve".into()
+            })
+        );
+        assert_eq!(
+            GeyserMsg::parse("[12:00:00 INFO] Started Geyser on 0.0.0.0:19132"),
+            None
+        );
+    }
+
+    #[test]
+    fn bedrock_players() {
+        let mut players = BedrockPlayers::new(".".into());
+        assert!(players.is_bedrock(".Steve"));
+        assert!(!players.is_bedrock("Alex"));
+        assert_eq!(players.display_name(".Steve"), "Steve");
+        assert_eq!(players.display_name("Alex"), "Alex");
+
+        // A Bedrock player with a linked Java account
+        players.apply(GeyserMsg::Connected {
+            bedrock_name: "AlexPE".into(),
+            java_name: "Alex".into(),
+        });
+        assert!(players.is_bedrock("Alex"));
+        players.apply(GeyserMsg::Disconnected {
+            bedrock_name: "AlexPE".into(),
+        });
+        assert!(!players.is_bedrock("Alex"));
+    }
+}
@@ -0,0 +1,74 @@
+//! HTTP endpoints for container orchestrators and uptime monitors to probe
+//!
+//! `/healthz` responds with 200 while the wrapper is running, and `/readyz`
+//! responds with 200 once the Minecraft server has finished loading (503
+//! otherwise). They don't require authentication, so they don't say anything
+//! more than that. Nothing else is served.
+
+use std::sync::Arc;
+
+use mc_server_wrapper_lib::{status::McServerStatus, McServerManager};
+use tokio::net::TcpListener;
+
+use crate::http::{self, Response};
+
+/// Returns the response to a request for `path`
+///
+/// `ready` is whether the Minecraft server has finished loading.
+pub fn respond(path: &str, ready: bool) -> Response {
+    match path {
+        "/healthz" => Response::text(200, "ok\n"),
+        "/readyz" if ready => Response::text(200, "ready\n"),
+        "/readyz" => Response::text(503, "not ready\n"),
+        _ => Response::not_found(),
+    }
+}
+
+/// Answers probes sent to `listener` until the wrapper exits
+pub async fn serve(listener: TcpListener, mc_server: Arc<McServerManager>) {
+    http::serve(listener, "health check", move |path| {
+        let mc_server = mc_server.clone();
+        async move {
+            let ready = matches!(mc_server.status().await, McServerStatus::Running { .. });
+            respond(&path, ready)
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    use super::*;
+
+    #[test]
+    fn responses() {
+        assert_eq!(respond("/healthz", false).status, 200);
+        assert_eq!(respond("/readyz", false).status, 503);
+        assert_eq!(respond("/readyz", true).status, 200);
+        assert_eq!(respond("/status", true).status, 404);
+    }
+
+    #[tokio::test]
+    async fn serves_probes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (mc_server, _, _) = McServerManager::new();
+        tokio::spawn(serve(listener, mc_server));
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET /readyz HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.ends_with("\r\n\r\nnot ready\n"));
+    }
+}
@@ -0,0 +1,180 @@
+//! Keeping server list listings up to date with periodic heartbeats
+//!
+//! Each `[[heartbeat]]` in the config is a URL template (and optionally a
+//! body template) that's requested every `interval_minutes` with the server's
+//! current player count and version filled in. Placeholders in URLs are
+//! percent-encoded; placeholders in bodies are filled in as-is.
+
+use std::time::{Duration, Instant};
+
+use log::warn;
+
+use crate::config::Heartbeat;
+
+/// What's sent in a heartbeat
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub online: bool,
+    pub players: usize,
+    pub max_players: Option<u32>,
+    pub version: Option<String>,
+}
+
+/// Percent-encodes `value` for use in a URL
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Fills in the placeholders in `template`, percent-encoding the values if
+/// `url` is set
+fn fill(template: &str, key: Option<&str>, snapshot: &Snapshot, url: bool) -> String {
+    let values = [
+        ("{key}", key.unwrap_or_default().to_string()),
+        ("{online}", snapshot.online.to_string()),
+        ("{players}", snapshot.players.to_string()),
+        (
+            "{max_players}",
+            snapshot
+                .max_players
+                .map(|max| max.to_string())
+                .unwrap_or_default(),
+        ),
+        ("{version}", snapshot.version.clone().unwrap_or_default()),
+    ];
+
+    values
+        .iter()
+        .fold(template.to_string(), |text, (placeholder, value)| {
+            let value = if url { encode(value) } else { value.clone() };
+            text.replace(placeholder, &value)
+        })
+}
+
+/// Keeps track of when each heartbeat is next due
+#[derive(Debug)]
+pub struct Heartbeats {
+    next: Vec<Instant>,
+}
+
+impl Heartbeats {
+    /// Starts tracking `count` heartbeats, all due at `now`
+    pub fn new(count: usize, now: Instant) -> Self {
+        Self {
+            next: vec![now; count],
+        }
+    }
+
+    /// Returns the indexes of the heartbeats in `heartbeats` that are due at
+    /// `now`, scheduling their next ones
+    pub fn due(&mut self, heartbeats: &[Heartbeat], now: Instant) -> Vec<usize> {
+        let mut due = vec![];
+        for (i, (next, heartbeat)) in self.next.iter_mut().zip(heartbeats).enumerate() {
+            if *next <= now {
+                *next = now + Duration::from_secs(heartbeat.interval_minutes * 60);
+                due.push(i);
+            }
+        }
+        due
+    }
+}
+
+/// Sends `heartbeat` with the values in `snapshot`
+pub async fn send(client: &reqwest::Client, heartbeat: &Heartbeat, snapshot: &Snapshot) {
+    let key = heartbeat.key.as_deref();
+    let url = fill(&heartbeat.url, key, snapshot, true);
+    let request = match &heartbeat.body {
+        Some(body) => client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .body(fill(body, key, snapshot, false)),
+        None => client.get(&url),
+    };
+
+    let result = match request.send().await {
+        Ok(response) => response.error_for_status().map(|_| ()),
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        // The URL is left out since it can contain the API key
+        let host = reqwest::Url::parse(&heartbeat.url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+        warn!(
+            "Failed to send a server list heartbeat to {}: {}",
+            host,
+            e.without_url()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn heartbeat(interval_minutes: u64) -> Heartbeat {
+        Heartbeat {
+            url: "https://example.com".into(),
+            key: None,
+            body: None,
+            interval_minutes,
+        }
+    }
+
+    #[test]
+    fn filled() {
+        let snapshot = Snapshot {
+            online: true,
+            players: 3,
+            max_players: Some(20),
+            version: Some("Paper 1.20.1".into()),
+        };
+
+        assert_eq!(
+            fill(
+                "https://example.com/ping?key={key}&players={players}/{max_players}&v={version}",
+                Some("a&b"),
+                &snapshot,
+                true
+            ),
+            "https://example.com/ping?key=a%26b&players=3/20&v=Paper%201.20.1"
+        );
+        assert_eq!(
+            fill(
+                r#"{"online": {online}, "version": "{version}"}"#,
+                None,
+                &snapshot,
+                false
+            ),
+            r#"{"online": true, "version": "Paper 1.20.1"}"#
+        );
+    }
+
+    #[test]
+    fn scheduled() {
+        let heartbeats = [heartbeat(5), heartbeat(10)];
+        let start = Instant::now();
+        let mut scheduled = Heartbeats::new(heartbeats.len(), start);
+
+        assert_eq!(scheduled.due(&heartbeats, start), vec![0, 1]);
+        assert!(scheduled
+            .due(&heartbeats, start + Duration::from_secs(60))
+            .is_empty());
+        assert_eq!(
+            scheduled.due(&heartbeats, start + Duration::from_secs(5 * 60)),
+            vec![0]
+        );
+        assert_eq!(
+            scheduled.due(&heartbeats, start + Duration::from_secs(10 * 60)),
+            vec![0, 1]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! A minimal HTTP/1.1 server for the wrapper's endpoints
+//!
+//! Read-only endpoints (served with `serve`) only answer `GET` and `HEAD`
+//! requests and ignore request bodies. APIs (served with `serve_api`) also
+//! answer `POST` requests, with bodies of up to `MAX_BODY_LEN` bytes. Each
+//! connection is closed after one response.
+
+use std::{future::Future, time::Duration};
+
+use log::{debug, warn};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+/// The most of a request's line and headers that's read before giving up on
+/// it
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+/// The longest request body that's accepted
+const MAX_BODY_LEN: usize = 16 * 1024;
+/// The methods read-only endpoints answer
+const READ_ONLY_METHODS: &[&str] = &["GET", "HEAD"];
+/// The methods APIs answer
+const API_METHODS: &[&str] = &["GET", "HEAD", "POST"];
+
+/// How long a client has to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A response to a request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into().into_bytes(),
+        }
+    }
+
+    pub fn html(body: String) -> Self {
+        Self {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: body.into_bytes(),
+        }
+    }
+
+    pub fn png(body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type: "image/png",
+            body,
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::text(404, "not found\n")
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Payload Too Large",
+            503 => "Service Unavailable",
+            _ => "",
+        }
+    }
+
+    /// The response in HTTP/1.1 format, without the body if `head_only`
+    fn to_http(&self, head_only: bool) -> Vec<u8> {
+        let mut http = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\
+            Cache-Control: no-store\r\nConnection: close\r\n\r\n",
+            self.status,
+            self.reason(),
+            self.content_type,
+            self.body.len(),
+        )
+        .into_bytes();
+        if !head_only {
+            http.extend_from_slice(&self.body);
+        }
+        http
+    }
+}
+
+/// A request to an API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names and values, with the names in lowercase
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the value of the header called `name`, if it was sent
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers
+            .iter()
+            .find(|(header, _)| *header == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Returns the method and path of a request with the given request line (like
+/// `GET /healthz HTTP/1.1`), or the response to send if it can't be answered
+///
+/// Only `methods` are answered. Query strings (like cache busters) are dropped
+/// from the path.
+pub fn parse_request_line<'a>(
+    request_line: &'a str,
+    methods: &[&str],
+) -> Result<(&'a str, &'a str), Response> {
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method, target),
+        _ => return Err(Response::text(400, "bad request\n")),
+    };
+    if !methods.contains(&method) {
+        return Err(Response::text(405, "method not allowed\n"));
+    }
+
+    Ok((method, target.split('?').next().unwrap_or_default()))
+}
+
+/// Parses the headers in the head of a request (everything after the request
+/// line), lowercasing their names
+fn parse_headers(head: &str) -> Vec<(String, String)> {
+    head.lines()
+        .skip(1)
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect()
+}
+
+/// Answers requests sent to `listener` with `handler` until the wrapper
+/// exits
+///
+/// `handler` is given the requested path. `name` describes what's being
+/// served in log messages.
+pub async fn serve<F, Fut>(listener: TcpListener, name: &'static str, handler: F)
+where
+    F: Fn(String) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    serve_requests(listener, name, READ_ONLY_METHODS, move |request| {
+        handler(request.path)
+    })
+    .await
+}
+
+/// Answers API requests (including `POST` requests) sent to `listener` with
+/// `handler` until the wrapper exits
+///
+/// `name` describes what's being served in log messages.
+pub async fn serve_api<F, Fut>(listener: TcpListener, name: &'static str, handler: F)
+where
+    F: Fn(Request) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    serve_requests(listener, name, API_METHODS, handler).await
+}
+
+async fn serve_requests<F, Fut>(
+    listener: TcpListener,
+    name: &'static str,
+    methods: &'static [&'static str],
+    handler: F,
+) where
+    F: Fn(Request) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept a {} connection: {}", name, e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, methods, handler).await {
+                debug!("Failed to answer a {} request: {}", name, e);
+            }
+        });
+    }
+}
+
+async fn handle_connection<F, Fut>(
+    mut stream: TcpStream,
+    methods: &[&str],
+    handler: F,
+) -> Result<(), anyhow::Error>
+where
+    F: Fn(Request) -> Fut,
+    Fut: Future<Output = Response>,
+{
+    let read = async {
+        let (head, body) = read_request_head(&mut stream).await?;
+        let request_line = head.lines().next().unwrap_or_default();
+        let (method, path) = match parse_request_line(request_line, methods) {
+            Ok(parsed) => parsed,
+            Err(response) => return Ok(Err(response)),
+        };
+
+        let headers = parse_headers(&head);
+        let body = if method == "POST" {
+            let len = headers
+                .iter()
+                .find(|(name, _)| name == "content-length")
+                .and_then(|(_, len)| len.parse::<usize>().ok())
+                .unwrap_or(0);
+            if len > MAX_BODY_LEN {
+                return Ok(Err(Response::text(413, "request body too large\n")));
+            }
+            read_body(&mut stream, body, len).await?
+        } else {
+            vec![]
+        };
+
+        Ok::<_, anyhow::Error>(Ok(Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            headers,
+            body,
+        }))
+    };
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, read).await??;
+    let head_only = request
+        .as_ref()
+        .is_ok_and(|request| request.method == "HEAD");
+
+    let response = match request {
+        Ok(request) => handler(request).await,
+        Err(response) => response,
+    };
+    stream.write_all(&response.to_http(head_only)).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Reads the request line and headers of a request, returning them along with
+/// the start of the body if any of it was read too
+async fn read_request_head(stream: &mut TcpStream) -> Result<(String, Vec<u8>), anyhow::Error> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    let end = loop {
+        if let Some(end) = head.windows(4).position(|window| window == b"\r\n\r\n") {
+            break end + 4;
+        }
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break head.len();
+        }
+        head.extend_from_slice(&buf[..read]);
+        if head.len() > MAX_REQUEST_LEN {
+            anyhow::bail!("the request was too long");
+        }
+    };
+
+    let body = head.split_off(end);
+    Ok((String::from_utf8_lossy(&head).into_owned(), body))
+}
+
+/// Reads the rest of a request body that's `len` bytes long, given the part of
+/// it that's already been read
+async fn read_body(
+    stream: &mut TcpStream,
+    mut body: Vec<u8>,
+    len: usize,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let mut buf = [0; 1024];
+    while body.len() < len {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            anyhow::bail!("the request body ended early");
+        }
+        body.extend_from_slice(&buf[..read]);
+    }
+
+    body.truncate(len);
+    Ok(body)
+}
+
+/// Escapes `text` for use in HTML
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn request_lines() {
+        assert_eq!(
+            parse_request_line("GET /healthz HTTP/1.1", READ_ONLY_METHODS),
+            Ok(("GET", "/healthz"))
+        );
+        assert_eq!(
+            parse_request_line("HEAD /readyz?t=1 HTTP/1.1", READ_ONLY_METHODS),
+            Ok(("HEAD", "/readyz"))
+        );
+        assert_eq!(
+            parse_request_line("POST /healthz HTTP/1.1", READ_ONLY_METHODS)
+                .unwrap_err()
+                .status,
+            405
+        );
+        assert_eq!(
+            parse_request_line("POST /api/chat HTTP/1.1", API_METHODS),
+            Ok(("POST", "/api/chat"))
+        );
+        assert_eq!(
+            parse_request_line("", READ_ONLY_METHODS)
+                .unwrap_err()
+                .status,
+            400
+        );
+    }
+
+    #[test]
+    fn headers() {
+        let request = Request {
+            method: "POST".into(),
+            path: "/api/chat".into(),
+            headers: parse_headers(
+                "POST /api/chat HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer abc\r\n\r\n",
+            ),
+            body: vec![],
+        };
+
+        assert_eq!(request.header("authorization"), Some("Bearer abc"));
+        assert_eq!(request.header("Host"), Some("localhost"));
+        assert_eq!(request.header("Content-Length"), None);
+    }
+
+    #[test]
+    fn head_has_no_body() {
+        let http = String::from_utf8(Response::text(503, "not ready\n").to_http(true)).unwrap();
+        assert!(http.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(http.contains("Content-Length: 10\r\n"));
+        assert!(http.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn escaped() {
+        assert_eq!(
+            escape_html("<b>\"Tom\" & 'Jerry'</b>"),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;"
+        );
+    }
+}
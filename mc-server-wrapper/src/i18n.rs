@@ -0,0 +1,232 @@
+//! Translations of the wrapper's own text
+//!
+//! Text shown in the TUI, sent to Discord, and served on the status page comes
+//! from the locale bundle for the `language` config option. Bundles are the
+//! TOML files in `locales/`, built into the binary. Text from the Minecraft
+//! server itself (like chat and its console output) is passed through as-is,
+//! as are the wrapper's log messages.
+
+use std::fmt;
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde_derive::{Deserialize, Serialize};
+
+static LANGUAGE: OnceCell<Language> = OnceCell::new();
+
+static ENGLISH: Lazy<Strings> = Lazy::new(|| Strings::parse(include_str!("../locales/en.toml")));
+static GERMAN: Lazy<Strings> = Lazy::new(|| Strings::parse(include_str!("../locales/de.toml")));
+
+/// The languages the wrapper's text is available in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "de")]
+    German,
+}
+
+impl Language {
+    pub const ALL: &'static [Language] = &[Language::English, Language::German];
+
+    /// The language's code, like `en`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+}
+
+/// The text in a locale bundle
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Strings {
+    pub tui: Tui,
+    pub discord: Discord,
+    pub status_embed: StatusEmbed,
+    pub status_page: StatusPage,
+}
+
+impl Strings {
+    fn parse(bundle: &str) -> Self {
+        toml::from_str(bundle).expect("invalid locale bundle")
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Tui {
+    pub tab_logs: String,
+    pub tab_players: String,
+    pub tab_geyser: String,
+    pub maintenance_banner: String,
+    pub status_stopped: String,
+    pub status_starting: String,
+    /// `{progress}`
+    pub status_starting_progress: String,
+    /// `{uptime}`, `{players}`
+    pub status_running: String,
+    pub status_stopping: String,
+    pub restart_pending: String,
+    /// `{version}`
+    pub wrapper_update: String,
+    /// `{size}`
+    pub disk_free: String,
+    /// `{state}`
+    pub discord_state: String,
+    pub column_name: String,
+    pub column_login_time: String,
+    pub column_session_length: String,
+    pub column_last_location: String,
+    pub column_edition: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Discord {
+    /// `{player}`
+    pub player_joined: String,
+    /// `{player}`
+    pub player_left: String,
+    pub server_crashed: String,
+    pub server_restarting: String,
+    pub status_offline: String,
+    pub status_restarting: String,
+    pub maintenance_on: String,
+    pub maintenance_off: String,
+    pub backup_restored: String,
+    pub upgraded: String,
+    /// `{progress}`
+    pub startup_progress: String,
+    pub startup_finished: String,
+    pub startup_stopped: String,
+    /// `{streamer}`
+    pub stream_live: String,
+    /// `{player}`, `{service}`
+    pub vote: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct StatusEmbed {
+    pub title: String,
+    pub status: String,
+    pub version: String,
+    pub address: String,
+    /// `{count}`
+    pub players: String,
+    pub offline: String,
+    pub starting: String,
+    /// `{progress}`
+    pub starting_progress: String,
+    /// `{uptime}`
+    pub online: String,
+    pub stopping: String,
+    pub nobody_playing: String,
+    /// `{count}`
+    pub more_players: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct StatusPage {
+    pub default_title: String,
+    pub online: String,
+    pub starting: String,
+    /// `{progress}`
+    pub starting_progress: String,
+    pub offline: String,
+    pub players: String,
+    pub motd: String,
+    pub address: String,
+}
+
+/// Sets the language used for the wrapper's text
+///
+/// This can only be done once; English is used if it isn't done at all.
+pub fn set_language(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+/// Returns the language used for the wrapper's text
+pub fn language() -> Language {
+    LANGUAGE.get().copied().unwrap_or_default()
+}
+
+/// Returns the text for the language in use
+pub fn strings() -> &'static Strings {
+    strings_for(language())
+}
+
+/// Returns the text for `language`
+pub fn strings_for(language: Language) -> &'static Strings {
+    match language {
+        Language::English => &ENGLISH,
+        Language::German => &GERMAN,
+    }
+}
+
+/// Fills in the `{placeholders}` in `template`
+pub fn fill(template: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+    args.iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), &value.to_string())
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    const BUNDLES: &[(&str, &str)] = &[
+        ("en", include_str!("../locales/en.toml")),
+        ("de", include_str!("../locales/de.toml")),
+    ];
+
+    /// Returns each key in `bundle` along with the placeholders its text uses
+    fn placeholders(bundle: &str) -> BTreeSet<(String, Vec<String>)> {
+        let value: toml::Value = toml::from_str(bundle).unwrap();
+        let mut keys = BTreeSet::new();
+        for (section, table) in value.as_table().unwrap() {
+            for (key, text) in table.as_table().unwrap() {
+                let mut names: Vec<_> = text
+                    .as_str()
+                    .unwrap()
+                    .split('{')
+                    .skip(1)
+                    .filter_map(|rest| rest.split_once('}'))
+                    .map(|(name, _)| name.to_string())
+                    .collect();
+                names.sort();
+                keys.insert((format!("{}.{}", section, key), names));
+            }
+        }
+        keys
+    }
+
+    #[test]
+    fn bundles_match() {
+        let english = placeholders(BUNDLES[0].1);
+        for (code, bundle) in BUNDLES {
+            Strings::parse(bundle);
+            assert_eq!(placeholders(bundle), english, "{} differs from en", code);
+        }
+    }
+
+    #[test]
+    fn filled() {
+        assert_eq!(
+            fill(
+                &ENGLISH.tui.status_running,
+                &[("uptime", &"2h 5m"), ("players", &3)]
+            ),
+            "Running (2h 5m, 3 online)"
+        );
+        assert_eq!(
+            fill(&GERMAN.discord.player_joined, &[("player", &"Cldfire")]),
+            "_Cldfire hat das Spiel betreten_"
+        );
+    }
+}
@@ -0,0 +1,397 @@
+//! Importing an existing server from a `.zip` archive
+//!
+//! `mc-server-wrapper import <zip>` unpacks the archive (dropping a single
+//! top-level folder if everything is in one), finds the server jar, picks up
+//! the memory and JVM flags from any start scripts, writes a config for the
+//! wrapper (or uses the one in archives made with `export`), and reports
+//! anything about the EULA or the world that needs attention. This is meant for moving a server over from a hosting panel or
+//! a hand-written start script.
+
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::{backups, config::Config, export, worlds};
+
+/// The extensions of files that might be start scripts
+const SCRIPT_EXTENSIONS: &[&str] = &["sh", "bat", "cmd", "command"];
+/// Words in the names of jars that are probably the server
+const SERVER_JAR_HINTS: &[&str] = &[
+    "server",
+    "paper",
+    "purpur",
+    "spigot",
+    "fabric",
+    "forge",
+    "quilt",
+    "minecraft",
+];
+/// Forge keeps the JVM flags for its start scripts in this file
+const USER_JVM_ARGS: &str = "user_jvm_args.txt";
+
+/// What was found in a start script
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct StartScript {
+    /// The jar passed to `-jar`
+    jar: Option<String>,
+    /// The memory given with `-Xmx`, in megabytes
+    memory: Option<u16>,
+    /// Other JVM flags
+    flags: Vec<String>,
+}
+
+/// Parses a JVM memory size like `4G` or `2048M` into megabytes
+fn parse_memory(size: &str) -> Option<u16> {
+    let split = size.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = size.split_at(split);
+    let amount: u64 = amount.parse().ok()?;
+    let megabytes = match unit {
+        "k" | "K" => amount / 1024,
+        "m" | "M" => amount,
+        "g" | "G" => amount * 1024,
+        _ => return None,
+    };
+    Some(megabytes.min(u16::MAX as u64) as u16).filter(|&mb| mb > 0)
+}
+
+/// Picks the jar, memory, and JVM flags out of a start script
+///
+/// Only the flags before `-jar` on a line with `-jar` are read; the wrapper
+/// sets `-Xms` itself.
+fn parse_start_script(contents: &str) -> StartScript {
+    let mut script = StartScript::default();
+    for line in contents.lines().filter(|line| line.contains("-jar")) {
+        let mut args = line
+            .split_whitespace()
+            .map(|arg| arg.trim_matches(|c| c == '"' || c == '\''));
+        while let Some(arg) = args.next() {
+            if arg == "-jar" {
+                script.jar = args.next().map(str::to_string);
+                break;
+            } else if let Some(size) = arg.strip_prefix("-Xmx") {
+                script.memory = parse_memory(size);
+            } else if (arg.starts_with("-X") && !arg.starts_with("-Xms")) || arg.starts_with("-D") {
+                script.flags.push(arg.to_string());
+            }
+        }
+        if script.jar.is_some() {
+            break;
+        }
+    }
+    script
+}
+
+/// Returns the folder that every entry in an archive is in, if there is one
+fn common_root<'a>(names: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let mut root = None;
+    for name in names {
+        let (first, _) = name.split_once('/')?;
+        if first.is_empty() || root.is_some_and(|root| root != first) {
+            return None;
+        }
+        root = Some(first);
+    }
+    root.map(str::to_string)
+}
+
+/// Unpacks the archive at `archive_path` into `dir`
+fn unpack(archive_path: &Path, dir: &Path) -> Result<(), anyhow::Error> {
+    let mut archive = zip::ZipArchive::new(
+        File::open(archive_path).with_context(|| format!("Failed to open {:?}", archive_path))?,
+    )
+    .with_context(|| format!("{:?} is not a valid zip archive", archive_path))?;
+    let root = common_root(archive.file_names());
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let path = backups::entry_path(file.name())?;
+        let path = match &root {
+            Some(root) => path.strip_prefix(root).unwrap_or(&path).to_path_buf(),
+            None => path,
+        };
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let out_path = dir.join(path);
+
+        if file.is_dir() {
+            fs::create_dir_all(&out_path)?;
+        } else {
+            if let Some(parent) = out_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut file, &mut File::create(&out_path)?)
+                .with_context(|| format!("Failed to write {:?}", out_path))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the start scripts in `dir`
+fn read_start_scripts(dir: &Path) -> Result<StartScript, anyhow::Error> {
+    let mut found = StartScript::default();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_script = path
+            .extension()
+            .is_some_and(|ext| SCRIPT_EXTENSIONS.iter().any(|s| ext == *s));
+        if !is_script || !path.is_file() {
+            continue;
+        }
+
+        let script = parse_start_script(&fs::read_to_string(&path).unwrap_or_default());
+        if script.jar.is_some() && found.jar.is_none() {
+            found = script;
+        }
+    }
+
+    if let Ok(args) = fs::read_to_string(dir.join(USER_JVM_ARGS)) {
+        let args = parse_start_script(&format!(
+            "{} -jar",
+            args.lines()
+                .filter(|line| !line.trim_start().starts_with('#'))
+                .collect::<Vec<_>>()
+                .join(" ")
+        ));
+        found.memory = found.memory.or(args.memory);
+        found.flags.extend(args.flags);
+    }
+
+    Ok(found)
+}
+
+/// Picks the server jar out of the jars in the server's folder
+///
+/// The jar a start script runs is used if there is one.
+fn pick_jar(jars: &[String], from_script: Option<&str>) -> Result<String, anyhow::Error> {
+    if let Some(jar) = from_script.filter(|jar| jars.iter().any(|j| j == jar)) {
+        return Ok(jar.to_string());
+    }
+
+    let candidates: Vec<_> = jars
+        .iter()
+        .filter(|jar| !jar.to_lowercase().contains("installer"))
+        .collect();
+    if let [jar] = candidates.as_slice() {
+        return Ok(jar.to_string());
+    }
+    let hinted: Vec<_> = candidates
+        .iter()
+        .filter(|jar| {
+            let jar = jar.to_lowercase();
+            SERVER_JAR_HINTS.iter().any(|hint| jar.contains(hint))
+        })
+        .collect();
+    match hinted.as_slice() {
+        [jar] => Ok(jar.to_string()),
+        [] if candidates.is_empty() => Err(anyhow!(
+            "no server jar was found (servers started with `@libraries/...` arguments, like \
+            newer Forge servers, can't be run by the wrapper)"
+        )),
+        _ => Err(anyhow!(
+            "couldn't tell which jar is the server: {} (remove the others from the archive and \
+            try again)",
+            candidates
+                .iter()
+                .map(|jar| jar.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Returns notes about the EULA and the world of the server at `server_path`
+async fn check_layout(server_path: &Path) -> Result<Vec<String>, anyhow::Error> {
+    let dir = server_path.parent().unwrap_or_else(|| Path::new("."));
+    let mut notes = vec![];
+
+    let eula = tokio::fs::read_to_string(dir.join("eula.txt"))
+        .await
+        .unwrap_or_default();
+    if !eula.lines().any(|line| line.trim() == "eula=true") {
+        notes.push(
+            "The EULA hasn't been agreed to yet; the wrapper will agree to it when the server \
+            first starts (set `minecraft.auto_agree_eula = false` to be asked first)"
+                .into(),
+        );
+    }
+
+    if !dir.join("server.properties").exists() {
+        notes.push("There's no server.properties; the server will create one".into());
+    }
+    let world = worlds::active_world(server_path).await?;
+    if !dir.join(&world).join("level.dat").exists() {
+        notes.push(format!(
+            "The world \"{}\" (from `level-name`) wasn't found; the server will generate a \
+            new one",
+            world
+        ));
+    }
+
+    Ok(notes)
+}
+
+/// Runs `mc-server-wrapper import`, writing the wrapper's config to
+/// `config_path`
+///
+/// The server is unpacked into `dir`, or a folder named after the archive if
+/// that isn't given.
+pub async fn run(
+    archive_path: PathBuf,
+    dir: Option<PathBuf>,
+    config_path: &Path,
+) -> Result<(), anyhow::Error> {
+    if config_path.exists() {
+        bail!(
+            "{:?} already exists (pass `--config` to write the imported server's config \
+            somewhere else)",
+            config_path
+        );
+    }
+    let dir = match dir {
+        Some(dir) => dir,
+        None => PathBuf::from(
+            archive_path
+                .file_stem()
+                .ok_or_else(|| anyhow!("{:?} isn't a file", archive_path))?,
+        ),
+    };
+    if dir
+        .read_dir()
+        .is_ok_and(|mut entries| entries.next().is_some())
+    {
+        bail!("{:?} already exists and isn't empty", dir);
+    }
+
+    let (server_path, script) = tokio::task::spawn_blocking({
+        let dir = dir.clone();
+        move || -> Result<_, anyhow::Error> {
+            fs::create_dir_all(&dir).with_context(|| format!("Failed to create {:?}", dir))?;
+            let unpacked = unpack(&archive_path, &dir).and_then(|_| {
+                let mut jars = vec![];
+                for entry in fs::read_dir(&dir)? {
+                    let name = entry?.file_name().to_string_lossy().into_owned();
+                    if name.ends_with(".jar") {
+                        jars.push(name);
+                    }
+                }
+                jars.sort();
+                let script = read_start_scripts(&dir)?;
+                let jar = pick_jar(&jars, script.jar.as_deref())?;
+                Ok((dir.join(jar), script))
+            });
+            // The folder was empty, so nothing but the import is lost
+            if unpacked.is_err() {
+                let _ = fs::remove_dir_all(&dir);
+            }
+            unpacked
+        }
+    })
+    .await??;
+
+    // Archives from `export` come with their config
+    let included_config = dir.join(export::CONFIG_NAME);
+    let mut config = if included_config.is_file() {
+        Config::load(&included_config).await?
+    } else {
+        let mut config = Config::default();
+        if let Some(memory) = script.memory {
+            config.minecraft.memory = memory;
+        }
+        if !script.flags.is_empty() {
+            config.minecraft.jvm_flags = Some(script.flags.join(" "));
+        }
+        config
+    };
+    config.minecraft.server_path = server_path.clone();
+    config
+        .store(config_path)
+        .await
+        .with_context(|| format!("Failed to write the config to {:?}", config_path))?;
+
+    println!("Imported the server into {:?}", dir);
+    println!("  Server jar: {:?}", server_path);
+    println!("  Memory: {} MB", config.minecraft.memory);
+    if let Some(flags) = &config.minecraft.jvm_flags {
+        println!("  JVM flags: {}", flags);
+    }
+    println!("  Config: {:?}", config_path);
+    for note in check_layout(&server_path).await? {
+        println!("Note: {}", note);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn memory() {
+        assert_eq!(parse_memory("4G"), Some(4096));
+        assert_eq!(parse_memory("2048m"), Some(2048));
+        assert_eq!(parse_memory("524288K"), Some(512));
+        assert_eq!(parse_memory("4T"), None);
+        assert_eq!(parse_memory("G"), None);
+    }
+
+    #[test]
+    fn start_scripts() {
+        assert_eq!(
+            parse_start_script(
+                "#!/bin/sh\ncd \"$(dirname \"$0\")\"\n\
+                java -Xms1G -Xmx6G -XX:+UseG1GC -Dusing.aikars.flags=true -jar \"paper-1.20.1.jar\" nogui\n"
+            ),
+            StartScript {
+                jar: Some("paper-1.20.1.jar".into()),
+                memory: Some(6144),
+                flags: vec!["-XX:+UseG1GC".into(), "-Dusing.aikars.flags=true".into()],
+            }
+        );
+        assert_eq!(parse_start_script("echo hi"), StartScript::default());
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(
+            common_root([
+                "survival/",
+                "survival/server.jar",
+                "survival/world/level.dat"
+            ]),
+            Some("survival".into())
+        );
+        assert_eq!(common_root(["server.jar", "world/level.dat"]), None);
+        assert_eq!(common_root(["a/server.jar", "b/level.dat"]), None);
+    }
+
+    #[test]
+    fn jars() {
+        let jars = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            pick_jar(&jars(&["server.jar"]), None).unwrap(),
+            "server.jar"
+        );
+        assert_eq!(
+            pick_jar(&jars(&["custom.jar", "forge-installer.jar"]), None).unwrap(),
+            "custom.jar"
+        );
+        assert_eq!(
+            pick_jar(&jars(&["fabric-server-launch.jar", "lib.jar"]), None).unwrap(),
+            "fabric-server-launch.jar"
+        );
+        assert_eq!(
+            pick_jar(&jars(&["a.jar", "b.jar"]), Some("b.jar")).unwrap(),
+            "b.jar"
+        );
+        assert!(pick_jar(&jars(&["a.jar", "b.jar"]), None).is_err());
+        assert!(pick_jar(&[], None).is_err());
+    }
+}
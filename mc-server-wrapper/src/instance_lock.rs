@@ -0,0 +1,139 @@
+//! Making sure only one wrapper manages a server at a time
+//!
+//! The wrapper holds an exclusive lock on `mc-server-wrapper.lock` next to the
+//! server jar for as long as it runs, with its process ID written inside so
+//! a second wrapper can say which one is in its way. The operating system
+//! releases the lock if the wrapper crashes. On filesystems without locking
+//! (like some network shares), the recorded process ID is checked instead,
+//! which can be wrong after a crash if the ID has been reused; `--force`
+//! skips the check.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    path::Path,
+};
+
+use anyhow::bail;
+use fs2::FileExt;
+use log::warn;
+
+use crate::orphans;
+
+/// The file (next to the server jar) the lock is held on
+const LOCK_FILENAME: &str = "mc-server-wrapper.lock";
+
+/// The lock on a server, released when dropped
+#[derive(Debug)]
+pub struct InstanceLock {
+    file: File,
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // The file is left in place; removing it would let a wrapper waiting
+        // on the old file and one that creates a new file both get a lock
+        let _ = self.file.set_len(0);
+        let _ = self.file.unlock();
+    }
+}
+
+/// Returns the ID of the wrapper recorded in the lock file
+fn recorded_pid(file: &mut File) -> Option<u32> {
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).ok()?;
+    contents.trim().parse().ok()
+}
+
+/// Records this wrapper's process ID in the lock file
+fn record_pid(file: &mut File) -> io::Result<()> {
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "{}", std::process::id())?;
+    file.flush()
+}
+
+/// Locks the server at `server_path` for this wrapper
+///
+/// Fails if another wrapper is managing the server, unless `force` is set.
+/// `None` is returned when the lock is forced, since it's still held by the
+/// other wrapper.
+pub fn acquire(server_path: &Path, force: bool) -> Result<Option<InstanceLock>, anyhow::Error> {
+    let path = server_path.with_file_name(LOCK_FILENAME);
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+
+    let holder = match file.try_lock_exclusive() {
+        Ok(()) => None,
+        Err(e) if e.kind() == fs2::lock_contended_error().kind() => Some(
+            recorded_pid(&mut file)
+                .map_or_else(|| "unknown PID".into(), |pid| format!("PID {}", pid)),
+        ),
+        Err(e) => {
+            warn!(
+                "Couldn't lock {:?} ({}); checking the process ID recorded in it instead",
+                path, e
+            );
+            recorded_pid(&mut file)
+                .filter(|pid| *pid != std::process::id() && orphans::is_running(*pid))
+                .map(|pid| format!("PID {}", pid))
+        }
+    };
+
+    match holder {
+        Some(holder) if force => {
+            warn!(
+                "Another mc-server-wrapper ({}) is managing this server; continuing anyway \
+                because of `--force`",
+                holder
+            );
+            Ok(None)
+        }
+        Some(holder) => bail!(
+            "Another mc-server-wrapper ({}) is already managing the server in {:?}; stop it \
+            first, or pass `--force` if it isn't running anymore",
+            holder,
+            path.parent().unwrap_or(&path)
+        ),
+        None => {
+            record_pid(&mut file)?;
+            Ok(Some(InstanceLock { file }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn locked_once() {
+        let dir = std::env::temp_dir().join(format!("mcsw-test-lock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let server_path = dir.join("server.jar");
+
+        let first = acquire(&server_path, false);
+        let second = acquire(&server_path, false);
+        let forced = acquire(&server_path, true);
+        let recorded = std::fs::read_to_string(dir.join(LOCK_FILENAME));
+        drop(first);
+        let after_release = acquire(&server_path, false);
+        let reacquired = matches!(after_release, Ok(Some(_)));
+        drop(after_release);
+        let cleared = std::fs::read_to_string(dir.join(LOCK_FILENAME));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(second.unwrap_err().to_string().contains(&format!(
+            "Another mc-server-wrapper (PID {})",
+            std::process::id()
+        )));
+        assert!(forced.unwrap().is_none());
+        assert_eq!(recorded.unwrap(), std::process::id().to_string());
+        assert!(reacquired);
+        assert_eq!(cleared.unwrap(), "");
+    }
+}
@@ -0,0 +1,110 @@
+//! Links between Discord accounts and Minecraft names
+//!
+//! Links are saved next to the server jar. Each Discord account can be linked
+//! to one Minecraft name, and each name to one Discord account.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde_derive::{Deserialize, Serialize};
+use tokio::fs;
+
+/// The file (next to the server jar) links are saved in
+const LINKS_FILENAME: &str = "mc-server-wrapper-links.toml";
+
+/// Discord accounts and the Minecraft names they're linked to
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct Links {
+    /// Discord user ID -> Minecraft name
+    players: BTreeMap<String, String>,
+}
+
+fn links_path(server_path: &Path) -> PathBuf {
+    server_path.with_file_name(LINKS_FILENAME)
+}
+
+impl Links {
+    /// Reads the links saved for the server at `server_path`
+    pub async fn load(server_path: &Path) -> Result<Self, anyhow::Error> {
+        let path = links_path(server_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Saves these links for the server at `server_path`
+    pub async fn store(&self, server_path: &Path) -> Result<(), anyhow::Error> {
+        let path = links_path(server_path);
+        fs::write(&path, toml::to_string(self)?)
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// The Minecraft name linked to the given Discord user
+    pub fn minecraft_name(&self, discord_id: u64) -> Option<&str> {
+        self.players
+            .get(&discord_id.to_string())
+            .map(String::as_str)
+    }
+
+    /// Links the given Discord user to the Minecraft name `name`
+    ///
+    /// Fails if either is already linked to something else. Linking the same
+    /// pair again does nothing.
+    pub fn link(&mut self, discord_id: u64, name: &str) -> Result<(), anyhow::Error> {
+        if let Some(linked) = self.minecraft_name(discord_id) {
+            if linked.eq_ignore_ascii_case(name) {
+                return Ok(());
+            }
+
+            anyhow::bail!("Your Discord account is already linked to {}", linked);
+        }
+        if self
+            .players
+            .values()
+            .any(|linked| linked.eq_ignore_ascii_case(name))
+        {
+            anyhow::bail!("{} is already linked to another Discord account", name);
+        }
+
+        self.players
+            .insert(discord_id.to_string(), name.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn one_name_per_account() {
+        let mut links = Links::default();
+
+        links.link(1, "Cldfire").unwrap();
+        links.link(1, "cldfire").unwrap();
+        assert!(links.link(1, "Steve").is_err());
+        assert!(links.link(2, "CLDFIRE").is_err());
+        links.link(2, "Steve").unwrap();
+
+        assert_eq!(links.minecraft_name(1), Some("Cldfire"));
+        assert_eq!(links.minecraft_name(3), None);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut links = Links::default();
+        links.link(123, "Cldfire").unwrap();
+
+        let parsed: Links = toml::from_str(&toml::to_string(&links).unwrap()).unwrap();
+        assert_eq!(parsed, links);
+    }
+}
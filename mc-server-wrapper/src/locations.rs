@@ -0,0 +1,184 @@
+//! Tracking where players are and where they last died
+//!
+//! Locations come from players logging in and from the responses to
+//! `data get entity <name> Pos`, which are run for AFK checks and by the
+//! `whereis` console command. Death locations come from the responses to
+//! `data get entity <name> LastDeathLocation`, which Minecraft records as of
+//! 1.19; death messages don't include coordinates.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use time::OffsetDateTime;
+
+/// Where a player was at some point
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub position: [f64; 3],
+    /// The world or dimension, if known
+    pub world: Option<String>,
+    /// When the player was here
+    pub at: OffsetDateTime,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [x, y, z] = self.position;
+        write!(f, "{:.0}, {:.0}, {:.0}", x, y, z)?;
+        if let Some(world) = &self.world {
+            write!(f, " in {}", world)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The last known locations of players
+///
+/// Names are matched case-insensitively.
+#[derive(Debug, Default)]
+pub struct Locations {
+    last_known: HashMap<String, Location>,
+    last_death: HashMap<String, Location>,
+    /// Players whose position was looked up with `whereis`
+    pending_positions: HashSet<String>,
+    /// Players whose death location was looked up with `whereis`
+    pending_deaths: HashSet<String>,
+}
+
+impl Locations {
+    /// Records that the player named `name` was seen at `location`
+    pub fn seen(&mut self, name: &str, location: Location) {
+        self.last_known.insert(name.to_lowercase(), location);
+    }
+
+    /// Records that the player named `name` last died at `location`
+    pub fn died(&mut self, name: &str, location: Location) {
+        self.last_death.insert(name.to_lowercase(), location);
+    }
+
+    /// Returns where the player named `name` was last seen
+    pub fn last_known(&self, name: &str) -> Option<&Location> {
+        self.last_known.get(&name.to_lowercase())
+    }
+
+    /// Returns where the player named `name` last died
+    pub fn last_death(&self, name: &str) -> Option<&Location> {
+        self.last_death.get(&name.to_lowercase())
+    }
+
+    /// Returns the commands that look up where the online player named `name`
+    /// is and where they last died
+    ///
+    /// The responses should be reported rather than logged as-is, which
+    /// `take_pending_position` and `take_pending_death` keep track of.
+    pub fn whereis(&mut self, name: &str) -> [String; 2] {
+        self.pending_positions.insert(name.to_lowercase());
+        self.pending_deaths.insert(name.to_lowercase());
+
+        [
+            format!("data get entity {} Pos", name),
+            format!("data get entity {} LastDeathLocation", name),
+        ]
+    }
+
+    /// Returns true if `whereis` is waiting on the position of the player
+    /// named `name`, which it no longer is afterwards
+    pub fn take_pending_position(&mut self, name: &str) -> bool {
+        self.pending_positions.remove(&name.to_lowercase())
+    }
+
+    /// Returns true if `whereis` is waiting on the death location of the
+    /// player named `name`, which it no longer is afterwards
+    pub fn take_pending_death(&mut self, name: &str) -> bool {
+        self.pending_deaths.remove(&name.to_lowercase())
+    }
+}
+
+/// Parses the response to `data get entity <name> LastDeathLocation`, like
+/// `Cldfire has the following entity data: {dimension: "minecraft:overworld",
+/// pos: [I; -12, 64, 300]}`, into the player's name, the dimension, and the
+/// position
+pub fn parse_death_location(msg: &str) -> Option<(&str, &str, [f64; 3])> {
+    let (name, data) = msg.split_once(" has the following entity data: ")?;
+    let data = data.strip_prefix('{')?.strip_suffix('}')?;
+
+    let dimension = data.split_once("dimension: \"")?.1.split_once('"')?.0;
+    let pos = data.split_once("pos: [I;")?.1.split_once(']')?.0;
+
+    let mut coords = pos.split(',').map(|c| c.trim().parse::<i32>());
+    let position = [
+        f64::from(coords.next()?.ok()?),
+        f64::from(coords.next()?.ok()?),
+        f64::from(coords.next()?.ok()?),
+    ];
+    if coords.next().is_some() {
+        return None;
+    }
+
+    Some((name, dimension, position))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn death_locations() {
+        assert_eq!(
+            parse_death_location(
+                "Cldfire has the following entity data: \
+                {dimension: \"minecraft:the_nether\", pos: [I; -12, 64, 300]}"
+            ),
+            Some(("Cldfire", "minecraft:the_nether", [-12.0, 64.0, 300.0]))
+        );
+        assert_eq!(
+            parse_death_location("Cldfire has the following entity data: [0.5d, 64.0d, -12.3d]"),
+            None
+        );
+        assert_eq!(
+            parse_death_location(
+                "Cldfire has the following entity data: {dimension: \"minecraft:overworld\"}"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn lookups_are_case_insensitive() {
+        let mut locations = Locations::default();
+        locations.seen(
+            "Cldfire",
+            Location {
+                position: [0.5, 64.0, -12.7],
+                world: Some("world".into()),
+                at: OffsetDateTime::UNIX_EPOCH,
+            },
+        );
+
+        assert_eq!(
+            locations.last_known("cldfire").unwrap().to_string(),
+            "0, 64, -13 in world"
+        );
+        assert!(locations.last_death("Cldfire").is_none());
+    }
+
+    #[test]
+    fn pending_lookups() {
+        let mut locations = Locations::default();
+        assert_eq!(
+            locations.whereis("Cldfire"),
+            [
+                "data get entity Cldfire Pos",
+                "data get entity Cldfire LastDeathLocation"
+            ]
+        );
+
+        assert!(locations.take_pending_position("cldfire"));
+        assert!(!locations.take_pending_position("Cldfire"));
+        assert!(locations.take_pending_death("Cldfire"));
+        assert!(!locations.take_pending_death("Cldfire"));
+    }
+}
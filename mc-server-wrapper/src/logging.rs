@@ -1,15 +1,145 @@
-use mc_server_wrapper_lib::CONSOLE_MSG_LOG_TARGET;
-use std::path::Path;
-use time::format_description::FormatItem;
-use tokio::sync::mpsc::Sender;
+use crate::crash_reports;
+use mc_server_wrapper_lib::{local_time, CONSOLE_MSG_LOG_TARGET};
+use std::{
+    fmt,
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread::{self, ThreadId},
+};
+use time::{format_description::FormatItem, OffsetDateTime};
+use tokio::sync::mpsc::{error::TrySendError, Sender};
 
+/// A log record sent to the TUI
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    /// When the record was logged, in local time
+    pub timestamp: OffsetDateTime,
+    pub level: log::Level,
+    pub target: String,
+    pub message: String,
+}
+
+impl LogRecord {
+    fn new(record: &log::Record) -> Self {
+        Self {
+            timestamp: local_time::now_local(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LogRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const CONSOLE_TIMESTAMP_FORMAT: &[FormatItem] = time::macros::format_description!(
+            "[hour repr:12 padding:none]:[minute]:[second] [period]"
+        );
+
+        write!(
+            f,
+            "[{}] [{}, {}]: {}",
+            // TODO: log errors here somehow
+            self.timestamp
+                .format(&CONSOLE_TIMESTAMP_FORMAT)
+                .unwrap_or_else(|_| String::from("time error")),
+            self.target,
+            self.level,
+            self.message
+        )
+    }
+}
+
+/// Sends records to the TUI in the order they're logged
+///
+/// When the channel is full, threads wait for room so that no records are
+/// lost. The thread reading from the channel can't wait on itself, so its
+/// records are dropped instead (they're still written to the log file).
+struct RecordSender {
+    sender: Sender<LogRecord>,
+    reader: ThreadId,
+}
+
+impl RecordSender {
+    fn send(&self, record: LogRecord) {
+        let record = match self.sender.try_send(record) {
+            Err(TrySendError::Full(record)) => record,
+            Ok(()) | Err(TrySendError::Closed(_)) => return,
+        };
+        if thread::current().id() == self.reader {
+            return;
+        }
+
+        if tokio::runtime::Handle::try_current().is_ok() {
+            let _ = tokio::task::block_in_place(|| self.sender.blocking_send(record));
+        } else {
+            let _ = self.sender.blocking_send(record);
+        }
+    }
+}
+
+/// The levels used for file logging
+///
+/// These are kept outside of the logger so that they can be changed while
+/// the wrapper is running.
+struct FileLogLevels {
+    all: AtomicUsize,
+    self_level: AtomicUsize,
+    discord: AtomicUsize,
+}
+
+static FILE_LOG_LEVELS: FileLogLevels = FileLogLevels {
+    all: AtomicUsize::new(log::Level::Warn as usize),
+    self_level: AtomicUsize::new(log::Level::Debug as usize),
+    discord: AtomicUsize::new(log::Level::Info as usize),
+};
+
+impl FileLogLevels {
+    /// Whether a record with the given metadata should be written to the log file
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let target = metadata.target();
+        let level = if target.starts_with("twilight") {
+            &self.discord
+        } else if target == "mc_server_wrapper" || target.starts_with("mc_server_wrapper::") {
+            &self.self_level
+        } else {
+            &self.all
+        };
+
+        metadata.level() as usize <= level.load(Ordering::Relaxed)
+    }
+}
+
+/// Change the levels used for file logging
+pub fn set_file_log_levels(
+    log_level_all: log::Level,
+    log_level_self: log::Level,
+    log_level_discord: log::Level,
+) {
+    let levels = &FILE_LOG_LEVELS;
+    levels.all.store(log_level_all as usize, Ordering::Relaxed);
+    levels
+        .self_level
+        .store(log_level_self as usize, Ordering::Relaxed);
+    levels
+        .discord
+        .store(log_level_discord as usize, Ordering::Relaxed);
+}
+
+/// Set up logging to the log file at `logfile_path` and to the TUI through
+/// `log_sender`
+///
+/// This must be called from the thread that reads from `log_sender`'s
+/// receiver.
 pub fn setup_logger<P: AsRef<Path>>(
     logfile_path: P,
-    log_sender: Sender<String>,
+    log_sender: Sender<LogRecord>,
     log_level_all: log::Level,
     log_level_self: log::Level,
     log_level_discord: log::Level,
 ) -> Result<(), fern::InitError> {
+    set_file_log_levels(log_level_all, log_level_self, log_level_discord);
+
     let file_logger = fern::Dispatch::new()
         .format(|out, message, record| {
             const LOG_TIMESTAMP_FORMAT: &[FormatItem] = time::macros::format_description!(
@@ -18,9 +148,7 @@ pub fn setup_logger<P: AsRef<Path>>(
 
             let formatted_time_now = || -> Option<String> {
                 // TODO: log errors here somehow
-                time::OffsetDateTime::now_local()
-                    .ok()
-                    .and_then(|datetime| datetime.format(&LOG_TIMESTAMP_FORMAT).ok())
+                local_time::now_local().format(&LOG_TIMESTAMP_FORMAT).ok()
             };
 
             out.finish(format_args!(
@@ -31,21 +159,8 @@ pub fn setup_logger<P: AsRef<Path>>(
                 message
             ))
         })
-        .level(log_level_all.to_level_filter())
-        .level_for("twilight_http", log_level_discord.to_level_filter())
-        .level_for("twilight_gateway", log_level_discord.to_level_filter())
-        .level_for("twilight-cache", log_level_discord.to_level_filter())
-        .level_for(
-            "twilight-command-parser",
-            log_level_discord.to_level_filter(),
-        )
-        .level_for("twilight-model", log_level_discord.to_level_filter())
-        .level_for(
-            "twilight-cache-inmemory",
-            log_level_discord.to_level_filter(),
-        )
-        .level_for("twilight-cache-trait", log_level_discord.to_level_filter())
-        .level_for("mc_server_wrapper", log_level_self.to_level_filter())
+        .level(log::LevelFilter::Trace)
+        .filter(|metadata| FILE_LOG_LEVELS.enabled(metadata))
         .level_for(
             *CONSOLE_MSG_LOG_TARGET.get().unwrap(),
             log::LevelFilter::Off,
@@ -66,42 +181,25 @@ pub fn setup_logger<P: AsRef<Path>>(
             *CONSOLE_MSG_LOG_TARGET.get().unwrap(),
             log::LevelFilter::Info,
         )
-        .chain(fern::Output::call(move |record| {
-            const CONSOLE_TIMESTAMP_FORMAT: &[FormatItem] = time::macros::format_description!(
-                "[hour repr:12 padding:none]:[minute]:[second] [period]"
-            );
-
-            let formatted_time_now = || -> Option<String> {
-                // TODO: log errors here somehow
-                time::OffsetDateTime::now_local()
-                    .ok()
-                    .and_then(|datetime| datetime.format(&CONSOLE_TIMESTAMP_FORMAT).ok())
+        .chain({
+            let log_sender = RecordSender {
+                sender: log_sender,
+                reader: thread::current().id(),
             };
+            fern::Output::call(move |record| log_sender.send(LogRecord::new(record)))
+        });
 
-            let record = format!(
-                "[{}] [{}, {}]: {}",
-                formatted_time_now().unwrap_or_else(|| String::from("time error")),
-                record.target(),
-                record.level(),
-                record.args()
-            );
-
-            let log_sender_clone = log_sender.clone();
-            // TODO: right now log messages can print out-of-order because we
-            // don't block on sending them
-            //
-            // Tried using `Handle::block_on` but couldn't get it to not panic
-            // with `Illegal instruction`
-            //
-            // Need to investigate
-            tokio::spawn(async move {
-                let _ = log_sender_clone.send(record).await;
-            });
-        }));
+    // Errors are reported, and the rest kept as breadcrumbs, if crash
+    // reporting is enabled
+    let crash_reporter = fern::Dispatch::new()
+        .level(log::LevelFilter::Off)
+        .level_for("mc_server_wrapper", log::LevelFilter::Info)
+        .chain(fern::Output::call(crash_reports::log_record));
 
     fern::Dispatch::new()
         .chain(tui_logger)
         .chain(file_logger)
+        .chain(crash_reporter)
         .apply()?;
 
     Ok(())
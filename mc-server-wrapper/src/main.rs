@@ -1,61 +1,111 @@
-use std::{collections::BTreeMap, path::PathBuf, time::Instant};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
 
 use futures::{FutureExt, StreamExt};
-use time::OffsetDateTime;
-use tokio::sync::{mpsc, Mutex};
+use time::{OffsetDateTime, UtcOffset};
+use tokio::sync::{mpsc, oneshot};
 
-use once_cell::sync::OnceCell;
 use scopeguard::defer;
 
 use mc_server_wrapper_lib::{
-    communication::*, parse::*, McServerConfig, McServerManager, CONSOLE_MSG_LOG_TARGET,
+    communication::*, console_log_target, hooks::Hook, local_time, parse::*, replay::ReplayConfig,
+    status::ProcessInfo, McServerConfig, McServerManager, CONSOLE_MSG_LOG_TARGET,
 };
 
 use log::*;
 
 use crate::discord::{util::sanitize_for_markdown, *};
 
-use crate::ui::TuiState;
+use crate::{
+    chat::LinkFilter,
+    exit_code::ExitCode,
+    geyser::{BedrockPlayers, GeyserEvent, GeyserMsg, GeyserProcess},
+    player_tracker::{OnlinePlayers, PlayerTracker},
+    remote_commands::CommandRejected,
+    ui::{RedrawSchedule, TuiState},
+};
 
-use config::Config;
+use config::{Config, PlayerEvent};
 use crossterm::{
-    event::{Event, EventStream, KeyCode},
+    event::{DisableBracketedPaste, EnableBracketedPaste, Event, EventStream, KeyCode},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 use structopt::StructOpt;
-use util::{format_online_players, OnlinePlayerFormat};
 
+mod addons;
+mod afk;
+mod aliases;
+mod availability;
+mod backups;
+mod chat;
+mod chat_api;
+mod cli;
+mod command_capture;
+mod command_queue;
 mod config;
+mod config_check;
+mod config_migration;
+mod crash_reports;
+mod diagnostics;
 mod discord;
+mod disk;
+mod exit_code;
+mod export;
+mod fleet_restart;
+mod gc_log;
+mod geyser;
+mod health;
+mod heartbeats;
+mod http;
+mod i18n;
+mod import;
+mod instance_lock;
+mod links;
+mod locations;
 mod logging;
+mod maintenance;
+mod moderation;
+mod orphans;
+mod panic_hook;
+mod player_count;
+mod player_counter;
+mod player_notifications;
+mod player_tracker;
+mod pregen;
+mod presence;
+mod profiling;
+mod properties;
+mod provision;
+mod remote_commands;
+mod restart_vote;
+mod self_update;
+mod server_icon;
+mod sessions;
+mod staging;
+mod startup_progress;
+mod stats;
+mod status_embed;
+mod status_page;
+mod stream_alerts;
 mod ui;
+mod votifier;
+mod watchlist;
+mod whitelist;
+mod worlds;
 
 static APPLICATION_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-/// Maintains a hashset of players currently on the Minecraft server
-///
-/// Player name -> info
-static ONLINE_PLAYERS: OnceCell<Mutex<BTreeMap<String, OnlinePlayerInfo>>> = OnceCell::new();
-
-/// Info about online players
-#[derive(Debug)]
-pub struct OnlinePlayerInfo {
-    joined_at: OffsetDateTime,
-}
-
-impl Default for OnlinePlayerInfo {
-    fn default() -> Self {
-        Self {
-            joined_at: OffsetDateTime::now_utc(),
-        }
-    }
-}
+/// How long `profile` runs the profiler for if no duration is given
+const DEFAULT_PROFILE_DURATION: Duration = Duration::from_secs(60);
 
-#[derive(StructOpt, Debug)]
+#[derive(StructOpt, Debug, Clone)]
 pub struct Opt {
     /// Path to config
     #[structopt(
@@ -74,52 +124,367 @@ pub struct Opt {
     #[structopt(short = "V", long)]
     version: bool,
 
+    /// Check the config for problems and then exit the program
+    #[structopt(long)]
+    check_config: bool,
+
     /// Path to the Minecraft server jar
     #[structopt(parse(from_os_str))]
     server_path: Option<PathBuf>,
 
+    /// The name of a server defined with `[[server]]` in the config to run
+    #[structopt(short = "s", long)]
+    server: Option<String>,
+
+    /// Amount of memory in megabytes to allocate for the server
+    #[structopt(long)]
+    memory: Option<u16>,
+
+    /// Custom flags to pass to the JVM
+    #[structopt(long, allow_hyphen_values = true)]
+    jvm_flags: Option<String>,
+
     /// Bridge server chat to discord
     #[structopt(short = "b", long)]
     bridge_to_discord: bool,
+
+    /// Replay a recorded server log (such as `logs/latest.log`) instead of
+    /// running the server
+    #[structopt(long, parse(from_os_str))]
+    replay: Option<PathBuf>,
+
+    /// How many times faster than real time to replay the log, or 0 to
+    /// replay it as fast as possible
+    // This can't use `requires = "replay"`, since clap counts the default
+    // value as the flag being given and then rejects every run without
+    // `--replay` (including subcommands)
+    #[structopt(long, default_value = "1")]
+    replay_speed: f64,
+
+    /// Start without running the Minecraft server, to use console commands
+    /// like `provision`, `restore`, and `import-logs` before there is one
+    #[structopt(long, conflicts_with = "replay")]
+    no_server: bool,
+
+    /// Run the staging variant of the server (see the `staging` section of the
+    /// server's config)
+    #[structopt(long)]
+    staging: bool,
+
+    /// Start even if another mc-server-wrapper seems to be managing the
+    /// server (for a lock left behind after a crash)
+    #[structopt(long)]
+    force: bool,
+
+    /// Print a man page and then exit the program
+    #[structopt(long)]
+    man: bool,
+
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
 }
 
-#[tokio::main]
-async fn main() -> Result<(), anyhow::Error> {
-    // See https://github.com/time-rs/time/issues/293#issuecomment-1005002386. The
-    // unsoundness here is not in the `time` library, but in the Rust stdlib, and as
-    // such it needs to be fixed there.
-    unsafe {
-        time::util::local_offset::set_soundness(time::util::local_offset::Soundness::Unsound);
+#[derive(StructOpt, Debug, Clone)]
+pub enum Command {
+    /// Print a shell completion script and then exit the program
+    Completions {
+        /// The shell to generate completions for
+        #[structopt(possible_values = &structopt::clap::Shell::variants())]
+        shell: structopt::clap::Shell,
+    },
+    /// Update mc-server-wrapper to the latest release and then exit the program
+    SelfUpdate {
+        /// Only check whether a newer release is available
+        #[structopt(long)]
+        check: bool,
+    },
+    /// Import an existing server from a zip archive, writing a config for it
+    /// (to the `--config` path) and then exit the program
+    Import {
+        /// The zip archive of the server
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+        /// The folder to unpack the server into (defaults to a folder named
+        /// after the archive)
+        #[structopt(long, parse(from_os_str))]
+        dir: Option<PathBuf>,
+    },
+    /// Package the server's setup (the config, jar, server.properties,
+    /// whitelist, ops, plugins, and mods) into a zip archive that `import`
+    /// can set up elsewhere and then exit the program
+    Export {
+        /// The zip archive to write
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+        /// Include the worlds
+        #[structopt(long)]
+        worlds: bool,
+    },
+    /// Copy the staging server's settings, plugins, and mods to the server
+    /// and then exit the program
+    Promote {
+        /// Promote even if the staging server didn't finish starting the last
+        /// time it ran
+        #[structopt(long)]
+        force: bool,
+    },
+    /// Copy the server's setup into another folder as a staging server on
+    /// another port and then exit the program
+    Clone {
+        /// The folder to copy the server into
+        #[structopt(parse(from_os_str))]
+        dir: PathBuf,
+        /// The port for the copy (defaults to the server's port plus one)
+        #[structopt(long)]
+        port: Option<u16>,
+        /// Copy the worlds too
+        #[structopt(long)]
+        worlds: bool,
+    },
+    /// Restart servers defined with `[[server]]` (all of them unless some are
+    /// named) through the wrappers running them and then exit the program
+    RestartServers {
+        /// The servers to restart
+        names: Vec<String>,
+        /// How many servers to restart at once
+        #[structopt(long, default_value = "1")]
+        parallel: usize,
+        /// How long (in seconds) to warn players for, instead of each
+        /// server's `restart_warning`
+        #[structopt(long)]
+        warning: Option<u64>,
+        /// How long (in minutes) each server has to finish loading after its
+        /// warning is over
+        #[structopt(long, default_value = "10")]
+        timeout: u64,
+    },
+}
+
+/// Loads the config for `export` and `clone`, with the server picked with
+/// `--server` (if any) in `minecraft`
+///
+/// Secrets aren't resolved, so references to them are copied rather than
+/// their values.
+async fn load_config_for_copy(opt: &Opt) -> Result<Config, anyhow::Error> {
+    if !opt.config.exists() {
+        return Err(anyhow::anyhow!("There's no config at {:?}", opt.config))
+            .context(ExitCode::Config);
+    }
+    let mut config = Config::load(&opt.config).await.context(ExitCode::Config)?;
+    if let Some(name) = &opt.server {
+        config.select_server(name).context(ExitCode::Config)?;
     }
+    Ok(config)
+}
+
+fn main() {
+    // The local offset can only be determined soundly while there's a single
+    // thread, so this is done before the runtime starts
+    let local_offset = UtcOffset::current_local_offset().ok();
+
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start the async runtime");
+    let exit_code = match runtime.block_on(run(local_offset)) {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            // This matches how errors returned from `main` are printed
+            eprintln!("Error: {:?}", e);
+            ExitCode::for_error(&e)
+        }
+    };
+
+    std::process::exit(exit_code as i32);
+}
 
+/// Runs the wrapper, returning the code to exit with
+///
+/// `local_offset` is the local offset from UTC, if it could be determined.
+async fn run(local_offset: Option<UtcOffset>) -> Result<ExitCode, anyhow::Error> {
     log_panics::init();
     CONSOLE_MSG_LOG_TARGET.set("mc").unwrap();
-    ONLINE_PLAYERS.set(Mutex::new(BTreeMap::new())).unwrap();
 
     let opt = Opt::from_args();
+    if opt.check_config {
+        return config_check::run(&opt.config)
+            .map(|_| ExitCode::Clean)
+            .context(ExitCode::Config);
+    }
+    if opt.man {
+        print!("{}", cli::man_page());
+        return Ok(ExitCode::Clean);
+    }
+    match opt.cmd.clone() {
+        Some(Command::Completions { shell }) => {
+            cli::write_completions(shell, &mut std::io::stdout());
+            return Ok(ExitCode::Clean);
+        }
+        Some(Command::SelfUpdate { check }) => {
+            return self_update::run(check).await.map(|_| ExitCode::Clean)
+        }
+        Some(Command::Import { archive, dir }) => {
+            return import::run(archive, dir, &opt.config)
+                .await
+                .map(|_| ExitCode::Clean)
+        }
+        Some(Command::Export { archive, worlds }) => {
+            let config = load_config_for_copy(&opt).await?;
+            return export::export(&config, archive, worlds)
+                .await
+                .map(|_| ExitCode::Clean);
+        }
+        Some(Command::Promote { force }) => {
+            let mut config = load_config_for_copy(&opt).await?;
+            let production_path = config.use_staging().context(ExitCode::Config)?;
+            return staging::promote(&production_path, &config.minecraft.server_path, force)
+                .await
+                .map(|_| ExitCode::Clean);
+        }
+        Some(Command::Clone { dir, port, worlds }) => {
+            let config = load_config_for_copy(&opt).await?;
+            return export::clone(&config, dir, port, worlds)
+                .await
+                .map(|_| ExitCode::Clean);
+        }
+        Some(Command::RestartServers {
+            names,
+            parallel,
+            warning,
+            timeout,
+        }) => {
+            let config = load_config_for_copy(&opt).await?;
+            return fleet_restart::run(
+                &config,
+                &names,
+                parallel,
+                warning,
+                Duration::from_secs(timeout * 60),
+            )
+            .await
+            .map(|_| ExitCode::Clean);
+        }
+        None => {}
+    }
+
     let config_filepath = opt.config.clone();
-    let mut config = Config::load(&config_filepath).await?;
+    let mut config = Config::load(&config_filepath)
+        .await
+        .context(ExitCode::Config)?;
     let mut notify_receiver = config.setup_watcher(config_filepath.clone());
 
     if opt.gen_config {
-        return Ok(());
+        return Ok(ExitCode::Clean);
     }
 
     if opt.version {
         println!("mc-server-wrapper {APPLICATION_VERSION}");
-        return Ok(());
+        return Ok(ExitCode::Clean);
+    }
+
+    let replay = opt.replay.clone().map(|path| ReplayConfig {
+        path,
+        speed: opt.replay_speed,
+    });
+    let no_server = opt.no_server;
+    let force = opt.force;
+    // Kept to apply the same overrides when the config is reloaded
+    let reload_opt = opt.clone();
+    config.merge_in_args(opt).context(ExitCode::Config)?;
+    let fallback_utc_offset = config
+        .fallback_utc_offset()
+        .context(ExitCode::Config)?
+        .unwrap_or(UtcOffset::UTC);
+    local_time::set_local_offset(local_offset.unwrap_or(fallback_utc_offset));
+    i18n::set_language(config.language);
+    if let Some(crash_reporting) = &config.crash_reporting {
+        crash_reports::init(crash_reporting).context(ExitCode::Config)?;
     }
 
-    config.merge_in_args(opt)?;
-    let (log_sender, mut log_receiver) = mpsc::channel(64);
+    if let Some(provision) = config
+        .minecraft
+        .provision
+        .as_ref()
+        .filter(|_| replay.is_none())
+    {
+        if !config.minecraft.server_path.exists() {
+            println!(
+                "Setting up a {} {} server at {:?}...",
+                provision.flavor, provision.version, config.minecraft.server_path
+            );
+        }
+
+        if let Some(jar) = provision::provision(provision, &config.minecraft.server_path)
+            .await
+            .with_context(|| "Failed to set up the Minecraft server")?
+        {
+            println!("Installed {}", jar);
+        }
+    }
+
+    // Done after provisioning so the real server's jar is the one installed
+    if reload_opt.staging {
+        let production_path = config.use_staging().context(ExitCode::Config)?;
+        if replay.is_none() && config.minecraft.attach.is_none() {
+            staging::prepare(
+                &production_path,
+                &config.minecraft.server_path,
+                config.minecraft.staging.as_ref().unwrap(),
+            )
+            .await
+            .with_context(|| "Failed to set up the staging server")?;
+        }
+    }
+
+    // Without a server jar to run (and no way to get one) the wrapper starts
+    // in management mode, where the server is only started with `start`
+    let mut management_mode = no_server
+        || (replay.is_none()
+            && config.minecraft.attach.is_none()
+            && config.minecraft.provision.is_none()
+            && !config.minecraft.server_path.exists());
+    if management_mode {
+        // The wrapper's log file is kept in the server's folder
+        tokio::fs::create_dir_all(provision::server_dir(&config.minecraft.server_path))
+            .await
+            .with_context(|| "Failed to create the server's folder")?;
+    }
+    // Held until the wrapper exits; replays don't touch the server, so they
+    // can run alongside it
+    let _instance_lock = if replay.is_none() {
+        instance_lock::acquire(&config.minecraft.server_path, force)
+            .context(ExitCode::AlreadyRunning)?
+    } else {
+        None
+    };
+
+    let mut watchlist =
+        watchlist::Watchlist::new(config.minecraft.watchlist.as_deref().unwrap_or_default())
+            .context(ExitCode::Config)
+            .with_context(|| "Invalid regular expression in the watchlist")?;
+    let mut bridge_filters = config
+        .discord
+        .as_ref()
+        .map(|discord| discord.bridge_filters())
+        .transpose()
+        .context(ExitCode::Config)
+        .with_context(|| "Invalid regular expression in the Discord bridge filters")?
+        .unwrap_or_else(regex::RegexSet::empty);
+
+    let (log_sender, mut log_receiver) = mpsc::channel(256);
     let stdout = std::io::stdout();
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
-    let mut tui_state = TuiState::new();
+    let mut tui_state = TuiState::new(
+        config.minecraft.geyser.is_some(),
+        config.logging.tui_max_records,
+    );
+    tui_state.maintenance = maintenance::is_enabled(&config.minecraft.server_path);
 
     enable_raw_mode()?;
-    terminal.backend_mut().execute(EnterAlternateScreen)?;
+    terminal
+        .backend_mut()
+        .execute(EnterAlternateScreen)?
+        .execute(EnableBracketedPaste)?;
     defer! {
+        std::io::stdout().execute(DisableBracketedPaste).unwrap();
         std::io::stdout().execute(LeaveAlternateScreen).unwrap();
         disable_raw_mode().unwrap();
     }
@@ -135,61 +500,452 @@ async fn main() -> Result<(), anyhow::Error> {
         config.logging.discord,
     )
     .with_context(|| "Failed to set up logging")?;
+    if local_offset.is_none() {
+        warn!(
+            "Couldn't determine the local time zone, showing times at UTC{} \
+            (set `fallback_utc_offset` in the config to change this)",
+            fallback_utc_offset
+        );
+    }
 
-    let mc_config = McServerConfig::new(
-        config.minecraft.server_path.clone(),
-        config.minecraft.memory,
-        config.minecraft.jvm_flags,
-        false,
-    );
+    let mut mc_config = Some(build_mc_config(&config.minecraft, replay.as_ref()));
+    let mut message_patterns = build_message_patterns(&config.minecraft);
     let (mc_server, mc_cmd_sender, mut mc_event_receiver) = McServerManager::new();
+    panic_hook::install(mc_server.clone(), mc_cmd_sender.clone());
 
-    info!("Starting the Minecraft server");
-    mc_cmd_sender
-        .send(ServerCommand::StartServer {
-            config: Some(mc_config),
-        })
-        .await
-        .unwrap();
-    let mut last_start_time = Instant::now();
+    if let Some(address) = config.health_address {
+        let listener = tokio::net::TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Failed to listen for health checks on {}", address))?;
+        info!("Serving health checks on http://{}", address);
+        tokio::spawn(health::serve(listener, mc_server.clone()));
+    }
 
-    // TODO: start drawing UI before setting up discord
-    let discord = if let Some(discord_config) = config.discord {
-        if discord_config.enable_bridge {
-            setup_discord(
-                discord_config.token,
-                discord_config.channel_id.into(),
-                mc_cmd_sender.clone(),
-                discord_config.update_status,
-            )
+    if let Some(status_page) = &config.status_page {
+        let listener = tokio::net::TcpListener::bind(status_page.address)
             .await
-            .with_context(|| "Failed to connect to Discord")?
-        } else {
-            DiscordBridge::new_noop()
+            .with_context(|| {
+                format!(
+                    "Failed to listen for the status page on {}",
+                    status_page.address
+                )
+            })?;
+        info!("Serving the status page on http://{}", status_page.address);
+        tokio::spawn(status_page::serve(
+            listener,
+            mc_server.clone(),
+            config.minecraft.server_path.clone(),
+            status_page.server_address.clone().or_else(|| {
+                config
+                    .discord
+                    .as_ref()
+                    .and_then(|discord| discord.server_address.clone())
+            }),
+        ));
+    }
+
+    let (chat_injection_sender, mut chat_injection_receiver) = mpsc::channel(16);
+    if let Some(chat_api) = &config.chat_api {
+        let listener = tokio::net::TcpListener::bind(chat_api.address)
+            .await
+            .with_context(|| {
+                format!("Failed to listen for the chat API on {}", chat_api.address)
+            })?;
+        info!(
+            "Serving the chat API on http://{}/api/chat",
+            chat_api.address
+        );
+        tokio::spawn(chat_api::serve(
+            listener,
+            chat_api.token.clone(),
+            chat_injection_sender,
+        ));
+    }
+
+    let (vote_sender, mut vote_receiver) = mpsc::channel(16);
+    if let Some(votifier) = &config.votifier {
+        let listener = tokio::net::TcpListener::bind(votifier.address)
+            .await
+            .with_context(|| format!("Failed to listen for votes on {}", votifier.address))?;
+        info!("Receiving votes on {}", votifier.address);
+        tokio::spawn(votifier::serve(
+            listener,
+            votifier.token.clone(),
+            vote_sender,
+        ));
+    }
+
+    // A server left running by a previous run, which the server isn't started
+    // alongside of until it's been dealt with
+    let mut pending_orphan = None;
+    if management_mode {
+        if !config.minecraft.server_path.exists() {
+            info!("No server jar found at {:?}", config.minecraft.server_path);
+            info!("Run `provision <flavor> <version>` to download one");
         }
+        info!("Running without a Minecraft server; run `start` to start it or `stop` to exit");
+    } else if let Some(orphan) = (replay.is_none() && config.minecraft.attach.is_none())
+        .then(|| orphans::find(&config.minecraft.server_path))
+        .flatten()
+    {
+        warn!(
+            "A Minecraft server from a previous run is still running ({})",
+            orphan
+        );
+        info!(
+            "Type `adopt` to attach to it over RCON, `kill` to stop it and start a new one, \
+            or anything else to leave it running"
+        );
+        tui_state.raise_alert("A server from a previous run is still running".into());
+        pending_orphan = Some(orphan);
     } else {
-        DiscordBridge::new_noop()
-    };
+        if let Some(replay) = &replay {
+            info!("Replaying {:?}", replay.path);
+        } else if config.minecraft.attach.is_some() {
+            info!("Attaching to the Minecraft server");
+        } else {
+            apply_staged_addon_updates(&config.minecraft.server_path);
+            info!("Starting the Minecraft server");
+        }
+        mc_cmd_sender
+            .send(ServerCommand::StartServer {
+                config: mc_config.take(),
+            })
+            .await
+            .unwrap();
+    }
+    let mut last_start_time = Instant::now();
+
+    let mut bridge_whispers = config
+        .discord
+        .as_ref()
+        .map(|discord| discord.bridge_whispers)
+        .unwrap_or(false);
+    // TODO: start drawing UI before setting up discord
+    let (capture_sender, mut capture_receiver) = mpsc::channel(16);
+    let (whitelist_sender, mut whitelist_receiver) = mpsc::channel(16);
+    let mut command_captures = command_capture::CommandCaptures::default();
+    let mut command_limiter =
+        remote_commands::CommandLimiter::new(config.remote_commands_per_second);
+    let mut capture_timer = tokio::time::interval(Duration::from_millis(250));
+    let mut discord = connect_discord(
+        config.discord.as_ref(),
+        mc_cmd_sender.clone(),
+        capture_sender.clone(),
+        whitelist_sender.clone(),
+    )
+    .await
+    .with_context(|| "Failed to connect to Discord")?;
 
     let mut term_events = EventStream::new();
 
+    // An update for the server jar that can be installed with `upgrade`
+    let mut available_update = None;
+    // Set when the server is being stopped in order to upgrade it
+    let mut upgrade_after_stop = false;
+    let (update_sender, mut update_receiver) = mpsc::channel(4);
+    let (upgrade_result_sender, mut upgrade_result_receiver) = mpsc::channel(1);
+    // Set when mc-server-wrapper should update itself once the server has stopped
+    let mut self_update_after_stop = false;
+    let (wrapper_release_sender, mut wrapper_release_receiver) = mpsc::channel(1);
+    let (self_update_result_sender, mut self_update_result_receiver) = mpsc::channel(1);
+    if config.check_for_updates {
+        self_update::spawn_update_checker(
+            Duration::from_secs(24 * 60 * 60),
+            wrapper_release_sender,
+        );
+    }
+    // Server list heartbeats, checked every minute
+    let mut heartbeats = heartbeats::Heartbeats::new(config.heartbeats.len(), Instant::now());
+    let mut heartbeat_timer = tokio::time::interval(Duration::from_secs(60));
+    let heartbeat_client = match provision::http_client() {
+        Ok(client) => Some(client),
+        Err(e) if !config.heartbeats.is_empty() => {
+            warn!("Failed to set up server list heartbeats: {}", e);
+            None
+        }
+        Err(_) => None,
+    };
+    let (live_stream_sender, mut live_stream_receiver) = mpsc::channel(4);
+    if let Some(stream_alerts) = &config.stream_alerts {
+        stream_alerts::spawn_checker(stream_alerts.clone(), live_stream_sender);
+    }
+    // Plugin and mod updates that can be staged with `stage-updates`
+    let mut available_addon_updates = vec![];
+    let (addon_result_sender, mut addon_result_receiver) = mpsc::channel(1);
+    // A change to the active world to make once the server has stopped
+    let mut pending_world_change: Option<WorldChange> = None;
+    let (world_list_sender, mut world_list_receiver) = mpsc::channel(1);
+    // A backup waiting for the user to confirm that it should be restored
+    let mut pending_restore = None;
+    // Set while waiting for the user to agree to the EULA
+    let mut pending_eula = false;
+    // Why the wrapper will exit once the server is stopped for good
+    let mut exit_code = ExitCode::Clean;
+    // Set when the server is being stopped in order to restore a backup
+    let mut restore_after_stop = None;
+    let backup_dir = backups::backup_dir(
+        &config.minecraft.server_path,
+        config.minecraft.backup_dir.as_deref(),
+    );
+    let (restore_sender, mut restore_receiver) = mpsc::channel(1);
+    let mut players = PlayerTracker::new(
+        mc_server.subscribe_status(),
+        sessions::load(&config.minecraft.server_path)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to load saved player sessions: {:#}", e);
+                BTreeMap::new()
+            }),
+    );
+    let mut players_changed = players.subscribe();
+    let mut stats = stats::Stats::load(&config.minecraft.server_path)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to load stats: {:#}", e);
+            stats::Stats::default()
+        });
+    // Chat and sessions change the stats often, so they're saved on a timer
+    // rather than after every change
+    let mut stats_dirty = false;
+    let mut stats_save_timer = tokio::time::interval(Duration::from_secs(60));
+    let mut moderation_history = moderation::ModerationHistory::load(&config.minecraft.server_path)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to load the moderation history: {:#}", e);
+            moderation::ModerationHistory::default()
+        });
+    let (import_sender, mut import_receiver) = mpsc::channel(1);
+    // Periodically run `list` to correct the record of online players in case
+    // any join or leave messages were missed
+    let mut player_list_timer = tokio::time::interval(Duration::from_secs(
+        config.minecraft.player_list_interval.max(1) * 60,
+    ));
+    // The number of `list` responses expected from the wrapper's own requests,
+    // which aren't logged
+    let mut pending_player_lists = 0u32;
+    // The latest disk usage measurement and whether an alert has been sent for it
+    let mut disk_usage = None;
+    let mut low_disk_alerted = false;
+    let (disk_usage_sender, mut disk_usage_receiver) = mpsc::channel(1);
+    if let Some(disk_monitoring) = &config.minecraft.disk_monitoring {
+        disk::spawn_disk_monitor(
+            config.minecraft.server_path.clone(),
+            Duration::from_secs(disk_monitoring.check_interval * 60),
+            disk_usage_sender,
+        );
+    }
+    // Long GC pause reporting, if GC logging is enabled
+    let (gc_pause_sender, mut gc_pause_receiver) = mpsc::channel(16);
+    let mut gc_monitor = config.minecraft.gc_logging.as_ref().map(|gc_logging| {
+        gc_log::spawn_follower(gc_log::path(&config.minecraft.server_path), gc_pause_sender);
+        gc_log::GcMonitor::new(gc_logging.long_pause_ms)
+    });
+    // AFK detection, if it's enabled
+    let mut afk_tracker = config
+        .minecraft
+        .afk
+        .as_ref()
+        .map(|afk| afk::AfkTracker::new(Duration::from_secs(afk.idle_minutes * 60)));
+    let mut afk_timer = tokio::time::interval(Duration::from_secs(60));
+    // The number of position checks sent by the wrapper, which aren't logged
+    let mut pending_position_checks = 0u32;
+    let mut pregen_task: Option<pregen::Pregen> = None;
+    let mut pregen_timer = tokio::time::interval(pregen::STEP_INTERVAL);
+    let mut command_queue = command_queue::CommandQueue::default();
+    let mut startup_progress = startup_progress::StartupProgress::default();
+    let mut command_queue_timer = tokio::time::interval(command_queue::SEND_INTERVAL);
+    let mut locations = locations::Locations::default();
+    // The server's player limit, as of the last `list`
+    let mut max_players = None;
+    // Set when `server.properties` is changed while the server is running
+    let mut properties_changed = false;
+    // The world seed and difficulty, as of the last time the server printed them
+    let mut world_seed = None;
+    let mut difficulty = None;
+    // Restart votes, if they're enabled
+    let mut restart_voting = config
+        .minecraft
+        .restart_vote
+        .clone()
+        .map(restart_vote::RestartVoting::new);
+    let mut restart_vote_timer = tokio::time::interval(Duration::from_secs(5));
+    // Uptime and restarts, with a summary of each day once it's over
+    let mut availability =
+        availability::Availability::new(OffsetDateTime::now_utc(), local_time::local_offset());
+    let mut availability_timer = tokio::time::interval(Duration::from_secs(60));
+    let mut presence = presence::PresenceCycle::new(
+        config
+            .discord
+            .as_ref()
+            .map(|d| d.status_templates.as_slice())
+            .unwrap_or_default(),
+    );
+    let mut presence_timer = status_timer(config.discord.as_ref());
+    // When to edit the pinned status message, if it's enabled
+    let mut status_embed_updates = status_embed::StatusEmbedUpdates::default();
+    let mut status_embed_timer = tokio::time::interval(Duration::from_secs(5));
+    // The voice channel showing the player count, if there is one
+    let mut player_counter = player_counter::PlayerCounter::default();
+    let mut player_counter_timer = tokio::time::interval(Duration::from_secs(15));
+    let mut player_notifications = player_notifications::PlayerNotifications::new(
+        config
+            .minecraft
+            .player_notifications
+            .as_deref()
+            .unwrap_or_default(),
+    );
+    let mut player_count =
+        player_count::PlayerCountTracker::new(config.minecraft.player_count_notifications.as_ref());
+    // Set when the server is being stopped in order to restart it
+    let mut restart_after_stop = false;
+    // A restart requested by `restart-servers` that players are being warned
+    // about, and the ID of one that's waiting for the server to load again
+    let mut fleet_drain: Option<fleet_restart::Drain> = None;
+    let mut fleet_restart_id: Option<String> = None;
+    let mut fleet_restart_timer = tokio::time::interval(fleet_restart::POLL_INTERVAL);
+    // The profiler a report is being waited on from, along with an ID used to
+    // match it with its timeout
+    let mut pending_profile: Option<(u32, profiling::Profiler)> = None;
+    let mut next_profile_id = 0u32;
+    let (profile_timeout_sender, mut profile_timeout_receiver) = mpsc::channel(1);
+    // A server config with changes from the config file that's used the next
+    // time the server starts (or the initial config in management mode)
+    let mut pending_mc_config: Option<McServerConfig> = mc_config;
+    let (provision_result_sender, mut provision_result_receiver) = mpsc::channel(1);
+    let (orphan_result_sender, mut orphan_result_receiver) = mpsc::channel(1);
+    let (dump_result_sender, mut dump_result_receiver) = mpsc::channel(1);
+    // The Geyser process, which runs while the server is up
+    let mut geyser: Option<GeyserProcess> = None;
+    let (geyser_sender, mut geyser_receiver) = mpsc::channel(64);
+    let mut bedrock_players = config
+        .minecraft
+        .geyser
+        .as_ref()
+        .map(|geyser| BedrockPlayers::new(geyser.bedrock_prefix.clone()));
+    if let Some(provision) = &config.minecraft.provision {
+        if let Some(interval) = provision.update_check_interval {
+            provision::spawn_update_checker(
+                provision.clone(),
+                config.minecraft.server_path.clone(),
+                Duration::from_secs(interval * 60 * 60),
+                update_sender,
+            );
+        }
+    }
+
+    let mut tui_redraw = RedrawSchedule::new();
+
     // This loop handles both user input and events from the Minecraft server
     loop {
         // Make sure we are up-to-date on logs before drawing the UI
         while let Some(Some(record)) = log_receiver.recv().now_or_never() {
-            tui_state.logs_state.add_record(record);
+            tui_state.logs_state.add_log_record(&record);
+            tui_redraw.changed();
         }
 
-        {
-            let online_players = ONLINE_PLAYERS.get().unwrap().lock().await;
+        let discord_state = discord.connection_state();
+        if discord_state != tui_state.discord_state {
+            tui_state.discord_state = discord_state;
+            tui_redraw.changed();
+        }
+        if tui_redraw.due(Instant::now()) {
+            let server_status = mc_server.status().await;
+            let online_players = players.players();
             // TODO: figure out what to do if the terminal fails to draw
-            let _ = terminal.draw(|f| tui_state.draw(f, &online_players));
+            let _ = terminal.draw(|f| {
+                tui_state.draw(
+                    f,
+                    &online_players,
+                    &server_status,
+                    disk_usage.as_ref(),
+                    bedrock_players.as_ref(),
+                    &locations,
+                )
+            });
+            tui_redraw.drawn(Instant::now());
         }
+        let next_draw = tui_redraw.next_draw().unwrap_or_else(Instant::now);
 
         tokio::select! {
+            _ = tokio::time::sleep_until(next_draw.into()) => continue,
             e = mc_event_receiver.recv() => if let Some(e) = e {
-                match e {
-                    ServerEvent::ConsoleEvent(console_msg, Some(specific_msg)) => {
+                let instance = e.instance.as_deref();
+                crash_reports::server_event(&e.kind);
+                if let ServerEventKind::ServerStopped(..) = e.kind {
+                    availability.server_stopped(OffsetDateTime::now_utc());
+                    status_embed_updates.changed();
+                    if let Some(text) = startup_progress.stopped() {
+                        discord.clone().update_progress_message(text, true);
+                    }
+                    if std::mem::take(&mut self_update_after_stop) {
+                        info!("Minecraft server stopped, updating mc-server-wrapper");
+                        spawn_self_update(self_update_result_sender.clone());
+                    }
+
+                    let left = players.clear();
+                    let now = OffsetDateTime::now_utc();
+                    for (name, info) in left.iter() {
+                        stats.record_session(name, info.joined_at, now);
+                    }
+                    if !left.is_empty() {
+                        save_stats(&config.minecraft.server_path, &stats).await;
+                    }
+                    save_sessions(&config.minecraft.server_path, &players.players()).await;
+                    pending_player_lists = 0;
+                    pending_position_checks = 0;
+                    if pregen_task.take().is_some() {
+                        info!("Pre-generation stopped because the server stopped");
+                        tui_state.logs_state.clear_progress();
+                    }
+                    properties_changed = false;
+                    if let Some(afk_tracker) = &mut afk_tracker {
+                        afk_tracker.clear();
+                    }
+
+                    if let Some(process) = geyser.take() {
+                        info!("Stopping Geyser");
+                        process.stop().await;
+                    }
+                }
+
+                let line = match &e.kind {
+                    ServerEventKind::ConsoleEvent(console_msg, _) => Some(console_msg.msg.as_str()),
+                    ServerEventKind::StdoutLine(line) | ServerEventKind::StderrLine(line) => Some(line.as_str()),
+                    _ => None,
+                };
+                let filtered = line.is_some_and(|line| bridge_filters.is_match(line));
+                let bridge_chat = config.discord.as_ref().is_none_or(|discord| discord.bridge_direction.to_discord());
+                let bridge_joins = bridge_chat && config.discord.as_ref().is_none_or(|discord| discord.bridge_joins);
+                if let Some(line) = line {
+                    command_captures.line(line);
+                    for rule in watchlist.check(line, Instant::now()) {
+                        let alert = format!("Watchlist match for `{}`: {}", rule.pattern, line);
+                        warn!("{}", alert);
+                        if rule.discord {
+                            discord.clone().send_channel_msg(format!(
+                                "{}Watchlist match for `{}`: {}",
+                                rule.discord_mention.as_deref().map(|m| format!("{} ", m)).unwrap_or_default(),
+                                rule.pattern,
+                                sanitize_for_markdown(line)
+                            ));
+                        }
+                        tui_state.raise_alert(alert);
+                    }
+                }
+
+                if let ServerEventKind::ConsoleEvent(console_msg, _) = &e.kind {
+                    if let Some((_, profiler)) = pending_profile {
+                        if let Some(url) = profiling::find_report_url(&console_msg.msg) {
+                            pending_profile = None;
+                            info!("The {} report is ready: {}", profiler, url);
+                            discord.clone().send_channel_msg(format!("Server {} report: {}", profiler, url));
+                        }
+                    }
+                }
+
+                match e.kind {
+                    ServerEventKind::ConsoleEvent(console_msg, Some(specific_msg)) => {
                         if let ConsoleMsgType::Unknown(ref s) = console_msg.msg_type {
                             warn!("Encountered unknown message type from Minecraft: {}", s);
                         }
@@ -198,73 +954,335 @@ async fn main() -> Result<(), anyhow::Error> {
 
                         match specific_msg {
                             ConsoleMsgSpecific::PlayerLogout { name } => {
-                                discord.clone().send_channel_msg(format!(
-                                    "_**{}** left the game_",
-                                    sanitize_for_markdown(&name)
-                                ));
+                                let msg = i18n::fill(
+                                    &i18n::strings().discord.player_left,
+                                    &[("player", &format_player_for_discord(&name, bedrock_players.as_ref()))],
+                                );
+                                if let Some(msg) = player_notifications
+                                    .announcement(&name, PlayerEvent::Leave)
+                                    .format(&msg)
+                                    .filter(|_| bridge_joins && !filtered)
+                                {
+                                    discord.clone().send_channel_msg(msg);
+                                }
+
+                                if let Some(afk_tracker) = &mut afk_tracker {
+                                    afk_tracker.left(&name);
+                                }
 
-                                let mut online_players = ONLINE_PLAYERS.get().unwrap().lock().await;
-                                online_players.remove(&name);
-                                discord.clone().update_status(format_online_players(
-                                    &online_players,
-                                    OnlinePlayerFormat::BotStatus
+                                if let Some(info) = players.left(&name) {
+                                    stats.record_session(&name, info.joined_at, OffsetDateTime::now_utc());
+                                    stats_dirty = true;
+                                }
+                                save_sessions(&config.minecraft.server_path, &players.players()).await;
+                                discord.clone().update_status(running_status(
+                                    &presence,
+                                    &players.players(),
+                                    max_players,
+                                    &availability,
                                 ));
                             },
-                            ConsoleMsgSpecific::PlayerLogin { name, .. } => {
-                                discord.clone().send_channel_msg(format!(
-                                    "_**{}** joined the game_",
-                                    sanitize_for_markdown(&name)
-                                ));
+                            ConsoleMsgSpecific::PlayerLogin { name, coords, world, .. } => {
+                                let (x, y, z) = coords;
+                                locations.seen(&name, locations::Location {
+                                    position: [x.into(), y.into(), z.into()],
+                                    world,
+                                    at: OffsetDateTime::now_utc(),
+                                });
+
+                                let msg = i18n::fill(
+                                    &i18n::strings().discord.player_joined,
+                                    &[("player", &format_player_for_discord(&name, bedrock_players.as_ref()))],
+                                );
+                                if let Some(msg) = player_notifications
+                                    .announcement(&name, PlayerEvent::Join)
+                                    .format(&msg)
+                                    .filter(|_| bridge_joins && !filtered)
+                                {
+                                    discord.clone().send_channel_msg(msg);
+                                }
+
+                                if let Some(afk_tracker) = &mut afk_tracker {
+                                    afk_tracker.active(&name, Instant::now());
+                                }
 
-                                let mut online_players = ONLINE_PLAYERS.get().unwrap().lock().await;
-                                online_players.insert(name, OnlinePlayerInfo::default());
-                                discord.clone().update_status(format_online_players(
-                                    &online_players,
-                                    OnlinePlayerFormat::BotStatus
+                                players.joined(name);
+                                save_sessions(&config.minecraft.server_path, &players.players()).await;
+                                update_player_count(
+                                    &mut player_count,
+                                    players.len(),
+                                    &mut stats,
+                                    &config.minecraft.server_path,
+                                    &discord,
+                                ).await;
+                                discord.clone().update_status(running_status(
+                                    &presence,
+                                    &players.players(),
+                                    max_players,
+                                    &availability,
                                 ));
                             },
+                            ConsoleMsgSpecific::PlayerAuth { name, uuid } => players.authenticated(name, uuid),
                             ConsoleMsgSpecific::PlayerMsg { name, msg } => {
+                                stats.record_chat(&name, &msg, OffsetDateTime::now_utc());
+                                stats_dirty = true;
+                                if let Some(afk_tracker) = &mut afk_tracker {
+                                    afk_tracker.active(&name, Instant::now());
+                                }
+
+                                if msg.trim() == restart_vote::VOTE_MESSAGE {
+                                    if let Some(restart_voting) = &mut restart_voting {
+                                        let outcome = restart_voting.vote(Some(&name), players.len(), Instant::now());
+                                        restart_after_stop |= handle_restart_vote(outcome, Some(&name), &mc_cmd_sender).await;
+                                    }
+                                }
+
+                                let msg = match &config.discord {
+                                    Some(discord_config) if !discord_config.mentionable_users.is_empty() => {
+                                        util::mention_users_in(
+                                            &msg,
+                                            &discord.mentionable_names(&discord_config.mentionable_users)
+                                        )
+                                    },
+                                    _ => msg,
+                                };
+                                let msg = util::disable_links(&msg, &link_filter(&config));
+                                if bridge_chat && !filtered {
+                                    discord.clone().send_channel_msg(format!(
+                                        "**{}** {}",
+                                        sanitize_for_markdown(name),
+                                        msg
+                                    ));
+                                }
+                            },
+                            ConsoleMsgSpecific::PlayerWhisper { from, to, msg } if bridge_whispers && bridge_chat && !filtered => {
                                 discord.clone().send_channel_msg(format!(
-                                    "**{}** {}",
-                                    sanitize_for_markdown(name),
-                                    msg
+                                    "**{}** → **{}**: {}",
+                                    sanitize_for_markdown(from),
+                                    sanitize_for_markdown(to),
+                                    util::disable_links(&msg, &link_filter(&config))
                                 ));
                             },
                             ConsoleMsgSpecific::SpawnPrepareProgress { progress } => {
                                 tui_state.logs_state.set_progress_percent(progress as u32);
+                                status_embed_updates.changed();
+                                if config.discord.as_ref().is_some_and(|discord| discord.startup_progress) {
+                                    if let Some(text) = startup_progress.progress(progress, Instant::now()) {
+                                        discord.clone().update_progress_message(text, false);
+                                    }
+                                }
                                 should_log = false;
                             },
                             ConsoleMsgSpecific::SpawnPrepareFinish { .. } => {
                                 tui_state.logs_state.set_progress_percent(100);
                             },
-                            ConsoleMsgSpecific::FinishedLoading { .. } => {
-                                let online_players = ONLINE_PLAYERS.get().unwrap().lock().await;
-                                discord.clone().update_status(format_online_players(
-                                    &online_players,
-                                    OnlinePlayerFormat::BotStatus
+                            ConsoleMsgSpecific::Overloaded { behind_ms, .. } => {
+                                if let Some(warning) = gc_monitor.as_mut().and_then(|m| m.overloaded(behind_ms, Instant::now())) {
+                                    warn!("{}", warning);
+                                    tui_state.raise_alert(format!("The server fell behind by {}ms after a long GC pause", behind_ms));
+                                }
+                            },
+                            ConsoleMsgSpecific::FinishedLoading { time_elapsed_s } => {
+                                if let Some(id) = fleet_restart_id.take() {
+                                    info!("Finished restarting the server for `restart-servers`");
+                                    fleet_restart::write_result(&config.minecraft.server_path, id, Ok(())).await;
+                                }
+                                if let Some(regression) = stats.record_startup(time_elapsed_s, OffsetDateTime::now_utc()) {
+                                    warn!("{}", regression);
+                                    tui_state.raise_alert(format!(
+                                        "Slow startup: {:.1}s (usually {:.1}s)",
+                                        regression.seconds, regression.usual
+                                    ));
+                                }
+                                save_stats(&config.minecraft.server_path, &stats).await;
+                                if geyser.is_none() {
+                                    geyser = start_geyser(config.minecraft.geyser.as_ref(), geyser_sender.clone());
+                                }
+                                status_embed_updates.changed();
+                                if let Some(text) = startup_progress.finished() {
+                                    discord.clone().update_progress_message(text, true);
+                                }
+
+                                discord.clone().update_status(running_status(
+                                    &presence,
+                                    &players.players(),
+                                    max_players,
+                                    &availability,
                                 ));
                             },
+                            ConsoleMsgSpecific::ServerVersion { version } => {
+                                tui_state.server_version = Some(version);
+                                status_embed_updates.changed();
+                            },
+                            ConsoleMsgSpecific::WorldSeed { seed } => world_seed = Some(seed),
+                            ConsoleMsgSpecific::Difficulty { difficulty: d } => difficulty = Some(d),
+                            ConsoleMsgSpecific::Moderation { action, name, reason, by } => {
+                                moderation_history.record(
+                                    moderation::ModerationEntry {
+                                        time: OffsetDateTime::now_utc().unix_timestamp(),
+                                        action,
+                                        name,
+                                        reason,
+                                        by,
+                                    },
+                                    Instant::now(),
+                                );
+                                if let Err(e) = moderation_history.store(&config.minecraft.server_path).await {
+                                    warn!("Failed to save the moderation history: {:#}", e);
+                                }
+                            },
+                            ConsoleMsgSpecific::PlayerList { players: listed, max, .. } => {
+                                max_players = Some(max);
+
+                                if pending_player_lists > 0 {
+                                    pending_player_lists -= 1;
+                                    should_log = false;
+                                }
+
+                                if players.reconcile(&listed) {
+                                    save_sessions(&config.minecraft.server_path, &players.players()).await;
+                                    update_player_count(
+                                        &mut player_count,
+                                        players.len(),
+                                        &mut stats,
+                                        &config.minecraft.server_path,
+                                        &discord,
+                                    ).await;
+                                    discord.clone().update_status(running_status(
+                                        &presence,
+                                        &players.players(),
+                                        max_players,
+                                        &availability,
+                                    ));
+                                }
+                            },
                             _ => {}
                         }
 
                         if should_log {
-                            console_msg.log();
+                            console_msg.log_for_instance(instance);
+                        }
+                    },
+                    ServerEventKind::ConsoleEvent(console_msg, None) => {
+                        let mut should_log = true;
+
+                        if let Some(task) = &mut pregen_task {
+                            match task.handle_output(&console_msg.msg) {
+                                Some(pregen::Output::Consumed) => {
+                                    tui_state.logs_state.set_progress_percent(task.percent().min(99));
+                                    should_log = false;
+                                },
+                                Some(pregen::Output::Finished) => {
+                                    info!("Pre-generation finished");
+                                    tui_state.logs_state.clear_progress();
+                                    pregen_task = None;
+                                    should_log = false;
+                                },
+                                None => {},
+                            }
+                        }
+
+                        if let Some((name, position)) = afk::parse_position(&console_msg.msg) {
+                            if let Some(afk_tracker) = &mut afk_tracker {
+                                afk_tracker.position(name, position, Instant::now());
+                            }
+                            let location = locations::Location {
+                                position,
+                                world: None,
+                                at: OffsetDateTime::now_utc(),
+                            };
+                            if locations.take_pending_position(name) {
+                                info!("{} is at {}", name, location);
+                                should_log = false;
+                            } else if pending_position_checks > 0 {
+                                pending_position_checks -= 1;
+                                should_log = false;
+                            }
+                            locations.seen(name, location);
+                        } else if let Some((name, dimension, position)) = locations::parse_death_location(&console_msg.msg) {
+                            let location = locations::Location {
+                                position,
+                                world: Some(dimension.into()),
+                                at: OffsetDateTime::now_utc(),
+                            };
+                            if locations.take_pending_death(name) {
+                                info!("{} last died at {}", name, location);
+                                should_log = false;
+                            }
+                            locations.died(name, location);
+                        }
+
+                        if should_log {
+                            console_msg.log_for_instance(instance);
+                        }
+                    },
+                    ServerEventKind::StdoutLine(line) => {
+                        info!(target: &console_log_target(instance), "{}", line);
+                    },
+                    ServerEventKind::StderrLine(line) => {
+                        warn!(target: &console_log_target(instance), "{}", line);
+                    },
+
+                    ServerEventKind::ServerStopped(_, Some(ShutdownReason::RequestedToStop)) if upgrade_after_stop => {
+                        upgrade_after_stop = false;
+                        apply_staged_addon_updates(&config.minecraft.server_path);
+
+                        if let Some(jar) = available_update.take() {
+                            info!("Minecraft server stopped, upgrading to {}", jar);
+                            spawn_upgrade(jar, config.minecraft.server_path.clone(), upgrade_result_sender.clone());
                         }
                     },
-                    ServerEvent::ConsoleEvent(console_msg, None) => {
-                        console_msg.log();
+                    ServerEventKind::ServerStopped(_, Some(ShutdownReason::RequestedToStop)) if restore_after_stop.is_some() => {
+                        if let Some(backup) = restore_after_stop.take() {
+                            info!("Minecraft server stopped, restoring {}", backup);
+                            spawn_restore_task(
+                                RestoreTask::Restore { backup, restart: true },
+                                config.minecraft.server_path.clone(),
+                                backup_dir.clone(),
+                                restore_sender.clone(),
+                            );
+                        }
                     },
-                    ServerEvent::StdoutLine(line) => {
-                        info!(target: CONSOLE_MSG_LOG_TARGET.get().unwrap(), "{}", line);
+                    ServerEventKind::ServerStopped(_, Some(ShutdownReason::RequestedToStop)) if restart_after_stop => {
+                        restart_after_stop = false;
+                        apply_staged_addon_updates(&config.minecraft.server_path);
+
+                        info!("Starting the Minecraft server");
+                        mc_cmd_sender.send(ServerCommand::StartServer { config: pending_mc_config.take() }).await.unwrap();
+                        last_start_time = Instant::now();
                     },
-                    ServerEvent::StderrLine(line) => {
-                        warn!(target: CONSOLE_MSG_LOG_TARGET.get().unwrap(), "{}", line);
+                    ServerEventKind::ServerStopped(_, Some(ShutdownReason::RequestedToStop)) if pending_world_change.is_some() => {
+                        apply_staged_addon_updates(&config.minecraft.server_path);
+
+                        if let Some(change) = pending_world_change.take() {
+                            change.apply(&config.minecraft.server_path).await;
+                        }
+
+                        info!("Starting the Minecraft server");
+                        mc_cmd_sender.send(ServerCommand::StartServer { config: pending_mc_config.take() }).await.unwrap();
+                        last_start_time = Instant::now();
                     },
+                    ServerEventKind::ServerStopped(process_result, reason) => {
+                        apply_staged_addon_updates(&config.minecraft.server_path);
+                        if let Some(id) = fleet_restart_id.take() {
+                            fleet_restart::write_result(
+                                &config.minecraft.server_path,
+                                id,
+                                Err("the server stopped before it finished loading".into()),
+                            ).await;
+                        }
 
-                    ServerEvent::ServerStopped(process_result, reason) => {
                         if let Some(ShutdownReason::EulaNotAccepted) = reason {
-                            info!("Agreeing to EULA!");
-                            mc_cmd_sender.send(ServerCommand::AgreeToEula).await.unwrap();
+                            if config.minecraft.auto_agree_eula {
+                                info!("Agreeing to EULA!");
+                                mc_cmd_sender.send(ServerCommand::AgreeToEula).await.unwrap();
+                            } else {
+                                info!("The server requires agreeing to the Minecraft EULA (https://aka.ms/MinecraftEULA)");
+                                info!("Type `agree` to agree to it and start the server, or anything else to cancel");
+                                tui_state.raise_alert("Type `agree` in the console to agree to the Minecraft EULA".into());
+                                discord.clone().update_status(i18n::strings().discord.status_offline.as_str());
+                                pending_eula = true;
+                            }
                         } else {
                             let mut sent_restart_command = false;
 
@@ -281,23 +1299,37 @@ async fn main() -> Result<(), anyhow::Error> {
                                         error!("Minecraft server process exited non-successfully with error {}", e);
                                     }
                                 }
+                            } else if let Some(ShutdownReason::HostShutdown) = reason {
+                                // Most likely the machine is shutting down, so this isn't a crash
+                                warn!("Minecraft server was told to stop by the system, not restarting server");
+                            } else if let Some(ShutdownReason::PortInUse) = reason {
+                                error!("Minecraft server couldn't bind its port, not restarting server");
                             } else {
                                 // We did not ask the server to stop
                                 match process_result {
                                     Ok(exit_status) => {
-                                        warn!("Minecraft server process exited with code {}", &exit_status);
-                                        discord.clone().send_channel_msg("The Minecraft server crashed!");
+                                        match &reason {
+                                            Some(reason) if !matches!(reason, ShutdownReason::Crashed { .. }) => warn!("Minecraft server process exited with code {} because {}", &exit_status, reason),
+                                            _ => warn!("Minecraft server process exited with code {}", &exit_status),
+                                        }
+                                        if let Some(ShutdownReason::OutOfMemory) = reason {
+                                            warn!("Consider giving it more memory with `memory` in the config ({} MB currently)", config.minecraft.memory);
+                                        }
+                                        stats.record_crash(OffsetDateTime::now_utc());
+                                        save_stats(&config.minecraft.server_path, &stats).await;
+                                        discord.clone().send_channel_msg(i18n::strings().discord.server_crashed.as_str());
 
                                         // Attempt to restart the server if it's been up for at least 5 minutes
                                         // TODO: make this configurable
                                         // TODO: maybe parse logs for things that definitely indicate a crash?
                                         if last_start_time.elapsed().as_secs() > 300 {
-                                            mc_cmd_sender.send(ServerCommand::StartServer { config: None }).await.unwrap();
+                                            mc_cmd_sender.send(ServerCommand::StartServer { config: pending_mc_config.take() }).await.unwrap();
 
                                             last_start_time = Instant::now();
                                             sent_restart_command = true;
                                         } else {
                                             error!("Fatal error believed to have been encountered, not restarting server");
+                                            exit_code = ExitCode::CrashLoop;
                                         }
                                     },
                                     Err(e) => {
@@ -307,31 +1339,63 @@ async fn main() -> Result<(), anyhow::Error> {
                             }
 
                             if sent_restart_command {
-                                discord.clone().send_channel_msg("Restarting the Minecraft server...");
-                                discord.clone().update_status("server is restarting");
+                                discord.clone().send_channel_msg(i18n::strings().discord.server_restarting.as_str());
+                                discord.clone().update_status(i18n::strings().discord.status_restarting.as_str());
                                 info!("Restarting server...");
                             } else {
-                                discord.clone().update_status("server is offline");
+                                discord.clone().update_status(i18n::strings().discord.status_offline.as_str());
                                 info!("Start the Minecraft server back up with `start` or shutdown the wrapper with `stop`");
                             }
                         }
                     },
 
-                    ServerEvent::AgreeToEulaResult(res) => {
+                    ServerEventKind::AgreeToEulaResult(res) => {
                         if let Err(e) = res {
                             error!("Failed to agree to EULA: {:?}", e);
+                            exit_code = ExitCode::Error;
                             mc_cmd_sender.send(ServerCommand::StopServer { forever: true }).await.unwrap();
                         } else {
-                            mc_cmd_sender.send(ServerCommand::StartServer { config: None }).await.unwrap();
+                            mc_cmd_sender.send(ServerCommand::StartServer { config: pending_mc_config.take() }).await.unwrap();
                             last_start_time = Instant::now();
                         }
                     }
-                    ServerEvent::StartServerResult(res) => {
+                    ServerEventKind::Attached => {
+                        info!("Attached to the Minecraft server");
+                        if geyser.is_none() {
+                            geyser = start_geyser(config.minecraft.geyser.as_ref(), geyser_sender.clone());
+                        }
+
+                        // Find out who's already online
+                        pending_player_lists += 1;
+                        mc_cmd_sender.send(ServerCommand::WriteCommandToStdin("list uuids".into())).await.unwrap();
+                    }
+                    ServerEventKind::StartServerResult(res) => {
+                        tui_state.pending_restart = pending_mc_config.is_some();
+                        status_embed_updates.changed();
                         // TODO: it's impossible to read start failures right now because the TUI
                         // leaves the alternate screen right away and the logs are gone
-                        if let Err(e) = res {
-                            error!("Failed to start the Minecraft server: {}", e);
-                            mc_cmd_sender.send(ServerCommand::StopServer { forever: true }).await.unwrap();
+                        match res {
+                            // The server recovered from whatever happened before this start
+                            Ok(()) => {
+                                exit_code = ExitCode::Clean;
+                                management_mode = false;
+                                availability.server_started(OffsetDateTime::now_utc());
+                            },
+                            Err(e) if management_mode => {
+                                error!("Failed to start the Minecraft server: {}", e);
+                            },
+                            Err(e) => {
+                                error!("Failed to start the Minecraft server: {}", e);
+                                if let Some(id) = fleet_restart_id.take() {
+                                    fleet_restart::write_result(
+                                        &config.minecraft.server_path,
+                                        id,
+                                        Err(format!("failed to start the server: {}", e)),
+                                    ).await;
+                                }
+                                exit_code = ExitCode::Error;
+                                mc_cmd_sender.send(ServerCommand::StopServer { forever: true }).await.unwrap();
+                            }
                         }
                     }
                 }
@@ -339,41 +1403,507 @@ async fn main() -> Result<(), anyhow::Error> {
                 break;
             },
             Some(record) = log_receiver.recv() => {
-                tui_state.logs_state.add_record(record);
+                tui_state.logs_state.add_log_record(&record);
             },
             maybe_term_event = term_events.next() => {
                 match maybe_term_event {
                     Some(Ok(event)) => {
                         if let Event::Key(key_event) = event {
-                            #[allow(clippy::single_match)]
                             match key_event.code {
-                                KeyCode::Enter => if tui_state.tab_state.current_idx() == 0 {
-                                    if mc_server.running().await {
-                                        mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(tui_state.logs_state.input_state.value().to_string())).await.unwrap();
-                                    } else {
-                                        // TODO: create a command parser for user input?
-                                        // https://docs.rs/clap/2.33.1/clap/struct.App.html#method.get_matches_from_safe
-                                        match tui_state.logs_state.input_state.value() {
-                                            "start" => {
-                                                info!("Starting the Minecraft server");
-                                                mc_cmd_sender.send(ServerCommand::StartServer { config: None }).await.unwrap();
-                                                last_start_time = Instant::now();
+                                KeyCode::Enter if tui_state.tab_state.current_idx() == 0 => {
+                                    // These are not Minecraft commands, so they're handled whether
+                                    // or not the server is running
+                                    let input = tui_state.logs_state.input_state.value();
+                                    if std::mem::take(&mut pending_eula) {
+                                        if input == "agree" {
+                                            info!("Agreeing to EULA!");
+                                            mc_cmd_sender.send(ServerCommand::AgreeToEula).await.unwrap();
+                                        } else {
+                                            info!("EULA not agreed to; the server won't start until it is (run `start` to be asked again)");
+                                            exit_code = ExitCode::EulaDeclined;
+                                        }
+                                    } else if let Some(orphan) = pending_orphan.take() {
+                                        match input {
+                                            "adopt" => match orphans::attach_config(&config.minecraft.server_path).await {
+                                                Some(attach) => {
+                                                    info!("Attaching to the server from the previous run (PID {})", orphan.pid);
+                                                    // `pending_mc_config` is left alone so the server is started
+                                                    // normally the next time
+                                                    let attach_config = build_mc_config(&config.minecraft, None).with_attach(attach);
+                                                    mc_cmd_sender.send(ServerCommand::StartServer { config: Some(attach_config) }).await.unwrap();
+                                                },
+                                                None => {
+                                                    info!(
+                                                        "The server can't be attached to without RCON (set `enable-rcon=true` and \
+                                                        `rcon.password` in its server.properties); type `adopt` to try again or \
+                                                        `kill` to stop it"
+                                                    );
+                                                    pending_orphan = Some(orphan);
+                                                },
                                             },
-                                            "stop" => {
-                                                mc_cmd_sender.send(ServerCommand::StopServer { forever: true }).await.unwrap();
+                                            "kill" => {
+                                                info!("Stopping the server from the previous run (PID {})", orphan.pid);
+                                                let server_path = config.minecraft.server_path.clone();
+                                                let orphan_result_sender = orphan_result_sender.clone();
+                                                tokio::spawn(async move {
+                                                    let _ = orphan_result_sender.send(orphans::terminate(&orphan, &server_path).await).await;
+                                                });
                                             },
-                                            _ => {}
+                                            _ => info!("Leaving the server from the previous run running; run `start` once it has stopped"),
                                         }
-                                    }
+                                    } else if let Some(backup) = pending_restore.take() {
+                                        if input != "confirm" {
+                                            info!("Restore cancelled");
+                                        } else if mc_server.running().await {
+                                            info!("Stopping the Minecraft server to restore {}", backup);
+                                            restore_after_stop = Some(backup);
+                                            mc_cmd_sender.send(ServerCommand::StopServer { forever: false }).await.unwrap();
+                                        } else {
+                                            info!("Restoring {}", backup);
+                                            spawn_restore_task(
+                                                RestoreTask::Restore { backup, restart: false },
+                                                config.minecraft.server_path.clone(),
+                                                backup_dir.clone(),
+                                                restore_sender.clone(),
+                                            );
+                                        }
+                                    } else if tui_state.logs_state.input_state.is_multiline() {
+                                        if mc_server.running().await {
+                                            let queued = command_queue.push_lines(input);
+                                            info!("Queued {} commands", queued);
+                                            tui_state.logs_state.set_queued_commands(command_queue.len());
+                                        } else {
+                                            info!("The Minecraft server isn't running, so the pasted commands weren't queued");
+                                        }
+                                    } else if input == "queue clear" {
+                                        info!("Cleared {} queued commands", command_queue.clear());
+                                        tui_state.logs_state.set_queued_commands(0);
+                                    } else if let Some(name) = input.strip_prefix("whereis ") {
+                                        let name = name.trim();
+                                        let online = players.players().keys().any(|n| n.eq_ignore_ascii_case(name));
+                                        if online && mc_server.running().await {
+                                            for command in locations.whereis(name) {
+                                                mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                                            }
+                                        } else {
+                                            match locations.last_known(name) {
+                                                Some(location) => info!(
+                                                    "{} is offline, last seen at {} ({} ago)",
+                                                    name,
+                                                    location,
+                                                    ui::make_session_time_string(OffsetDateTime::now_utc() - location.at)
+                                                ),
+                                                None => info!("{} is offline and hasn't been seen", name),
+                                            }
+                                            if let Some(death) = locations.last_death(name) {
+                                                info!("{} last died at {}", name, death);
+                                            }
+                                        }
+                                    } else if let Some(name) = input.strip_prefix("history ") {
+                                        let name = name.trim();
+                                        let mut entries = moderation_history.player(name).peekable();
+                                        if entries.peek().is_none() {
+                                            info!("{} has no moderation history", name);
+                                        }
+                                        for entry in entries {
+                                            info!("{}", entry);
+                                        }
+                                    } else if input == "worldstats" || input.starts_with("worldstats ") {
+                                        let name = input.strip_prefix("worldstats").unwrap().trim().to_string();
+                                        let server_path = config.minecraft.server_path.clone();
+                                        tokio::spawn(async move {
+                                            match stats::world::read(&server_path).await {
+                                                Ok(players) if name.is_empty() => {
+                                                    if players.is_empty() {
+                                                        info!("The world has no player stats yet");
+                                                    }
+                                                    for player in players {
+                                                        info!("{}", player);
+                                                    }
+                                                },
+                                                Ok(players) => match players.iter().find(|p| p.name.eq_ignore_ascii_case(&name)) {
+                                                    Some(player) => info!("{}", player),
+                                                    None => info!("The world has no stats for {}", name),
+                                                },
+                                                Err(e) => error!("Failed to read the world's player stats: {:#}", e),
+                                            }
+                                        });
+                                    } else if input == "pregen cancel" {
+                                        match pregen_task.take() {
+                                            Some(mut task) => {
+                                                for command in task.cancel() {
+                                                    mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                                                }
+                                                tui_state.logs_state.clear_progress();
+                                                info!("Pre-generation cancelled");
+                                            },
+                                            None => info!("Pre-generation isn't running"),
+                                        }
+                                    } else if let Some(args) = input.strip_prefix("pregen ") {
+                                        match pregen::parse_args(args) {
+                                            None => info!("Usage: `pregen <radius> [x z]` or `pregen cancel`"),
+                                            Some(_) if pregen_task.is_some() => info!("Pre-generation is already running"),
+                                            Some(_) if !mc_server.running().await => info!("The Minecraft server must be running to pre-generate chunks"),
+                                            Some((radius, center)) => {
+                                                let task = pregen::Pregen::new(
+                                                    &provision::server_dir(&config.minecraft.server_path),
+                                                    radius,
+                                                    center,
+                                                );
+                                                info!(
+                                                    "Pre-generating chunks within {} blocks of {}, {}{}",
+                                                    radius,
+                                                    center.0,
+                                                    center.1,
+                                                    if task.method() == pregen::Method::Chunky { " with Chunky" } else { "" }
+                                                );
+                                                for command in task.start() {
+                                                    mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                                                }
+                                                tui_state.logs_state.set_progress_percent(0);
+                                                pregen_timer.reset();
+                                                pregen_task = Some(task);
+                                            },
+                                        }
+                                    } else if input == "backups" {
+                                        match backups::list(&backup_dir) {
+                                            Ok(backups) if backups.is_empty() => info!("No backups found in {:?}", backup_dir),
+                                            Ok(backups) => {
+                                                for backup in backups {
+                                                    info!("{}", backup);
+                                                }
+                                            },
+                                            Err(e) => error!("Failed to list backups: {:#}", e),
+                                        }
+                                    } else if let Some(args) = input.strip_prefix("restore ") {
+                                        let args: Vec<_> = args.split_whitespace().collect();
+                                        match args.as_slice() {
+                                            [id] | [id, "--dry-run"] => spawn_restore_task(
+                                                RestoreTask::Verify { id: id.to_string(), dry_run: args.len() == 2 },
+                                                config.minecraft.server_path.clone(),
+                                                backup_dir.clone(),
+                                                restore_sender.clone(),
+                                            ),
+                                            _ => info!("Usage: `restore <backup-id> [--dry-run]`"),
+                                        }
+                                    } else if input == "import-logs" {
+                                        info!("Importing stats from old server logs");
+                                        let logs_dir = provision::server_dir(&config.minecraft.server_path).join("logs");
+                                        let already_imported = stats.imported_logs.clone();
+                                        let offset = local_time::local_offset();
+                                        let sender = import_sender.clone();
+                                        let message_patterns = message_patterns.clone();
+                                        tokio::spawn(async move {
+                                            let result = tokio::task::spawn_blocking(move || {
+                                                stats::import::import_logs(&logs_dir, &already_imported, offset, &message_patterns)
+                                            })
+                                            .await
+                                            .unwrap_or_else(|e| Err(e.into()));
+                                            let _ = sender.send(result).await;
+                                        });
+                                    } else if input == "profile" || input.starts_with("profile ") {
+                                        let duration = match input.strip_prefix("profile").unwrap().trim() {
+                                            "" => Some(DEFAULT_PROFILE_DURATION),
+                                            secs => secs.parse().ok().filter(|&secs| secs > 0).map(Duration::from_secs),
+                                        };
 
-                                    tui_state.logs_state.input_state.clear();
-                                },
-                                _ => {}
-                            }
-                        }
+                                        match duration {
+                                            _ if !mc_server.running().await => info!("The Minecraft server isn't running"),
+                                            _ if pending_profile.is_some() => info!("Already waiting for a report"),
+                                            Some(duration) => {
+                                                let profiler = profiling::Profiler::detect(
+                                                    &provision::server_dir(&config.minecraft.server_path)
+                                                );
+                                                info!("Collecting a {} report, this can take a while", profiler);
+                                                mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(profiler.command(duration))).await.unwrap();
 
-                        tui_state.handle_input(event);
-                    },
+                                                let id = next_profile_id;
+                                                next_profile_id = next_profile_id.wrapping_add(1);
+                                                pending_profile = Some((id, profiler));
+                                                let sender = profile_timeout_sender.clone();
+                                                let timeout = profiler.timeout(duration);
+                                                tokio::spawn(async move {
+                                                    tokio::time::sleep(timeout).await;
+                                                    let _ = sender.send(id).await;
+                                                });
+                                            },
+                                            None => info!("Usage: `profile [seconds]`"),
+                                        }
+                                    } else if input == "maintenance on" {
+                                        let settings = config.minecraft.maintenance.clone().unwrap_or_default();
+                                        match maintenance::enable(&config.minecraft.server_path, &settings.admins, settings.motd.as_deref()).await {
+                                            Ok(allowed) => {
+                                                info!("Maintenance mode is on, allowed players: {}", allowed.join(", "));
+                                                tui_state.maintenance = true;
+                                                discord.clone().send_channel_msg(i18n::strings().discord.maintenance_on.as_str());
+
+                                                if mc_server.running().await {
+                                                    let mut commands = vec!["whitelist reload".to_string(), "whitelist on".to_string()];
+                                                    commands.extend(settings.admins.iter().map(|admin| format!("whitelist add {}", admin)));
+
+                                                    commands.extend(
+                                                        players
+                                                            .players()
+                                                            .keys()
+                                                            .filter(|name| {
+                                                                !allowed.iter().chain(&settings.admins).any(|a| a.eq_ignore_ascii_case(name))
+                                                            })
+                                                            .map(|name| format!("kick {} {}", name, settings.kick_message)),
+                                                    );
+
+                                                    for command in commands {
+                                                        moderation_history.running(&command, "mc-server-wrapper (maintenance)", Instant::now());
+                                                        mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                                                    }
+                                                }
+                                                if settings.motd.is_some() {
+                                                    info!("The maintenance MOTD will be shown after the server restarts");
+                                                }
+                                            },
+                                            Err(e) => error!("Failed to turn on maintenance mode: {:#}", e),
+                                        }
+                                    } else if input == "maintenance off" {
+                                        match maintenance::disable(&config.minecraft.server_path).await {
+                                            Ok(state) => {
+                                                info!("Maintenance mode is off");
+                                                tui_state.maintenance = false;
+                                                discord.clone().send_channel_msg(i18n::strings().discord.maintenance_off.as_str());
+
+                                                if mc_server.running().await {
+                                                    mc_cmd_sender.send(ServerCommand::WriteCommandToStdin("whitelist reload".into())).await.unwrap();
+                                                    if !state.whitelist_was_on() {
+                                                        mc_cmd_sender.send(ServerCommand::WriteCommandToStdin("whitelist off".into())).await.unwrap();
+                                                    }
+                                                }
+                                                if state.motd.is_some() {
+                                                    info!("The previous MOTD will be shown after the server restarts");
+                                                }
+                                            },
+                                            Err(e) => error!("Failed to turn off maintenance mode: {:#}", e),
+                                        }
+                                    } else if input == "restartvote" {
+                                        match &mut restart_voting {
+                                            _ if !mc_server.running().await => info!("The Minecraft server isn't running"),
+                                            Some(restart_voting) => {
+                                                let outcome = restart_voting.vote(None, players.len(), Instant::now());
+                                                restart_after_stop |= handle_restart_vote(outcome, None, &mc_cmd_sender).await;
+                                            },
+                                            None => info!("Restart votes are not enabled (see the `minecraft.restart_vote` config section)"),
+                                        }
+                                    } else if input == "afk" {
+                                        match &afk_tracker {
+                                            Some(afk_tracker) => {
+                                                let idle = afk_tracker.idle_players(Instant::now());
+                                                if idle.is_empty() {
+                                                    info!("Nobody is AFK");
+                                                }
+                                                for (name, inactive) in idle {
+                                                    info!("{} has been AFK for {} minutes", name, inactive.as_secs() / 60);
+                                                }
+                                            },
+                                            None => info!("AFK detection is not enabled (see the `minecraft.afk` config section)"),
+                                        }
+                                    } else if input == "status" {
+                                        info!("Status: {}", ui::format_server_status(&mc_server.status().await));
+                                        if let Some(version) = &tui_state.server_version {
+                                            info!("Version: {}", version);
+                                        }
+                                        match world_seed {
+                                            Some(seed) => info!("Seed: {}", seed),
+                                            None => info!("Seed: unknown (run `seed` to find it)"),
+                                        }
+                                        match properties::ServerProperties::load(&config.minecraft.server_path).await {
+                                            Ok(properties) => {
+                                                info!("MOTD: {}", properties.get("motd").unwrap_or_else(|| "A Minecraft Server".into()));
+                                                info!("Max players: {}", properties.get("max-players").unwrap_or_else(|| "20".into()));
+                                                let difficulty = difficulty.clone()
+                                                    .or_else(|| properties.get("difficulty"))
+                                                    .unwrap_or_else(|| "easy".into());
+                                                info!("Difficulty: {}", difficulty);
+                                            },
+                                            Err(e) => error!("Failed to read server.properties: {:#}", e),
+                                        }
+                                        if properties_changed {
+                                            info!("server.properties has changed; restart the server to apply the changes");
+                                        }
+                                        for line in availability.describe(OffsetDateTime::now_utc()) {
+                                            info!("{}", line);
+                                        }
+                                        if let Some(startup_times) = stats.describe_startup_times() {
+                                            info!("{}", startup_times);
+                                        }
+                                        if let Some(gc_monitor) = &mut gc_monitor {
+                                            info!("{}", gc_monitor.summary(Instant::now()));
+                                        }
+                                    } else if let Some(motd) = input.strip_prefix("motd set ") {
+                                        let changed = set_property(&config.minecraft.server_path, "motd", motd, mc_server.running().await).await;
+                                        properties_changed |= changed;
+                                    } else if let Some(source) = input.strip_prefix("icon set ") {
+                                        match server_icon::set(&config.minecraft.server_path, Path::new(source.trim())).await {
+                                            Ok((width, height)) => {
+                                                if (width, height) == (server_icon::ICON_SIZE, server_icon::ICON_SIZE) {
+                                                    info!("Set the server icon");
+                                                } else {
+                                                    info!("Set the server icon (scaled from {}x{} to 64x64)", width, height);
+                                                }
+                                                if mc_server.running().await {
+                                                    info!("Restart the server to show the new icon");
+                                                }
+                                            },
+                                            Err(e) => error!("Failed to set the server icon: {:#}", e),
+                                        }
+                                    } else if let Some(max) = input.strip_prefix("maxplayers ") {
+                                        match max.trim().parse::<u32>() {
+                                            Ok(max) if max > 0 => {
+                                                let changed = set_property(&config.minecraft.server_path, "max-players", &max.to_string(), mc_server.running().await).await;
+                                                properties_changed |= changed;
+                                            },
+                                            _ => info!("Usage: `maxplayers <n>`"),
+                                        }
+                                    } else if input == "check-updates" {
+                                        info!("Checking for plugin and mod updates");
+                                        spawn_addon_task(
+                                            AddonTask::Check(config.minecraft.provision.clone()),
+                                            config.minecraft.server_path.clone(),
+                                            addon_result_sender.clone(),
+                                        );
+                                    } else if input == "stage-updates" {
+                                        if available_addon_updates.is_empty() {
+                                            info!("No plugin or mod updates are available (run `check-updates` to look for some)");
+                                        } else {
+                                            info!("Downloading {} plugin and mod updates", available_addon_updates.len());
+                                            spawn_addon_task(
+                                                AddonTask::Stage(std::mem::take(&mut available_addon_updates)),
+                                                config.minecraft.server_path.clone(),
+                                                addon_result_sender.clone(),
+                                            );
+                                        }
+                                    } else if input == "worlds" {
+                                        let server_path = config.minecraft.server_path.clone();
+                                        let sender = world_list_sender.clone();
+                                        tokio::spawn(async move {
+                                            let _ = sender.send(worlds::list(&server_path).await).await;
+                                        });
+                                    } else if input == "world check" || input == "world check --restore" {
+                                        if mc_server.running().await {
+                                            info!("Stop the Minecraft server before checking the world");
+                                        } else {
+                                            info!("Checking the world's region files");
+                                            let task = check_world(
+                                                config.minecraft.server_path.clone(),
+                                                backup_dir.clone(),
+                                                input.ends_with("--restore"),
+                                            );
+                                            tokio::spawn(async move {
+                                                if let Err(e) = task.await {
+                                                    error!("Failed to check the world: {:#}", e);
+                                                }
+                                            });
+                                        }
+                                    } else if let Some(args) = input.strip_prefix("world ") {
+                                        match WorldChange::parse(args) {
+                                            Some(change) if mc_server.running().await => {
+                                                info!("Stopping the Minecraft server to change worlds");
+                                                pending_world_change = Some(change);
+                                                mc_cmd_sender.send(ServerCommand::StopServer { forever: false }).await.unwrap();
+                                            },
+                                            Some(change) => change.apply(&config.minecraft.server_path).await,
+                                            None => info!("Usage: `world switch <name>` or `world create <name> [seed]`"),
+                                        }
+                                    } else if input == "self-update" {
+                                        info!("Updating mc-server-wrapper");
+                                        spawn_self_update(self_update_result_sender.clone());
+                                    } else if input == "self-update later" {
+                                        info!("mc-server-wrapper will be updated the next time the Minecraft server stops");
+                                        self_update_after_stop = true;
+                                    } else if let Some(kind) = input.strip_prefix("dump ") {
+                                        // The server can be dumped while it's still starting, like when
+                                        // it seems to be stuck loading
+                                        match (kind.trim().parse::<diagnostics::DumpKind>(), mc_server.process_info().await) {
+                                            (Err(usage), _) => info!("{}", usage),
+                                            (Ok(kind), Some(ProcessInfo { pid, .. })) => {
+                                                info!("Taking a {} dump of the Minecraft server", kind);
+                                                let path = diagnostics::dump_path(&config.minecraft.server_path, kind, OffsetDateTime::now_utc());
+                                                let dump_result_sender = dump_result_sender.clone();
+                                                tokio::spawn(async move {
+                                                    let _ = dump_result_sender.send(diagnostics::dump(kind, pid, path).await).await;
+                                                });
+                                            },
+                                            (Ok(_), None) if mc_server.running().await => {
+                                                info!("Dumps can only be taken of a server started by mc-server-wrapper");
+                                            },
+                                            (Ok(_), None) => info!("The Minecraft server isn't running"),
+                                        }
+                                    } else if let Some(args) = input.strip_prefix("provision ") {
+                                        match provision::parse_args(args) {
+                                            Ok(_) if config.minecraft.server_path.exists() => {
+                                                info!("There's already a server jar at {:?}", config.minecraft.server_path);
+                                            },
+                                            Ok(provision) => {
+                                                info!(
+                                                    "Setting up a {} {} server at {:?}",
+                                                    provision.flavor, provision.version, config.minecraft.server_path
+                                                );
+                                                spawn_provision(provision, config.minecraft.server_path.clone(), provision_result_sender.clone());
+                                            },
+                                            Err(e) => info!("{}", e),
+                                        }
+                                    } else if input == "upgrade" {
+                                        if available_update.is_none() {
+                                            info!("No server update is available");
+                                        } else if mc_server.running().await {
+                                            info!("Stopping the Minecraft server to upgrade it");
+                                            upgrade_after_stop = true;
+                                            mc_cmd_sender.send(ServerCommand::StopServer { forever: false }).await.unwrap();
+                                        } else if let Some(jar) = available_update.take() {
+                                            info!("Upgrading to {}", jar);
+                                            spawn_upgrade(jar, config.minecraft.server_path.clone(), upgrade_result_sender.clone());
+                                        }
+                                    } else if mc_server.running().await {
+                                        match aliases::expand(&config.aliases, input) {
+                                            Ok(commands) => for command in commands {
+                                                moderation_history.running(&command, "console", Instant::now());
+                                                mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                                            },
+                                            Err(e) => info!("{}", e),
+                                        }
+                                    } else {
+                                        // TODO: create a command parser for user input?
+                                        // https://docs.rs/clap/2.33.1/clap/struct.App.html#method.get_matches_from_safe
+                                        match tui_state.logs_state.input_state.value() {
+                                            "start" => {
+                                                info!("Starting the Minecraft server");
+                                                mc_cmd_sender.send(ServerCommand::StartServer { config: pending_mc_config.take() }).await.unwrap();
+                                                last_start_time = Instant::now();
+                                            },
+                                            "stop" => {
+                                                mc_cmd_sender.send(ServerCommand::StopServer { forever: true }).await.unwrap();
+                                            },
+                                            _ => {}
+                                        }
+                                    }
+
+                                    tui_state.logs_state.input_state.clear();
+                                },
+                                KeyCode::Enter if tui_state.tab_state.current_idx() == 2 => {
+                                    if let Some(geyser_logs_state) = &mut tui_state.geyser_logs_state {
+                                        let input = geyser_logs_state.input_state.value();
+                                        match &mut geyser {
+                                            Some(process) => if let Err(e) = process.write_command(input).await {
+                                                error!("Failed to send command to Geyser: {}", e);
+                                            },
+                                            None => info!("Geyser is not running"),
+                                        }
+
+                                        geyser_logs_state.input_state.clear();
+                                    }
+                                },
+                                _ => {}
+                            }
+                        }
+
+                        tui_state.handle_input(event);
+                    },
                     Some(Err(e)) => {
                         error!("TUI input error: {}", e);
                     },
@@ -383,14 +1913,712 @@ async fn main() -> Result<(), anyhow::Error> {
                     },
                 }
             },
+            _ = pregen_timer.tick(), if pregen_task.is_some() => {
+                if let Some(task) = &mut pregen_task {
+                    if task.finished() {
+                        info!("Pre-generation finished");
+                        tui_state.logs_state.clear_progress();
+                        pregen_task = None;
+                    } else {
+                        let was_paused = task.paused();
+                        let commands = task.step();
+                        if task.paused() && !was_paused {
+                            info!("Pausing pre-generation while the server's TPS is low");
+                        } else if was_paused && !task.paused() {
+                            info!("Continuing pre-generation");
+                        }
+
+                        for command in commands {
+                            mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                        }
+                    }
+                }
+                continue;
+            },
+            _ = command_queue_timer.tick(), if !command_queue.is_empty() => {
+                if mc_server.running().await {
+                    if let Some(command) = command_queue.pop() {
+                        match aliases::expand(&config.aliases, &command) {
+                            Ok(commands) => for command in commands {
+                                mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                            },
+                            Err(e) => info!("Skipping queued command `{}`: {}", command, e),
+                        }
+                    }
+                } else {
+                    info!("The Minecraft server isn't running, dropping {} queued commands", command_queue.clear());
+                }
+                tui_state.logs_state.set_queued_commands(command_queue.len());
+                continue;
+            },
+            Some(pause) = gc_pause_receiver.recv() => {
+                if let Some(warning) = gc_monitor.as_mut().and_then(|m| m.pause(pause, Instant::now())) {
+                    warn!("{}", warning);
+                }
+                continue;
+            },
+            _ = stats_save_timer.tick() => {
+                if std::mem::take(&mut stats_dirty) {
+                    save_stats(&config.minecraft.server_path, &stats).await;
+                }
+                continue;
+            },
+            _ = afk_timer.tick(), if afk_tracker.is_some() => {
+                if let (Some(afk), Some(afk_tracker)) = (&config.minecraft.afk, &mut afk_tracker) {
+                    if mc_server.status().await.players().is_some() {
+                        let now = Instant::now();
+                        for name in afk_tracker.newly_idle(now) {
+                            info!("{} is AFK", name);
+                        }
+
+                        let online_players = players.players();
+                        let mut commands = vec![];
+                        if afk.position_checks {
+                            for name in online_players.keys() {
+                                pending_position_checks += 1;
+                                commands.push(format!("data get entity {} Pos", name));
+                            }
+                        }
+
+                        if let Some(max_players) = max_players.filter(|_| afk.kick_when_full) {
+                            let free_slots = max_players.saturating_sub(online_players.len() as u32);
+                            if free_slots <= afk.free_slots {
+                                let to_kick = (afk.free_slots + 1 - free_slots) as usize;
+                                for (name, _) in afk_tracker.idle_players(now).into_iter().take(to_kick) {
+                                    info!("Kicking {} for being AFK while the server is nearly full", name);
+                                    commands.push(format!("kick {} {}", name, afk.kick_message));
+                                }
+                            }
+                        }
+                        drop(online_players);
+
+                        for command in commands {
+                            moderation_history.running(&command, "mc-server-wrapper (AFK)", Instant::now());
+                            mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                        }
+                    }
+                }
+                continue;
+            },
+            _ = fleet_restart_timer.tick() => {
+                if fleet_drain.is_none() && fleet_restart_id.is_none() {
+                    if let Some(request) = fleet_restart::take_request(&config.minecraft.server_path).await {
+                        if mc_server.running().await {
+                            info!("Restarting the server for `restart-servers`");
+                            fleet_drain = Some(fleet_restart::Drain::new(request, Instant::now()));
+                        } else {
+                            fleet_restart::write_result(
+                                &config.minecraft.server_path,
+                                request.id,
+                                Err("the server isn't running".into()),
+                            ).await;
+                        }
+                    }
+                }
+
+                let step = fleet_drain.as_mut().and_then(|drain| drain.step(Instant::now(), players.players().len()));
+                match step {
+                    Some(fleet_restart::DrainStep::Warn(seconds)) => {
+                        let warning = format!("The server will restart in {} seconds", seconds);
+                        info!("{}", warning);
+                        mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(format!("say {}", warning))).await.unwrap();
+                    },
+                    Some(fleet_restart::DrainStep::Restart) => {
+                        fleet_restart_id = fleet_drain.take().map(|drain| drain.id);
+                        restart_after_stop = true;
+                        mc_cmd_sender.send(ServerCommand::StopServer { forever: false }).await.unwrap();
+                    },
+                    None => {},
+                }
+                continue;
+            },
+            _ = restart_vote_timer.tick(), if restart_voting.as_ref().map(|v| v.in_progress()).unwrap_or(false) => {
+                if let Some(restart_voting) = &mut restart_voting {
+                    if restart_voting.expire(Instant::now()) {
+                        info!("The restart vote failed");
+                        mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(
+                            "say The restart vote failed".into()
+                        )).await.unwrap();
+                    }
+                }
+                continue;
+            },
+            Some(id) = profile_timeout_receiver.recv() => {
+                if let Some((pending_id, profiler)) = pending_profile {
+                    if pending_id == id {
+                        pending_profile = None;
+                        warn!("Gave up waiting for the {} report; check the server's console output", profiler);
+                    }
+                }
+            },
+            Some(geyser_event) = geyser_receiver.recv() => {
+                match geyser_event {
+                    GeyserEvent::Line(line) => {
+                        if let (Some(msg), Some(bedrock_players)) = (GeyserMsg::parse(&line), &mut bedrock_players) {
+                            bedrock_players.apply(msg);
+                        }
+                        if let Some(geyser_logs_state) = &mut tui_state.geyser_logs_state {
+                            geyser_logs_state.add_record(line);
+                        }
+                    },
+                    GeyserEvent::Stopped(result) => {
+                        if let Some(bedrock_players) = &mut bedrock_players {
+                            bedrock_players.clear();
+                        }
+
+                        // The handle is only still around if Geyser wasn't asked to stop
+                        if geyser.take().is_some() {
+                            match result {
+                                Ok(exit_status) => warn!("Geyser exited unexpectedly with code {}", exit_status),
+                                Err(e) => error!("Geyser exited unexpectedly with error: {}", e),
+                            }
+                            info!("Geyser will be started again the next time the Minecraft server starts");
+                        } else {
+                            info!("Geyser stopped");
+                        }
+                    },
+                }
+            },
+            Some(jar) = update_receiver.recv() => {
+                info!("A server update is available: {} (run `upgrade` to install it)", jar);
+                discord.clone().send_channel_msg(format!(
+                    "A Minecraft server update is available: {}",
+                    jar
+                ));
+                available_update = Some(jar);
+            },
+            Some(release) = wrapper_release_receiver.recv() => {
+                info!(
+                    "mc-server-wrapper {} is available (run `self-update` to install it)",
+                    release.version()
+                );
+                tui_state.wrapper_update = Some(release.version().to_string());
+            },
+            Some(vote) = vote_receiver.recv() => {
+                info!("{} voted for the server on {}", vote.username, vote.service);
+                let votifier = config.votifier.as_ref().unwrap();
+                let thanks = i18n::fill(
+                    &i18n::strings().discord.vote,
+                    &[("player", &vote.username), ("service", &vote.service)],
+                );
+                if mc_server.running().await {
+                    if votifier.announce {
+                        mc_cmd_sender
+                            .send(ServerCommand::TellRawAll(votifier::tellraw(&thanks)))
+                            .await
+                            .unwrap();
+                    }
+                    for command in votifier::reward_commands(&votifier.rewards, &vote) {
+                        mc_cmd_sender
+                            .send(ServerCommand::WriteCommandToStdin(command))
+                            .await
+                            .unwrap();
+                    }
+                } else if !votifier.rewards.is_empty() {
+                    warn!(
+                        "Not rewarding {} for voting because the server isn't running",
+                        vote.username
+                    );
+                }
+                if votifier.discord {
+                    discord.clone().send_channel_msg(sanitize_for_markdown(&thanks));
+                }
+            },
+            Some(stream) = live_stream_receiver.recv() => {
+                let announcement = i18n::fill(
+                    &i18n::strings().discord.stream_live,
+                    &[("streamer", &stream.user_name)],
+                );
+                info!("{} {}", announcement, stream.url());
+                if mc_server.running().await {
+                    mc_cmd_sender
+                        .send(ServerCommand::TellRawAll(stream_alerts::tellraw(&announcement, &stream)))
+                        .await
+                        .unwrap();
+                }
+                if config.stream_alerts.as_ref().map(|s| s.discord).unwrap_or(false) {
+                    let title = if stream.game_name.is_empty() {
+                        sanitize_for_markdown(&stream.title)
+                    } else {
+                        format!(
+                            "{} ({})",
+                            sanitize_for_markdown(&stream.title),
+                            sanitize_for_markdown(&stream.game_name)
+                        )
+                    };
+                    discord.clone().send_channel_msg(format!(
+                        "**{}** {}\n{}",
+                        sanitize_for_markdown(&announcement),
+                        title,
+                        stream.url()
+                    ));
+                }
+            },
+            Some(self_update_result) = self_update_result_receiver.recv() => {
+                match self_update_result {
+                    Ok(Some((version, old_path))) => {
+                        info!(
+                            "Updated mc-server-wrapper to {} (the previous version was moved to {:?}), \
+                            restart it to use the new version",
+                            version,
+                            old_path
+                        );
+                        tui_state.wrapper_update = None;
+                    },
+                    Ok(None) => info!("mc-server-wrapper {} is up-to-date", APPLICATION_VERSION),
+                    Err(e) => error!("Failed to update mc-server-wrapper: {:#}", e),
+                }
+            },
+            _ = availability_timer.tick() => {
+                if let Some(mut summary) = availability.roll_day(OffsetDateTime::now_utc()) {
+                    summary.peak_players = Some(player_count.take_day_peak(players.len() as u32));
+                    info!("{}", summary);
+                    if config.discord.as_ref().map(|discord| discord.daily_summary).unwrap_or(false) {
+                        discord.clone().send_channel_msg(summary.to_string());
+                    }
+                }
+                continue;
+            },
+            Some(request) = capture_receiver.recv() => {
+                let command = remote_commands::sanitize(&request.command);
+                if command.is_empty() {
+                    let _ = request.output.send(Err(CommandRejected::Empty));
+                } else if !command_limiter.check(&request.origin, Instant::now()) {
+                    warn!("{} is running commands too quickly, dropping `{}`", request.origin, command);
+                    let _ = request.output.send(Err(CommandRejected::RateLimited));
+                } else if mc_server.running().await {
+                    match aliases::expand(&config.aliases, &command) {
+                        Ok(commands) => {
+                            info!("{} ran `{}`", request.origin, command);
+                            for command in commands {
+                                moderation_history.running(&command, request.origin.to_string(), Instant::now());
+                                mc_cmd_sender.send(ServerCommand::WriteCommandToStdin(command)).await.unwrap();
+                            }
+                            command_captures.start(request.output, Instant::now());
+                        },
+                        Err(e) => {
+                            let _ = request.output.send(Err(CommandRejected::Alias(e)));
+                        },
+                    }
+                }
+                // Otherwise dropping the request tells the sender the server
+                // isn't running
+            },
+            Some(injection) = chat_injection_receiver.recv() => {
+                let running = mc_server.running().await;
+                if running {
+                    let post = &injection.post;
+                    let (plain, parts) = chat_api::tellraw(post);
+                    for part in parts {
+                        mc_cmd_sender.send(ServerCommand::TellRawAll(part)).await.unwrap();
+                    }
+                    // The bridge filters apply as they would to chat from
+                    // the console, which this would look like
+                    let filtered = bridge_filters.is_match(&plain);
+                    // Tellraw commands aren't logged to the console
+                    ConsoleMsg::new(ConsoleMsgType::Info, plain).log();
+
+                    if post.discord && !filtered {
+                        let message = util::disable_links(&post.message, &link_filter(&config));
+                        discord.clone().send_channel_msg(match &post.from {
+                            Some(from) => format!("**{}** {}", sanitize_for_markdown(from), message),
+                            None => message.into_owned(),
+                        });
+                    }
+                }
+                let _ = injection.sent.send(running);
+            },
+            Some(request) = whitelist_receiver.recv() => {
+                let reply = match whitelist::add(
+                    &config.minecraft.server_path,
+                    request.discord_id,
+                    &request.profile,
+                    mc_server.running().await,
+                    &mc_cmd_sender,
+                ).await {
+                    Ok(reply) => {
+                        info!("{} added themselves to the whitelist from Discord", request.profile.name);
+                        reply
+                    },
+                    Err(e) => e.to_string(),
+                };
+                let _ = request.reply.send(reply);
+            },
+            _ = capture_timer.tick(), if !command_captures.is_empty() => {
+                command_captures.finish(Instant::now());
+                continue;
+            },
+            _ = presence_timer.tick() => {
+                if presence.advance() && mc_server.running().await {
+                    discord.clone().update_status(running_status(
+                        &presence,
+                        &players.players(),
+                        max_players,
+                        &availability,
+                    ));
+                }
+                continue;
+            },
+            Ok(()) = players_changed.changed() => {
+                status_embed_updates.changed();
+            },
+            _ = status_embed_timer.tick(), if config.discord.as_ref().is_some_and(|d| d.status_embed) => {
+                if status_embed_updates.due(Instant::now()) {
+                    let server_status = mc_server.status().await;
+                    let embed = status_embed::build(
+                        &status_embed::StatusEmbedInfo {
+                            status: &server_status,
+                            online_players: &players.players(),
+                            max_players,
+                            version: tui_state.server_version.as_deref(),
+                            address: config.discord.as_ref().and_then(|d| d.server_address.as_deref()),
+                            player_uuids: Some(players.uuids()).filter(|_| {
+                                config.discord.as_ref().is_some_and(|d| d.player_avatars)
+                            }),
+                        },
+                        OffsetDateTime::now_utc(),
+                    );
+                    discord.clone().update_status_embed(embed);
+                }
+                continue;
+            },
+            _ = heartbeat_timer.tick(), if heartbeat_client.is_some() && !config.heartbeats.is_empty() => {
+                let due = heartbeats.due(&config.heartbeats, Instant::now());
+                if !due.is_empty() {
+                    let online = mc_server.status().await.players().is_some();
+                    let snapshot = heartbeats::Snapshot {
+                        online,
+                        players: if online { players.len() } else { 0 },
+                        max_players,
+                        version: tui_state.server_version.clone(),
+                    };
+                    for idx in due {
+                        let client = heartbeat_client.clone().unwrap();
+                        let heartbeat = config.heartbeats[idx].clone();
+                        let snapshot = snapshot.clone();
+                        tokio::spawn(async move {
+                            heartbeats::send(&client, &heartbeat, &snapshot).await;
+                        });
+                    }
+                }
+                continue;
+            },
+            _ = player_counter_timer.tick(), if config.discord.as_ref().is_some_and(|d| d.player_count_channel.is_some()) => {
+                let channel_id = config.discord.as_ref().and_then(|d| d.player_count_channel).unwrap();
+                let online = mc_server.status().await.players().map(|_| players.len());
+                let name = player_counter::channel_name(online, max_players);
+                if player_counter.rename(&name, Instant::now()) {
+                    discord.clone().rename_channel(channel_id.into(), name);
+                }
+                continue;
+            },
+            _ = player_list_timer.tick(), if config.minecraft.player_list_interval > 0 => {
+                if mc_server.status().await.players().is_some() {
+                    pending_player_lists += 1;
+                    mc_cmd_sender.send(ServerCommand::WriteCommandToStdin("list uuids".into())).await.unwrap();
+                }
+                continue;
+            },
+            Some(usage) = disk_usage_receiver.recv() => {
+                debug!(
+                    "Worlds are using {}, {} of {} free",
+                    worlds::format_size(usage.world_size),
+                    worlds::format_size(usage.free_space),
+                    worlds::format_size(usage.total_space)
+                );
+
+                let min_free_space = config.minecraft.disk_monitoring.as_ref().unwrap().min_free_space;
+                if usage.is_low(min_free_space) {
+                    if !low_disk_alerted {
+                        let msg = format!(
+                            "The Minecraft server is running low on disk space ({} free, worlds are using {})",
+                            worlds::format_size(usage.free_space),
+                            worlds::format_size(usage.world_size)
+                        );
+                        warn!("{}", msg);
+                        discord.clone().send_channel_msg(msg);
+                        low_disk_alerted = true;
+                    }
+                } else if low_disk_alerted {
+                    info!("Free disk space has recovered ({} free)", worlds::format_size(usage.free_space));
+                    low_disk_alerted = false;
+                }
+
+                disk_usage = Some(usage);
+            },
+            Some(import_result) = import_receiver.recv() => {
+                match import_result {
+                    Ok(imported) if imported.imported_logs.is_empty() => info!("No new logs to import"),
+                    Ok(imported) => {
+                        info!(
+                            "Imported {} logs ({} players, {} chat messages, {} crashes)",
+                            imported.imported_logs.len(),
+                            imported.players.len(),
+                            imported.players.values().map(|p| p.chat_messages).sum::<u32>(),
+                            imported.crashes.len()
+                        );
+                        stats.merge(imported);
+                        save_stats(&config.minecraft.server_path, &stats).await;
+                    },
+                    Err(e) => error!("Failed to import logs: {:#}", e),
+                }
+            },
+            Some(restore_result) = restore_receiver.recv() => {
+                match restore_result {
+                    Ok(RestoreTaskResult::Verified { backup, worlds, dry_run: true }) => {
+                        info!("{} is intact and contains: {}", backup, worlds.join(", "));
+                    },
+                    Ok(RestoreTaskResult::Verified { backup, worlds, dry_run: false }) => {
+                        info!(
+                            "Restoring {} will replace: {} (the current copies will be moved aside{})",
+                            backup,
+                            worlds.join(", "),
+                            if mc_server.running().await { " and the server will be restarted" } else { "" }
+                        );
+                        info!("Type `confirm` to continue or anything else to cancel");
+                        pending_restore = Some(backup);
+                    },
+                    Ok(RestoreTaskResult::Restored { snapshots, restart }) => {
+                        for snapshot in snapshots {
+                            info!("Previous world moved to {:?}", snapshot);
+                        }
+                        info!("Backup restored");
+                        discord.clone().send_channel_msg(i18n::strings().discord.backup_restored.as_str());
+
+                        if restart {
+                            mc_cmd_sender.send(ServerCommand::StartServer { config: pending_mc_config.take() }).await.unwrap();
+                            last_start_time = Instant::now();
+                        }
+                    },
+                    Err(e) => error!("Restore failed: {:#}", e),
+                }
+            },
+            Some(world_list) = world_list_receiver.recv() => {
+                match world_list {
+                    Ok(worlds) if worlds.is_empty() => info!("No worlds have been generated yet"),
+                    Ok(worlds) => {
+                        for world in worlds {
+                            info!("{}", world);
+                        }
+                    },
+                    Err(e) => error!("Failed to list worlds: {:#}", e),
+                }
+            },
+            Some(addon_result) = addon_result_receiver.recv() => {
+                match addon_result {
+                    Ok(AddonTaskResult::Found(updates)) => {
+                        if updates.is_empty() {
+                            info!("All plugins and mods are up-to-date");
+                        } else {
+                            for update in &updates {
+                                info!("Update available: {}", update);
+                            }
+                            info!("Run `stage-updates` to download these for the next restart");
+                        }
+                        available_addon_updates = updates;
+                    },
+                    Ok(AddonTaskResult::Staged(count)) => {
+                        info!("{} plugin and mod updates will be installed the next time the server stops or starts", count);
+                    },
+                    Err(e) => error!("Plugin and mod updates failed: {:#}", e),
+                }
+            },
+            Some(dump_result) = dump_result_receiver.recv() => {
+                match dump_result {
+                    Ok(diagnostics::Dumped::Saved(path)) => {
+                        info!("Saved the dump to {}", path.display());
+                        tui_state.raise_alert(format!("Dump saved to {}", path.display()));
+                    },
+                    Ok(diagnostics::Dumped::Signaled(path)) => {
+                        info!("jcmd wasn't found, so the thread dump is being captured from the server's console");
+                        let (output_sender, output_receiver) = oneshot::channel();
+                        command_captures.start_for(output_sender, Instant::now(), diagnostics::SIGNAL_CAPTURE_DURATION);
+                        let dump_result_sender = dump_result_sender.clone();
+                        tokio::spawn(async move {
+                            let lines = output_receiver.await.ok().and_then(Result::ok).unwrap_or_default();
+                            let result = tokio::fs::write(&path, lines.join("\n"))
+                                .await
+                                .map(|_| diagnostics::Dumped::Saved(path.clone()))
+                                .with_context(|| format!("Failed to write {:?}", path));
+                            let _ = dump_result_sender.send(result).await;
+                        });
+                    },
+                    Err(e) => error!("Failed to take the dump: {:#}", e),
+                }
+            },
+            Some(orphan_result) = orphan_result_receiver.recv() => {
+                match orphan_result {
+                    Ok(()) => {
+                        apply_staged_addon_updates(&config.minecraft.server_path);
+                        info!("The server from the previous run has stopped; starting the Minecraft server");
+                        mc_cmd_sender.send(ServerCommand::StartServer { config: pending_mc_config.take() }).await.unwrap();
+                        last_start_time = Instant::now();
+                    },
+                    Err(e) => error!("Failed to stop the server from the previous run: {:#}", e),
+                }
+            },
+            Some(provision_result) = provision_result_receiver.recv() => {
+                match provision_result {
+                    Ok(Some(jar)) => info!("Installed {}; run `start` to start the Minecraft server", jar),
+                    Ok(None) => info!("There's already a server jar at {:?}", config.minecraft.server_path),
+                    Err(e) => error!("Failed to set up the Minecraft server: {:#}", e),
+                }
+            },
+            Some(upgrade_result) = upgrade_result_receiver.recv() => {
+                match upgrade_result {
+                    Ok(backup_path) => {
+                        info!("Upgrade complete (previous jar backed up to {:?}), starting the Minecraft server", backup_path);
+                        discord.clone().send_channel_msg(i18n::strings().discord.upgraded.as_str());
+                    },
+                    Err(e) => {
+                        error!("Failed to upgrade the Minecraft server: {:#}", e);
+                    }
+                }
+
+                // The jar is only swapped in once it's been fully downloaded and verified,
+                // so the server can be started back up regardless
+                mc_cmd_sender.send(ServerCommand::StartServer { config: pending_mc_config.take() }).await.unwrap();
+                last_start_time = Instant::now();
+            },
             config_file_event = notify_receiver.recv() => {
                 match config_file_event {
-                    // this currently is not used for anything, it's here
-                    // for future use
-                    Some(event) => match event {
-                        Ok(_events) => debug!("Events fired for config file at path"),
-                        Err(_error) => debug!("Received error from config file watcher"),
+                    // `Config::load` would write a default config if the file was removed
+                    Some(Ok(_events)) if !config_filepath.exists() => {},
+                    Some(Ok(_events)) => {
+                        let reloaded = match Config::load(&config_filepath).await {
+                            Ok(mut reloaded) => reloaded
+                                .merge_in_args(reload_opt.clone())
+                                .and_then(|_| if reload_opt.staging { reloaded.use_staging().map(|_| ()) } else { Ok(()) })
+                                .map(|_| reloaded),
+                            Err(e) => Err(e),
+                        };
+                        let reloaded = match reloaded {
+                            Ok(reloaded) => reloaded,
+                            Err(e) => {
+                                warn!("Failed to reload the config: {:#}", e);
+                                continue;
+                            }
+                        };
+
+                        if reloaded.logging != config.logging {
+                            logging::set_file_log_levels(
+                                reloaded.logging.all,
+                                reloaded.logging.self_level,
+                                reloaded.logging.discord,
+                            );
+                            tui_state.set_max_records(reloaded.logging.tui_max_records);
+                            info!("Applied the new logging settings");
+                        }
+
+                        if reloaded.remote_commands_per_second != config.remote_commands_per_second {
+                            command_limiter.set_per_second(reloaded.remote_commands_per_second);
+                        }
+
+                        if reloaded.discord != config.discord {
+                            bridge_whispers = reloaded
+                                .discord
+                                .as_ref()
+                                .map(|discord| discord.bridge_whispers)
+                                .unwrap_or(false);
+
+                            let templates = reloaded.discord.as_ref().map(|d| d.status_templates.as_slice());
+                            if templates != config.discord.as_ref().map(|d| d.status_templates.as_slice()) {
+                                presence = presence::PresenceCycle::new(templates.unwrap_or_default());
+                            }
+                            presence_timer = status_timer(reloaded.discord.as_ref());
+
+                            match reloaded.discord.as_ref().map(|d| d.bridge_filters()).transpose() {
+                                Ok(filters) => bridge_filters = filters.unwrap_or_else(regex::RegexSet::empty),
+                                Err(e) => warn!("Invalid regular expression in the Discord bridge filters: {}", e),
+                            }
+
+                            let old_discord = config.discord.as_ref();
+                            let new_discord = reloaded.discord.as_ref();
+                            let reconnect = old_discord.map(|d| (d.enable_bridge, &d.token, d.channel_id, d.update_status, &d.run_roles, d.self_whitelist, &d.ignore_users, &d.link_domains, &d.chat_prefix, d.bridge_direction))
+                                != new_discord.map(|d| (d.enable_bridge, &d.token, d.channel_id, d.update_status, &d.run_roles, d.self_whitelist, &d.ignore_users, &d.link_domains, &d.chat_prefix, d.bridge_direction));
+                            if reconnect {
+                                discord.shutdown();
+                                discord = match connect_discord(
+                                    new_discord,
+                                    mc_cmd_sender.clone(),
+                                    capture_sender.clone(),
+                                    whitelist_sender.clone(),
+                                ).await {
+                                    Ok(bridge) => bridge,
+                                    Err(e) => {
+                                        error!("Failed to connect to Discord: {:#}", e);
+                                        DiscordBridge::new_noop()
+                                    }
+                                };
+                            }
+                            status_embed_updates.changed();
+                            player_counter.reset();
+                        }
+
+                        let changed = config.minecraft.changed_options(&reloaded.minecraft);
+                        let old = std::mem::replace(&mut config.minecraft, reloaded.minecraft);
+                        // These are only read when the wrapper starts up, so the old values
+                        // are kept until then
+                        config.minecraft.server_path = old.server_path;
+                        config.minecraft.provision = old.provision;
+                        config.minecraft.disk_monitoring = old.disk_monitoring;
+                        config.minecraft.gc_logging = old.gc_logging;
+                        config.minecraft.backup_dir = old.backup_dir;
+                        config.minecraft.geyser = old.geyser;
+                        config.minecraft.restart_vote = old.restart_vote;
+                        config.minecraft.afk = old.afk;
+                        config.minecraft.player_count_notifications = old.player_count_notifications;
+                        config.logging = reloaded.logging;
+                        config.discord = reloaded.discord;
+                        config.servers = reloaded.servers;
+                        config.aliases = reloaded.aliases;
+
+                        let mut server_changes = vec![];
+                        let mut wrapper_changes = vec![];
+                        for option in changed {
+                            match option.as_str() {
+                                "watchlist" => {
+                                    let rules = config.minecraft.watchlist.as_deref().unwrap_or_default();
+                                    match watchlist::Watchlist::new(rules) {
+                                        Ok(new_watchlist) => watchlist = new_watchlist,
+                                        Err(e) => warn!("Invalid regular expression in the watchlist: {}", e),
+                                    }
+                                },
+                                "player_notifications" => {
+                                    player_notifications = player_notifications::PlayerNotifications::new(
+                                        config.minecraft.player_notifications.as_deref().unwrap_or_default(),
+                                    );
+                                },
+                                "player_list_interval" => {
+                                    player_list_timer = tokio::time::interval(Duration::from_secs(
+                                        config.minecraft.player_list_interval.max(1) * 60,
+                                    ));
+                                },
+                                // These are read whenever they're needed
+                                "auto_agree_eula" | "maintenance" | "restart_warning" => {},
+                                "server_path" | "provision" | "disk_monitoring" | "gc_logging" | "backup_dir" | "geyser"
+                                | "restart_vote" | "afk" | "player_count_notifications" => wrapper_changes.push(option),
+                                _ => server_changes.push(option),
+                            }
+                        }
+
+                        if !server_changes.is_empty() {
+                            message_patterns = build_message_patterns(&config.minecraft);
+                            pending_mc_config = Some(build_mc_config(&config.minecraft, replay.as_ref()));
+                            tui_state.pending_restart = true;
+                            info!(
+                                "Changes to {} will be applied the next time the server starts",
+                                server_changes.join(", ")
+                            );
+                        }
+                        if !wrapper_changes.is_empty() {
+                            warn!(
+                                "Restart mc-server-wrapper to apply changes to {}",
+                                wrapper_changes.join(", ")
+                            );
+                        }
                     },
+                    Some(Err(e)) => debug!("Received error from config file watcher: {}", e),
                     // TODO: should we break or panic in these cases?
                     None => unreachable!()
                 }
@@ -398,7 +2626,544 @@ async fn main() -> Result<(), anyhow::Error> {
             // TODO: get rid of this
             else => break,
         }
+
+        // Branches that can't change what the TUI shows skip this with
+        // `continue`
+        tui_redraw.changed();
+    }
+
+    if stats_dirty {
+        save_stats(&config.minecraft.server_path, &stats).await;
+    }
+
+    Ok(exit_code)
+}
+
+/// Builds the config used to run the Minecraft server, or to replay a log
+/// in its place
+fn build_mc_config(minecraft: &config::Minecraft, replay: Option<&ReplayConfig>) -> McServerConfig {
+    let mut jvm_flags = minecraft.jvm_flags.clone();
+    if minecraft.gc_logging.is_some() {
+        let gc_flag = gc_log::jvm_flag();
+        jvm_flags = Some(match jvm_flags {
+            Some(flags) => format!("{} {}", flags, gc_flag),
+            None => gc_flag,
+        });
+    }
+    let mut mc_config = McServerConfig::new(
+        minecraft.server_path.clone(),
+        minecraft.memory,
+        jvm_flags,
+        false,
+    )
+    .with_pid_file(orphans::pid_file(&minecraft.server_path));
+    if let Some(instance) = &minecraft.instance {
+        mc_config = mc_config.with_instance(instance);
+    }
+    if let Some(resources) = minecraft.resources.clone() {
+        mc_config = mc_config.with_resource_limits(resources.into());
+    }
+    if let Some(run_as) = minecraft.run_as.clone() {
+        mc_config = mc_config.with_run_as(run_as.into());
+    }
+    if let Some(replay) = replay {
+        mc_config = mc_config.with_replay(replay.clone());
+    } else if let Some(attach) = minecraft.attach.clone() {
+        mc_config = mc_config.with_attach(attach.into());
+    }
+    let hook_timeout = Duration::from_secs(minecraft.hook_timeout);
+    let hook = |command: &String| Hook {
+        command: command.clone(),
+        timeout: hook_timeout,
+    };
+    if let Some(command) = &minecraft.pre_start_cmd {
+        mc_config = mc_config.with_pre_start_hook(hook(command));
+    }
+    if let Some(command) = &minecraft.post_stop_cmd {
+        mc_config = mc_config.with_post_stop_hook(hook(command));
+    }
+
+    mc_config.with_message_patterns(build_message_patterns(minecraft))
+}
+
+/// Builds the patterns used to recognize console messages
+fn build_message_patterns(minecraft: &config::Minecraft) -> MessagePatterns {
+    let mut message_patterns: MessagePatterns = minecraft
+        .localized_messages
+        .clone()
+        .map(Into::into)
+        .unwrap_or_default();
+    message_patterns.player_msg = minecraft.chat_formats.clone().unwrap_or_default();
+    message_patterns.player_whisper = minecraft.whisper_formats.clone().unwrap_or_default();
+    message_patterns
+}
+
+/// Returns the filter for links bridged to and from Discord
+fn link_filter(config: &Config) -> LinkFilter {
+    config
+        .discord
+        .as_ref()
+        .map(config::Discord::link_filter)
+        .unwrap_or_default()
+}
+
+/// Connects to Discord if the bridge is enabled in `discord_config`
+async fn connect_discord(
+    discord_config: Option<&config::Discord>,
+    mc_cmd_sender: mpsc::Sender<ServerCommand>,
+    capture_sender: mpsc::Sender<command_capture::CaptureRequest>,
+    whitelist_sender: mpsc::Sender<whitelist::WhitelistRequest>,
+) -> Result<DiscordBridge, anyhow::Error> {
+    match discord_config {
+        Some(discord_config) if discord_config.enable_bridge => {
+            let console_access = if discord_config.run_roles.is_empty() {
+                None
+            } else {
+                Some(ConsoleAccess {
+                    roles: discord_config
+                        .run_roles
+                        .iter()
+                        .copied()
+                        .map(Into::into)
+                        .collect(),
+                    capture_sender,
+                })
+            };
+
+            setup_discord(
+                discord_config.token.clone(),
+                discord_config.channel_id.into(),
+                mc_cmd_sender,
+                discord_config.update_status,
+                console_access,
+                Some(whitelist_sender).filter(|_| discord_config.self_whitelist),
+                BridgeFilters {
+                    enabled: discord_config.bridge_direction.to_minecraft(),
+                    ignored_users: discord_config
+                        .ignore_users
+                        .iter()
+                        .copied()
+                        .map(Into::into)
+                        .collect(),
+                    links: discord_config.link_filter(),
+                    chat_prefix: discord_config.chat_prefix.clone(),
+                },
+            )
+            .await
+        }
+        _ => Ok(DiscordBridge::new_noop()),
+    }
+}
+
+/// Spawns a task to update mc-server-wrapper, sending the result over
+/// `result_sender`
+fn spawn_self_update(
+    result_sender: mpsc::Sender<Result<Option<(String, PathBuf)>, anyhow::Error>>,
+) {
+    tokio::spawn(async move {
+        let _ = result_sender.send(self_update::update().await).await;
+    });
+}
+
+/// Spawns a task to upgrade the server at `server_path` to `jar`, sending the
+/// result over `result_sender`
+fn spawn_upgrade(
+    jar: provision::ServerJar,
+    server_path: PathBuf,
+    result_sender: mpsc::Sender<Result<PathBuf, anyhow::Error>>,
+) {
+    tokio::spawn(async move {
+        let result = match provision::http_client() {
+            Ok(client) => provision::update::upgrade(&client, &jar, &server_path).await,
+            Err(e) => Err(e),
+        };
+
+        let _ = result_sender.send(result).await;
+    });
+}
+
+/// Spawns a task to set up a server at `server_path` as described by
+/// `provision`, sending the result over `result_sender`
+fn spawn_provision(
+    provision: config::Provision,
+    server_path: PathBuf,
+    result_sender: mpsc::Sender<Result<Option<provision::ServerJar>, anyhow::Error>>,
+) {
+    tokio::spawn(async move {
+        let result = provision::provision(&provision, &server_path).await;
+        let _ = result_sender.send(result).await;
+    });
+}
+
+/// Work on plugin and mod updates to perform in the background
+enum AddonTask {
+    /// Look for updates
+    Check(Option<config::Provision>),
+    /// Download the given updates for the next restart
+    Stage(Vec<addons::AddonUpdate>),
+}
+
+/// The outcome of an `AddonTask`
+enum AddonTaskResult {
+    Found(Vec<addons::AddonUpdate>),
+    Staged(usize),
+}
+
+/// Spawns a task to perform `task` for the server at `server_path`, sending
+/// the result over `result_sender`
+fn spawn_addon_task(
+    task: AddonTask,
+    server_path: PathBuf,
+    result_sender: mpsc::Sender<Result<AddonTaskResult, anyhow::Error>>,
+) {
+    tokio::spawn(async move {
+        let result = match (provision::http_client(), task) {
+            (Ok(client), AddonTask::Check(provision)) => {
+                addons::find_updates(&client, &server_path, provision.as_ref())
+                    .await
+                    .map(AddonTaskResult::Found)
+            }
+            (Ok(client), AddonTask::Stage(updates)) => addons::stage(&client, &updates)
+                .await
+                .map(|_| AddonTaskResult::Staged(updates.len())),
+            (Err(e), _) => Err(e),
+        };
+
+        let _ = result_sender.send(result).await;
+    });
+}
+
+/// Installs any plugin and mod updates that were staged while the server was
+/// running
+///
+/// Must only be called while the server is stopped.
+fn apply_staged_addon_updates(server_path: &Path) {
+    match addons::apply_staged(&provision::server_dir(server_path)) {
+        Ok(applied) => {
+            for name in applied {
+                info!("Installed staged update {}", name);
+            }
+        }
+        Err(e) => error!("Failed to install staged plugin and mod updates: {:#}", e),
+    }
+}
+
+/// A change to the world the server loads
+enum WorldChange {
+    Switch(String),
+    Create { name: String, seed: Option<String> },
+}
+
+impl WorldChange {
+    /// Parses the arguments to the `world` console command
+    fn parse(args: &str) -> Option<Self> {
+        let mut args = args.split_whitespace();
+
+        let change = match (args.next()?, args.next()?, args.next()) {
+            ("switch", name, None) => WorldChange::Switch(name.into()),
+            ("create", name, seed) => WorldChange::Create {
+                name: name.into(),
+                seed: seed.map(Into::into),
+            },
+            _ => return None,
+        };
+
+        if args.next().is_some() {
+            None
+        } else {
+            Some(change)
+        }
+    }
+
+    /// Makes this change, logging the outcome
+    ///
+    /// Must only be called while the server is stopped.
+    async fn apply(self, server_path: &Path) {
+        let result = match &self {
+            WorldChange::Switch(name) => worlds::switch(server_path, name).await,
+            WorldChange::Create { name, seed } => {
+                worlds::create(server_path, name, seed.as_deref()).await
+            }
+        };
+
+        match (result, self) {
+            (Ok(()), WorldChange::Switch(name)) => info!("Switched to world \"{}\"", name),
+            (Ok(()), WorldChange::Create { name, .. }) => {
+                info!(
+                    "World \"{}\" will be generated when the server starts",
+                    name
+                )
+            }
+            (Err(e), _) => error!("Failed to change worlds: {:#}", e),
+        }
+    }
+}
+
+/// Checks the active world's region files for corruption, logging what's
+/// found and restoring the damaged files from the latest backup if `restore`
+///
+/// Must only be called while the server is stopped.
+async fn check_world(
+    server_path: PathBuf,
+    backup_dir: PathBuf,
+    restore: bool,
+) -> Result<(), anyhow::Error> {
+    let world = worlds::active_world(&server_path).await?;
+
+    tokio::task::spawn_blocking(move || {
+        let problems = worlds::regions::check(&server_path, &world)?;
+        if problems.is_empty() {
+            info!("No problems were found in world \"{}\"", world);
+            return Ok(());
+        }
+        for problem in &problems {
+            warn!("{}", problem);
+        }
+
+        let mut damaged: Vec<_> = problems.into_iter().map(|p| p.path).collect();
+        damaged.dedup();
+        if !restore {
+            info!(
+                "{} region files are damaged; run `world check --restore` to restore them from the latest backup",
+                damaged.len()
+            );
+            return Ok(());
+        }
+
+        let backup = backups::latest(&backup_dir)?
+            .ok_or_else(|| anyhow::anyhow!("there are no backups in {:?} to restore from", backup_dir))?;
+        let restored = backups::restore_files(&server_path, &backup, &damaged)?;
+        for path in &damaged {
+            if restored.contains(path) {
+                info!("Restored {} from {}", path.display(), backup.id);
+            } else {
+                warn!("{} isn't in {}, so it wasn't restored", path.display(), backup.id);
+            }
+        }
+
+        Ok(())
+    })
+    .await?
+}
+
+/// Work on restoring a backup to perform in the background
+enum RestoreTask {
+    /// Check the integrity of the backup with the given ID
+    Verify { id: String, dry_run: bool },
+    /// Restore the given backup, starting the server afterwards if `restart`
+    Restore {
+        backup: backups::Backup,
+        restart: bool,
+    },
+}
+
+/// The outcome of a `RestoreTask`
+enum RestoreTaskResult {
+    Verified {
+        backup: backups::Backup,
+        worlds: Vec<String>,
+        dry_run: bool,
+    },
+    Restored {
+        snapshots: Vec<PathBuf>,
+        restart: bool,
+    },
+}
+
+/// Spawns a task to perform `task` for the server at `server_path`, sending
+/// the result over `result_sender`
+fn spawn_restore_task(
+    task: RestoreTask,
+    server_path: PathBuf,
+    backup_dir: PathBuf,
+    result_sender: mpsc::Sender<Result<RestoreTaskResult, anyhow::Error>>,
+) {
+    tokio::spawn(async move {
+        let result = tokio::task::spawn_blocking(move || match task {
+            RestoreTask::Verify { id, dry_run } => {
+                let backup = backups::find(&backup_dir, &id)?;
+                let worlds = backups::verify(&backup)?;
+                Ok(RestoreTaskResult::Verified {
+                    backup,
+                    worlds,
+                    dry_run,
+                })
+            }
+            RestoreTask::Restore { backup, restart } => {
+                let restored = backups::restore(&server_path, &backup);
+                match restored {
+                    Ok(snapshots) => Ok(RestoreTaskResult::Restored { snapshots, restart }),
+                    // The worlds were put back if unpacking failed, so the server can
+                    // still be started back up
+                    Err(e) if restart => Err(e.context("the server must be started manually")),
+                    Err(e) => Err(e),
+                }
+            }
+        })
+        .await
+        .unwrap_or_else(|e| Err(e.into()));
+
+        let _ = result_sender.send(result).await;
+    });
+}
+
+/// Returns the bot status to show while the server is running
+fn running_status(
+    presence: &presence::PresenceCycle,
+    online_players: &OnlinePlayers,
+    max_players: Option<u32>,
+    availability: &availability::Availability,
+) -> String {
+    presence.status(&presence::PresenceInfo {
+        online_players,
+        max_players,
+        uptime: availability.server_uptime(OffsetDateTime::now_utc()),
+    })
+}
+
+/// Returns a timer for moving on to the next bot status template
+fn status_timer(discord: Option<&config::Discord>) -> tokio::time::Interval {
+    // Discord rate limits presence updates
+    let period = Duration::from_secs(discord.map(|d| d.status_interval).unwrap_or(60).max(15));
+    tokio::time::interval_at(tokio::time::Instant::now() + period, period)
+}
+
+/// Updates `player_count` with the number of players online, announcing any
+/// milestones or peaks that were reached
+async fn update_player_count(
+    player_count: &mut player_count::PlayerCountTracker,
+    online: usize,
+    stats: &mut stats::Stats,
+    server_path: &Path,
+    discord: &DiscordBridge,
+) {
+    let peak = stats.peak_players;
+    for event in player_count.update(online as u32, stats, OffsetDateTime::now_utc()) {
+        info!("{}", event);
+        discord.clone().send_channel_msg(event.to_string());
+    }
+
+    if stats.peak_players != peak {
+        save_stats(server_path, stats).await;
+    }
+}
+
+/// Saves the sessions of `online_players`, logging any failure
+async fn save_sessions(server_path: &Path, online_players: &OnlinePlayers) {
+    if let Err(e) = sessions::store(server_path, online_players).await {
+        warn!("Failed to save player sessions: {:#}", e);
+    }
+}
+
+/// Saves `stats`, logging any failure
+async fn save_stats(server_path: &Path, stats: &stats::Stats) {
+    if let Err(e) = stats.store(server_path).await {
+        warn!("Failed to save stats: {:#}", e);
+    }
+}
+
+/// Starts Geyser if it's configured, logging any failure
+fn start_geyser(
+    geyser_config: Option<&config::Geyser>,
+    sender: mpsc::Sender<GeyserEvent>,
+) -> Option<GeyserProcess> {
+    let geyser_config = geyser_config?;
+
+    info!("Starting Geyser");
+    GeyserProcess::start(geyser_config, sender)
+        .map_err(|e| error!("Failed to start Geyser: {}", e))
+        .ok()
+}
+
+/// Formats a player's name for a join or leave message in Discord, noting
+/// whether they're playing Bedrock Edition
+fn format_player_for_discord(name: &str, bedrock_players: Option<&BedrockPlayers>) -> String {
+    match bedrock_players {
+        Some(bedrock_players) if bedrock_players.is_bedrock(name) => format!(
+            "**{}** (Bedrock)",
+            sanitize_for_markdown(bedrock_players.display_name(name))
+        ),
+        _ => format!("**{}**", sanitize_for_markdown(name)),
+    }
+}
+
+/// Announces the outcome of a restart vote by `voter` (or the console if
+/// `None`), stopping the server if the vote passed
+///
+/// Returns true if the server is being stopped to restart it.
+async fn handle_restart_vote(
+    outcome: restart_vote::VoteOutcome,
+    voter: Option<&str>,
+    mc_cmd_sender: &mpsc::Sender<ServerCommand>,
+) -> bool {
+    use restart_vote::{VoteOutcome, VOTE_MESSAGE};
+
+    let announcement = match outcome {
+        VoteOutcome::Started { votes, needed } => format!(
+            "{} started a vote to restart the server ({}/{}), type {} to vote",
+            voter.unwrap_or("An admin"),
+            votes,
+            needed,
+            VOTE_MESSAGE
+        ),
+        VoteOutcome::Counted { votes, needed } => {
+            format!("Restart vote: {}/{}", votes, needed)
+        }
+        VoteOutcome::AlreadyVoted => {
+            info!("A restart vote is already in progress");
+            return false;
+        }
+        VoteOutcome::CoolingDown(remaining) => format!(
+            "Another restart vote can be started in {} minutes",
+            remaining.as_secs() / 60 + 1
+        ),
+        VoteOutcome::Passed => "The restart vote passed, restarting the server".into(),
+    };
+
+    info!("{}", announcement);
+    mc_cmd_sender
+        .send(ServerCommand::WriteCommandToStdin(format!(
+            "say {}",
+            announcement
+        )))
+        .await
+        .unwrap();
+
+    if outcome == VoteOutcome::Passed {
+        mc_cmd_sender
+            .send(ServerCommand::StopServer { forever: false })
+            .await
+            .unwrap();
+        true
+    } else {
+        false
     }
+}
 
-    Ok(())
+/// Sets `key` to `value` in `server.properties`, logging the outcome
+///
+/// Returns true if the change needs a restart to apply because the server is
+/// `running`.
+async fn set_property(server_path: &Path, key: &str, value: &str, running: bool) -> bool {
+    let result = async {
+        let mut properties = properties::ServerProperties::load(server_path).await?;
+        properties.set(key, value);
+        properties.store(server_path).await
+    }
+    .await;
+
+    match result {
+        Ok(()) if running => {
+            info!("Set {} to {} (restart the server to apply it)", key, value);
+            true
+        }
+        Ok(()) => {
+            info!("Set {} to {}", key, value);
+            false
+        }
+        Err(e) => {
+            error!("Failed to update server.properties: {:#}", e);
+            false
+        }
+    }
 }
@@ -0,0 +1,203 @@
+//! Maintenance mode
+//!
+//! While maintenance mode is on, the server's whitelist is replaced with one
+//! that only contains operators and any configured admins, and the server's
+//! MOTD can be swapped for a maintenance message. The replaced whitelist and
+//! properties are saved next to the server jar so they can be restored by
+//! `disable`, even after the wrapper restarts.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde_derive::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::properties::ServerProperties;
+
+/// The file (next to the server jar) the pre-maintenance state is saved in
+const STATE_FILENAME: &str = "mc-server-wrapper-maintenance.toml";
+
+/// The server's state from before maintenance mode was turned on
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct SavedState {
+    /// The contents of `whitelist.json`, if it existed
+    pub whitelist: Option<String>,
+    /// The `white-list` property
+    pub white_list: Option<String>,
+    /// The `motd` property, if maintenance mode replaced it
+    pub motd: Option<String>,
+}
+
+impl SavedState {
+    /// Returns true if the whitelist was turned on before maintenance mode
+    pub fn whitelist_was_on(&self) -> bool {
+        self.white_list.as_deref() == Some("true")
+    }
+}
+
+/// An entry in `whitelist.json` or `ops.json`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PlayerEntry {
+    pub uuid: String,
+    pub name: String,
+}
+
+fn state_path(server_path: &Path) -> PathBuf {
+    server_path.with_file_name(STATE_FILENAME)
+}
+
+/// Returns true if maintenance mode is on for the server at `server_path`
+pub fn is_enabled(server_path: &Path) -> bool {
+    state_path(server_path).exists()
+}
+
+/// Turns on maintenance mode for the server at `server_path`
+///
+/// Returns the names of the players on the maintenance whitelist. Configured
+/// admins that aren't operators or already whitelisted are left out, since
+/// their UUIDs aren't known; a running server can add them with
+/// `whitelist add`.
+pub async fn enable(
+    server_path: &Path,
+    admins: &[String],
+    motd: Option<&str>,
+) -> Result<Vec<String>, anyhow::Error> {
+    if is_enabled(server_path) {
+        anyhow::bail!("Maintenance mode is already on");
+    }
+
+    let whitelist_path = server_path.with_file_name("whitelist.json");
+    let ops_path = server_path.with_file_name("ops.json");
+    let whitelist = read_optional(&whitelist_path).await?;
+    let ops = read_optional(&ops_path).await?;
+    let mut properties = ServerProperties::load(server_path).await?;
+
+    let state = SavedState {
+        whitelist: whitelist.clone(),
+        white_list: properties.get("white-list"),
+        motd: motd.and(properties.get("motd")),
+    };
+    let entries = admin_whitelist(ops.as_deref(), whitelist.as_deref(), admins)?;
+
+    // Save the old state first so nothing is lost if a later step fails
+    let path = state_path(server_path);
+    fs::write(&path, toml::to_string(&state)?)
+        .await
+        .with_context(|| format!("Failed to write {:?}", path))?;
+
+    fs::write(&whitelist_path, serde_json::to_string_pretty(&entries)?)
+        .await
+        .with_context(|| format!("Failed to write {:?}", whitelist_path))?;
+    properties.set("white-list", "true");
+    if let Some(motd) = motd {
+        properties.set("motd", motd);
+    }
+    properties.store(server_path).await?;
+
+    Ok(entries.into_iter().map(|e| e.name).collect())
+}
+
+/// Turns off maintenance mode for the server at `server_path`, restoring the
+/// state from before it was turned on
+pub async fn disable(server_path: &Path) -> Result<SavedState, anyhow::Error> {
+    let path = state_path(server_path);
+    if !path.exists() {
+        anyhow::bail!("Maintenance mode is not on");
+    }
+
+    let contents = fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    let state: SavedState =
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))?;
+
+    let whitelist_path = server_path.with_file_name("whitelist.json");
+    match &state.whitelist {
+        Some(whitelist) => fs::write(&whitelist_path, whitelist)
+            .await
+            .with_context(|| format!("Failed to write {:?}", whitelist_path))?,
+        None if whitelist_path.exists() => fs::remove_file(&whitelist_path)
+            .await
+            .with_context(|| format!("Failed to remove {:?}", whitelist_path))?,
+        None => {}
+    }
+
+    let mut properties = ServerProperties::load(server_path).await?;
+    properties.set("white-list", state.white_list.as_deref().unwrap_or("false"));
+    if let Some(motd) = &state.motd {
+        properties.set("motd", motd);
+    }
+    properties.store(server_path).await?;
+
+    fs::remove_file(&path)
+        .await
+        .with_context(|| format!("Failed to remove {:?}", path))?;
+    Ok(state)
+}
+
+async fn read_optional(path: &Path) -> Result<Option<String>, anyhow::Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    fs::read_to_string(path)
+        .await
+        .map(Some)
+        .with_context(|| format!("Failed to read {:?}", path))
+}
+
+/// Builds the maintenance whitelist from the contents of `ops.json` and
+/// `whitelist.json`
+fn admin_whitelist(
+    ops: Option<&str>,
+    whitelist: Option<&str>,
+    admins: &[String],
+) -> Result<Vec<PlayerEntry>, anyhow::Error> {
+    let parse = |contents: Option<&str>, name| -> Result<Vec<PlayerEntry>, anyhow::Error> {
+        match contents {
+            Some(contents) if !contents.trim().is_empty() => {
+                serde_json::from_str(contents).with_context(|| format!("Failed to parse {}", name))
+            }
+            _ => Ok(vec![]),
+        }
+    };
+
+    let mut entries = parse(ops, "ops.json")?;
+    for entry in parse(whitelist, "whitelist.json")? {
+        let is_admin = admins.iter().any(|a| a.eq_ignore_ascii_case(&entry.name));
+        if is_admin && !entries.iter().any(|e| e.uuid == entry.uuid) {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ops_and_whitelisted_admins() {
+        let ops = r#"[{"uuid": "1", "name": "Cldfire", "level": 4, "bypassesPlayerLimit": false}]"#;
+        let whitelist = r#"[
+            {"uuid": "1", "name": "Cldfire"},
+            {"uuid": "2", "name": "Helper"},
+            {"uuid": "3", "name": "Player"}
+        ]"#;
+
+        let entries = admin_whitelist(
+            Some(ops),
+            Some(whitelist),
+            &["helper".into(), "Ghost".into()],
+        )
+        .unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["Cldfire", "Helper"]);
+    }
+
+    #[test]
+    fn missing_files() {
+        assert!(admin_whitelist(None, Some(""), &[]).unwrap().is_empty());
+    }
+}
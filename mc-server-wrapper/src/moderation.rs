@@ -0,0 +1,237 @@
+//! A history of the kicks, bans, and pardons on the server
+//!
+//! Moderation actions are recognized in console output, so they're recorded
+//! whether they were taken in-game, from the console, or by the wrapper
+//! itself. Commands the wrapper runs on someone's behalf (like `/run` in
+//! Discord) are attributed to them. The history is saved next to the server
+//! jar.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use mc_server_wrapper_lib::{local_time, parse::ModerationAction};
+use serde_derive::{Deserialize, Serialize};
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime};
+use tokio::fs;
+
+/// The file (next to the server jar) the history is saved in
+const HISTORY_FILENAME: &str = "mc-server-wrapper-moderation.json";
+
+/// How long a command run by the wrapper has to show up in console output to
+/// be attributed to whoever ran it
+const ATTRIBUTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "ModerationAction", rename_all = "lowercase")]
+enum ModerationActionDef {
+    Kick,
+    Ban,
+    Pardon,
+}
+
+/// A kick, ban, or pardon
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ModerationEntry {
+    /// Unix timestamp of when the action was taken
+    pub time: i64,
+    #[serde(with = "ModerationActionDef")]
+    pub action: ModerationAction,
+    /// The name of the player the action was taken on
+    pub name: String,
+    pub reason: Option<String>,
+    /// Who took the action, if known
+    pub by: Option<String>,
+}
+
+impl fmt::Display for ModerationEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const TIME_FORMAT: &[FormatItem] =
+            format_description!("[year]-[month]-[day] [hour]:[minute]");
+
+        if let Ok(time) = OffsetDateTime::from_unix_timestamp(self.time) {
+            let time = time.to_offset(local_time::local_offset());
+            write!(f, "{} ", time.format(&TIME_FORMAT).map_err(|_| fmt::Error)?)?;
+        }
+        write!(f, "{} {}", self.action, self.name)?;
+        if let Some(by) = &self.by {
+            write!(f, " (by {})", by)?;
+        }
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A moderation command run by the wrapper that hasn't shown up in console
+/// output yet
+#[derive(Debug)]
+struct PendingCommand {
+    action: ModerationAction,
+    name: String,
+    by: String,
+    ran_at: Instant,
+}
+
+/// The moderation history store
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ModerationHistory {
+    /// The recorded actions, oldest first
+    entries: Vec<ModerationEntry>,
+    #[serde(skip)]
+    pending: Vec<PendingCommand>,
+}
+
+impl ModerationHistory {
+    fn path(server_path: &Path) -> PathBuf {
+        server_path.with_file_name(HISTORY_FILENAME)
+    }
+
+    /// Reads the history saved for the server at `server_path`
+    ///
+    /// Returns an empty history if none was saved.
+    pub async fn load(server_path: &Path) -> Result<Self, anyhow::Error> {
+        let path = Self::path(server_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        serde_json::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Saves this history for the server at `server_path`
+    pub async fn store(&self, server_path: &Path) -> Result<(), anyhow::Error> {
+        let path = Self::path(server_path);
+        fs::write(&path, serde_json::to_string(self)?)
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Notes that the wrapper is running `command` on behalf of `by`
+    ///
+    /// If `command` is a moderation command, the action it takes is
+    /// attributed to `by` when it's recorded.
+    pub fn running(&mut self, command: &str, by: impl Into<String>, now: Instant) {
+        let mut words = command.split_whitespace();
+        let action = words
+            .next()
+            .and_then(|command| ModerationAction::from_command(&command.to_lowercase()));
+        if let (Some(action), Some(name)) = (action, words.next()) {
+            self.pending.push(PendingCommand {
+                action,
+                name: name.into(),
+                by: by.into(),
+                ran_at: now,
+            });
+        }
+    }
+
+    /// Records an action seen in console output
+    pub fn record(&mut self, mut entry: ModerationEntry, now: Instant) -> &ModerationEntry {
+        self.pending
+            .retain(|pending| now.saturating_duration_since(pending.ran_at) < ATTRIBUTION_TIMEOUT);
+        if let Some(idx) = self.pending.iter().position(|pending| {
+            pending.action == entry.action && pending.name.eq_ignore_ascii_case(&entry.name)
+        }) {
+            let pending = self.pending.remove(idx);
+            entry.by = entry.by.or(Some(pending.by));
+        }
+
+        self.entries.push(entry);
+        self.entries.last().unwrap()
+    }
+
+    /// Returns the actions taken on the player named `name`, oldest first
+    pub fn player<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a ModerationEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(action: ModerationAction, name: &str, by: Option<&str>) -> ModerationEntry {
+        ModerationEntry {
+            time: 1_700_000_000,
+            action,
+            name: name.into(),
+            reason: None,
+            by: by.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn attribution() {
+        let start = Instant::now();
+        let mut history = ModerationHistory::default();
+        history.running("kick Steve being AFK", "mc-server-wrapper (AFK)", start);
+        history.running("ban Alex griefing", "Cldfire (Discord)", start);
+        history.running("list", "Cldfire (Discord)", start);
+
+        let recorded = history.record(entry(ModerationAction::Kick, "steve", None), start);
+        assert_eq!(recorded.by.as_deref(), Some("mc-server-wrapper (AFK)"));
+        // Only the first matching action is attributed
+        let recorded = history.record(entry(ModerationAction::Kick, "Steve", None), start);
+        assert_eq!(recorded.by, None);
+        // Players that ran the command in-game are kept
+        let recorded = history.record(
+            entry(ModerationAction::Ban, "Alex", Some("Notch")),
+            start + Duration::from_secs(1),
+        );
+        assert_eq!(recorded.by.as_deref(), Some("Notch"));
+        assert!(history.pending.is_empty());
+    }
+
+    #[test]
+    fn attribution_expires() {
+        let start = Instant::now();
+        let mut history = ModerationHistory::default();
+        history.running("pardon Steve", "console", start);
+
+        let recorded = history.record(
+            entry(ModerationAction::Pardon, "Steve", None),
+            start + ATTRIBUTION_TIMEOUT,
+        );
+        assert_eq!(recorded.by, None);
+    }
+
+    #[test]
+    fn player_history() {
+        let mut history = ModerationHistory::default();
+        let now = Instant::now();
+        history.record(entry(ModerationAction::Ban, "Steve", None), now);
+        history.record(entry(ModerationAction::Kick, "Alex", None), now);
+        history.record(entry(ModerationAction::Pardon, "STEVE", None), now);
+
+        let actions: Vec<_> = history.player("steve").map(|e| e.action).collect();
+        assert_eq!(actions, [ModerationAction::Ban, ModerationAction::Pardon]);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut history = ModerationHistory::default();
+        history.record(
+            ModerationEntry {
+                reason: Some("griefing".into()),
+                ..entry(ModerationAction::Ban, "Steve", Some("Cldfire"))
+            },
+            Instant::now(),
+        );
+
+        let json = serde_json::to_string(&history).unwrap();
+        assert!(json.contains(r#""action":"ban""#));
+        let loaded: ModerationHistory = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.entries, history.entries);
+    }
+}
@@ -0,0 +1,291 @@
+//! Finding server processes left running by a previous run of the wrapper
+//!
+//! The ID of the server process is written next to the server jar while it
+//! runs. If the wrapper is killed (or crashes) without stopping the server,
+//! the server keeps running and the file is left behind. When the wrapper
+//! starts, it checks whether that process is still running the server's jar
+//! (and on Linux, looks for any Java process running the jar from the
+//! server's folder) so it can offer to attach to it or stop it, rather than
+//! starting a second server that can't bind its port.
+
+use std::{
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::bail;
+use mc_server_wrapper_lib::attach::AttachConfig;
+
+use crate::{properties::ServerProperties, provision::server_dir};
+
+/// The file (next to the server jar) the server process's ID is written to
+const PID_FILENAME: &str = "mc-server-wrapper-server.pid";
+/// How long the server has to shut down after being asked to before it's
+/// killed
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(60);
+/// How long to wait for the server to exit after it's killed
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How a leftover server process was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoundBy {
+    PidFile,
+    CommandLine,
+}
+
+/// A server process left running by a previous run of the wrapper
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Orphan {
+    pub pid: u32,
+    pub found_by: FoundBy,
+}
+
+impl fmt::Display for Orphan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let found_by = match self.found_by {
+            FoundBy::PidFile => "from its PID file",
+            FoundBy::CommandLine => "by its command line",
+        };
+        write!(f, "PID {}, found {}", self.pid, found_by)
+    }
+}
+
+/// Returns the path the server at `server_path` has its process ID written to
+pub fn pid_file(server_path: &Path) -> PathBuf {
+    server_path.with_file_name(PID_FILENAME)
+}
+
+/// Returns the command line of the process with ID `pid`, if it's running
+#[cfg(target_os = "linux")]
+fn command_line(pid: u32) -> Option<String> {
+    let cmdline = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    // Zombies have an empty command line
+    if cmdline.is_empty() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&cmdline).replace('\0', " "))
+}
+
+/// Returns the command line of the process with ID `pid`, if it's running
+#[cfg(all(unix, not(target_os = "linux")))]
+fn command_line(pid: u32) -> Option<String> {
+    let output = std::process::Command::new("ps")
+        .args(["-o", "command=", "-p", &pid.to_string()])
+        .output()
+        .ok()?;
+    let command_line = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !command_line.is_empty()).then_some(command_line)
+}
+
+/// Returns the command line of the process with ID `pid`, if it's running
+#[cfg(not(unix))]
+fn command_line(_pid: u32) -> Option<String> {
+    // Leftover servers aren't looked for on Windows
+    None
+}
+
+/// Returns true if the process with ID `pid` is running
+///
+/// This is always false on Windows.
+pub fn is_running(pid: u32) -> bool {
+    command_line(pid).is_some()
+}
+
+/// Returns true if `command_line` runs the jar named `jar`
+fn runs_jar(command_line: &str, jar: &str) -> bool {
+    let args: Vec<_> = command_line.split_whitespace().collect();
+    args.windows(2)
+        .any(|pair| pair[0] == "-jar" && pair[1].trim_matches('"') == jar)
+}
+
+/// Looks for a Java process running `jar` from `dir` in `/proc`
+#[cfg(target_os = "linux")]
+fn find_by_command_line(dir: &Path, jar: &str) -> Option<u32> {
+    let dir = dir.canonicalize().ok()?;
+    let own_pid = std::process::id();
+
+    std::fs::read_dir("/proc")
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|pid| *pid != own_pid)
+        .find(|pid| {
+            command_line(*pid).is_some_and(|c| c.contains("java") && runs_jar(&c, jar))
+                && std::fs::read_link(format!("/proc/{}/cwd", pid)).ok() == Some(dir.clone())
+        })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_by_command_line(_dir: &Path, _jar: &str) -> Option<u32> {
+    None
+}
+
+/// Looks for a server process left running for the server at `server_path`
+///
+/// A PID file pointing to a process that isn't running the server's jar is
+/// stale, so it's removed.
+pub fn find(server_path: &Path) -> Option<Orphan> {
+    let jar = server_path.file_name()?.to_str()?;
+    let pid_file = pid_file(server_path);
+
+    if let Ok(contents) = std::fs::read_to_string(&pid_file) {
+        let running = contents
+            .trim()
+            .parse()
+            .ok()
+            .filter(|pid| command_line(*pid).is_some_and(|c| runs_jar(&c, jar)));
+        match running {
+            Some(pid) => {
+                return Some(Orphan {
+                    pid,
+                    found_by: FoundBy::PidFile,
+                })
+            }
+            None => {
+                let _ = std::fs::remove_file(&pid_file);
+            }
+        }
+    }
+
+    find_by_command_line(&server_dir(server_path), jar).map(|pid| Orphan {
+        pid,
+        found_by: FoundBy::CommandLine,
+    })
+}
+
+/// Returns the settings to attach to the server at `server_path` with, if
+/// RCON is enabled for it
+pub async fn attach_config(server_path: &Path) -> Option<AttachConfig> {
+    let properties = ServerProperties::load(server_path).await.ok()?;
+    if properties.get("enable-rcon").as_deref() != Some("true") {
+        return None;
+    }
+    let port = properties
+        .get("rcon.port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(25575);
+    let password = properties.get("rcon.password").filter(|p| !p.is_empty())?;
+
+    Some(AttachConfig {
+        rcon_address: SocketAddr::from(([127, 0, 0, 1], port)),
+        rcon_password: password,
+    })
+}
+
+/// Sends `signal` to the process with ID `pid`
+fn send_signal(pid: u32, signal: &str) -> Result<(), anyhow::Error> {
+    let status = if cfg!(windows) {
+        let mut command = std::process::Command::new("taskkill");
+        command.args(["/PID", &pid.to_string()]);
+        if signal == "KILL" {
+            command.arg("/F");
+        }
+        command.status()?
+    } else {
+        std::process::Command::new("kill")
+            .args([&format!("-{}", signal), &pid.to_string()])
+            .status()?
+    };
+    if !status.success() {
+        bail!("`kill -{} {}` failed ({})", signal, pid, status);
+    }
+    Ok(())
+}
+
+/// Waits up to `timeout` for the process with ID `pid` to exit, returning
+/// true if it did
+async fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let started = Instant::now();
+    while command_line(pid).is_some() {
+        if started.elapsed() >= timeout {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    true
+}
+
+/// Stops `orphan`, killing it if it doesn't shut down in time
+///
+/// Java runs the server's shutdown hook when it's asked to stop, so the
+/// worlds are saved unless it has to be killed.
+pub async fn terminate(orphan: &Orphan, server_path: &Path) -> Result<(), anyhow::Error> {
+    send_signal(orphan.pid, "TERM")?;
+    if !wait_for_exit(orphan.pid, TERMINATE_TIMEOUT).await {
+        log::warn!(
+            "The old server didn't stop within {} seconds; killing it",
+            TERMINATE_TIMEOUT.as_secs()
+        );
+        send_signal(orphan.pid, "KILL")?;
+        if !wait_for_exit(orphan.pid, KILL_TIMEOUT).await {
+            bail!("PID {} is still running after being killed", orphan.pid);
+        }
+    }
+
+    let _ = tokio::fs::remove_file(pid_file(server_path)).await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn jar_in_command_line() {
+        assert!(runs_jar(
+            "java -Xms1024M -Xmx1024M -jar \"server.jar\" nogui",
+            "server.jar"
+        ));
+        assert!(runs_jar("java -jar server.jar", "server.jar"));
+        assert!(!runs_jar("java -jar paper.jar nogui", "server.jar"));
+        assert!(!runs_jar("vim server.jar", "server.jar"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn finds_and_terminates() {
+        let dir = std::env::temp_dir().join(format!("mcsw-test-orphans-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let server_path = dir.join("server.jar");
+
+        // A stale PID file is cleaned up
+        std::fs::write(pid_file(&server_path), "4294967295").unwrap();
+        let stale = find(&server_path);
+        let stale_removed = !pid_file(&server_path).exists();
+
+        // Stands in for a server, with `-jar server.jar` in its command line
+        let mut process = std::process::Command::new("sh")
+            .args(["-c", "sleep 30; true", "-jar", "server.jar"])
+            .spawn()
+            .unwrap();
+        // Until the child `exec`s, it has this test's command line
+        let started = Instant::now();
+        while !command_line(process.id()).is_some_and(|c| runs_jar(&c, "server.jar"))
+            && started.elapsed() < Duration::from_secs(5)
+        {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        std::fs::write(pid_file(&server_path), process.id().to_string()).unwrap();
+        let orphan = find(&server_path);
+        let terminated = match &orphan {
+            Some(orphan) => terminate(orphan, &server_path).await,
+            None => Ok(()),
+        };
+        let _ = process.kill();
+        let _ = process.wait();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(stale, None);
+        assert!(stale_removed);
+        assert_eq!(
+            orphan,
+            Some(Orphan {
+                pid: process.id(),
+                found_by: FoundBy::PidFile
+            })
+        );
+        terminated.unwrap();
+    }
+}
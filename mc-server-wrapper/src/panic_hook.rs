@@ -0,0 +1,66 @@
+//! Cleaning up after a panic
+//!
+//! Without this a panic leaves the terminal in raw mode on the alternate
+//! screen, its message only makes it to the log file, and the Minecraft
+//! server is left running without anything reading its output.
+
+use std::{
+    panic, process,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    event::DisableBracketedPaste,
+    terminal::{disable_raw_mode, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use futures::FutureExt;
+use mc_server_wrapper_lib::{communication::ServerCommand, McServerManager};
+use tokio::sync::mpsc;
+
+use crate::{crash_reports, exit_code::ExitCode};
+
+/// How long to wait for the Minecraft server to stop before giving up
+const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Installs a panic hook that restores the terminal, prints (and, if enabled,
+/// reports) the panic, stops the Minecraft server, and then exits with a
+/// non-zero status
+///
+/// The previously installed hook is still run, so this should be called after
+/// `log_panics::init()` to keep panics in the log file.
+pub fn install(mc_server: Arc<McServerManager>, mc_cmd_sender: mpsc::Sender<ServerCommand>) {
+    let previous_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let _ = disable_raw_mode();
+        let _ = std::io::stdout().execute(DisableBracketedPaste);
+        let _ = std::io::stdout().execute(LeaveAlternateScreen);
+        previous_hook(info);
+        eprintln!("mc-server-wrapper {}", info);
+        crash_reports::report_panic(&info.to_string());
+
+        // This can't wait on anything asynchronously, so the server's state is
+        // polled instead. A lock that's held counts as the server still
+        // running, which avoids deadlocking if the panic happened while the
+        // lock was taken.
+        let running = || mc_server.running().now_or_never() != Some(false);
+        if running() {
+            eprintln!("Stopping the Minecraft server...");
+            let _ = mc_cmd_sender.try_send(ServerCommand::StopServer { forever: true });
+
+            let start = Instant::now();
+            while running() {
+                if start.elapsed() > STOP_TIMEOUT {
+                    eprintln!("Timed out waiting for the Minecraft server to stop");
+                    break;
+                }
+                thread::sleep(Duration::from_millis(250));
+            }
+        }
+
+        process::exit(ExitCode::Error as i32);
+    }));
+}
@@ -0,0 +1,151 @@
+//! Notifications for player count milestones and peaks
+//!
+//! Each milestone is announced at most once a day so that a player count
+//! hovering around one doesn't cause a flood of notifications. The all-time
+//! peak is kept in the stats store so it survives restarts.
+
+use std::{collections::BTreeSet, fmt};
+
+use time::OffsetDateTime;
+
+use crate::{config::PlayerCountNotifications, stats::Stats};
+
+/// Something worth announcing about the player count
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerCountEvent {
+    /// The player count reached one of the configured milestones
+    Milestone(u32),
+    /// The player count is higher than it's ever been
+    AllTimePeak(u32),
+}
+
+impl fmt::Display for PlayerCountEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayerCountEvent::Milestone(count) => write!(f, "{} players are online!", count),
+            PlayerCountEvent::AllTimePeak(count) => {
+                write!(f, "New record: {} players are online!", count)
+            }
+        }
+    }
+}
+
+/// Tracks the player count for milestones, peaks, and the daily summary
+#[derive(Debug)]
+pub struct PlayerCountTracker {
+    milestones: Vec<u32>,
+    all_time_peak: bool,
+    /// Milestones that have been announced today
+    reached_today: BTreeSet<u32>,
+    /// The highest player count seen today
+    day_peak: u32,
+}
+
+impl PlayerCountTracker {
+    pub fn new(config: Option<&PlayerCountNotifications>) -> Self {
+        Self {
+            milestones: config.map(|c| c.milestones.clone()).unwrap_or_default(),
+            all_time_peak: config.map(|c| c.all_time_peak).unwrap_or(false),
+            reached_today: BTreeSet::new(),
+            day_peak: 0,
+        }
+    }
+
+    /// Records the current player count, returning anything that should be
+    /// announced
+    ///
+    /// The all-time peak is updated in `stats` whether or not it's announced.
+    pub fn update(
+        &mut self,
+        count: u32,
+        stats: &mut Stats,
+        now: OffsetDateTime,
+    ) -> Vec<PlayerCountEvent> {
+        self.day_peak = self.day_peak.max(count);
+
+        let mut events = vec![];
+        // Only the highest milestone reached is announced if several were
+        // passed at once
+        let milestone = self
+            .milestones
+            .iter()
+            .copied()
+            .filter(|m| *m <= count && !self.reached_today.contains(m))
+            .max();
+        if let Some(milestone) = milestone {
+            self.reached_today
+                .extend(self.milestones.iter().filter(|m| **m <= milestone));
+            events.push(PlayerCountEvent::Milestone(milestone));
+        }
+
+        if stats.record_player_count(count, now) && self.all_time_peak {
+            // A new peak says more than a milestone at the same count
+            events.retain(|e| *e != PlayerCountEvent::Milestone(count));
+            events.push(PlayerCountEvent::AllTimePeak(count));
+        }
+
+        events
+    }
+
+    /// Returns the highest player count seen since this was last called, and
+    /// starts tracking a new day
+    pub fn take_day_peak(&mut self, current: u32) -> u32 {
+        self.reached_today.clear();
+        std::mem::replace(&mut self.day_peak, current)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::datetime;
+
+    fn tracker(milestones: &[u32]) -> PlayerCountTracker {
+        PlayerCountTracker::new(Some(&PlayerCountNotifications {
+            milestones: milestones.to_vec(),
+            all_time_peak: true,
+        }))
+    }
+
+    #[test]
+    fn milestones_once_a_day() {
+        let mut tracker = tracker(&[5, 10]);
+        let mut stats = Stats::default();
+        stats.record_player_count(20, datetime!(2023-10-01 12:00 UTC));
+        let now = datetime!(2023-10-02 12:00 UTC);
+
+        assert!(tracker.update(4, &mut stats, now).is_empty());
+        assert_eq!(
+            tracker.update(5, &mut stats, now),
+            [PlayerCountEvent::Milestone(5)]
+        );
+        assert!(tracker.update(4, &mut stats, now).is_empty());
+        assert!(tracker.update(5, &mut stats, now).is_empty());
+        assert_eq!(
+            tracker.update(12, &mut stats, now),
+            [PlayerCountEvent::Milestone(10)]
+        );
+
+        assert_eq!(tracker.take_day_peak(3), 12);
+        assert_eq!(
+            tracker.update(5, &mut stats, now),
+            [PlayerCountEvent::Milestone(5)]
+        );
+    }
+
+    #[test]
+    fn all_time_peak() {
+        let mut tracker = tracker(&[3]);
+        let mut stats = Stats::default();
+        let now = datetime!(2023-10-02 12:00 UTC);
+
+        tracker.update(2, &mut stats, now);
+        assert_eq!(
+            tracker.update(3, &mut stats, now),
+            [PlayerCountEvent::AllTimePeak(3)]
+        );
+        assert!(tracker.update(2, &mut stats, now).is_empty());
+        assert!(tracker.update(3, &mut stats, now).is_empty());
+        assert_eq!(stats.peak_players.unwrap().count, 3);
+    }
+}
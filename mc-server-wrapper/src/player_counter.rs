@@ -0,0 +1,91 @@
+//! Showing the player count in the name of a Discord voice channel
+//!
+//! Discord only allows a channel to be renamed twice every ten minutes, so
+//! renames are held back until they're allowed and only the latest name is
+//! used.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How many renames Discord allows within `RENAME_WINDOW`
+const MAX_RENAMES: usize = 2;
+const RENAME_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Returns the channel name for the given player count, or for an offline
+/// server if `online` is `None`
+pub fn channel_name(online: Option<usize>, max_players: Option<u32>) -> String {
+    match (online, max_players) {
+        (Some(online), Some(max)) => format!("🟢 Online: {}/{}", online, max),
+        (Some(online), None) => format!("🟢 Online: {}", online),
+        (None, _) => "🔴 Offline".into(),
+    }
+}
+
+/// Tracks the name of the counter channel and when it was renamed
+#[derive(Debug, Default)]
+pub struct PlayerCounter {
+    current: Option<String>,
+    renames: VecDeque<Instant>,
+}
+
+impl PlayerCounter {
+    /// Returns true if the channel should be renamed to `name` now, assuming
+    /// it will be
+    pub fn rename(&mut self, name: &str, now: Instant) -> bool {
+        if self.current.as_deref() == Some(name) {
+            return false;
+        }
+
+        while let Some(&oldest) = self.renames.front() {
+            if now.saturating_duration_since(oldest) >= RENAME_WINDOW {
+                self.renames.pop_front();
+            } else {
+                break;
+            }
+        }
+        if self.renames.len() >= MAX_RENAMES {
+            return false;
+        }
+
+        self.renames.push_back(now);
+        self.current = Some(name.to_string());
+        true
+    }
+
+    /// Forgets the channel's name so it's renamed again, such as after
+    /// reconnecting to Discord
+    pub fn reset(&mut self) {
+        self.current = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(channel_name(Some(7), Some(20)), "🟢 Online: 7/20");
+        assert_eq!(channel_name(Some(0), None), "🟢 Online: 0");
+        assert_eq!(channel_name(None, Some(20)), "🔴 Offline");
+    }
+
+    #[test]
+    fn rate_limited() {
+        let start = Instant::now();
+        let mut counter = PlayerCounter::default();
+
+        assert!(counter.rename("a", start));
+        assert!(!counter.rename("a", start + Duration::from_secs(1)));
+        assert!(counter.rename("b", start + Duration::from_secs(2)));
+        assert!(!counter.rename("c", start + Duration::from_secs(3)));
+        // The first rename has left the window
+        assert!(counter.rename("c", start + RENAME_WINDOW));
+        assert!(!counter.rename("d", start + RENAME_WINDOW + Duration::from_secs(1)));
+
+        counter.reset();
+        assert!(counter.rename("c", start + RENAME_WINDOW * 2));
+    }
+}
@@ -0,0 +1,134 @@
+//! Per-player rules for how joins and leaves are announced on Discord
+
+use crate::config::{PlayerEvent, PlayerNotification};
+
+/// How a join or leave should be announced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Announcement {
+    /// Post the usual message, prefixed with the given mentions
+    Post { mentions: Vec<String> },
+    /// Don't post anything
+    Silenced,
+}
+
+impl Announcement {
+    /// Formats `msg` with any mentions, returning `None` if it shouldn't be
+    /// sent
+    pub fn format(&self, msg: &str) -> Option<String> {
+        match self {
+            Announcement::Post { mentions } if mentions.is_empty() => Some(msg.to_string()),
+            Announcement::Post { mentions } => Some(format!("{} {}", mentions.join(" "), msg)),
+            Announcement::Silenced => None,
+        }
+    }
+}
+
+/// The configured player notification rules
+#[derive(Debug, Default)]
+pub struct PlayerNotifications {
+    rules: Vec<PlayerNotification>,
+}
+
+impl PlayerNotifications {
+    pub fn new(rules: &[PlayerNotification]) -> Self {
+        Self {
+            rules: rules.to_vec(),
+        }
+    }
+
+    /// Decides how `event` for the player `name` should be announced
+    ///
+    /// Silencing takes precedence if several rules match.
+    pub fn announcement(&self, name: &str, event: PlayerEvent) -> Announcement {
+        let mut mentions = vec![];
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| rule.player.eq_ignore_ascii_case(name) && rule.events.contains(&event))
+        {
+            if rule.silence {
+                return Announcement::Silenced;
+            }
+
+            if let Some(mention) = &rule.discord_mention {
+                if !mentions.contains(mention) {
+                    mentions.push(mention.clone());
+                }
+            }
+        }
+
+        Announcement::Post { mentions }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(player: &str, events: &[PlayerEvent]) -> PlayerNotification {
+        PlayerNotification {
+            player: player.into(),
+            events: events.to_vec(),
+            discord_mention: None,
+            silence: false,
+        }
+    }
+
+    #[test]
+    fn mentions() {
+        let notifications = PlayerNotifications::new(&[
+            PlayerNotification {
+                discord_mention: Some("<@&1>".into()),
+                ..rule("Cldfire", &[PlayerEvent::Join])
+            },
+            PlayerNotification {
+                discord_mention: Some("<@2>".into()),
+                ..rule("cldfire", &[PlayerEvent::Join, PlayerEvent::Leave])
+            },
+        ]);
+
+        assert_eq!(
+            notifications
+                .announcement("Cldfire", PlayerEvent::Join)
+                .format("_Cldfire joined the game_")
+                .unwrap(),
+            "<@&1> <@2> _Cldfire joined the game_"
+        );
+        assert_eq!(
+            notifications.announcement("Cldfire", PlayerEvent::Leave),
+            Announcement::Post {
+                mentions: vec!["<@2>".into()]
+            }
+        );
+        assert_eq!(
+            notifications
+                .announcement("Someone", PlayerEvent::Join)
+                .format("_Someone joined the game_")
+                .unwrap(),
+            "_Someone joined the game_"
+        );
+    }
+
+    #[test]
+    fn silenced() {
+        let notifications = PlayerNotifications::new(&[
+            PlayerNotification {
+                silence: true,
+                ..rule("CldfireAlt", &[PlayerEvent::Join, PlayerEvent::Leave])
+            },
+            PlayerNotification {
+                discord_mention: Some("<@&1>".into()),
+                ..rule("CldfireAlt", &[PlayerEvent::Join])
+            },
+        ]);
+
+        assert_eq!(
+            notifications.announcement("CldfireAlt", PlayerEvent::Join),
+            Announcement::Silenced
+        );
+        assert!(notifications
+            .announcement("CldfireAlt", PlayerEvent::Leave)
+            .format("_CldfireAlt left the game_")
+            .is_none());
+    }
+}
@@ -0,0 +1,267 @@
+//! Keeping track of the players online on the Minecraft server
+//!
+//! Who's online comes from the server's status, which `McServerManager`
+//! maintains from the server's output, so the two can't disagree. The tracker
+//! adds what the wrapper needs to know about those players on top of that,
+//! and is kept up-to-date by the main loop. Anything else that needs to know
+//! who's online can `subscribe` to get a handle that sees changes as they
+//! happen.
+
+use std::collections::{BTreeMap, HashMap};
+
+use log::debug;
+use mc_server_wrapper_lib::{parse::ListedPlayer, status::McServerStatus};
+use time::OffsetDateTime;
+use tokio::sync::watch;
+
+/// Player name -> info
+pub type OnlinePlayers = BTreeMap<String, OnlinePlayerInfo>;
+
+/// Info about online players
+#[derive(Debug, Clone)]
+pub struct OnlinePlayerInfo {
+    pub joined_at: OffsetDateTime,
+}
+
+impl Default for OnlinePlayerInfo {
+    fn default() -> Self {
+        Self {
+            joined_at: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+/// Tracks the players online on the server
+#[derive(Debug)]
+pub struct PlayerTracker {
+    /// The server's status, which has the names of the players online
+    status: watch::Receiver<McServerStatus>,
+    /// The sessions of players the main loop has seen join
+    sessions: OnlinePlayers,
+    /// Sessions saved by a previous run of the wrapper, used for players the
+    /// first `list` finds online
+    restored: OnlinePlayers,
+    /// The UUIDs of players that have joined since the wrapper started
+    uuids: HashMap<String, String>,
+}
+
+impl PlayerTracker {
+    /// Tracks the players online according to `status`, with the given
+    /// sessions from a previous run
+    pub fn new(status: watch::Receiver<McServerStatus>, restored: OnlinePlayers) -> Self {
+        Self {
+            status,
+            sessions: OnlinePlayers::new(),
+            restored,
+            uuids: HashMap::new(),
+        }
+    }
+
+    /// Returns the players that are online
+    pub fn players(&self) -> OnlinePlayers {
+        match self.status.borrow().players() {
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    let info = self.sessions.get(name).cloned().unwrap_or_default();
+                    (name.clone(), info)
+                })
+                .collect(),
+            None => OnlinePlayers::new(),
+        }
+    }
+
+    /// Returns how many players are online
+    pub fn len(&self) -> usize {
+        self.status.borrow().players().map_or(0, |names| names.len())
+    }
+
+    /// Returns a handle that sees the server's status, including the players
+    /// online, and is notified when it changes
+    pub fn subscribe(&self) -> watch::Receiver<McServerStatus> {
+        self.status.clone()
+    }
+
+    /// Returns the UUIDs of players that have joined, by name
+    pub fn uuids(&self) -> &HashMap<String, String> {
+        &self.uuids
+    }
+
+    /// Records the server authenticating `name` as the player with `uuid`
+    ///
+    /// This happens right before they join.
+    pub fn authenticated(&mut self, name: String, uuid: String) {
+        self.uuids.insert(name, uuid);
+    }
+
+    /// Records `name` joining now
+    pub fn joined(&mut self, name: String) {
+        self.restored.remove(&name);
+        self.sessions.insert(name, OnlinePlayerInfo::default());
+    }
+
+    /// Records `name` leaving, returning their session if they were online
+    pub fn left(&mut self, name: &str) -> Option<OnlinePlayerInfo> {
+        self.sessions.remove(name)
+    }
+
+    /// Records everyone leaving as the server stopped, returning their
+    /// sessions
+    ///
+    /// Sessions from a previous run are forgotten too.
+    pub fn clear(&mut self) -> OnlinePlayers {
+        self.restored.clear();
+        std::mem::take(&mut self.sessions)
+    }
+
+    /// Records the response to a `list` command, returning true if it had
+    /// players joining or leaving that weren't already recorded
+    ///
+    /// Players that weren't known to be online are given their session from
+    /// a previous run if they have one. Those sessions are only used for the
+    /// first `list`.
+    pub fn reconcile(&mut self, listed: &[ListedPlayer]) -> bool {
+        for player in listed {
+            if let Some(uuid) = &player.uuid {
+                self.uuids.insert(player.name.clone(), uuid.clone());
+            }
+        }
+
+        let mut restored = std::mem::take(&mut self.restored);
+        let before = self.sessions.len();
+        self.sessions
+            .retain(|name, _| listed.iter().any(|p| &p.name == name));
+        let mut changed = self.sessions.len() != before;
+
+        for player in listed {
+            if !self.sessions.contains_key(&player.name) {
+                self.sessions.insert(
+                    player.name.clone(),
+                    restored.remove(&player.name).unwrap_or_default(),
+                );
+                changed = true;
+            }
+        }
+
+        if changed {
+            debug!("Corrected the list of online players from `list` output");
+        }
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn listed(names: &[&str]) -> Vec<ListedPlayer> {
+        names
+            .iter()
+            .map(|name| ListedPlayer {
+                name: name.to_string(),
+                uuid: None,
+            })
+            .collect()
+    }
+
+    fn running(names: &[&str]) -> McServerStatus {
+        McServerStatus::Running {
+            since: OffsetDateTime::now_utc(),
+            players: names.iter().map(|name| name.to_string()).collect(),
+            pid: None,
+        }
+    }
+
+    fn tracker(
+        status: McServerStatus,
+        restored: OnlinePlayers,
+    ) -> (watch::Sender<McServerStatus>, PlayerTracker) {
+        let (sender, receiver) = watch::channel(status);
+        (sender, PlayerTracker::new(receiver, restored))
+    }
+
+    #[test]
+    fn join_and_leave() {
+        let (status, mut tracker) = tracker(running(&[]), OnlinePlayers::new());
+        let mut changes = tracker.subscribe();
+
+        status.send_replace(running(&["Cldfire"]));
+        tracker.joined("Cldfire".into());
+        assert!(changes.has_changed().unwrap());
+        changes.borrow_and_update();
+        assert!(tracker.players().contains_key("Cldfire"));
+
+        assert!(tracker.left("Steve").is_none());
+        status.send_replace(running(&[]));
+        assert!(tracker.left("Cldfire").is_some());
+        assert!(changes.has_changed().unwrap());
+        assert_eq!(tracker.len(), 0);
+    }
+
+    #[test]
+    fn follows_status() {
+        let (status, mut tracker) = tracker(running(&["Cldfire"]), OnlinePlayers::new());
+        tracker.joined("Steve".into());
+
+        // Who's online always matches the server's status
+        assert_eq!(tracker.len(), 1);
+        assert_eq!(tracker.players().keys().collect::<Vec<_>>(), ["Cldfire"]);
+
+        status.send_replace(McServerStatus::Stopping);
+        assert_eq!(tracker.len(), 0);
+        assert!(tracker.players().is_empty());
+    }
+
+    #[test]
+    fn clear() {
+        let (_status, mut tracker) = tracker(running(&[]), OnlinePlayers::new());
+        tracker.joined("Cldfire".into());
+        tracker.joined("Steve".into());
+
+        let left = tracker.clear();
+        assert_eq!(left.keys().collect::<Vec<_>>(), ["Cldfire", "Steve"]);
+        assert!(tracker.left("Cldfire").is_none());
+    }
+
+    #[test]
+    fn uuids() {
+        let (_status, mut tracker) = tracker(running(&[]), OnlinePlayers::new());
+        tracker.authenticated("Cldfire".into(), "361e5fb3".into());
+        tracker.reconcile(&[ListedPlayer {
+            name: "Steve".into(),
+            uuid: Some("8667ba71".into()),
+        }]);
+
+        let uuids = tracker.uuids();
+        assert_eq!(uuids["Cldfire"], "361e5fb3");
+        assert_eq!(uuids["Steve"], "8667ba71");
+        assert!(!uuids.contains_key("Alex"));
+    }
+
+    #[test]
+    fn reconcile() {
+        let joined_at = datetime!(2023-10-01 12:00 UTC);
+        let mut restored = OnlinePlayers::new();
+        restored.insert("Alex".into(), OnlinePlayerInfo { joined_at });
+        restored.insert("Herobrine".into(), OnlinePlayerInfo { joined_at });
+        let (status, mut tracker) = tracker(running(&["Cldfire", "Steve"]), restored);
+        tracker.joined("Cldfire".into());
+        tracker.joined("Steve".into());
+
+        status.send_replace(running(&["Cldfire", "Alex"]));
+        assert!(tracker.reconcile(&listed(&["Cldfire", "Alex"])));
+        let players = tracker.players();
+        assert_eq!(players.keys().collect::<Vec<_>>(), ["Alex", "Cldfire"]);
+        assert_eq!(players["Alex"].joined_at, joined_at);
+        assert!(tracker.left("Steve").is_none());
+
+        assert!(!tracker.reconcile(&listed(&["Cldfire", "Alex"])));
+        // Sessions from a previous run are only used for the first `list`
+        status.send_replace(running(&["Cldfire", "Alex", "Herobrine"]));
+        assert!(tracker.reconcile(&listed(&["Cldfire", "Alex", "Herobrine"])));
+        assert_ne!(tracker.players()["Herobrine"].joined_at, joined_at);
+    }
+}
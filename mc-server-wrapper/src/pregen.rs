@@ -0,0 +1,513 @@
+//! Pre-generating the chunks around a point by driving the server console
+//!
+//! If the Chunky plugin or mod is installed it does the work, and its
+//! progress messages are followed. Otherwise the area is force loaded a batch
+//! of chunks at a time with `forceload`, which generates them, and unloaded
+//! again at the next step.
+//!
+//! The server's TPS is queried at every step (with `tps` on servers with
+//! plugins, and `tick query` on vanilla 1.20.3+). Generation is paused while
+//! it's low so players aren't affected.
+
+use std::{collections::VecDeque, fs, path::Path, time::Duration};
+
+use crate::addons::addon_dirs;
+
+/// How often a step is taken
+pub const STEP_INTERVAL: Duration = Duration::from_secs(5);
+/// The width of the square of chunks force loaded at each step
+const BATCH_WIDTH: i32 = 8;
+/// Generation is paused below this TPS
+const PAUSE_TPS: f32 = 15.0;
+/// Paused generation is continued at or above this TPS
+const RESUME_TPS: f32 = 18.0;
+
+/// How the chunks are generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Vanilla,
+    Chunky,
+}
+
+impl Method {
+    /// Uses Chunky if it's installed in the server at `server_dir`
+    pub fn detect(server_dir: &Path) -> Self {
+        let has_chunky = addon_dirs(server_dir)
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .any(|entry| {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                name.starts_with("chunky") && name.ends_with(".jar")
+            });
+
+        if has_chunky {
+            Method::Chunky
+        } else {
+            Method::Vanilla
+        }
+    }
+}
+
+/// A rectangle of chunks, in chunk coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Area {
+    from: (i32, i32),
+    to: (i32, i32),
+}
+
+impl Area {
+    fn command(&self, action: &str) -> String {
+        // `forceload` takes block coordinates
+        format!(
+            "forceload {} {} {} {} {}",
+            action,
+            self.from.0 * 16,
+            self.from.1 * 16,
+            self.to.0 * 16,
+            self.to.1 * 16
+        )
+    }
+}
+
+/// What a line of console output meant for pre-generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    /// The line was a response to a command run for pre-generation and
+    /// doesn't need to be shown
+    Consumed,
+    /// Pre-generation finished
+    Finished,
+}
+
+/// A pre-generation task
+#[derive(Debug)]
+pub struct Pregen {
+    method: Method,
+    /// The command that queries the server's TPS, unless the server doesn't
+    /// have one
+    tps_command: Option<&'static str>,
+    /// The radius in blocks and the center in block coordinates
+    radius: u32,
+    center: (i32, i32),
+    /// The areas left to force load (vanilla only)
+    areas: VecDeque<Area>,
+    /// The area that's currently force loaded (vanilla only)
+    loaded: Option<Area>,
+    total_chunks: u64,
+    generated_chunks: u64,
+    /// The completion reported by Chunky
+    chunky_percent: f32,
+    tps: Option<f32>,
+    paused: bool,
+    /// The number of TPS queries whose responses haven't been seen
+    pending_tps_queries: u32,
+}
+
+impl Pregen {
+    /// Sets up pre-generation of the chunks within `radius` blocks of
+    /// `center` on the server at `server_dir`
+    pub fn new(server_dir: &Path, radius: u32, center: (i32, i32)) -> Self {
+        let tps_command = if server_dir.join("plugins").is_dir() {
+            "tps"
+        } else {
+            "tick query"
+        };
+
+        Self::with_method(Method::detect(server_dir), tps_command, radius, center)
+    }
+
+    fn with_method(
+        method: Method,
+        tps_command: &'static str,
+        radius: u32,
+        center: (i32, i32),
+    ) -> Self {
+        let radius_chunks = (radius as i32 + 15) / 16;
+        let (center_x, center_z) = (center.0.div_euclid(16), center.1.div_euclid(16));
+        let (min_x, max_x) = (center_x - radius_chunks, center_x + radius_chunks);
+        let (min_z, max_z) = (center_z - radius_chunks, center_z + radius_chunks);
+
+        let mut areas = VecDeque::new();
+        if method == Method::Vanilla {
+            for x in (min_x..=max_x).step_by(BATCH_WIDTH as usize) {
+                for z in (min_z..=max_z).step_by(BATCH_WIDTH as usize) {
+                    areas.push_back(Area {
+                        from: (x, z),
+                        to: (
+                            (x + BATCH_WIDTH - 1).min(max_x),
+                            (z + BATCH_WIDTH - 1).min(max_z),
+                        ),
+                    });
+                }
+            }
+        }
+        let width = (max_x - min_x + 1) as u64;
+
+        Self {
+            method,
+            tps_command: Some(tps_command),
+            radius,
+            center,
+            areas,
+            loaded: None,
+            total_chunks: width * width,
+            generated_chunks: 0,
+            chunky_percent: 0.0,
+            tps: None,
+            paused: false,
+            pending_tps_queries: 0,
+        }
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// Returns the commands that start pre-generation
+    pub fn start(&self) -> Vec<String> {
+        match self.method {
+            Method::Chunky => vec![
+                format!("chunky center {} {}", self.center.0, self.center.1),
+                format!("chunky radius {}", self.radius),
+                "chunky start".into(),
+            ],
+            Method::Vanilla => vec![],
+        }
+    }
+
+    /// Returns the commands to run for the next step
+    pub fn step(&mut self) -> Vec<String> {
+        let mut commands = vec![];
+
+        match self.tps {
+            Some(tps) if tps < PAUSE_TPS && !self.paused => {
+                self.paused = true;
+                if self.method == Method::Chunky {
+                    commands.push("chunky pause".into());
+                }
+            }
+            Some(tps) if tps >= RESUME_TPS && self.paused => {
+                self.paused = false;
+                if self.method == Method::Chunky {
+                    commands.push("chunky continue".into());
+                }
+            }
+            _ => {}
+        }
+
+        if self.method == Method::Vanilla {
+            // The last area has had a step's worth of time to generate
+            if let Some(loaded) = self.loaded.take() {
+                commands.push(loaded.command("remove"));
+            }
+            if !self.paused {
+                if let Some(area) = self.areas.pop_front() {
+                    commands.push(area.command("add"));
+                    self.loaded = Some(area);
+                }
+            }
+        }
+
+        if let Some(tps_command) = self.tps_command {
+            self.pending_tps_queries += 1;
+            commands.push(tps_command.into());
+        }
+        commands
+    }
+
+    /// Returns true if generation is paused because the TPS is low
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Returns the completion from 0 to 100
+    pub fn percent(&self) -> u32 {
+        let percent = match self.method {
+            Method::Chunky => self.chunky_percent,
+            Method::Vanilla => {
+                self.generated_chunks as f32 / self.total_chunks.max(1) as f32 * 100.0
+            }
+        };
+
+        (percent as u32).min(100)
+    }
+
+    /// Returns true if the vanilla method has no areas left to load or unload
+    ///
+    /// Chunky reports when it's finished instead.
+    pub fn finished(&self) -> bool {
+        self.method == Method::Vanilla && self.areas.is_empty() && self.loaded.is_none()
+    }
+
+    /// Follows a line of console output, returning what it meant for
+    /// pre-generation (if anything)
+    pub fn handle_output(&mut self, msg: &str) -> Option<Output> {
+        let msg = strip_formatting(msg);
+
+        if let Some(tps) = parse_tps(&msg) {
+            self.tps = Some(tps);
+            if self.pending_tps_queries > 0 {
+                self.pending_tps_queries -= 1;
+                return Some(Output::Consumed);
+            }
+            return None;
+        }
+        if msg.starts_with("Unknown or incomplete command") && self.pending_tps_queries > 0 {
+            // Older vanilla servers don't have `tick query`, so generation
+            // can't be throttled
+            self.tps_command = None;
+            self.pending_tps_queries = 0;
+            return Some(Output::Consumed);
+        }
+
+        match self.method {
+            Method::Vanilla => {
+                if let Some(count) = parse_forceload_added(&msg) {
+                    self.generated_chunks += count;
+                    Some(Output::Consumed)
+                } else if msg.starts_with("Unmarked ") && msg.ends_with(" for force loading") {
+                    Some(Output::Consumed)
+                } else {
+                    None
+                }
+            }
+            Method::Chunky => {
+                let percent = parse_chunky_percent(&msg)?;
+                self.chunky_percent = percent;
+                if msg.contains("Task finished") {
+                    Some(Output::Finished)
+                } else {
+                    Some(Output::Consumed)
+                }
+            }
+        }
+    }
+
+    /// Returns the commands that stop pre-generation
+    pub fn cancel(&mut self) -> Vec<String> {
+        match self.method {
+            Method::Chunky => vec!["chunky cancel".into(), "chunky confirm".into()],
+            Method::Vanilla => {
+                self.areas.clear();
+                self.loaded
+                    .take()
+                    .map(|a| a.command("remove"))
+                    .into_iter()
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Parses the arguments to the `pregen` console command, `<radius> [x z]`,
+/// into the radius and center
+pub fn parse_args(args: &str) -> Option<(u32, (i32, i32))> {
+    let args: Vec<_> = args.split_whitespace().collect();
+    let (radius, center) = match args.as_slice() {
+        [radius] => (radius, (0, 0)),
+        [radius, x, z] => (radius, (x.parse().ok()?, z.parse().ok()?)),
+        _ => return None,
+    };
+
+    Some((radius.parse().ok().filter(|r| *r > 0)?, center))
+}
+
+/// Removes Minecraft formatting codes (like `§a`) from `text`
+fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Parses the response to `tps` (`TPS from last 1m, 5m, 15m: 19.9, 20.0,
+/// 20.0`) or the average tick time in the response to `tick query`
+/// (`Average time per tick: 12.3ms (Target: 50.0ms)`) into the current TPS
+fn parse_tps(msg: &str) -> Option<f32> {
+    if let Some(values) = msg.split_once("TPS from last 1m, 5m, 15m: ") {
+        // Paper marks values above 20 with a `*`
+        let tps = values.1.split(',').next()?.trim().trim_start_matches('*');
+        return tps.parse().ok();
+    }
+
+    let rest = msg.split_once("Average time per tick: ")?.1;
+    let (mspt, rest) = rest.split_once("ms")?;
+    let target = rest
+        .split_once("Target: ")
+        .and_then(|(_, target)| target.split_once("ms"))
+        .and_then(|(target, _)| target.parse::<f32>().ok())
+        .unwrap_or(50.0);
+    let mspt: f32 = mspt.trim().parse().ok()?;
+
+    Some((1000.0 / mspt.max(target)).min(1000.0 / target))
+}
+
+/// Parses the number of chunks in the response to `forceload add`, like
+/// `Marked 64 chunks in minecraft:overworld from [0, 0] to [7, 7] to be force
+/// loaded` or `Marked chunk [0, 0] in minecraft:overworld to be force loaded`
+fn parse_forceload_added(msg: &str) -> Option<u64> {
+    let rest = msg.strip_prefix("Marked ")?;
+    if !rest.ends_with(" to be force loaded") {
+        return None;
+    }
+
+    if rest.starts_with("chunk ") {
+        Some(1)
+    } else {
+        rest.split_once(' ')?.0.parse().ok()
+    }
+}
+
+/// Parses the completion in Chunky's progress messages, like `[Chunky] Task
+/// running for minecraft:overworld. Processed: 384 chunks (0.60%), ETA:
+/// 0:05:12, Rate: 204.1 cps, Current: -20, 11`
+fn parse_chunky_percent(msg: &str) -> Option<f32> {
+    if !msg.contains("Task running for") && !msg.contains("Task finished for") {
+        return None;
+    }
+
+    let percent = msg.split_once(" chunks (")?.1.split_once("%)")?.0;
+    percent.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn args() {
+        assert_eq!(parse_args("1000"), Some((1000, (0, 0))));
+        assert_eq!(parse_args("500 100 -200"), Some((500, (100, -200))));
+        assert_eq!(parse_args("0"), None);
+        assert_eq!(parse_args("500 100"), None);
+        assert_eq!(parse_args("far"), None);
+    }
+
+    #[test]
+    fn tps() {
+        assert_eq!(
+            parse_tps(&strip_formatting(
+                "§6TPS from last 1m, 5m, 15m: §a*20.0, §a19.5, §a19.9"
+            )),
+            Some(20.0)
+        );
+        assert_eq!(
+            parse_tps("Average time per tick: 100.0ms (Target: 50.0ms)"),
+            Some(10.0)
+        );
+        assert_eq!(
+            parse_tps("Average time per tick: 12.3ms (Target: 50.0ms)"),
+            Some(20.0)
+        );
+        assert_eq!(
+            parse_tps("There are 0 of a max of 20 players online:"),
+            None
+        );
+    }
+
+    #[test]
+    fn vanilla() {
+        let mut pregen = Pregen::with_method(Method::Vanilla, "tick query", 100, (0, 0));
+        // -7..=7 chunks in each direction
+        assert_eq!(pregen.total_chunks, 225);
+        assert_eq!(pregen.areas.len(), 4);
+
+        assert_eq!(pregen.step(), ["forceload add -112 -112 0 0", "tick query"]);
+        assert_eq!(
+            pregen.handle_output(
+                "Marked 64 chunks in minecraft:overworld from [-7, -7] to [0, 0] to be force loaded"
+            ),
+            Some(Output::Consumed)
+        );
+        assert_eq!(pregen.percent(), 28);
+        assert_eq!(
+            pregen.handle_output("Average time per tick: 12.3ms (Target: 50.0ms)"),
+            Some(Output::Consumed)
+        );
+
+        assert_eq!(
+            pregen.step(),
+            [
+                "forceload remove -112 -112 0 0",
+                "forceload add -112 16 0 112",
+                "tick query"
+            ]
+        );
+    }
+
+    #[test]
+    fn vanilla_finishes() {
+        let mut pregen = Pregen::with_method(Method::Vanilla, "tps", 16, (0, 0));
+        assert_eq!(pregen.areas.len(), 1);
+
+        pregen.step();
+        assert!(!pregen.finished());
+        assert_eq!(pregen.step(), ["forceload remove -16 -16 16 16", "tps"]);
+        assert_eq!(
+            pregen.handle_output(
+                "Unmarked 9 chunks in minecraft:overworld from [-1, -1] to [1, 1] for force loading"
+            ),
+            Some(Output::Consumed)
+        );
+        assert!(pregen.finished());
+    }
+
+    #[test]
+    fn tps_unsupported() {
+        let mut pregen = Pregen::with_method(Method::Vanilla, "tick query", 16, (0, 0));
+        assert_eq!(pregen.step().len(), 2);
+
+        assert_eq!(
+            pregen.handle_output("Unknown or incomplete command, see below for error"),
+            Some(Output::Consumed)
+        );
+        assert_eq!(pregen.step(), ["forceload remove -16 -16 16 16"]);
+    }
+
+    #[test]
+    fn throttling() {
+        let mut pregen = Pregen::with_method(Method::Chunky, "tps", 1000, (0, 0));
+        assert_eq!(
+            pregen.start(),
+            ["chunky center 0 0", "chunky radius 1000", "chunky start"]
+        );
+
+        pregen.handle_output("TPS from last 1m, 5m, 15m: 12.0, 19.0, 20.0");
+        assert_eq!(pregen.step(), ["chunky pause", "tps"]);
+        assert!(pregen.paused());
+
+        pregen.handle_output("TPS from last 1m, 5m, 15m: 16.0, 19.0, 20.0");
+        assert_eq!(pregen.step(), ["tps"]);
+        pregen.handle_output("TPS from last 1m, 5m, 15m: 19.0, 19.0, 20.0");
+        assert_eq!(pregen.step(), ["chunky continue", "tps"]);
+    }
+
+    #[test]
+    fn chunky_progress() {
+        let mut pregen = Pregen::with_method(Method::Chunky, "tps", 1000, (0, 0));
+
+        assert_eq!(
+            pregen.handle_output(
+                "[Chunky] Task running for minecraft:overworld. Processed: 384 chunks (42.60%), \
+                ETA: 0:05:12, Rate: 204.1 cps, Current: -20, 11"
+            ),
+            Some(Output::Consumed)
+        );
+        assert_eq!(pregen.percent(), 42);
+        assert_eq!(
+            pregen.handle_output(
+                "[Chunky] Task finished for minecraft:overworld. Processed: 7569 chunks (100.00%), \
+                Total time: 0:00:47"
+            ),
+            Some(Output::Finished)
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! Cycling the Discord bot's status between configured templates
+//!
+//! Templates can use these placeholders:
+//!
+//! * `{players}`: the names of the players online, or "nobody"
+//! * `{player_count}`: how many players are online
+//! * `{max_players}`: the server's player limit
+//! * `{uptime}`: how long the server has been up
+
+use std::collections::BTreeMap;
+
+use time::Duration;
+
+use crate::{
+    discord::util::{format_online_players, OnlinePlayerFormat},
+    player_tracker::OnlinePlayerInfo,
+    ui::make_session_time_string,
+};
+
+/// Discord doesn't show statuses longer than this
+const MAX_STATUS_LEN: usize = 128;
+
+/// What a status template can show
+#[derive(Debug)]
+pub struct PresenceInfo<'a> {
+    pub online_players: &'a BTreeMap<String, OnlinePlayerInfo>,
+    pub max_players: Option<u32>,
+    /// How long the server has been up, if it's up
+    pub uptime: Option<Duration>,
+}
+
+/// The configured status templates, and which one is being shown
+#[derive(Debug, Default)]
+pub struct PresenceCycle {
+    templates: Vec<String>,
+    current: usize,
+}
+
+impl PresenceCycle {
+    pub fn new(templates: &[String]) -> Self {
+        Self {
+            templates: templates.to_vec(),
+            current: 0,
+        }
+    }
+
+    /// Returns the status to show while the server is running
+    ///
+    /// The list of online players is shown if there aren't any templates.
+    pub fn status(&self, info: &PresenceInfo<'_>) -> String {
+        let template = match self.templates.get(self.current) {
+            Some(template) => template,
+            None => {
+                return format_online_players(info.online_players, OnlinePlayerFormat::BotStatus)
+            }
+        };
+
+        let players = if info.online_players.is_empty() {
+            "nobody".to_string()
+        } else {
+            info.online_players
+                .keys()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let status = template
+            .replace("{players}", &players)
+            .replace("{player_count}", &info.online_players.len().to_string())
+            .replace(
+                "{max_players}",
+                &info
+                    .max_players
+                    .map(|max| max.to_string())
+                    .unwrap_or_else(|| "?".into()),
+            )
+            .replace(
+                "{uptime}",
+                &info
+                    .uptime
+                    .map(make_session_time_string)
+                    .unwrap_or_else(|| "0m".into()),
+            );
+
+        if status.chars().count() > MAX_STATUS_LEN {
+            let mut truncated: String = status.chars().take(MAX_STATUS_LEN - 3).collect();
+            truncated.push_str("...");
+            truncated
+        } else {
+            status
+        }
+    }
+
+    /// Moves on to the next template, returning whether the status changed
+    pub fn advance(&mut self) -> bool {
+        if self.templates.len() < 2 {
+            return false;
+        }
+
+        self.current = (self.current + 1) % self.templates.len();
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn templates() {
+        let mut online_players = BTreeMap::new();
+        online_players.insert("Cldfire".to_string(), OnlinePlayerInfo::default());
+        online_players.insert("Steve".to_string(), OnlinePlayerInfo::default());
+        let info = PresenceInfo {
+            online_players: &online_players,
+            max_players: Some(20),
+            uptime: Some(Duration::minutes(125)),
+        };
+        let mut presence = PresenceCycle::new(&[
+            "Minecraft with {players}".into(),
+            "with {player_count}/{max_players} players".into(),
+            "for {uptime}".into(),
+        ]);
+
+        assert_eq!(presence.status(&info), "Minecraft with Cldfire, Steve");
+        assert!(presence.advance());
+        assert_eq!(presence.status(&info), "with 2/20 players");
+        assert!(presence.advance());
+        assert_eq!(presence.status(&info), "for 2h 5m");
+        assert!(presence.advance());
+        assert_eq!(presence.status(&info), "Minecraft with Cldfire, Steve");
+    }
+
+    #[test]
+    fn no_templates() {
+        let online_players = BTreeMap::new();
+        let info = PresenceInfo {
+            online_players: &online_players,
+            max_players: None,
+            uptime: None,
+        };
+        let mut presence = PresenceCycle::new(&[]);
+
+        assert!(!presence.advance());
+        assert_eq!(presence.status(&info), "Minecraft with nobody");
+    }
+
+    #[test]
+    fn truncates() {
+        let online_players = BTreeMap::new();
+        let info = PresenceInfo {
+            online_players: &online_players,
+            max_players: None,
+            uptime: None,
+        };
+        let presence = PresenceCycle::new(&["a".repeat(200)]);
+
+        assert_eq!(presence.status(&info).chars().count(), MAX_STATUS_LEN);
+    }
+}
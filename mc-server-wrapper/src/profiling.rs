@@ -0,0 +1,125 @@
+//! Collecting performance reports from the server
+//!
+//! The `profile` console command runs spark's profiler if spark is installed,
+//! or Paper's timings otherwise. Both upload their report and print a link to
+//! it in the console, which is picked out with `find_report_url`.
+
+use std::{fmt, fs, path::Path, time::Duration};
+
+use crate::addons;
+
+/// How long to wait for a report to be uploaded after it's been collected
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Hosts that reports are uploaded to
+const REPORT_URL_PREFIXES: &[&str] = &[
+    "https://spark.lucko.me/",
+    "https://timings.aikar.co/",
+    "https://timin.gs/",
+];
+
+/// A tool that can produce a performance report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profiler {
+    /// The spark plugin / mod
+    Spark,
+    /// Paper's built-in timings
+    Timings,
+}
+
+impl Profiler {
+    /// Picks the profiler to use for the server in `server_dir`
+    pub fn detect(server_dir: &Path) -> Self {
+        let has_spark = addons::addon_dirs(server_dir)
+            .iter()
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .any(|entry| {
+                let name = entry.file_name().to_string_lossy().to_lowercase();
+                name.starts_with("spark") && name.ends_with(".jar")
+            });
+
+        if has_spark {
+            Self::Spark
+        } else {
+            Self::Timings
+        }
+    }
+
+    /// The console command that produces a report covering `duration`
+    ///
+    /// Timings always report on the data collected since they were last reset,
+    /// so `duration` only applies to spark.
+    pub fn command(self, duration: Duration) -> String {
+        match self {
+            Self::Spark => format!("spark profiler start --timeout {}", duration.as_secs()),
+            Self::Timings => "timings report".into(),
+        }
+    }
+
+    /// How long to wait for a report before giving up on it
+    pub fn timeout(self, duration: Duration) -> Duration {
+        match self {
+            Self::Spark => duration + UPLOAD_TIMEOUT,
+            Self::Timings => UPLOAD_TIMEOUT,
+        }
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Spark => f.write_str("spark profiler"),
+            Self::Timings => f.write_str("timings"),
+        }
+    }
+}
+
+/// Returns the link to an uploaded report in a console message, if any
+pub fn find_report_url(msg: &str) -> Option<&str> {
+    let start = REPORT_URL_PREFIXES
+        .iter()
+        .filter_map(|prefix| msg.find(prefix))
+        .min()?;
+
+    msg[start..].split_whitespace().next()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spark_url() {
+        assert_eq!(
+            find_report_url("https://spark.lucko.me/abc123XYZ"),
+            Some("https://spark.lucko.me/abc123XYZ")
+        );
+    }
+
+    #[test]
+    fn timings_url() {
+        assert_eq!(
+            find_report_url("View Timings Report: https://timings.aikar.co/?id=0123abcd"),
+            Some("https://timings.aikar.co/?id=0123abcd")
+        );
+        assert_eq!(find_report_url("Preparing Timings Report..."), None);
+    }
+
+    #[test]
+    fn commands() {
+        assert_eq!(
+            Profiler::Spark.command(Duration::from_secs(30)),
+            "spark profiler start --timeout 30"
+        );
+        assert_eq!(
+            Profiler::Spark.timeout(Duration::from_secs(30)),
+            Duration::from_secs(90)
+        );
+        assert_eq!(
+            Profiler::Timings.command(Duration::from_secs(30)),
+            "timings report"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Reading and editing `server.properties`
+//!
+//! Edits preserve the comments, ordering, and formatting of every line that
+//! isn't changed.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use tokio::fs;
+
+/// The contents of a `server.properties` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerProperties {
+    lines: Vec<String>,
+}
+
+impl ServerProperties {
+    /// Returns the path of the `server.properties` file for the server at
+    /// `server_path`
+    pub fn path(server_path: &Path) -> PathBuf {
+        server_path.with_file_name("server.properties")
+    }
+
+    /// Reads the properties for the server at `server_path`
+    ///
+    /// A missing file is treated as empty.
+    pub async fn load(server_path: &Path) -> Result<Self, anyhow::Error> {
+        let path = Self::path(server_path);
+        if !path.exists() {
+            return Ok(Self::parse(""));
+        }
+
+        let contents = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        Ok(Self::parse(&contents))
+    }
+
+    /// Writes these properties for the server at `server_path`
+    pub async fn store(&self, server_path: &Path) -> Result<(), anyhow::Error> {
+        let path = Self::path(server_path);
+        fs::write(&path, self.to_string())
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        Self {
+            lines: contents.lines().map(Into::into).collect(),
+        }
+    }
+
+    /// Returns the value of the property `key`, if it is set
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.lines
+            .iter()
+            .filter_map(|l| split_line(l))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| unescape(v))
+    }
+
+    /// Sets the property `key` to `value`, adding it if it isn't present
+    pub fn set(&mut self, key: &str, value: &str) {
+        let line = format!("{}={}", key, escape(value));
+
+        match self
+            .lines
+            .iter_mut()
+            .find(|l| split_line(l).map(|(k, _)| k == key).unwrap_or(false))
+        {
+            Some(existing) => *existing = line,
+            None => self.lines.push(line),
+        }
+    }
+}
+
+impl std::fmt::Display for ServerProperties {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a line into its key and raw value, skipping blank lines and comments
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    if line.is_empty() || line.starts_with('#') || line.starts_with('!') {
+        return None;
+    }
+
+    match line.find(['=', ':']) {
+        Some(idx) => Some((line[..idx].trim_end(), line[idx + 1..].trim_start())),
+        None => Some((line.trim_end(), "")),
+    }
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('f') => out.push('\x0c'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if let Some(c) = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                    out.push(c);
+                }
+            }
+            Some(c) => out.push(c),
+            None => {}
+        }
+    }
+
+    out
+}
+
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            ' ' if i == 0 => out.push_str("\\ "),
+            '\\' | '=' | ':' | '#' | '!' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PROPERTIES: &str = "\
+#Minecraft server properties
+#Fri Oct 13 12:00:00 UTC 2023
+level-name=world
+motd=A \\: Minecraft Server
+level-seed=
+";
+
+    #[test]
+    fn get() {
+        let props = ServerProperties::parse(PROPERTIES);
+
+        assert_eq!(props.get("level-name").as_deref(), Some("world"));
+        assert_eq!(props.get("motd").as_deref(), Some("A : Minecraft Server"));
+        assert_eq!(props.get("level-seed").as_deref(), Some(""));
+        assert_eq!(props.get("gamemode"), None);
+    }
+
+    #[test]
+    fn set_preserves_other_lines() {
+        let mut props = ServerProperties::parse(PROPERTIES);
+        props.set("level-name", "creative: 2");
+        props.set("gamemode", "creative");
+
+        assert_eq!(
+            props.to_string(),
+            "\
+#Minecraft server properties
+#Fri Oct 13 12:00:00 UTC 2023
+level-name=creative\\: 2
+motd=A \\: Minecraft Server
+level-seed=
+gamemode=creative
+"
+        );
+        assert_eq!(props.get("level-name").as_deref(), Some("creative: 2"));
+    }
+}
@@ -0,0 +1,96 @@
+//! Fabric server launcher jars, from the Fabric meta API
+//!
+//! The Fabric meta API does not publish checksums for server launcher jars, so
+//! these downloads are not verified.
+
+use anyhow::anyhow;
+use serde_derive::Deserialize;
+
+use super::{vanilla::VersionManifest, Flavor, ServerJar};
+
+const META_BASE: &str = "https://meta.fabricmc.net/v2/versions";
+
+#[derive(Deserialize, Debug)]
+pub struct Component {
+    pub version: String,
+    pub stable: bool,
+}
+
+/// Returns the first stable component in the given list (the meta API lists
+/// newest first)
+pub fn latest_stable(components: &[Component]) -> Option<&Component> {
+    components.iter().find(|c| c.stable)
+}
+
+/// Fetches the newest stable loader and installer versions
+pub async fn latest_loader_and_installer(
+    client: &reqwest::Client,
+) -> Result<(String, String), anyhow::Error> {
+    let loaders: Vec<Component> = client
+        .get(format!("{}/loader", META_BASE))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let installers: Vec<Component> = client
+        .get(format!("{}/installer", META_BASE))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let loader = latest_stable(&loaders).ok_or_else(|| anyhow!("no stable Fabric loader"))?;
+    let installer =
+        latest_stable(&installers).ok_or_else(|| anyhow!("no stable Fabric installer"))?;
+
+    Ok((loader.version.clone(), installer.version.clone()))
+}
+
+pub async fn resolve(
+    client: &reqwest::Client,
+    version: &str,
+    loader_version: Option<&str>,
+) -> Result<ServerJar, anyhow::Error> {
+    let version = if version == "latest" {
+        VersionManifest::fetch(client).await?.latest.release
+    } else {
+        version.to_string()
+    };
+    let (mut loader, installer) = latest_loader_and_installer(client).await?;
+    if let Some(loader_version) = loader_version {
+        loader = loader_version.to_string();
+    }
+
+    Ok(ServerJar {
+        flavor: Flavor::Fabric,
+        url: format!(
+            "{}/loader/{}/{}/{}/server/jar",
+            META_BASE, version, loader, installer
+        ),
+        version,
+        build: Some(loader),
+        checksum: None,
+        installer: false,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn skips_unstable() {
+        let components: Vec<Component> = serde_json::from_str(
+            r#"[
+                { "version": "0.14.24-beta.1", "stable": false },
+                { "version": "0.14.23", "stable": true },
+                { "version": "0.14.22", "stable": true }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(latest_stable(&components).unwrap().version, "0.14.23");
+    }
+}
@@ -0,0 +1,183 @@
+//! Forge and NeoForge server installers, from their Maven repositories
+//!
+//! Neither project publishes a server jar directly; instead their installers
+//! are downloaded and run in the server directory (see `super::install`).
+
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use serde_derive::Deserialize;
+
+use super::{Checksum, Flavor, ServerJar};
+
+const FORGE_PROMOTIONS_URL: &str =
+    "https://files.minecraftforge.net/net/minecraftforge/forge/promotions_slim.json";
+const FORGE_MAVEN_BASE: &str = "https://maven.minecraftforge.net/net/minecraftforge/forge";
+const NEOFORGE_VERSIONS_URL: &str =
+    "https://maven.neoforged.net/api/maven/versions/releases/net/neoforged/neoforge";
+const NEOFORGE_MAVEN_BASE: &str = "https://maven.neoforged.net/releases/net/neoforged/neoforge";
+
+#[derive(Deserialize, Debug)]
+pub struct Promotions {
+    pub promos: HashMap<String, String>,
+}
+
+impl Promotions {
+    /// The recommended (or, failing that, latest) Forge version for the given
+    /// Minecraft version
+    pub fn for_version(&self, mc_version: &str) -> Option<&str> {
+        self.promos
+            .get(&format!("{}-recommended", mc_version))
+            .or_else(|| self.promos.get(&format!("{}-latest", mc_version)))
+            .map(|s| s.as_str())
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NeoForgeVersions {
+    pub versions: Vec<String>,
+}
+
+impl NeoForgeVersions {
+    /// The newest stable NeoForge version for the given Minecraft version
+    ///
+    /// NeoForge versions are derived from the Minecraft version they target:
+    /// Minecraft 1.20.2 is targeted by NeoForge 20.2.x.
+    pub fn for_version(&self, mc_version: &str) -> Option<&str> {
+        let mut parts = mc_version.split('.').skip(1);
+        let prefix = format!("{}.{}.", parts.next()?, parts.next().unwrap_or("0"));
+
+        // The API lists versions oldest first
+        self.versions
+            .iter()
+            .rev()
+            .find(|v| v.starts_with(&prefix) && !v.contains("beta"))
+            .map(|s| s.as_str())
+    }
+}
+
+/// Fetches the SHA-1 published alongside a Maven artifact, if there is one
+async fn maven_sha1(client: &reqwest::Client, artifact_url: &str) -> Option<Checksum> {
+    let sha1 = client
+        .get(format!("{}.sha1", artifact_url))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .text()
+        .await
+        .ok()?;
+
+    Some(Checksum::Sha1(sha1.trim().to_string()))
+}
+
+pub async fn resolve_forge(
+    client: &reqwest::Client,
+    mc_version: &str,
+    loader_version: Option<&str>,
+) -> Result<ServerJar, anyhow::Error> {
+    let forge_version = match loader_version {
+        Some(v) => v.to_string(),
+        None => {
+            let promotions: Promotions = client
+                .get(FORGE_PROMOTIONS_URL)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            promotions
+                .for_version(mc_version)
+                .ok_or_else(|| anyhow!("no Forge release exists for {}", mc_version))?
+                .to_string()
+        }
+    };
+
+    let full_version = format!("{}-{}", mc_version, forge_version);
+    let url = format!(
+        "{}/{}/forge-{}-installer.jar",
+        FORGE_MAVEN_BASE, full_version, full_version
+    );
+
+    Ok(ServerJar {
+        flavor: Flavor::Forge,
+        version: mc_version.to_string(),
+        build: Some(forge_version),
+        checksum: maven_sha1(client, &url).await,
+        url,
+        installer: true,
+    })
+}
+
+pub async fn resolve_neoforge(
+    client: &reqwest::Client,
+    mc_version: &str,
+    loader_version: Option<&str>,
+) -> Result<ServerJar, anyhow::Error> {
+    let neoforge_version = match loader_version {
+        Some(v) => v.to_string(),
+        None => {
+            let versions: NeoForgeVersions = client
+                .get(NEOFORGE_VERSIONS_URL)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            versions
+                .for_version(mc_version)
+                .ok_or_else(|| anyhow!("no NeoForge release exists for {}", mc_version))?
+                .to_string()
+        }
+    };
+
+    let url = format!(
+        "{}/{}/neoforge-{}-installer.jar",
+        NEOFORGE_MAVEN_BASE, neoforge_version, neoforge_version
+    );
+
+    Ok(ServerJar {
+        flavor: Flavor::NeoForge,
+        version: mc_version.to_string(),
+        build: Some(neoforge_version),
+        checksum: maven_sha1(client, &url).await,
+        url,
+        installer: true,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn forge_prefers_recommended() {
+        let promotions: Promotions = serde_json::from_str(
+            r#"{ "promos": {
+                "1.20.1-latest": "47.2.20",
+                "1.20.1-recommended": "47.2.0",
+                "1.20.2-latest": "48.0.40"
+            } }"#,
+        )
+        .unwrap();
+
+        assert_eq!(promotions.for_version("1.20.1"), Some("47.2.0"));
+        assert_eq!(promotions.for_version("1.20.2"), Some("48.0.40"));
+        assert_eq!(promotions.for_version("1.7.10"), None);
+    }
+
+    #[test]
+    fn neoforge_matches_mc_version() {
+        let versions: NeoForgeVersions = serde_json::from_str(
+            r#"{ "versions": ["20.2.86", "20.2.88", "20.4.80-beta", "20.4.237", "21.0.167"] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(versions.for_version("1.20.2"), Some("20.2.88"));
+        assert_eq!(versions.for_version("1.20.4"), Some("20.4.237"));
+        assert_eq!(versions.for_version("1.21"), Some("21.0.167"));
+    }
+}
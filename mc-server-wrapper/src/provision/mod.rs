@@ -0,0 +1,524 @@
+//! Downloading and setting up Minecraft server jars
+//!
+//! Given a server flavor and version, this resolves the matching server jar
+//! from the flavor's download API, downloads it (verifying its checksum when
+//! the API provides one), and prepares the server directory for a first run.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt, sync::mpsc};
+
+use crate::config::Provision;
+
+mod fabric;
+mod forge;
+pub mod modpack;
+mod paper;
+pub mod update;
+mod vanilla;
+
+/// The contents written to `server.properties` if one doesn't exist yet
+const DEFAULT_SERVER_PROPERTIES: &str = "\
+#Minecraft server properties
+#Generated by mc-server-wrapper
+motd=A Minecraft Server
+server-port=25565
+max-players=20
+difficulty=easy
+gamemode=survival
+online-mode=true
+white-list=false
+view-distance=10
+";
+
+/// Supported kinds of Minecraft servers
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Flavor {
+    Vanilla,
+    Paper,
+    Fabric,
+    Forge,
+    NeoForge,
+}
+
+impl fmt::Display for Flavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Flavor::Vanilla => f.write_str("vanilla"),
+            Flavor::Paper => f.write_str("paper"),
+            Flavor::Fabric => f.write_str("fabric"),
+            Flavor::Forge => f.write_str("forge"),
+            Flavor::NeoForge => f.write_str("neoforge"),
+        }
+    }
+}
+
+impl std::str::FromStr for Flavor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "vanilla" => Ok(Flavor::Vanilla),
+            "paper" => Ok(Flavor::Paper),
+            "fabric" => Ok(Flavor::Fabric),
+            "forge" => Ok(Flavor::Forge),
+            "neoforge" => Ok(Flavor::NeoForge),
+            _ => Err(format!(
+                "Unknown server flavor `{}` (expected vanilla, paper, fabric, forge, or neoforge)",
+                s
+            )),
+        }
+    }
+}
+
+/// A checksum provided by a download API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    Sha1(String),
+    Sha256(String),
+}
+
+impl Checksum {
+    /// Returns an error if `bytes` do not match this checksum
+    pub fn verify(&self, bytes: &[u8]) -> Result<(), anyhow::Error> {
+        let (expected, actual) = match self {
+            Checksum::Sha1(expected) => (expected, to_hex(&Sha1::digest(bytes))),
+            Checksum::Sha256(expected) => (expected, to_hex(&Sha256::digest(bytes))),
+        };
+
+        if expected.eq_ignore_ascii_case(&actual) {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "checksum mismatch (expected {}, got {})",
+                expected,
+                actual
+            ))
+        }
+    }
+}
+
+/// A server jar that can be downloaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerJar {
+    pub flavor: Flavor,
+    /// The Minecraft version the jar is for
+    pub version: String,
+    /// The flavor-specific build identifier (if the flavor has builds)
+    pub build: Option<String>,
+    /// Where to download the jar from
+    pub url: String,
+    /// The checksum to verify the download against (if the API provides one)
+    pub checksum: Option<Checksum>,
+    /// Whether this is an installer that produces the server jar when run,
+    /// rather than the server jar itself
+    pub installer: bool,
+}
+
+impl fmt::Display for ServerJar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.flavor, self.version)?;
+
+        if let Some(build) = &self.build {
+            write!(f, " (build {})", build)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds the HTTP client used for talking to download APIs
+pub fn http_client() -> Result<reqwest::Client, anyhow::Error> {
+    Ok(reqwest::Client::builder()
+        .user_agent(concat!(
+            "mc-server-wrapper/",
+            env!("CARGO_PKG_VERSION"),
+            " (https://github.com/Cldfire/mc-server-wrapper)"
+        ))
+        .build()?)
+}
+
+/// Finds the server jar for the given flavor and version
+///
+/// A version of `latest` resolves to the newest stable release. For mod
+/// loaders, `loader_version` selects a specific loader release (the newest
+/// stable one is used otherwise).
+pub async fn resolve(
+    client: &reqwest::Client,
+    flavor: Flavor,
+    version: &str,
+    loader_version: Option<&str>,
+) -> Result<ServerJar, anyhow::Error> {
+    let version = if version == "latest" && matches!(flavor, Flavor::Forge | Flavor::NeoForge) {
+        vanilla::VersionManifest::fetch(client)
+            .await?
+            .latest
+            .release
+    } else {
+        version.to_string()
+    };
+    let version = version.as_str();
+
+    match flavor {
+        Flavor::Vanilla => vanilla::resolve(client, version).await,
+        Flavor::Paper => paper::resolve(client, version).await,
+        Flavor::Fabric => fabric::resolve(client, version, loader_version).await,
+        Flavor::Forge => forge::resolve_forge(client, version, loader_version).await,
+        Flavor::NeoForge => forge::resolve_neoforge(client, version, loader_version).await,
+    }
+    .with_context(|| {
+        format!(
+            "Failed to find a {} server jar for version {}",
+            flavor, version
+        )
+    })
+}
+
+/// Downloads the given jar to `dest`, verifying its checksum
+///
+/// The jar is downloaded next to `dest` and only moved into place once it has
+/// been verified, so a failed download never leaves a broken jar behind.
+pub async fn download(
+    client: &reqwest::Client,
+    jar: &ServerJar,
+    dest: &Path,
+) -> Result<(), anyhow::Error> {
+    let bytes = client
+        .get(&jar.url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("Failed to download {}", jar.url))?
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to download {}", jar.url))?;
+
+    if let Some(checksum) = &jar.checksum {
+        checksum
+            .verify(&bytes)
+            .with_context(|| format!("Failed to verify {}", jar.url))?;
+    }
+
+    if let Some(parent) = dest.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("Failed to create directory {:?}", parent))?;
+    }
+
+    let part_path = dest.with_extension("jar.part");
+    let mut file = fs::File::create(&part_path)
+        .await
+        .with_context(|| format!("Failed to create {:?}", part_path))?;
+    file.write_all(&bytes)
+        .await
+        .with_context(|| format!("Failed to write {:?}", part_path))?;
+    file.sync_all().await?;
+
+    fs::rename(&part_path, dest)
+        .await
+        .with_context(|| format!("Failed to move {:?} to {:?}", part_path, dest))
+}
+
+/// Installs the given jar at `server_path`
+///
+/// Server jars are downloaded directly. Installers are downloaded into the
+/// server's directory and run there, after which the runnable jar they produce
+/// is moved to `server_path`.
+pub async fn install(
+    client: &reqwest::Client,
+    jar: &ServerJar,
+    server_path: &Path,
+) -> Result<(), anyhow::Error> {
+    if !jar.installer {
+        return download(client, jar, server_path).await;
+    }
+
+    let dir = server_dir(server_path);
+    let installer_path = dir.join(format!("{}-installer.jar", jar.flavor));
+    download(client, jar, &installer_path).await?;
+
+    let output = tokio::process::Command::new("java")
+        .arg("-jar")
+        .arg(&installer_path)
+        .arg("--installServer")
+        .current_dir(&dir)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run the {} installer", jar.flavor))?;
+    let _ = fs::remove_file(&installer_path).await;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "the {} installer exited with {}: {}",
+            jar.flavor,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut names = vec![];
+    let mut entries = fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+
+    let runnable = pick_runnable_jar(&names, jar).ok_or_else(|| {
+        anyhow!(
+            "the {} installer did not produce a jar that can be launched with `java -jar` \
+            (launching servers through argument files is not supported)",
+            jar.flavor
+        )
+    })?;
+
+    fs::rename(dir.join(runnable), server_path)
+        .await
+        .with_context(|| format!("Failed to move the {} server jar into place", jar.flavor))
+}
+
+/// Picks the jar produced by a mod loader installer that launches the server
+///
+/// Newer installers produce a `-shim.jar` for this purpose; older ones produce
+/// a jar named after the loader and version.
+fn pick_runnable_jar<'a>(names: &'a [String], jar: &ServerJar) -> Option<&'a str> {
+    let flavor = jar.flavor.to_string();
+    let candidates = names.iter().filter(|n| {
+        n.ends_with(".jar") && n.starts_with(&format!("{}-", flavor)) && !n.contains("installer")
+    });
+
+    candidates
+        .clone()
+        .find(|n| n.ends_with("-shim.jar"))
+        .or_else(|| {
+            let build = jar.build.as_deref().unwrap_or_default();
+            candidates
+                .clone()
+                .find(|n| n.contains(build) && !n.contains("universal"))
+        })
+        .map(|n| n.as_str())
+}
+
+/// The directory containing the server at `server_path`
+pub(crate) fn server_dir(server_path: &Path) -> PathBuf {
+    server_path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf()
+}
+
+/// Sets up a server at `server_path` according to `provision` if no server
+/// jar exists there yet
+///
+/// Returns the jar that was installed, if one was.
+pub async fn provision(
+    provision: &Provision,
+    server_path: &Path,
+) -> Result<Option<ServerJar>, anyhow::Error> {
+    if server_path.exists() {
+        return Ok(None);
+    }
+
+    let client = http_client()?;
+    let jar = match &provision.modpack {
+        Some(modpack) => {
+            modpack::install(
+                &client,
+                modpack,
+                provision,
+                server_path,
+                &server_dir(server_path),
+            )
+            .await?
+        }
+        None => {
+            let jar = resolve(
+                &client,
+                provision.flavor,
+                &provision.version,
+                provision.loader_version.as_deref(),
+            )
+            .await?;
+            install(&client, &jar, server_path).await?;
+            jar
+        }
+    };
+    update::InstalledJar::from(&jar).store(server_path).await?;
+
+    let properties_path = server_path.with_file_name("server.properties");
+    if !properties_path.exists() {
+        fs::write(&properties_path, DEFAULT_SERVER_PROPERTIES)
+            .await
+            .with_context(|| format!("Failed to write {:?}", properties_path))?;
+    }
+
+    if provision.agree_to_eula {
+        let eula_path = server_path.with_file_name("eula.txt");
+        fs::write(&eula_path, "eula=true")
+            .await
+            .with_context(|| format!("Failed to write {:?}", eula_path))?;
+    }
+
+    Ok(Some(jar))
+}
+
+/// Spawns a task that periodically checks for a newer server jar than the
+/// installed one, sending any that are found over `update_sender`
+///
+/// Each available jar is only sent once.
+pub fn spawn_update_checker(
+    provision: Provision,
+    server_path: PathBuf,
+    interval: Duration,
+    update_sender: mpsc::Sender<ServerJar>,
+) {
+    tokio::spawn(async move {
+        let client = match http_client() {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Failed to set up server update checks: {}", e);
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(interval);
+        let mut last_sent = None;
+
+        loop {
+            interval.tick().await;
+
+            match update::check_for_update(&client, &provision, &server_path).await {
+                Ok(Some(jar)) => {
+                    if last_sent.as_ref() != Some(&jar) {
+                        last_sent = Some(jar.clone());
+                        if update_sender.send(jar).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to check for server updates: {:#}", e),
+            }
+        }
+    });
+}
+
+/// Parses the arguments to the `provision` console command
+/// (`<flavor> <version> [loader-version]`)
+pub fn parse_args(args: &str) -> Result<Provision, String> {
+    const USAGE: &str = "Usage: provision <flavor> <version> [loader-version]";
+
+    let args: Vec<_> = args.split_whitespace().collect();
+    let (flavor, version, loader_version) = match args.as_slice() {
+        [flavor, version] => (flavor, version, None),
+        [flavor, version, loader_version] => (flavor, version, Some(loader_version.to_string())),
+        _ => return Err(USAGE.into()),
+    };
+
+    Ok(Provision {
+        flavor: flavor.parse()?,
+        version: version.to_string(),
+        loader_version,
+        modpack: None,
+        curseforge_api_key: None,
+        agree_to_eula: false,
+        update_check_interval: None,
+    })
+}
+
+/// Formats the given bytes as lowercase hex
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn provision_args() {
+        let provision = parse_args("fabric 1.20.4 0.15.7").unwrap();
+        assert_eq!(provision.flavor, Flavor::Fabric);
+        assert_eq!(provision.version, "1.20.4");
+        assert_eq!(provision.loader_version.as_deref(), Some("0.15.7"));
+
+        assert_eq!(parse_args("Paper latest").unwrap().flavor, Flavor::Paper);
+        assert!(parse_args("spigot 1.20.4")
+            .unwrap_err()
+            .starts_with("Unknown server flavor `spigot`"));
+        assert!(parse_args("vanilla").unwrap_err().starts_with("Usage"));
+    }
+
+    #[test]
+    fn sha1_matches() {
+        Checksum::Sha1("a9993e364706816aba3e25717850c26c9cd0d89d".into())
+            .verify(b"abc")
+            .unwrap();
+    }
+
+    #[test]
+    fn sha256_matches() {
+        Checksum::Sha256("BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD".into())
+            .verify(b"abc")
+            .unwrap();
+    }
+
+    fn forge_jar(build: &str) -> ServerJar {
+        ServerJar {
+            flavor: Flavor::Forge,
+            version: "1.12.2".into(),
+            build: Some(build.into()),
+            url: "https://example.com".into(),
+            checksum: None,
+            installer: true,
+        }
+    }
+
+    #[test]
+    fn runnable_jar_prefers_shim() {
+        let names = vec![
+            "forge-1.20.4-49.0.3-installer.jar.log".to_string(),
+            "forge-1.20.4-49.0.3-shim.jar".to_string(),
+            "libraries".to_string(),
+            "run.sh".to_string(),
+        ];
+
+        assert_eq!(
+            pick_runnable_jar(&names, &forge_jar("49.0.3")),
+            Some("forge-1.20.4-49.0.3-shim.jar")
+        );
+    }
+
+    #[test]
+    fn runnable_jar_legacy() {
+        let names = vec![
+            "forge-1.12.2-14.23.5.2859.jar".to_string(),
+            "forge-1.12.2-14.23.5.2859-universal.jar".to_string(),
+            "minecraft_server.1.12.2.jar".to_string(),
+        ];
+
+        assert_eq!(
+            pick_runnable_jar(&names, &forge_jar("14.23.5.2859")),
+            Some("forge-1.12.2-14.23.5.2859.jar")
+        );
+    }
+
+    #[test]
+    fn runnable_jar_missing() {
+        let names = vec!["run.sh".to_string(), "user_jvm_args.txt".to_string()];
+        assert_eq!(pick_runnable_jar(&names, &forge_jar("47.2.0")), None);
+    }
+
+    #[test]
+    fn sha256_mismatch() {
+        assert!(Checksum::Sha256("00".into()).verify(b"abc").is_err());
+    }
+}
@@ -0,0 +1,564 @@
+//! Installing servers from Modrinth (`.mrpack`) and CurseForge modpacks
+//!
+//! A modpack pins the Minecraft version and mod loader it was built for. The
+//! loader is installed like any other provisioned server, after which the
+//! pack's mods are downloaded and its bundled overrides (configs and the like)
+//! are extracted into the server directory.
+
+use std::{
+    fs::File,
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use serde_derive::Deserialize;
+use tokio::fs;
+
+use super::{install as install_jar, resolve, Checksum, Flavor, ServerJar};
+use crate::config::Provision;
+
+const CURSEFORGE_API_BASE: &str = "https://api.curseforge.com/v1";
+
+/// A parsed modpack
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modpack {
+    pub name: String,
+    pub flavor: Flavor,
+    pub mc_version: String,
+    pub loader_version: Option<String>,
+    pub files: Vec<ModpackFile>,
+    /// Directories in the archive whose contents are extracted into the server
+    /// directory (each ending in `/`)
+    pub override_dirs: Vec<String>,
+}
+
+/// A file that needs to be downloaded as part of a modpack
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModpackFile {
+    /// A file with a known download location (Modrinth)
+    Url {
+        path: PathBuf,
+        url: String,
+        checksum: Option<Checksum>,
+    },
+    /// A file that has to be looked up with the CurseForge API
+    CurseForge { project_id: u32, file_id: u32 },
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ModrinthIndex {
+    name: String,
+    files: Vec<ModrinthFile>,
+    dependencies: std::collections::HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModrinthFile {
+    path: String,
+    hashes: ModrinthHashes,
+    env: Option<ModrinthEnv>,
+    downloads: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModrinthHashes {
+    sha1: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModrinthEnv {
+    server: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CurseForgeManifest {
+    name: String,
+    minecraft: CurseForgeMinecraft,
+    files: Vec<CurseForgeFile>,
+    overrides: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CurseForgeMinecraft {
+    version: String,
+    mod_loaders: Vec<CurseForgeModLoader>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CurseForgeModLoader {
+    id: String,
+    primary: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CurseForgeFile {
+    #[serde(rename = "projectID")]
+    project_id: u32,
+    #[serde(rename = "fileID")]
+    file_id: u32,
+    required: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct CurseForgeFileResponse {
+    data: CurseForgeFileInfo,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct CurseForgeFileInfo {
+    display_name: String,
+    file_name: String,
+    download_url: Option<String>,
+    hashes: Vec<CurseForgeHash>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CurseForgeHash {
+    value: String,
+    /// 1 is SHA-1, 2 is MD5
+    algo: u8,
+}
+
+/// Maps a mod loader name used by modpack formats to a `Flavor`
+fn loader_flavor(name: &str) -> Option<Flavor> {
+    match name {
+        "fabric-loader" | "fabric" => Some(Flavor::Fabric),
+        "forge" => Some(Flavor::Forge),
+        "neoforge" => Some(Flavor::NeoForge),
+        _ => None,
+    }
+}
+
+/// Parses the `modrinth.index.json` from a `.mrpack`
+pub fn parse_modrinth_index(json: &str) -> Result<Modpack, anyhow::Error> {
+    let index: ModrinthIndex = serde_json::from_str(json)?;
+
+    let mc_version = index
+        .dependencies
+        .get("minecraft")
+        .ok_or_else(|| anyhow!("the modpack does not specify a Minecraft version"))?
+        .clone();
+    let (flavor, loader_version) = index
+        .dependencies
+        .iter()
+        .filter(|(name, _)| name.as_str() != "minecraft")
+        .map(|(name, version)| {
+            loader_flavor(name)
+                .map(|flavor| (flavor, version.clone()))
+                .ok_or_else(|| anyhow!("unsupported mod loader \"{}\"", name))
+        })
+        .next()
+        .transpose()?
+        .map(|(flavor, version)| (flavor, Some(version)))
+        .unwrap_or((Flavor::Vanilla, None));
+
+    let files = index
+        .files
+        .into_iter()
+        .filter(|f| {
+            f.env
+                .as_ref()
+                .map(|e| e.server != "unsupported")
+                .unwrap_or(true)
+        })
+        .map(|f| {
+            let path = safe_relative_path(&f.path)
+                .ok_or_else(|| anyhow!("the modpack contains an unsafe path: {}", f.path))?;
+            let url = f
+                .downloads
+                .first()
+                .ok_or_else(|| anyhow!("no download is available for {}", f.path))?
+                .clone();
+
+            Ok(ModpackFile::Url {
+                path,
+                url,
+                checksum: f.hashes.sha1.map(Checksum::Sha1),
+            })
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+
+    Ok(Modpack {
+        name: index.name,
+        flavor,
+        mc_version,
+        loader_version,
+        files,
+        override_dirs: vec!["overrides/".into(), "server-overrides/".into()],
+    })
+}
+
+/// Parses the `manifest.json` from a CurseForge modpack
+pub fn parse_curseforge_manifest(json: &str) -> Result<Modpack, anyhow::Error> {
+    let manifest: CurseForgeManifest = serde_json::from_str(json)?;
+
+    let loader = manifest
+        .minecraft
+        .mod_loaders
+        .iter()
+        .find(|l| l.primary)
+        .or_else(|| manifest.minecraft.mod_loaders.first());
+    let (flavor, loader_version) = match loader {
+        Some(loader) => {
+            let (name, version) = loader
+                .id
+                .split_once('-')
+                .ok_or_else(|| anyhow!("unrecognized mod loader \"{}\"", loader.id))?;
+            (
+                loader_flavor(name)
+                    .ok_or_else(|| anyhow!("unsupported mod loader \"{}\"", name))?,
+                Some(version.to_string()),
+            )
+        }
+        None => (Flavor::Vanilla, None),
+    };
+
+    Ok(Modpack {
+        name: manifest.name,
+        flavor,
+        mc_version: manifest.minecraft.version,
+        loader_version,
+        files: manifest
+            .files
+            .into_iter()
+            .filter(|f| f.required)
+            .map(|f| ModpackFile::CurseForge {
+                project_id: f.project_id,
+                file_id: f.file_id,
+            })
+            .collect(),
+        override_dirs: vec![format!(
+            "{}/",
+            manifest.overrides.as_deref().unwrap_or("overrides")
+        )],
+    })
+}
+
+/// Returns `path` if it is a relative path that stays within the directory it
+/// is joined to
+pub fn safe_relative_path(path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+
+    if path.as_os_str().is_empty()
+        || !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        None
+    } else {
+        Some(path.to_path_buf())
+    }
+}
+
+/// Reads the modpack manifest out of the archive at `path`
+fn read_archive(path: &Path) -> Result<Modpack, anyhow::Error> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+    let is_modrinth = archive.file_names().any(|n| n == "modrinth.index.json");
+    let name = if is_modrinth {
+        "modrinth.index.json"
+    } else {
+        "manifest.json"
+    };
+
+    let manifest = io::read_to_string(
+        archive
+            .by_name(name)
+            .with_context(|| "The archive does not contain a modpack manifest")?,
+    )?;
+    if is_modrinth {
+        parse_modrinth_index(&manifest)
+    } else {
+        parse_curseforge_manifest(&manifest)
+    }
+    .with_context(|| format!("Failed to parse {}", name))
+}
+
+/// Extracts the contents of the given directories in the archive at `path`
+/// into `dest`
+fn extract_overrides(
+    path: &Path,
+    override_dirs: &[String],
+    dest: &Path,
+) -> Result<(), anyhow::Error> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let relative = match override_dirs
+            .iter()
+            .find_map(|dir| entry.name().strip_prefix(dir.as_str()))
+        {
+            Some(relative) if !relative.is_empty() => relative.to_string(),
+            _ => continue,
+        };
+        let out_path = dest.join(
+            safe_relative_path(&relative)
+                .ok_or_else(|| anyhow!("the modpack contains an unsafe path: {}", entry.name()))?,
+        );
+
+        if entry.is_dir() {
+            std::fs::create_dir_all(&out_path)?;
+        } else {
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut entry, &mut File::create(&out_path)?)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Downloads a single modpack file into `dir`
+async fn download_file(
+    client: &reqwest::Client,
+    file: &ModpackFile,
+    dir: &Path,
+    curseforge_api_key: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let (path, url, checksum) = match file {
+        ModpackFile::Url {
+            path,
+            url,
+            checksum,
+        } => (path.clone(), url.clone(), checksum.clone()),
+        ModpackFile::CurseForge {
+            project_id,
+            file_id,
+        } => {
+            let api_key = curseforge_api_key.ok_or_else(|| {
+                anyhow!("a CurseForge API key is required to install CurseForge modpacks")
+            })?;
+            let info = client
+                .get(format!(
+                    "{}/mods/{}/files/{}",
+                    CURSEFORGE_API_BASE, project_id, file_id
+                ))
+                .header("x-api-key", api_key)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<CurseForgeFileResponse>()
+                .await?
+                .data;
+
+            let url = info.download_url.clone().ok_or_else(|| {
+                anyhow!(
+                    "{} does not allow third-party downloads; download {} manually and \
+                    place it in the mods folder",
+                    info.display_name,
+                    info.file_name
+                )
+            })?;
+            let path = safe_relative_path(&info.file_name)
+                .ok_or_else(|| anyhow!("unsafe file name: {}", info.file_name))?;
+
+            (
+                Path::new("mods").join(path),
+                url,
+                info.hashes
+                    .into_iter()
+                    .find(|h| h.algo == 1)
+                    .map(|h| Checksum::Sha1(h.value)),
+            )
+        }
+    };
+
+    let bytes = client
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    if let Some(checksum) = checksum {
+        checksum.verify(&bytes)?;
+    }
+
+    let out_path = dir.join(path);
+    if let Some(parent) = out_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(&out_path, &bytes).await?;
+
+    Ok(())
+}
+
+/// Installs the modpack at `source` (a path or URL) into `dir`, placing the
+/// pack's server jar at `server_path`
+///
+/// Returns the server jar that was installed.
+pub async fn install(
+    client: &reqwest::Client,
+    source: &str,
+    provision: &Provision,
+    server_path: &Path,
+    dir: &Path,
+) -> Result<ServerJar, anyhow::Error> {
+    let archive_path = if source.starts_with("https://") || source.starts_with("http://") {
+        let archive_path = dir.join("modpack.zip");
+        let bytes = client
+            .get(source)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await
+            .with_context(|| format!("Failed to download the modpack from {}", source))?;
+        fs::create_dir_all(dir).await?;
+        fs::write(&archive_path, &bytes).await?;
+        archive_path
+    } else {
+        PathBuf::from(source)
+    };
+
+    let archive_path_clone = archive_path.clone();
+    let pack = tokio::task::spawn_blocking(move || read_archive(&archive_path_clone))
+        .await?
+        .with_context(|| format!("Failed to read the modpack at {:?}", archive_path))?;
+
+    if pack.flavor != provision.flavor {
+        return Err(anyhow!(
+            "the modpack \"{}\" is for {}, but the config specifies {}",
+            pack.name,
+            pack.flavor,
+            provision.flavor
+        ));
+    }
+
+    let jar = resolve(
+        client,
+        pack.flavor,
+        &pack.mc_version,
+        pack.loader_version.as_deref(),
+    )
+    .await?;
+    install_jar(client, &jar, server_path).await?;
+
+    for file in &pack.files {
+        download_file(client, file, dir, provision.curseforge_api_key.as_deref())
+            .await
+            .with_context(|| format!("Failed to download a file for \"{}\"", pack.name))?;
+    }
+
+    let dir = dir.to_path_buf();
+    let override_dirs = pack.override_dirs.clone();
+    tokio::task::spawn_blocking(move || extract_overrides(&archive_path, &override_dirs, &dir))
+        .await?
+        .with_context(|| "Failed to extract the modpack's overrides")?;
+
+    Ok(jar)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn modrinth_index() {
+        let pack = parse_modrinth_index(
+            r#"{
+                "formatVersion": 1,
+                "game": "minecraft",
+                "versionId": "1.0.0",
+                "name": "Test Pack",
+                "files": [
+                    {
+                        "path": "mods/sodium.jar",
+                        "hashes": { "sha1": "aa", "sha512": "bb" },
+                        "env": { "client": "required", "server": "unsupported" },
+                        "downloads": ["https://cdn.modrinth.com/sodium.jar"],
+                        "fileSize": 1
+                    },
+                    {
+                        "path": "mods/lithium.jar",
+                        "hashes": { "sha1": "cc", "sha512": "dd" },
+                        "env": { "client": "required", "server": "required" },
+                        "downloads": ["https://cdn.modrinth.com/lithium.jar"],
+                        "fileSize": 1
+                    }
+                ],
+                "dependencies": { "minecraft": "1.20.1", "fabric-loader": "0.14.22" }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(pack.flavor, Flavor::Fabric);
+        assert_eq!(pack.mc_version, "1.20.1");
+        assert_eq!(pack.loader_version.as_deref(), Some("0.14.22"));
+        assert_eq!(
+            pack.files,
+            vec![ModpackFile::Url {
+                path: "mods/lithium.jar".into(),
+                url: "https://cdn.modrinth.com/lithium.jar".into(),
+                checksum: Some(Checksum::Sha1("cc".into())),
+            }]
+        );
+    }
+
+    #[test]
+    fn modrinth_index_unsafe_path() {
+        let res = parse_modrinth_index(
+            r#"{
+                "name": "Evil Pack",
+                "files": [
+                    {
+                        "path": "../../.bashrc",
+                        "hashes": { "sha1": "aa" },
+                        "downloads": ["https://example.com/evil"]
+                    }
+                ],
+                "dependencies": { "minecraft": "1.20.1" }
+            }"#,
+        );
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn curseforge_manifest() {
+        let pack = parse_curseforge_manifest(
+            r#"{
+                "minecraft": {
+                    "version": "1.20.1",
+                    "modLoaders": [{ "id": "forge-47.2.0", "primary": true }]
+                },
+                "manifestType": "minecraftModpack",
+                "name": "Test Pack",
+                "files": [
+                    { "projectID": 1, "fileID": 10, "required": true },
+                    { "projectID": 2, "fileID": 20, "required": false }
+                ],
+                "overrides": "overrides"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(pack.flavor, Flavor::Forge);
+        assert_eq!(pack.loader_version.as_deref(), Some("47.2.0"));
+        assert_eq!(
+            pack.files,
+            vec![ModpackFile::CurseForge {
+                project_id: 1,
+                file_id: 10
+            }]
+        );
+        assert_eq!(pack.override_dirs, vec!["overrides/".to_string()]);
+    }
+
+    #[test]
+    fn safe_paths() {
+        assert!(safe_relative_path("mods/a.jar").is_some());
+        assert!(safe_relative_path("./config/a.toml").is_some());
+        assert!(safe_relative_path("../a.jar").is_none());
+        assert!(safe_relative_path("/etc/passwd").is_none());
+        assert!(safe_relative_path("").is_none());
+    }
+}
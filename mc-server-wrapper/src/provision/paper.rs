@@ -0,0 +1,142 @@
+//! Paper server jars, from the PaperMC downloads API
+
+use anyhow::anyhow;
+use serde_derive::Deserialize;
+
+use super::{Checksum, Flavor, ServerJar};
+
+const API_BASE: &str = "https://api.papermc.io/v2/projects/paper";
+
+#[derive(Deserialize, Debug)]
+struct Project {
+    versions: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Builds {
+    pub version: String,
+    pub builds: Vec<Build>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Build {
+    pub build: u32,
+    pub channel: String,
+    pub downloads: BuildDownloads,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BuildDownloads {
+    pub application: Download,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Download {
+    pub name: String,
+    pub sha256: String,
+}
+
+impl Builds {
+    /// Fetches the builds for the given Minecraft version, resolving `latest`
+    /// to the newest version Paper supports
+    pub async fn fetch(client: &reqwest::Client, version: &str) -> Result<Self, anyhow::Error> {
+        let version = if version == "latest" {
+            let project: Project = client
+                .get(API_BASE)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            project
+                .versions
+                .last()
+                .cloned()
+                .ok_or_else(|| anyhow!("Paper has no versions available"))?
+        } else {
+            version.to_string()
+        };
+
+        Ok(client
+            .get(format!("{}/versions/{}/builds", API_BASE, version))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// The newest build on the default (stable) channel
+    pub fn latest_stable(&self) -> Option<&Build> {
+        self.builds
+            .iter()
+            .filter(|b| b.channel == "default")
+            .max_by_key(|b| b.build)
+    }
+
+    /// The downloadable jar for the given build
+    pub fn server_jar(&self, build: &Build) -> ServerJar {
+        ServerJar {
+            flavor: Flavor::Paper,
+            version: self.version.clone(),
+            build: Some(build.build.to_string()),
+            url: format!(
+                "{}/versions/{}/builds/{}/downloads/{}",
+                API_BASE, self.version, build.build, build.downloads.application.name
+            ),
+            checksum: Some(Checksum::Sha256(build.downloads.application.sha256.clone())),
+            installer: false,
+        }
+    }
+}
+
+pub async fn resolve(client: &reqwest::Client, version: &str) -> Result<ServerJar, anyhow::Error> {
+    let builds = Builds::fetch(client, version).await?;
+    let build = builds
+        .latest_stable()
+        .ok_or_else(|| anyhow!("no stable Paper builds exist for {}", builds.version))?;
+
+    Ok(builds.server_jar(build))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BUILDS: &str = r#"{
+        "project_id": "paper",
+        "version": "1.20.2",
+        "builds": [
+            {
+                "build": 230,
+                "channel": "default",
+                "downloads": { "application": { "name": "paper-1.20.2-230.jar", "sha256": "aa" } }
+            },
+            {
+                "build": 231,
+                "channel": "default",
+                "downloads": { "application": { "name": "paper-1.20.2-231.jar", "sha256": "bb" } }
+            },
+            {
+                "build": 232,
+                "channel": "experimental",
+                "downloads": { "application": { "name": "paper-1.20.2-232.jar", "sha256": "cc" } }
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn latest_stable_build() {
+        let builds: Builds = serde_json::from_str(BUILDS).unwrap();
+        let jar = builds.server_jar(builds.latest_stable().unwrap());
+
+        assert_eq!(jar.build.as_deref(), Some("231"));
+        assert_eq!(
+            jar.url,
+            "https://api.papermc.io/v2/projects/paper/versions/1.20.2/builds/231/downloads/\
+            paper-1.20.2-231.jar"
+        );
+        assert_eq!(jar.checksum, Some(Checksum::Sha256("bb".into())));
+    }
+}
@@ -0,0 +1,164 @@
+//! Checking for and installing newer builds of a provisioned server
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use serde_derive::{Deserialize, Serialize};
+use tokio::fs;
+
+use super::{install, resolve, Flavor, ServerJar};
+use crate::config::Provision;
+
+/// The file (next to the server jar) that records what was installed
+const INSTALLED_RECORD_FILENAME: &str = "mc-server-wrapper-installed.toml";
+
+/// A record of the server jar that was installed by the wrapper
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InstalledJar {
+    pub flavor: Flavor,
+    pub version: String,
+    pub build: Option<String>,
+}
+
+impl From<&ServerJar> for InstalledJar {
+    fn from(jar: &ServerJar) -> Self {
+        Self {
+            flavor: jar.flavor,
+            version: jar.version.clone(),
+            build: jar.build.clone(),
+        }
+    }
+}
+
+impl InstalledJar {
+    fn record_path(server_path: &Path) -> PathBuf {
+        server_path.with_file_name(INSTALLED_RECORD_FILENAME)
+    }
+
+    /// Reads the record for the server at `server_path`, if one exists
+    pub async fn load(server_path: &Path) -> Result<Option<Self>, anyhow::Error> {
+        let path = Self::record_path(server_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        Ok(Some(
+            toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))?,
+        ))
+    }
+
+    /// Writes this record for the server at `server_path`
+    pub async fn store(&self, server_path: &Path) -> Result<(), anyhow::Error> {
+        let path = Self::record_path(server_path);
+        fs::write(&path, toml::to_string(self)?)
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Returns true if `jar` is a different build than this one
+    pub fn is_outdated_by(&self, jar: &ServerJar) -> bool {
+        self.flavor == jar.flavor && (self.version != jar.version || self.build != jar.build)
+    }
+}
+
+/// Checks whether a newer server jar than the installed one is available
+///
+/// Returns `None` if the installed jar is up-to-date or was not installed by
+/// the wrapper. Servers installed from a modpack are never updated, since the
+/// pack pins the versions it was built for.
+pub async fn check_for_update(
+    client: &reqwest::Client,
+    provision: &Provision,
+    server_path: &Path,
+) -> Result<Option<ServerJar>, anyhow::Error> {
+    if provision.modpack.is_some() {
+        return Ok(None);
+    }
+
+    let installed = match InstalledJar::load(server_path).await? {
+        Some(installed) => installed,
+        None => return Ok(None),
+    };
+    let latest = resolve(
+        client,
+        provision.flavor,
+        &provision.version,
+        provision.loader_version.as_deref(),
+    )
+    .await?;
+
+    Ok(if installed.is_outdated_by(&latest) {
+        Some(latest)
+    } else {
+        None
+    })
+}
+
+/// Backs up the current server jar and replaces it with `jar`
+///
+/// The server must not be running when this is called. Returns the path of
+/// the backup.
+pub async fn upgrade(
+    client: &reqwest::Client,
+    jar: &ServerJar,
+    server_path: &Path,
+) -> Result<PathBuf, anyhow::Error> {
+    let backup_name = match InstalledJar::load(server_path).await? {
+        Some(installed) => format!(
+            "{}-{}{}.jar.bak",
+            installed.flavor,
+            installed.version,
+            installed
+                .build
+                .map(|b| format!("-{}", b))
+                .unwrap_or_default()
+        ),
+        None => "server.jar.bak".into(),
+    };
+    let backup_path = server_path.with_file_name(backup_name);
+
+    fs::copy(server_path, &backup_path)
+        .await
+        .with_context(|| format!("Failed to back up {:?}", server_path))?;
+    install(client, jar, server_path).await?;
+    InstalledJar::from(jar).store(server_path).await?;
+
+    Ok(backup_path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn jar(version: &str, build: Option<&str>) -> ServerJar {
+        ServerJar {
+            flavor: Flavor::Paper,
+            version: version.into(),
+            build: build.map(Into::into),
+            url: "https://example.com".into(),
+            checksum: None,
+            installer: false,
+        }
+    }
+
+    #[test]
+    fn same_build_is_current() {
+        let installed = InstalledJar::from(&jar("1.20.2", Some("231")));
+        assert!(!installed.is_outdated_by(&jar("1.20.2", Some("231"))));
+    }
+
+    #[test]
+    fn new_build_is_update() {
+        let installed = InstalledJar::from(&jar("1.20.2", Some("231")));
+        assert!(installed.is_outdated_by(&jar("1.20.2", Some("232"))));
+    }
+
+    #[test]
+    fn new_version_is_update() {
+        let installed = InstalledJar::from(&jar("1.20.1", Some("196")));
+        assert!(installed.is_outdated_by(&jar("1.20.2", Some("10"))));
+    }
+}
@@ -0,0 +1,126 @@
+//! Vanilla server jars, from Mojang's version manifest
+
+use anyhow::anyhow;
+use serde_derive::Deserialize;
+
+use super::{Checksum, Flavor, ServerJar};
+
+pub const VERSION_MANIFEST_URL: &str =
+    "https://piston-meta.mojang.com/mc/game/version_manifest_v2.json";
+
+#[derive(Deserialize, Debug)]
+pub struct VersionManifest {
+    pub latest: Latest,
+    pub versions: Vec<ManifestVersion>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Latest {
+    pub release: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ManifestVersion {
+    pub id: String,
+    /// URL of the version's metadata
+    pub url: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct VersionInfo {
+    downloads: Downloads,
+}
+
+#[derive(Deserialize, Debug)]
+struct Downloads {
+    server: Option<Download>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Download {
+    sha1: String,
+    url: String,
+}
+
+impl VersionManifest {
+    /// Fetches the current version manifest
+    pub async fn fetch(client: &reqwest::Client) -> Result<Self, anyhow::Error> {
+        Ok(client
+            .get(VERSION_MANIFEST_URL)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// Finds the given version in the manifest, resolving `latest` to the
+    /// latest release
+    pub fn find(&self, version: &str) -> Option<&ManifestVersion> {
+        let version = if version == "latest" {
+            self.latest.release.as_str()
+        } else {
+            version
+        };
+
+        self.versions.iter().find(|v| v.id == version)
+    }
+}
+
+pub async fn resolve(client: &reqwest::Client, version: &str) -> Result<ServerJar, anyhow::Error> {
+    let manifest = VersionManifest::fetch(client).await?;
+    let manifest_version = manifest
+        .find(version)
+        .ok_or_else(|| anyhow!("unknown Minecraft version {}", version))?;
+
+    let info: VersionInfo = client
+        .get(&manifest_version.url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let server = info
+        .downloads
+        .server
+        .ok_or_else(|| anyhow!("no server download exists for {}", manifest_version.id))?;
+
+    Ok(ServerJar {
+        flavor: Flavor::Vanilla,
+        version: manifest_version.id.clone(),
+        build: None,
+        url: server.url,
+        checksum: Some(Checksum::Sha1(server.sha1)),
+        installer: false,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MANIFEST: &str = r#"{
+        "latest": { "release": "1.20.2", "snapshot": "23w41a" },
+        "versions": [
+            { "id": "23w41a", "type": "snapshot", "url": "https://example.com/23w41a.json" },
+            { "id": "1.20.2", "type": "release", "url": "https://example.com/1.20.2.json" },
+            { "id": "1.20.1", "type": "release", "url": "https://example.com/1.20.1.json" }
+        ]
+    }"#;
+
+    #[test]
+    fn find_latest() {
+        let manifest: VersionManifest = serde_json::from_str(MANIFEST).unwrap();
+        assert_eq!(manifest.find("latest").unwrap().id, "1.20.2");
+    }
+
+    #[test]
+    fn find_specific() {
+        let manifest: VersionManifest = serde_json::from_str(MANIFEST).unwrap();
+        assert_eq!(
+            manifest.find("1.20.1").unwrap().url,
+            "https://example.com/1.20.1.json"
+        );
+        assert!(manifest.find("1.0.0").is_none());
+    }
+}
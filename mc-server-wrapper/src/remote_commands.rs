@@ -0,0 +1,184 @@
+//! Guarding the server console against commands run from outside the TUI
+//!
+//! Commands run remotely (such as with `/run` in Discord) are written to the
+//! server's stdin like commands typed into the TUI. A newline in one would
+//! end the command early and start another, so control characters are
+//! stripped from them first. Each origin can also only run so many commands
+//! a second, so a compromised or misbehaving account can't flood the console.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    time::{Duration, Instant},
+};
+
+use twilight_model::id::{marker::UserMarker, Id};
+
+use crate::aliases::UsageError;
+
+/// The window `per_second` limits are checked over
+const WINDOW: Duration = Duration::from_secs(1);
+
+/// Where a remote command came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandOrigin {
+    /// `name` is only for display, since users can change it
+    Discord { user: Id<UserMarker>, name: String },
+}
+
+impl CommandOrigin {
+    /// Returns what identifies the origin for rate limiting
+    fn key(&self) -> OriginKey {
+        match self {
+            CommandOrigin::Discord { user, .. } => OriginKey::Discord(*user),
+        }
+    }
+}
+
+/// The part of a `CommandOrigin` that stays the same across its commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum OriginKey {
+    Discord(Id<UserMarker>),
+}
+
+impl fmt::Display for CommandOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandOrigin::Discord { name, .. } => write!(f, "{} (Discord)", name),
+        }
+    }
+}
+
+/// Why a remote command wasn't run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandRejected {
+    /// Nothing was left of the command after sanitizing it
+    Empty,
+    /// The origin is running commands too quickly
+    RateLimited,
+    /// The command was an alias given the wrong arguments
+    Alias(UsageError),
+}
+
+impl fmt::Display for CommandRejected {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandRejected::Empty => f.write_str("No command was given"),
+            CommandRejected::RateLimited => {
+                f.write_str("Commands are being run too quickly, please wait a moment")
+            }
+            CommandRejected::Alias(e) => e.fmt(f),
+        }
+    }
+}
+
+/// Prepares a remote command to be written to the server console
+///
+/// Control characters (including newlines) are removed, as is any leading
+/// `/`.
+pub fn sanitize(command: &str) -> String {
+    let command: String = command.chars().filter(|c| !c.is_control()).collect();
+    command.trim().trim_start_matches('/').trim().to_string()
+}
+
+/// Limits how many commands each origin can run a second
+#[derive(Debug)]
+pub struct CommandLimiter {
+    /// Zero disables the limit
+    per_second: u32,
+    run: HashMap<OriginKey, VecDeque<Instant>>,
+}
+
+impl CommandLimiter {
+    pub fn new(per_second: u32) -> Self {
+        Self {
+            per_second,
+            run: HashMap::new(),
+        }
+    }
+
+    pub fn set_per_second(&mut self, per_second: u32) {
+        self.per_second = per_second;
+    }
+
+    /// Checks whether `origin` may run a command `now`, assuming it will be
+    /// run if it's allowed
+    pub fn check(&mut self, origin: &CommandOrigin, now: Instant) -> bool {
+        if self.per_second == 0 {
+            return true;
+        }
+
+        // Forget about origins that haven't run anything recently
+        self.run.retain(|_, run| {
+            while run
+                .front()
+                .is_some_and(|&oldest| now.saturating_duration_since(oldest) >= WINDOW)
+            {
+                run.pop_front();
+            }
+            !run.is_empty()
+        });
+
+        let run = self.run.entry(origin.key()).or_default();
+        if run.len() < self.per_second as usize {
+            run.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn discord(user: u64) -> CommandOrigin {
+        discord_named(user, "Cldfire")
+    }
+
+    fn discord_named(user: u64, name: &str) -> CommandOrigin {
+        CommandOrigin::Discord {
+            user: Id::new(user),
+            name: name.into(),
+        }
+    }
+
+    #[test]
+    fn sanitized() {
+        assert_eq!(sanitize("/say hi"), "say hi");
+        assert_eq!(sanitize("say hi\nstop"), "say histop");
+        assert_eq!(sanitize(" \r\n/op\tCldfire\u{1b} "), "opCldfire");
+        assert_eq!(sanitize("\n"), "");
+    }
+
+    #[test]
+    fn rate_limited() {
+        let start = Instant::now();
+        let mut limiter = CommandLimiter::new(2);
+
+        assert!(limiter.check(&discord(1), start));
+        assert!(limiter.check(&discord(1), start));
+        assert!(!limiter.check(&discord(1), start));
+        // Other origins aren't affected
+        assert!(limiter.check(&discord(2), start));
+
+        assert!(limiter.check(&discord(1), start + WINDOW));
+
+        limiter.set_per_second(0);
+        for _ in 0..10 {
+            assert!(limiter.check(&discord(1), start + WINDOW));
+        }
+    }
+
+    #[test]
+    fn rate_limited_across_names() {
+        let start = Instant::now();
+        let mut limiter = CommandLimiter::new(2);
+
+        // Changing nickname doesn't reset the limit
+        assert!(limiter.check(&discord_named(1, "Cldfire"), start));
+        assert!(limiter.check(&discord_named(1, "Notch"), start));
+        assert!(!limiter.check(&discord_named(1, "Herobrine"), start));
+    }
+}
@@ -0,0 +1,207 @@
+//! Letting players vote to restart the server
+//!
+//! Players start or join a vote by typing `!restartvote` in chat, and admins
+//! can start one with `restartvote` in the console. The vote passes once
+//! enough of the online players have voted for it; otherwise it expires. A
+//! new vote can't be started until a cooldown has passed after the last one.
+
+use std::{
+    collections::BTreeSet,
+    time::{Duration, Instant},
+};
+
+use crate::config::RestartVote;
+
+/// The chat message players vote with
+pub const VOTE_MESSAGE: &str = "!restartvote";
+
+/// The result of a vote being cast
+#[derive(Debug, PartialEq, Eq)]
+pub enum VoteOutcome {
+    /// A new vote was started
+    Started { votes: usize, needed: usize },
+    /// A vote was added to the vote in progress
+    Counted { votes: usize, needed: usize },
+    /// The player has already voted, or the console tried to start a vote
+    /// while one is in progress
+    AlreadyVoted,
+    /// A new vote can't be started for this long
+    CoolingDown(Duration),
+    /// Enough votes were cast to restart the server
+    Passed,
+}
+
+#[derive(Debug)]
+struct Vote {
+    voters: BTreeSet<String>,
+    started_at: Instant,
+}
+
+/// Tracks restart votes
+#[derive(Debug)]
+pub struct RestartVoting {
+    settings: RestartVote,
+    vote: Option<Vote>,
+    last_ended: Option<Instant>,
+}
+
+impl RestartVoting {
+    pub fn new(settings: RestartVote) -> Self {
+        Self {
+            settings,
+            vote: None,
+            last_ended: None,
+        }
+    }
+
+    /// Returns true if a vote is in progress
+    pub fn in_progress(&self) -> bool {
+        self.vote.is_some()
+    }
+
+    /// The number of votes needed with `online` players on the server
+    fn needed(&self, online: usize) -> usize {
+        let quorum = (online as f32 * self.settings.quorum).ceil() as usize;
+        quorum.max(self.settings.min_votes as usize)
+    }
+
+    /// Casts a vote for `voter` (or starts a vote from the console if `None`)
+    /// with `online` players on the server
+    pub fn vote(&mut self, voter: Option<&str>, online: usize, now: Instant) -> VoteOutcome {
+        let needed = self.needed(online);
+
+        let (vote, started) = match &mut self.vote {
+            Some(vote) => (vote, false),
+            None => {
+                if let Some(last_ended) = self.last_ended {
+                    let cooldown = Duration::from_secs(self.settings.cooldown * 60);
+                    let elapsed = now.saturating_duration_since(last_ended);
+                    if elapsed < cooldown {
+                        return VoteOutcome::CoolingDown(cooldown - elapsed);
+                    }
+                }
+
+                (
+                    self.vote.get_or_insert(Vote {
+                        voters: BTreeSet::new(),
+                        started_at: now,
+                    }),
+                    true,
+                )
+            }
+        };
+
+        match voter {
+            Some(voter) if vote.voters.insert(voter.to_lowercase()) => {}
+            _ if started => {}
+            _ => return VoteOutcome::AlreadyVoted,
+        }
+
+        let votes = vote.voters.len();
+        if votes >= needed {
+            self.vote = None;
+            self.last_ended = Some(now);
+            VoteOutcome::Passed
+        } else if started {
+            VoteOutcome::Started { votes, needed }
+        } else {
+            VoteOutcome::Counted { votes, needed }
+        }
+    }
+
+    /// Ends the vote in progress if it has run for too long, returning true if
+    /// it did
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let duration = Duration::from_secs(self.settings.duration);
+        match &self.vote {
+            Some(vote) if now.saturating_duration_since(vote.started_at) >= duration => {
+                self.vote = None;
+                self.last_ended = Some(now);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn voting() -> RestartVoting {
+        RestartVoting::new(RestartVote {
+            quorum: 0.5,
+            min_votes: 2,
+            duration: 120,
+            cooldown: 30,
+        })
+    }
+
+    #[test]
+    fn quorum_passes() {
+        let mut voting = voting();
+        let now = Instant::now();
+
+        assert_eq!(
+            voting.vote(Some("Cldfire"), 6, now),
+            VoteOutcome::Started {
+                votes: 1,
+                needed: 3
+            }
+        );
+        assert_eq!(
+            voting.vote(Some("cldfire"), 6, now),
+            VoteOutcome::AlreadyVoted
+        );
+        assert_eq!(
+            voting.vote(Some("Notch"), 6, now),
+            VoteOutcome::Counted {
+                votes: 2,
+                needed: 3
+            }
+        );
+        assert_eq!(voting.vote(Some("Alex"), 6, now), VoteOutcome::Passed);
+        assert!(!voting.in_progress());
+    }
+
+    #[test]
+    fn min_votes() {
+        let mut voting = voting();
+        assert_eq!(
+            voting.vote(Some("Cldfire"), 1, Instant::now()),
+            VoteOutcome::Started {
+                votes: 1,
+                needed: 2
+            }
+        );
+    }
+
+    #[test]
+    fn console_starts_vote() {
+        let mut voting = voting();
+        let now = Instant::now();
+
+        assert_eq!(
+            voting.vote(None, 4, now),
+            VoteOutcome::Started {
+                votes: 0,
+                needed: 2
+            }
+        );
+        assert_eq!(voting.vote(None, 4, now), VoteOutcome::AlreadyVoted);
+    }
+
+    #[test]
+    fn expiry_and_cooldown() {
+        let mut voting = voting();
+        let now = Instant::now();
+
+        voting.vote(Some("Cldfire"), 4, now);
+        assert!(!voting.expire(now + Duration::from_secs(60)));
+        assert!(voting.expire(now + Duration::from_secs(120)));
+        assert_eq!(
+            voting.vote(Some("Cldfire"), 4, now + Duration::from_secs(180)),
+            VoteOutcome::CoolingDown(Duration::from_secs(29 * 60))
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! Updating mc-server-wrapper itself from GitHub releases
+//!
+//! Releases are expected to have a binary for each platform named like
+//! `mc-server-wrapper-linux-amd64` (as uploaded by the publish workflow),
+//! along with a `.sha256` file containing its checksum. The checksum comes
+//! from the same release as the binary, so it only catches corrupted
+//! downloads; it doesn't prove who published the release.
+
+use std::{cmp::Ordering, path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, Context};
+use log::debug;
+use serde_derive::Deserialize;
+use tokio::{fs, sync::mpsc};
+
+use crate::{provision::Checksum, APPLICATION_VERSION};
+
+const LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/Cldfire/mc-server-wrapper/releases/latest";
+
+/// A GitHub release
+#[derive(Deserialize, Debug, Clone)]
+pub struct Release {
+    pub tag_name: String,
+    pub assets: Vec<Asset>,
+}
+
+/// A file attached to a GitHub release
+#[derive(Deserialize, Debug, Clone)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// Fetches the latest release
+    pub async fn fetch_latest(client: &reqwest::Client) -> Result<Self, anyhow::Error> {
+        Ok(client
+            .get(LATEST_RELEASE_URL)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    /// The release's version, without a leading `v`
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    /// Whether this release is newer than the running version
+    pub fn is_newer(&self) -> bool {
+        compare_versions(self.version(), APPLICATION_VERSION) == Ordering::Greater
+    }
+
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// The name of the release asset for the platform this was built for
+///
+/// This has to match the `asset_name`s in `.github/workflows/publish.yml`.
+pub fn asset_name() -> String {
+    asset_name_for(
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::EXE_SUFFIX,
+    )
+}
+
+fn asset_name_for(os: &str, arch: &str, exe_suffix: &str) -> String {
+    // Release names use Debian-style architecture names
+    let arch = match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => other,
+    };
+    format!("mc-server-wrapper-{}-{}{}", os, arch, exe_suffix)
+}
+
+/// Compares two versions like `0.1.0-alpha9`
+///
+/// Pre-releases are older than the release they precede, and numbers within
+/// pre-release tags are compared numerically (so `alpha10` is newer than
+/// `alpha9`).
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    #[derive(PartialEq, Eq, PartialOrd, Ord)]
+    enum Chunk<'a> {
+        Num(u64),
+        Text(&'a str),
+    }
+
+    fn chunks(s: &str) -> Vec<Chunk<'_>> {
+        let mut chunks = vec![];
+        let mut rest = s;
+        while let Some(first) = rest.chars().next() {
+            let is_digit = first.is_ascii_digit();
+            let end = rest
+                .find(|c: char| c.is_ascii_digit() != is_digit)
+                .unwrap_or(rest.len());
+            let (chunk, tail) = rest.split_at(end);
+            chunks.push(match chunk.parse() {
+                Ok(n) if is_digit => Chunk::Num(n),
+                _ => Chunk::Text(chunk),
+            });
+            rest = tail;
+        }
+        chunks
+    }
+
+    let (a_core, a_pre) = split_pre_release(a);
+    let (b_core, b_pre) = split_pre_release(b);
+
+    chunks(a_core)
+        .cmp(&chunks(b_core))
+        .then_with(|| match (a_pre, b_pre) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a_pre), Some(b_pre)) => chunks(a_pre).cmp(&chunks(b_pre)),
+        })
+}
+
+fn split_pre_release(version: &str) -> (&str, Option<&str>) {
+    match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    }
+}
+
+/// Downloads the binary for this platform from `release` and swaps it in
+/// for the running executable
+///
+/// The new binary is only moved into place once it matches the checksum
+/// published with it. The running process isn't affected; the new version is used the
+/// next time mc-server-wrapper starts. Returns the path the previous binary
+/// was moved to.
+pub async fn install(
+    client: &reqwest::Client,
+    release: &Release,
+) -> Result<PathBuf, anyhow::Error> {
+    let name = asset_name();
+    let asset = release.asset(&name).ok_or_else(|| {
+        anyhow!(
+            "release {} doesn't have a build for this platform ({})",
+            release.tag_name,
+            name
+        )
+    })?;
+    let checksum_asset = release.asset(&format!("{}.sha256", name)).ok_or_else(|| {
+        anyhow!(
+            "release {} doesn't have a checksum for {}",
+            release.tag_name,
+            name
+        )
+    })?;
+
+    let checksum = client
+        .get(&checksum_asset.browser_download_url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("Failed to download {}", checksum_asset.browser_download_url))?
+        .text()
+        .await?;
+    let checksum = checksum
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("{} is empty", checksum_asset.name))?;
+
+    let bytes = client
+        .get(&asset.browser_download_url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("Failed to download {}", asset.browser_download_url))?
+        .bytes()
+        .await
+        .with_context(|| format!("Failed to download {}", asset.browser_download_url))?;
+    Checksum::Sha256(checksum.into())
+        .verify(&bytes)
+        .with_context(|| {
+            format!(
+                "{} doesn't match the checksum published with it",
+                asset.name
+            )
+        })?;
+
+    let exe = std::env::current_exe().with_context(|| "Failed to find the running executable")?;
+    let new_path = exe.with_extension("new");
+    let old_path = exe.with_extension("old");
+
+    fs::write(&new_path, &bytes)
+        .await
+        .with_context(|| format!("Failed to write {:?}", new_path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(&new_path, std::fs::Permissions::from_mode(0o755))
+            .await
+            .with_context(|| format!("Failed to make {:?} executable", new_path))?;
+    }
+
+    // Renaming the running executable works on every platform, unlike
+    // overwriting it
+    fs::rename(&exe, &old_path)
+        .await
+        .with_context(|| format!("Failed to move {:?} to {:?}", exe, old_path))?;
+    if let Err(e) = fs::rename(&new_path, &exe).await {
+        let _ = fs::rename(&old_path, &exe).await;
+        return Err(e).with_context(|| format!("Failed to move {:?} to {:?}", new_path, exe));
+    }
+
+    Ok(old_path)
+}
+
+/// Updates mc-server-wrapper to the latest release if it's newer, returning
+/// the new version and the path the previous binary was moved to
+pub async fn update() -> Result<Option<(String, PathBuf)>, anyhow::Error> {
+    let client = crate::provision::http_client()?;
+    let release = Release::fetch_latest(&client)
+        .await
+        .with_context(|| "Failed to fetch the latest release")?;
+    if !release.is_newer() {
+        return Ok(None);
+    }
+
+    let old_path = install(&client, &release).await?;
+    Ok(Some((release.version().to_string(), old_path)))
+}
+
+/// Runs `mc-server-wrapper self-update`
+///
+/// If `check_only` is set the latest version is printed but not installed.
+pub async fn run(check_only: bool) -> Result<(), anyhow::Error> {
+    if check_only {
+        let release = Release::fetch_latest(&crate::provision::http_client()?)
+            .await
+            .with_context(|| "Failed to fetch the latest release")?;
+        if release.is_newer() {
+            println!("mc-server-wrapper {} is available", release.version());
+        } else {
+            println!("mc-server-wrapper {} is up-to-date", APPLICATION_VERSION);
+        }
+
+        return Ok(());
+    }
+
+    match update().await? {
+        Some((version, old_path)) => println!(
+            "Updated mc-server-wrapper to {} (the previous version was moved to {:?})",
+            version, old_path
+        ),
+        None => println!("mc-server-wrapper {} is up-to-date", APPLICATION_VERSION),
+    }
+
+    Ok(())
+}
+
+/// Spawns a task that periodically checks for a newer release of
+/// mc-server-wrapper, sending any that are found over `release_sender`
+///
+/// Each release is only sent once.
+pub fn spawn_update_checker(interval: Duration, release_sender: mpsc::Sender<Release>) {
+    tokio::spawn(async move {
+        let client = match crate::provision::http_client() {
+            Ok(client) => client,
+            Err(e) => {
+                debug!("Failed to set up mc-server-wrapper update checks: {}", e);
+                return;
+            }
+        };
+        let mut interval = tokio::time::interval(interval);
+        let mut last_sent = None;
+
+        loop {
+            interval.tick().await;
+
+            match Release::fetch_latest(&client).await {
+                Ok(release) if release.is_newer() => {
+                    if last_sent.as_ref() != Some(&release.tag_name) {
+                        last_sent = Some(release.tag_name.clone());
+                        if release_sender.send(release).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Ok(_) => {}
+                // This isn't important enough to bother people who run the
+                // wrapper offline with
+                Err(e) => debug!("Failed to check for mc-server-wrapper updates: {:#}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn compares_versions() {
+        assert_eq!(compare_versions("0.1.0", "0.1.0"), Ordering::Equal);
+        assert_eq!(compare_versions("0.2.0", "0.1.9"), Ordering::Greater);
+        assert_eq!(compare_versions("0.10.0", "0.9.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.0.0", "1.0.0-beta1"), Ordering::Greater);
+    }
+
+    #[test]
+    fn compares_pre_releases_numerically() {
+        assert_eq!(
+            compare_versions("0.1.0-alpha10", "0.1.0-alpha9"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_versions("0.1.0-beta1", "0.1.0-alpha9"),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn asset_names_match_publish_workflow() {
+        assert_eq!(
+            asset_name_for("linux", "x86_64", ""),
+            "mc-server-wrapper-linux-amd64"
+        );
+        assert_eq!(
+            asset_name_for("windows", "x86_64", ".exe"),
+            "mc-server-wrapper-windows-amd64.exe"
+        );
+        assert_eq!(
+            asset_name_for("macos", "x86_64", ""),
+            "mc-server-wrapper-macos-amd64"
+        );
+    }
+
+    #[test]
+    fn release_version() {
+        let release = Release {
+            tag_name: "v99.0.0".into(),
+            assets: vec![],
+        };
+
+        assert_eq!(release.version(), "99.0.0");
+        assert!(release.is_newer());
+    }
+}
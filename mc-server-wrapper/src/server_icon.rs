@@ -0,0 +1,437 @@
+//! Setting the server icon (`server-icon.png`)
+//!
+//! Minecraft only shows the icon if it's a 64x64 PNG, so other PNGs are
+//! scaled to fit before they're saved. The icon is read when the server
+//! starts.
+
+use std::{
+    convert::TryInto,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+use tokio::fs;
+
+/// The width and height of a server icon
+pub const ICON_SIZE: u32 = 64;
+/// The largest image that's decoded, to keep memory use reasonable
+const MAX_DIMENSION: u32 = 4096;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Returns the path of the icon for the server at `server_path`
+pub fn path(server_path: &Path) -> PathBuf {
+    server_path.with_file_name("server-icon.png")
+}
+
+/// An image with 8-bit RGBA pixels
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Image {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+/// The fields of a PNG's `IHDR` chunk that matter here
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    /// How many samples each pixel has
+    fn channels(&self) -> Result<usize, anyhow::Error> {
+        Ok(match self.color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            6 => 4,
+            other => bail!("unknown PNG color type {}", other),
+        })
+    }
+}
+
+/// A PNG chunk's type and data
+type Chunk<'a> = ([u8; 4], &'a [u8]);
+
+/// Splits a PNG into its header and chunks, checking their CRCs
+fn read_chunks(png: &[u8]) -> Result<(Header, Vec<Chunk<'_>>), anyhow::Error> {
+    let mut rest = png
+        .strip_prefix(PNG_SIGNATURE)
+        .ok_or_else(|| anyhow!("the file isn't a PNG"))?;
+
+    let mut chunks = vec![];
+    while rest.len() >= 12 {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        if rest.len() < 12 + len {
+            break;
+        }
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let data = &rest[8..8 + len];
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        if crc32(&kind, data) != crc {
+            bail!("the PNG is corrupt (bad checksum)");
+        }
+
+        chunks.push((kind, data));
+        rest = &rest[12 + len..];
+        if &kind == b"IEND" {
+            break;
+        }
+    }
+
+    let ihdr = match chunks.first() {
+        Some((kind, data)) if kind == b"IHDR" && data.len() == 13 => data,
+        _ => bail!("the PNG is corrupt (missing header)"),
+    };
+    let header = Header {
+        width: u32::from_be_bytes(ihdr[..4].try_into().unwrap()),
+        height: u32::from_be_bytes(ihdr[4..8].try_into().unwrap()),
+        bit_depth: ihdr[8],
+        color_type: ihdr[9],
+        interlaced: ihdr[12] != 0,
+    };
+
+    Ok((header, chunks))
+}
+
+fn crc32(kind: &[u8], data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    hasher.finalize()
+}
+
+/// Decodes a PNG
+///
+/// Interlaced PNGs and bit depths other than 8 and 16 aren't supported.
+fn decode(png: &[u8]) -> Result<Image, anyhow::Error> {
+    let (header, chunks) = read_chunks(png)?;
+    if header.width == 0 || header.height == 0 {
+        bail!("the PNG is empty");
+    }
+    if header.width > MAX_DIMENSION || header.height > MAX_DIMENSION {
+        bail!(
+            "the PNG is too large ({}x{}, the most is {}x{})",
+            header.width,
+            header.height,
+            MAX_DIMENSION,
+            MAX_DIMENSION
+        );
+    }
+    if header.interlaced {
+        bail!("interlaced PNGs aren't supported");
+    }
+    let bytes_per_sample = match header.bit_depth {
+        8 => 1,
+        16 if header.color_type != 3 => 2,
+        other => bail!("PNGs with a bit depth of {} aren't supported", other),
+    };
+
+    let channels = header.channels()?;
+    let bpp = channels * bytes_per_sample;
+    let stride = header.width as usize * bpp;
+
+    let compressed: Vec<u8> = chunks
+        .iter()
+        .filter(|(kind, _)| kind == b"IDAT")
+        .flat_map(|(_, data)| data.iter().copied())
+        .collect();
+    let expected_len = (stride + 1) * header.height as usize;
+    let mut filtered = Vec::with_capacity(expected_len);
+    ZlibDecoder::new(compressed.as_slice())
+        .take(expected_len as u64)
+        .read_to_end(&mut filtered)
+        .context("the PNG is corrupt")?;
+    if filtered.len() < expected_len {
+        bail!("the PNG is corrupt (its image data is too short)");
+    }
+
+    let raw = unfilter(&filtered, stride, bpp)?;
+
+    let palette = chunks
+        .iter()
+        .find(|(kind, _)| kind == b"PLTE")
+        .map(|(_, data)| *data);
+    let transparency = chunks
+        .iter()
+        .find(|(kind, _)| kind == b"tRNS")
+        .map(|(_, data)| *data)
+        .unwrap_or_default();
+    if header.color_type == 3 && palette.is_none() {
+        bail!("the PNG is corrupt (missing palette)");
+    }
+
+    let mut rgba = Vec::with_capacity(header.width as usize * header.height as usize * 4);
+    for pixel in raw.chunks_exact(bpp) {
+        // Only the most significant byte of 16-bit samples is kept
+        let sample = |idx: usize| pixel[idx * bytes_per_sample];
+        let rgba_pixel = match header.color_type {
+            0 => [sample(0), sample(0), sample(0), 255],
+            2 => [sample(0), sample(1), sample(2), 255],
+            3 => {
+                let idx = sample(0) as usize;
+                let palette = palette.unwrap_or_default();
+                let rgb = palette
+                    .get(idx * 3..idx * 3 + 3)
+                    .ok_or_else(|| anyhow!("the PNG is corrupt (bad palette index)"))?;
+                [
+                    rgb[0],
+                    rgb[1],
+                    rgb[2],
+                    transparency.get(idx).copied().unwrap_or(255),
+                ]
+            }
+            4 => [sample(0), sample(0), sample(0), sample(1)],
+            _ => [sample(0), sample(1), sample(2), sample(3)],
+        };
+        rgba.extend_from_slice(&rgba_pixel);
+    }
+
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        rgba,
+    })
+}
+
+/// Reverses the per-row filters applied to PNG image data
+fn unfilter(filtered: &[u8], stride: usize, bpp: usize) -> Result<Vec<u8>, anyhow::Error> {
+    let rows = filtered.len() / (stride + 1);
+    let mut raw = vec![0; rows * stride];
+
+    for row in 0..rows {
+        let filter = filtered[row * (stride + 1)];
+        let line = &filtered[row * (stride + 1) + 1..(row + 1) * (stride + 1)];
+        let (done, current) = raw.split_at_mut(row * stride);
+        let prev = if row > 0 {
+            &done[(row - 1) * stride..]
+        } else {
+            &[][..]
+        };
+        let current = &mut current[..stride];
+
+        for i in 0..stride {
+            let a = if i >= bpp { current[i - bpp] } else { 0 };
+            let b = prev.get(i).copied().unwrap_or(0);
+            let c = if i >= bpp {
+                prev.get(i - bpp).copied().unwrap_or(0)
+            } else {
+                0
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                other => bail!("the PNG is corrupt (unknown filter {})", other),
+            };
+            current[i] = line[i].wrapping_add(predicted);
+        }
+    }
+
+    Ok(raw)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Encodes an image as an RGBA PNG
+fn encode(image: &Image) -> Vec<u8> {
+    let stride = image.width as usize * 4;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    for row in image.rgba.chunks_exact(stride) {
+        // Writing to a `Vec` can't fail
+        encoder.write_all(&[0]).unwrap();
+        encoder.write_all(row).unwrap();
+    }
+    let compressed = encoder.finish().unwrap();
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&image.width.to_be_bytes());
+    ihdr.extend_from_slice(&image.height.to_be_bytes());
+    // 8-bit RGBA, default compression and filtering, not interlaced
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    for (kind, data) in [
+        (b"IHDR", ihdr.as_slice()),
+        (b"IDAT", compressed.as_slice()),
+        (b"IEND", &[][..]),
+    ] {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        png.extend_from_slice(&crc32(kind, data).to_be_bytes());
+    }
+    png
+}
+
+/// Scales `image` to `width` by `height`
+///
+/// Each pixel is the average of the pixels it covers in the original image
+/// (or the nearest one, when scaling up).
+fn resize(image: &Image, width: u32, height: u32) -> Image {
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+    let span = |out: u32, out_len: u32, in_len: u32| {
+        let start = (out as u64 * in_len as u64 / out_len as u64) as u32;
+        let end = ((out as u64 + 1) * in_len as u64).div_ceil(out_len as u64) as u32;
+        start..end.max(start + 1).min(in_len)
+    };
+
+    for y in 0..height {
+        let rows = span(y, height, image.height);
+        for x in 0..width {
+            let cols = span(x, width, image.width);
+            let mut sum = [0u64; 4];
+            let mut count = 0;
+            for src_y in rows.clone() {
+                for src_x in cols.clone() {
+                    let idx = (src_y as usize * image.width as usize + src_x as usize) * 4;
+                    let pixel = &image.rgba[idx..idx + 4];
+                    // Colors are weighted by alpha so transparent pixels
+                    // don't darken the edges
+                    let alpha = pixel[3] as u64;
+                    for channel in 0..3 {
+                        sum[channel] += pixel[channel] as u64 * alpha;
+                    }
+                    sum[3] += alpha;
+                    count += 1;
+                }
+            }
+
+            let alpha = sum[3];
+            for channel in &sum[..3] {
+                rgba.push(channel.checked_div(alpha).unwrap_or(0) as u8);
+            }
+            rgba.push((alpha / count) as u8);
+        }
+    }
+
+    Image {
+        width,
+        height,
+        rgba,
+    }
+}
+
+/// Returns `png` as a server icon, scaling it to 64x64 if it isn't already
+///
+/// Images that aren't square are stretched.
+pub fn prepare(png: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let image = decode(png)?;
+    if image.width == ICON_SIZE && image.height == ICON_SIZE {
+        return Ok(png.to_vec());
+    }
+
+    Ok(encode(&resize(&image, ICON_SIZE, ICON_SIZE)))
+}
+
+/// Sets the icon of the server at `server_path` to the PNG at `source`,
+/// returning the size of the original image
+pub async fn set(server_path: &Path, source: &Path) -> Result<(u32, u32), anyhow::Error> {
+    let png = fs::read(source)
+        .await
+        .with_context(|| format!("Failed to read {:?}", source))?;
+    let (header, _) = read_chunks(&png).with_context(|| format!("Invalid image {:?}", source))?;
+    let icon = prepare(&png).with_context(|| format!("Invalid image {:?}", source))?;
+
+    let path = path(server_path);
+    fs::write(&path, icon)
+        .await
+        .with_context(|| format!("Failed to write {:?}", path))?;
+
+    Ok((header.width, header.height))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn checkerboard(size: u32) -> Image {
+        let mut rgba = vec![];
+        for y in 0..size {
+            for x in 0..size {
+                let value = if (x + y) % 2 == 0 { 255 } else { 0 };
+                rgba.extend_from_slice(&[value, value, value, 255]);
+            }
+        }
+        Image {
+            width: size,
+            height: size,
+            rgba,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let image = checkerboard(5);
+        assert_eq!(decode(&encode(&image)).unwrap(), image);
+    }
+
+    #[test]
+    fn icons_are_kept() {
+        let png = encode(&checkerboard(ICON_SIZE));
+        assert_eq!(prepare(&png).unwrap(), png);
+    }
+
+    #[test]
+    fn other_sizes_are_scaled() {
+        for size in [16, 128, 100] {
+            let icon = decode(&prepare(&encode(&checkerboard(size))).unwrap()).unwrap();
+            assert_eq!((icon.width, icon.height), (ICON_SIZE, ICON_SIZE));
+        }
+
+        // Scaling down averages the pixels
+        let icon = resize(&checkerboard(128), ICON_SIZE, ICON_SIZE);
+        assert!(icon
+            .rgba
+            .chunks(4)
+            .all(|pixel| pixel == [127, 127, 127, 255]));
+    }
+
+    #[test]
+    fn transparent_pixels_dont_darken() {
+        let image = Image {
+            width: 2,
+            height: 1,
+            rgba: vec![255, 0, 0, 255, 0, 0, 0, 0],
+        };
+        assert_eq!(resize(&image, 1, 1).rgba, [255, 0, 0, 127]);
+    }
+
+    #[test]
+    fn filters() {
+        // Two rows of one RGB pixel, using the sub and paeth filters
+        let raw = unfilter(&[1, 10, 20, 30, 4, 1, 1, 1], 3, 3).unwrap();
+        assert_eq!(raw, [10, 20, 30, 11, 21, 31]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(prepare(b"GIF89a").is_err());
+
+        let mut png = encode(&checkerboard(4));
+        let last = png.len() - 20;
+        png[last] ^= 0xff;
+        assert!(prepare(&png).is_err());
+    }
+}
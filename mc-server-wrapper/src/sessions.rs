@@ -0,0 +1,86 @@
+//! Persisting online player sessions across wrapper restarts
+//!
+//! The sessions of online players are written next to the server jar whenever
+//! they change. When the wrapper starts, the saved sessions are used for
+//! players that are found to still be online, so their session lengths carry
+//! over.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde_derive::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::fs;
+
+use crate::player_tracker::OnlinePlayerInfo;
+
+/// The file (next to the server jar) sessions are saved in
+const SESSIONS_FILENAME: &str = "mc-server-wrapper-sessions.toml";
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SavedSessions {
+    /// Player name -> Unix timestamp of when they joined
+    joined_at: BTreeMap<String, i64>,
+}
+
+fn sessions_path(server_path: &Path) -> PathBuf {
+    server_path.with_file_name(SESSIONS_FILENAME)
+}
+
+/// Reads the sessions saved for the server at `server_path`
+///
+/// Returns an empty map if none were saved.
+pub async fn load(server_path: &Path) -> Result<BTreeMap<String, OnlinePlayerInfo>, anyhow::Error> {
+    let path = sessions_path(server_path);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let contents = fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read {:?}", path))?;
+    let saved: SavedSessions =
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))?;
+
+    Ok(saved
+        .joined_at
+        .into_iter()
+        .filter_map(|(name, timestamp)| {
+            let joined_at = OffsetDateTime::from_unix_timestamp(timestamp).ok()?;
+            Some((name, OnlinePlayerInfo { joined_at }))
+        })
+        .collect())
+}
+
+/// Saves the sessions of `online_players` for the server at `server_path`
+///
+/// The file is removed when no players are online.
+pub async fn store(
+    server_path: &Path,
+    online_players: &BTreeMap<String, OnlinePlayerInfo>,
+) -> Result<(), anyhow::Error> {
+    let path = sessions_path(server_path);
+
+    if online_players.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path)
+                .await
+                .with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+
+        return Ok(());
+    }
+
+    let saved = SavedSessions {
+        joined_at: online_players
+            .iter()
+            .map(|(name, info)| (name.clone(), info.joined_at.unix_timestamp()))
+            .collect(),
+    };
+    fs::write(&path, toml::to_string(&saved)?)
+        .await
+        .with_context(|| format!("Failed to write {:?}", path))
+}
@@ -0,0 +1,409 @@
+//! Staging variants of servers for testing changes before they go live
+//!
+//! A server's `staging` section describes a variant of it that's run with
+//! `--staging`: the same jar in its own folder, on another port and
+//! optionally with another world. The staging folder is set up from the
+//! server the first time it's used, and the jar is copied over each time so
+//! the two always match. Once changes have been tested, `promote` copies the
+//! staging server's settings, plugins, and mods back to the real server.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use time::OffsetDateTime;
+
+use crate::{
+    config::Staging,
+    export::{self, PORT_PROPERTIES},
+    properties::ServerProperties,
+    provision::server_dir,
+};
+
+/// The properties that are specific to each server and aren't promoted,
+/// along with the values servers use if they aren't set
+const KEPT_PROPERTIES: &[(&str, &str)] = &[
+    ("server-port", "25565"),
+    ("rcon.port", "25575"),
+    ("query.port", "25565"),
+    ("level-name", "world"),
+];
+/// The server config files that are promoted
+const PROMOTED_FILES: &[&str] = &[
+    "bukkit.yml",
+    "spigot.yml",
+    "paper.yml",
+    "purpur.yml",
+    "commands.yml",
+    "permissions.yml",
+];
+/// The folders whose jars are promoted, removing jars that aren't in the
+/// staging server's
+const ADDON_DIRS: &[&str] = &["plugins", "mods"];
+/// The extensions of plugins' config files, which are promoted from the top
+/// of each plugin's folder
+const PLUGIN_CONFIG_EXTENSIONS: &[&str] = &["yml", "yaml", "json", "toml", "conf", "properties"];
+/// Mods' configs, which are promoted as a whole
+const MOD_CONFIG_DIR: &str = "config";
+/// What the server prints once it has finished starting
+const STARTED_MARKER: &str = "Done (";
+/// Where the files replaced by a promotion are kept, next to the server jar
+const PROMOTE_BACKUP_DIRNAME: &str = "promote-backups";
+
+/// Sets up the staging server at `staging_path` from the server at
+/// `production_path`
+///
+/// The staging folder is filled with the server's setup (but not its worlds)
+/// if it's new. The jar is always copied, and the ports and world are set in
+/// `server.properties`.
+pub async fn prepare(
+    production_path: &Path,
+    staging_path: &Path,
+    staging: &Staging,
+) -> Result<(), anyhow::Error> {
+    let production_dir = server_dir(production_path);
+    let staging_dir = server_dir(staging_path);
+    let new = !ServerProperties::path(staging_path).exists();
+
+    let paths = if new {
+        export::setup_paths(production_path, false).await?
+    } else {
+        vec![PathBuf::from(production_path.file_name().unwrap())]
+    };
+    tokio::task::spawn_blocking({
+        let (production_dir, staging_dir) = (production_dir.clone(), staging_dir.clone());
+        move || -> Result<(), anyhow::Error> {
+            fs::create_dir_all(&staging_dir)
+                .with_context(|| format!("Failed to create {:?}", staging_dir))?;
+            paths.iter().try_for_each(|path| {
+                export::copy_recursively(&production_dir.join(path), &staging_dir.join(path))
+            })
+        }
+    })
+    .await??;
+
+    let mut ports = ServerProperties::load(production_path).await?;
+    export::move_ports(&mut ports, staging.port)?;
+    let mut properties = ServerProperties::load(staging_path).await?;
+    for key in PORT_PROPERTIES {
+        if let Some(port) = ports.get(key) {
+            properties.set(key, &port);
+        }
+    }
+    if let Some(world) = &staging.world {
+        properties.set("level-name", world);
+    }
+    properties.store(staging_path).await?;
+
+    if new {
+        log::info!(
+            "Set up the staging server in {:?} from {:?}",
+            staging_dir,
+            production_dir
+        );
+    }
+    Ok(())
+}
+
+/// Returns `staging`'s `server.properties` with the properties in
+/// `KEPT_PROPERTIES` taken from `production`
+fn promoted_properties(staging: &str, production: &ServerProperties) -> ServerProperties {
+    let mut properties = ServerProperties::parse(staging);
+    for (key, default) in KEPT_PROPERTIES {
+        let value = production.get(key).unwrap_or_else(|| default.to_string());
+        properties.set(key, &value);
+    }
+    properties
+}
+
+/// Returns the jars in `dir`
+fn jars(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut jars = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "jar") {
+            jars.push(path);
+        }
+    }
+    jars.sort();
+    Ok(jars)
+}
+
+/// Returns the files (relative to `dir`) under `path`, which is relative to
+/// `dir`
+fn files_under(dir: &Path, path: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let full = dir.join(path);
+    if full.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !full.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut files = vec![];
+    for entry in fs::read_dir(&full).with_context(|| format!("Failed to read {:?}", full))? {
+        files.extend(files_under(dir, &path.join(entry?.file_name()))?);
+    }
+    Ok(files)
+}
+
+/// What a promotion changes in the real server, as paths relative to its
+/// folder
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Promotion {
+    /// Files copied from the staging server
+    copied: Vec<PathBuf>,
+    /// Jars removed because the staging server doesn't have them
+    removed: Vec<PathBuf>,
+}
+
+/// Works out what promoting the staging server in `staging_dir` to the
+/// server in `production_dir` changes
+fn plan(staging_dir: &Path, production_dir: &Path) -> Result<Promotion, anyhow::Error> {
+    let mut promotion = Promotion::default();
+    promotion.copied.extend(
+        PROMOTED_FILES
+            .iter()
+            .map(PathBuf::from)
+            .filter(|path| staging_dir.join(path).is_file()),
+    );
+
+    for addon_dir in ADDON_DIRS {
+        let staged = jars(&staging_dir.join(addon_dir))?;
+        let names: Vec<_> = staged.iter().filter_map(|jar| jar.file_name()).collect();
+        for jar in &staged {
+            promotion
+                .copied
+                .push(Path::new(addon_dir).join(jar.file_name().unwrap()));
+        }
+        for jar in jars(&production_dir.join(addon_dir))? {
+            let name = jar.file_name().unwrap();
+            if !names.contains(&name) {
+                promotion.removed.push(Path::new(addon_dir).join(name));
+            }
+        }
+    }
+
+    let plugins = staging_dir.join("plugins");
+    if plugins.is_dir() {
+        for entry in fs::read_dir(&plugins)? {
+            let plugin = entry?.path();
+            if !plugin.is_dir() {
+                continue;
+            }
+            for config in fs::read_dir(&plugin)? {
+                let config = config?.path();
+                let is_config = config
+                    .extension()
+                    .is_some_and(|ext| PLUGIN_CONFIG_EXTENSIONS.iter().any(|e| ext == *e));
+                if config.is_file() && is_config {
+                    promotion
+                        .copied
+                        .push(config.strip_prefix(staging_dir).unwrap().to_path_buf());
+                }
+            }
+        }
+    }
+    promotion
+        .copied
+        .extend(files_under(staging_dir, Path::new(MOD_CONFIG_DIR))?);
+
+    Ok(promotion)
+}
+
+/// Checks that the staging server in `staging_dir` is fit to be promoted
+fn validate(staging_dir: &Path, promotion: &Promotion) -> Result<(), anyhow::Error> {
+    let log = fs::read_to_string(staging_dir.join("logs/latest.log")).unwrap_or_default();
+    if !log.contains(STARTED_MARKER) {
+        bail!(
+            "the staging server didn't finish starting the last time it ran (test it with \
+            `--staging`, or pass `--force` to promote it anyway)"
+        );
+    }
+
+    for path in &promotion.copied {
+        if path.extension().is_some_and(|ext| ext == "jar") {
+            let jar = staging_dir.join(path);
+            fs::File::open(&jar)
+                .map_err(anyhow::Error::from)
+                .and_then(|file| Ok(zip::ZipArchive::new(file)?))
+                .with_context(|| format!("{:?} isn't a valid jar", jar))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves `path` (relative to `dir`) into `backup_dir`
+fn back_up(dir: &Path, path: &Path, backup_dir: &Path) -> Result<(), anyhow::Error> {
+    let backup = backup_dir.join(path);
+    if let Some(parent) = backup.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(dir.join(path), &backup)
+        .with_context(|| format!("Failed to move {:?} to {:?}", path, backup))
+}
+
+/// Runs `mc-server-wrapper promote`, copying the settings, plugins, and mods
+/// of the staging server at `staging_path` to the server at
+/// `production_path`
+///
+/// The files that are replaced or removed are moved into a timestamped folder
+/// in `promote-backups` first. Unless `force` is set, the staging server must
+/// have started successfully the last time it ran.
+pub async fn promote(
+    production_path: &Path,
+    staging_path: &Path,
+    force: bool,
+) -> Result<(), anyhow::Error> {
+    let production_dir = server_dir(production_path);
+    let staging_dir = server_dir(staging_path);
+    if !staging_dir.is_dir() {
+        bail!(
+            "there's no staging server in {:?} yet (start it with `--staging` first)",
+            staging_dir
+        );
+    }
+
+    let staged_properties = tokio::fs::read_to_string(ServerProperties::path(staging_path))
+        .await
+        .with_context(|| "The staging server has no server.properties")?;
+    let properties = promoted_properties(
+        &staged_properties,
+        &ServerProperties::load(production_path).await?,
+    );
+    let backup_dir = production_dir
+        .join(PROMOTE_BACKUP_DIRNAME)
+        .join(OffsetDateTime::now_utc().unix_timestamp().to_string());
+
+    let properties_path = ServerProperties::path(production_path);
+    let properties = properties.to_string();
+
+    let promotion = tokio::task::spawn_blocking({
+        let (production_dir, backup_dir) = (production_dir.clone(), backup_dir.clone());
+        move || -> Result<Promotion, anyhow::Error> {
+            let promotion = plan(&staging_dir, &production_dir)?;
+            if !force {
+                validate(&staging_dir, &promotion)?;
+            }
+
+            let properties_name = PathBuf::from("server.properties");
+            for path in promotion
+                .copied
+                .iter()
+                .chain(&promotion.removed)
+                .chain([&properties_name])
+            {
+                if production_dir.join(path).is_file() {
+                    back_up(&production_dir, path, &backup_dir)?;
+                }
+            }
+            for path in &promotion.copied {
+                let to = production_dir.join(path);
+                if let Some(parent) = to.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(staging_dir.join(path), &to)
+                    .with_context(|| format!("Failed to copy {:?}", path))?;
+            }
+            fs::write(&properties_path, properties)
+                .with_context(|| format!("Failed to write {:?}", properties_path))?;
+            Ok(promotion)
+        }
+    })
+    .await?
+    .map_err(|e| {
+        anyhow!(
+            "{:#} (anything already replaced was moved to {:?})",
+            e,
+            backup_dir
+        )
+    })?;
+
+    println!(
+        "Promoted server.properties and {} other files to {:?}",
+        promotion.copied.len(),
+        production_dir
+    );
+    for removed in &promotion.removed {
+        println!("  Removed {:?}", removed);
+    }
+    if backup_dir.exists() {
+        println!(
+            "The files that were replaced were moved to {:?}",
+            backup_dir
+        );
+    }
+    println!("Restart the server to use the promoted settings");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn kept_properties() {
+        let production = ServerProperties::parse("server-port=25565\nlevel-name=survival\n");
+        let properties = promoted_properties(
+            "server-port=25566\nrcon.port=25576\nlevel-name=staging\nview-distance=8\n",
+            &production,
+        );
+
+        assert_eq!(properties.get("server-port").as_deref(), Some("25565"));
+        assert_eq!(properties.get("rcon.port").as_deref(), Some("25575"));
+        assert_eq!(properties.get("level-name").as_deref(), Some("survival"));
+        assert_eq!(properties.get("view-distance").as_deref(), Some("8"));
+    }
+
+    #[test]
+    fn planned() {
+        let dir = std::env::temp_dir().join(format!("mcsw-test-promote-{}", std::process::id()));
+        let (staging, production) = (dir.join("staging"), dir.join("production"));
+        for path in [
+            "staging/plugins/Essentials",
+            "staging/config/create",
+            "production/plugins",
+        ] {
+            fs::create_dir_all(dir.join(path)).unwrap();
+        }
+        for path in [
+            "staging/paper.yml",
+            "staging/plugins/Essentials.jar",
+            "staging/plugins/Essentials/config.yml",
+            "staging/plugins/Essentials/userdata.dat",
+            "staging/config/create/client.toml",
+            "production/plugins/Essentials.jar",
+            "production/plugins/OldPlugin.jar",
+        ] {
+            fs::write(dir.join(path), "").unwrap();
+        }
+
+        let promotion = plan(&staging, &production);
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut promotion = promotion.unwrap();
+        promotion.copied.sort();
+        assert_eq!(
+            promotion,
+            Promotion {
+                copied: [
+                    "config/create/client.toml",
+                    "paper.yml",
+                    "plugins/Essentials/config.yml",
+                    "plugins/Essentials.jar",
+                ]
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+                removed: vec![PathBuf::from("plugins/OldPlugin.jar")],
+            }
+        );
+    }
+}
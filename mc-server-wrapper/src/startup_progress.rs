@@ -0,0 +1,84 @@
+//! Reporting the server's world loading progress to Discord while it starts
+//!
+//! The server logs its spawn preparation progress about once a second, which
+//! is more often than a Discord message should be edited, so updates are
+//! limited to one every `EDIT_INTERVAL`.
+
+use std::time::{Duration, Instant};
+
+use crate::i18n;
+
+/// The least time between edits of the progress message
+pub const EDIT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Decides when to update the startup progress message and what it says
+#[derive(Debug, Default)]
+pub struct StartupProgress {
+    /// When the message was last updated, if it's been sent for this start
+    last_update: Option<Instant>,
+}
+
+impl StartupProgress {
+    /// Returns the text to show if the message should be updated for the
+    /// world being `percent` loaded at `now`
+    pub fn progress(&mut self, percent: u8, now: Instant) -> Option<String> {
+        if self
+            .last_update
+            .is_some_and(|last| now.saturating_duration_since(last) < EDIT_INTERVAL)
+        {
+            return None;
+        }
+
+        self.last_update = Some(now);
+        Some(i18n::fill(
+            &i18n::strings().discord.startup_progress,
+            &[("progress", &percent)],
+        ))
+    }
+
+    /// Returns the text to finish the message with once the server has
+    /// started, if a message was sent
+    pub fn finished(&mut self) -> Option<String> {
+        self.last_update
+            .take()
+            .map(|_| i18n::strings().discord.startup_finished.clone())
+    }
+
+    /// Returns the text to finish the message with if the server stopped
+    /// before it finished starting, if a message was sent
+    pub fn stopped(&mut self) -> Option<String> {
+        self.last_update
+            .take()
+            .map(|_| i18n::strings().discord.startup_stopped.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn throttled() {
+        let start = Instant::now();
+        let mut progress = StartupProgress::default();
+
+        assert_eq!(
+            progress.progress(4, start).as_deref(),
+            Some("Server starting… 4%")
+        );
+        assert_eq!(progress.progress(20, start + Duration::from_secs(1)), None);
+        assert_eq!(
+            progress.progress(44, start + EDIT_INTERVAL).as_deref(),
+            Some("Server starting… 44%")
+        );
+        assert_eq!(progress.finished().as_deref(), Some("Server started"));
+    }
+
+    #[test]
+    fn nothing_to_finish() {
+        let mut progress = StartupProgress::default();
+
+        assert_eq!(progress.finished(), None);
+        assert_eq!(progress.stopped(), None);
+    }
+}
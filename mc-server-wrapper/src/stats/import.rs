@@ -0,0 +1,214 @@
+//! Backfilling stats from the server's rotated log files
+//!
+//! Minecraft compresses each finished log into `logs/YYYY-MM-DD-N.log.gz`,
+//! named after the day the server started writing it. Log lines only contain
+//! the time of day, so the date is taken from the file name and advanced
+//! whenever the time wraps around midnight.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use mc_server_wrapper_lib::parse::{ConsoleMsg, ConsoleMsgSpecific, MessagePatterns};
+use time::{Date, Month, PrimitiveDateTime, UtcOffset};
+
+use super::Stats;
+
+/// Reads every `.log.gz` file in `logs_dir` that isn't in `already_imported`,
+/// returning the stats found in them
+///
+/// Times in the logs are interpreted using `offset`, and messages in other
+/// languages are recognized with `patterns`.
+pub fn import_logs(
+    logs_dir: &Path,
+    already_imported: &BTreeSet<String>,
+    offset: UtcOffset,
+    patterns: &MessagePatterns,
+) -> Result<Stats, anyhow::Error> {
+    let mut stats = Stats::default();
+    let mut logs = vec![];
+
+    for entry in fs::read_dir(logs_dir).with_context(|| format!("Failed to read {:?}", logs_dir))? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if let Some(date) = log_date(&name) {
+            if !already_imported.contains(&name) {
+                logs.push((name, date));
+            }
+        }
+    }
+
+    // Files from the same day are numbered in order
+    logs.sort_by_key(|(name, date)| (*date, log_number(name)));
+
+    for (name, date) in logs {
+        let path = logs_dir.join(&name);
+        let reader = BufReader::new(GzDecoder::new(
+            File::open(&path).with_context(|| format!("Failed to open {:?}", path))?,
+        ));
+
+        import_log(reader, date, offset, patterns, &mut stats)
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        stats.imported_logs.insert(name);
+    }
+
+    Ok(stats)
+}
+
+/// Adds the stats found in a single decompressed log to `stats`
+pub fn import_log(
+    mut reader: impl BufRead,
+    date: Date,
+    offset: UtcOffset,
+    patterns: &MessagePatterns,
+    stats: &mut Stats,
+) -> Result<(), anyhow::Error> {
+    let mut date = date;
+    let mut last_time = None;
+    let mut last_seen = None;
+    let mut joined_at = BTreeMap::new();
+    let mut started = false;
+    let mut stopped_cleanly = false;
+
+    let mut buf = vec![];
+    while reader.read_until(b'\n', &mut buf)? != 0 {
+        let line = String::from_utf8_lossy(&buf);
+        let console_msg = match ConsoleMsg::try_parse_from(line.trim_end()) {
+            Some(console_msg) => console_msg,
+            None => {
+                buf.clear();
+                continue;
+            }
+        };
+        buf.clear();
+
+        let time = console_msg.timestamp.time();
+        if last_time.map(|t| time < t).unwrap_or(false) {
+            date = date.next_day().unwrap_or(date);
+        }
+        last_time = Some(time);
+        let now = PrimitiveDateTime::new(date, time).assume_offset(offset);
+        last_seen = Some(now);
+
+        if console_msg.msg.starts_with("Stopping server") {
+            stopped_cleanly = true;
+        }
+
+        match ConsoleMsgSpecific::try_parse_with(&console_msg, patterns) {
+            Some(ConsoleMsgSpecific::FinishedLoading { .. }) => started = true,
+            Some(ConsoleMsgSpecific::PlayerLogin { name, .. }) => {
+                joined_at.insert(name, now);
+            }
+            Some(ConsoleMsgSpecific::PlayerLogout { name }) => {
+                if let Some(joined) = joined_at.remove(&name) {
+                    stats.record_session(&name, joined, now);
+                }
+            }
+            Some(ConsoleMsgSpecific::PlayerMsg { name, msg }) => {
+                stats.record_chat(&name, &msg, now);
+            }
+            _ => {}
+        }
+    }
+
+    // Anyone still online was disconnected when the log ended
+    if let Some(end) = last_seen {
+        for (name, joined) in joined_at {
+            stats.record_session(&name, joined, end);
+        }
+
+        if started && !stopped_cleanly {
+            stats.record_crash(end);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the date in a log file name like `2023-10-13-1.log.gz`
+fn log_date(name: &str) -> Option<Date> {
+    let stem = name.strip_suffix(".log.gz")?;
+    let mut parts = stem.splitn(4, '-');
+
+    let year = parts.next()?.parse().ok()?;
+    let month = Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+    let day = parts.next()?.parse().ok()?;
+    parts.next()?.parse::<u32>().ok()?;
+
+    Date::from_calendar_date(year, month, day).ok()
+}
+
+fn log_number(name: &str) -> u32 {
+    name.trim_end_matches(".log.gz")
+        .rsplit('-')
+        .next()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::{date, datetime};
+
+    const LOG: &str = "\
+[23:50:00] [Server thread/INFO]: Done (7.410s)! For help, type \"help\"
+[23:55:00] [Server thread/INFO]: Cldfire[/127.0.0.1:56538] logged in with entity id 97 at (0.5, 64.0, 0.5)
+[23:56:00] [Async Chat Thread - #0/INFO]: <Cldfire> hi!
+[00:05:00] [Server thread/INFO]: Cldfire left the game
+[00:06:00] [Server thread/INFO]: Notch[/127.0.0.1:56539] logged in with entity id 98 at (0.5, 64.0, 0.5)
+[00:16:00] [Server thread/INFO]: Can't keep up!
+";
+
+    #[test]
+    fn file_names() {
+        assert_eq!(log_date("2023-10-13-2.log.gz"), Some(date!(2023 - 10 - 13)));
+        assert_eq!(log_number("2023-10-13-2.log.gz"), 2);
+        assert_eq!(log_date("latest.log"), None);
+        assert_eq!(log_date("debug-1.log.gz"), None);
+    }
+
+    #[test]
+    fn sessions_chat_and_crashes() {
+        let mut stats = Stats::default();
+        import_log(
+            LOG.as_bytes(),
+            date!(2023 - 10 - 13),
+            UtcOffset::UTC,
+            &MessagePatterns::default(),
+            &mut stats,
+        )
+        .unwrap();
+
+        assert_eq!(stats.players["Cldfire"].playtime, 10 * 60);
+        assert_eq!(stats.players["Cldfire"].chat_messages, 1);
+        // Notch was still online when the log ended
+        assert_eq!(stats.players["Notch"].playtime, 10 * 60);
+        assert_eq!(stats.chat_history[0].msg, "hi!");
+        assert_eq!(
+            stats.crashes,
+            vec![datetime!(2023-10-14 00:16 UTC).unix_timestamp()]
+        );
+    }
+
+    #[test]
+    fn clean_stop_is_not_crash() {
+        let log = format!("{}[00:20:00] [Server thread/INFO]: Stopping server\n", LOG);
+        let mut stats = Stats::default();
+        import_log(
+            log.as_bytes(),
+            date!(2023 - 10 - 13),
+            UtcOffset::UTC,
+            &MessagePatterns::default(),
+            &mut stats,
+        )
+        .unwrap();
+
+        assert!(stats.crashes.is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+//! Long-term statistics about players and the server
+//!
+//! Stats are recorded as events come in from the server and saved next to the
+//! server jar. Stats from before the wrapper was used can be backfilled from
+//! the server's old log files with `import::import_logs`. The stats the server
+//! keeps itself are read with `world::read`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde_derive::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::fs;
+
+pub mod import;
+pub mod world;
+
+/// The file (next to the server jar) stats are saved in
+const STATS_FILENAME: &str = "mc-server-wrapper-stats.json";
+
+/// The number of chat messages kept in the history
+const CHAT_HISTORY_LEN: usize = 500;
+
+/// The number of startup times kept
+const STARTUP_HISTORY_LEN: usize = 50;
+/// The number of previous startups needed before regressions are reported
+const MIN_STARTUPS_FOR_REGRESSION: usize = 5;
+/// How many times longer than usual a startup has to take to be a regression
+const REGRESSION_FACTOR: f32 = 2.0;
+/// How many seconds longer than usual a startup has to take to be a
+/// regression, so fast servers starting a few seconds slower aren't reported
+const REGRESSION_MIN_SECONDS: f32 = 15.0;
+/// The number of recent startup times shown by `describe_startup_times`
+const STARTUP_TREND_LEN: usize = 5;
+
+/// Stats about a single player
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct PlayerStats {
+    /// Total time spent online in seconds
+    pub playtime: u64,
+    /// The number of times the player has joined
+    pub sessions: u32,
+    /// The number of chat messages the player has sent
+    pub chat_messages: u32,
+    /// Unix timestamp of when the player was last online
+    pub last_seen: Option<i64>,
+}
+
+/// A chat message sent by a player
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ChatMessage {
+    /// Unix timestamp of when the message was sent
+    pub time: i64,
+    pub name: String,
+    pub msg: String,
+}
+
+/// The most players that have been online at once
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerPeak {
+    pub count: u32,
+    /// Unix timestamp of when the peak was reached
+    pub time: i64,
+}
+
+/// How long the server took to start
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct StartupTime {
+    /// Unix timestamp of when the server finished starting
+    pub time: i64,
+    pub seconds: f32,
+}
+
+/// A startup that took much longer than usual
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StartupRegression {
+    pub seconds: f32,
+    /// The median of the previous startup times
+    pub usual: f32,
+}
+
+impl fmt::Display for StartupRegression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The server took {:.1}s to start, up from the usual {:.1}s \
+            (this can be a sign of world corruption or a misbehaving mod)",
+            self.seconds, self.usual
+        )
+    }
+}
+
+/// The stats store
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    /// Player name -> stats
+    pub players: BTreeMap<String, PlayerStats>,
+    /// The most recent chat messages, oldest first
+    pub chat_history: VecDeque<ChatMessage>,
+    /// Unix timestamps of when the server crashed
+    pub crashes: Vec<i64>,
+    /// The names of log files that have been imported
+    pub imported_logs: BTreeSet<String>,
+    /// The all-time peak player count
+    #[serde(default)]
+    pub peak_players: Option<PlayerPeak>,
+    /// The most recent startup times, oldest first
+    #[serde(default)]
+    pub startup_times: VecDeque<StartupTime>,
+}
+
+/// Returns the median of `values`, which must not be empty
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_by(f32::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+impl Stats {
+    fn path(server_path: &Path) -> PathBuf {
+        server_path.with_file_name(STATS_FILENAME)
+    }
+
+    /// Reads the stats saved for the server at `server_path`
+    ///
+    /// Returns empty stats if none were saved.
+    pub async fn load(server_path: &Path) -> Result<Self, anyhow::Error> {
+        let path = Self::path(server_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .await
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        serde_json::from_str(&contents).with_context(|| format!("Failed to parse {:?}", path))
+    }
+
+    /// Saves these stats for the server at `server_path`
+    pub async fn store(&self, server_path: &Path) -> Result<(), anyhow::Error> {
+        let path = Self::path(server_path);
+        fs::write(&path, serde_json::to_string(self)?)
+            .await
+            .with_context(|| format!("Failed to write {:?}", path))
+    }
+
+    /// Records a play session that has ended
+    pub fn record_session(
+        &mut self,
+        name: &str,
+        joined_at: OffsetDateTime,
+        left_at: OffsetDateTime,
+    ) {
+        let player = self.players.entry(name.into()).or_default();
+        player.playtime += (left_at - joined_at).whole_seconds().max(0) as u64;
+        player.sessions += 1;
+        player.last_seen = Some(
+            player
+                .last_seen
+                .unwrap_or(i64::MIN)
+                .max(left_at.unix_timestamp()),
+        );
+    }
+
+    /// Records a chat message
+    pub fn record_chat(&mut self, name: &str, msg: &str, time: OffsetDateTime) {
+        self.players.entry(name.into()).or_default().chat_messages += 1;
+        self.chat_history.push_back(ChatMessage {
+            time: time.unix_timestamp(),
+            name: name.into(),
+            msg: msg.into(),
+        });
+        self.trim_chat_history();
+    }
+
+    /// Records a server crash
+    pub fn record_crash(&mut self, time: OffsetDateTime) {
+        self.crashes.push(time.unix_timestamp());
+    }
+
+    /// Records the number of players online, returning true if it's a new
+    /// all-time peak
+    pub fn record_player_count(&mut self, count: u32, time: OffsetDateTime) -> bool {
+        if self.peak_players.is_some_and(|peak| peak.count >= count) {
+            return false;
+        }
+
+        self.peak_players = Some(PlayerPeak {
+            count,
+            time: time.unix_timestamp(),
+        });
+        true
+    }
+
+    /// Records how long the server took to start, returning a regression if
+    /// it took much longer than usual
+    pub fn record_startup(
+        &mut self,
+        seconds: f32,
+        time: OffsetDateTime,
+    ) -> Option<StartupRegression> {
+        let regression = self.usual_startup_time().and_then(|usual| {
+            let regressed = self.startup_times.len() >= MIN_STARTUPS_FOR_REGRESSION
+                && seconds > usual * REGRESSION_FACTOR
+                && seconds - usual >= REGRESSION_MIN_SECONDS;
+            regressed.then_some(StartupRegression { seconds, usual })
+        });
+
+        self.startup_times.push_back(StartupTime {
+            time: time.unix_timestamp(),
+            seconds,
+        });
+        while self.startup_times.len() > STARTUP_HISTORY_LEN {
+            self.startup_times.pop_front();
+        }
+
+        regression
+    }
+
+    /// Returns the median startup time, if any have been recorded
+    pub fn usual_startup_time(&self) -> Option<f32> {
+        if self.startup_times.is_empty() {
+            return None;
+        }
+
+        let mut seconds: Vec<_> = self.startup_times.iter().map(|s| s.seconds).collect();
+        Some(median(&mut seconds))
+    }
+
+    /// Describes the recent startup times and the usual one, like
+    /// `Startup times: 31.0s, 29.5s, 30.2s (usually 30.1s over 12 starts)`
+    pub fn describe_startup_times(&self) -> Option<String> {
+        let usual = self.usual_startup_time()?;
+        let recent: Vec<_> = self
+            .startup_times
+            .iter()
+            .skip(self.startup_times.len().saturating_sub(STARTUP_TREND_LEN))
+            .map(|s| format!("{:.1}s", s.seconds))
+            .collect();
+
+        Some(format!(
+            "Startup times: {} (usually {:.1}s over {} starts)",
+            recent.join(", "),
+            usual,
+            self.startup_times.len()
+        ))
+    }
+
+    /// Adds the stats in `other` to these
+    pub fn merge(&mut self, other: Stats) {
+        for (name, stats) in other.players {
+            let player = self.players.entry(name).or_default();
+            player.playtime += stats.playtime;
+            player.sessions += stats.sessions;
+            player.chat_messages += stats.chat_messages;
+            player.last_seen = player.last_seen.max(stats.last_seen);
+        }
+
+        self.chat_history.extend(other.chat_history);
+        self.chat_history.make_contiguous().sort_by_key(|m| m.time);
+        self.trim_chat_history();
+
+        self.crashes.extend(other.crashes);
+        self.crashes.sort_unstable();
+        self.imported_logs.extend(other.imported_logs);
+        if other.peak_players.map(|p| p.count) > self.peak_players.map(|p| p.count) {
+            self.peak_players = other.peak_players;
+        }
+
+        self.startup_times.extend(other.startup_times);
+        self.startup_times.make_contiguous().sort_by_key(|s| s.time);
+        while self.startup_times.len() > STARTUP_HISTORY_LEN {
+            self.startup_times.pop_front();
+        }
+    }
+
+    fn trim_chat_history(&mut self) {
+        while self.chat_history.len() > CHAT_HISTORY_LEN {
+            self.chat_history.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::macros::datetime;
+
+    #[test]
+    fn sessions_add_up() {
+        let mut stats = Stats::default();
+        stats.record_session(
+            "Cldfire",
+            datetime!(2023-10-13 12:00 UTC),
+            datetime!(2023-10-13 13:30 UTC),
+        );
+        stats.record_session(
+            "Cldfire",
+            datetime!(2023-10-14 12:00 UTC),
+            datetime!(2023-10-14 12:10 UTC),
+        );
+
+        let player = &stats.players["Cldfire"];
+        assert_eq!(player.playtime, 100 * 60);
+        assert_eq!(player.sessions, 2);
+        assert_eq!(
+            player.last_seen,
+            Some(datetime!(2023-10-14 12:10 UTC).unix_timestamp())
+        );
+    }
+
+    #[test]
+    fn startup_regressions() {
+        let mut stats = Stats::default();
+        let time = datetime!(2023-10-13 12:00 UTC);
+        for seconds in [30.0, 28.0, 31.0, 29.0] {
+            assert_eq!(stats.record_startup(seconds, time), None);
+        }
+        // Not enough startups have been recorded yet
+        assert_eq!(stats.record_startup(90.0, time), None);
+        stats.startup_times.pop_back();
+        assert_eq!(stats.record_startup(32.0, time), None);
+
+        assert_eq!(
+            stats.record_startup(75.0, time),
+            Some(StartupRegression {
+                seconds: 75.0,
+                usual: 30.0
+            })
+        );
+        assert_eq!(
+            stats.describe_startup_times().unwrap(),
+            "Startup times: 28.0s, 31.0s, 29.0s, 32.0s, 75.0s (usually 30.5s over 6 starts)"
+        );
+    }
+
+    #[test]
+    fn fast_startups_not_regressions() {
+        let mut stats = Stats::default();
+        let time = datetime!(2023-10-13 12:00 UTC);
+        for _ in 0..MIN_STARTUPS_FOR_REGRESSION {
+            stats.record_startup(4.0, time);
+        }
+
+        // More than twice as long, but only by a few seconds
+        assert_eq!(stats.record_startup(10.0, time), None);
+    }
+
+    #[test]
+    fn merge_orders_chat() {
+        let mut stats = Stats::default();
+        stats.record_chat("Cldfire", "later", datetime!(2023-10-14 12:00 UTC));
+
+        let mut imported = Stats::default();
+        imported.record_chat("Notch", "earlier", datetime!(2023-10-13 12:00 UTC));
+        imported.imported_logs.insert("2023-10-13-1.log.gz".into());
+        stats.merge(imported);
+
+        let msgs: Vec<_> = stats.chat_history.iter().map(|m| m.msg.as_str()).collect();
+        assert_eq!(msgs, ["earlier", "later"]);
+        assert_eq!(stats.players["Notch"].chat_messages, 1);
+        assert!(stats.imported_logs.contains("2023-10-13-1.log.gz"));
+    }
+}
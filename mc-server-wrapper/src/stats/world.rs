@@ -0,0 +1,182 @@
+//! Reading the stats the server keeps for each player in the world folder
+//!
+//! Minecraft (1.13 and newer) writes a player's stats to
+//! `<world>/stats/<uuid>.json`, and `usercache.json` next to the server jar
+//! maps UUIDs to names. These include time played before the wrapper was
+//! used, and don't need any plugins.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use serde_derive::Deserialize;
+use time::Duration;
+
+use crate::{provision::server_dir, ui::make_session_time_string, worlds};
+
+/// Minecraft counts time in ticks, 20 per second
+const TICKS_PER_SECOND: u64 = 20;
+
+/// The stats the server recorded for a player
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldPlayerStats {
+    /// The player's name, or their UUID if it isn't in `usercache.json`
+    pub name: String,
+    /// Time played in seconds
+    pub playtime: u64,
+    pub deaths: u64,
+    pub mob_kills: u64,
+    pub player_kills: u64,
+}
+
+impl fmt::Display for WorldPlayerStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} played, {} deaths, {} mob kills, {} player kills",
+            self.name,
+            make_session_time_string(Duration::seconds(self.playtime as i64)),
+            self.deaths,
+            self.mob_kills,
+            self.player_kills
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct StatsFile {
+    /// Category (like `minecraft:custom`) -> stat -> value
+    #[serde(default)]
+    stats: HashMap<String, HashMap<String, u64>>,
+}
+
+#[derive(Deserialize)]
+struct UserCacheEntry {
+    name: String,
+    uuid: String,
+}
+
+/// Parses a player's stats file, naming the player `name`
+fn parse_stats(contents: &str, name: String) -> Result<WorldPlayerStats, serde_json::Error> {
+    let file: StatsFile = serde_json::from_str(contents)?;
+    let custom = file.stats.get("minecraft:custom");
+    let stat = |key: &str| custom.and_then(|c| c.get(key)).copied().unwrap_or(0);
+
+    Ok(WorldPlayerStats {
+        name,
+        // Renamed from `play_one_minute` (which also counted ticks) in 1.17
+        playtime: stat("minecraft:play_time").max(stat("minecraft:play_one_minute"))
+            / TICKS_PER_SECOND,
+        deaths: stat("minecraft:deaths"),
+        mob_kills: stat("minecraft:mob_kills"),
+        player_kills: stat("minecraft:player_kills"),
+    })
+}
+
+/// Parses `usercache.json` into a map of UUIDs to names
+fn parse_usercache(contents: &str) -> Result<HashMap<String, String>, serde_json::Error> {
+    let entries: Vec<UserCacheEntry> = serde_json::from_str(contents)?;
+    Ok(entries
+        .into_iter()
+        .map(|entry| (entry.uuid, entry.name))
+        .collect())
+}
+
+fn stats_dir(server_path: &Path, world: &str) -> PathBuf {
+    server_dir(server_path).join(world).join("stats")
+}
+
+/// Reads the stats of every player in the active world of the server at
+/// `server_path`, sorted by playtime (longest first)
+pub async fn read(server_path: &Path) -> Result<Vec<WorldPlayerStats>, anyhow::Error> {
+    let dir = stats_dir(server_path, &worlds::active_world(server_path).await?);
+    let usercache_path = server_dir(server_path).join("usercache.json");
+
+    tokio::task::spawn_blocking(move || {
+        let names = match fs::read_to_string(&usercache_path) {
+            Ok(contents) => parse_usercache(&contents)
+                .with_context(|| format!("Failed to parse {:?}", usercache_path))?,
+            Err(_) => HashMap::new(),
+        };
+
+        let mut players = vec![];
+        for entry in fs::read_dir(&dir).with_context(|| format!("Failed to read {:?}", dir))? {
+            let path = entry?.path();
+            let uuid = match path
+                .file_name()
+                .and_then(|n| n.to_str()?.strip_suffix(".json"))
+            {
+                Some(uuid) => uuid.to_string(),
+                None => continue,
+            };
+
+            let contents =
+                fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+            let name = names.get(&uuid).cloned().unwrap_or(uuid);
+            players.push(
+                parse_stats(&contents, name)
+                    .with_context(|| format!("Failed to parse {:?}", path))?,
+            );
+        }
+
+        players.sort_by(|a, b| b.playtime.cmp(&a.playtime).then(a.name.cmp(&b.name)));
+        Ok(players)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let contents = r#"{
+            "stats": {
+                "minecraft:custom": {
+                    "minecraft:play_time": 1512000,
+                    "minecraft:deaths": 3,
+                    "minecraft:mob_kills": 42,
+                    "minecraft:jump": 1000
+                },
+                "minecraft:killed": { "minecraft:zombie": 40 }
+            },
+            "DataVersion": 3465
+        }"#;
+        let stats = parse_stats(contents, "Cldfire".into()).unwrap();
+
+        assert_eq!(
+            stats,
+            WorldPlayerStats {
+                name: "Cldfire".into(),
+                playtime: 75600,
+                deaths: 3,
+                mob_kills: 42,
+                player_kills: 0,
+            }
+        );
+        assert_eq!(
+            stats.to_string(),
+            "Cldfire: 21h 0m played, 3 deaths, 42 mob kills, 0 player kills"
+        );
+    }
+
+    #[test]
+    fn old_playtime_stat() {
+        let contents = r#"{"stats": {"minecraft:custom": {"minecraft:play_one_minute": 2400}}}"#;
+        assert_eq!(parse_stats(contents, "Notch".into()).unwrap().playtime, 120);
+    }
+
+    #[test]
+    fn usercache() {
+        let contents = r#"[
+            {"name": "Cldfire", "uuid": "361e5fb3-dbce-4f91-86b2-43423a4888d5", "expiresOn": "2024-01-01 00:00:00 +0000"}
+        ]"#;
+        let names = parse_usercache(contents).unwrap();
+
+        assert_eq!(names["361e5fb3-dbce-4f91-86b2-43423a4888d5"], "Cldfire");
+    }
+}
@@ -0,0 +1,396 @@
+//! The status embed kept pinned in the bridged Discord channel
+//!
+//! The embed shows the server's status, the players online and how long
+//! they've been playing, the server's version, and the address to connect
+//! to. It's edited whenever any of that changes, and periodically so session
+//! times stay fresh.
+//!
+//! Player avatars can be shown too, which has Discord fetch them from
+//! mc-heads.net by UUID.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use mc_server_wrapper_lib::status::McServerStatus;
+use time::OffsetDateTime;
+use twilight_model::{
+    channel::message::embed::{Embed, EmbedField, EmbedThumbnail},
+    util::Timestamp,
+};
+
+use crate::{
+    discord::util::sanitize_for_markdown,
+    i18n::{self, Language},
+    player_tracker::OnlinePlayerInfo,
+    ui::make_session_time_string,
+};
+
+/// Returns true if `title` is the title of the embed in any language, which is
+/// how it's found again after a restart
+pub fn is_title(title: &str) -> bool {
+    Language::ALL
+        .iter()
+        .any(|&language| i18n::strings_for(language).status_embed.title == title)
+}
+
+/// Discord doesn't allow embed field values longer than this
+const MAX_FIELD_LEN: usize = 1024;
+
+/// How often the embed is edited when nothing has changed
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// How long to wait between edits, since Discord rate limits them
+const MIN_EDIT_INTERVAL: Duration = Duration::from_secs(5);
+
+const COLOR_ONLINE: u32 = 0x43b581;
+const COLOR_STARTING: u32 = 0xfaa61a;
+const COLOR_OFFLINE: u32 = 0xf04747;
+
+/// What the status embed shows
+#[derive(Debug)]
+pub struct StatusEmbedInfo<'a> {
+    pub status: &'a McServerStatus,
+    pub online_players: &'a BTreeMap<String, OnlinePlayerInfo>,
+    pub max_players: Option<u32>,
+    pub version: Option<&'a str>,
+    pub address: Option<&'a str>,
+    /// The UUIDs of players by name if their avatars are shown
+    pub player_uuids: Option<&'a HashMap<String, String>>,
+}
+
+/// Returns the URL of the avatar of the player with `uuid`
+///
+/// Nothing is returned if `uuid` doesn't look like a UUID, so it can't be
+/// used to point somewhere else.
+pub fn avatar_url(uuid: &str) -> Option<String> {
+    if uuid.is_empty() || !uuid.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+        return None;
+    }
+
+    Some(format!("https://mc-heads.net/avatar/{}/64", uuid))
+}
+
+/// Builds the status embed as of `now`
+pub fn build(info: &StatusEmbedInfo<'_>, now: OffsetDateTime) -> Embed {
+    let strings = &i18n::strings().status_embed;
+    let (status, color) = match info.status {
+        McServerStatus::Stopped => (format!("🔴 {}", strings.offline), COLOR_OFFLINE),
+        McServerStatus::Starting { progress: None } => {
+            (format!("🟡 {}", strings.starting), COLOR_STARTING)
+        }
+        McServerStatus::Starting {
+            progress: Some(progress),
+        } => (
+            format!(
+                "🟡 {}",
+                i18n::fill(&strings.starting_progress, &[("progress", progress)])
+            ),
+            COLOR_STARTING,
+        ),
+        McServerStatus::Running { since, .. } => (
+            format!(
+                "🟢 {}",
+                i18n::fill(
+                    &strings.online,
+                    &[("uptime", &make_session_time_string(now - *since))]
+                )
+            ),
+            COLOR_ONLINE,
+        ),
+        McServerStatus::Stopping => (format!("🟡 {}", strings.stopping), COLOR_STARTING),
+    };
+
+    let mut fields = vec![field(&strings.status, status, true)];
+    if let Some(version) = info.version {
+        fields.push(field(&strings.version, version.to_string(), true));
+    }
+    if let Some(address) = info.address {
+        fields.push(field(
+            &strings.address,
+            format!("`{}`", address.replace('`', "'")),
+            true,
+        ));
+    }
+    if let McServerStatus::Running { .. } = info.status {
+        let count = match info.max_players {
+            Some(max) => format!("{}/{}", info.online_players.len(), max),
+            None => info.online_players.len().to_string(),
+        };
+        fields.push(field(
+            &i18n::fill(&strings.players, &[("count", &count)]),
+            format_players(info.online_players, info.player_uuids, now),
+            false,
+        ));
+    }
+
+    // The player that joined most recently is shown in the corner
+    let thumbnail = info.player_uuids.and_then(|uuids| {
+        let (name, _) = info
+            .online_players
+            .iter()
+            .max_by_key(|(_, player)| player.joined_at)?;
+        let url = avatar_url(uuids.get(name)?)?;
+        Some(EmbedThumbnail {
+            height: None,
+            proxy_url: None,
+            url,
+            width: None,
+        })
+    });
+
+    Embed {
+        author: None,
+        color: Some(color),
+        description: None,
+        fields,
+        footer: None,
+        image: None,
+        kind: "rich".into(),
+        provider: None,
+        thumbnail,
+        timestamp: Timestamp::from_secs(now.unix_timestamp()).ok(),
+        title: Some(strings.title.clone()),
+        url: None,
+        video: None,
+    }
+}
+
+fn field(name: &str, value: String, inline: bool) -> EmbedField {
+    EmbedField {
+        inline,
+        name: name.into(),
+        value,
+    }
+}
+
+/// Lists the online players with their session times, one per line
+///
+/// Players are linked to their avatars if `player_uuids` is given. Players
+/// that don't fit in an embed field are summarized at the end.
+fn format_players(
+    online_players: &BTreeMap<String, OnlinePlayerInfo>,
+    player_uuids: Option<&HashMap<String, String>>,
+    now: OffsetDateTime,
+) -> String {
+    let strings = &i18n::strings().status_embed;
+    if online_players.is_empty() {
+        return strings.nobody_playing.clone();
+    }
+
+    let mut lines = String::new();
+    for (i, (name, info)) in online_players.iter().enumerate() {
+        let avatar = player_uuids
+            .and_then(|uuids| uuids.get(name))
+            .and_then(|uuid| avatar_url(uuid));
+        let name = match avatar {
+            Some(avatar) => format!("[{}]({})", sanitize_for_markdown(name), avatar),
+            None => sanitize_for_markdown(name),
+        };
+        let line = format!(
+            "{} ({})\n",
+            name,
+            make_session_time_string(now - info.joined_at)
+        );
+        // Leave room for the summary of the rest
+        let more = i18n::fill(
+            &strings.more_players,
+            &[("count", &(online_players.len() - i))],
+        );
+        if lines.len() + line.len() + more.len() > MAX_FIELD_LEN {
+            lines.push_str(&more);
+            return lines;
+        }
+        lines.push_str(&line);
+    }
+
+    lines.trim_end().to_string()
+}
+
+/// Decides when the status embed should be edited
+#[derive(Debug, Default)]
+pub struct StatusEmbedUpdates {
+    changed: bool,
+    last_edit: Option<Instant>,
+}
+
+impl StatusEmbedUpdates {
+    /// Notes that something shown in the embed changed
+    pub fn changed(&mut self) {
+        self.changed = true;
+    }
+
+    /// Returns true if the embed should be edited now, assuming it will be
+    pub fn due(&mut self, now: Instant) -> bool {
+        let due = match self.last_edit {
+            None => true,
+            Some(last_edit) => {
+                let since = now.saturating_duration_since(last_edit);
+                (self.changed && since >= MIN_EDIT_INTERVAL) || since >= REFRESH_INTERVAL
+            }
+        };
+
+        if due {
+            self.changed = false;
+            self.last_edit = Some(now);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn running() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let mut online_players = BTreeMap::new();
+        online_players.insert(
+            "Cldfire".to_string(),
+            OnlinePlayerInfo {
+                joined_at: now - time::Duration::minutes(65),
+            },
+        );
+        online_players.insert(
+            "some_one".to_string(),
+            OnlinePlayerInfo {
+                joined_at: now - time::Duration::minutes(3),
+            },
+        );
+        let status = McServerStatus::Running {
+            since: now - time::Duration::hours(26),
+            players: BTreeSet::new(),
+            pid: None,
+        };
+        let embed = build(
+            &StatusEmbedInfo {
+                status: &status,
+                online_players: &online_players,
+                max_players: Some(20),
+                version: Some("1.20.1"),
+                address: Some("mc.example.com"),
+                player_uuids: None,
+            },
+            now,
+        );
+
+        assert!(is_title(embed.title.as_deref().unwrap()));
+        assert_eq!(embed.color, Some(COLOR_ONLINE));
+        let fields: Vec<_> = embed
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("Status", "🟢 Online for 1d 2h 0m"),
+                ("Version", "1.20.1"),
+                ("Address", "`mc.example.com`"),
+                ("Players (2/20)", "Cldfire (1h 5m)\nsome\\_one (3m)"),
+            ]
+        );
+        assert!(embed.thumbnail.is_none());
+    }
+
+    #[test]
+    fn avatars() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let mut online_players = BTreeMap::new();
+        for (name, minutes) in [("Cldfire", 65), ("Steve", 3), ("Alex", 10)] {
+            online_players.insert(
+                name.to_string(),
+                OnlinePlayerInfo {
+                    joined_at: now - time::Duration::minutes(minutes),
+                },
+            );
+        }
+        let mut player_uuids = HashMap::new();
+        player_uuids.insert("Cldfire".to_string(), "361e5fb3".to_string());
+        player_uuids.insert("Steve".to_string(), "8667ba71".to_string());
+        player_uuids.insert("Alex".to_string(), "](https://evil".to_string());
+        let status = McServerStatus::Running {
+            since: now,
+            players: BTreeSet::new(),
+            pid: None,
+        };
+        let embed = build(
+            &StatusEmbedInfo {
+                status: &status,
+                online_players: &online_players,
+                max_players: None,
+                version: None,
+                address: None,
+                player_uuids: Some(&player_uuids),
+            },
+            now,
+        );
+
+        assert_eq!(
+            embed.fields[1].value,
+            "Alex (10m)\n\
+            [Cldfire](https://mc-heads.net/avatar/361e5fb3/64) (1h 5m)\n\
+            [Steve](https://mc-heads.net/avatar/8667ba71/64) (3m)"
+        );
+        assert_eq!(
+            embed.thumbnail.unwrap().url,
+            "https://mc-heads.net/avatar/8667ba71/64"
+        );
+    }
+
+    #[test]
+    fn stopped() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let online_players = BTreeMap::new();
+        let embed = build(
+            &StatusEmbedInfo {
+                status: &McServerStatus::Stopped,
+                online_players: &online_players,
+                max_players: None,
+                version: None,
+                address: None,
+                player_uuids: None,
+            },
+            now,
+        );
+
+        assert_eq!(embed.color, Some(COLOR_OFFLINE));
+        assert_eq!(embed.fields.len(), 1);
+        assert_eq!(embed.fields[0].value, "🔴 Offline");
+    }
+
+    #[test]
+    fn too_many_players() {
+        let now = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let online_players: BTreeMap<_, _> = (0..200)
+            .map(|i| {
+                (
+                    format!("player{:03}", i),
+                    OnlinePlayerInfo { joined_at: now },
+                )
+            })
+            .collect();
+
+        let players = format_players(&online_players, None, now);
+        assert!(players.len() <= MAX_FIELD_LEN);
+        assert!(players.ends_with(" more"));
+    }
+
+    #[test]
+    fn updates() {
+        let start = Instant::now();
+        let mut updates = StatusEmbedUpdates::default();
+
+        assert!(updates.due(start));
+        assert!(!updates.due(start + Duration::from_secs(10)));
+
+        updates.changed();
+        assert!(!updates.due(start + Duration::from_secs(2)));
+        assert!(updates.due(start + Duration::from_secs(5)));
+        assert!(!updates.due(start + Duration::from_secs(30)));
+        assert!(updates.due(start + Duration::from_secs(65)));
+    }
+}
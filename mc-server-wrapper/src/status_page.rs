@@ -0,0 +1,286 @@
+//! A public, read-only status page for players
+//!
+//! The page shows whether the server is up (with its world loading progress
+//! while it starts), how many players are online, the MOTD, the address to
+//! connect to, and the server icon. It doesn't require authentication, so
+//! it doesn't show anything that isn't safe to share with players (like who's
+//! online or the console). It's served on its own address, separately from
+//! the health checks.
+
+use std::{path::PathBuf, sync::Arc};
+
+use log::warn;
+use mc_server_wrapper_lib::{status::McServerStatus, McServerManager};
+use tokio::net::TcpListener;
+
+use crate::{
+    http::{self, escape_html, Response},
+    i18n,
+    properties::ServerProperties,
+    server_icon,
+};
+
+/// What's shown on the status page
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusInfo {
+    pub state: ServerState,
+    pub motd: Option<String>,
+    pub max_players: Option<u32>,
+    /// The address players connect to
+    pub address: Option<String>,
+    /// Whether the server has an icon (served at `/icon.png`)
+    pub has_icon: bool,
+}
+
+/// Whether the server is up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerState {
+    Online {
+        players: usize,
+    },
+    /// `progress` is the world loading percentage, once the server has
+    /// started loading the world
+    Starting {
+        progress: Option<u8>,
+    },
+    Offline,
+}
+
+impl ServerState {
+    fn from_status(status: &McServerStatus) -> Self {
+        match status {
+            McServerStatus::Running { players, .. } => ServerState::Online {
+                players: players.len(),
+            },
+            McServerStatus::Starting { progress } => ServerState::Starting {
+                progress: *progress,
+            },
+            McServerStatus::Stopped | McServerStatus::Stopping => ServerState::Offline,
+        }
+    }
+}
+
+/// Removes Minecraft formatting codes (like `§a`) from `text`
+fn strip_formatting(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '§' {
+            chars.next();
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Renders the status page
+pub fn render(info: &StatusInfo) -> String {
+    let strings = &i18n::strings().status_page;
+    let title = info.address.as_deref().unwrap_or(&strings.default_title);
+    let (state_class, state) = match info.state {
+        ServerState::Online { .. } => ("online", escape_html(&strings.online)),
+        ServerState::Starting {
+            progress: Some(progress),
+        } => (
+            "starting",
+            format!(
+                r#"{} <progress max="100" value="{}"></progress>"#,
+                escape_html(&i18n::fill(
+                    &strings.starting_progress,
+                    &[("progress", &progress)]
+                )),
+                progress
+            ),
+        ),
+        ServerState::Starting { progress: None } => ("starting", escape_html(&strings.starting)),
+        ServerState::Offline => ("offline", escape_html(&strings.offline)),
+    };
+    // Refreshed more often while starting so the progress keeps up
+    let refresh = match info.state {
+        ServerState::Starting { .. } => 5,
+        _ => 60,
+    };
+
+    let mut details = String::new();
+    if let ServerState::Online { players } = info.state {
+        let players = match info.max_players {
+            Some(max) => format!("{} / {}", players, max),
+            None => players.to_string(),
+        };
+        details.push_str(&format!(
+            "<dt>{}</dt><dd>{}</dd>",
+            escape_html(&strings.players),
+            players
+        ));
+    }
+    if let Some(motd) = &info.motd {
+        details.push_str(&format!(
+            "<dt>{}</dt><dd>{}</dd>",
+            escape_html(&strings.motd),
+            escape_html(&strip_formatting(motd))
+        ));
+    }
+    if let Some(address) = &info.address {
+        details.push_str(&format!(
+            "<dt>{}</dt><dd><code>{}</code></dd>",
+            escape_html(&strings.address),
+            escape_html(address)
+        ));
+    }
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta http-equiv="refresh" content="{refresh}">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; max-width: 32em; margin: 2em auto; padding: 0 1em; }}
+.online {{ color: #2a7d2a; }}
+.starting {{ color: #b07d00; }}
+.offline {{ color: #b02a2a; }}
+dt {{ font-weight: bold; }}
+dd {{ margin: 0 0 1em; }}
+h1 img {{ vertical-align: middle; image-rendering: pixelated; }}
+</style>
+</head>
+<body>
+<h1>{icon}{title}</h1>
+<p class="{state_class}">{state}</p>
+<dl>{details}</dl>
+</body>
+</html>
+"#,
+        lang = i18n::language().code(),
+        title = escape_html(title),
+        icon = if info.has_icon {
+            r#"<img src="/icon.png" alt="" width="64" height="64"> "#
+        } else {
+            ""
+        },
+        refresh = refresh,
+        state_class = state_class,
+        state = state,
+        details = details,
+    )
+}
+
+/// Serves the status page for the server at `server_path` on `listener`
+/// until the wrapper exits
+///
+/// `address` is the address players connect to, shown on the page.
+pub async fn serve(
+    listener: TcpListener,
+    mc_server: Arc<McServerManager>,
+    server_path: PathBuf,
+    address: Option<String>,
+) {
+    http::serve(listener, "status page", move |path| {
+        let mc_server = mc_server.clone();
+        let server_path = server_path.clone();
+        let address = address.clone();
+        async move {
+            let icon_path = server_icon::path(&server_path);
+            match path.as_str() {
+                "/" => {}
+                "/icon.png" => {
+                    return match tokio::fs::read(&icon_path).await {
+                        Ok(icon) => Response::png(icon),
+                        Err(_) => Response::not_found(),
+                    }
+                }
+                _ => return Response::not_found(),
+            }
+
+            let properties = match ServerProperties::load(&server_path).await {
+                Ok(properties) => Some(properties),
+                Err(e) => {
+                    warn!(
+                        "Failed to read server.properties for the status page: {:#}",
+                        e
+                    );
+                    None
+                }
+            };
+            let info = StatusInfo {
+                state: ServerState::from_status(&mc_server.status().await),
+                motd: properties.as_ref().and_then(|p| p.get("motd")),
+                max_players: properties
+                    .as_ref()
+                    .and_then(|p| p.get("max-players"))
+                    .and_then(|max| max.parse().ok()),
+                address,
+                has_icon: icon_path.exists(),
+            };
+            Response::html(render(&info))
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn formatting_stripped() {
+        assert_eq!(
+            strip_formatting("§aA §lMinecraft§r Server"),
+            "A Minecraft Server"
+        );
+        assert_eq!(strip_formatting("trailing §"), "trailing ");
+    }
+
+    #[test]
+    fn online() {
+        let page = render(&StatusInfo {
+            state: ServerState::Online { players: 3 },
+            motd: Some("§6Welcome <friends>".into()),
+            max_players: Some(20),
+            address: Some("play.example.com".into()),
+            has_icon: true,
+        });
+
+        assert!(page.contains("<title>play.example.com</title>"));
+        assert!(page.contains(r#"<p class="online">Online</p>"#));
+        assert!(page.contains("<dd>3 / 20</dd>"));
+        assert!(page.contains("<dd>Welcome &lt;friends&gt;</dd>"));
+        assert!(page.contains("<code>play.example.com</code>"));
+        assert!(page.contains(r#"<img src="/icon.png""#));
+    }
+
+    #[test]
+    fn starting() {
+        let page = render(&StatusInfo {
+            state: ServerState::Starting { progress: Some(44) },
+            motd: None,
+            max_players: Some(20),
+            address: None,
+            has_icon: false,
+        });
+
+        assert!(page.contains(
+            r#"<p class="starting">Starting (44%) <progress max="100" value="44"></progress></p>"#
+        ));
+        assert!(page.contains(r#"<meta http-equiv="refresh" content="5">"#));
+    }
+
+    #[test]
+    fn offline() {
+        let page = render(&StatusInfo {
+            state: ServerState::Offline,
+            motd: None,
+            max_players: Some(20),
+            address: None,
+            has_icon: false,
+        });
+
+        assert!(page.contains("<title>Minecraft server</title>"));
+        assert!(page.contains(r#"<p class="offline">Offline</p>"#));
+        assert!(!page.contains("Players"));
+        assert!(!page.contains("<img"));
+    }
+}
@@ -0,0 +1,251 @@
+//! Announcing when streamers go live on Twitch
+//!
+//! The streamers in the `stream_alerts` config section are looked up with
+//! Twitch's Helix API every so often, and each one that has started
+//! broadcasting since the last check is announced in Minecraft and Discord.
+//! Polling (rather than subscribing with EventSub) means the wrapper doesn't
+//! need a public HTTPS address for Twitch to call.
+
+use std::{collections::HashSet, time::Duration};
+
+use anyhow::{anyhow, Context};
+use log::{debug, warn};
+use minecraft_chat::{Color, MessageBuilder, Payload};
+use reqwest::StatusCode;
+use serde_derive::Deserialize;
+use tokio::sync::mpsc;
+
+use crate::{chat, config::StreamAlerts};
+
+const TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+const STREAMS_URL: &str = "https://api.twitch.tv/helix/streams";
+/// The most streamers that can be looked up in one request
+pub const MAX_STREAMERS: usize = 100;
+
+/// A stream that's live
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Stream {
+    pub user_login: String,
+    pub user_name: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub game_name: String,
+}
+
+impl Stream {
+    /// The stream's URL
+    pub fn url(&self) -> String {
+        format!("https://twitch.tv/{}", self.user_login)
+    }
+}
+
+#[derive(Deserialize)]
+struct Streams {
+    data: Vec<Stream>,
+}
+
+#[derive(Deserialize)]
+struct Token {
+    access_token: String,
+}
+
+/// Keeps track of which streamers are live
+#[derive(Debug, Default)]
+struct LiveStreamers {
+    /// The logins of the streamers that were live at the last check, or
+    /// `None` before the first check
+    live: Option<HashSet<String>>,
+}
+
+impl LiveStreamers {
+    /// Records the streams that are live now, returning the ones that
+    /// weren't live at the last check
+    ///
+    /// Nothing is returned for the first check, so streams that were already
+    /// live when the wrapper started aren't announced.
+    fn update(&mut self, streams: Vec<Stream>) -> Vec<Stream> {
+        let now: HashSet<_> = streams.iter().map(|s| s.user_login.clone()).collect();
+        let started = match &self.live {
+            Some(live) => streams
+                .into_iter()
+                .filter(|s| !live.contains(&s.user_login))
+                .collect(),
+            None => vec![],
+        };
+        self.live = Some(now);
+        started
+    }
+}
+
+/// Gets an app access token for the Helix API
+async fn fetch_token(
+    client: &reqwest::Client,
+    config: &StreamAlerts,
+) -> Result<String, anyhow::Error> {
+    let token: Token = client
+        .post(TOKEN_URL)
+        .form(&[
+            ("client_id", config.twitch_client_id.as_str()),
+            ("client_secret", config.twitch_client_secret.as_str()),
+            ("grant_type", "client_credentials"),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(token.access_token)
+}
+
+/// Looks up which of the configured streamers are live
+///
+/// Returns `Ok(None)` if the token was rejected (it may have expired).
+async fn fetch_live(
+    client: &reqwest::Client,
+    config: &StreamAlerts,
+    token: &str,
+) -> Result<Option<Vec<Stream>>, anyhow::Error> {
+    let query: Vec<_> = config
+        .streamers
+        .iter()
+        .map(|login| ("user_login", login.to_lowercase()))
+        .collect();
+    let response = client
+        .get(STREAMS_URL)
+        .query(&query)
+        .header("Client-Id", &config.twitch_client_id)
+        .bearer_auth(token)
+        .send()
+        .await?;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Ok(None);
+    }
+
+    let streams: Streams = response.error_for_status()?.json().await?;
+    Ok(Some(streams.data))
+}
+
+/// Looks up which streamers are live, getting a new token first if needed
+async fn check(
+    client: &reqwest::Client,
+    config: &StreamAlerts,
+    token: &mut Option<String>,
+) -> Result<Vec<Stream>, anyhow::Error> {
+    // A rejected token is replaced once before giving up
+    for _ in 0..2 {
+        let current = match token {
+            Some(token) => token,
+            None => token.insert(
+                fetch_token(client, config)
+                    .await
+                    .with_context(|| "Failed to get a Twitch access token")?,
+            ),
+        };
+        match fetch_live(client, config, current).await? {
+            Some(streams) => return Ok(streams),
+            None => *token = None,
+        }
+    }
+
+    Err(anyhow!("Twitch rejected the access token"))
+}
+
+/// Spawns a task that checks whether the configured streamers are live every
+/// `check_interval_minutes`, sending the ones that started streaming over
+/// `live_sender`
+pub fn spawn_checker(config: StreamAlerts, live_sender: mpsc::Sender<Stream>) {
+    tokio::spawn(async move {
+        let client = match crate::provision::http_client() {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Failed to set up stream alerts: {}", e);
+                return;
+            }
+        };
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(config.check_interval_minutes * 60));
+        let mut token = None;
+        let mut live = LiveStreamers::default();
+
+        loop {
+            interval.tick().await;
+
+            let streams = match check(&client, &config, &mut token).await {
+                Ok(streams) => streams,
+                // Streams that are still live at the next successful check
+                // aren't announced again
+                Err(e) => {
+                    debug!("Failed to check for live streams: {:#}", e);
+                    continue;
+                }
+            };
+            for stream in live.update(streams) {
+                if live_sender.send(stream).await.is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+/// Builds the `tellraw` component announcing `stream`, with `announcement`
+/// as its text
+pub fn tellraw(announcement: &str, stream: &Stream) -> String {
+    let url = stream.url();
+    let mut builder = MessageBuilder::builder(Payload::text(""))
+        .then(Payload::text(&format!("{} ", announcement)))
+        .color(Color::LightPurple);
+    if !stream.title.is_empty() {
+        builder = builder
+            .then(Payload::text(&format!("{} ", stream.title)))
+            .color(Color::White);
+    }
+    chat::link(builder.then(Payload::text(&url)), &url)
+        .build()
+        .to_json()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn stream(login: &str) -> Stream {
+        Stream {
+            user_login: login.into(),
+            user_name: login.into(),
+            title: String::new(),
+            game_name: String::new(),
+        }
+    }
+
+    #[test]
+    fn announces_streams_that_start() {
+        let mut live = LiveStreamers::default();
+
+        assert_eq!(live.update(vec![stream("cldfire")]), vec![]);
+        assert_eq!(
+            live.update(vec![stream("cldfire"), stream("someone")]),
+            vec![stream("someone")]
+        );
+        assert_eq!(live.update(vec![stream("someone")]), vec![]);
+        assert_eq!(
+            live.update(vec![stream("cldfire"), stream("someone")]),
+            vec![stream("cldfire")]
+        );
+    }
+
+    #[test]
+    fn parses_streams() {
+        let streams: Streams = serde_json::from_str(
+            r#"{"data": [{"id": "1", "user_login": "cldfire", "user_name": "Cldfire",
+            "game_name": "Minecraft", "type": "live", "title": "Building a castle"}],
+            "pagination": {}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(streams.data[0].user_name, "Cldfire");
+        assert_eq!(streams.data[0].url(), "https://twitch.tv/cldfire");
+    }
+}
@@ -1,21 +1,81 @@
 use std::{
     collections::{BTreeMap, VecDeque},
     fmt::Display,
+    time::Instant,
 };
 
 use crossterm::event::{Event, KeyCode};
 use ratatui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span, Text},
     widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table, Tabs},
     Frame,
 };
-use time::{format_description::FormatItem, Duration, OffsetDateTime, UtcOffset};
+use time::{format_description::FormatItem, Duration, OffsetDateTime};
 use unicode_width::UnicodeWidthStr;
 
-use crate::OnlinePlayerInfo;
+use mc_server_wrapper_lib::{local_time, status::McServerStatus};
+
+use crate::{
+    discord::ConnectionState, disk::DiskUsage, geyser::BedrockPlayers, i18n, locations::Locations,
+    logging, player_tracker::OnlinePlayerInfo, worlds::format_size,
+};
+
+/// How long alerts are shown for
+const ALERT_DURATION: std::time::Duration = std::time::Duration::from_secs(30);
+/// The shortest time between draws, which caps the TUI at about 30 frames
+/// per second
+const MIN_FRAME_TIME: std::time::Duration = std::time::Duration::from_millis(33);
+/// How often the TUI is drawn when nothing has changed, which keeps uptimes,
+/// session lengths, and alerts up-to-date
+const IDLE_FRAME_TIME: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Decides when to draw the TUI, so it's only drawn when something changed
+/// (or once in a while to update times), and not too often
+#[derive(Debug)]
+pub struct RedrawSchedule {
+    /// Whether something changed since the last draw
+    changed: bool,
+    last_draw: Option<Instant>,
+}
+
+impl RedrawSchedule {
+    pub fn new() -> Self {
+        Self {
+            changed: true,
+            last_draw: None,
+        }
+    }
+
+    /// Note that something shown in the TUI changed
+    pub fn changed(&mut self) {
+        self.changed = true;
+    }
+
+    /// Returns when the TUI should next be drawn, or `None` if it hasn't been
+    /// drawn yet
+    pub fn next_draw(&self) -> Option<Instant> {
+        let wait = if self.changed {
+            MIN_FRAME_TIME
+        } else {
+            IDLE_FRAME_TIME
+        };
+        self.last_draw.map(|last_draw| last_draw + wait)
+    }
+
+    /// Returns true if the TUI should be drawn at `now`
+    pub fn due(&self, now: Instant) -> bool {
+        self.next_draw().is_none_or(|next_draw| next_draw <= now)
+    }
+
+    /// Note that the TUI was drawn at `now`
+    pub fn drawn(&mut self, now: Instant) {
+        self.changed = false;
+        self.last_draw = Some(now);
+    }
+}
 
 /// Represents the current state of the terminal UI
 #[derive(Debug)]
@@ -23,39 +83,163 @@ pub struct TuiState {
     pub tab_state: TabsState,
     pub logs_state: LogsState,
     pub players_state: PlayersState,
+    /// Output from Geyser, if it's enabled
+    pub geyser_logs_state: Option<LogsState>,
+    /// The most recent alert and when it was raised
+    alert: Option<(String, Instant)>,
+    /// Whether maintenance mode is on
+    pub maintenance: bool,
+    /// The Minecraft version the server reported running as it started
+    pub server_version: Option<String>,
+    /// Whether config changes are waiting for the server to restart
+    pub pending_restart: bool,
+    /// A newer version of mc-server-wrapper that's available
+    pub wrapper_update: Option<String>,
+    /// The state of the connection to Discord, if the bridge is enabled
+    pub discord_state: Option<ConnectionState>,
 }
 
 impl TuiState {
-    pub fn new() -> Self {
+    /// `geyser` controls whether a tab for Geyser's output is shown, and
+    /// `max_records` is how many lines of output each tab keeps
+    pub fn new(geyser: bool, max_records: usize) -> Self {
+        // TODO: don't hardcode this
+        let strings = &i18n::strings().tui;
+        let mut titles = vec![strings.tab_logs.clone(), strings.tab_players.clone()];
+        if geyser {
+            titles.push(strings.tab_geyser.clone());
+        }
+
         TuiState {
-            // TODO: don't hardcode this
-            tab_state: TabsState::new(vec!["Logs".into(), "Players".into()]),
-            logs_state: LogsState {
-                records: VecDeque::with_capacity(512),
-                progress_bar: None,
-                input_state: InputState { value: "".into() },
-            },
+            tab_state: TabsState::new(titles),
+            logs_state: LogsState::new(max_records),
             players_state: PlayersState,
+            geyser_logs_state: if geyser {
+                Some(LogsState::new(max_records))
+            } else {
+                None
+            },
+            alert: None,
+            maintenance: false,
+            server_version: None,
+            pending_restart: false,
+            wrapper_update: None,
+            discord_state: None,
         }
     }
 
+    /// Set how many lines of output each tab keeps
+    pub fn set_max_records(&mut self, max_records: usize) {
+        self.logs_state.set_max_records(max_records);
+        if let Some(geyser_logs_state) = &mut self.geyser_logs_state {
+            geyser_logs_state.set_max_records(max_records);
+        }
+    }
+
+    /// Show `msg` in a highlighted line below the header for a while
+    pub fn raise_alert(&mut self, msg: String) {
+        self.alert = Some((msg, Instant::now()));
+    }
+
     /// Draw the current state to the given frame
     pub fn draw<B: Backend>(
         &mut self,
         f: &mut Frame<B>,
         online_players: &BTreeMap<String, OnlinePlayerInfo>,
+        server_status: &McServerStatus,
+        disk_usage: Option<&DiskUsage>,
+        bedrock_players: Option<&BedrockPlayers>,
+        locations: &Locations,
     ) {
+        if let Some((_, raised_at)) = &self.alert {
+            if raised_at.elapsed() > ALERT_DURATION {
+                self.alert = None;
+            }
+        }
+        let banner = match &self.alert {
+            Some((alert, _)) => Some((alert.as_str(), Color::Red)),
+            None if self.maintenance => Some((
+                i18n::strings().tui.maintenance_banner.as_str(),
+                Color::Yellow,
+            )),
+            None => None,
+        };
+        let banner_height = if banner.is_some() { 1 } else { 0 };
+
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Length(2), Constraint::Min(0)].as_ref())
+            .constraints(
+                [
+                    Constraint::Length(2),
+                    Constraint::Length(banner_height),
+                    Constraint::Min(0),
+                ]
+                .as_ref(),
+            )
             .split(f.size());
 
-        self.tab_state.draw(f, chunks[0]);
+        let strings = &i18n::strings().tui;
+        let mut status_string = format_server_status(server_status);
+        if let Some(version) = &self.server_version {
+            status_string += &format!(" | {}", version);
+        }
+        if self.pending_restart {
+            status_string += &format!(" | {}", strings.restart_pending);
+        }
+        if let Some(version) = &self.wrapper_update {
+            status_string += " | ";
+            status_string += &i18n::fill(&strings.wrapper_update, &[("version", version)]);
+        }
+        if let Some(usage) = disk_usage {
+            status_string += " | ";
+            status_string += &i18n::fill(
+                &strings.disk_free,
+                &[("size", &format_size(usage.free_space))],
+            );
+        }
+        if let Some(state) = &self.discord_state {
+            status_string += " | ";
+            status_string += &i18n::fill(&strings.discord_state, &[("state", state)]);
+        }
+        let header_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(
+                [
+                    Constraint::Min(0),
+                    Constraint::Length(status_string.width() as u16 + 1),
+                ]
+                .as_ref(),
+            )
+            .split(chunks[0]);
+
+        self.tab_state.draw(f, header_chunks[0]);
+        f.render_widget(
+            Paragraph::new(status_string).block(Block::default().borders(Borders::BOTTOM)),
+            header_chunks[1],
+        );
+        if let Some((banner, color)) = banner {
+            f.render_widget(
+                Paragraph::new(banner).style(
+                    Style::default()
+                        .fg(Color::Black)
+                        .bg(color)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                chunks[1],
+            );
+        }
         // TODO: create tab structs that report what index they belong at so this
         // isn't hardcoded
         match self.tab_state.current_idx {
-            0 => self.logs_state.draw(f, chunks[1]),
-            1 => self.players_state.draw(f, chunks[1], online_players),
+            0 => self.logs_state.draw(f, chunks[2]),
+            1 => self
+                .players_state
+                .draw(f, chunks[2], online_players, bedrock_players, locations),
+            2 => {
+                if let Some(geyser_logs_state) = &mut self.geyser_logs_state {
+                    geyser_logs_state.draw(f, chunks[2]);
+                }
+            }
             _ => unreachable!(),
         }
     }
@@ -67,6 +251,11 @@ impl TuiState {
         match self.tab_state.current_idx {
             0 => self.logs_state.handle_input(&event),
             1 => self.players_state.handle_input(&event),
+            2 => {
+                if let Some(geyser_logs_state) = &mut self.geyser_logs_state {
+                    geyser_logs_state.handle_input(&event);
+                }
+            }
             _ => unreachable!(),
         }
     }
@@ -164,21 +353,57 @@ impl Display for ProgressBarState {
     }
 }
 
+/// A line of output shown in the logs
+#[derive(Debug)]
+struct LogRecord {
+    text: String,
+    style: Style,
+    /// The record wrapped into lines, and the width it was wrapped at
+    wrapped: Option<(Vec<String>, u16)>,
+}
+
+impl LogRecord {
+    /// Returns the record wrapped to `width`, only wrapping it again if it was
+    /// last wrapped at a different width
+    fn wrapped(&mut self, width: u16) -> &[String] {
+        if self.wrapped.as_ref().map(|(_, at)| *at) != Some(width) {
+            let lines = textwrap::wrap(&self.text, width as usize)
+                .into_iter()
+                .map(|line| line.into_owned())
+                .collect();
+            self.wrapped = Some((lines, width));
+        }
+
+        &self.wrapped.as_ref().unwrap().0
+    }
+}
+
 #[derive(Debug)]
-#[allow(clippy::type_complexity)]
 pub struct LogsState {
-    /// Stores the log messages to be displayed
-    ///
-    /// (original_message, (wrapped_message, wrapped_at_width))
-    records: VecDeque<(String, Option<(Vec<ListItem<'static>>, u16)>)>,
+    /// Stores the log messages to be displayed, oldest first
+    records: VecDeque<LogRecord>,
+    /// How many records are kept before the oldest are dropped
+    max_records: usize,
     /// The current state of the active progress bar (if present)
     progress_bar: Option<ProgressBarState>,
+    /// The number of pasted commands waiting to be sent
+    queued_commands: usize,
     /// State for the input (child widget)
     // TODO: this being public is a hack
     pub input_state: InputState,
 }
 
 impl LogsState {
+    fn new(max_records: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(max_records.min(512)),
+            max_records: max_records.max(1),
+            progress_bar: None,
+            queued_commands: 0,
+            input_state: InputState { value: "".into() },
+        }
+    }
+
     /// Draw the current state in the given `area`
     fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
         let (input_area, logs_area) = {
@@ -194,74 +419,66 @@ impl LogsState {
 
         let available_lines = if self.progress_bar.is_some() {
             // Account for space needed for progress bar
-            logs_area.height as usize - 1
+            (logs_area.height as usize).saturating_sub(1)
         } else {
             logs_area.height as usize
         };
-        let logs_area_width = logs_area.width as usize;
 
-        let bar_string = if let Some(bar) = &self.progress_bar {
-            bar.to_string()
-        } else {
-            String::new()
-        };
-
-        let num_records = self.records.len();
-        // Keep track of the number of lines after wrapping so we can skip lines as
-        // needed below
-        let mut wrapped_lines_len = 0;
-
-        let mut items = Vec::with_capacity(logs_area.height as usize);
-        items.extend(
-            self.records
-                .iter_mut()
-                // Only wrap the records we could potentially be displaying
-                .skip(num_records.saturating_sub(available_lines))
-                .flat_map(|r| {
-                    // See if we can use a cached wrapped line
-                    if let Some(wrapped) = &r.1 {
-                        if wrapped.1 as usize == logs_area_width {
-                            wrapped_lines_len += wrapped.0.len();
-                            return wrapped.0.clone();
-                        }
-                    }
-
-                    // If not, wrap the line and cache it
-                    r.1 = Some((
-                        textwrap::wrap(r.0.as_ref(), logs_area_width)
-                            .into_iter()
-                            .map(|s| s.to_string())
-                            .map(Span::from)
-                            .map(ListItem::new)
-                            .collect::<Vec<ListItem>>(),
-                        logs_area.width,
-                    ));
-
-                    wrapped_lines_len += r.1.as_ref().unwrap().0.len();
-                    r.1.as_ref().unwrap().0.clone()
-                }),
-        );
-
-        if self.progress_bar.is_some() {
-            items.push(ListItem::new(bar_string.as_str()));
-        }
+        let bar_string = self.progress_bar.as_ref().map(ToString::to_string);
 
         // TODO: we should be wrapping text with paragraph, but it currently
         // doesn't support wrapping and staying scrolled to the bottom
         //
         // see https://github.com/fdehau/tui-rs/issues/89
-        let logs = List::new(
-            items
-                .into_iter()
-                // Wrapping could have created more lines than what we can display;
-                // skip them
-                .skip(wrapped_lines_len.saturating_sub(available_lines))
-                .collect::<Vec<_>>(),
-        )
-        .block(Block::default().borders(Borders::NONE));
+        let mut items: Vec<_> = self
+            .last_lines(logs_area.width, available_lines)
+            .into_iter()
+            .map(|(line, style)| ListItem::new(line).style(style))
+            .collect();
+        if let Some(bar_string) = &bar_string {
+            items.push(ListItem::new(bar_string.as_str()));
+        }
+        let logs = List::new(items).block(Block::default().borders(Borders::NONE));
 
         f.render_widget(logs, logs_area);
         self.input_state.draw(f, input_area);
+        if self.queued_commands > 0 && input_area.height > 1 {
+            let queued = Paragraph::new(format!("{} queued commands", self.queued_commands))
+                .style(Style::default().fg(Color::DarkGray));
+            f.render_widget(
+                queued,
+                Rect {
+                    y: input_area.y + 1,
+                    height: 1,
+                    ..input_area
+                },
+            );
+        }
+    }
+
+    /// Returns the last `count` lines of the logs wrapped to `width` and the
+    /// style to show them in, oldest first
+    ///
+    /// Only the records that end up being shown are wrapped.
+    fn last_lines(&mut self, width: u16, count: usize) -> Vec<(&str, Style)> {
+        let mut lines = Vec::with_capacity(count);
+        for record in self.records.iter_mut().rev() {
+            if lines.len() >= count {
+                break;
+            }
+            let style = record.style;
+            lines.extend(
+                record
+                    .wrapped(width)
+                    .iter()
+                    .rev()
+                    .map(|line| (line.as_str(), style)),
+            );
+        }
+
+        lines.truncate(count);
+        lines.reverse();
+        lines
     }
 
     /// Update the state based on the given input
@@ -269,9 +486,51 @@ impl LogsState {
         self.input_state.handle_input(event);
     }
 
-    /// Add a record to be displayed
+    /// Add a record to be displayed, dropping the oldest ones if there are
+    /// too many
     pub fn add_record(&mut self, record: String) {
-        self.records.push_back((record, None));
+        self.push_record(record, Style::default());
+    }
+
+    /// Add a record from the wrapper's logger to be displayed, highlighting
+    /// errors and warnings
+    pub fn add_log_record(&mut self, record: &logging::LogRecord) {
+        let style = match record.level {
+            log::Level::Error => Style::default().fg(Color::Red),
+            log::Level::Warn => Style::default().fg(Color::Yellow),
+            _ => Style::default(),
+        };
+        self.push_record(record.to_string(), style);
+    }
+
+    fn push_record(&mut self, text: String, style: Style) {
+        self.records.push_back(LogRecord {
+            text,
+            style,
+            wrapped: None,
+        });
+        while self.records.len() > self.max_records {
+            self.records.pop_front();
+        }
+    }
+
+    /// Set how many records are kept, dropping the oldest ones if there are
+    /// too many
+    pub fn set_max_records(&mut self, max_records: usize) {
+        self.max_records = max_records.max(1);
+        while self.records.len() > self.max_records {
+            self.records.pop_front();
+        }
+    }
+
+    /// Set the number of pasted commands waiting to be sent
+    pub fn set_queued_commands(&mut self, count: usize) {
+        self.queued_commands = count;
+    }
+
+    /// Remove the progress bar, if there is one
+    pub fn clear_progress(&mut self) {
+        self.progress_bar = None;
     }
 
     /// Set the progress bar to the given percentage of completion
@@ -306,6 +565,8 @@ impl PlayersState {
         f: &mut Frame<B>,
         area: Rect,
         online_players: &BTreeMap<String, OnlinePlayerInfo>,
+        bedrock_players: Option<&BedrockPlayers>,
+        locations: &Locations,
     ) {
         let now_utc = OffsetDateTime::now_utc();
 
@@ -317,38 +578,58 @@ impl PlayersState {
                 const LOGIN_TIME_FORMAT: &[FormatItem] =
                     time::macros::format_description!("[hour repr:12]:[minute]:[second] [period]");
 
-                // TODO: log failure here somehow
-                let local_login_time = UtcOffset::current_local_offset()
-                    .map(|offset| info.joined_at.to_offset(offset))
-                    .ok();
+                let local_login_time = info.joined_at.to_offset(local_time::local_offset());
 
                 let session_time = now_utc - info.joined_at;
                 let session_time_string = make_session_time_string(session_time);
 
-                [
+                let mut row = vec![
                     n.to_string(),
                     local_login_time
-                        .and_then(|local_login_time| {
-                            // TODO: log failure here somehow
-                            local_login_time.format(&LOGIN_TIME_FORMAT).ok()
-                        })
-                        .unwrap_or_else(|| String::from("time error")),
+                        .format(&LOGIN_TIME_FORMAT)
+                        .unwrap_or_else(|_| String::from("time error")),
                     session_time_string,
-                ]
+                    locations
+                        .last_known(n)
+                        .map(|location| location.to_string())
+                        .unwrap_or_default(),
+                ];
+                if let Some(bedrock_players) = bedrock_players {
+                    row.push(if bedrock_players.is_bedrock(n) {
+                        "Bedrock".into()
+                    } else {
+                        "Java".into()
+                    });
+                }
+
+                row
             })
             .collect::<Vec<_>>();
 
+        let strings = &i18n::strings().tui;
+        let mut header = vec![
+            strings.column_name.as_str(),
+            strings.column_login_time.as_str(),
+            strings.column_session_length.as_str(),
+            strings.column_last_location.as_str(),
+        ];
+        if bedrock_players.is_some() {
+            header.push(strings.column_edition.as_str());
+        }
+
         let online_players = Table::new(
             online_players
                 .iter()
                 .map(|d| Row::new(d.iter().map(|s| s.as_str()).map(Text::from))),
         )
-        .header(Row::new(vec!["Name", "Login Time", "Session Length"]))
+        .header(Row::new(header))
         .block(Block::default().borders(Borders::NONE))
         .widths(&[
             Constraint::Length(16),
             Constraint::Length(11),
             Constraint::Length(14),
+            Constraint::Length(32),
+            Constraint::Length(7),
         ])
         .column_spacing(3);
 
@@ -359,7 +640,30 @@ impl PlayersState {
     fn handle_input(&mut self, _event: &Event) {}
 }
 
-fn make_session_time_string(session_duration: Duration) -> String {
+/// Formats the given server status for display in the header
+pub fn format_server_status(status: &McServerStatus) -> String {
+    let strings = &i18n::strings().tui;
+    match status {
+        McServerStatus::Stopped => strings.status_stopped.clone(),
+        McServerStatus::Starting { progress: None } => strings.status_starting.clone(),
+        McServerStatus::Starting {
+            progress: Some(progress),
+        } => i18n::fill(&strings.status_starting_progress, &[("progress", progress)]),
+        McServerStatus::Running { since, players, .. } => i18n::fill(
+            &strings.status_running,
+            &[
+                (
+                    "uptime",
+                    &make_session_time_string(OffsetDateTime::now_utc() - *since),
+                ),
+                ("players", &players.len()),
+            ],
+        ),
+        McServerStatus::Stopping => strings.status_stopping.clone(),
+    }
+}
+
+pub fn make_session_time_string(session_duration: Duration) -> String {
     let (session_minutes, session_hours, session_days) = (
         (session_duration - Duration::hours(session_duration.whole_hours())).whole_minutes(),
         (session_duration - Duration::days(session_duration.whole_days())).whole_hours(),
@@ -384,8 +688,20 @@ pub struct InputState {
 impl InputState {
     /// Draw the current state in the given `area`
     fn draw<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
-        let text = Line::from(vec![Span::raw("> "), Span::raw(&self.value)]);
-        let value_width = self.value.width() as u16;
+        // Only the first line of a multi-line paste is shown
+        let mut lines = self.value.lines();
+        let first_line = lines.next().unwrap_or_default();
+        let more = match lines.count() {
+            0 => String::new(),
+            1 => " (+1 more line)".into(),
+            count => format!(" (+{} more lines)", count),
+        };
+        let text = Line::from(vec![
+            Span::raw("> "),
+            Span::raw(first_line),
+            Span::styled(more.as_str(), Style::default().fg(Color::DarkGray)),
+        ]);
+        let value_width = (first_line.width() + more.width()) as u16;
 
         let input = Paragraph::new(text)
             .style(Style::default().fg(Color::Yellow))
@@ -405,9 +721,17 @@ impl InputState {
                 }
                 _ => {}
             }
+        } else if let Event::Paste(text) = event {
+            self.value
+                .push_str(text.replace("\r\n", "\n").replace('\r', "\n").trim_end());
         }
     }
 
+    /// Whether the input holds several lines (pasted in)
+    pub fn is_multiline(&self) -> bool {
+        self.value.contains('\n')
+    }
+
     /// Clear the input
     pub fn clear(&mut self) {
         self.value.clear();
@@ -461,6 +785,159 @@ mod test {
         }
     }
 
+    mod logs {
+        use ratatui::style::{Color, Style};
+        use time::macros::datetime;
+
+        use crate::{logging::LogRecord, ui::LogsState};
+
+        fn last_text(logs: &mut LogsState, width: u16, count: usize) -> Vec<&str> {
+            logs.last_lines(width, count)
+                .into_iter()
+                .map(|(line, _)| line)
+                .collect()
+        }
+
+        #[test]
+        fn bounded() {
+            let mut logs = LogsState::new(3);
+            for i in 0..5 {
+                logs.add_record(i.to_string());
+            }
+            assert_eq!(last_text(&mut logs, 80, 10), ["2", "3", "4"]);
+
+            logs.set_max_records(2);
+            assert_eq!(last_text(&mut logs, 80, 10), ["3", "4"]);
+        }
+
+        #[test]
+        fn wraps_last_lines() {
+            let mut logs = LogsState::new(100);
+            logs.add_record("first".into());
+            logs.add_record("second line".into());
+            logs.add_record("a third line".into());
+
+            assert_eq!(last_text(&mut logs, 7, 3), ["line", "a third", "line"]);
+            assert_eq!(last_text(&mut logs, 80, 2), ["second line", "a third line"]);
+        }
+
+        #[test]
+        fn log_records_are_styled() {
+            let mut logs = LogsState::new(100);
+            logs.add_log_record(&LogRecord {
+                timestamp: datetime!(2023-10-01 13:05:09 UTC),
+                level: log::Level::Warn,
+                target: "mc_server_wrapper".into(),
+                message: "something happened".into(),
+            });
+            logs.add_record("output".into());
+
+            assert_eq!(
+                logs.last_lines(80, 2),
+                [
+                    (
+                        "[1:05:09 PM] [mc_server_wrapper, WARN]: something happened",
+                        Style::default().fg(Color::Yellow)
+                    ),
+                    ("output", Style::default())
+                ]
+            );
+        }
+
+        #[test]
+        fn only_shown_records_are_wrapped() {
+            let mut logs = LogsState::new(100);
+            for _ in 0..10 {
+                logs.add_record("some output".into());
+            }
+
+            logs.last_lines(80, 2);
+            let wrapped_at = |logs: &LogsState| {
+                logs.records
+                    .iter()
+                    .map(|record| record.wrapped.as_ref().map(|(_, width)| *width))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(wrapped_at(&logs)[..8], [None; 8]);
+            assert_eq!(wrapped_at(&logs)[8..], [Some(80); 2]);
+
+            // Resizing rewraps the records that are shown
+            logs.last_lines(40, 3);
+            assert_eq!(wrapped_at(&logs)[7..], [Some(40); 3]);
+        }
+    }
+
+    mod input {
+        use crate::ui::InputState;
+        use crossterm::event::Event;
+
+        #[test]
+        fn paste() {
+            let mut input = InputState {
+                value: "say ".into(),
+            };
+            input.handle_input(&Event::Paste("hi\r\n".into()));
+            assert_eq!(input.value(), "say hi");
+            assert!(!input.is_multiline());
+
+            input.handle_input(&Event::Paste("\r\nsay there\r\n".into()));
+            assert_eq!(input.value(), "say hi\nsay there");
+            assert!(input.is_multiline());
+        }
+    }
+
+    mod redraw_schedule {
+        use std::time::{Duration, Instant};
+
+        use crate::ui::{RedrawSchedule, IDLE_FRAME_TIME, MIN_FRAME_TIME};
+
+        #[test]
+        fn first_draw() {
+            assert!(RedrawSchedule::new().due(Instant::now()));
+        }
+
+        #[test]
+        fn capped() {
+            let start = Instant::now();
+            let mut redraw = RedrawSchedule::new();
+            redraw.drawn(start);
+            redraw.changed();
+
+            assert!(!redraw.due(start + Duration::from_millis(10)));
+            assert_eq!(redraw.next_draw(), Some(start + MIN_FRAME_TIME));
+            assert!(redraw.due(start + MIN_FRAME_TIME));
+        }
+
+        #[test]
+        fn idle() {
+            let start = Instant::now();
+            let mut redraw = RedrawSchedule::new();
+            redraw.drawn(start);
+
+            assert!(!redraw.due(start + MIN_FRAME_TIME));
+            assert!(redraw.due(start + IDLE_FRAME_TIME));
+        }
+    }
+
+    mod server_status {
+        use mc_server_wrapper_lib::status::McServerStatus;
+
+        use crate::ui::format_server_status;
+
+        #[test]
+        fn stopped() {
+            assert_eq!(format_server_status(&McServerStatus::Stopped), "Stopped");
+        }
+
+        #[test]
+        fn starting_with_progress() {
+            assert_eq!(
+                format_server_status(&McServerStatus::Starting { progress: Some(40) }),
+                "Starting (40%)"
+            );
+        }
+    }
+
     mod session_time_string {
         use time::Duration;
 
@@ -0,0 +1,315 @@
+//! Receiving votes from server lists with the NuVotifier protocol
+//!
+//! Server lists connect to the `votifier` address and are greeted with
+//! `VOTIFIER 2 <challenge>`. They reply with a vote signed with the shared
+//! token, which is checked before the vote is passed on to be announced and
+//! rewarded. Only version 2 (token) votes are accepted; version 1 votes are
+//! encrypted with an RSA key pair, which the wrapper doesn't have.
+
+use std::{
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::anyhow;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use log::{debug, warn};
+use minecraft_chat::{Color, MessageBuilder, Payload};
+use serde_derive::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+
+use crate::whitelist;
+
+/// Starts each version 2 vote
+const MAGIC: u16 = 0x733a;
+/// How long a server list has to send its vote
+const VOTE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A vote for the server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vote {
+    /// The server list the vote came from
+    pub service: String,
+    /// The name of the player who voted
+    pub username: String,
+}
+
+/// A vote as it's sent
+#[derive(Deserialize)]
+struct SignedVote {
+    payload: String,
+    signature: String,
+}
+
+/// What's signed in a vote
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VotePayload {
+    service_name: String,
+    username: String,
+    challenge: String,
+}
+
+/// Computes the HMAC-SHA256 of `message` with `key`
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_LEN: usize = 64;
+
+    let mut block = [0; BLOCK_LEN];
+    if key.len() > BLOCK_LEN {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner = Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+/// Returns a challenge that's different for each connection
+fn new_challenge(peer: SocketAddr) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let digest = Sha256::new()
+        .chain_update(now.as_nanos().to_le_bytes())
+        .chain_update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes())
+        .chain_update(peer.to_string())
+        .finalize();
+    digest[..16].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Checks a vote's signature and challenge, returning the vote
+fn verify(message: &[u8], token: &str, challenge: &str) -> Result<Vote, anyhow::Error> {
+    // Anyone could sign a vote with an empty key (one is refused when the
+    // config is loaded, but this doesn't rely on it)
+    if token.is_empty() {
+        return Err(anyhow!("no token is set"));
+    }
+
+    let signed: SignedVote = serde_json::from_slice(message)?;
+    let signature = STANDARD
+        .decode(&signed.signature)
+        .map_err(|_| anyhow!("the signature isn't valid base64"))?;
+    let expected = hmac_sha256(token.as_bytes(), signed.payload.as_bytes());
+    // Compared in constant time so the signature can't be guessed a byte at
+    // a time
+    let matches = signature.len() == expected.len()
+        && signature
+            .iter()
+            .zip(expected.iter())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    if !matches {
+        return Err(anyhow!("the signature doesn't match (is the token right?)"));
+    }
+
+    let payload: VotePayload = serde_json::from_str(&signed.payload)?;
+    if payload.challenge != challenge {
+        return Err(anyhow!("the challenge doesn't match"));
+    }
+    // The name is put into reward commands, so anything else is refused
+    if !whitelist::is_valid_name(&payload.username) {
+        return Err(anyhow!("{:?} isn't a Minecraft name", payload.username));
+    }
+
+    Ok(Vote {
+        service: payload.service_name,
+        username: payload.username,
+    })
+}
+
+/// Reads a vote from `stream` after greeting it with `challenge`
+async fn read_vote(
+    stream: &mut TcpStream,
+    token: &str,
+    challenge: &str,
+) -> Result<Vote, anyhow::Error> {
+    stream
+        .write_all(format!("VOTIFIER 2 {}\n", challenge).as_bytes())
+        .await?;
+
+    let message = tokio::time::timeout(VOTE_TIMEOUT, async {
+        let magic = stream.read_u16().await?;
+        if magic != MAGIC {
+            return Err(anyhow!(
+                "unsupported vote format (only version 2 votes are accepted)"
+            ));
+        }
+        let len = stream.read_u16().await?;
+        let mut message = vec![0; len as usize];
+        stream.read_exact(&mut message).await?;
+        Ok(message)
+    })
+    .await
+    .map_err(|_| anyhow!("timed out"))??;
+
+    verify(&message, token, challenge)
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    token: &str,
+    vote_sender: &mpsc::Sender<Vote>,
+) -> Result<(), anyhow::Error> {
+    let challenge = new_challenge(peer);
+    let reply = match read_vote(&mut stream, token, &challenge).await {
+        Ok(vote) => {
+            let _ = vote_sender.send(vote).await;
+            json!({ "status": "ok" })
+        }
+        Err(e) => {
+            warn!("Rejected a vote from {}: {}", peer.ip(), e);
+            json!({ "status": "error", "cause": "BadVote", "error": e.to_string() })
+        }
+    };
+
+    stream
+        .write_all(format!("{}\r\n", reply).as_bytes())
+        .await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Receives votes on `listener` until the wrapper exits, sending the ones
+/// signed with `token` over `vote_sender`
+pub async fn serve(listener: TcpListener, token: String, vote_sender: mpsc::Sender<Vote>) {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept a Votifier connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+
+        let token = token.clone();
+        let vote_sender = vote_sender.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, peer, &token, &vote_sender).await {
+                debug!("Failed to answer a Votifier connection: {}", e);
+            }
+        });
+    }
+}
+
+/// Builds the `tellraw` component thanking a voter, with `thanks` as its text
+pub fn tellraw(thanks: &str) -> String {
+    MessageBuilder::builder(Payload::text(thanks))
+        .color(Color::Green)
+        .build()
+        .to_json()
+        .unwrap()
+}
+
+/// Returns the reward commands to run for `vote`
+pub fn reward_commands(rewards: &[String], vote: &Vote) -> Vec<String> {
+    rewards
+        .iter()
+        .map(|command| command.replace("{player}", &vote.username))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::AsyncBufReadExt;
+
+    use super::*;
+
+    fn signed_vote(token: &str, username: &str, challenge: &str) -> Vec<u8> {
+        let payload = json!({
+            "serviceName": "minecraft-mp.com",
+            "username": username,
+            "address": "127.0.0.1",
+            "timestamp": 1700000000000u64,
+            "challenge": challenge,
+        })
+        .to_string();
+        let signature = STANDARD.encode(hmac_sha256(token.as_bytes(), payload.as_bytes()));
+        json!({ "payload": payload, "signature": signature })
+            .to_string()
+            .into_bytes()
+    }
+
+    #[test]
+    fn hmac() {
+        // From RFC 4231
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(
+            mac.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn verifies_votes() {
+        assert_eq!(
+            verify(&signed_vote("s3cret", "Cldfire", "abc"), "s3cret", "abc").unwrap(),
+            Vote {
+                service: "minecraft-mp.com".into(),
+                username: "Cldfire".into(),
+            }
+        );
+        assert!(verify(&signed_vote("guess!", "Cldfire", "abc"), "s3cret", "abc").is_err());
+        assert!(verify(&signed_vote("s3cret", "Cldfire", "old"), "s3cret", "abc").is_err());
+        assert!(verify(&signed_vote("s3cret", "a; op b", "abc"), "s3cret", "abc").is_err());
+    }
+
+    #[test]
+    fn empty_token_refuses_votes() {
+        assert!(verify(&signed_vote("", "Cldfire", "abc"), "", "abc").is_err());
+    }
+
+    #[test]
+    fn rewards() {
+        let vote = Vote {
+            service: "minecraft-mp.com".into(),
+            username: "Cldfire".into(),
+        };
+
+        assert_eq!(
+            reward_commands(&["give {player} diamond 1".into()], &vote),
+            vec!["give Cldfire diamond 1".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn receives_votes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (vote_sender, mut vote_receiver) = mpsc::channel(1);
+        tokio::spawn(serve(listener, "s3cret".into(), vote_sender));
+
+        let mut stream = tokio::io::BufReader::new(TcpStream::connect(address).await.unwrap());
+        let mut greeting = String::new();
+        stream.read_line(&mut greeting).await.unwrap();
+        let challenge = greeting.trim().strip_prefix("VOTIFIER 2 ").unwrap();
+
+        let vote = signed_vote("s3cret", "Cldfire", challenge);
+        stream.write_u16(MAGIC).await.unwrap();
+        stream.write_u16(vote.len() as u16).await.unwrap();
+        stream.write_all(&vote).await.unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).await.unwrap();
+
+        assert_eq!(reply, "{\"status\":\"ok\"}\r\n");
+        assert_eq!(vote_receiver.recv().await.unwrap().username, "Cldfire");
+    }
+}
@@ -0,0 +1,128 @@
+//! Alerting on console output that matches configured keywords or patterns
+
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::config::WatchRule;
+
+/// A watchlist rule ready to be matched against console output
+#[derive(Debug)]
+struct Rule {
+    config: WatchRule,
+    /// Set for rules that use a regular expression
+    regex: Option<Regex>,
+    /// When the rule last raised an alert
+    last_alert: Option<Instant>,
+}
+
+impl Rule {
+    fn is_match(&self, line: &str) -> bool {
+        match &self.regex {
+            Some(regex) => regex.is_match(line),
+            None => line.contains(&self.config.pattern),
+        }
+    }
+}
+
+/// The configured watchlist rules
+#[derive(Debug, Default)]
+pub struct Watchlist {
+    rules: Vec<Rule>,
+}
+
+impl Watchlist {
+    /// Compiles the given rules, failing if any regular expression is invalid
+    pub fn new(rules: &[WatchRule]) -> Result<Self, regex::Error> {
+        let rules = rules
+            .iter()
+            .map(|config| {
+                Ok(Rule {
+                    regex: if config.regex {
+                        Some(Regex::new(&config.pattern)?)
+                    } else {
+                        None
+                    },
+                    config: config.clone(),
+                    last_alert: None,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { rules })
+    }
+
+    /// Returns the rules that `line` matches and that aren't cooling down
+    /// from a previous alert
+    ///
+    /// The returned rules start cooling down as of `now`.
+    pub fn check(&mut self, line: &str, now: Instant) -> Vec<&WatchRule> {
+        self.rules
+            .iter_mut()
+            .filter(|rule| rule.is_match(line))
+            .filter_map(|rule| {
+                let cooldown = Duration::from_secs(rule.config.cooldown);
+                if let Some(last_alert) = rule.last_alert {
+                    if now.saturating_duration_since(last_alert) < cooldown {
+                        return None;
+                    }
+                }
+
+                rule.last_alert = Some(now);
+                Some(&rule.config)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(pattern: &str, regex: bool) -> WatchRule {
+        WatchRule {
+            pattern: pattern.into(),
+            regex,
+            cooldown: 60,
+            discord: false,
+            discord_mention: None,
+        }
+    }
+
+    #[test]
+    fn keyword_and_regex() {
+        let mut watchlist =
+            Watchlist::new(&[rule("Griefer123", false), rule(r"(?i)tps: \d", true)]).unwrap();
+        let now = Instant::now();
+
+        assert_eq!(watchlist.check("Griefer123 joined the game", now).len(), 1);
+        let matched = watchlist.check("Current TPS: 12.5", now);
+        assert_eq!(matched.len(), 1);
+        assert!(matched[0].regex);
+        assert!(watchlist
+            .check("griefer123 joined the game", now)
+            .is_empty());
+    }
+
+    #[test]
+    fn cooldown() {
+        let mut watchlist = Watchlist::new(&[rule("Exception", false)]).unwrap();
+        let now = Instant::now();
+
+        assert_eq!(watchlist.check("java.lang.Exception", now).len(), 1);
+        assert!(watchlist
+            .check("java.lang.Exception", now + Duration::from_secs(30))
+            .is_empty());
+        assert_eq!(
+            watchlist
+                .check("java.lang.Exception", now + Duration::from_secs(60))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn invalid_regex() {
+        assert!(Watchlist::new(&[rule("(unclosed", true)]).is_err());
+    }
+}
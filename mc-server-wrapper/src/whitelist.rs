@@ -0,0 +1,186 @@
+//! Letting Discord users add themselves to the whitelist
+
+use std::path::Path;
+
+use anyhow::Context;
+use serde_derive::Deserialize;
+use tokio::{
+    fs,
+    sync::{mpsc, oneshot},
+};
+
+use mc_server_wrapper_lib::communication::ServerCommand;
+
+use crate::{links::Links, maintenance::PlayerEntry};
+
+const PROFILE_URL: &str = "https://api.mojang.com/users/profiles/minecraft/";
+
+/// A Minecraft: Java Edition profile
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// The profile's UUID, without hyphens
+    pub id: String,
+    pub name: String,
+}
+
+impl Profile {
+    /// The profile's UUID with hyphens, as used in `whitelist.json`
+    pub fn uuid(&self) -> String {
+        if self.id.len() != 32 || !self.id.is_ascii() {
+            return self.id.clone();
+        }
+
+        let id = &self.id;
+        format!(
+            "{}-{}-{}-{}-{}",
+            &id[..8],
+            &id[8..12],
+            &id[12..16],
+            &id[16..20],
+            &id[20..]
+        )
+    }
+}
+
+/// A request from Discord to add someone to the whitelist
+#[derive(Debug)]
+pub struct WhitelistRequest {
+    pub discord_id: u64,
+    pub profile: Profile,
+    /// Receives the reply to send to the requester
+    pub reply: oneshot::Sender<String>,
+}
+
+/// Whether `name` could be a Minecraft name
+pub fn is_valid_name(name: &str) -> bool {
+    (3..=16).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Looks up the profile with the given name, returning `None` if there isn't
+/// one
+pub async fn lookup(
+    client: &reqwest::Client,
+    name: &str,
+) -> Result<Option<Profile>, anyhow::Error> {
+    let response = client
+        .get(format!("{}{}", PROFILE_URL, name))
+        .send()
+        .await
+        .with_context(|| "Failed to reach the Mojang API")?;
+    if matches!(response.status().as_u16(), 204 | 404) {
+        return Ok(None);
+    }
+
+    Ok(Some(response.error_for_status()?.json().await?))
+}
+
+/// Links the requester's Discord account to `profile` and adds it to the
+/// whitelist of the server at `server_path`, returning the reply to send them
+///
+/// A running server is told to add the player with `whitelist add`;
+/// otherwise `whitelist.json` is edited directly.
+pub async fn add(
+    server_path: &Path,
+    discord_id: u64,
+    profile: &Profile,
+    server_running: bool,
+    mc_cmd_sender: &mpsc::Sender<ServerCommand>,
+) -> Result<String, anyhow::Error> {
+    if crate::maintenance::is_enabled(server_path) {
+        anyhow::bail!("The server is down for maintenance, please try again later");
+    }
+
+    let mut links = Links::load(server_path).await?;
+    links.link(discord_id, &profile.name)?;
+
+    if server_running {
+        mc_cmd_sender
+            .send(ServerCommand::WriteCommandToStdin(format!(
+                "whitelist add {}",
+                profile.name
+            )))
+            .await?;
+    } else {
+        let path = server_path.with_file_name("whitelist.json");
+        let contents = if path.exists() {
+            Some(
+                fs::read_to_string(&path)
+                    .await
+                    .with_context(|| format!("Failed to read {:?}", path))?,
+            )
+        } else {
+            None
+        };
+
+        if let Some(whitelist) = add_entry(contents.as_deref(), profile)? {
+            fs::write(&path, whitelist)
+                .await
+                .with_context(|| format!("Failed to write {:?}", path))?;
+        }
+    }
+
+    links.store(server_path).await?;
+    Ok(format!("Added {} to the whitelist", profile.name))
+}
+
+/// Adds `profile` to the given `whitelist.json` contents, returning the new
+/// contents or `None` if it was already on the list
+fn add_entry(whitelist: Option<&str>, profile: &Profile) -> Result<Option<String>, anyhow::Error> {
+    let mut entries: Vec<PlayerEntry> = match whitelist {
+        Some(whitelist) => {
+            serde_json::from_str(whitelist).with_context(|| "Failed to parse whitelist.json")?
+        }
+        None => vec![],
+    };
+    if entries
+        .iter()
+        .any(|entry| entry.name.eq_ignore_ascii_case(&profile.name))
+    {
+        return Ok(None);
+    }
+
+    entries.push(PlayerEntry {
+        uuid: profile.uuid(),
+        name: profile.name.clone(),
+    });
+    Ok(Some(serde_json::to_string_pretty(&entries)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn profile() -> Profile {
+        Profile {
+            id: "069a79f444e94726a5befca90e38aaf5".into(),
+            name: "Notch".into(),
+        }
+    }
+
+    #[test]
+    fn names() {
+        assert!(is_valid_name("Cldfire"));
+        assert!(is_valid_name("a_b"));
+        assert!(!is_valid_name("ab"));
+        assert!(!is_valid_name("has space"));
+        assert!(!is_valid_name("../../etc"));
+    }
+
+    #[test]
+    fn uuid() {
+        assert_eq!(profile().uuid(), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+    }
+
+    #[test]
+    fn adds_entries() {
+        let whitelist = add_entry(None, &profile()).unwrap().unwrap();
+        let entries: Vec<PlayerEntry> = serde_json::from_str(&whitelist).unwrap();
+        assert_eq!(entries[0].name, "Notch");
+
+        let profile = Profile {
+            name: "notch".into(),
+            ..profile()
+        };
+        assert!(add_entry(Some(&whitelist), &profile).unwrap().is_none());
+    }
+}
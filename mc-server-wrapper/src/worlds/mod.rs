@@ -0,0 +1,223 @@
+//! Listing, switching between, and creating worlds
+//!
+//! A world is any folder next to the server jar that contains a `level.dat`.
+//! The active world is the one named by `level-name` in `server.properties`;
+//! changes to it take effect the next time the server starts. Worlds can be
+//! checked for corruption with `regions::check`.
+
+use std::{
+    fmt,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, bail, Context};
+use flate2::read::GzDecoder;
+
+use crate::{properties::ServerProperties, provision::server_dir};
+
+mod nbt;
+pub mod regions;
+
+/// The world the server uses if `level-name` isn't set
+const DEFAULT_LEVEL_NAME: &str = "world";
+
+/// Information about a world on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct World {
+    pub name: String,
+    /// The total size of the world's folder in bytes
+    pub size: u64,
+    /// The world's seed, if it could be read from `level.dat`
+    pub seed: Option<i64>,
+    /// Whether this is the world the server loads
+    pub active: bool,
+}
+
+impl fmt::Display for World {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}", self.name, format_size(self.size))?;
+        if let Some(seed) = self.seed {
+            write!(f, ", seed {}", seed)?;
+        }
+        write!(f, ")")?;
+        if self.active {
+            write!(f, " [active]")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the name of the world the server at `server_path` loads
+pub async fn active_world(server_path: &Path) -> Result<String, anyhow::Error> {
+    Ok(ServerProperties::load(server_path)
+        .await?
+        .get("level-name")
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| DEFAULT_LEVEL_NAME.into()))
+}
+
+/// Lists the worlds of the server at `server_path`, sorted by name
+pub async fn list(server_path: &Path) -> Result<Vec<World>, anyhow::Error> {
+    let active = active_world(server_path).await?;
+    let dir = server_dir(server_path);
+
+    tokio::task::spawn_blocking(move || {
+        let mut worlds = vec![];
+
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("Failed to read {:?}", dir))? {
+            let path = entry?.path();
+            if !path.join("level.dat").is_file() {
+                continue;
+            }
+
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            worlds.push(World {
+                active: name == active,
+                size: dir_size(&path)?,
+                // A world that's being written to may have a partial `level.dat`,
+                // so failing to read the seed isn't fatal
+                seed: read_seed(&path.join("level.dat")).ok().flatten(),
+                name,
+            });
+        }
+
+        worlds.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(worlds)
+    })
+    .await?
+}
+
+/// Makes `name` the world the server at `server_path` loads
+///
+/// The world must already exist.
+pub async fn switch(server_path: &Path, name: &str) -> Result<(), anyhow::Error> {
+    let path = world_path(server_path, name)?;
+    if !path.join("level.dat").is_file() {
+        bail!("there is no world named \"{}\"", name);
+    }
+
+    let mut properties = ServerProperties::load(server_path).await?;
+    properties.set("level-name", name);
+    properties.store(server_path).await
+}
+
+/// Sets up the server at `server_path` to generate a new world called `name`
+/// when it next starts, using `seed` if given
+pub async fn create(
+    server_path: &Path,
+    name: &str,
+    seed: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let path = world_path(server_path, name)?;
+    if path.exists() {
+        bail!("\"{}\" already exists", name);
+    }
+
+    let mut properties = ServerProperties::load(server_path).await?;
+    properties.set("level-name", name);
+    properties.set("level-seed", seed.unwrap_or(""));
+    properties.store(server_path).await
+}
+
+/// Returns the folder of the world `name`, rejecting names that aren't a
+/// single path component
+fn world_path(server_path: &Path, name: &str) -> Result<PathBuf, anyhow::Error> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(anyhow!("\"{}\" is not a valid world name", name));
+    }
+
+    Ok(server_dir(server_path).join(name))
+}
+
+/// Reads the seed out of a world's `level.dat`
+fn read_seed(level_dat: &Path) -> Result<Option<i64>, anyhow::Error> {
+    let file = File::open(level_dat)?;
+    let root = nbt::read(BufReader::new(GzDecoder::new(file)))?;
+
+    // 1.16 moved the seed into the world generation settings
+    Ok(
+        match root
+            .get_path(&["Data", "WorldGenSettings", "seed"])
+            .or_else(|| root.get_path(&["Data", "RandomSeed"]))
+        {
+            Some(nbt::Tag::Long(seed)) => Some(*seed),
+            _ => None,
+        },
+    )
+}
+
+/// Returns the total size of the files in `path`
+pub fn dir_size(path: &Path) -> Result<u64, anyhow::Error> {
+    let mut size = 0;
+
+    for entry in std::fs::read_dir(path).with_context(|| format!("Failed to read {:?}", path))? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+
+    Ok(size)
+}
+
+/// Formats a number of bytes for display (e.g. `1.5 GB`)
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn world_names() {
+        let server_path = Path::new("server/server.jar");
+
+        assert_eq!(
+            world_path(server_path, "creative").unwrap(),
+            Path::new("server/creative")
+        );
+        assert!(world_path(server_path, "..").is_err());
+        assert!(world_path(server_path, "../world").is_err());
+        assert!(world_path(server_path, "").is_err());
+    }
+
+    #[test]
+    fn display() {
+        let world = World {
+            name: "world".into(),
+            size: 2048,
+            seed: Some(-1234),
+            active: true,
+        };
+
+        assert_eq!(world.to_string(), "world (2.0 KB, seed -1234) [active]");
+    }
+}
@@ -0,0 +1,196 @@
+//! A minimal reader for Minecraft's NBT format, enough to pull values out of
+//! `level.dat`
+
+use std::{collections::HashMap, convert::TryFrom, io::Read};
+
+use anyhow::{anyhow, bail};
+
+/// An NBT tag
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(HashMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    /// Follows `path` through nested compounds
+    pub fn get_path(&self, path: &[&str]) -> Option<&Tag> {
+        path.iter().try_fold(self, |tag, key| match tag {
+            Tag::Compound(map) => map.get(*key),
+            _ => None,
+        })
+    }
+}
+
+/// Reads an uncompressed NBT document, returning its root tag
+pub fn read(mut reader: impl Read) -> Result<Tag, anyhow::Error> {
+    let id = read_u8(&mut reader)?;
+    if id != 10 {
+        bail!("root NBT tag is not a compound (found tag type {})", id);
+    }
+
+    // The root tag's name is unused
+    read_string(&mut reader)?;
+    read_payload(&mut reader, id, 0)
+}
+
+/// Nesting deeper than this is treated as a malformed file
+const MAX_DEPTH: usize = 512;
+
+fn read_payload(reader: &mut impl Read, id: u8, depth: usize) -> Result<Tag, anyhow::Error> {
+    if depth > MAX_DEPTH {
+        bail!("NBT data is nested too deeply");
+    }
+
+    Ok(match id {
+        1 => Tag::Byte(read_u8(reader)? as i8),
+        2 => Tag::Short(i16::from_be_bytes(read_array(reader)?)),
+        3 => Tag::Int(read_i32(reader)?),
+        4 => Tag::Long(i64::from_be_bytes(read_array(reader)?)),
+        5 => Tag::Float(f32::from_be_bytes(read_array(reader)?)),
+        6 => Tag::Double(f64::from_be_bytes(read_array(reader)?)),
+        7 => {
+            let len = read_len(reader)?;
+            let mut buf = vec![0; len];
+            reader.read_exact(&mut buf)?;
+            Tag::ByteArray(buf.into_iter().map(|b| b as i8).collect())
+        }
+        8 => Tag::String(read_string(reader)?),
+        9 => {
+            let item_id = read_u8(reader)?;
+            let len = read_len(reader)?;
+            let mut items = Vec::with_capacity(len.min(1024));
+            for _ in 0..len {
+                items.push(read_payload(reader, item_id, depth + 1)?);
+            }
+            Tag::List(items)
+        }
+        10 => {
+            let mut map = HashMap::new();
+            loop {
+                let item_id = read_u8(reader)?;
+                if item_id == 0 {
+                    break;
+                }
+
+                let name = read_string(reader)?;
+                map.insert(name, read_payload(reader, item_id, depth + 1)?);
+            }
+            Tag::Compound(map)
+        }
+        11 => {
+            let len = read_len(reader)?;
+            let mut items = Vec::with_capacity(len.min(1024));
+            for _ in 0..len {
+                items.push(read_i32(reader)?);
+            }
+            Tag::IntArray(items)
+        }
+        12 => {
+            let len = read_len(reader)?;
+            let mut items = Vec::with_capacity(len.min(1024));
+            for _ in 0..len {
+                items.push(i64::from_be_bytes(read_array(reader)?));
+            }
+            Tag::LongArray(items)
+        }
+        _ => return Err(anyhow!("unknown NBT tag type {}", id)),
+    })
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], anyhow::Error> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8, anyhow::Error> {
+    Ok(read_array::<1>(reader)?[0])
+}
+
+fn read_i32(reader: &mut impl Read) -> Result<i32, anyhow::Error> {
+    Ok(i32::from_be_bytes(read_array(reader)?))
+}
+
+fn read_len(reader: &mut impl Read) -> Result<usize, anyhow::Error> {
+    let len = read_i32(reader)?;
+    usize::try_from(len).map_err(|_| anyhow!("negative NBT length {}", len))
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String, anyhow::Error> {
+    let len = u16::from_be_bytes(read_array(reader)?);
+    let mut buf = vec![0; len as usize];
+    reader.read_exact(&mut buf)?;
+    // NBT strings are "modified UTF-8", which only differs from UTF-8 for
+    // characters that don't matter here
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn named(id: u8, name: &str, payload: &[u8]) -> Vec<u8> {
+        let mut out = vec![id];
+        out.extend((name.len() as u16).to_be_bytes());
+        out.extend(name.as_bytes());
+        out.extend(payload);
+        out
+    }
+
+    #[test]
+    fn nested_compound() {
+        let mut data = named(4, "seed", &(-42i64).to_be_bytes());
+        data.extend(named(8, "name", b"\x00\x02hi"));
+        data.push(0);
+        let mut data = named(10, "WorldGenSettings", &data);
+        data.push(0);
+        let mut data = named(10, "Data", &data);
+        data.push(0);
+        let mut data = named(10, "", &data);
+        data.push(0);
+
+        let root = read(&data[..]).unwrap();
+        assert_eq!(
+            root.get_path(&["Data", "WorldGenSettings", "seed"]),
+            Some(&Tag::Long(-42))
+        );
+        assert_eq!(
+            root.get_path(&["Data", "WorldGenSettings", "name"]),
+            Some(&Tag::String("hi".into()))
+        );
+        assert_eq!(root.get_path(&["Data", "missing"]), None);
+    }
+
+    #[test]
+    fn list_of_ints() {
+        let mut payload = vec![3];
+        payload.extend(2i32.to_be_bytes());
+        payload.extend(7i32.to_be_bytes());
+        payload.extend(9i32.to_be_bytes());
+        let mut data = named(9, "list", &payload);
+        data.push(0);
+        let mut data = named(10, "", &data);
+        data.push(0);
+
+        assert_eq!(
+            read(&data[..]).unwrap().get_path(&["list"]),
+            Some(&Tag::List(vec![Tag::Int(7), Tag::Int(9)]))
+        );
+    }
+
+    #[test]
+    fn truncated_is_error() {
+        assert!(read(&[10, 0, 0, 4][..]).is_err());
+    }
+}
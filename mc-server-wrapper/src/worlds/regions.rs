@@ -0,0 +1,231 @@
+//! Checking a world's region files for corruption
+//!
+//! Region files (`.mca`, used for terrain, entities, and points of interest)
+//! start with an 8 KiB header. Its first half holds the location of each of
+//! the region's 1024 chunks as an offset and a length in 4 KiB sectors. Each
+//! chunk starts with its length in bytes and its compression type, followed
+//! by the compressed chunk data.
+
+use std::{
+    fmt, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use flate2::read::{GzDecoder, ZlibDecoder};
+
+use crate::provision::server_dir;
+
+const SECTOR_SIZE: usize = 4096;
+/// The size of the chunk locations and timestamps at the start of a region
+const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+/// Set in a chunk's compression type when it's stored in its own `.mcc` file
+const EXTERNAL_FLAG: u8 = 0x80;
+
+/// A problem found in a region file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionProblem {
+    /// The region file's path relative to the server's folder
+    pub path: PathBuf,
+    pub description: String,
+}
+
+impl fmt::Display for RegionProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.description)
+    }
+}
+
+/// Checks the contents of a region file, returning descriptions of the
+/// problems found
+///
+/// Chunks stored in external `.mcc` files and chunks compressed with LZ4
+/// aren't decompressed.
+pub fn check_region(data: &[u8]) -> Vec<String> {
+    // Minecraft leaves empty region files behind at times
+    if data.is_empty() {
+        return vec![];
+    }
+    if data.len() < HEADER_SIZE {
+        return vec![format!(
+            "the header is truncated ({} of {} bytes)",
+            data.len(),
+            HEADER_SIZE
+        )];
+    }
+
+    let mut problems = vec![];
+    for (i, location) in data[..SECTOR_SIZE].chunks_exact(4).enumerate() {
+        let location = u32::from_be_bytes([location[0], location[1], location[2], location[3]]);
+        if location == 0 {
+            continue;
+        }
+
+        if let Err(problem) = check_chunk(data, location) {
+            problems.push(format!("chunk ({}, {}) {}", i % 32, i / 32, problem));
+        }
+    }
+
+    problems
+}
+
+/// Checks the chunk at `location` (from the region's header), returning a
+/// description of what's wrong with it
+fn check_chunk(data: &[u8], location: u32) -> Result<(), String> {
+    let offset = (location >> 8) as usize * SECTOR_SIZE;
+    let sectors = (location & 0xff) as usize;
+    if offset < HEADER_SIZE {
+        return Err("points into the header".into());
+    }
+
+    let header = data
+        .get(offset..offset + 5)
+        .ok_or("is past the end of the file")?;
+    let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let compression = header[4];
+    if length == 0 {
+        return Err("is empty".into());
+    }
+    if length + 4 > sectors * SECTOR_SIZE {
+        return Err("is longer than the space allocated to it".into());
+    }
+    let chunk = data
+        .get(offset + 5..offset + 4 + length)
+        .ok_or("is truncated")?;
+
+    if compression & EXTERNAL_FLAG != 0 {
+        return Ok(());
+    }
+    let result = match compression {
+        1 => io::copy(&mut GzDecoder::new(chunk), &mut io::sink()),
+        2 => io::copy(&mut ZlibDecoder::new(chunk), &mut io::sink()),
+        3 | 4 => Ok(0),
+        other => return Err(format!("has an unknown compression type ({})", other)),
+    };
+
+    result
+        .map(|_| ())
+        .map_err(|e| format!("can't be decompressed ({})", e))
+}
+
+/// Checks every region file in the world named `world` of the server at
+/// `server_path`
+pub fn check(server_path: &Path, world: &str) -> Result<Vec<RegionProblem>, anyhow::Error> {
+    let dir = server_dir(server_path);
+    let mut paths = vec![];
+    find_regions(&dir.join(world), &mut paths)?;
+    paths.sort();
+
+    let mut problems = vec![];
+    for path in paths {
+        let mut data = vec![];
+        fs::File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .with_context(|| format!("Failed to read {:?}", path))?;
+
+        let relative = path.strip_prefix(&dir).unwrap_or(&path);
+        problems.extend(
+            check_region(&data)
+                .into_iter()
+                .map(|description| RegionProblem {
+                    path: relative.to_path_buf(),
+                    description,
+                }),
+        );
+    }
+
+    Ok(problems)
+}
+
+/// Adds the paths of the region files in `dir` (and its subfolders) to
+/// `paths`
+fn find_regions(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), anyhow::Error> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            find_regions(&path, paths)?;
+        } else if file_type.is_file() && path.extension().is_some_and(|e| e == "mca") {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    /// Builds a region with a chunk at (1, 0) containing `payload` with the
+    /// given compression type
+    fn region(compression: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; HEADER_SIZE];
+        // The chunk takes up one sector right after the header
+        data[4..8].copy_from_slice(&((2 << 8) | 1u32).to_be_bytes());
+        data.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+        data.push(compression);
+        data.extend_from_slice(payload);
+        data.resize(HEADER_SIZE + SECTOR_SIZE, 0);
+        data
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn valid() {
+        assert!(check_region(&region(2, &zlib(b"chunk data"))).is_empty());
+        assert!(check_region(&region(3, b"chunk data")).is_empty());
+        assert!(check_region(&[]).is_empty());
+    }
+
+    #[test]
+    fn truncated_header() {
+        assert_eq!(
+            check_region(&[0; 100]),
+            ["the header is truncated (100 of 8192 bytes)"]
+        );
+    }
+
+    #[test]
+    fn truncated_chunk() {
+        let mut data = region(2, &zlib(b"chunk data"));
+        data.truncate(HEADER_SIZE + 2);
+        assert_eq!(
+            check_region(&data),
+            ["chunk (1, 0) is past the end of the file"]
+        );
+
+        let mut data = region(3, &[1; 100]);
+        data.truncate(HEADER_SIZE + 50);
+        assert_eq!(check_region(&data), ["chunk (1, 0) is truncated"]);
+    }
+
+    #[test]
+    fn bad_chunks() {
+        let mut compressed = zlib(b"chunk data");
+        compressed[4] ^= 0xff;
+        assert!(check_region(&region(2, &compressed))[0]
+            .starts_with("chunk (1, 0) can't be decompressed"));
+
+        assert_eq!(
+            check_region(&region(9, b"chunk data")),
+            ["chunk (1, 0) has an unknown compression type (9)"]
+        );
+
+        let mut data = region(3, b"chunk data");
+        data[4..8].copy_from_slice(&((1 << 8) | 1u32).to_be_bytes());
+        assert_eq!(check_region(&data), ["chunk (1, 0) points into the header"]);
+    }
+}